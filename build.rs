@@ -57,4 +57,21 @@ fn main() {
     bindings
         .write_to_file(out_path.join("xkbcommon_sys.rs"))
         .expect("Couldn't write bindings!");
+
+    // Protocols that are too new for the version of wayland-protocols that we use.
+    #[cfg(feature = "wayland")]
+    {
+        println!("cargo:rerun-if-changed=protocols/fractional-scale-v1.xml");
+        wayland_scanner::generate_code(
+            "protocols/fractional-scale-v1.xml",
+            out_path.join("fractional-scale-v1_client_api.rs"),
+            wayland_scanner::Side::Client,
+        );
+        println!("cargo:rerun-if-changed=protocols/cursor-shape-v1.xml");
+        wayland_scanner::generate_code(
+            "protocols/cursor-shape-v1.xml",
+            out_path.join("cursor-shape-v1_client_api.rs"),
+            wayland_scanner::Side::Client,
+        );
+    }
 }
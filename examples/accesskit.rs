@@ -21,7 +21,9 @@ use accesskit::{
 
 use glazier::kurbo::Size;
 
-use glazier::{Application, KbKey, KeyEvent, Region, WinHandler, WindowBuilder, WindowHandle};
+use glazier::{
+    Application, KbKey, KeyEvent, PaintReason, Region, WinHandler, WindowBuilder, WindowHandle,
+};
 
 const WINDOW_TITLE: &str = "Hello world";
 
@@ -132,7 +134,7 @@ impl WinHandler for HelloState {
 
     fn prepare_paint(&mut self) {}
 
-    fn paint(&mut self, _: &Region) {}
+    fn paint(&mut self, _: &Region, _: PaintReason) {}
 
     fn accesskit_tree(&mut self) -> TreeUpdate {
         let root = Arc::new(Node {
@@ -19,7 +19,8 @@ use glazier::kurbo::Size;
 
 use glazier::{
     Application, Cursor, FileDialogOptions, FileDialogToken, FileInfo, FileSpec, HotKey, KeyEvent,
-    Menu, MouseEvent, Region, SysMods, TimerToken, WinHandler, WindowBuilder, WindowHandle,
+    Menu, MouseEvent, PaintReason, Region, SysMods, TimerToken, WinHandler, WindowBuilder,
+    WindowHandle,
 };
 use piet_gpu_hal::{
     include_shader, BindType, Buffer, BufferUsage, ComputePassDescriptor, DescriptorSet, Image,
@@ -49,7 +50,7 @@ impl WinHandler for HelloState {
         self.handle.invalidate();
     }
 
-    fn paint(&mut self, _: &Region) {
+    fn paint(&mut self, _: &Region, _: PaintReason) {
         unsafe {
             // TODO: wire up size
             let width = 1000;
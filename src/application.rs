@@ -17,11 +17,19 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::backend::application as backend;
 use crate::clipboard::Clipboard;
 use crate::error::Error;
+use crate::keyboard::KeyboardLayout;
+use crate::media::MediaCommand;
+use crate::screen::{Monitor, Screen};
+use crate::sensors::{SensorEvent, SensorKind};
+use crate::strings::{self, BuiltinString};
 use crate::util;
+use crate::window::{FullscreenOptions, SystemBar, WindowHandle};
+use crate::window_group::WindowGroup;
 
 /// A top-level handler that is not associated with any window.
 ///
@@ -40,6 +48,161 @@ pub trait AppHandler {
     /// Called when a menu item is selected.
     #[allow(unused_variables)]
     fn command(&mut self, id: u32) {}
+
+    /// Called with a new reading from a sensor enabled with
+    /// [`Application::enable_sensor`].
+    #[allow(unused_variables)]
+    fn sensor(&mut self, event: SensorEvent) {}
+
+    /// Called when the desktop's media controls ask the application to play, pause, seek
+    /// and so on.
+    ///
+    /// On Linux, this requires publishing a player with [`dbus::Mpris`], which isn't supported
+    /// with the GTK backend.
+    ///
+    /// [`dbus::Mpris`]: crate::dbus::Mpris
+    #[allow(unused_variables)]
+    fn media_command(&mut self, command: MediaCommand) {}
+
+    /// Called when the application hasn't received any input for the period set with
+    /// [`Application::set_idle_timeout`].
+    ///
+    /// This is a good time to dim the UI, lock a session or start background work.
+    /// `since_last_input` is the time that has passed since the last input event.
+    ///
+    /// This is never called on the headless backend.
+    #[allow(unused_variables)]
+    fn idle(&mut self, since_last_input: Duration) {}
+
+    /// Called on the first input event after [`idle`].
+    ///
+    /// [`idle`]: AppHandler::idle
+    fn idle_ended(&mut self) {}
+
+    /// Called when the system wants to save the application's state, so that it can be
+    /// restored if the application is relaunched (for example after a system update).
+    ///
+    /// The returned blob is handed back to [`restore_session`] on the next launch. An empty
+    /// blob means there is nothing to save.
+    ///
+    /// This is currently only called on macOS.
+    ///
+    /// [`restore_session`]: AppHandler::restore_session
+    fn save_session(&mut self) -> SessionBlob {
+        SessionBlob::default()
+    }
+
+    /// Called at launch with state previously returned by [`save_session`], so that
+    /// the application can reopen its windows.
+    ///
+    /// This is called before [`restore_window`].
+    ///
+    /// This is currently only called on macOS.
+    ///
+    /// [`save_session`]: AppHandler::save_session
+    /// [`restore_window`]: AppHandler::restore_window
+    #[allow(unused_variables)]
+    fn restore_session(&mut self, session: SessionBlob) {}
+
+    /// Called once the application has launched, for each window that was open with a
+    /// [restoration id] when it last quit.
+    ///
+    /// The handler should build the window again and return its handle, which lets the
+    /// system put it back where it was. Return `None` to leave the window closed. The
+    /// default does that for every window.
+    ///
+    /// This is currently only called on macOS.
+    ///
+    /// [restoration id]: crate::WindowBuilder::set_restoration_id
+    #[allow(unused_variables)]
+    fn restore_window(&mut self, id: &str) -> Option<WindowHandle> {
+        None
+    }
+
+    /// Called when monitors are connected, disconnected or rearranged, or when their
+    /// resolution, scale or refresh rate changes.
+    ///
+    /// Use [`Application::monitors`] to get the new arrangement.
+    ///
+    /// This is currently called on macOS, X11 and Wayland.
+    fn monitors_changed(&mut self) {}
+}
+
+/// Opaque application state, saved and restored by the system across relaunches.
+///
+/// See [`AppHandler::save_session`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SessionBlob(Vec<u8>);
+
+impl SessionBlob {
+    /// Create a new `SessionBlob` from raw bytes.
+    pub fn new(data: impl Into<Vec<u8>>) -> SessionBlob {
+        SessionBlob(data.into())
+    }
+
+    /// Returns `true` if the blob contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The raw bytes of the blob.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Consume the blob, returning its raw bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// What [`Application::enter_kiosk_mode`] locks down, besides making the window fullscreen.
+///
+/// The default locks down everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KioskOptions {
+    /// Keep system shortcuts, such as Alt+Tab, the Windows key and Cmd+Tab, from switching
+    /// away from the window. Shortcuts that the system reserves for itself, such as
+    /// Ctrl+Alt+Del, can't be blocked.
+    pub block_system_shortcuts: bool,
+    /// Keep the screen saver from starting and the display from going to sleep.
+    pub inhibit_screensaver: bool,
+    /// Hide the mouse cursor over the window, for touch screens and displays without input.
+    pub hide_cursor: bool,
+}
+
+impl KioskOptions {
+    /// Nothing locked down, which is what leaving kiosk mode goes back to.
+    pub(crate) const OFF: KioskOptions = KioskOptions {
+        block_system_shortcuts: false,
+        inhibit_screensaver: false,
+        hide_cursor: false,
+    };
+}
+
+impl Default for KioskOptions {
+    fn default() -> Self {
+        KioskOptions {
+            block_system_shortcuts: true,
+            inhibit_screensaver: true,
+            hide_cursor: true,
+        }
+    }
+}
+
+/// The order in which the event loop delivers input, timers and idle callbacks.
+///
+/// Set it with [`Application::set_dispatch_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum DispatchPolicy {
+    /// Input events are delivered first, then expired timers, then idle callbacks. Pending
+    /// input is checked again between the timers and the idle callbacks, so that a flood of
+    /// either can't hold up input processing.
+    #[default]
+    InputFirst,
+    /// Whatever order the platform's event loop happens to deliver things in.
+    Native,
 }
 
 /// The top level application object.
@@ -54,6 +217,9 @@ pub struct Application {
 /// Platform-independent `Application` state.
 struct State {
     running: bool,
+    dispatch_policy: DispatchPolicy,
+    /// The window that is in kiosk mode, if any.
+    kiosk_window: Option<WindowHandle>,
 }
 
 /// Used to ensure only one Application instance is ever created.
@@ -80,7 +246,11 @@ impl Application {
             .map_err(|_| Error::ApplicationAlreadyExists)?;
         util::claim_main_thread();
         let backend_app = backend::Application::new()?;
-        let state = Rc::new(RefCell::new(State { running: false }));
+        let state = Rc::new(RefCell::new(State {
+            running: false,
+            dispatch_policy: DispatchPolicy::default(),
+            kiosk_window: None,
+        }));
         let app = Application { backend_app, state };
         GLOBAL_APP.with(|global_app| {
             *global_app.borrow_mut() = Some(app.clone());
@@ -175,6 +345,124 @@ impl Application {
         self.backend_app.clipboard().into()
     }
 
+    /// Creates a new, empty [`WindowGroup`].
+    ///
+    /// Add windows to it with [`WindowBuilder::set_group`].
+    ///
+    /// [`WindowBuilder::set_group`]: crate::WindowBuilder::set_group
+    pub fn new_window_group(&self) -> WindowGroup {
+        WindowGroup::new()
+    }
+
+    /// Start delivering readings from the given sensor to [`AppHandler::sensor`].
+    ///
+    /// Returns `false` if the sensor is not present or is not supported on this platform.
+    /// Sensors are only supported on Linux with the x11 or Wayland backend, where they are
+    /// read from iio-sensor-proxy.
+    pub fn enable_sensor(&self, kind: SensorKind) -> bool {
+        self.backend_app.enable_sensor(kind)
+    }
+
+    /// Stop delivering readings from the given sensor.
+    pub fn disable_sensor(&self, kind: SensorKind) {
+        self.backend_app.disable_sensor(kind)
+    }
+
+    /// Set how long the application must go without input before [`AppHandler::idle`]
+    /// is called.
+    ///
+    /// Only input delivered to this application counts; activity in other applications
+    /// doesn't reset the timer. `None`, the default, disables idle detection.
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.backend_app.set_idle_timeout(timeout)
+    }
+
+    /// Set the order in which the event loop delivers input, timers and idle callbacks.
+    ///
+    /// The default is [`DispatchPolicy::InputFirst`]. This is only a preference: on macOS and
+    /// on the web the platform runs the event loop and decides the order itself.
+    pub fn set_dispatch_policy(&self, policy: DispatchPolicy) {
+        self.state.borrow_mut().dispatch_policy = policy;
+        self.backend_app.set_dispatch_policy(policy)
+    }
+
+    /// Returns the order in which the event loop delivers input, timers and idle callbacks.
+    pub fn dispatch_policy(&self) -> DispatchPolicy {
+        self.state.borrow().dispatch_policy
+    }
+
+    /// Lock the application down for a kiosk deployment, showing `window` fullscreen.
+    ///
+    /// Besides making the window fullscreen with the menu bar, dock and taskbar hidden, this
+    /// does what `options` asks for. Only one window can be in kiosk mode; calling this again
+    /// moves kiosk mode to the new window. Use [`exit_kiosk_mode`] to undo all of it.
+    ///
+    /// How much can be locked down depends on the platform. On X11 blocking shortcuts grabs
+    /// the keyboard, which fails if the window isn't shown yet. On Wayland it needs the
+    /// compositor's support, and the compositor may ask the user first. GTK and the web
+    /// only make the window fullscreen.
+    ///
+    /// [`exit_kiosk_mode`]: Application::exit_kiosk_mode
+    pub fn enter_kiosk_mode(&self, window: &WindowHandle, options: KioskOptions) {
+        self.exit_kiosk_mode();
+        // On macOS the options are picked up when the window goes fullscreen, so they go first.
+        window.0.set_kiosk(options);
+        window.set_fullscreen(Some(FullscreenOptions {
+            menu_bar: SystemBar::Hidden,
+            dock: SystemBar::Hidden,
+        }));
+        self.state.borrow_mut().kiosk_window = Some(window.clone());
+    }
+
+    /// Leave kiosk mode, undoing everything that [`enter_kiosk_mode`] did.
+    ///
+    /// This does nothing if no window is in kiosk mode.
+    ///
+    /// [`enter_kiosk_mode`]: Application::enter_kiosk_mode
+    pub fn exit_kiosk_mode(&self) {
+        let window = self.state.borrow_mut().kiosk_window.take();
+        if let Some(window) = window {
+            window.0.set_kiosk(KioskOptions::OFF);
+            window.set_fullscreen(None);
+        }
+    }
+
+    /// Returns the monitors that are connected to the system.
+    ///
+    /// [`AppHandler::monitors_changed`] is called when this changes.
+    pub fn monitors(&self) -> Vec<Monitor> {
+        Screen::get_monitors()
+    }
+
+    /// Returns the keyboard layouts the user has enabled.
+    ///
+    /// Use [`WindowHandle::input_language`] to find out which one is currently active.
+    ///
+    /// [`WindowHandle::input_language`]: crate::WindowHandle::input_language
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        self.backend_app.keyboard_layouts()
+    }
+
+    /// Replaces the text that glazier shows for `string`, or goes back to the built-in text
+    /// if `text` is `None`.
+    ///
+    /// This is for translating into languages that glazier doesn't know, or for matching the
+    /// application's own wording.
+    pub fn set_builtin_string(&self, string: BuiltinString, text: Option<String>) {
+        strings::set_override(string, text)
+    }
+
+    /// Sets the locale that glazier translates its built-in text into.
+    ///
+    /// `None`, the default, follows [`get_locale`]. Strings replaced with
+    /// [`set_builtin_string`] are shown whatever the locale.
+    ///
+    /// [`get_locale`]: Application::get_locale
+    /// [`set_builtin_string`]: Application::set_builtin_string
+    pub fn set_builtin_strings_locale(&self, locale: Option<String>) {
+        strings::set_locale(locale)
+    }
+
     /// Returns the current locale string.
     ///
     /// This should a [Unicode language identifier].
@@ -0,0 +1,231 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Middle-click autoscroll, the way web browsers on Windows do it.
+//!
+//! Backends keep an [`Autoscroll`] for each window, and [`dispatch::wrap`] gives it the
+//! window's input first. While autoscroll is active, it takes over pointer input and turns
+//! the pointer's distance from the origin into a stream of wheel events.
+//!
+//! [`dispatch::wrap`]: crate::dispatch::wrap
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+use instant::Instant;
+
+use crate::keyboard::KeyEvent;
+use crate::kurbo::{Point, Vec2};
+use crate::mouse::{
+    Cursor, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent, ScrollDelta,
+};
+use crate::window::{TimerToken, WinHandler, WindowHandle};
+use crate::{KbKey, Modifiers};
+
+/// How often to send wheel events.
+const TICK: Duration = Duration::from_millis(16);
+/// How far the pointer can be from the origin, in display points, without scrolling.
+const DEAD_ZONE: f64 = 8.0;
+/// The scroll speed, in display points per second, for each display point that the pointer
+/// is past the dead zone.
+const SPEED: f64 = 8.0;
+
+/// The autoscroll state of a window.
+#[derive(Default)]
+pub(crate) struct Autoscroll(RefCell<State>);
+
+#[derive(Default)]
+struct State {
+    handle: Option<WindowHandle>,
+    active: Option<Active>,
+    /// A button whose release is hidden from the handler, because pressing it ended autoscroll.
+    swallowed_button: Option<MouseButton>,
+}
+
+struct Active {
+    origin: Point,
+    pos: Point,
+    timer: TimerToken,
+    last_tick: Instant,
+}
+
+impl Autoscroll {
+    /// Starts autoscrolling around `origin`, or moves the origin if already autoscrolling.
+    pub(crate) fn begin(&self, origin: Point) {
+        let mut state = self.0.borrow_mut();
+        let mut handle = match state.handle.clone() {
+            Some(handle) => handle,
+            None => return,
+        };
+        if let Some(active) = &mut state.active {
+            active.origin = origin;
+            active.pos = origin;
+            return;
+        }
+        state.active = Some(Active {
+            origin,
+            pos: origin,
+            timer: handle.request_timer(TICK),
+            last_tick: Instant::now(),
+        });
+        drop(state);
+        handle.set_cursor(&Cursor::AllScroll);
+    }
+
+    /// Stops autoscrolling. Returns `false` if we weren't autoscrolling.
+    fn end(&self) -> bool {
+        let mut state = self.0.borrow_mut();
+        if state.active.take().is_none() {
+            return false;
+        }
+        let handle = state.handle.clone();
+        drop(state);
+        // We don't know which cursor the handler wants, but it will set it again on the next
+        // mouse move.
+        if let Some(mut handle) = handle {
+            handle.set_cursor(&Cursor::Arrow);
+        }
+        true
+    }
+
+    /// If `token` is our timer, works out where and how far to scroll since the last tick, and
+    /// schedules the next one.
+    fn tick(&self, token: TimerToken) -> Option<(Point, Vec2)> {
+        let mut state = self.0.borrow_mut();
+        let handle = state.handle.clone()?;
+        let active = state.active.as_mut().filter(|a| a.timer == token)?;
+        let now = Instant::now();
+        let elapsed = now - active.last_tick;
+        active.last_tick = now;
+        active.timer = handle.request_timer(TICK);
+        let delta = velocity(active.pos - active.origin) * elapsed.as_secs_f64();
+        Some((active.origin, delta))
+    }
+
+    pub(crate) fn connect(&self, handle: &WindowHandle) {
+        self.0.borrow_mut().handle = Some(handle.clone());
+    }
+
+    /// Returns `true` if the key only stopped autoscroll.
+    pub(crate) fn key_down(&self, event: &KeyEvent) -> bool {
+        // Like in browsers, any key stops autoscroll, and escape does nothing else.
+        self.end() && event.key == KbKey::Escape
+    }
+
+    pub(crate) fn wheel(&self) {
+        self.end();
+    }
+
+    /// Returns `true` if the pointer moved the scroll speed, rather than the content's pointer.
+    pub(crate) fn mouse_move(&self, event: &MouseEvent) -> bool {
+        match &mut self.0.borrow_mut().active {
+            Some(active) => {
+                active.pos = event.pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the press only stopped autoscroll.
+    pub(crate) fn mouse_down(&self, event: &MouseEvent) -> bool {
+        // The click that ends autoscroll isn't a click on the content.
+        if self.end() {
+            self.0.borrow_mut().swallowed_button = Some(event.button);
+            return true;
+        }
+        false
+    }
+
+    /// Returns `true` if the release is hidden from the handler.
+    pub(crate) fn mouse_up(&self, event: &MouseEvent) -> bool {
+        {
+            let mut state = self.0.borrow_mut();
+            if state.swallowed_button == Some(event.button) {
+                state.swallowed_button = None;
+                return true;
+            }
+        }
+        // If the pointer moved while the button that started autoscroll was held, the user is
+        // dragging rather than clicking, so releasing the button stops.
+        let dragged = self
+            .0
+            .borrow()
+            .active
+            .as_ref()
+            .is_some_and(|active| (active.pos - active.origin).hypot() > DEAD_ZONE);
+        if dragged {
+            self.end();
+        }
+        false
+    }
+
+    /// If `token` is our timer, scrolls `handler` and returns `true`.
+    pub(crate) fn timer(&self, token: TimerToken, handler: &mut dyn WinHandler) -> bool {
+        let (origin, delta) = match self.tick(token) {
+            Some(tick) => tick,
+            None => return false,
+        };
+        if delta != Vec2::ZERO {
+            handler.wheel(&MouseEvent {
+                pos: origin,
+                buttons: MouseButtons::new(),
+                mods: Modifiers::empty(),
+                count: 0,
+                focus: false,
+                button: MouseButton::None,
+                wheel_delta: ScrollDelta::Pixels(delta),
+                momentum_phase: MomentumPhase::None,
+                source: InputSource::Hardware,
+                coalesced: Vec::new(),
+            });
+        }
+        true
+    }
+
+    pub(crate) fn lost_focus(&self) {
+        self.end();
+    }
+
+    pub(crate) fn destroy(&self) {
+        self.0.borrow_mut().active = None;
+    }
+}
+
+/// The scroll velocity, in display points per second, when the pointer is `offset` from the
+/// origin.
+fn velocity(offset: Vec2) -> Vec2 {
+    let axis = |d: f64| {
+        if d.abs() <= DEAD_ZONE {
+            0.0
+        } else {
+            d.signum() * (d.abs() - DEAD_ZONE) * SPEED
+        }
+    };
+    Vec2::new(axis(offset.x), axis(offset.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn velocity_dead_zone() {
+        assert_eq!(velocity(Vec2::new(DEAD_ZONE, -DEAD_ZONE)), Vec2::ZERO);
+        assert_eq!(
+            velocity(Vec2::new(DEAD_ZONE + 10.0, -(DEAD_ZONE + 5.0))),
+            Vec2::new(10.0 * SPEED, -5.0 * SPEED)
+        );
+    }
+}
@@ -14,13 +14,24 @@
 
 //! GTK implementation of features at the application scope.
 
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use gtk::gdk::EventType;
 use gtk::gio::prelude::ApplicationExtManual;
 use gtk::gio::{ApplicationFlags, Cancellable};
+use gtk::glib::{Continue, DateTime, SourceId};
 use gtk::Application as GtkApplication;
 
 use gtk::prelude::{ApplicationExt, GtkApplicationExt};
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, DispatchPolicy};
+use crate::common_util::IdleTracker;
+use crate::keyboard::KeyboardLayout;
+use crate::locale::LocaleInfo;
+use crate::sensors::SensorKind;
 
 use super::clipboard::Clipboard;
 use super::error::Error;
@@ -28,6 +39,16 @@ use super::error::Error;
 #[derive(Clone)]
 pub(crate) struct Application {
     gtk_app: GtkApplication,
+    idle: Rc<Idle>,
+}
+
+/// Tells the `AppHandler` when the application goes idle.
+#[derive(Default)]
+struct Idle {
+    tracker: IdleTracker,
+    handler: RefCell<Option<Box<dyn AppHandler>>>,
+    /// The timeout that is scheduled to check for idleness, and when it is for.
+    check: RefCell<Option<(SourceId, Instant)>>,
 }
 
 impl Application {
@@ -51,7 +72,10 @@ impl Application {
             return Err(Error::Error(err));
         }
 
-        Ok(Application { gtk_app })
+        Ok(Application {
+            gtk_app,
+            idle: Default::default(),
+        })
     }
 
     #[inline]
@@ -59,7 +83,17 @@ impl Application {
         &self.gtk_app
     }
 
-    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
+    pub fn run(self, handler: Option<Box<dyn AppHandler>>) {
+        *self.idle.handler.borrow_mut() = handler;
+        // Every event from GDK comes through here, so this is where we see the user's input.
+        let idle = self.idle.clone();
+        gtk::gdk::Event::set_handler(Some(move |event: &mut gtk::gdk::Event| {
+            if is_input(event.event_type()) && idle.tracker.input() {
+                idle.update();
+            }
+            gtk::main_do_event(event);
+        }));
+        self.idle.update();
         self.gtk_app.run();
     }
 
@@ -81,6 +115,31 @@ impl Application {
         }
     }
 
+    pub fn enable_sensor(&self, _kind: SensorKind) -> bool {
+        tracing::warn!(
+            "Application::enable_sensor is currently unimplemented for the GTK backend."
+        );
+        false
+    }
+
+    pub fn disable_sensor(&self, _kind: SensorKind) {}
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.idle.tracker.set_timeout(timeout);
+        self.idle.update();
+    }
+
+    pub fn set_dispatch_policy(&self, _policy: DispatchPolicy) {
+        // Windows look the policy up when they schedule their timers.
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        tracing::warn!(
+            "Application::keyboard_layouts is currently unimplemented for the GTK backend."
+        );
+        Vec::new()
+    }
+
     pub fn get_locale() -> String {
         let mut locale: String = gtk::glib::language_names()[0].as_str().into();
         // This is done because the locale parsing library we use expects an unicode locale, but these vars have an ISO locale
@@ -89,6 +148,103 @@ impl Application {
         }
         locale
     }
+
+    pub fn locale_info() -> LocaleInfo {
+        let mut info = LocaleInfo {
+            locale: Application::get_locale().replace('_', "-"),
+            ..LocaleInfo::default()
+        };
+        // GTK sets the C locale from the environment, so we find out what it does by
+        // formatting samples. The `'` flag groups the digits.
+        let number = unsafe {
+            let s = glib_sys::g_strdup_printf(b"%'.1f\0".as_ptr().cast(), 1234567.5f64);
+            let number = CStr::from_ptr(s).to_string_lossy().into_owned();
+            glib_sys::g_free(s.cast());
+            number
+        };
+        let separators = number
+            .strip_prefix('1')
+            .and_then(|n| n.strip_suffix('5'))
+            .and_then(|n| n.split_once("234"))
+            .and_then(|(group, n)| Some((group, n.split_once("567")?.1)));
+        if let Some((group, decimal)) = separators {
+            info.grouping_separator = group.into();
+            info.decimal_separator = decimal.into();
+        }
+        let sample = |format| {
+            DateTime::from_local(2033, 11, 22, 13, 0, 0.0)
+                .ok()?
+                .format(format)
+                .ok()
+        };
+        if let (Some(date), Some(time)) = (sample("%x"), sample("%X")) {
+            // Turn the samples back into patterns, such as `22.11.33` into `%d.%m.%y`.
+            let date = date
+                .replace("2033", "%Y")
+                .replace("33", "%y")
+                .replace("11", "%m")
+                .replace("22", "%d");
+            let time = if time.contains("13") { "%H" } else { "%I" };
+            info.apply_patterns(&date, time);
+        }
+        info
+    }
+
+    #[cfg(feature = "dbus")]
+    pub(crate) fn media_command_sink(&self) -> Option<crate::dbus::MediaCommandSink> {
+        // The commands would have to get from the D-Bus thread to the `AppHandler` on the main
+        // loop, which isn't implemented.
+        None
+    }
+}
+
+impl Idle {
+    /// Tells the `AppHandler` about any change, and schedules the next check.
+    fn update(self: &Rc<Self>) {
+        // The handler is borrowed if it's the one changing the timeout.
+        if let Ok(mut handler) = self.handler.try_borrow_mut() {
+            if let Some(handler) = handler.as_mut() {
+                self.tracker.dispatch(&mut **handler);
+            }
+        }
+        let deadline = self.tracker.deadline();
+        let mut check = self.check.borrow_mut();
+        if check.as_ref().map(|(_, at)| *at) == deadline {
+            return;
+        }
+        if let Some((source, _)) = check.take() {
+            source.remove();
+        }
+        if let Some(deadline) = deadline {
+            let interval = deadline.saturating_duration_since(Instant::now());
+            let idle = Rc::downgrade(self);
+            let source = gtk::glib::timeout_add_local(interval, move || {
+                if let Some(idle) = idle.upgrade() {
+                    // This source is finished, so it mustn't be removed again.
+                    idle.check.borrow_mut().take();
+                    idle.update();
+                }
+                Continue(false)
+            });
+            *check = Some((source, deadline));
+        }
+    }
+}
+
+/// Whether the event is the user's input, which keeps the application from going idle.
+fn is_input(event_type: EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::KeyPress
+            | EventType::KeyRelease
+            | EventType::ButtonPress
+            | EventType::ButtonRelease
+            | EventType::MotionNotify
+            | EventType::Scroll
+            | EventType::TouchBegin
+            | EventType::TouchUpdate
+            | EventType::TouchEnd
+    )
 }
 
 impl crate::platform::linux::ApplicationExt for crate::Application {
@@ -97,4 +253,14 @@ impl crate::platform::linux::ApplicationExt for crate::Application {
             selection: gtk::gdk::SELECTION_PRIMARY,
         })
     }
+
+    fn xkb_keymap(&self) -> Option<String> {
+        tracing::warn!("ApplicationExt::xkb_keymap is currently unimplemented for gtk.");
+        None
+    }
+
+    fn set_xkb_keymap(&self, _keymap: Option<crate::platform::linux::XkbKeymap>) -> bool {
+        tracing::warn!("ApplicationExt::set_xkb_keymap is currently unimplemented for gtk.");
+        false
+    }
 }
@@ -140,6 +140,18 @@ impl Clipboard {
         clipboard.wait_for_contents(&atom).map(|data| data.data())
     }
 
+    pub fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        let display = gtk::gdk::Display::default().unwrap();
+        let clipboard = gtk::Clipboard::for_display(&display, &self.selection);
+
+        // GTK only tells us the targets; their sizes would take a transfer each.
+        let targets = clipboard.wait_for_targets().unwrap_or_default();
+        targets
+            .iter()
+            .map(|atom| (atom.name().to_string(), None))
+            .collect()
+    }
+
     pub fn available_type_names(&self) -> Vec<String> {
         let display = gtk::gdk::Display::default().unwrap();
         let clipboard = gtk::Clipboard::for_display(&display, &self.selection);
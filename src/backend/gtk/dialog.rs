@@ -22,6 +22,7 @@ use gtk::{FileChooserAction, FileFilter, ResponseType, Window};
 use gtk::prelude::{FileChooserExt, NativeDialogExt};
 
 use crate::dialog::{FileDialogOptions, FileDialogType, FileSpec};
+use crate::strings::{self, BuiltinString};
 use crate::Error;
 
 fn file_filter(fs: &FileSpec) -> FileFilter {
@@ -38,18 +39,19 @@ pub(crate) fn get_file_dialog_path(
     ty: FileDialogType,
     options: FileDialogOptions,
 ) -> Result<Vec<OsString>, Error> {
-    // TODO: support message localization
-
     let (title, action) = match (ty, options.select_directories) {
-        (FileDialogType::Open, false) => ("Open File", FileChooserAction::Open),
-        (FileDialogType::Open, true) => ("Open Folder", FileChooserAction::SelectFolder),
-        (FileDialogType::Save, _) => ("Save File", FileChooserAction::Save),
+        (FileDialogType::Open, false) => (BuiltinString::OpenFileTitle, FileChooserAction::Open),
+        (FileDialogType::Open, true) => (
+            BuiltinString::OpenFolderTitle,
+            FileChooserAction::SelectFolder,
+        ),
+        (FileDialogType::Save, _) => (BuiltinString::SaveFileTitle, FileChooserAction::Save),
     };
-    let title = options.title.as_deref().unwrap_or(title);
+    let title = options.title.clone().unwrap_or_else(|| strings::get(title));
 
     let mut dialog = gtk::FileChooserNativeBuilder::new()
         .transient_for(window)
-        .title(title);
+        .title(&title);
     if let Some(button_text) = &options.button_text {
         dialog = dialog.accept_label(button_text);
     }
@@ -27,6 +27,7 @@ use super::window::WindowHandle;
 use crate::common_util::strip_access_key;
 use crate::hotkey::{HotKey, RawMods};
 use crate::keyboard::{KbKey, Modifiers};
+use crate::menu::run_command;
 
 #[derive(Default, Debug)]
 pub struct Menu {
@@ -162,7 +163,8 @@ fn add_menu_entry<
     let handle = handle.clone();
     item.connect_activate(move |_| {
         if let Some(state) = handle.state.upgrade() {
-            state.handler.borrow_mut().command(id);
+            let focus = state.active_text_input.get();
+            run_command(&mut **state.handler.borrow_mut(), focus, id);
         }
     });
 
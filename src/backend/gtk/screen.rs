@@ -14,7 +14,7 @@
 
 //! GTK Monitors and Screen information.
 
-use crate::screen::Monitor;
+use crate::screen::{ColorShift, Monitor};
 use gtk::gdk::{Display, DisplayManager, Rectangle};
 use kurbo::{Point, Rect, Size};
 
@@ -34,6 +34,10 @@ fn translate_gdk_monitor(mon: gtk::gdk::Monitor) -> Monitor {
             .map(translate_gdk_rectangle)
             .unwrap_or(area),
     )
+    .with_scale(mon.scale_factor() as f64)
+    // GDK gives the refresh rate in millihertz, or 0 if it doesn't know.
+    .with_refresh_rate((mon.refresh_rate() > 0).then(|| mon.refresh_rate() as f64 / 1000.0))
+    .with_name(mon.model().map(|model| model.to_string()))
 }
 
 pub(crate) fn get_monitors() -> Vec<Monitor> {
@@ -52,3 +56,8 @@ pub(crate) fn get_monitors() -> Vec<Monitor> {
         })
         .collect()
 }
+
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    tracing::warn!("Screen::get_color_shift() is not implemented for GTK.");
+    None
+}
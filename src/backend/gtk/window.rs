@@ -15,11 +15,13 @@
 //! GTK window creation and management.
 
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::ffi::c_void;
 use std::os::raw::{c_int, c_uint};
 use std::panic::Location;
 use std::ptr;
+use std::rc::Rc;
 use std::slice;
 use std::sync::{Arc, Mutex, Weak};
 use std::time::Instant;
@@ -28,6 +30,7 @@ use gtk::gdk_pixbuf::Colorspace::Rgb;
 use gtk::gdk_pixbuf::Pixbuf;
 use gtk::glib::source::Continue;
 use gtk::glib::translate::FromGlib;
+use gtk::glib::SourceId;
 use gtk::prelude::*;
 use gtk::traits::SettingsExt;
 use gtk::{AccelGroup, ApplicationWindow, DrawingArea};
@@ -45,21 +48,37 @@ use tracing::{error, warn};
 
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, XcbWindowHandle};
 
-use crate::kurbo::{Insets, Point, Rect, Size, Vec2};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
 use crate::piet::{Piet, PietText, RenderContext};
 
-use crate::common_util::{ClickCounter, IdleCallback};
+use crate::application::{DispatchPolicy, KioskOptions};
+use crate::autoscroll::Autoscroll;
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
+use crate::common_util::{
+    session_input_source, shape_to_rects, ClickCounter, FrameThrottle, IdleCallback,
+    DEFAULT_REFRESH_INTERVAL,
+};
 use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::dispatch;
+use crate::dnd::{DragData, DragImage};
 use crate::error::Error as ShellError;
-use crate::keyboard::{KbKey, KeyEvent, KeyState, Modifiers};
-use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
-use crate::piet::ImageFormat;
+use crate::keyboard::{KbKey, KeyEvent, KeyState, KeyboardLayout, Modifiers};
+use crate::locale::LocaleInfo;
+use crate::mouse::{
+    self, Cursor, CursorDesc, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent,
+    ScrollDelta,
+};
 use crate::region::Region;
 use crate::scale::{Scalable, Scale, ScaledArea};
+use crate::screen::{self, Monitor, MonitorId};
+use crate::shortcut_capture::ShortcutCapture;
 use crate::text::{simulate_input, Event};
 use crate::window::{
-    self, FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel,
+    self, FileDialogToken, FocusDirection, FullscreenOptions, IdleToken, PaintReason,
+    ScrollbarMetrics, TextFieldToken, Theme, TimerToken, WinHandler, WindowLevel,
 };
+use crate::window_group::WindowGroup;
 
 use super::application::Application;
 use super::dialog;
@@ -128,6 +147,26 @@ unsafe impl HasRawWindowHandle for WindowHandle {
     }
 }
 
+impl crate::platform::linux::WindowHandleExt for crate::WindowHandle {
+    fn export_handle(&self, f: Box<dyn FnOnce(Option<String>)>) {
+        warn!("WindowHandleExt::export_handle is currently unimplemented for gtk.");
+        f(None);
+    }
+
+    fn set_transient_for_foreign(&self, _handle: &str) -> bool {
+        warn!("WindowHandleExt::set_transient_for_foreign is currently unimplemented for gtk.");
+        false
+    }
+
+    fn set_momentum_scroll(&self, _enabled: bool) {
+        warn!("WindowHandleExt::set_momentum_scroll is currently unimplemented for gtk.");
+    }
+
+    fn set_client_decorations(&self, _theme: Option<crate::platform::linux::DecorationTheme>) {
+        // GTK draws its own decorations when the compositor won't.
+    }
+}
+
 /// Operations that we defer in order to avoid re-entrancy. See the documentation in the windows
 /// backend for more details.
 enum DeferredOp {
@@ -140,6 +179,7 @@ enum DeferredOp {
 pub(crate) struct WindowBuilder {
     app: Application,
     handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
     title: String,
     menu: Option<Menu>,
     position: Option<Point>,
@@ -147,9 +187,14 @@ pub(crate) struct WindowBuilder {
     state: Option<window::WindowState>,
     size: Size,
     min_size: Option<Size>,
+    resize_increments: Option<Size>,
     resizable: bool,
     show_titlebar: bool,
     transparent: bool,
+    focusable: bool,
+    activate_on_show: bool,
+    show_after_first_paint: bool,
+    forced_scale: Option<Scale>,
 }
 
 #[derive(Clone)]
@@ -170,6 +215,8 @@ enum IdleKind {
 pub(crate) struct WindowState {
     window: ApplicationWindow,
     scale: Cell<Scale>,
+    // A scale to use instead of the one GTK reports; this stops `scale` from being updated.
+    forced_scale: Option<Scale>,
     area: Cell<ScaledArea>,
     is_transparent: Cell<bool>,
     handle_titlebar: Cell<bool>,
@@ -193,16 +240,28 @@ pub(crate) struct WindowState {
     // The invalid region, in display points.
     invalid: RefCell<Region>,
     pub(crate) handler: RefCell<Box<dyn WinHandler>>,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
     idle_queue: Arc<Mutex<Vec<IdleKind>>>,
     current_keycode: Cell<Option<u16>>,
+    // Whether key repeats are passed on to the handler.
+    key_repeat: Cell<bool>,
     click_counter: ClickCounter,
-    active_text_input: Cell<Option<TextFieldToken>>,
+    pub(crate) active_text_input: Cell<Option<TextFieldToken>>,
     deferred_queue: RefCell<Vec<DeferredOp>>,
 
     request_animation: Cell<bool>,
+    /// Whether the handler asked for an animation frame since the last time we painted.
+    anim_frame_requested: Cell<bool>,
     in_draw: Cell<bool>,
+    frame_throttle: FrameThrottle,
 
     parent: Option<crate::WindowHandle>,
+    // Tooltips and drop-downs opened from this window, which are closed when it is clicked or
+    // loses focus.
+    popups: RefCell<Vec<WindowHandle>>,
+    // The GLib sources of the timers that haven't fired yet, so that they can be cancelled.
+    timers: RefCell<HashMap<TimerToken, SourceId>>,
 }
 
 impl std::fmt::Debug for WindowState {
@@ -222,6 +281,7 @@ impl WindowBuilder {
         WindowBuilder {
             app,
             handler: None,
+            group: None,
             title: String::new(),
             menu: None,
             size: Size::new(500.0, 400.0),
@@ -229,9 +289,14 @@ impl WindowBuilder {
             level: None,
             state: None,
             min_size: None,
+            resize_increments: None,
             resizable: true,
             show_titlebar: true,
             transparent: false,
+            focusable: true,
+            activate_on_show: true,
+            show_after_first_paint: false,
+            forced_scale: None,
         }
     }
 
@@ -239,6 +304,10 @@ impl WindowBuilder {
         self.handler = Some(handler);
     }
 
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
     pub fn set_size(&mut self, size: Size) {
         self.size = size;
     }
@@ -247,6 +316,10 @@ impl WindowBuilder {
         self.min_size = Some(size);
     }
 
+    pub fn set_resize_increments(&mut self, increments: Size) {
+        self.resize_increments = Some(increments);
+    }
+
     pub fn resizable(&mut self, resizable: bool) {
         self.resizable = resizable;
     }
@@ -264,6 +337,9 @@ impl WindowBuilder {
     }
 
     pub fn set_level(&mut self, level: WindowLevel) {
+        if let WindowLevel::Layer { .. } = level {
+            warn!("layer windows are only supported on wayland");
+        }
         self.level = Some(level);
     }
 
@@ -271,24 +347,56 @@ impl WindowBuilder {
         self.state = Some(state);
     }
 
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.focusable = focusable;
+    }
+
+    pub fn set_activate_on_show(&mut self, activate_on_show: bool) {
+        self.activate_on_show = activate_on_show;
+    }
+
+    pub fn show_after_first_paint(&mut self, show_after_first_paint: bool) {
+        self.show_after_first_paint = show_after_first_paint;
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
     pub fn set_title(&mut self, title: impl Into<String>) {
         self.title = title.into();
     }
 
+    pub fn set_taskbar_group(&mut self, _group: String) {
+        warn!("WindowBuilder::set_taskbar_group is currently unimplemented for gtk.");
+    }
+
+    pub fn set_restoration_id(&mut self, _id: String) {
+        // Ignored: only macOS restores windows.
+    }
+
     pub fn set_menu(&mut self, menu: Menu) {
         self.menu = Some(menu);
     }
 
-    pub fn build(self) -> Result<WindowHandle, ShellError> {
+    pub fn build(mut self) -> Result<WindowHandle, ShellError> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
         let handler = self
             .handler
             .expect("Tried to build a window without setting the handler");
+        let autoscroll = Rc::new(Autoscroll::default());
+        let shortcut_capture = Rc::new(ShortcutCapture::default());
+        let handler = dispatch::wrap(handler, &autoscroll, &shortcut_capture);
 
         let window = ApplicationWindow::new(self.app.gtk_app());
 
         window.set_title(&self.title);
         window.set_resizable(self.resizable);
         window.set_decorated(self.show_titlebar);
+        window.set_accept_focus(self.focusable);
+        window.set_focus_on_map(self.focusable && self.activate_on_show);
         let mut transparent = false;
         if self.transparent {
             if let Some(screen) = window.screen() {
@@ -300,13 +408,38 @@ impl WindowBuilder {
         window.set_app_paintable(transparent);
 
         // Get the scale factor based on the GTK reported DPI
-        let scale_factor = window.display().default_screen().resolution() / SCALE_TARGET_DPI;
-        let scale = Scale::new(scale_factor, scale_factor);
+        let scale = self.forced_scale.unwrap_or_else(|| {
+            let scale_factor = window.display().default_screen().resolution() / SCALE_TARGET_DPI;
+            Scale::new(scale_factor, scale_factor)
+        });
         let area = ScaledArea::from_dp(self.size, scale);
         let size_px = area.size_px();
 
         window.set_default_size(size_px.width as i32, size_px.height as i32);
 
+        if let Some(increments) = self.resize_increments {
+            let increments = increments.to_px(scale);
+            let base = self.min_size.unwrap_or(Size::ZERO).to_px(scale);
+            let geometry = gtk::gdk::Geometry::new(
+                0,
+                0,
+                0,
+                0,
+                base.width.round() as i32,
+                base.height.round() as i32,
+                (increments.width.round() as i32).max(1),
+                (increments.height.round() as i32).max(1),
+                0.0,
+                0.0,
+                gtk::gdk::Gravity::NorthWest,
+            );
+            window.set_geometry_hints(
+                None::<&gtk::Widget>,
+                Some(&geometry),
+                gtk::gdk::WindowHints::BASE_SIZE | gtk::gdk::WindowHints::RESIZE_INC,
+            );
+        }
+
         let accel_group = AccelGroup::new();
         window.add_accel_group(&accel_group);
 
@@ -318,7 +451,7 @@ impl WindowBuilder {
         let mut parent: Option<crate::WindowHandle> = None;
         if let Some(level) = &self.level {
             let hint = match level {
-                WindowLevel::AppWindow => WindowTypeHint::Normal,
+                WindowLevel::AppWindow | WindowLevel::Layer { .. } => WindowTypeHint::Normal,
                 WindowLevel::Tooltip(_) => WindowTypeHint::Tooltip,
                 WindowLevel::DropDown(_) => WindowTypeHint::DropdownMenu,
                 WindowLevel::Modal(_) => WindowTypeHint::Dialog,
@@ -327,11 +460,11 @@ impl WindowBuilder {
             window.set_type_hint(hint);
 
             match &level {
-                WindowLevel::Tooltip(p) => {
-                    parent = Some(p.clone());
-                }
-                WindowLevel::DropDown(p) => {
+                WindowLevel::Tooltip(p) | WindowLevel::DropDown(p) => {
                     parent = Some(p.clone());
+                    // Popups never take the keyboard focus; it stays with their parent.
+                    window.set_accept_focus(false);
+                    window.set_focus_on_map(false);
                 }
                 WindowLevel::Modal(p) => {
                     parent = Some(p.clone());
@@ -350,6 +483,7 @@ impl WindowBuilder {
         let state = WindowState {
             window,
             scale: Cell::new(scale),
+            forced_scale: self.forced_scale,
             area: Cell::new(area),
             is_transparent: Cell::new(transparent),
             handle_titlebar: Cell::new(false),
@@ -359,14 +493,21 @@ impl WindowBuilder {
             surface_size: Cell::new((0, 0)),
             invalid: RefCell::new(Region::EMPTY),
             handler: RefCell::new(handler),
+            autoscroll,
+            shortcut_capture,
             idle_queue: Arc::new(Mutex::new(vec![])),
             current_keycode: Cell::new(None),
+            key_repeat: Cell::new(true),
             click_counter: ClickCounter::default(),
             active_text_input: Cell::new(None),
             deferred_queue: RefCell::new(Vec::new()),
             request_animation: Cell::new(false),
+            anim_frame_requested: Cell::new(false),
             in_draw: Cell::new(false),
+            frame_throttle: FrameThrottle::default(),
             parent,
+            popups: RefCell::new(Vec::new()),
+            timers: RefCell::new(HashMap::new()),
         };
 
         let win_state = Arc::new(state);
@@ -388,6 +529,12 @@ impl WindowBuilder {
             handle.set_position(pos);
         }
 
+        if let Some(WindowLevel::Tooltip(parent) | WindowLevel::DropDown(parent)) = &self.level {
+            if let Some(parent_state) = parent.0.state.upgrade() {
+                parent_state.popups.borrow_mut().push(handle.clone());
+            }
+        }
+
         if let Some(state) = self.state {
             handle.set_window_state(state)
         }
@@ -460,6 +607,7 @@ impl WindowBuilder {
                 // Check if the GTK reported DPI has changed,
                 // so that we can change our scale factor without restarting the application.
                 if let Some(scale_factor) = state.window.window()
+                    .filter(|_| state.forced_scale.is_none())
                     .map(|w| w.display().default_screen().resolution() / SCALE_TARGET_DPI) {
                     let reported_scale = Scale::new(scale_factor, scale_factor);
                     if scale != reported_scale {
@@ -486,6 +634,19 @@ impl WindowBuilder {
                     state.invalidate_rect(size_dp.to_rect());
                 }
 
+                state.frame_throttle.frame_started();
+                // Animation frames requested from here on are for the next frame.
+                let reason = if state.anim_frame_requested.replace(false) {
+                    PaintReason::Animation
+                } else {
+                    PaintReason::Damage
+                };
+                let missed = state.frame_throttle.missed_frames(reason);
+                if missed > 0 {
+                    state.with_handler(|h| h.frame_missed(missed));
+                }
+                let info = state.frame_throttle.frame_info();
+                state.with_handler(|h| h.prepare_frame(info));
                 state.with_handler(|h| h.prepare_paint());
 
                 let invalid = match state.invalid.try_borrow_mut() {
@@ -513,7 +674,7 @@ impl WindowBuilder {
 
                         surface_context.scale(scale.x(), scale.y());
                         let mut piet_context = Piet::new(&surface_context);
-                        handler.paint(&mut piet_context, &invalid);
+                        handler.paint(&mut piet_context, &invalid, reason);
                         if let Err(e) = piet_context.finish() {
                             error!("piet error on render: {:?}", e);
                         }
@@ -535,6 +696,19 @@ impl WindowBuilder {
             Inhibit(false)
         }));
 
+        if self.show_after_first_paint {
+            // Keep the window transparent until the draw handler above has run once.
+            win_state.window.set_opacity(0.0);
+            win_state.drawing_area.connect_draw(|widget, _| {
+                if let Some(window) = widget.toplevel() {
+                    if window.opacity() < 1.0 {
+                        window.set_opacity(1.0);
+                    }
+                }
+                Inhibit(false)
+            });
+        }
+
         win_state.drawing_area.connect_screen_changed(
             clone!(handle => move |widget, _prev_screen| {
                 if let Some(state) = handle.state.upgrade() {
@@ -548,8 +722,18 @@ impl WindowBuilder {
             }),
         );
 
+        win_state
+            .window
+            .connect_configure_event(clone!(handle => move |_, _| {
+                if let Some(state) = handle.state.upgrade() {
+                    state.update_refresh_rate();
+                }
+                false
+            }));
+
         win_state.drawing_area.connect_button_press_event(clone!(handle => move |_widget, event| {
             if let Some(state) = handle.state.upgrade() {
+                state.dismiss_popups();
                 state.with_handler(|handler| {
                     if let Some(button) = get_mouse_button(event.button()) {
                         let scale = state.scale.get();
@@ -576,7 +760,10 @@ impl WindowBuilder {
                                     count,
                                     focus: false,
                                     button,
-                                    wheel_delta: Vec2::ZERO
+                                    wheel_delta: ScrollDelta::ZERO,
+                                    momentum_phase: MomentumPhase::None,
+                                    source: input_source(&event),
+                                    coalesced: Vec::new(),
                                 },
                             );
                         }
@@ -605,7 +792,10 @@ impl WindowBuilder {
                                 count: 0,
                                 focus: false,
                                 button,
-                                wheel_delta: Vec2::ZERO
+                                wheel_delta: ScrollDelta::ZERO,
+                                momentum_phase: MomentumPhase::None,
+                                source: input_source(&event),
+                                coalesced: Vec::new(),
                             },
                         );
                         if button.is_left() {
@@ -630,7 +820,10 @@ impl WindowBuilder {
                         count: 0,
                         focus: false,
                         button: MouseButton::None,
-                        wheel_delta: Vec2::ZERO
+                        wheel_delta: ScrollDelta::ZERO,
+                        momentum_phase: MomentumPhase::None,
+                        source: input_source(&motion),
+                        coalesced: Vec::new(),
                     };
 
                     state.with_handler(|h| h.mouse_move(&mouse_event));
@@ -652,7 +845,10 @@ impl WindowBuilder {
                         count: 0,
                         focus: false,
                         button: MouseButton::None,
-                        wheel_delta: Vec2::ZERO
+                        wheel_delta: ScrollDelta::ZERO,
+                        momentum_phase: MomentumPhase::None,
+                        source: input_source(&crossing),
+                        coalesced: Vec::new(),
                     };
 
                     state.with_handler(|h| h.mouse_move(&mouse_event));
@@ -669,21 +865,17 @@ impl WindowBuilder {
                     let scale = state.scale.get();
                     let mods = get_modifiers(scroll.state());
 
-                    // The magic "120"s are from Microsoft's documentation for WM_MOUSEWHEEL.
-                    // They claim that one "tick" on a scroll wheel should be 120 units.
                     let shift = mods.shift();
-                    let wheel_delta = match scroll.direction() {
-                        ScrollDirection::Up if shift => Some(Vec2::new(-120.0, 0.0)),
-                        ScrollDirection::Up => Some(Vec2::new(0.0, -120.0)),
-                        ScrollDirection::Down if shift => Some(Vec2::new(120.0, 0.0)),
-                        ScrollDirection::Down => Some(Vec2::new(0.0, 120.0)),
-                        ScrollDirection::Left => Some(Vec2::new(-120.0, 0.0)),
-                        ScrollDirection::Right => Some(Vec2::new(120.0, 0.0)),
+                    let notches = match scroll.direction() {
+                        ScrollDirection::Up if shift => Some(Vec2::new(-1.0, 0.0)),
+                        ScrollDirection::Up => Some(Vec2::new(0.0, -1.0)),
+                        ScrollDirection::Down if shift => Some(Vec2::new(1.0, 0.0)),
+                        ScrollDirection::Down => Some(Vec2::new(0.0, 1.0)),
+                        ScrollDirection::Left => Some(Vec2::new(-1.0, 0.0)),
+                        ScrollDirection::Right => Some(Vec2::new(1.0, 0.0)),
                         ScrollDirection::Smooth => {
-                            //TODO: Look at how gtk's scroll containers implements it
+                            // Smooth deltas are also in notches, but can be fractional.
                             let (mut delta_x, mut delta_y) = scroll.delta();
-                            delta_x *= 120.;
-                            delta_y *= 120.;
                             if shift {
                                 delta_x += delta_y;
                                 delta_y = 0.;
@@ -699,7 +891,7 @@ impl WindowBuilder {
                         }
                     };
 
-                    if let Some(wheel_delta) = wheel_delta {
+                    if let Some(notches) = notches {
                         let mouse_event = MouseEvent {
                             pos: Point::from(scroll.position()).to_dp(scale),
                             buttons: get_mouse_buttons_from_modifiers(scroll.state()),
@@ -707,7 +899,10 @@ impl WindowBuilder {
                             count: 0,
                             focus: false,
                             button: MouseButton::None,
-                            wheel_delta
+                            wheel_delta: ScrollDelta::Lines(notches * mouse::LINES_PER_NOTCH),
+                            momentum_phase: MomentumPhase::None,
+                            source: input_source(&scroll),
+                            coalesced: Vec::new(),
                         };
 
                         state.with_handler(|h| h.wheel(&mouse_event));
@@ -727,14 +922,48 @@ impl WindowBuilder {
 
                     state.current_keycode.set(Some(hw_keycode));
 
-                    state.with_handler(|h|
-                        simulate_input(h, state.active_text_input.get(), make_key_event(key, repeat, KeyState::Down))
+                    if repeat && !state.key_repeat.get() {
+                        return Inhibit(true);
+                    }
+
+                    let event = make_key_event(key, repeat, KeyState::Down);
+                    let focus = FocusDirection::from_key_event(&event);
+                    let handled = state.with_handler(|h|
+                        simulate_input(h, state.active_text_input.get(), event)
                     );
+                    // Let GTK move the focus for unhandled tabs; it asks us through the focus
+                    // signal below.
+                    if handled == Some(false) && focus.is_some() {
+                        return Inhibit(false);
+                    }
                 }
 
                 Inhibit(true)
             }));
 
+        win_state
+            .drawing_area
+            .connect_focus(clone!(handle => move |widget, direction| {
+                let direction = match direction {
+                    gtk::DirectionType::TabForward => FocusDirection::Next,
+                    gtk::DirectionType::TabBackward => FocusDirection::Previous,
+                    gtk::DirectionType::Up => FocusDirection::Up,
+                    gtk::DirectionType::Down => FocusDirection::Down,
+                    gtk::DirectionType::Left => FocusDirection::Left,
+                    gtk::DirectionType::Right => FocusDirection::Right,
+                    _ => return Inhibit(false),
+                };
+                if let Some(state) = handle.state.upgrade() {
+                    if state.with_handler(|h| h.focus_navigation(direction)) == Some(true) {
+                        widget.grab_focus();
+                        return Inhibit(true);
+                    }
+                }
+                // The default handler moves the focus out if we have it, and takes it otherwise,
+                // so that the window never ends up without a focused widget.
+                Inhibit(false)
+            }));
+
         win_state
             .drawing_area
             .connect_key_release_event(clone!(handle => move |_widget, key| {
@@ -766,6 +995,7 @@ impl WindowBuilder {
             .drawing_area
             .connect_focus_out_event(clone!(handle => move |_widget, _event| {
                 if let Some(state) = handle.state.upgrade() {
+                    state.dismiss_popups();
                     state.with_handler(|h| h.lost_focus());
                 }
                 Inhibit(true)
@@ -786,6 +1016,10 @@ impl WindowBuilder {
             .drawing_area
             .connect_destroy(clone!(handle => move |_widget| {
                 if let Some(state) = handle.state.upgrade() {
+                    // Repeating timers would otherwise keep the state alive.
+                    for (_, source) in state.timers.borrow_mut().drain() {
+                        source.remove();
+                    }
                     state.with_handler(|h| h.destroy());
                 }
             }));
@@ -800,7 +1034,7 @@ impl WindowBuilder {
 
         if let Some(level) = self.level {
             let override_redirect = match level {
-                WindowLevel::AppWindow => false,
+                WindowLevel::AppWindow | WindowLevel::Layer { .. } => false,
                 WindowLevel::Tooltip(_) | WindowLevel::DropDown(_) | WindowLevel::Modal(_) => true,
             };
             if let Some(window) = win_state.window.window() {
@@ -820,6 +1054,32 @@ impl WindowBuilder {
 }
 
 impl WindowState {
+    /// Close any tooltips and drop-downs that were opened from this window.
+    /// Looks up the refresh rate of the monitor that the window is on, and tells the handler if
+    /// it changed.
+    fn update_refresh_rate(&self) {
+        let monitor = self
+            .window
+            .window()
+            .and_then(|window| window.display().monitor_at_window(&window));
+        // GDK gives the refresh rate in mHz, or 0 if it doesn't know.
+        let hz = monitor
+            .map(|monitor| monitor.refresh_rate())
+            .filter(|&mhz| mhz > 0)
+            .map(|mhz| mhz as f64 / 1000.0);
+        if self.frame_throttle.set_refresh_rate(hz) {
+            let interval = self.frame_throttle.refresh_interval();
+            self.with_handler(|h| h.refresh_interval_changed(interval));
+        }
+    }
+
+    fn dismiss_popups(&self) {
+        let popups = std::mem::take(&mut *self.popups.borrow_mut());
+        for popup in popups {
+            popup.close();
+        }
+    }
+
     #[track_caller]
     fn with_handler<T, F: FnOnce(&mut dyn WinHandler) -> T>(&self, f: F) -> Option<T> {
         if self.invalid.try_borrow_mut().is_err() || self.surface.try_borrow_mut().is_err() {
@@ -886,8 +1146,16 @@ impl WindowState {
     /// Queues a call to `prepare_paint` and `paint`, but without marking any region for
     /// invalidation.
     fn request_anim_frame(&self) {
+        self.anim_frame_requested.set(true);
         if self.in_draw.get() {
             self.request_animation.set(true);
+        } else if let Some(deadline) = self.frame_throttle.next_frame_deadline() {
+            let interval = deadline.saturating_duration_since(Instant::now());
+            let drawing_area = self.drawing_area.clone();
+            gtk::glib::timeout_add_local(interval, move || {
+                drawing_area.queue_draw();
+                Continue(false)
+            });
         } else {
             self.drawing_area.queue_draw()
         }
@@ -1018,6 +1286,23 @@ impl WindowHandle {
         }
     }
 
+    pub fn monitor(&self) -> Option<Monitor> {
+        let origin = self.map_to_screen(Point::ZERO);
+        screen::monitor_containing(Rect::from_origin_size(origin, self.get_size()))
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        if let Some(state) = self.state.upgrade() {
+            // The drawing area has its own GDK window, so this skips the menu bar.
+            if let Some(window) = state.drawing_area.window() {
+                let (_, x, y) = window.origin();
+                let origin = Point::new(x as f64, y as f64).to_dp(state.scale.get());
+                return origin + point.to_vec2();
+            }
+        }
+        point
+    }
+
     pub fn content_insets(&self) -> Insets {
         if let Some(state) = self.state.upgrade() {
             let scale = state.scale.get();
@@ -1050,6 +1335,10 @@ impl WindowHandle {
         }
     }
 
+    pub fn safe_area(&self) -> Insets {
+        Insets::ZERO
+    }
+
     pub fn set_size(&self, size: Size) {
         if let Some(state) = self.state.upgrade() {
             let px = size.to_px(state.scale.get());
@@ -1099,6 +1388,26 @@ impl WindowHandle {
         Restored
     }
 
+    pub fn set_fullscreen(&self, options: Option<FullscreenOptions>) {
+        // The window manager decides what happens to panels, so the options don't apply.
+        if let Some(state) = self.state.upgrade() {
+            match options {
+                Some(_) => state.window.fullscreen(),
+                None => state.window.unfullscreen(),
+            }
+        }
+    }
+
+    pub fn preferred_output(&self, _monitor: Option<MonitorId>) {
+        // The window manager puts fullscreen windows on the monitor that they are on.
+    }
+
+    pub(crate) fn set_kiosk(&self, options: KioskOptions) {
+        if options != KioskOptions::OFF {
+            warn!("Kiosk mode lockdown is currently unimplemented for gtk.");
+        }
+    }
+
     pub fn handle_titlebar(&self, val: bool) {
         if let Some(state) = self.state.upgrade() {
             state.handle_titlebar.set(val);
@@ -1108,6 +1417,7 @@ impl WindowHandle {
     /// Close the window.
     pub fn close(&self) {
         if let Some(state) = self.state.upgrade() {
+            state.dismiss_popups();
             state.closing.set(true);
             state.window.close();
         }
@@ -1122,6 +1432,58 @@ impl WindowHandle {
         }
     }
 
+    pub fn raise_above(&self, other: &WindowHandle) {
+        self.restack(other, true);
+    }
+
+    pub fn lower_below(&self, other: &WindowHandle) {
+        self.restack(other, false);
+    }
+
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        if let Some(state) = self.state.upgrade() {
+            // An empty input shape lets pointer events through to whatever is below us.
+            let region = passthrough.then(cairo::Region::create);
+            state.window.input_shape_combine_region(region.as_ref());
+        }
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        if let Some(state) = self.state.upgrade() {
+            state.key_repeat.set(enabled);
+        }
+    }
+
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        // We don't use GTK's input methods, so key events are never composed.
+    }
+
+    pub fn set_shape(&self, shape: Option<BezPath>) {
+        if let Some(state) = self.state.upgrade() {
+            let region = shape.map(|shape| {
+                let rects: Vec<_> = shape_to_rects(&shape, Scale::default())
+                    .into_iter()
+                    .map(|r| cairo::RectangleInt {
+                        x: r.x0 as i32,
+                        y: r.y0 as i32,
+                        width: r.width() as i32,
+                        height: r.height() as i32,
+                    })
+                    .collect();
+                cairo::Region::create_rectangles(&rects)
+            });
+            state.window.shape_combine_region(region.as_ref());
+        }
+    }
+
+    fn restack(&self, other: &WindowHandle, above: bool) {
+        if let (Some(state), Some(other)) = (self.state.upgrade(), other.state.upgrade()) {
+            if let (Some(window), Some(sibling)) = (state.window.window(), other.window.window()) {
+                window.restack(Some(&sibling), above);
+            }
+        }
+    }
+
     /// Request a new paint, but without invalidating anything.
     pub fn request_anim_frame(&self) {
         if let Some(state) = self.state.upgrade() {
@@ -1129,6 +1491,19 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        if let Some(state) = self.state.upgrade() {
+            state.frame_throttle.set_max_frame_rate(fps);
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        match self.state.upgrade() {
+            Some(state) => state.frame_throttle.refresh_interval(),
+            None => DEFAULT_REFRESH_INTERVAL,
+        }
+    }
+
     /// Request invalidation of the entire window contents.
     pub fn invalidate(&self) {
         if let Some(state) = self.state.upgrade() {
@@ -1144,6 +1519,11 @@ impl WindowHandle {
         }
     }
 
+    pub fn scroll_region(&self, rect: Rect, _delta: Vec2) {
+        // TODO: move the contents of our cairo surface instead.
+        self.invalidate_rect(rect);
+    }
+
     pub fn text(&self) -> PietText {
         PietText::new()
     }
@@ -1170,20 +1550,55 @@ impl WindowHandle {
         // noop until we get a real text input implementation
     }
 
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Instant,
+        _tolerance: Duration,
+    ) -> TimerToken {
+        // TODO: GLib only coalesces timers to whole seconds (`g_timeout_add_seconds`).
+        self.request_timer(deadline)
+    }
+
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
         let interval = deadline
             .checked_duration_since(Instant::now())
             .unwrap_or_default();
+        self.schedule_timer(interval, false)
+    }
+
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        self.schedule_timer(interval, true)
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        if let Some(state) = self.state.upgrade() {
+            if let Some(source) = state.timers.borrow_mut().remove(&token) {
+                source.remove();
+            }
+        }
+    }
 
+    fn schedule_timer(&self, interval: Duration, repeating: bool) -> TimerToken {
         let token = TimerToken::next();
 
+        // GDK delivers input at the default priority, so timers only go after it if they have
+        // a lower one.
+        let priority = match crate::Application::try_global().map(|app| app.dispatch_policy()) {
+            Some(DispatchPolicy::Native) => gtk::glib::PRIORITY_DEFAULT,
+            _ => gtk::glib::PRIORITY_HIGH_IDLE,
+        };
         if let Some(state) = self.state.upgrade() {
-            gtk::glib::timeout_add(interval, move || {
-                if state.with_handler(|h| h.timer(token)).is_some() {
+            let timer_state = state.clone();
+            let source = gtk::glib::timeout_source_new(interval, None, priority, move || {
+                // If the handler is busy, we try again on the next turn of the loop.
+                if timer_state.with_handler(|h| h.timer(token)).is_some() && !repeating {
+                    timer_state.timers.borrow_mut().remove(&token);
                     return Continue(false);
                 }
                 Continue(true)
-            });
+            })
+            .attach(None);
+            state.timers.borrow_mut().insert(token, source);
         }
         token
     }
@@ -1195,22 +1610,62 @@ impl WindowHandle {
         }
     }
 
+    pub fn confine_pointer(&self, _region: Option<Rect>) {
+        warn!("WindowHandle::confine_pointer is currently unimplemented for gtk.");
+    }
+
+    pub fn lock_pointer(&self) {
+        warn!("WindowHandle::lock_pointer is currently unimplemented for gtk.");
+    }
+
+    pub fn unlock_pointer(&self) {
+        warn!("WindowHandle::unlock_pointer is currently unimplemented for gtk.");
+    }
+
+    pub fn set_raw_pointer_input(&self, _enabled: bool) {
+        warn!("WindowHandle::set_raw_pointer_input is currently unimplemented for gtk.");
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        if let Some(state) = self.state.upgrade() {
+            state.autoscroll.begin(origin);
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        if let Some(state) = self.state.upgrade() {
+            state.shortcut_capture.begin(callback);
+        }
+    }
+
     pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
         if let Some(state) = self.state.upgrade() {
             if let Some(gdk_window) = state.window.window() {
-                // TODO: Pixbuf expects unpremultiplied alpha. We should convert.
-                let has_alpha = !matches!(desc.image.format(), ImageFormat::Rgb);
-                let bytes_per_pixel = desc.image.format().bytes_per_pixel();
+                if desc.width == 0 || desc.height == 0 {
+                    return None;
+                }
+                // Pixbuf expects unpremultiplied alpha.
+                let pixels = desc
+                    .rgba()
+                    .flat_map(|[r, g, b, a]| {
+                        let straight = |c: u8| match a {
+                            0 => 0,
+                            a => (c as u32 * 255 / a as u32).min(255) as u8,
+                        };
+                        [straight(r), straight(g), straight(b), a]
+                    })
+                    .collect::<Vec<u8>>();
                 let pixbuf = Pixbuf::from_mut_slice(
-                    desc.image.raw_pixels().to_owned(),
+                    pixels,
                     Rgb,
-                    has_alpha,
+                    // has_alpha
+                    true,
                     // bits_per_sample
                     8,
-                    desc.image.width() as i32,
-                    desc.image.height() as i32,
+                    desc.width as i32,
+                    desc.height as i32,
                     // row stride (in bytes)
-                    (desc.image.width() * bytes_per_pixel) as i32,
+                    (desc.width * 4) as i32,
                 );
                 let c = gtk::gdk::Cursor::from_pixbuf(
                     &gdk_window.display(),
@@ -1227,6 +1682,14 @@ impl WindowHandle {
         }
     }
 
+    pub fn start_drag(&self, _data: DragData, _image: Option<DragImage>) {
+        warn!("start_drag is unimplemented for gtk");
+    }
+
+    pub fn set_drop_formats(&self, _formats: &[FormatId]) {
+        // TODO: drops aren't implemented for gtk yet.
+    }
+
     pub fn open_file(&mut self, options: FileDialogOptions) -> Option<FileDialogToken> {
         if let Some(state) = self.state.upgrade() {
             let tok = FileDialogToken::next();
@@ -1255,6 +1718,15 @@ impl WindowHandle {
         })
     }
 
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        warn!("WindowHandle::input_language is currently unimplemented for gtk.");
+        None
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        Application::locale_info()
+    }
+
     /// Get the `Scale` of the window.
     pub fn get_scale(&self) -> Result<Scale, ShellError> {
         Ok(self
@@ -1265,6 +1737,31 @@ impl WindowHandle {
             .get())
     }
 
+    pub fn text_scale_factor(&self) -> f64 {
+        // Desktops put the text scaling factor into the DPI, which we already use for the scale.
+        1.0
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        // High-contrast GTK themes are ordinary themes, with no palette that we can query.
+        None
+    }
+
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        // Scrolled windows overlay their scrollbars unless this is turned off, with the
+        // environment or, since GTK 3.24.9, with a setting.
+        const SETTING: &str = "gtk-overlay-scrolling";
+        let overlay = std::env::var_os("GTK_OVERLAY_SCROLLING").map_or(true, |v| v != "0")
+            && gtk::Settings::default().map_or(true, |settings| {
+                settings.find_property(SETTING).is_none() || settings.property::<bool>(SETTING)
+            });
+        // The theme sizes the scrollbars in its CSS, which we can't ask about.
+        ScrollbarMetrics {
+            overlay,
+            ..ScrollbarMetrics::default()
+        }
+    }
+
     pub fn set_menu(&self, menu: Menu) {
         if let Some(state) = self.state.upgrade() {
             let window = &state.window;
@@ -1296,6 +1793,10 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_theme_override(&self, _theme: Option<Theme>) {
+        warn!("WindowHandle::set_theme_override is currently unimplemented for gtk.");
+    }
+
     #[cfg(feature = "accesskit")]
     pub fn update_accesskit_if_active(
         &self,
@@ -1391,6 +1892,9 @@ fn make_gdk_cursor(cursor: &Cursor, gdk_window: &Window) -> Option<gtk::gdk::Cur
                 Cursor::NotAllowed => "not-allowed",
                 Cursor::ResizeLeftRight => "ew-resize",
                 Cursor::ResizeUpDown => "ns-resize",
+                Cursor::AllScroll => "all-scroll",
+                Cursor::Wait => "wait",
+                Cursor::Progress => "progress",
                 Cursor::Custom(_) => unreachable!(),
             },
         )
@@ -1409,6 +1913,15 @@ fn get_mouse_button(button: u32) -> Option<MouseButton> {
     }
 }
 
+/// Where a pointer event came from. Events that another client sent are injected.
+fn input_source(event: &gtk::gdk::Event) -> InputSource {
+    if event.send_event() {
+        InputSource::Injected
+    } else {
+        session_input_source()
+    }
+}
+
 fn get_mouse_buttons_from_modifiers(modifiers: ModifierType) -> MouseButtons {
     let mut buttons = MouseButtons::new();
     if modifiers.contains(ModifierType::BUTTON1_MASK) {
@@ -1468,12 +1981,14 @@ fn get_modifiers(modifiers: ModifierType) -> Modifiers {
 
 fn make_key_event(key: &EventKey, repeat: bool, state: KeyState) -> KeyEvent {
     let keyval = key.keyval();
+    // GDK keyvals are xkb keysyms.
+    let xkb_keysym = *keyval;
     let hardware_keycode = key.hardware_keycode();
 
     let keycode = hardware_keycode_to_keyval(hardware_keycode).unwrap_or_else(|| keyval.clone());
 
     let text = keyval.to_unicode();
-    let mods = get_modifiers(key.state());
+    let mut mods = get_modifiers(key.state());
     let key = keycodes::raw_key_to_key(keyval).unwrap_or_else(|| {
         if let Some(c) = text {
             if c >= ' ' && c != '\x7f' {
@@ -1485,6 +2000,20 @@ fn make_key_event(key: &EventKey, repeat: bool, state: KeyState) -> KeyEvent {
             KbKey::Unidentified
         }
     });
+    // GDK gives us the modifiers from before the event, so modifier keys don't count
+    // themselves yet.
+    let own_mod = match key {
+        KbKey::Shift => Some(Modifiers::SHIFT),
+        KbKey::Control => Some(Modifiers::CONTROL),
+        KbKey::Alt => Some(Modifiers::ALT),
+        KbKey::AltGraph => Some(Modifiers::ALT_GRAPH),
+        KbKey::Meta => Some(Modifiers::META),
+        KbKey::Super => Some(Modifiers::SUPER),
+        _ => None,
+    };
+    if let Some(own_mod) = own_mod {
+        mods.set(own_mod, state == KeyState::Down);
+    }
     let code = keycodes::hardware_keycode_to_code(hardware_keycode);
     let location = keycodes::raw_key_to_location(keycode);
     let is_composing = false;
@@ -1497,6 +2026,7 @@ fn make_key_event(key: &EventKey, repeat: bool, state: KeyState) -> KeyEvent {
         mods,
         repeat,
         is_composing,
+        xkb_keysym: Some(xkb_keysym),
     }
 }
 
@@ -0,0 +1,200 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The headless application and its event loop.
+
+use instant::{Duration, Instant};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::application::{AppHandler, DispatchPolicy};
+use crate::keyboard::KeyboardLayout;
+use crate::locale::LocaleInfo;
+use crate::media::MediaCommand;
+use crate::sensors::SensorKind;
+
+use super::clipboard::Clipboard;
+use super::error::Error;
+use super::window::Window;
+
+#[derive(Clone)]
+pub(crate) struct Application {
+    state: Rc<State>,
+}
+
+struct State {
+    windows: RefCell<Vec<Rc<Window>>>,
+    clipboard: Clipboard,
+    quitting: Cell<bool>,
+    wakeup: Arc<Wakeup>,
+    /// Commands from the desktop's media controls, which arrive on the D-Bus thread and are
+    /// delivered to the `AppHandler` by the event loop.
+    media_commands: Arc<Mutex<Vec<MediaCommand>>>,
+}
+
+/// Wakes the event loop up when there is work for it, which can come from other threads.
+#[derive(Default)]
+pub(crate) struct Wakeup {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Wakeup {
+    pub(crate) fn wake(&self) {
+        *self.woken.lock().unwrap() = true;
+        self.condvar.notify_one();
+    }
+
+    /// Blocks until [`wake`] is called, or until `deadline` if there is one.
+    ///
+    /// [`wake`]: Wakeup::wake
+    fn wait(&self, deadline: Option<Instant>) {
+        let mut woken = self.woken.lock().unwrap();
+        while !*woken {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    woken = self.condvar.wait_timeout(woken, deadline - now).unwrap().0;
+                }
+                None => woken = self.condvar.wait(woken).unwrap(),
+            }
+        }
+        *woken = false;
+    }
+}
+
+impl Application {
+    pub fn new() -> Result<Application, Error> {
+        Ok(Application {
+            state: Rc::new(State {
+                windows: RefCell::new(Vec::new()),
+                clipboard: Clipboard::default(),
+                quitting: Cell::new(false),
+                wakeup: Arc::new(Wakeup::default()),
+                media_commands: Default::default(),
+            }),
+        })
+    }
+
+    pub(crate) fn add_window(&self, window: Rc<Window>) {
+        self.state.windows.borrow_mut().push(window);
+    }
+
+    pub(crate) fn remove_window(&self, window: &Window) {
+        self.state
+            .windows
+            .borrow_mut()
+            .retain(|w| !std::ptr::eq(&**w, window));
+    }
+
+    pub(crate) fn wakeup(&self) -> &Arc<Wakeup> {
+        &self.state.wakeup
+    }
+
+    /// Runs everything that is due in all of the windows.
+    fn dispatch(&self) {
+        let now = Instant::now();
+        // Handlers can open and close windows while we go through them.
+        let windows = self.state.windows.borrow().clone();
+        for window in windows {
+            window.dispatch(now);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.state
+            .windows
+            .borrow()
+            .iter()
+            .filter_map(|window| window.next_deadline())
+            .min()
+    }
+
+    pub fn run(self, mut handler: Option<Box<dyn AppHandler>>) {
+        // There are no menus or monitors, so the application handler only hears about media
+        // commands.
+        loop {
+            self.dispatch();
+            let commands = std::mem::take(&mut *self.state.media_commands.lock().unwrap());
+            if let Some(handler) = handler.as_mut() {
+                for command in commands {
+                    handler.media_command(command);
+                }
+            }
+            if self.state.quitting.get() && self.state.windows.borrow().is_empty() {
+                break;
+            }
+            self.state.wakeup.wait(self.next_deadline());
+        }
+    }
+
+    pub fn quit(&self) {
+        if !self.state.quitting.replace(true) {
+            for window in self.state.windows.borrow().iter() {
+                window.close();
+            }
+            self.state.wakeup.wake();
+        }
+    }
+
+    pub fn clipboard(&self) -> Clipboard {
+        self.state.clipboard.clone()
+    }
+
+    pub fn enable_sensor(&self, _kind: SensorKind) -> bool {
+        // There are no sensors.
+        false
+    }
+
+    pub fn disable_sensor(&self, _kind: SensorKind) {}
+
+    pub fn set_idle_timeout(&self, _timeout: Option<Duration>) {
+        // There's no user to go idle.
+    }
+
+    pub fn set_dispatch_policy(&self, _policy: DispatchPolicy) {
+        // Input is injected directly into the handlers, so there's nothing to reorder.
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        // Injected key events come with their keys already mapped.
+        Vec::new()
+    }
+
+    pub fn get_locale() -> String {
+        // A fixed locale, so that tests don't depend on the machine they run on.
+        Application::locale_info().locale
+    }
+
+    pub fn locale_info() -> LocaleInfo {
+        LocaleInfo::default()
+    }
+
+    #[cfg(all(
+        any(target_os = "freebsd", target_os = "linux", target_os = "openbsd"),
+        feature = "dbus"
+    ))]
+    pub(crate) fn media_command_sink(&self) -> Option<crate::dbus::MediaCommandSink> {
+        let queue = self.state.media_commands.clone();
+        let wakeup = self.state.wakeup.clone();
+        Some(Arc::new(move |command| {
+            queue.lock().unwrap().push(command);
+            wakeup.wake();
+        }))
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A backend that runs windows without a display server.
+//!
+//! Nothing is drawn and no input arrives by itself: windows only see the events that are
+//! injected with [`EventInjector`]. This lets the handler logic of GUI toolkits run in CI.
+//!
+//! [`EventInjector`]: crate::platform::headless::EventInjector
+
+pub mod application;
+pub mod clipboard;
+pub mod error;
+pub mod menu;
+pub mod screen;
+pub mod window;
@@ -0,0 +1,36 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The pretend monitor that headless windows are on.
+
+use crate::kurbo::{Rect, Size};
+use crate::screen::{ColorShift, Monitor};
+
+/// The size of the monitor, in display points.
+const MONITOR_SIZE: Size = Size::new(1920.0, 1080.0);
+
+/// How many times a second the monitor refreshes, which paces animation frames.
+pub(crate) const REFRESH_RATE: f64 = 60.0;
+
+pub(crate) fn get_monitors() -> Vec<Monitor> {
+    let rect = Rect::from_origin_size((0.0, 0.0), MONITOR_SIZE);
+    vec![Monitor::new(true, rect, rect)
+        .with_refresh_rate(Some(REFRESH_RATE))
+        .with_name(Some("headless".into()))]
+}
+
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    // Nothing tints a screen that doesn't exist.
+    None
+}
@@ -0,0 +1,1009 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless windows, which only exist in memory.
+
+use instant::{Duration, Instant};
+use std::cell::{Cell, RefCell};
+use std::panic::Location;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, WebDisplayHandle,
+    WebWindowHandle,
+};
+use tracing::error;
+
+use crate::application::KioskOptions;
+use crate::autoscroll::Autoscroll;
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
+use crate::common_util::{ClickCounter, FrameThrottle, IdleCallback};
+use crate::dialog::FileDialogOptions;
+use crate::dispatch;
+use crate::dnd::{DragData, DragImage};
+use crate::error::Error as ShellError;
+use crate::keyboard::{KeyEvent, KeyboardLayout, Modifiers};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
+use crate::locale::LocaleInfo;
+use crate::mouse::{
+    Cursor, CursorDesc, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent,
+    ScrollDelta,
+};
+use crate::region::Region;
+use crate::scale::Scale;
+use crate::screen::{Monitor, MonitorId};
+use crate::shortcut_capture::ShortcutCapture;
+use crate::text::{simulate_input, Event};
+use crate::window;
+use crate::window::{
+    FileDialogToken, FocusDirection, FullscreenOptions, IdleToken, PaintReason, ScrollbarMetrics,
+    TextFieldToken, Theme, TimerToken, WinHandler, WindowLevel,
+};
+use crate::window_group::WindowGroup;
+
+use super::application::{Application, Wakeup};
+use super::error::Error;
+use super::menu::Menu;
+use super::screen::{self, REFRESH_RATE};
+use crate::backend::shared::{Timer, TimerQueue};
+
+pub(crate) struct WindowBuilder {
+    app: Application,
+    handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
+    position: Point,
+    size: Size,
+    min_size: Size,
+    state: window::WindowState,
+    forced_scale: Option<Scale>,
+}
+
+impl WindowBuilder {
+    pub fn new(app: Application) -> WindowBuilder {
+        WindowBuilder {
+            app,
+            handler: None,
+            group: None,
+            position: Point::ZERO,
+            size: Size::new(500.0, 400.0),
+            min_size: Size::ZERO,
+            state: window::WindowState::Restored,
+            forced_scale: None,
+        }
+    }
+
+    pub fn set_handler(&mut self, handler: Box<dyn WinHandler>) {
+        self.handler = Some(handler);
+    }
+
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    pub fn set_min_size(&mut self, size: Size) {
+        self.min_size = size;
+    }
+
+    pub fn set_resize_increments(&mut self, _increments: Size) {
+        // Ignored
+    }
+
+    pub fn resizable(&mut self, _resizable: bool) {
+        // Ignored
+    }
+
+    pub fn show_titlebar(&mut self, _show_titlebar: bool) {
+        // Ignored
+    }
+
+    pub fn set_transparent(&mut self, _transparent: bool) {
+        // Ignored
+    }
+
+    pub fn set_position(&mut self, position: Point) {
+        self.position = position;
+    }
+
+    pub fn set_level(&mut self, _level: WindowLevel) {
+        // Ignored
+    }
+
+    pub fn set_window_state(&mut self, state: window::WindowState) {
+        self.state = state;
+    }
+
+    pub fn set_focusable(&mut self, _focusable: bool) {
+        // Ignored
+    }
+
+    pub fn set_activate_on_show(&mut self, _activate_on_show: bool) {
+        // Ignored
+    }
+
+    pub fn show_after_first_paint(&mut self, _show_after_first_paint: bool) {
+        // Ignored
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
+    pub fn set_title<S: Into<String>>(&mut self, _title: S) {
+        // Ignored
+    }
+
+    pub fn set_taskbar_group(&mut self, _group: String) {
+        // Ignored
+    }
+
+    pub fn set_restoration_id(&mut self, _id: String) {
+        // Ignored: only macOS restores windows.
+    }
+
+    pub fn set_menu(&mut self, _menu: Menu) {
+        // Ignored
+    }
+
+    pub fn build(mut self) -> Result<WindowHandle, Error> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
+        let autoscroll = Rc::new(Autoscroll::default());
+        let shortcut_capture = Rc::new(ShortcutCapture::default());
+        let handler = dispatch::wrap(
+            self.handler.expect("Missing handler"),
+            &autoscroll,
+            &shortcut_capture,
+        );
+        let frame_throttle = FrameThrottle::default();
+        frame_throttle.set_refresh_rate(Some(REFRESH_RATE));
+        frame_throttle.set_max_frame_rate(Some(REFRESH_RATE as f32));
+        let window = Rc::new(Window {
+            app: self.app.clone(),
+            handler: RefCell::new(handler),
+            position: Cell::new(self.position),
+            size: Cell::new(self.size),
+            min_size: self.min_size,
+            size_changed: Cell::new(false),
+            scale: Cell::new(self.forced_scale.unwrap_or_else(|| Scale::new(1.0, 1.0))),
+            state: Cell::new(self.state),
+            visible: Cell::new(false),
+            closing: Cell::new(false),
+            invalid: RefCell::new(Region::EMPTY),
+            anim_frame_requested: Cell::new(false),
+            frame_throttle,
+            timer_queue: RefCell::new(TimerQueue::new()),
+            idle_queue: Arc::new(Mutex::new(Vec::new())),
+            active_text_field: Cell::new(None),
+            key_repeat: Cell::new(true),
+            autoscroll,
+            shortcut_capture,
+            click_counter: ClickCounter::default(),
+            buttons: Cell::new(MouseButtons::new()),
+            mods: Cell::new(Modifiers::empty()),
+            throttle: Throttle::default(),
+        });
+        self.app.add_window(window.clone());
+        let handle = WindowHandle(Rc::downgrade(&window));
+        window.connect(handle.clone());
+        Ok(handle)
+    }
+}
+
+/// A window that only exists in memory.
+pub(crate) struct Window {
+    app: Application,
+    handler: RefCell<Box<dyn WinHandler>>,
+    position: Cell<Point>,
+    size: Cell<Size>,
+    min_size: Size,
+    /// Whether the size changed without the handler being told yet.
+    size_changed: Cell<bool>,
+    scale: Cell<Scale>,
+    state: Cell<window::WindowState>,
+    visible: Cell<bool>,
+    /// Whether the window is to be destroyed the next time the event loop gets to it.
+    closing: Cell<bool>,
+    invalid: RefCell<Region>,
+    /// Whether the handler asked for an animation frame since the last paint.
+    anim_frame_requested: Cell<bool>,
+    /// Paces animation frames to the refresh rate of the pretend monitor.
+    frame_throttle: FrameThrottle,
+    timer_queue: RefCell<TimerQueue<()>>,
+    idle_queue: Arc<Mutex<Vec<IdleKind>>>,
+    active_text_field: Cell<Option<TextFieldToken>>,
+    /// Whether key repeats are passed on to the handler.
+    key_repeat: Cell<bool>,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
+    click_counter: ClickCounter,
+    /// The mouse buttons that injected events left pressed.
+    buttons: Cell<MouseButtons>,
+    /// The modifiers of the last injected key event.
+    mods: Cell<Modifiers>,
+    throttle: Throttle,
+}
+
+/// How the event loop holds back the window's timers and frames, to imitate a busy system
+/// or a stalled compositor.
+#[derive(Default)]
+pub(crate) struct Throttle {
+    /// How long after their deadlines timers fire.
+    pub(crate) timer_delay: Cell<Duration>,
+    /// Whether timers that are due together fire latest deadline first.
+    pub(crate) reverse_timers: Cell<bool>,
+    /// How many timers to drop without telling the handler.
+    pub(crate) dropped_timers: Cell<u32>,
+    /// Nothing is painted until then.
+    pub(crate) stalled_until: Cell<Option<Instant>>,
+    /// How many animation frames to drop without painting.
+    pub(crate) dropped_frames: Cell<u32>,
+}
+
+impl Throttle {
+    fn stalled(&self, now: Instant) -> bool {
+        self.stalled_until.get().is_some_and(|until| until > now)
+    }
+
+    /// Returns `true` if the next timer or frame is dropped.
+    fn take_drop(count: &Cell<u32>) -> bool {
+        let dropped = count.get() > 0;
+        count.set(count.get().saturating_sub(1));
+        dropped
+    }
+}
+
+impl Window {
+    #[track_caller]
+    fn with_handler<T, F: FnOnce(&mut dyn WinHandler) -> T>(&self, f: F) -> Option<T> {
+        match self.handler.try_borrow_mut() {
+            Ok(mut h) => Some(f(&mut **h)),
+            Err(_) => {
+                error!("failed to borrow WinHandler at {}", Location::caller());
+                None
+            }
+        }
+    }
+
+    fn connect(&self, handle: WindowHandle) {
+        let scale = self.scale.get();
+        let size = self.size.get();
+        self.with_handler(|h| {
+            h.connect(&handle.into());
+            h.scale(scale);
+            h.size(size);
+        });
+    }
+
+    fn wake(&self) {
+        self.app.wakeup().wake();
+    }
+
+    /// Runs the timers, idle callbacks and paint that are due.
+    pub(crate) fn dispatch(&self, now: Instant) {
+        if self.closing.get() {
+            self.destroy();
+            return;
+        }
+        if self.size_changed.replace(false) {
+            let size = self.size.get();
+            self.with_handler(|h| h.size(size));
+        }
+        self.run_timers(now);
+        self.run_idle();
+        if self.paint_due(now) {
+            if self.anim_frame_requested.get() && Throttle::take_drop(&self.throttle.dropped_frames)
+            {
+                // The frame never comes, so the next one is an interval later.
+                self.frame_throttle.frame_started();
+            } else {
+                self.render();
+            }
+        }
+    }
+
+    /// When [`dispatch`] next has something to do, if it is waiting for anything.
+    ///
+    /// [`dispatch`]: Window::dispatch
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        let paint = if !self.visible.get() {
+            None
+        } else if self.anim_frame_requested.get() {
+            Some(
+                self.frame_throttle
+                    .next_frame_deadline()
+                    .unwrap_or_else(Instant::now),
+            )
+        } else if !self.invalid.borrow().is_empty() {
+            Some(Instant::now())
+        } else {
+            None
+        };
+        // A stalled window paints nothing until the stall is over.
+        let paint = paint.map(|at| at.max(self.throttle.stalled_until.get().unwrap_or(at)));
+        let delay = self.throttle.timer_delay.get();
+        let timers = self.timer_queue.borrow().next_wakeup().map(|at| at + delay);
+        paint.into_iter().chain(timers).min()
+    }
+
+    fn run_timers(&self, now: Instant) {
+        let due = match now.checked_sub(self.throttle.timer_delay.get()) {
+            Some(due) => due,
+            None => return,
+        };
+        let mut tokens = Vec::new();
+        while let Some(timer) = self.timer_queue.borrow_mut().pop_expired(due) {
+            if !Throttle::take_drop(&self.throttle.dropped_timers) {
+                tokens.push(timer.token());
+            }
+        }
+        // Timers only promise not to fire early, so the ones that are late can go in any order.
+        if self.throttle.reverse_timers.get() {
+            tokens.reverse();
+        }
+        for token in tokens {
+            self.with_handler(|h| h.timer(token));
+        }
+    }
+
+    fn run_idle(&self) {
+        let queue = std::mem::take(&mut *self.idle_queue.lock().unwrap());
+        self.with_handler(|handler| {
+            for callback in queue {
+                match callback {
+                    IdleKind::Callback(f) => f.call(handler),
+                    IdleKind::Token(tok) => handler.idle(tok),
+                }
+            }
+        });
+    }
+
+    fn paint_due(&self, now: Instant) -> bool {
+        if !self.visible.get() || self.throttle.stalled(now) {
+            false
+        } else if self.anim_frame_requested.get() {
+            self.frame_throttle.next_frame_deadline().is_none()
+        } else {
+            !self.invalid.borrow().is_empty()
+        }
+    }
+
+    fn render(&self) {
+        self.frame_throttle.frame_started();
+        // Animation frames requested from here on are for the next frame.
+        let reason = if self.anim_frame_requested.replace(false) {
+            PaintReason::Animation
+        } else {
+            PaintReason::Damage
+        };
+        let missed = self.frame_throttle.missed_frames(reason);
+        if missed > 0 {
+            self.with_handler(|h| h.frame_missed(missed));
+        }
+        let info = self.frame_throttle.frame_info();
+        self.with_handler(|h| h.prepare_frame(info));
+        self.with_handler(|h| h.prepare_paint());
+        let invalid = std::mem::replace(&mut *self.invalid.borrow_mut(), Region::EMPTY);
+        self.with_handler(|h| h.paint(&invalid, reason));
+    }
+
+    fn invalidate_rect(&self, rect: Rect) {
+        self.invalid.borrow_mut().add_rect(rect);
+        self.wake();
+    }
+
+    fn invalidate(&self) {
+        self.invalidate_rect(self.size.get().to_rect());
+    }
+
+    /// Changes the size, and returns the new size if it is different from the old one.
+    fn update_size(&self, size: Size) -> Option<Size> {
+        let size = size.clamp(self.min_size, Size::new(f64::INFINITY, f64::INFINITY));
+        if self.size.replace(size) == size {
+            return None;
+        }
+        self.invalidate();
+        Some(size)
+    }
+
+    /// Resizes the window on the handler's request, which it hears about from the event loop,
+    /// like it would from a window manager.
+    fn set_size(&self, size: Size) {
+        if self.update_size(size).is_some() {
+            self.size_changed.set(true);
+        }
+    }
+
+    fn resize(&self, size: Size) {
+        if let Some(size) = self.update_size(size) {
+            self.size_changed.set(false);
+            self.with_handler(|h| h.size(size));
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        self.closing.set(true);
+        self.wake();
+    }
+
+    fn destroy(&self) {
+        self.with_handler(|h| h.destroy());
+        self.app.remove_window(self);
+    }
+
+    fn mouse_event(&self, pos: Point, button: MouseButton, count: u8) -> MouseEvent {
+        MouseEvent {
+            pos,
+            buttons: self.buttons.get(),
+            mods: self.mods.get(),
+            count,
+            focus: false,
+            button,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: InputSource::Injected,
+            coalesced: Vec::new(),
+        }
+    }
+
+    fn mouse_move(&self, pos: Point) {
+        let event = self.mouse_event(pos, MouseButton::None, 0);
+        self.with_handler(|h| h.mouse_move(&event));
+    }
+
+    fn mouse_down(&self, pos: Point, button: MouseButton) {
+        self.buttons.set(self.buttons.get().with(button));
+        let count = self.click_counter.count_for_click(pos);
+        let event = self.mouse_event(pos, button, count);
+        self.with_handler(|h| h.mouse_down(&event));
+    }
+
+    fn mouse_up(&self, pos: Point, button: MouseButton) {
+        self.buttons.set(self.buttons.get().without(button));
+        let event = self.mouse_event(pos, button, 0);
+        self.with_handler(|h| h.mouse_up(&event));
+    }
+
+    fn wheel(&self, pos: Point, delta: ScrollDelta) {
+        let event = MouseEvent {
+            wheel_delta: delta,
+            ..self.mouse_event(pos, MouseButton::None, 0)
+        };
+        self.with_handler(|h| h.wheel(&event));
+    }
+
+    fn key_down(&self, event: KeyEvent) -> bool {
+        self.mods.set(event.mods);
+        if event.repeat && !self.key_repeat.get() {
+            return false;
+        }
+        let focus = FocusDirection::from_key_event(&event);
+        let text_field = self.active_text_field.get();
+        self.with_handler(|h| {
+            let handled = simulate_input(h, text_field, event);
+            match (handled, focus) {
+                (false, Some(direction)) => h.focus_navigation(direction),
+                _ => handled,
+            }
+        })
+        .unwrap_or(false)
+    }
+
+    fn key_up(&self, event: KeyEvent) {
+        self.mods.set(event.mods);
+        self.with_handler(|h| h.key_up(event));
+    }
+
+    fn set_scale(&self, scale: Scale) {
+        if self.scale.replace(scale) != scale {
+            self.invalidate();
+            self.with_handler(|h| h.scale(scale));
+        }
+    }
+}
+
+// There's no pointer to show a custom cursor on.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CustomCursor;
+
+/// A handle that can get used to schedule an idle handler. Note that
+/// this handle can be cloned and sent between threads.
+#[derive(Clone)]
+pub struct IdleHandle {
+    queue: Arc<Mutex<Vec<IdleKind>>>,
+    wakeup: Arc<Wakeup>,
+}
+
+enum IdleKind {
+    Callback(Box<dyn IdleCallback>),
+    Token(IdleToken),
+}
+
+impl IdleHandle {
+    pub fn add_idle_callback<F>(&self, callback: F)
+    where
+        F: FnOnce(&mut dyn WinHandler) + Send + 'static,
+    {
+        self.add_idle(IdleKind::Callback(Box::new(callback)));
+    }
+
+    pub fn add_idle_token(&self, token: IdleToken) {
+        self.add_idle(IdleKind::Token(token));
+    }
+
+    fn add_idle(&self, idle: IdleKind) {
+        self.queue.lock().unwrap().push(idle);
+        self.wakeup.wake();
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct WindowHandle(Weak<Window>);
+
+impl PartialEq for WindowHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for WindowHandle {}
+
+impl WindowHandle {
+    pub fn show(&self) {
+        if let Some(w) = self.0.upgrade() {
+            if !w.visible.replace(true) {
+                w.invalidate();
+            }
+        }
+    }
+
+    pub fn close(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.close();
+        }
+    }
+
+    pub fn resizable(&self, _resizable: bool) {
+        // Only injected resizes happen anyway.
+    }
+
+    pub fn show_titlebar(&self, _show_titlebar: bool) {
+        // There are no decorations.
+    }
+
+    pub fn set_position(&self, position: Point) {
+        if let Some(w) = self.0.upgrade() {
+            w.position.set(position);
+        }
+    }
+
+    pub fn get_position(&self) -> Point {
+        self.0
+            .upgrade()
+            .map(|w| w.position.get())
+            .unwrap_or_default()
+    }
+
+    pub fn monitor(&self) -> Option<Monitor> {
+        // There's only the one.
+        screen::get_monitors().into_iter().next()
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        self.get_position() + point.to_vec2()
+    }
+
+    pub fn content_insets(&self) -> Insets {
+        Insets::ZERO
+    }
+
+    pub fn safe_area(&self) -> Insets {
+        Insets::ZERO
+    }
+
+    pub fn set_size(&self, size: Size) {
+        if let Some(w) = self.0.upgrade() {
+            w.set_size(size);
+        }
+    }
+
+    pub fn get_size(&self) -> Size {
+        self.0.upgrade().map(|w| w.size.get()).unwrap_or_default()
+    }
+
+    pub fn set_window_state(&self, state: window::WindowState) {
+        if let Some(w) = self.0.upgrade() {
+            w.state.set(state);
+        }
+    }
+
+    pub fn get_window_state(&self) -> window::WindowState {
+        self.0
+            .upgrade()
+            .map(|w| w.state.get())
+            .unwrap_or(window::WindowState::Restored)
+    }
+
+    pub fn set_fullscreen(&self, _options: Option<FullscreenOptions>) {
+        // There's no screen to fill.
+    }
+
+    pub fn preferred_output(&self, _monitor: Option<MonitorId>) {
+        // There's only one monitor.
+    }
+
+    pub(crate) fn set_kiosk(&self, _options: KioskOptions) {
+        // There's nothing to lock down.
+    }
+
+    pub fn handle_titlebar(&self, _val: bool) {
+        // There are no decorations.
+    }
+
+    pub fn bring_to_front_and_focus(&self) {
+        // Focus changes are injected.
+    }
+
+    pub fn raise_above(&self, _other: &WindowHandle) {
+        // Windows don't overlap anything.
+    }
+
+    pub fn lower_below(&self, _other: &WindowHandle) {
+        // Windows don't overlap anything.
+    }
+
+    pub fn set_mouse_passthrough(&self, _passthrough: bool) {
+        // Pointer events are injected into a particular window anyway.
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.key_repeat.set(enabled);
+        }
+    }
+
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        // There's no input method to compose injected key events.
+    }
+
+    pub fn set_shape(&self, _shape: Option<BezPath>) {
+        // Pointer events are injected into a particular window anyway.
+    }
+
+    pub fn request_anim_frame(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.anim_frame_requested.set(true);
+        }
+    }
+
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        if let Some(w) = self.0.upgrade() {
+            // The monitor's refresh rate stays the limit.
+            let fps = fps.map_or(REFRESH_RATE as f32, |fps| fps.min(REFRESH_RATE as f32));
+            w.frame_throttle.set_max_frame_rate(Some(fps));
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / REFRESH_RATE)
+    }
+
+    pub fn invalidate(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.invalidate();
+        }
+    }
+
+    pub fn invalidate_rect(&self, rect: Rect) {
+        if let Some(w) = self.0.upgrade() {
+            w.invalidate_rect(rect);
+        }
+    }
+
+    pub fn scroll_region(&self, rect: Rect, delta: Vec2) {
+        if let Some(w) = self.0.upgrade() {
+            w.invalid.borrow_mut().scroll(rect, delta);
+            w.wake();
+        }
+    }
+
+    pub fn set_title(&self, _title: &str) {
+        // There's no title bar to show it in.
+    }
+
+    pub fn set_theme_override(&self, _theme: Option<Theme>) {
+        // There's no system theme to override.
+    }
+
+    pub fn set_menu(&self, _menu: Menu) {
+        // There's no menu bar to show it in.
+    }
+
+    pub fn add_text_field(&self) -> TextFieldToken {
+        TextFieldToken::next()
+    }
+
+    pub fn remove_text_field(&self, token: TextFieldToken) {
+        if let Some(w) = self.0.upgrade() {
+            if w.active_text_field.get() == Some(token) {
+                w.active_text_field.set(None);
+            }
+        }
+    }
+
+    pub fn set_focused_text_field(&self, active_field: Option<TextFieldToken>) {
+        if let Some(w) = self.0.upgrade() {
+            w.active_text_field.set(active_field);
+        }
+    }
+
+    pub fn update_text_field(&self, _token: TextFieldToken, _update: Event) {
+        // There's no input method to tell.
+    }
+
+    pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.request_timer_with_tolerance(deadline, Duration::ZERO)
+    }
+
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Instant,
+        tolerance: Duration,
+    ) -> TimerToken {
+        self.schedule_timer(Timer::with_tolerance(deadline, tolerance, ()))
+    }
+
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        self.schedule_timer(Timer::repeating(interval, ()))
+    }
+
+    fn schedule_timer(&self, timer: Timer<()>) -> TimerToken {
+        if let Some(w) = self.0.upgrade() {
+            w.timer_queue.borrow_mut().push(timer);
+            // The event loop may be waiting for a later deadline.
+            w.wake();
+            timer.token()
+        } else {
+            TimerToken::INVALID
+        }
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        if let Some(w) = self.0.upgrade() {
+            w.timer_queue.borrow_mut().cancel(token);
+        }
+    }
+
+    pub fn set_cursor(&mut self, _cursor: &Cursor) {
+        // There's no pointer to show it.
+    }
+
+    pub fn confine_pointer(&self, _region: Option<Rect>) {
+        // Injected pointer events go wherever they are sent.
+    }
+
+    pub fn lock_pointer(&self) {
+        // Injected pointer events go wherever they are sent.
+    }
+
+    pub fn unlock_pointer(&self) {
+        // Injected pointer events go wherever they are sent.
+    }
+
+    pub fn set_raw_pointer_input(&self, _enabled: bool) {
+        // There's no pointer device to read from.
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        if let Some(w) = self.0.upgrade() {
+            w.autoscroll.begin(origin);
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        if let Some(w) = self.0.upgrade() {
+            w.shortcut_capture.begin(callback);
+        }
+    }
+
+    pub fn make_cursor(&self, _desc: &CursorDesc) -> Option<Cursor> {
+        // Nothing will ever show it, but it works as far as the handler can tell.
+        Some(Cursor::Custom(CustomCursor))
+    }
+
+    pub fn start_drag(&self, _data: DragData, _image: Option<DragImage>) {
+        // There's nowhere to drop it.
+    }
+
+    pub fn set_drop_formats(&self, _formats: &[FormatId]) {
+        // Nothing gets dropped on a headless window.
+    }
+
+    pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
+        // There's nobody to choose a file.
+        None
+    }
+
+    pub fn save_as(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
+        // There's nobody to choose a file.
+        None
+    }
+
+    pub fn show_context_menu(&self, _menu: Menu, _pos: Point) {
+        // There's nobody to choose an item.
+    }
+
+    pub fn get_idle_handle(&self) -> Option<IdleHandle> {
+        self.0.upgrade().map(|w| IdleHandle {
+            queue: w.idle_queue.clone(),
+            wakeup: w.app.wakeup().clone(),
+        })
+    }
+
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        // Injected key events come with their keys already mapped.
+        None
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        Application::locale_info()
+    }
+
+    pub fn get_scale(&self) -> Result<Scale, ShellError> {
+        Ok(self
+            .0
+            .upgrade()
+            .ok_or(ShellError::WindowDropped)?
+            .scale
+            .get())
+    }
+
+    pub fn text_scale_factor(&self) -> f64 {
+        1.0
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        None
+    }
+
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        ScrollbarMetrics::default()
+    }
+
+    #[cfg(feature = "accesskit")]
+    pub fn update_accesskit_if_active(
+        &self,
+        _update_factory: impl FnOnce() -> accesskit::TreeUpdate,
+    ) {
+        // There are no assistive technologies to tell.
+    }
+
+    pub(crate) fn inject_mouse_move(&self, pos: Point) {
+        if let Some(w) = self.0.upgrade() {
+            w.mouse_move(pos);
+        }
+    }
+
+    pub(crate) fn inject_mouse_down(&self, pos: Point, button: MouseButton) {
+        if let Some(w) = self.0.upgrade() {
+            w.mouse_down(pos, button);
+        }
+    }
+
+    pub(crate) fn inject_mouse_up(&self, pos: Point, button: MouseButton) {
+        if let Some(w) = self.0.upgrade() {
+            w.mouse_up(pos, button);
+        }
+    }
+
+    pub(crate) fn inject_wheel(&self, pos: Point, delta: ScrollDelta) {
+        if let Some(w) = self.0.upgrade() {
+            w.wheel(pos, delta);
+        }
+    }
+
+    #[cfg(feature = "record")]
+    pub(crate) fn inject_mouse_event(
+        &self,
+        event: &MouseEvent,
+        callback: fn(&mut dyn WinHandler, &MouseEvent),
+    ) {
+        if let Some(w) = self.0.upgrade() {
+            w.buttons.set(event.buttons);
+            w.mods.set(event.mods);
+            w.with_handler(|h| callback(h, event));
+        }
+    }
+
+    pub(crate) fn inject_mouse_leave(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.with_handler(|h| h.mouse_leave());
+        }
+    }
+
+    pub(crate) fn inject_key_down(&self, event: KeyEvent) -> bool {
+        match self.0.upgrade() {
+            Some(w) => w.key_down(event),
+            None => false,
+        }
+    }
+
+    pub(crate) fn inject_key_up(&self, event: KeyEvent) {
+        if let Some(w) = self.0.upgrade() {
+            w.key_up(event);
+        }
+    }
+
+    pub(crate) fn inject_resize(&self, size: Size) {
+        if let Some(w) = self.0.upgrade() {
+            w.resize(size);
+        }
+    }
+
+    pub(crate) fn inject_scale(&self, scale: Scale) {
+        if let Some(w) = self.0.upgrade() {
+            w.set_scale(scale);
+        }
+    }
+
+    pub(crate) fn inject_focus(&self, focused: bool) {
+        if let Some(w) = self.0.upgrade() {
+            if focused {
+                w.with_handler(|h| h.got_focus());
+            } else {
+                w.with_handler(|h| h.lost_focus());
+            }
+        }
+    }
+
+    pub(crate) fn inject_close_request(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.with_handler(|h| h.request_close());
+        }
+    }
+
+    /// Changes how the event loop holds back the window's timers and frames.
+    pub(crate) fn throttle(&self, f: impl FnOnce(&Throttle)) {
+        if let Some(w) = self.0.upgrade() {
+            f(&w.throttle);
+            w.wake();
+        }
+    }
+
+    pub(crate) fn run_pending(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.dispatch(Instant::now());
+        }
+    }
+}
+
+unsafe impl HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        error!("Headless windows have no raw window handle.");
+        RawWindowHandle::Web(WebWindowHandle::empty())
+    }
+}
+
+unsafe impl HasRawDisplayHandle for WindowHandle {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        error!("Headless windows have no raw display handle.");
+        RawDisplayHandle::Web(WebDisplayHandle::empty())
+    }
+}
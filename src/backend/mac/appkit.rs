@@ -17,6 +17,7 @@
 #![allow(clippy::upper_case_acronyms, non_snake_case, non_upper_case_globals)]
 
 use bitflags::bitflags;
+use cocoa::appkit::CGFloat;
 use cocoa::base::id;
 use cocoa::foundation::NSRect;
 use objc::{class, msg_send, sel, sel_impl};
@@ -26,6 +27,15 @@ extern "C" {
     pub static NSRunLoopCommonModes: id;
 }
 
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct NSEdgeInsets {
+    pub top: CGFloat,
+    pub left: CGFloat,
+    pub bottom: CGFloat,
+    pub right: CGFloat,
+}
+
 bitflags! {
     pub struct NSTrackingAreaOptions: i32 {
         const MouseEnteredAndExited = 1;
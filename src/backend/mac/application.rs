@@ -16,36 +16,64 @@
 
 #![allow(non_upper_case_globals)]
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ffi::c_void;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicyRegular};
-use cocoa::base::{id, nil, NO, YES};
-use cocoa::foundation::{NSArray, NSAutoreleasePool};
+use anyhow::anyhow;
+use block::{Block, ConcreteBlock, RcBlock};
+use cocoa::appkit::{
+    NSApp, NSApplication, NSApplicationActivationPolicyRegular, NSEvent, NSEventType,
+};
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{NSArray, NSAutoreleasePool, NSString, NSUInteger};
 use lazy_static::lazy_static;
 use objc::declare::ClassDecl;
-use objc::runtime::{Class, Object, Sel};
+use objc::runtime::{Class, Object, Protocol, Sel};
 use objc::{class, msg_send, sel, sel_impl};
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, DispatchPolicy, SessionBlob};
+use crate::keyboard::KeyboardLayout;
+use crate::kurbo::Point;
+use crate::locale::LocaleInfo;
+use crate::mouse::{InputSource, MomentumPhase, MouseButton, MouseEvent, ScrollDelta};
+use crate::platform::mac::{GlobalPointerEvent, GlobalPointerMonitor};
+use crate::sensors::SensorKind;
+use crate::window::WindowHandle;
 
 use super::clipboard::Clipboard;
 use super::error::Error;
+use super::keyboard::{self, make_modifiers};
+use super::screen;
 use super::util;
+use super::window::{get_mouse_button, get_mouse_buttons};
 
 static APP_HANDLER_IVAR: &str = "druidAppHandler";
+static SESSION_KEY: &str = "druidSession";
+
+// Values of NSDateFormatterStyle.
+const NSDateFormatterNoStyle: NSUInteger = 0;
+const NSDateFormatterShortStyle: NSUInteger = 1;
 
 #[derive(Clone)]
 pub(crate) struct Application {
     ns_app: id,
     state: Rc<RefCell<State>>,
+    idle: Rc<IdleState>,
 }
 
 struct State {
     quitting: bool,
 }
 
+/// Tracks input so that we can tell the `AppHandler` when the application goes idle.
+struct IdleState {
+    timeout: Cell<Option<Duration>>,
+    last_input: Cell<Instant>,
+    is_idle: Cell<bool>,
+}
+
 impl Application {
     pub fn new() -> Result<Application, Error> {
         // macOS demands that we run not just on one thread,
@@ -55,8 +83,17 @@ impl Application {
             let _pool = NSAutoreleasePool::new(nil);
             let ns_app = NSApp();
             let state = Rc::new(RefCell::new(State { quitting: false }));
+            let idle = Rc::new(IdleState {
+                timeout: Cell::new(None),
+                last_input: Cell::new(Instant::now()),
+                is_idle: Cell::new(false),
+            });
 
-            Ok(Application { ns_app, state })
+            Ok(Application {
+                ns_app,
+                state,
+                idle,
+            })
         }
     }
 
@@ -65,15 +102,36 @@ impl Application {
             // Initialize the application delegate
             let delegate: id = msg_send![APP_DELEGATE.0, alloc];
             let () = msg_send![delegate, init];
-            let state = DelegateState { handler };
+            let state = DelegateState {
+                handler,
+                idle: self.idle.clone(),
+                pending_restores: Vec::new(),
+            };
             let state_ptr = Box::into_raw(Box::new(state));
             (*delegate).set_ivar(APP_HANDLER_IVAR, state_ptr as *mut c_void);
             let () = msg_send![self.ns_app, setDelegate: delegate];
 
+            // Watch all of our input, so that we can tell when the app goes idle.
+            let mask = INPUT_EVENTS
+                .iter()
+                .fold(0u64, |mask, &ty| mask | 1 << ty as u64);
+            let block = ConcreteBlock::new(move |nsevent: id| -> id {
+                input_received(delegate);
+                nsevent
+            });
+            let block = block.copy();
+            let monitor: id = msg_send![class!(NSEvent), addLocalMonitorForEventsMatchingMask: mask handler: &*block];
+            let monitor: id = msg_send![monitor, retain];
+            self.idle.last_input.set(Instant::now());
+            schedule_idle_check(delegate, Duration::ZERO);
+
             // Run the main app loop
             self.ns_app.run();
 
-            // Clean up the delegate
+            // Clean up the input monitor and the delegate
+            let () = msg_send![class!(NSEvent), removeMonitor: monitor];
+            let () = msg_send![monitor, release];
+            cancel_idle_check(delegate);
             let () = msg_send![self.ns_app, setDelegate: nil];
             drop(Box::from_raw(state_ptr)); // Causes it to drop & dealloc automatically
         }
@@ -105,6 +163,34 @@ impl Application {
         Clipboard
     }
 
+    pub fn enable_sensor(&self, _kind: SensorKind) -> bool {
+        tracing::warn!(
+            "Application::enable_sensor is currently unimplemented for the macOS backend."
+        );
+        false
+    }
+
+    pub fn disable_sensor(&self, _kind: SensorKind) {}
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.idle.timeout.set(timeout);
+        unsafe {
+            // If we're already running, check right away; the check reschedules itself.
+            let delegate: id = msg_send![self.ns_app, delegate];
+            if delegate != nil {
+                schedule_idle_check(delegate, Duration::ZERO);
+            }
+        }
+    }
+
+    pub fn set_dispatch_policy(&self, _policy: DispatchPolicy) {
+        // AppKit runs the event loop, and decides the order itself.
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        keyboard::keyboard_layouts()
+    }
+
     pub fn get_locale() -> String {
         unsafe {
             let nslocale_class = class!(NSLocale);
@@ -118,6 +204,36 @@ impl Application {
             locale
         }
     }
+
+    pub fn locale_info() -> LocaleInfo {
+        unsafe {
+            let locale: id = msg_send![class!(NSLocale), currentLocale];
+            let decimal: id = msg_send![locale, decimalSeparator];
+            let group: id = msg_send![locale, groupingSeparator];
+            // Formatters with a style pick up the formats that the user chose in the system
+            // settings.
+            let pattern = |date_style: NSUInteger, time_style: NSUInteger| {
+                let formatter: id = msg_send![class!(NSDateFormatter), new];
+                let () = msg_send![formatter, setDateStyle: date_style];
+                let () = msg_send![formatter, setTimeStyle: time_style];
+                let format: id = msg_send![formatter, dateFormat];
+                let format = util::from_nsstring(format);
+                let () = msg_send![formatter, release];
+                format
+            };
+            let mut info = LocaleInfo {
+                locale: Application::get_locale().replace('_', "-"),
+                decimal_separator: util::from_nsstring(decimal),
+                grouping_separator: util::from_nsstring(group),
+                ..LocaleInfo::default()
+            };
+            info.apply_patterns(
+                &pattern(NSDateFormatterShortStyle, NSDateFormatterNoStyle),
+                &pattern(NSDateFormatterNoStyle, NSDateFormatterShortStyle),
+            );
+            info
+        }
+    }
 }
 
 impl crate::platform::mac::ApplicationExt for crate::Application {
@@ -140,10 +256,130 @@ impl crate::platform::mac::ApplicationExt for crate::Application {
             NSApp().setMainMenu_(menu.0.menu);
         }
     }
+
+    fn add_global_pointer_monitor(
+        &self,
+        callback: Box<dyn FnMut(GlobalPointerEvent)>,
+    ) -> Result<GlobalPointerMonitor, crate::Error> {
+        unsafe {
+            if !accessibility_trusted_with_prompt() {
+                return Err(anyhow!("accessibility permissions have not been granted").into());
+            }
+
+            let mask = GLOBAL_POINTER_EVENTS
+                .iter()
+                .fold(0u64, |mask, &ty| mask | 1 << ty as u64);
+            let callback = RefCell::new(callback);
+            let block = ConcreteBlock::new(move |nsevent: id| {
+                if let Some(event) = global_pointer_event(nsevent) {
+                    (&mut *callback.borrow_mut())(event);
+                }
+            });
+            let block = block.copy();
+            let monitor: id = msg_send![class!(NSEvent), addGlobalMonitorForEventsMatchingMask: mask handler: &*block];
+            if monitor == nil {
+                return Err(anyhow!("failed to install the global event monitor").into());
+            }
+            let monitor: id = msg_send![monitor, retain];
+            Ok(GlobalPointerMonitor::new(move || {
+                let () = msg_send![class!(NSEvent), removeMonitor: monitor];
+                let () = msg_send![monitor, release];
+            }))
+        }
+    }
+}
+
+const GLOBAL_POINTER_EVENTS: [NSEventType; 10] = [
+    NSEventType::NSLeftMouseDown,
+    NSEventType::NSLeftMouseUp,
+    NSEventType::NSRightMouseDown,
+    NSEventType::NSRightMouseUp,
+    NSEventType::NSOtherMouseDown,
+    NSEventType::NSOtherMouseUp,
+    NSEventType::NSMouseMoved,
+    NSEventType::NSLeftMouseDragged,
+    NSEventType::NSRightMouseDragged,
+    NSEventType::NSOtherMouseDragged,
+];
+
+/// The events that count as input for the purposes of idle detection.
+const INPUT_EVENTS: [NSEventType; 14] = [
+    NSEventType::NSLeftMouseDown,
+    NSEventType::NSLeftMouseUp,
+    NSEventType::NSRightMouseDown,
+    NSEventType::NSRightMouseUp,
+    NSEventType::NSOtherMouseDown,
+    NSEventType::NSOtherMouseUp,
+    NSEventType::NSMouseMoved,
+    NSEventType::NSLeftMouseDragged,
+    NSEventType::NSRightMouseDragged,
+    NSEventType::NSOtherMouseDragged,
+    NSEventType::NSScrollWheel,
+    NSEventType::NSKeyDown,
+    NSEventType::NSKeyUp,
+    NSEventType::NSFlagsChanged,
+];
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    static kAXTrustedCheckOptionPrompt: id;
+    fn AXIsProcessTrustedWithOptions(options: id) -> u8;
+}
+
+/// Checks whether we have accessibility permissions, asking the user for them if we don't.
+unsafe fn accessibility_trusted_with_prompt() -> bool {
+    let yes: id = msg_send![class!(NSNumber), numberWithBool: YES];
+    let options: id = msg_send![class!(NSDictionary), dictionaryWithObject: yes forKey: kAXTrustedCheckOptionPrompt];
+    AXIsProcessTrustedWithOptions(options) != 0
+}
+
+unsafe fn global_pointer_event(nsevent: id) -> Option<GlobalPointerEvent> {
+    // For events without a window, locationInWindow is in screen coordinates
+    // with the origin at the bottom left of the primary screen.
+    let location = nsevent.locationInWindow();
+    let pos = Point::new(location.x, screen::primary_screen_height() - location.y);
+    let event = MouseEvent {
+        pos,
+        buttons: get_mouse_buttons(NSEvent::pressedMouseButtons(nsevent)),
+        mods: make_modifiers(nsevent.modifierFlags()),
+        count: 0,
+        focus: false,
+        button: get_mouse_button(nsevent.buttonNumber()).unwrap_or(MouseButton::None),
+        wheel_delta: ScrollDelta::ZERO,
+        momentum_phase: MomentumPhase::None,
+        source: InputSource::Hardware,
+        coalesced: Vec::new(),
+    };
+    match nsevent.eventType() {
+        NSEventType::NSLeftMouseDown
+        | NSEventType::NSRightMouseDown
+        | NSEventType::NSOtherMouseDown => Some(GlobalPointerEvent::Down(MouseEvent {
+            count: nsevent.clickCount() as u8,
+            ..event
+        })),
+        NSEventType::NSLeftMouseUp | NSEventType::NSRightMouseUp | NSEventType::NSOtherMouseUp => {
+            Some(GlobalPointerEvent::Up(event))
+        }
+        NSEventType::NSMouseMoved
+        | NSEventType::NSLeftMouseDragged
+        | NSEventType::NSRightMouseDragged
+        | NSEventType::NSOtherMouseDragged => Some(GlobalPointerEvent::Move(MouseEvent {
+            button: MouseButton::None,
+            ..event
+        })),
+        _ => None,
+    }
 }
 
+/// The completion handler of `restoreWindowWithIdentifier:state:completionHandler:`, which
+/// takes the restored window and an error.
+type RestoreCompletion = RcBlock<(id, id), ()>;
+
 struct DelegateState {
     handler: Option<Box<dyn AppHandler>>,
+    idle: Rc<IdleState>,
+    /// The windows that AppKit asked us to restore while the app was launching.
+    pending_restores: Vec<(String, RestoreCompletion)>,
 }
 
 impl DelegateState {
@@ -152,6 +388,51 @@ impl DelegateState {
             inner.command(command)
         }
     }
+
+    fn idle(&mut self, since_last_input: Duration) {
+        if let Some(inner) = self.handler.as_mut() {
+            inner.idle(since_last_input)
+        }
+    }
+
+    fn idle_ended(&mut self) {
+        if let Some(inner) = self.handler.as_mut() {
+            inner.idle_ended()
+        }
+    }
+
+    fn save_session(&mut self) -> SessionBlob {
+        self.handler
+            .as_mut()
+            .map(|inner| inner.save_session())
+            .unwrap_or_default()
+    }
+
+    fn restore_session(&mut self, session: SessionBlob) {
+        if let Some(inner) = self.handler.as_mut() {
+            inner.restore_session(session)
+        }
+    }
+
+    fn monitors_changed(&mut self) {
+        if let Some(inner) = self.handler.as_mut() {
+            inner.monitors_changed()
+        }
+    }
+
+    /// Asks the handler to rebuild the windows that AppKit wants to restore.
+    fn restore_windows(&mut self) {
+        for (restoration_id, completion) in std::mem::take(&mut self.pending_restores) {
+            let window = self
+                .handler
+                .as_mut()
+                .and_then(|inner| inner.restore_window(&restoration_id))
+                .map(|handle: WindowHandle| handle.0.ns_window())
+                .unwrap_or(nil);
+            // A nil window tells AppKit to forget about this one.
+            unsafe { completion.call((window, nil)) };
+        }
+    }
 }
 
 struct AppDelegate(*const Class);
@@ -173,17 +454,213 @@ lazy_static! {
             sel!(handleMenuItem:),
             handle_menu_item as extern "C" fn(&mut Object, Sel, id),
         );
+
+        decl.add_method(
+            sel!(checkIdle:),
+            check_idle as extern "C" fn(&mut Object, Sel, id),
+        );
+
+        decl.add_method(
+            sel!(applicationDidResignActive:),
+            application_did_resign_active as extern "C" fn(&mut Object, Sel, id),
+        );
+
+        decl.add_method(
+            sel!(applicationDidChangeScreenParameters:),
+            application_did_change_screen_parameters as extern "C" fn(&mut Object, Sel, id),
+        );
+
+        decl.add_method(
+            sel!(applicationSupportsSecureRestorableState:),
+            application_supports_secure_restorable_state as extern "C" fn(&Object, Sel, id) -> BOOL,
+        );
+
+        decl.add_method(
+            sel!(application:willEncodeRestorableState:),
+            application_will_encode_restorable_state as extern "C" fn(&mut Object, Sel, id, id),
+        );
+
+        decl.add_method(
+            sel!(application:didDecodeRestorableState:),
+            application_did_decode_restorable_state as extern "C" fn(&mut Object, Sel, id, id),
+        );
         AppDelegate(decl.register())
     };
 }
 
-extern "C" fn application_did_finish_launching(_this: &mut Object, _: Sel, _notification: id) {
+struct WindowRestoration(*const Class);
+unsafe impl Sync for WindowRestoration {}
+unsafe impl Send for WindowRestoration {}
+
+lazy_static! {
+    static ref WINDOW_RESTORATION: WindowRestoration = unsafe {
+        let mut decl = ClassDecl::new("DruidWindowRestoration", class!(NSObject))
+            .expect("Window restoration class definition failed");
+        if let Some(protocol) = Protocol::get("NSWindowRestoration") {
+            decl.add_protocol(protocol);
+        }
+        decl.add_class_method(
+            sel!(restoreWindowWithIdentifier:state:completionHandler:),
+            restore_window_with_identifier
+                as extern "C" fn(&Class, Sel, id, id, *mut Block<(id, id), ()>),
+        );
+        WindowRestoration(decl.register())
+    };
+}
+
+/// The class that AppKit asks to recreate restorable windows on relaunch.
+pub(crate) fn window_restoration_class() -> *const Class {
+    WINDOW_RESTORATION.0
+}
+
+extern "C" fn application_did_finish_launching(this: &mut Object, _: Sel, _notification: id) {
     unsafe {
+        // AppKit has asked for all the windows it restores, and decoded our state, by now.
+        let inner: *mut c_void = *this.get_ivar(APP_HANDLER_IVAR);
+        let inner = &mut *(inner as *mut DelegateState);
+        inner.restore_windows();
+
         let ns_app = NSApp();
         // We need to delay setting the activation policy and activating the app
         // until we have the main menu all set up. Otherwise the menu won't be interactable.
         ns_app.setActivationPolicy_(NSApplicationActivationPolicyRegular);
         let () = msg_send![ns_app, activateIgnoringOtherApps: YES];
+        // Make sure AppKit asks us for our state at least once.
+        let () = msg_send![ns_app, invalidateRestorableState];
+    }
+}
+
+extern "C" fn application_did_resign_active(_this: &mut Object, _: Sel, _notification: id) {
+    unsafe {
+        // The app is most likely to be quit (or updated) while in the background, so
+        // this is a good time to ask AppKit to save a fresh copy of our state.
+        let () = msg_send![NSApp(), invalidateRestorableState];
+    }
+}
+
+extern "C" fn application_supports_secure_restorable_state(
+    _this: &Object,
+    _: Sel,
+    _app: id,
+) -> BOOL {
+    YES
+}
+
+extern "C" fn application_will_encode_restorable_state(
+    this: &mut Object,
+    _: Sel,
+    _app: id,
+    coder: id,
+) {
+    unsafe {
+        let inner: *mut c_void = *this.get_ivar(APP_HANDLER_IVAR);
+        let inner = &mut *(inner as *mut DelegateState);
+        let session = inner.save_session();
+        if session.is_empty() {
+            return;
+        }
+        let bytes = session.as_bytes();
+        let data: id = msg_send![class!(NSData), dataWithBytes: bytes.as_ptr() as *const c_void length: bytes.len()];
+        let key = NSString::alloc(nil).init_str(SESSION_KEY).autorelease();
+        let () = msg_send![coder, encodeObject: data forKey: key];
+    }
+}
+
+extern "C" fn application_did_decode_restorable_state(
+    this: &mut Object,
+    _: Sel,
+    _app: id,
+    coder: id,
+) {
+    unsafe {
+        let key = NSString::alloc(nil).init_str(SESSION_KEY).autorelease();
+        let data: id = msg_send![coder, decodeObjectOfClass: class!(NSData) forKey: key];
+        if data == nil {
+            return;
+        }
+        let bytes: *const u8 = msg_send![data, bytes];
+        let len: usize = msg_send![data, length];
+        let session = SessionBlob::new(std::slice::from_raw_parts(bytes, len));
+        let inner: *mut c_void = *this.get_ivar(APP_HANDLER_IVAR);
+        let inner = &mut *(inner as *mut DelegateState);
+        inner.restore_session(session);
+    }
+}
+
+extern "C" fn restore_window_with_identifier(
+    _this: &Class,
+    _: Sel,
+    identifier: id,
+    _state: id,
+    completion: *mut Block<(id, id), ()>,
+) {
+    unsafe {
+        let delegate: id = msg_send![NSApp(), delegate];
+        if delegate.is_null() {
+            (*completion).call((nil, nil));
+            return;
+        }
+        // The handler's state may not have been restored yet, so the window is rebuilt once
+        // the app has finished launching. AppKit lets the completion handler be called later.
+        let inner: *mut c_void = *(*delegate).get_ivar(APP_HANDLER_IVAR);
+        let inner = &mut *(inner as *mut DelegateState);
+        let restoration_id = util::from_nsstring(identifier);
+        inner
+            .pending_restores
+            .push((restoration_id, RcBlock::copy(completion)));
+    }
+}
+
+/// Runs `checkIdle:` on the delegate after `delay`, replacing any check that is already pending.
+unsafe fn schedule_idle_check(delegate: id, delay: Duration) {
+    cancel_idle_check(delegate);
+    let () = msg_send![delegate, performSelector: sel!(checkIdle:) withObject: nil afterDelay: delay.as_secs_f64()];
+}
+
+unsafe fn cancel_idle_check(delegate: id) {
+    let () = msg_send![class!(NSObject), cancelPreviousPerformRequestsWithTarget: delegate selector: sel!(checkIdle:) object: nil];
+}
+
+extern "C" fn check_idle(this: &mut Object, _: Sel, _: id) {
+    unsafe {
+        let inner: *mut c_void = *this.get_ivar(APP_HANDLER_IVAR);
+        let inner = &mut *(inner as *mut DelegateState);
+        let timeout = match inner.idle.timeout.get() {
+            Some(timeout) if !inner.idle.is_idle.get() => timeout,
+            _ => return,
+        };
+        let since_last_input = inner.idle.last_input.get().elapsed();
+        if since_last_input >= timeout {
+            inner.idle.is_idle.set(true);
+            inner.idle(since_last_input);
+        } else {
+            schedule_idle_check(this, timeout - since_last_input);
+        }
+    }
+}
+
+/// Called from the local event monitor for every input event.
+unsafe fn input_received(delegate: id) {
+    let inner: *mut c_void = *(*delegate).get_ivar(APP_HANDLER_IVAR);
+    let inner = &mut *(inner as *mut DelegateState);
+    inner.idle.last_input.set(Instant::now());
+    if inner.idle.is_idle.replace(false) {
+        if let Some(timeout) = inner.idle.timeout.get() {
+            schedule_idle_check(delegate, timeout);
+        }
+        inner.idle_ended();
+    }
+}
+
+extern "C" fn application_did_change_screen_parameters(
+    this: &mut Object,
+    _: Sel,
+    _notification: id,
+) {
+    unsafe {
+        let inner: *mut c_void = *this.get_ivar(APP_HANDLER_IVAR);
+        let inner = &mut *(inner as *mut DelegateState);
+        inner.monitors_changed();
     }
 }
 
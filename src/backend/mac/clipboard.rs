@@ -127,6 +127,14 @@ impl Clipboard {
         }
     }
 
+    pub fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        // The pasteboard doesn't tell us how big the data is without handing it over.
+        self.available_type_names()
+            .into_iter()
+            .map(|name| (name, None))
+            .collect()
+    }
+
     pub fn available_type_names(&self) -> Vec<String> {
         unsafe {
             let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
@@ -0,0 +1,269 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drag and drop, with the view as the `NSDraggingDestination` and `NSDraggingSource`.
+
+use cocoa::appkit::{NSApp, NSPasteboardTypeString};
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{NSArray, NSInteger, NSPoint, NSRect, NSSize, NSUInteger};
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::dnd::{DragData, DragImage, DropEffect};
+use crate::kurbo::Point;
+
+use super::util::{from_nsdata, from_nsstring, make_nsdata, make_nsstring};
+use super::window::with_drag_from_window;
+
+// The NSDragOperation values we use.
+const DRAG_OPERATION_NONE: NSUInteger = 0;
+const DRAG_OPERATION_COPY: NSUInteger = 1;
+const DRAG_OPERATION_LINK: NSUInteger = 2;
+const DRAG_OPERATION_MOVE: NSUInteger = 16;
+
+const FILE_URL: &str = "public.file-url";
+const URL: &str = "public.url";
+
+/// The drag and drop state of a view.
+#[derive(Default)]
+pub(super) struct DragState {
+    /// The custom formats to read from drags.
+    pub(super) formats: Vec<FormatId>,
+    /// The data of the drag over the view.
+    pub(super) data: Option<DragData>,
+}
+
+/// Sets the pasteboard types that `view` accepts drags of: text, URLs and `formats`.
+pub(super) fn register_types(view: id, formats: &[FormatId]) {
+    unsafe {
+        let types = [
+            NSPasteboardTypeString,
+            make_nsstring(FILE_URL),
+            make_nsstring(URL),
+        ]
+        .into_iter()
+        .chain(formats.iter().map(|format| make_nsstring(format)))
+        .collect::<Vec<_>>();
+        let array = NSArray::arrayWithObjects(nil, &types);
+        let () = msg_send![view, registerForDraggedTypes: array];
+    }
+}
+
+pub(super) extern "C" fn dragging_entered(this: &mut Object, _: Sel, info: id) -> NSUInteger {
+    let pos = position(this, info);
+    with_drag_from_window(this, |handler, state| {
+        let data = unsafe { read(info, &state.formats) };
+        let effect = handler.drag_enter(pos, &data);
+        state.data = Some(data);
+        operation(effect, info)
+    })
+}
+
+pub(super) extern "C" fn dragging_updated(this: &mut Object, _: Sel, info: id) -> NSUInteger {
+    let pos = position(this, info);
+    with_drag_from_window(this, |handler, state| match &state.data {
+        Some(data) => operation(handler.drag_over(pos, data), info),
+        None => DRAG_OPERATION_NONE,
+    })
+}
+
+pub(super) extern "C" fn dragging_exited(this: &mut Object, _: Sel, _info: id) {
+    with_drag_from_window(this, |handler, state| {
+        if state.data.take().is_some() {
+            handler.drag_leave();
+        }
+    })
+}
+
+pub(super) extern "C" fn perform_drag_operation(this: &mut Object, _: Sel, info: id) -> BOOL {
+    let pos = position(this, info);
+    with_drag_from_window(this, |handler, state| {
+        let effect = match state.data.take() {
+            Some(data) => operation(handler.drag_drop(pos, &data), info),
+            None => DRAG_OPERATION_NONE,
+        };
+        if effect == DRAG_OPERATION_NONE {
+            NO
+        } else {
+            YES
+        }
+    })
+}
+
+pub(super) extern "C" fn source_operation_mask(
+    _this: &mut Object,
+    _: Sel,
+    _session: id,
+    _context: NSInteger,
+) -> NSUInteger {
+    DRAG_OPERATION_COPY
+}
+
+/// Starts dragging `data` out of `view`.
+///
+/// This has to be called while handling a mouse event, which becomes the start of the drag.
+pub(super) fn start_drag(view: id, data: &DragData, image: Option<&DragImage>) {
+    unsafe {
+        let event: id = msg_send![NSApp(), currentEvent];
+        if event.is_null() {
+            tracing::warn!("a drag can only start from a mouse event");
+            return;
+        }
+        let new_item = || -> id { msg_send![class!(NSPasteboardItem), new] };
+        let first = new_item();
+        if let Some(text) = &data.text {
+            let _: BOOL =
+                msg_send![first, setString: make_nsstring(text) forType: NSPasteboardTypeString];
+        }
+        for format in &data.formats {
+            let bytes = make_nsdata(&format.data);
+            let kind = make_nsstring(format.identifier);
+            let _: BOOL = msg_send![first, setData: bytes forType: kind];
+        }
+        // Each URL needs an item of its own.
+        let mut items = vec![first];
+        for (i, uri) in data.uris.iter().enumerate() {
+            let item = match i {
+                0 => first,
+                _ => {
+                    let item = new_item();
+                    items.push(item);
+                    item
+                }
+            };
+            let kind = if uri.starts_with("file:") {
+                FILE_URL
+            } else {
+                URL
+            };
+            let _: BOOL =
+                msg_send![item, setString: make_nsstring(uri) forType: make_nsstring(kind)];
+        }
+
+        let window: id = msg_send![view, window];
+        let scale: f64 = msg_send![window, backingScaleFactor];
+        let location: NSPoint = msg_send![event, locationInWindow];
+        let location: NSPoint = msg_send![view, convertPoint: location fromView: nil];
+        let (contents, frame) = match image.map(|image| {
+            let contents = make_image(image.width, image.height, &image.pixels, scale);
+            (contents, image)
+        }) {
+            Some((contents, image)) if !contents.is_null() => {
+                let size = NSSize::new(image.width as f64 / scale, image.height as f64 / scale);
+                let origin = NSPoint::new(
+                    location.x - image.hotspot.0 as f64 / scale,
+                    location.y - image.hotspot.1 as f64 / scale,
+                );
+                (contents, NSRect::new(origin, size))
+            }
+            _ => (nil, NSRect::new(location, NSSize::new(0.0, 0.0))),
+        };
+
+        let dragging_items = items
+            .into_iter()
+            .map(|item| {
+                let dragging_item: id = msg_send![class!(NSDraggingItem), alloc];
+                let dragging_item: id = msg_send![dragging_item, initWithPasteboardWriter: item];
+                let () = msg_send![item, release];
+                let () = msg_send![dragging_item, setDraggingFrame: frame contents: contents];
+                let _: id = msg_send![dragging_item, autorelease];
+                dragging_item
+            })
+            .collect::<Vec<_>>();
+        let array = NSArray::arrayWithObjects(nil, &dragging_items);
+        let _: id = msg_send![view, beginDraggingSessionWithItems: array event: event source: view];
+    }
+}
+
+/// The position of a drag, in the view's coordinates.
+fn position(view: &mut Object, info: id) -> Point {
+    unsafe {
+        let location: NSPoint = msg_send![info, draggingLocation];
+        let location: NSPoint = msg_send![view, convertPoint: location fromView: nil];
+        Point::new(location.x, location.y)
+    }
+}
+
+/// The drag operation for `effect`, if the source allows it.
+fn operation(effect: DropEffect, info: id) -> NSUInteger {
+    let operation = match effect {
+        DropEffect::None => DRAG_OPERATION_NONE,
+        DropEffect::Copy => DRAG_OPERATION_COPY,
+        DropEffect::Move => DRAG_OPERATION_MOVE,
+        DropEffect::Link => DRAG_OPERATION_LINK,
+    };
+    let allowed: NSUInteger = unsafe { msg_send![info, draggingSourceOperationMask] };
+    operation & allowed
+}
+
+unsafe fn read(info: id, formats: &[FormatId]) -> DragData {
+    let pasteboard: id = msg_send![info, draggingPasteboard];
+    let text: id = msg_send![pasteboard, stringForType: NSPasteboardTypeString];
+    let text = (!text.is_null()).then(|| from_nsstring(text));
+    let classes = NSArray::arrayWithObjects(nil, &[class!(NSURL) as *const Class as id]);
+    let urls: id = msg_send![pasteboard, readObjectsForClasses: classes options: nil];
+    let uris = if urls.is_null() {
+        Vec::new()
+    } else {
+        (0..urls.count())
+            .map(|i| from_nsstring(msg_send![urls.objectAtIndex(i), absoluteString]))
+            .collect()
+    };
+    let formats = formats
+        .iter()
+        .filter_map(|format| {
+            let data: id = msg_send![pasteboard, dataForType: make_nsstring(format)];
+            (!data.is_null()).then(|| ClipboardFormat::new(format, from_nsdata(data)))
+        })
+        .collect();
+    DragData {
+        text,
+        uris,
+        formats,
+    }
+}
+
+/// Makes an `NSImage` of premultiplied RGBA `pixels`, which are `scale` times smaller than
+/// points.
+pub(super) unsafe fn make_image(width: u32, height: u32, pixels: &[u8], scale: f64) -> id {
+    let (width, height) = (width as usize, height as usize);
+    if width == 0 || height == 0 || pixels.len() < width * height * 4 {
+        return nil;
+    }
+    // Without a bitmap format, the rep is premultiplied RGBA, like ours.
+    let rep: id = msg_send![class!(NSBitmapImageRep), alloc];
+    let rep: id = msg_send![rep,
+        initWithBitmapDataPlanes: std::ptr::null_mut::<*mut u8>()
+        pixelsWide: width as NSInteger
+        pixelsHigh: height as NSInteger
+        bitsPerSample: 8 as NSInteger
+        samplesPerPixel: 4 as NSInteger
+        hasAlpha: YES
+        isPlanar: NO
+        colorSpaceName: make_nsstring("NSDeviceRGBColorSpace")
+        bytesPerRow: (width * 4) as NSInteger
+        bitsPerPixel: 32 as NSInteger];
+    if rep.is_null() {
+        return nil;
+    }
+    let bits: *mut u8 = msg_send![rep, bitmapData];
+    std::ptr::copy_nonoverlapping(pixels.as_ptr(), bits, width * height * 4);
+    let size = NSSize::new(width as f64 / scale, height as f64 / scale);
+    let nsimage: id = msg_send![class!(NSImage), alloc];
+    let nsimage: id = msg_send![nsimage, initWithSize: size];
+    let () = msg_send![nsimage, addRepresentation: rep];
+    let () = msg_send![rep, release];
+    msg_send![nsimage, autorelease]
+}
@@ -15,10 +15,10 @@
 //! Conversion of backend keyboard event into cross-platform event.
 
 use cocoa::appkit::{NSEvent, NSEventModifierFlags, NSEventType};
-use cocoa::base::id;
-use objc::{msg_send, sel, sel_impl};
+use cocoa::base::{id, nil};
+use objc::{class, msg_send, sel, sel_impl};
 
-use crate::keyboard::{Code, KbKey, KeyEvent, KeyState, Modifiers};
+use crate::keyboard::{Code, KbKey, KeyEvent, KeyState, KeyboardLayout, Modifiers};
 
 use super::super::shared;
 use super::util::from_nsstring;
@@ -332,6 +332,7 @@ impl KeyboardState {
                 mods,
                 repeat,
                 is_composing,
+                xkb_keysym: None,
             };
             Some(event)
         }
@@ -349,6 +350,79 @@ const MODIFIER_MAP: &[(NSEventModifierFlags, Modifiers)] = &[
     ),
 ];
 
+// Text Input Source Services. The TIS types are CoreFoundation types, which are
+// toll-free bridged, so we treat them as objc objects.
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    static kTISPropertyInputSourceCategory: id;
+    static kTISPropertyInputSourceID: id;
+    static kTISPropertyInputSourceLanguages: id;
+    static kTISPropertyLocalizedName: id;
+    static kTISCategoryKeyboardInputSource: id;
+    fn TISCreateInputSourceList(properties: id, include_all_installed: u8) -> id;
+    fn TISCopyCurrentKeyboardInputSource() -> id;
+    fn TISGetInputSourceProperty(source: id, key: id) -> id;
+}
+
+unsafe fn layout_from_input_source(source: id) -> KeyboardLayout {
+    let string_property = |key| {
+        let value = TISGetInputSourceProperty(source, key);
+        if value.is_null() {
+            None
+        } else {
+            Some(from_nsstring(value))
+        }
+    };
+    let languages = TISGetInputSourceProperty(source, kTISPropertyInputSourceLanguages);
+    let language = if languages.is_null() {
+        None
+    } else {
+        let first: id = msg_send![languages, firstObject];
+        if first.is_null() {
+            None
+        } else {
+            Some(from_nsstring(first))
+        }
+    };
+    KeyboardLayout {
+        id: string_property(kTISPropertyInputSourceID).unwrap_or_default(),
+        name: string_property(kTISPropertyLocalizedName).unwrap_or_default(),
+        language,
+    }
+}
+
+/// The keyboard input sources that the user has enabled.
+pub(crate) fn keyboard_layouts() -> Vec<KeyboardLayout> {
+    unsafe {
+        let filter: id = msg_send![class!(NSDictionary),
+            dictionaryWithObject: kTISCategoryKeyboardInputSource
+            forKey: kTISPropertyInputSourceCategory];
+        let sources = TISCreateInputSourceList(filter, 0);
+        if sources == nil {
+            return Vec::new();
+        }
+        let count: usize = msg_send![sources, count];
+        let layouts = (0..count)
+            .map(|i| layout_from_input_source(msg_send![sources, objectAtIndex: i]))
+            .collect();
+        let () = msg_send![sources, release];
+        layouts
+    }
+}
+
+/// The keyboard input source that is currently selected.
+pub(crate) fn current_keyboard_layout() -> Option<KeyboardLayout> {
+    unsafe {
+        let source = TISCopyCurrentKeyboardInputSource();
+        if source == nil {
+            return None;
+        }
+        let layout = layout_from_input_source(source);
+        let () = msg_send![source, release];
+        Some(layout)
+    }
+}
+
 pub(crate) fn make_modifiers(raw: NSEventModifierFlags) -> Modifiers {
     let mut modifiers = Modifiers::empty();
     for &(flags, mods) in MODIFIER_MAP {
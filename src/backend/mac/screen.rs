@@ -15,16 +15,19 @@
 //! macOS Monitors and Screen information.
 
 use crate::kurbo::Rect;
-use crate::screen::Monitor;
+use crate::screen::{ColorShift, Monitor};
 use cocoa::appkit::NSScreen;
-use cocoa::base::id;
-use cocoa::foundation::NSArray;
+use cocoa::base::{id, nil, BOOL, NO};
+use cocoa::foundation::{NSArray, NSInteger};
 use objc::{class, msg_send, sel, sel_impl};
 
+use super::util::from_nsstring;
+
 pub(crate) fn get_monitors() -> Vec<Monitor> {
     unsafe {
         let screens: id = msg_send![class![NSScreen], screens];
         let mut monitors = Vec::<(Rect, Rect)>::new();
+        let mut details = Vec::new();
         let mut total_rect = Rect::ZERO;
 
         for idx in 0..screens.count() {
@@ -41,14 +44,62 @@ pub(crate) fn get_monitors() -> Vec<Monitor> {
                 (vis_frame.size.width, vis_frame.size.height),
             );
             monitors.push((frame_r, vis_frame_r));
+            details.push(screen_details(screen));
             total_rect = total_rect.union(frame_r)
         }
         // TODO save this total_rect.y1 for screen coord transformations in get_position/set_position
         // and invalidate on monitor changes
         transform_coords(monitors, total_rect.y1)
+            .into_iter()
+            .zip(details)
+            .map(|(monitor, (scale, refresh_rate, name))| {
+                monitor
+                    .with_scale(scale)
+                    .with_refresh_rate(refresh_rate)
+                    .with_name(name)
+            })
+            .collect()
+    }
+}
+
+/// The height of the primary screen, whose bottom left corner is the origin of Cocoa's
+/// screen coordinates.
+pub(crate) fn primary_screen_height() -> f64 {
+    unsafe {
+        let screens: id = msg_send![class![NSScreen], screens];
+        if screens.count() == 0 {
+            return 0.0;
+        }
+        NSScreen::frame(screens.objectAtIndex(0)).size.height
     }
 }
 
+/// The scale, refresh rate and name of a screen.
+unsafe fn screen_details(screen: id) -> (f64, Option<f64>, Option<String>) {
+    let scale = NSScreen::backingScaleFactor(screen);
+    // These were added in macOS 12 and 10.15.
+    let responds: BOOL = msg_send![screen, respondsToSelector: sel!(maximumFramesPerSecond)];
+    let refresh_rate = if responds != NO {
+        let fps: NSInteger = msg_send![screen, maximumFramesPerSecond];
+        (fps > 0).then_some(fps as f64)
+    } else {
+        None
+    };
+    let responds: BOOL = msg_send![screen, respondsToSelector: sel!(localizedName)];
+    let name = if responds != NO {
+        let name: id = msg_send![screen, localizedName];
+        (name != nil).then(|| from_nsstring(name))
+    } else {
+        None
+    };
+    (scale, refresh_rate, name)
+}
+
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    tracing::warn!("Screen::get_color_shift() is not implemented for macOS.");
+    None
+}
+
 fn transform_coords(monitors_build: Vec<(Rect, Rect)>, max_y: f64) -> Vec<Monitor> {
     //Flip y and move to opposite horizontal edges (On mac, Y goes up and origin is bottom left corner)
     let fix_rect = |frame: &Rect| {
@@ -25,7 +25,10 @@ use std::ffi::c_void;
 use std::ops::Range;
 use std::os::raw::c_uchar;
 
-use super::window::with_edit_lock_from_window;
+use super::window::{
+    end_composition_from_window, preedit_len_from_window, set_preedit_from_window,
+    with_edit_lock_from_window,
+};
 use crate::text::{
     Action, Affinity, Direction, InputHandler, Movement, Selection, VerticalMovement,
     WritingDirection,
@@ -70,7 +73,8 @@ pub extern "C" fn has_marked_text(this: &mut Object, _: Sel) -> BOOL {
     with_edit_lock_from_window(this, false, |edit_lock| {
         edit_lock.composition_range().is_some()
     })
-    .unwrap_or(false)
+    // without a text field, the only marked text is the preedit we report to the handler
+    .unwrap_or_else(|| preedit_len_from_window(this).is_some())
     .into()
 }
 
@@ -81,7 +85,11 @@ pub extern "C" fn marked_range(this: &mut Object, _: Sel) -> NSRange {
             .map(|range| encode_nsrange(&mut edit_lock, range))
             .unwrap_or(NSRange::NONE)
     })
-    .unwrap_or(NSRange::NONE)
+    .unwrap_or_else(|| {
+        preedit_len_from_window(this)
+            .map(|len| NSRange::new(0, len as NSUInteger))
+            .unwrap_or(NSRange::NONE)
+    })
 }
 
 pub extern "C" fn selected_range(this: &mut Object, _: Sel) -> NSRange {
@@ -99,6 +107,14 @@ pub extern "C" fn set_marked_text(
     selected_range: NSRange,
     replacement_range: NSRange,
 ) {
+    let preedit = parse_attributed_string(&text);
+    if preedit.is_empty() {
+        end_composition_from_window(this, Some(""));
+    } else {
+        let cursor = decode_utf16_range(preedit, &selected_range);
+        set_preedit_from_window(this, preedit, cursor);
+    }
+
     with_edit_lock_from_window(this, true, |mut edit_lock| {
         let mut composition_range = edit_lock.composition_range().unwrap_or_else(|| {
             // no existing composition range? default to replacement range, interpreted in absolute coordinates
@@ -152,6 +168,7 @@ pub extern "C" fn set_marked_text(
 }
 
 pub extern "C" fn unmark_text(this: &mut Object, _: Sel) {
+    end_composition_from_window(this, None);
     with_edit_lock_from_window(this, false, |mut edit_lock| {
         edit_lock.set_composition_range(None)
     });
@@ -191,6 +208,8 @@ pub extern "C" fn attributed_substring_for_proposed_range(
 }
 
 pub extern "C" fn insert_text(this: &mut Object, _: Sel, text: id, replacement_range: NSRange) {
+    end_composition_from_window(this, Some(parse_attributed_string(&text)));
+
     with_edit_lock_from_window(this, true, |mut edit_lock| {
         let text_string = parse_attributed_string(&text);
 
@@ -621,6 +640,25 @@ fn decode_nsrange(
     Some(start_utf8..end_utf8)
 }
 
+// Decodes a UTF-16 `NSRange` within `text` into a UTF-8 `Range<usize>`.
+fn decode_utf16_range(text: &str, range: &NSRange) -> Option<Range<usize>> {
+    if range.location as usize >= i32::max_value() as usize {
+        return None;
+    }
+    let to_utf8 = |utf16: usize| {
+        let mut count = 0;
+        for (idx, c) in text.char_indices() {
+            if count >= utf16 {
+                return idx;
+            }
+            count += c.len_utf16();
+        }
+        text.len()
+    };
+    let start = range.location as usize;
+    Some(to_utf8(start)..to_utf8(start + range.length as usize))
+}
+
 // Encodes the UTF-8 `Range<usize>` into a UTF-16 `NSRange`.
 fn encode_nsrange(edit_lock: &mut Box<dyn InputHandler>, mut range: Range<usize>) -> NSRange {
     while !edit_lock.is_char_boundary(range.start) {
@@ -16,10 +16,13 @@
 
 #![allow(non_snake_case)]
 
+use std::collections::HashMap;
 use std::ffi::c_void;
 use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex, Weak};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "accesskit")]
 use accesskit_macos::Adapter as AccessKitAdapter;
@@ -46,32 +49,99 @@ use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle,
 };
 
-use crate::kurbo::{Insets, Point, Rect, Size, Vec2};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
 
 use super::appkit::{
-    NSRunLoopCommonModes, NSTrackingArea, NSTrackingAreaOptions, NSView as NSViewExt,
+    NSEdgeInsets, NSRunLoopCommonModes, NSTrackingArea, NSTrackingAreaOptions, NSView as NSViewExt,
 };
-use super::application::Application;
+use super::application::{window_restoration_class, Application};
 use super::dialog;
-use super::keyboard::{make_modifiers, KeyboardState};
+use super::dnd::{self, DragState};
+use super::keyboard::{self, make_modifiers, KeyboardState};
 use super::menu::Menu;
 use super::text_input::NSRange;
 use super::util::{assert_main_thread, make_nsstring};
-use crate::common_util::IdleCallback;
+use crate::application::KioskOptions;
+use crate::autoscroll::Autoscroll;
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
+use crate::common_util::{FrameThrottle, IdleCallback, DEFAULT_REFRESH_INTERVAL};
 use crate::dialog::{FileDialogOptions, FileDialogType};
+use crate::dispatch;
+use crate::dnd::{DragData, DragImage};
+use crate::keyboard::{CompositionEvent, CompositionState, KeyEvent, KeyboardLayout};
 use crate::keyboard_types::KeyState;
-use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
+use crate::locale::LocaleInfo;
+use crate::menu;
+use crate::mouse::{
+    Cursor, CursorDesc, ForceClick, InputSource, MomentumPhase, MouseButton, MouseButtons,
+    MouseEvent, ScrollDelta,
+};
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::{self, Monitor, MonitorId};
+use crate::shortcut_capture::ShortcutCapture;
 use crate::text::{Event, InputHandler};
 use crate::window::{
-    FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel, WindowState,
+    FileDialogToken, FocusDirection, FullscreenOptions, IdleToken, PaintReason, ScrollbarMetrics,
+    SystemBar, TextFieldToken, Theme, TimerToken, WinHandler, WindowLevel, WindowState,
 };
+use crate::window_group::WindowGroup;
 use crate::Error;
 
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn CGWarpMouseCursorPosition(new_cursor_position: NSPoint) -> i32;
+    fn CGAssociateMouseAndMouseCursorPosition(connected: u32) -> i32;
+}
+
 #[allow(non_upper_case_globals)]
 const NSWindowDidBecomeKeyNotification: &str = "NSWindowDidBecomeKeyNotification";
 
+// Values of NSSelectionDirection.
+#[allow(non_upper_case_globals)]
+const NSSelectingNext: NSUInteger = 1;
+#[allow(non_upper_case_globals)]
+const NSSelectingPrevious: NSUInteger = 2;
+
+// Values of NSEventPhase.
+#[allow(non_upper_case_globals)]
+const NSEventPhaseBegan: NSUInteger = 1 << 0;
+#[allow(non_upper_case_globals)]
+const NSEventPhaseChanged: NSUInteger = 1 << 2;
+#[allow(non_upper_case_globals)]
+const NSEventPhaseEnded: NSUInteger = 1 << 3;
+#[allow(non_upper_case_globals)]
+const NSEventPhaseCancelled: NSUInteger = 1 << 4;
+
+// Values of NSApplicationPresentationOptions.
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationAutoHideDock: NSUInteger = 1 << 0;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationHideDock: NSUInteger = 1 << 1;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationAutoHideMenuBar: NSUInteger = 1 << 2;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationHideMenuBar: NSUInteger = 1 << 3;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationFullScreen: NSUInteger = 1 << 10;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationDisableAppleMenu: NSUInteger = 1 << 4;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationDisableProcessSwitching: NSUInteger = 1 << 5;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationDisableForceQuit: NSUInteger = 1 << 6;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationDisableSessionTermination: NSUInteger = 1 << 7;
+#[allow(non_upper_case_globals)]
+const NSApplicationPresentationDisableHideApplication: NSUInteger = 1 << 8;
+
+// Values of NSActivityOptions.
+#[allow(non_upper_case_globals)]
+const NSActivityIdleDisplaySleepDisabled: u64 = 1 << 40;
+#[allow(non_upper_case_globals)]
+const NSActivityUserInitiated: u64 = 0x00FF_FFFF | (1 << 20);
+
 #[allow(dead_code)]
 #[allow(non_upper_case_globals)]
 mod levels {
@@ -91,7 +161,7 @@ mod levels {
     pub fn as_raw_window_level(window_level: WindowLevel) -> i32 {
         use WindowLevel::*;
         match window_level {
-            AppWindow => NSNormalWindowLevel,
+            AppWindow | Layer { .. } => NSNormalWindowLevel,
             Tooltip(_) => NSFloatingWindowLevel,
             DropDown(_) => NSFloatingWindowLevel,
             Modal(_) => NSModalPanelWindowLevel,
@@ -139,16 +209,24 @@ impl Default for WindowHandle {
 /// Builder abstraction for creating new windows.
 pub(crate) struct WindowBuilder {
     handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
     title: String,
+    taskbar_group: Option<String>,
+    restoration_id: Option<String>,
     menu: Option<Menu>,
     size: Size,
     min_size: Option<Size>,
+    resize_increments: Option<Size>,
     position: Option<Point>,
     level: Option<WindowLevel>,
     window_state: Option<WindowState>,
     resizable: bool,
     show_titlebar: bool,
     transparent: bool,
+    focusable: bool,
+    activate_on_show: bool,
+    show_after_first_paint: bool,
+    forced_scale: Option<Scale>,
 }
 
 #[derive(Clone)]
@@ -185,30 +263,92 @@ struct ViewState {
     // Tracks whether we have already received the mouseExited event
     mouse_left: bool,
     keyboard_state: KeyboardState,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
+    /// Whether key repeats are passed on to the handler.
+    key_repeat: bool,
+    /// Whether the view has an input context, which input methods compose text through.
+    ime_allowed: bool,
+    /// The part of the view that the cursor is kept in, in view coordinates.
+    pointer_confinement: Option<Rect>,
+    /// Where the cursor is held while the pointer is locked, in view coordinates.
+    pointer_lock: Option<Point>,
+    /// Whether raw pointer motion is sent to the handler, with the cursor held still.
+    raw_pointer_input: bool,
+    /// The last safe area that we told the handler about.
+    safe_area: Insets,
     active_text_input: Option<TextFieldToken>,
+    /// The input method's current preedit text, if a composition is in progress.
+    preedit: Option<String>,
     parent: Option<crate::WindowHandle>,
+    /// Tooltips and drop-downs opened from this window, which are closed when it is clicked
+    /// or stops being key.
+    popups: Vec<WindowHandle>,
+    /// Whether `show` should make the window key.
+    activate_on_show: bool,
+    /// Whether the window is transparent until it has been painted for the first time.
+    hidden_until_paint: bool,
+    /// Whether the window's shape changed, so that its shadow needs recomputing after the
+    /// next paint.
+    shape_changed: bool,
+    /// What happens to the menu bar and dock when the window goes fullscreen.
+    fullscreen_options: FullscreenOptions,
+    /// What kiosk mode locks down.
+    kiosk: KioskOptions,
+    /// The activity that keeps the display awake in kiosk mode, or nil.
+    kiosk_activity: id,
+    /// Whether the handler asked for an animation frame that hasn't started yet.
+    anim_frame_requested: bool,
+    /// Whether the frame being drawn is an animation frame.
+    drawing_anim_frame: bool,
+    /// A scale to report instead of the screen's.
+    forced_scale: Option<Scale>,
+    frame_throttle: FrameThrottle,
+    drag: DragState,
+    /// The `NSTimer`s that can still fire, so that they can be cancelled. The run loop keeps
+    /// them alive until then.
+    timers: HashMap<TimerToken, id>,
     #[cfg(feature = "accesskit")]
     accesskit_adapter: OnceCell<AccessKitAdapter>,
 }
 
 #[derive(Clone, PartialEq, Eq)]
-// TODO: support custom cursors
-pub struct CustomCursor;
+pub struct CustomCursor(Rc<NSCursorRef>);
+
+/// An `NSCursor` that we own.
+#[derive(PartialEq, Eq)]
+struct NSCursorRef(id);
+
+impl Drop for NSCursorRef {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.0, release];
+        }
+    }
+}
 
 impl WindowBuilder {
     pub fn new(_app: Application) -> WindowBuilder {
         WindowBuilder {
             handler: None,
+            group: None,
             title: String::new(),
+            taskbar_group: None,
+            restoration_id: None,
             menu: None,
             size: Size::new(500., 400.),
             min_size: None,
+            resize_increments: None,
             position: None,
             level: None,
             window_state: None,
             resizable: true,
             show_titlebar: true,
             transparent: false,
+            focusable: true,
+            activate_on_show: true,
+            show_after_first_paint: false,
+            forced_scale: None,
         }
     }
 
@@ -216,6 +356,10 @@ impl WindowBuilder {
         self.handler = Some(handler);
     }
 
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
     pub fn set_size(&mut self, size: Size) {
         self.size = size;
     }
@@ -224,6 +368,10 @@ impl WindowBuilder {
         self.min_size = Some(size);
     }
 
+    pub fn set_resize_increments(&mut self, increments: Size) {
+        self.resize_increments = Some(increments);
+    }
+
     pub fn resizable(&mut self, resizable: bool) {
         self.resizable = resizable;
     }
@@ -237,9 +385,28 @@ impl WindowBuilder {
     }
 
     pub fn set_level(&mut self, level: WindowLevel) {
+        if let WindowLevel::Layer { .. } = level {
+            tracing::warn!("layer windows are only supported on wayland");
+        }
         self.level = Some(level);
     }
 
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.focusable = focusable;
+    }
+
+    pub fn set_activate_on_show(&mut self, activate_on_show: bool) {
+        self.activate_on_show = activate_on_show;
+    }
+
+    pub fn show_after_first_paint(&mut self, show_after_first_paint: bool) {
+        self.show_after_first_paint = show_after_first_paint;
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
     pub fn set_position(&mut self, position: Point) {
         self.position = Some(position)
     }
@@ -252,11 +419,22 @@ impl WindowBuilder {
         self.title = title.into();
     }
 
+    pub fn set_taskbar_group(&mut self, group: String) {
+        self.taskbar_group = Some(group);
+    }
+
+    pub fn set_restoration_id(&mut self, id: String) {
+        self.restoration_id = Some(id);
+    }
+
     pub fn set_menu(&mut self, menu: Menu) {
         self.menu = Some(menu);
     }
 
-    pub fn build(self) -> Result<WindowHandle, Error> {
+    pub fn build(mut self) -> Result<WindowHandle, Error> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
         assert_main_thread();
         unsafe {
             let mut style_mask = NSWindowStyleMask::NSClosableWindowMask
@@ -283,18 +461,48 @@ impl WindowBuilder {
                 NSBackingStoreBuffered,
                 NO,
             );
+            // Popups never become key; the keyboard focus stays with their parent.
+            let is_popup = matches!(
+                self.level,
+                Some(WindowLevel::Tooltip(_) | WindowLevel::DropDown(_))
+            );
+            let focusable = self.focusable && !is_popup;
+            (*window).set_ivar("focusable", if focusable { YES } else { NO });
 
             if let Some(min_size) = self.min_size {
                 let size = NSSize::new(min_size.width, min_size.height);
                 window.setContentMinSize_(size);
             }
 
+            if let Some(increments) = self.resize_increments {
+                // An increment of 1.0 means no constraint.
+                let size = NSSize::new(increments.width.max(1.0), increments.height.max(1.0));
+                let () = msg_send![window, setContentResizeIncrements: size];
+            }
+
             if self.transparent {
                 window.setOpaque_(NO);
                 window.setBackgroundColor_(NSColor::clearColor(nil));
             }
 
             window.setTitle_(make_nsstring(&self.title));
+            if let Some(group) = &self.taskbar_group {
+                // The Dock has a single icon per app, so the closest thing we have is to only
+                // merge windows of the same group into tabs.
+                let () = msg_send![window, setTabbingIdentifier: make_nsstring(group)];
+            }
+            // AppKit only reopens windows that have a restoration class, and hands it the
+            // identifier to recreate them from.
+            match &self.restoration_id {
+                Some(restoration_id) => {
+                    let () = msg_send![window, setIdentifier: make_nsstring(restoration_id)];
+                    let () = msg_send![window, setRestorationClass: window_restoration_class()];
+                    let () = msg_send![window, setRestorable: YES];
+                }
+                None => {
+                    let () = msg_send![window, setRestorable: NO];
+                }
+            }
 
             let (view, idle_queue) = make_view(self.handler.expect("view"));
             let content_view = window.contentView();
@@ -322,6 +530,12 @@ impl WindowBuilder {
             content_view.addSubview_(view);
             let view_state: *mut c_void = *(*view).get_ivar("viewState");
             let view_state = &mut *(view_state as *mut ViewState);
+            view_state.activate_on_show = self.activate_on_show;
+            view_state.hidden_until_paint = self.show_after_first_paint;
+            view_state.forced_scale = self.forced_scale;
+            view_state
+                .frame_throttle
+                .set_refresh_rate(screen_refresh_rate(window));
             let mut handle = WindowHandle {
                 nsview: view_state.nsview.clone(),
                 idle_queue,
@@ -333,8 +547,14 @@ impl WindowBuilder {
 
             if let Some(level) = self.level {
                 match &level {
-                    WindowLevel::Tooltip(parent) => view_state.parent = Some(parent.clone()),
-                    WindowLevel::DropDown(parent) => view_state.parent = Some(parent.clone()),
+                    WindowLevel::Tooltip(parent) | WindowLevel::DropDown(parent) => {
+                        view_state.parent = Some(parent.clone());
+                        if let Some(parent_view) = (*parent.0.nsview.load()).as_ref() {
+                            let parent_state: *mut c_void = *parent_view.get_ivar("viewState");
+                            let parent_state = &mut *(parent_state as *mut ViewState);
+                            parent_state.popups.push(handle.clone());
+                        }
+                    }
                     WindowLevel::Modal(parent) => view_state.parent = Some(parent.clone()),
                     _ => {}
                 }
@@ -345,7 +565,9 @@ impl WindowBuilder {
             let frame = NSView::frame(content_view);
 
             view_state.handler.connect(&handle.clone().into());
-            view_state.handler.scale(Scale::default());
+            view_state
+                .handler
+                .scale(view_state.forced_scale.unwrap_or_default());
             view_state
                 .handler
                 .size(Size::new(frame.size.width, frame.size.height));
@@ -379,6 +601,10 @@ lazy_static! {
         extern "C" fn acceptsFirstResponder(_this: &Object, _sel: Sel) -> BOOL {
             YES
         }
+        decl.add_method(
+            sel!(becomeFirstResponder),
+            become_first_responder as extern "C" fn(&mut Object, Sel) -> BOOL,
+        );
         // acceptsFirstMouse is called when a left mouse click would focus the window
         decl.add_method(
             sel!(acceptsFirstMouse:),
@@ -409,6 +635,18 @@ lazy_static! {
             sel!(windowDidResignKey:),
             window_did_resign_key as extern "C" fn(&mut Object, Sel, id),
         );
+        decl.add_method(
+            sel!(windowDidChangeScreen:),
+            window_did_change_screen as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(keyboardSelectionDidChange:),
+            keyboard_selection_did_change as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(localeDidChange:),
+            locale_did_change as extern "C" fn(&mut Object, Sel, id),
+        );
         decl.add_method(
             sel!(setFrameSize:),
             set_frame_size as extern "C" fn(&mut Object, Sel, NSSize),
@@ -465,6 +703,10 @@ lazy_static! {
             sel!(magnifyWithEvent:),
             pinch_event as extern "C" fn(&mut Object, Sel, id),
         );
+        decl.add_method(
+            sel!(pressureChangeWithEvent:),
+            pressure_change as extern "C" fn(&mut Object, Sel, id),
+        );
         decl.add_method(
             sel!(keyDown:),
             key_down as extern "C" fn(&mut Object, Sel, id),
@@ -497,6 +739,11 @@ lazy_static! {
             sel!(windowShouldClose:),
             window_should_close as extern "C" fn(&mut Object, Sel, id)->BOOL,
         );
+        decl.add_method(
+            sel!(window:willUseFullScreenPresentationOptions:),
+            window_will_use_full_screen_presentation_options
+                as extern "C" fn(&mut Object, Sel, id, NSUInteger) -> NSUInteger,
+        );
         decl.add_method(
             sel!(windowWillClose:),
             window_will_close as extern "C" fn(&mut Object, Sel, id),
@@ -540,6 +787,8 @@ lazy_static! {
             }
         }
 
+        decl.add_method(sel!(inputContext), input_context as extern "C" fn(&mut Object, Sel) -> id);
+
         // methods for NSTextInputClient
         decl.add_method(sel!(hasMarkedText), super::text_input::has_marked_text as extern fn(&mut Object, Sel) -> BOOL);
         decl.add_method(
@@ -579,6 +828,28 @@ lazy_static! {
             super::text_input::do_command_by_selector as extern fn(&mut Object, Sel, Sel),
         );
 
+        decl.add_method(
+            sel!(draggingEntered:),
+            dnd::dragging_entered as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+        );
+        decl.add_method(
+            sel!(draggingUpdated:),
+            dnd::dragging_updated as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+        );
+        decl.add_method(
+            sel!(draggingExited:),
+            dnd::dragging_exited as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(performDragOperation:),
+            dnd::perform_drag_operation as extern "C" fn(&mut Object, Sel, id) -> BOOL,
+        );
+        decl.add_method(
+            sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+            dnd::source_operation_mask
+                as extern "C" fn(&mut Object, Sel, id, NSInteger) -> NSUInteger,
+        );
+
         let protocol = Protocol::get("NSTextInputClient").unwrap();
         decl.add_protocol(protocol);
 
@@ -604,6 +875,62 @@ pub(super) fn with_edit_lock_from_window<R>(
     Some(r)
 }
 
+/// Passes the `WinHandler`, and the view's drag and drop state, to a closure.
+pub(super) fn with_drag_from_window<R>(
+    this: &mut Object,
+    f: impl FnOnce(&mut dyn WinHandler, &mut DragState) -> R,
+) -> R {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    f(&mut *view_state.handler, &mut view_state.drag)
+}
+
+/// Reports new preedit text from the input method to the `WinHandler`.
+pub(super) fn set_preedit_from_window(this: &mut Object, text: &str, cursor: Option<Range<usize>>) {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    if view_state.preedit.is_none() {
+        view_state.handler.composition(CompositionEvent {
+            state: CompositionState::Start,
+            text: String::new(),
+            cursor: None,
+        });
+    }
+    view_state.preedit = Some(text.to_owned());
+    view_state.handler.composition(CompositionEvent {
+        state: CompositionState::Update,
+        text: text.to_owned(),
+        cursor,
+    });
+}
+
+/// Reports the end of the current composition, if there is one, to the `WinHandler`.
+///
+/// If `committed` is `None`, the preedit text is committed as it is.
+pub(super) fn end_composition_from_window(this: &mut Object, committed: Option<&str>) {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    view_state.end_composition(committed);
+}
+
+/// The length of the preedit text in UTF-16 code units, if a composition is in progress.
+pub(super) fn preedit_len_from_window(this: &mut Object) -> Option<usize> {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &*(view_state as *const ViewState)
+    };
+    view_state
+        .preedit
+        .as_ref()
+        .map(|text| text.encode_utf16().count())
+}
+
 fn make_view(handler: Box<dyn WinHandler>) -> (id, Weak<Mutex<Vec<IdleKind>>>) {
     let idle_queue = Arc::new(Mutex::new(Vec::new()));
     let queue_handle = Arc::downgrade(&idle_queue);
@@ -611,21 +938,46 @@ fn make_view(handler: Box<dyn WinHandler>) -> (id, Weak<Mutex<Vec<IdleKind>>>) {
         let view: id = msg_send![VIEW_CLASS.0, new];
         let nsview = WeakPtr::new(view);
         let keyboard_state = KeyboardState::new();
+        let autoscroll = Rc::new(Autoscroll::default());
+        let shortcut_capture = Rc::new(ShortcutCapture::default());
         let state = ViewState {
             nsview,
-            handler,
+            handler: dispatch::wrap(handler, &autoscroll, &shortcut_capture),
+            autoscroll,
+            shortcut_capture,
             idle_queue,
             focus_click: false,
             mouse_left: true,
             keyboard_state,
+            key_repeat: true,
+            ime_allowed: true,
+            pointer_confinement: None,
+            pointer_lock: None,
+            raw_pointer_input: false,
+            safe_area: Insets::ZERO,
             //text: PietText::new_with_unique_state(),
             active_text_input: None,
+            preedit: None,
             parent: None,
+            popups: Vec::new(),
+            activate_on_show: true,
+            hidden_until_paint: false,
+            shape_changed: false,
+            fullscreen_options: FullscreenOptions::default(),
+            kiosk: KioskOptions::OFF,
+            kiosk_activity: nil,
+            anim_frame_requested: false,
+            drawing_anim_frame: false,
+            forced_scale: None,
+            frame_throttle: FrameThrottle::default(),
+            drag: DragState::default(),
+            timers: HashMap::new(),
             #[cfg(feature = "accesskit")]
             accesskit_adapter: OnceCell::new(),
         };
         let state_ptr = Box::into_raw(Box::new(state));
         (*view).set_ivar("viewState", state_ptr as *mut c_void);
+        dnd::register_types(view, &[]);
         let options: NSAutoresizingMaskOptions = NSViewWidthSizable | NSViewHeightSizable;
         view.setAutoresizingMask_(options);
         (view.autorelease(), queue_handle)
@@ -640,12 +992,13 @@ lazy_static! {
     static ref WINDOW_CLASS: WindowClass = unsafe {
         let mut decl =
             ClassDecl::new("DruidWindow", class!(NSWindow)).expect("Window class defined");
+        decl.add_ivar::<BOOL>("focusable");
         decl.add_method(
             sel!(canBecomeKeyWindow),
             canBecomeKeyWindow as extern "C" fn(&Object, Sel) -> BOOL,
         );
-        extern "C" fn canBecomeKeyWindow(_this: &Object, _sel: Sel) -> BOOL {
-            YES
+        extern "C" fn canBecomeKeyWindow(this: &Object, _sel: Sel) -> BOOL {
+            unsafe { *this.get_ivar("focusable") }
         }
         WindowClass(decl.register())
     };
@@ -658,6 +1011,39 @@ extern "C" fn set_frame_size(this: &mut Object, _: Sel, size: NSSize) {
         view_state.handler.size(Size::new(size.width, size.height));
         let superclass = msg_send![this, superclass];
         let () = msg_send![super(this, superclass), setFrameSize: size];
+        // Entering or leaving fullscreen always resizes the view, so this is where the safe
+        // area changes.
+        let safe_area = safe_area(this);
+        if safe_area != view_state.safe_area {
+            view_state.safe_area = safe_area;
+            view_state.handler.safe_area_changed(safe_area);
+        }
+    }
+}
+
+/// The insets of the part of `view` that isn't covered by the camera housing.
+///
+/// This is only nonzero while the view's window is fullscreen, on macOS 12 or later.
+fn safe_area(view: *mut Object) -> Insets {
+    unsafe {
+        let window: id = msg_send![view, window];
+        if window.is_null()
+            || !window
+                .styleMask()
+                .contains(NSWindowStyleMask::NSFullScreenWindowMask)
+        {
+            return Insets::ZERO;
+        }
+        let screen: id = msg_send![window, screen];
+        if screen.is_null() {
+            return Insets::ZERO;
+        }
+        let supported: BOOL = msg_send![screen, respondsToSelector: sel!(safeAreaInsets)];
+        if supported == NO {
+            return Insets::ZERO;
+        }
+        let insets: NSEdgeInsets = msg_send![screen, safeAreaInsets];
+        Insets::new(insets.left, insets.top, insets.right, insets.bottom)
     }
 }
 
@@ -667,7 +1053,7 @@ fn mouse_event(
     count: u8,
     focus: bool,
     button: MouseButton,
-    wheel_delta: Vec2,
+    wheel_delta: ScrollDelta,
 ) -> MouseEvent {
     unsafe {
         let point = nsevent.locationInWindow();
@@ -683,11 +1069,14 @@ fn mouse_event(
             focus,
             button,
             wheel_delta,
+            momentum_phase: MomentumPhase::None,
+            source: InputSource::Hardware,
+            coalesced: Vec::new(),
         }
     }
 }
 
-fn get_mouse_button(button: NSInteger) -> Option<MouseButton> {
+pub(super) fn get_mouse_button(button: NSInteger) -> Option<MouseButton> {
     match button {
         0 => Some(MouseButton::Left),
         1 => Some(MouseButton::Right),
@@ -698,7 +1087,7 @@ fn get_mouse_button(button: NSInteger) -> Option<MouseButton> {
     }
 }
 
-fn get_mouse_buttons(mask: NSUInteger) -> MouseButtons {
+pub(super) fn get_mouse_buttons(mask: NSUInteger) -> MouseButtons {
     let mut buttons = MouseButtons::new();
     if mask & 1 != 0 {
         buttons.insert(MouseButton::Left);
@@ -740,7 +1129,8 @@ fn mouse_down(this: &mut Object, nsevent: id, button: MouseButton) {
         let view_state = &mut *(view_state as *mut ViewState);
         let count = nsevent.clickCount() as u8;
         let focus = view_state.focus_click && button == MouseButton::Left;
-        let event = mouse_event(nsevent, this as id, count, focus, button, Vec2::ZERO);
+        let event = mouse_event(nsevent, this as id, count, focus, button, ScrollDelta::ZERO);
+        dismiss_popups(view_state);
         view_state.handler.mouse_down(&event);
     }
 }
@@ -771,7 +1161,7 @@ fn mouse_up(this: &mut Object, nsevent: id, button: MouseButton) {
         } else {
             false
         };
-        let event = mouse_event(nsevent, this as id, 0, focus, button, Vec2::ZERO);
+        let event = mouse_event(nsevent, this as id, 0, focus, button, ScrollDelta::ZERO);
         view_state.handler.mouse_up(&event);
         // If we have already received a mouseExited event then that means
         // we're still receiving mouse events because some buttons are being held down.
@@ -788,17 +1178,76 @@ extern "C" fn mouse_move(this: &mut Object, _: Sel, nsevent: id) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
-        let event = mouse_event(nsevent, this as id, 0, false, MouseButton::None, Vec2::ZERO);
+        let mut event = mouse_event(
+            nsevent,
+            this as id,
+            0,
+            false,
+            MouseButton::None,
+            ScrollDelta::ZERO,
+        );
+        if view_state.raw_pointer_input {
+            let dx: CGFloat = msg_send![nsevent, deltaX];
+            let dy: CGFloat = msg_send![nsevent, deltaY];
+            if dx != 0.0 || dy != 0.0 {
+                view_state.handler.raw_pointer_motion(Vec2::new(dx, dy));
+            }
+        } else if let Some(pos) = view_state.pointer_lock {
+            // Like confinement, the lock is emulated by moving the cursor back.
+            if event.pos != pos {
+                event.pos = pos;
+                warp_cursor(this as id, pos);
+            }
+        } else if let Some(region) = view_state.pointer_confinement {
+            let window: id = msg_send![this as id, window];
+            let is_key: BOOL = msg_send![window, isKeyWindow];
+            if is_key == YES && !region.contains(event.pos) {
+                // macOS can't confine the cursor, so we move it back whenever it leaves.
+                event.pos = Point::new(
+                    event.pos.x.clamp(region.x0, region.x1),
+                    event.pos.y.clamp(region.y0, region.y1),
+                );
+                warp_cursor(this as id, event.pos);
+            }
+        }
         view_state.handler.mouse_move(&event);
     }
 }
 
+/// Converts `pos`, in the coordinates of `view`, to screen coordinates with the origin at the
+/// top left of the main display.
+unsafe fn view_to_screen(view: id, pos: Point) -> Point {
+    let window: id = msg_send![view, window];
+    let point = NSRect::new(NSPoint::new(pos.x, pos.y), NSSize::new(0., 0.));
+    let point: NSRect = msg_send![view, convertRect: point toView: nil];
+    let point = window.convertRectToScreen_(point).origin;
+    // Cocoa puts the origin at the bottom left of the main display, rather than the top left.
+    let screen_height = crate::Screen::get_display_rect().height();
+    Point::new(point.x, screen_height - point.y)
+}
+
+/// Moves the cursor to `pos`, in the coordinates of `view`.
+unsafe fn warp_cursor(view: id, pos: Point) {
+    let point = view_to_screen(view, pos);
+    CGWarpMouseCursorPosition(NSPoint::new(point.x, point.y));
+    // Warping suppresses mouse movement for a moment, which makes the cursor feel stuck.
+    // Reassociating the mouse with the cursor cancels that.
+    CGAssociateMouseAndMouseCursorPosition(1);
+}
+
 extern "C" fn mouse_enter(this: &mut Object, _sel: Sel, nsevent: id) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
         view_state.mouse_left = false;
-        let event = mouse_event(nsevent, this, 0, false, MouseButton::None, Vec2::ZERO);
+        let event = mouse_event(
+            nsevent,
+            this,
+            0,
+            false,
+            MouseButton::None,
+            ScrollDelta::ZERO,
+        );
         view_state.handler.mouse_move(&event);
     }
 }
@@ -816,24 +1265,32 @@ extern "C" fn scroll_wheel(this: &mut Object, _: Sel, nsevent: id) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
-        let (dx, dy) = {
-            let dx = -nsevent.scrollingDeltaX() as f64;
-            let dy = -nsevent.scrollingDeltaY() as f64;
-            if nsevent.hasPreciseScrollingDeltas() == cocoa::base::YES {
-                (dx, dy)
-            } else {
-                (dx * 32.0, dy * 32.0)
-            }
+        let delta = Vec2::new(
+            -nsevent.scrollingDeltaX() as f64,
+            -nsevent.scrollingDeltaY() as f64,
+        );
+        // Mouse wheels that scroll in steps report lines.
+        let wheel_delta = if nsevent.hasPreciseScrollingDeltas() == cocoa::base::YES {
+            ScrollDelta::Pixels(delta)
+        } else {
+            ScrollDelta::Lines(delta)
         };
 
-        let event = mouse_event(
+        let mut event = mouse_event(
             nsevent,
             this as id,
             0,
             false,
             MouseButton::None,
-            Vec2::new(dx, dy),
+            wheel_delta,
         );
+        let phase: NSUInteger = msg_send![nsevent, momentumPhase];
+        event.momentum_phase = match phase {
+            NSEventPhaseBegan => MomentumPhase::Began,
+            NSEventPhaseChanged => MomentumPhase::Changed,
+            NSEventPhaseEnded | NSEventPhaseCancelled => MomentumPhase::Ended,
+            _ => MomentumPhase::None,
+        };
         view_state.handler.wheel(&event);
     }
 }
@@ -848,13 +1305,73 @@ extern "C" fn pinch_event(this: &mut Object, _: Sel, nsevent: id) {
     }
 }
 
+extern "C" fn pressure_change(this: &mut Object, _: Sel, nsevent: id) {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+
+        let point = nsevent.locationInWindow();
+        let view_point = (this as id).convertPoint_fromView_(point, nil);
+        let stage: NSInteger = msg_send![nsevent, stage];
+        let pressure: f32 = msg_send![nsevent, pressure];
+        let stage_transition: CGFloat = msg_send![nsevent, stageTransition];
+        view_state.handler.force_click(&ForceClick {
+            pos: Point::new(view_point.x as f64, view_point.y as f64),
+            stage: stage.max(0) as u32,
+            pressure: pressure as f64,
+            stage_transition: stage_transition as f64,
+        });
+    }
+}
+
+extern "C" fn become_first_responder(this: &mut Object, _: Sel) -> BOOL {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    // When the window's key view loop brings the focus to us, tell the content which way it's
+    // going. We take the focus either way, so that the content is always a stop in the loop.
+    let selection_direction: NSUInteger = unsafe {
+        let window: id = msg_send![this, window];
+        msg_send![window, keyViewSelectionDirection]
+    };
+    let direction = match selection_direction {
+        NSSelectingNext => Some(FocusDirection::Next),
+        NSSelectingPrevious => Some(FocusDirection::Previous),
+        _ => None,
+    };
+    if let Some(direction) = direction {
+        view_state.handler.focus_navigation(direction);
+    }
+    YES
+}
+
 extern "C" fn key_down(this: &mut Object, _: Sel, nsevent: id) {
     let view_state = unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         &mut *(view_state as *mut ViewState)
     };
     if let Some(event) = view_state.keyboard_state.process_native_event(nsevent) {
+        if event.repeat && !view_state.key_repeat {
+            return;
+        }
+        let focus = FocusDirection::from_key_event(&event);
         if !view_state.handler.key_down(event) {
+            if let (Some(direction), None) = (focus, view_state.active_text_input) {
+                // Without a text field to insert it, a tab moves the focus; first within the
+                // content, and then on to the window's other key views.
+                if !view_state.handler.focus_navigation(direction) {
+                    unsafe {
+                        let window: id = msg_send![this, window];
+                        if direction == FocusDirection::Previous {
+                            let () = msg_send![window, selectPreviousKeyView: nil];
+                        } else {
+                            let () = msg_send![window, selectNextKeyView: nil];
+                        }
+                    }
+                }
+                return;
+            }
             // key down not handled; forward to text input system
             unsafe {
                 let events = NSArray::arrayWithObjects(nil, &[nsevent]);
@@ -864,6 +1381,20 @@ extern "C" fn key_down(this: &mut Object, _: Sel, nsevent: id) {
     }
 }
 
+extern "C" fn input_context(this: &mut Object, _: Sel) -> id {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &*(view_state as *const ViewState);
+        if !view_state.ime_allowed {
+            // Without an input context, `interpretKeyEvents:` inserts the characters of the keys
+            // as they are.
+            return nil;
+        }
+        let superclass = msg_send![this, superclass];
+        msg_send![super(this, superclass), inputContext]
+    }
+}
+
 extern "C" fn key_up(this: &mut Object, _: Sel, nsevent: id) {
     let view_state = unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
@@ -885,6 +1416,11 @@ extern "C" fn mods_changed(this: &mut Object, _: Sel, nsevent: id) {
         } else {
             view_state.handler.key_up(event);
         }
+    } else {
+        // The flags changed without a modifier key, for example because the app was
+        // deactivated with Command-Tab while it was held.
+        let mods = unsafe { make_modifiers(nsevent.modifierFlags()) };
+        view_state.handler.modifiers_changed(mods);
     }
 }
 
@@ -892,6 +1428,10 @@ extern "C" fn view_will_draw(this: &mut Object, _: Sel) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        // Animation frames requested from here on are for the next frame.
+        view_state.drawing_anim_frame = mem::replace(&mut view_state.anim_frame_requested, false);
+        let info = view_state.frame_throttle.frame_info();
+        view_state.handler.prepare_frame(info);
         view_state.handler.prepare_paint();
     }
 }
@@ -909,7 +1449,25 @@ extern "C" fn draw_rect(this: &mut Object, _: Sel, dirtyRect: NSRect) {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
 
-        view_state.handler.paint(&invalid);
+        view_state.frame_throttle.frame_started();
+        let reason = if mem::replace(&mut view_state.drawing_anim_frame, false) {
+            PaintReason::Animation
+        } else {
+            PaintReason::Damage
+        };
+        let missed = view_state.frame_throttle.missed_frames(reason);
+        if missed > 0 {
+            view_state.handler.frame_missed(missed);
+        }
+        view_state.handler.paint(&invalid, reason);
+        if mem::replace(&mut view_state.hidden_until_paint, false) {
+            let window: id = msg_send![this, window];
+            let () = msg_send![window, setAlphaValue: 1.0 as CGFloat];
+        }
+        if mem::replace(&mut view_state.shape_changed, false) {
+            let window: id = msg_send![this, window];
+            let () = msg_send![window, invalidateShadow];
+        }
 
         let superclass = msg_send![this, superclass];
         let () = msg_send![super(this, superclass), drawRect: dirtyRect];
@@ -976,7 +1534,17 @@ extern "C" fn run_idle(this: &mut Object, _: Sel) {
 
 extern "C" fn redraw(this: &mut Object, _: Sel) {
     unsafe {
-        let () = msg_send![this as *const _, setNeedsDisplay: YES];
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        view_state.anim_frame_requested = true;
+        // AppKit only draws views with something to redraw. Animation frames don't damage
+        // anything themselves, so we dirty as little as we can, and the handler invalidates
+        // what it needs in prepare_paint.
+        let needs_display: BOOL = msg_send![this as *const _, needsDisplay];
+        if needs_display == NO {
+            let rect = NSRect::new(NSPoint::new(0., 0.), NSSize::new(1., 1.));
+            let () = msg_send![this as *const _, setNeedsDisplayInRect: rect];
+        }
     }
 }
 
@@ -989,8 +1557,14 @@ extern "C" fn handle_timer(this: &mut Object, _: Sel, timer: id) {
         let user_info: id = msg_send![timer, userInfo];
         msg_send![user_info, unsignedIntValue]
     };
+    let token = TimerToken::from_raw(token);
+    // The run loop lets go of a timer that doesn't repeat once it has fired.
+    let interval: f64 = unsafe { msg_send![timer, timeInterval] };
+    if interval <= 0.0 {
+        view_state.timers.remove(&token);
+    }
 
-    view_state.handler.timer(TimerToken::from_raw(token));
+    view_state.handler.timer(token);
 }
 
 extern "C" fn handle_menu_item(this: &mut Object, _: Sel, item: id) {
@@ -998,7 +1572,8 @@ extern "C" fn handle_menu_item(this: &mut Object, _: Sel, item: id) {
         let tag: isize = msg_send![item, tag];
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
-        view_state.handler.command(tag as u32);
+        let focus = view_state.active_text_input;
+        menu::run_command(&mut *view_state.handler, focus, tag as u32);
     }
 }
 
@@ -1016,6 +1591,9 @@ extern "C" fn window_did_become_key(this: &mut Object, _: Sel, _notification: id
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        if view_state.raw_pointer_input {
+            CGAssociateMouseAndMouseCursorPosition(0);
+        }
         view_state.handler.got_focus();
     }
 }
@@ -1024,10 +1602,99 @@ extern "C" fn window_did_resign_key(this: &mut Object, _: Sel, _notification: id
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        dismiss_popups(view_state);
+        if view_state.raw_pointer_input {
+            CGAssociateMouseAndMouseCursorPosition(1);
+        }
+        let lock_lost = view_state.pointer_lock.take().is_some();
         view_state.handler.lost_focus();
+        if lock_lost {
+            view_state.handler.pointer_lock_lost();
+        }
+    }
+}
+
+extern "C" fn window_did_change_screen(this: &mut Object, _: Sel, notification: id) {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        let window: id = msg_send![notification, object];
+        let refresh_rate = screen_refresh_rate(window);
+        if view_state.frame_throttle.set_refresh_rate(refresh_rate) {
+            let interval = view_state.frame_throttle.refresh_interval();
+            view_state.handler.refresh_interval_changed(interval);
+        }
+    }
+}
+
+/// Returns the refresh rate of the screen that most of `window` is on, if it can be found out.
+///
+/// This needs macOS 12.
+unsafe fn screen_refresh_rate(window: id) -> Option<f64> {
+    let screen: id = msg_send![window, screen];
+    if screen.is_null() {
+        return None;
+    }
+    let supported: BOOL = msg_send![screen, respondsToSelector: sel!(maximumFramesPerSecond)];
+    if supported == NO {
+        return None;
+    }
+    let fps: NSInteger = msg_send![screen, maximumFramesPerSecond];
+    Some(fps as f64)
+}
+
+/// Close any tooltips and drop-downs that were opened from this window.
+fn dismiss_popups(view_state: &mut ViewState) {
+    for popup in std::mem::take(&mut view_state.popups) {
+        popup.close();
+    }
+}
+
+extern "C" fn window_will_use_full_screen_presentation_options(
+    this: &mut Object,
+    _: Sel,
+    _window: id,
+    _proposed: NSUInteger,
+) -> NSUInteger {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        presentation_options(
+            view_state.fullscreen_options,
+            view_state.kiosk.block_system_shortcuts,
+        )
     }
 }
 
+/// The presentation options for a fullscreen window, which keep the user from switching away
+/// from it if `block_shortcuts` is set.
+fn presentation_options(options: FullscreenOptions, block_shortcuts: bool) -> NSUInteger {
+    // AppKit only hides the menu bar along with the dock, and a fullscreen window can't leave
+    // either of them visible.
+    let hide_menu_bar = options.menu_bar == SystemBar::Hidden;
+    let hide_dock = hide_menu_bar || options.dock == SystemBar::Hidden;
+    let menu_bar = if hide_menu_bar {
+        NSApplicationPresentationHideMenuBar
+    } else {
+        NSApplicationPresentationAutoHideMenuBar
+    };
+    let dock = if hide_dock {
+        NSApplicationPresentationHideDock
+    } else {
+        NSApplicationPresentationAutoHideDock
+    };
+    let lockdown = if block_shortcuts {
+        NSApplicationPresentationDisableAppleMenu
+            | NSApplicationPresentationDisableProcessSwitching
+            | NSApplicationPresentationDisableForceQuit
+            | NSApplicationPresentationDisableSessionTermination
+            | NSApplicationPresentationDisableHideApplication
+    } else {
+        0
+    };
+    NSApplicationPresentationFullScreen | menu_bar | dock | lockdown
+}
+
 extern "C" fn window_should_close(this: &mut Object, _: Sel, _window: id) -> BOOL {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
@@ -1037,15 +1704,49 @@ extern "C" fn window_should_close(this: &mut Object, _: Sel, _window: id) -> BOO
     }
 }
 
+extern "C" fn keyboard_selection_did_change(this: &mut Object, _: Sel, _notification: id) {
+    if let Some(layout) = keyboard::current_keyboard_layout() {
+        unsafe {
+            let view_state: *mut c_void = *this.get_ivar("viewState");
+            let view_state = &mut *(view_state as *mut ViewState);
+            view_state.handler.input_language_changed(layout);
+        }
+    }
+}
+
+extern "C" fn locale_did_change(this: &mut Object, _: Sel, _notification: id) {
+    let info = Application::locale_info();
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        view_state.handler.locale_changed(info);
+    }
+}
+
 extern "C" fn window_will_close(this: &mut Object, _: Sel, _notification: id) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        // Repeating timers hold on to the view, so they would keep it alive forever.
+        for (_, timer) in view_state.timers.drain() {
+            let () = msg_send![timer, invalidate];
+        }
         view_state.handler.destroy();
     }
 }
 
 impl WindowHandle {
+    /// The `NSWindow` that holds the view, or nil if the window is gone.
+    pub(crate) fn ns_window(&self) -> id {
+        unsafe {
+            let view = *self.nsview.load();
+            if view.is_null() {
+                return nil;
+            }
+            msg_send![view, window]
+        }
+    }
+
     pub fn show(&self) {
         unsafe {
             let window: id = msg_send![*self.nsview.load(), window];
@@ -1056,13 +1757,44 @@ impl WindowHandle {
                 .autorelease();
             let notif_center: id = msg_send![notif_center_class, defaultCenter];
             let () = msg_send![notif_center, addObserver:*self.nsview.load() selector: sel!(windowDidBecomeKey:) name: notif_string object: window];
-            window.makeKeyAndOrderFront_(nil)
+            // and when the user switches keyboard layouts.
+            let notif_string = NSString::alloc(nil)
+                .init_str("NSTextInputContextKeyboardSelectionDidChangeNotification")
+                .autorelease();
+            let () = msg_send![notif_center, addObserver:*self.nsview.load() selector: sel!(keyboardSelectionDidChange:) name: notif_string object: nil];
+            // and when the user changes their regional settings.
+            let notif_string = NSString::alloc(nil)
+                .init_str("NSCurrentLocaleDidChangeNotification")
+                .autorelease();
+            let () = msg_send![notif_center, addObserver:*self.nsview.load() selector: sel!(localeDidChange:) name: notif_string object: nil];
+            let view = self.nsview.load();
+            let (activate, hidden) = match (*view).as_ref() {
+                Some(view) => {
+                    let view_state: *mut c_void = *view.get_ivar("viewState");
+                    let view_state = &*(view_state as *const ViewState);
+                    (view_state.activate_on_show, view_state.hidden_until_paint)
+                }
+                None => (true, false),
+            };
+            if hidden {
+                // draw_rect restores the alpha once we have painted something.
+                let () = msg_send![window, setAlphaValue: 0.0 as CGFloat];
+            }
+            if activate {
+                window.makeKeyAndOrderFront_(nil)
+            } else {
+                let () = msg_send![window, orderFront: nil];
+            }
         }
     }
 
     /// Close the window.
     pub fn close(&self) {
         unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                dismiss_popups(&mut *(view_state as *mut ViewState));
+            }
             let window: id = msg_send![*self.nsview.load(), window];
             let () = msg_send![window, performSelectorOnMainThread: sel!(close) withObject: nil waitUntilDone: NO];
         }
@@ -1076,14 +1808,131 @@ impl WindowHandle {
         }
     }
 
+    pub fn raise_above(&self, other: &WindowHandle) {
+        // NSWindowAbove
+        self.order_relative_to(other, 1);
+    }
+
+    pub fn lower_below(&self, other: &WindowHandle) {
+        // NSWindowBelow
+        self.order_relative_to(other, -1);
+    }
+
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        unsafe {
+            let window: id = msg_send![*self.nsview.load(), window];
+            let () = msg_send![window, setIgnoresMouseEvents: if passthrough { YES } else { NO }];
+        }
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                (*(view_state as *mut ViewState)).key_repeat = enabled;
+            }
+        }
+    }
+
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                if view_state.ime_allowed == allowed {
+                    return;
+                }
+                if !allowed {
+                    // Ends the composition while the input context is still there.
+                    let input_context: id = msg_send![*self.nsview.load(), inputContext];
+                    let () = msg_send![input_context, discardMarkedText];
+                    let () = msg_send![input_context, deactivate];
+                    view_state.end_composition(Some(""));
+                    if let Some(token) = view_state.active_text_input {
+                        let mut edit_lock = view_state.handler.acquire_input_lock(token, true);
+                        edit_lock.set_composition_range(None);
+                        view_state.handler.release_input_lock(token);
+                    }
+                }
+                view_state.ime_allowed = allowed;
+                if allowed {
+                    let input_context: id = msg_send![*self.nsview.load(), inputContext];
+                    let () = msg_send![input_context, activate];
+                }
+            }
+        }
+    }
+
+    pub fn set_shape(&self, shape: Option<BezPath>) {
+        unsafe {
+            let view = self.nsview.load();
+            let window: id = msg_send![*view, window];
+            if shape.is_some() {
+                // The window server works out the window's shape (for both clicks and the
+                // shadow) from the alpha channel, so we only need to let it see through.
+                window.setOpaque_(NO);
+                window.setBackgroundColor_(NSColor::clearColor(nil));
+            }
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                view_state.shape_changed = true;
+            }
+            let () = msg_send![*view, setNeedsDisplay: YES];
+        }
+    }
+
+    fn order_relative_to(&self, other: &WindowHandle, mode: NSInteger) {
+        unsafe {
+            let window: id = msg_send![*self.nsview.load(), window];
+            let other: id = msg_send![*other.nsview.load(), window];
+            let other_number: NSInteger = msg_send![other, windowNumber];
+            let () = msg_send![window, orderWindow: mode relativeTo: other_number];
+        }
+    }
+
     pub fn request_anim_frame(&self) {
         unsafe {
+            let view = self.nsview.load();
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &*(view_state as *const ViewState);
+                if let Some(deadline) = view_state.frame_throttle.next_frame_deadline() {
+                    let delay = time_interval_from_deadline(deadline);
+                    let () = msg_send![*self.nsview.load(), performSelector: sel!(redraw)
+                        withObject: nil afterDelay: delay];
+                    return;
+                }
+            }
             // TODO: synchronize with screen refresh rate using CVDisplayLink instead.
             let () = msg_send![*self.nsview.load(), performSelectorOnMainThread: sel!(redraw)
                 withObject: nil waitUntilDone: NO];
         }
     }
 
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        unsafe {
+            let view = self.nsview.load();
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &*(view_state as *const ViewState);
+                view_state.frame_throttle.set_max_frame_rate(fps);
+            }
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        unsafe {
+            let view = self.nsview.load();
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &*(view_state as *const ViewState);
+                return view_state.frame_throttle.refresh_interval();
+            }
+        }
+        DEFAULT_REFRESH_INTERVAL
+    }
+
     // Request invalidation of the entire window contents.
     pub fn invalidate(&self) {
         unsafe {
@@ -1104,6 +1953,11 @@ impl WindowHandle {
         }
     }
 
+    pub fn scroll_region(&self, rect: Rect, _delta: Vec2) {
+        // Views are layer-backed on recent macOS, and AppKit doesn't move layer contents for us.
+        self.invalidate_rect(rect);
+    }
+
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         unsafe {
             let nscursor = class!(NSCursor);
@@ -1117,20 +1971,169 @@ impl WindowHandle {
                 Cursor::NotAllowed => msg_send![nscursor, operationNotAllowedCursor],
                 Cursor::ResizeLeftRight => msg_send![nscursor, resizeLeftRightCursor],
                 Cursor::ResizeUpDown => msg_send![nscursor, resizeUpDownCursor],
-                // TODO: support custom cursors
-                Cursor::Custom(_) => msg_send![nscursor, arrowCursor],
+                // AppKit doesn't have an all-scroll cursor.
+                Cursor::AllScroll => msg_send![nscursor, openHandCursor],
+                // AppKit shows the spinning wait cursor by itself when the app stops responding,
+                // and doesn't let apps show it.
+                Cursor::Wait | Cursor::Progress => msg_send![nscursor, arrowCursor],
+                Cursor::Custom(custom) => (custom.0).0,
             };
             let () = msg_send![cursor, set];
         }
     }
 
-    pub fn make_cursor(&self, _cursor_desc: &CursorDesc) -> Option<Cursor> {
-        tracing::warn!("Custom cursors are not yet supported in the macOS backend");
-        None
+    pub fn confine_pointer(&self, region: Option<Rect>) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                view_state.pointer_confinement = region;
+                if region.is_some() {
+                    view_state.pointer_lock = None;
+                }
+            }
+        }
+    }
+
+    pub fn lock_pointer(&self) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let window: id = msg_send![view, window];
+                let is_key: BOOL = msg_send![window, isKeyWindow];
+                if is_key == NO {
+                    tracing::warn!("can't lock the pointer of a window without focus");
+                    return;
+                }
+                let pos: NSPoint = msg_send![window, mouseLocationOutsideOfEventStream];
+                let pos: NSPoint = msg_send![view, convertPoint: pos fromView: nil];
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                view_state.pointer_confinement = None;
+                view_state.pointer_lock = Some(Point::new(pos.x as f64, pos.y as f64));
+            }
+        }
+    }
+
+    pub fn unlock_pointer(&self) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                (*(view_state as *mut ViewState)).pointer_lock = None;
+            }
+        }
+    }
+
+    pub fn set_raw_pointer_input(&self, enabled: bool) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                view_state.raw_pointer_input = enabled;
+                // The cursor stays put while the mouse is disconnected from it, but mouse
+                // events keep coming with the mouse's motion.
+                let window: id = msg_send![view, window];
+                let is_key: BOOL = msg_send![window, isKeyWindow];
+                if is_key == YES {
+                    CGAssociateMouseAndMouseCursorPosition(if enabled { 0 } else { 1 });
+                }
+            }
+        }
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                (*(view_state as *mut ViewState)).autoscroll.begin(origin);
+            }
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                (*(view_state as *mut ViewState))
+                    .shortcut_capture
+                    .begin(callback);
+            }
+        }
+    }
+
+    pub fn make_cursor(&self, cursor_desc: &CursorDesc) -> Option<Cursor> {
+        unsafe {
+            let view = (*self.nsview.load()).as_ref()?;
+            let window: id = msg_send![view, window];
+            let scale: f64 = if window.is_null() {
+                1.0
+            } else {
+                msg_send![window, backingScaleFactor]
+            };
+            let image = dnd::make_image(
+                cursor_desc.width,
+                cursor_desc.height,
+                &cursor_desc.pixels,
+                scale,
+            );
+            if image.is_null() {
+                return None;
+            }
+            let hot = NSPoint::new(cursor_desc.hot.x / scale, cursor_desc.hot.y / scale);
+            let cursor: id = msg_send![class!(NSCursor), alloc];
+            let cursor: id = msg_send![cursor, initWithImage: image hotSpot: hot];
+            if cursor.is_null() {
+                return None;
+            }
+            Some(Cursor::Custom(CustomCursor(Rc::new(NSCursorRef(cursor)))))
+        }
+    }
+
+    pub fn start_drag(&self, data: DragData, image: Option<DragImage>) {
+        dnd::start_drag(*self.nsview.load(), &data, image.as_ref());
+    }
+
+    pub fn set_drop_formats(&self, formats: &[FormatId]) {
+        unsafe {
+            let view = self.nsview.load();
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                (*(view_state as *mut ViewState)).drag.formats = formats.to_vec();
+            }
+            dnd::register_types(*view, formats);
+        }
     }
 
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.request_timer_with_tolerance(deadline, Duration::ZERO)
+    }
+
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Instant,
+        tolerance: Duration,
+    ) -> TimerToken {
         let ti = time_interval_from_deadline(deadline);
+        self.schedule_timer(ti, tolerance, NO)
+    }
+
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        // NSTimer treats intervals of zero as 0.1 milliseconds.
+        self.schedule_timer(interval.as_secs_f64(), Duration::ZERO, YES)
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        unsafe {
+            if let Some(view) = (*self.nsview.load()).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                if let Some(timer) = view_state.timers.remove(&token) {
+                    let () = msg_send![timer, invalidate];
+                }
+            }
+        }
+    }
+
+    fn schedule_timer(&self, ti: f64, tolerance: Duration, repeats: BOOL) -> TimerToken {
         let token = TimerToken::next();
         unsafe {
             let nstimer = class!(NSTimer);
@@ -1138,9 +2141,17 @@ impl WindowHandle {
             let user_info: id = msg_send![nsnumber, numberWithUnsignedInteger: token.into_raw()];
             let selector = sel!(handleTimer:);
             let view = self.nsview.load();
-            let timer: id = msg_send![nstimer, timerWithTimeInterval: ti target: view selector: selector userInfo: user_info repeats: NO];
+            let timer: id = msg_send![nstimer, timerWithTimeInterval: ti target: view selector: selector userInfo: user_info repeats: repeats];
+            // This lets the system fire the timer together with others, to save power.
+            let () = msg_send![timer, setTolerance: tolerance.as_secs_f64()];
             let runloop: id = msg_send![class!(NSRunLoop), currentRunLoop];
             let () = msg_send![runloop, addTimer: timer forMode: NSRunLoopCommonModes];
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                (*(view_state as *mut ViewState))
+                    .timers
+                    .insert(token, timer);
+            }
         }
         token
     }
@@ -1196,6 +2207,7 @@ impl WindowHandle {
                     Event::Reset | Event::SelectionChanged => {
                         let input_context: id = msg_send![*self.nsview.load(), inputContext];
                         let _: () = msg_send![input_context, discardMarkedText];
+                        state.end_composition(Some(""));
                         let mut edit_lock = state.handler.acquire_input_lock(token, true);
                         edit_lock.set_composition_range(None);
                         state.handler.release_input_lock(token);
@@ -1246,6 +2258,24 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_theme_override(&self, theme: Option<Theme>) {
+        unsafe {
+            let window: id = msg_send![*self.nsview.load(), window];
+            // A nil appearance means the window follows the application's.
+            let appearance: id = match theme {
+                Some(theme) => {
+                    let name = match theme {
+                        Theme::Light => "NSAppearanceNameAqua",
+                        Theme::Dark => "NSAppearanceNameDarkAqua",
+                    };
+                    msg_send![class!(NSAppearance), appearanceNamed: make_nsstring(name)]
+                }
+                None => nil,
+            };
+            let () = msg_send![window, setAppearance: appearance];
+        }
+    }
+
     // TODO: Implement this
     pub fn show_titlebar(&self, _show_titlebar: bool) {}
 
@@ -1292,6 +2322,15 @@ impl WindowHandle {
         }
     }
 
+    pub fn monitor(&self) -> Option<Monitor> {
+        let origin = self.map_to_screen(Point::ZERO);
+        screen::monitor_containing(Rect::from_origin_size(origin, self.get_size()))
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        unsafe { view_to_screen(*self.nsview.load(), point) }
+    }
+
     pub fn content_insets(&self) -> Insets {
         unsafe {
             let screen_height = crate::Screen::get_display_rect().height();
@@ -1320,6 +2359,10 @@ impl WindowHandle {
         }
     }
 
+    pub fn safe_area(&self) -> Insets {
+        safe_area(*self.nsview.load())
+    }
+
     fn set_level(&self, level: WindowLevel) {
         unsafe {
             let level = levels::as_raw_window_level(level);
@@ -1355,6 +2398,92 @@ impl WindowHandle {
         WindowState::Restored
     }
 
+    pub fn set_fullscreen(&self, options: Option<FullscreenOptions>) {
+        unsafe {
+            let view = self.nsview.load();
+            let window: id = msg_send![*view, window];
+            let is_fullscreen = window
+                .styleMask()
+                .contains(NSWindowStyleMask::NSFullScreenWindowMask);
+            let mut block_shortcuts = false;
+            if let (Some(options), Some(view)) = (options, (*view).as_ref()) {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                view_state.fullscreen_options = options;
+                block_shortcuts = view_state.kiosk.block_system_shortcuts;
+            }
+            match options {
+                // AppKit asks the window delegate for the options when it goes fullscreen, but
+                // if it already is, we change them ourselves.
+                Some(options) if is_fullscreen => {
+                    let options = presentation_options(options, block_shortcuts);
+                    let () = msg_send![NSApp(), setPresentationOptions: options];
+                }
+                Some(_) => {
+                    let () = msg_send![window, toggleFullScreen: nil];
+                }
+                None if is_fullscreen => {
+                    let () = msg_send![window, toggleFullScreen: nil];
+                }
+                None => (),
+            }
+        }
+    }
+
+    pub fn preferred_output(&self, _monitor: Option<MonitorId>) {
+        // macOS puts fullscreen windows on the screen that they are on.
+    }
+
+    pub(crate) fn set_kiosk(&self, options: KioskOptions) {
+        unsafe {
+            let view = self.nsview.load();
+            let view = match (*view).as_ref() {
+                Some(view) => view,
+                None => return,
+            };
+            let view_state: *mut c_void = *view.get_ivar("viewState");
+            let view_state = &mut *(view_state as *mut ViewState);
+            let old = std::mem::replace(&mut view_state.kiosk, options);
+            if options.block_system_shortcuts != old.block_system_shortcuts {
+                let window: id = msg_send![view, window];
+                if window
+                    .styleMask()
+                    .contains(NSWindowStyleMask::NSFullScreenWindowMask)
+                {
+                    let options = presentation_options(
+                        view_state.fullscreen_options,
+                        options.block_system_shortcuts,
+                    );
+                    let () = msg_send![NSApp(), setPresentationOptions: options];
+                }
+            }
+            if options.inhibit_screensaver != old.inhibit_screensaver {
+                let process_info: id = msg_send![class!(NSProcessInfo), processInfo];
+                if options.inhibit_screensaver {
+                    let activity_options =
+                        NSActivityIdleDisplaySleepDisabled | NSActivityUserInitiated;
+                    let activity: id = msg_send![process_info,
+                        beginActivityWithOptions: activity_options
+                        reason: make_nsstring("Kiosk mode")];
+                    let () = msg_send![activity, retain];
+                    view_state.kiosk_activity = activity;
+                } else if view_state.kiosk_activity != nil {
+                    let () = msg_send![process_info, endActivity: view_state.kiosk_activity];
+                    let () = msg_send![view_state.kiosk_activity, release];
+                    view_state.kiosk_activity = nil;
+                }
+            }
+            // Hiding and unhiding are counted, so this has to stay balanced.
+            if options.hide_cursor != old.hide_cursor {
+                if options.hide_cursor {
+                    let () = msg_send![class!(NSCursor), hide];
+                } else {
+                    let () = msg_send![class!(NSCursor), unhide];
+                }
+            }
+        }
+    }
+
     pub fn set_window_state(&mut self, state: WindowState) {
         let cur_state = self.get_window_state();
         unsafe {
@@ -1430,10 +2559,80 @@ impl WindowHandle {
         }
     }
 
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        // The input source is shared by the whole app on macOS.
+        keyboard::current_keyboard_layout()
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        Application::locale_info()
+    }
+
     /// Get the `Scale` of the window.
     pub fn get_scale(&self) -> Result<Scale, Error> {
-        let scale_factor: CGFloat = unsafe { msg_send![*self.nsview.load(), backingScaleFactor] };
-        Ok(Scale::new(scale_factor, scale_factor))
+        unsafe {
+            let view = self.nsview.load();
+            if let Some(view) = (*view).as_ref() {
+                let view_state: *mut c_void = *view.get_ivar("viewState");
+                let view_state = &*(view_state as *const ViewState);
+                if let Some(scale) = view_state.forced_scale {
+                    return Ok(scale);
+                }
+            }
+            let scale_factor: CGFloat = msg_send![*view, backingScaleFactor];
+            Ok(Scale::new(scale_factor, scale_factor))
+        }
+    }
+
+    pub fn text_scale_factor(&self) -> f64 {
+        unsafe {
+            // Text styles are new in macOS 11. We don't link against NSFontTextStyleBody, so
+            // that we still load on older versions; this is its value.
+            let font_class = class!(NSFont);
+            let supported: BOOL = msg_send![
+                font_class,
+                respondsToSelector: sel!(preferredFontForTextStyle:options:)
+            ];
+            if supported == NO {
+                return 1.0;
+            }
+            let style = NSString::alloc(nil)
+                .init_str("NSCTFontBodyUsage")
+                .autorelease();
+            let options: id = msg_send![class!(NSDictionary), dictionary];
+            let font: id = msg_send![font_class, preferredFontForTextStyle: style options: options];
+            if font.is_null() {
+                return 1.0;
+            }
+            let size: CGFloat = msg_send![font, pointSize];
+            // The body text style is 13 points by default.
+            size / 13.0
+        }
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        // "Increase contrast" changes how the system draws its controls, but it doesn't give
+        // applications a palette to use.
+        None
+    }
+
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        unsafe {
+            let scroller = class!(NSScroller);
+            // This follows the "Show scroll bars" setting, and whether a mouse is connected.
+            let style: NSInteger = msg_send![scroller, preferredScrollerStyle];
+            // Regular scrollers, which are the ones that scroll views use.
+            let control_size: NSUInteger = 0;
+            let width: CGFloat =
+                msg_send![scroller, scrollerWidthForControlSize: control_size scrollerStyle: style];
+            // AppKit doesn't say how short the knob gets, so this keeps the default.
+            ScrollbarMetrics {
+                width,
+                // NSScrollerStyleOverlay
+                overlay: style == 1,
+                ..ScrollbarMetrics::default()
+            }
+        }
     }
 
     #[cfg(feature = "accesskit")]
@@ -1516,6 +2715,19 @@ impl accesskit::ActionHandler for AccessKitActionHandler {
     }
 }
 
+impl ViewState {
+    fn end_composition(&mut self, committed: Option<&str>) {
+        if let Some(preedit) = self.preedit.take() {
+            let text = committed.map(str::to_owned).unwrap_or(preedit);
+            self.handler.composition(CompositionEvent {
+                state: CompositionState::End,
+                text,
+                cursor: None,
+            });
+        }
+    }
+}
+
 #[cfg(feature = "accesskit")]
 impl ViewState {
     fn get_or_init_accesskit_adapter(&mut self, view: &mut Object) -> &AccessKitAdapter {
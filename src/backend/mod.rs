@@ -16,66 +16,81 @@
 
 // It would be clearer to use cfg_if! macros here, but that breaks rustfmt.
 
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", not(feature = "headless")))]
 mod windows;
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", not(feature = "headless")))]
 pub use windows::*;
 
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "headless")))]
 mod mac;
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "headless")))]
 pub use mac::*;
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "headless")))]
 pub(crate) mod shared;
 
 #[cfg(all(
+    not(feature = "headless"),
     feature = "x11",
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 mod x11;
 #[cfg(all(
+    not(feature = "headless"),
     feature = "x11",
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 pub use x11::*;
 
 #[cfg(all(
+    not(feature = "headless"),
     feature = "wayland",
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 mod wayland;
 #[cfg(all(
+    not(feature = "headless"),
     feature = "wayland",
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 pub use wayland::*;
 
 #[cfg(all(
+    not(feature = "headless"),
     any(feature = "wayland", feature = "x11"),
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 pub(crate) mod shared;
 
 #[cfg(all(
+    not(feature = "headless"),
     not(feature = "x11"),
     not(feature = "wayland"),
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 mod gtk;
 #[cfg(all(
+    not(feature = "headless"),
     not(feature = "x11"),
     not(feature = "wayland"),
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 pub use self::gtk::*;
 #[cfg(all(
+    not(feature = "headless"),
     not(feature = "x11"),
     not(feature = "wayland"),
     any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
 ))]
 pub(crate) mod shared;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", not(feature = "headless")))]
 mod web;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", not(feature = "headless")))]
 pub use web::*;
+
+#[cfg(feature = "headless")]
+mod headless;
+#[cfg(feature = "headless")]
+pub use headless::*;
+#[cfg(feature = "headless")]
+pub(crate) mod shared;
@@ -1,3 +1,9 @@
+use std::ffi::CStr;
+
+use nix::libc;
+
+use crate::locale::LocaleInfo;
+
 pub fn locale() -> String {
     fn locale_env_var(var: &str) -> Option<String> {
         match std::env::var(var) {
@@ -37,3 +43,35 @@ pub fn locale() -> String {
     }
     locale
 }
+
+/// The user's formatting preferences, from the `LC_NUMERIC` and `LC_TIME` categories.
+pub fn locale_info() -> LocaleInfo {
+    let mut info = LocaleInfo::default();
+    let tag = locale().replace('_', "-");
+    if tag != "C" && tag != "POSIX" {
+        info.locale = tag;
+    }
+    // Rust doesn't call setlocale, so the process is in the POSIX locale, and we have to load
+    // the one from the environment ourselves.
+    unsafe {
+        let loc = libc::newlocale(
+            libc::LC_NUMERIC_MASK | libc::LC_TIME_MASK,
+            b"\0".as_ptr().cast(),
+            std::ptr::null_mut(),
+        );
+        if loc.is_null() {
+            tracing::warn!("locale: failed to load the locale from the environment");
+            return info;
+        }
+        let item = |item| {
+            CStr::from_ptr(libc::nl_langinfo_l(item, loc))
+                .to_string_lossy()
+                .into_owned()
+        };
+        info.decimal_separator = item(libc::RADIXCHAR);
+        info.grouping_separator = item(libc::THOUSEP);
+        info.apply_patterns(&item(libc::D_FMT), &item(libc::T_FMT));
+        libc::freelocale(loc);
+    }
+    info
+}
@@ -1,31 +1,156 @@
 use crate::TimerToken;
-use std::{cmp::Ordering, time::Instant};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    time::{Duration, Instant},
+};
 
 /// A timer is a deadline (`std::Time::Instant`) and a `TimerToken`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct Timer<T> {
     deadline: Instant,
+    /// The latest time that the timer may fire, so that nearby timers can share a wakeup.
+    latest: Instant,
+    /// How long after each deadline a repeating timer fires again.
+    interval: Option<Duration>,
     token: TimerToken,
     pub data: T,
 }
 
 impl<T> Timer<T> {
-    pub(crate) fn new(deadline: Instant, data: T) -> Self {
+    pub(crate) fn with_tolerance(deadline: Instant, tolerance: Duration, data: T) -> Self {
         let token = TimerToken::next();
         Self {
             deadline,
+            latest: deadline + tolerance,
+            interval: None,
             token,
             data,
         }
     }
 
+    /// A timer that first fires after `interval`, and then every `interval` until it is
+    /// cancelled.
+    pub(crate) fn repeating(interval: Duration, data: T) -> Self {
+        // A zero interval would fire again straight away, forever.
+        let interval = interval.max(Duration::from_millis(1));
+        let deadline = Instant::now() + interval;
+        Self {
+            interval: Some(interval),
+            ..Self::with_tolerance(deadline, Duration::ZERO, data)
+        }
+    }
+
     pub(crate) fn deadline(&self) -> Instant {
         self.deadline
     }
 
+    pub(crate) fn latest(&self) -> Instant {
+        self.latest
+    }
+
     pub(crate) fn token(&self) -> TimerToken {
         self.token
     }
+
+    /// The next run of a repeating timer that fired at `now`, with the same token.
+    ///
+    /// Runs that were missed, for instance while the machine was asleep, are skipped rather
+    /// than fired in a burst.
+    fn next_run(&self, now: Instant) -> Option<Self>
+    where
+        T: Clone,
+    {
+        let interval = self.interval?;
+        let mut deadline = self.deadline + interval;
+        if deadline <= now {
+            deadline = now + interval;
+        }
+        Some(Self {
+            deadline,
+            latest: deadline + (self.latest - self.deadline),
+            interval: self.interval,
+            token: self.token,
+            data: self.data.clone(),
+        })
+    }
+}
+
+/// The pending timers, earliest deadline first.
+///
+/// Cancelled timers stay in the heap until they reach the front, so that cancelling doesn't
+/// need to search for them.
+pub(crate) struct TimerQueue<T> {
+    heap: BinaryHeap<Timer<T>>,
+    /// The tokens of the timers in `heap` that haven't been cancelled.
+    live: HashSet<TimerToken>,
+}
+
+impl<T: Clone + Eq> TimerQueue<T> {
+    pub(crate) fn new() -> Self {
+        TimerQueue {
+            heap: BinaryHeap::new(),
+            live: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, timer: Timer<T>) {
+        self.live.insert(timer.token());
+        self.heap.push(timer);
+    }
+
+    /// Stops a timer from firing. Returns `false` if there's no such timer pending.
+    pub(crate) fn cancel(&mut self, token: TimerToken) -> bool {
+        let cancelled = self.live.remove(&token);
+        self.drop_cancelled();
+        cancelled
+    }
+
+    /// Removes the earliest timer if its deadline has passed, and schedules its next run if it
+    /// repeats.
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<Timer<T>> {
+        if self.heap.peek()?.deadline() > now {
+            return None;
+        }
+        let timer = self.heap.pop().unwrap();
+        match timer.next_run(now) {
+            Some(next) => self.heap.push(next),
+            None => {
+                self.live.remove(&timer.token());
+            }
+        }
+        self.drop_cancelled();
+        Some(timer)
+    }
+
+    /// When to wake up for the pending timers. See [`next_wakeup`].
+    pub(crate) fn next_wakeup(&self) -> Option<Instant> {
+        next_wakeup(self.iter())
+    }
+
+    /// The timers that haven't been cancelled, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Timer<T>> {
+        self.heap
+            .iter()
+            .filter(move |timer| self.live.contains(&timer.token()))
+    }
+
+    /// Keeps a cancelled timer from being the earliest one.
+    fn drop_cancelled(&mut self) {
+        while matches!(self.heap.peek(), Some(timer) if !self.live.contains(&timer.token())) {
+            self.heap.pop();
+        }
+    }
+}
+
+/// When to wake up for the given timers.
+///
+/// Waking up at the earliest of their latest times, and then running every timer whose deadline
+/// has passed, runs timers that tolerate some lateness together with the ones around them.
+pub(crate) fn next_wakeup<'a, T: 'a>(
+    timers: impl IntoIterator<Item = &'a Timer<T>>,
+) -> Option<Instant> {
+    timers.into_iter().map(Timer::latest).min()
 }
 
 impl<T: Eq + PartialEq> Ord for Timer<T> {
@@ -42,3 +167,44 @@ impl<T: Eq + PartialEq> PartialOrd for Timer<T> {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerant_timers_share_wakeup() {
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        let timers = [
+            Timer::with_tolerance(now + ms(10), ms(50), ()),
+            Timer::with_tolerance(now + ms(30), Duration::ZERO, ()),
+            Timer::with_tolerance(now + ms(40), ms(5), ()),
+        ];
+        // The first timer can wait for the second one.
+        assert_eq!(next_wakeup(&timers), Some(now + ms(30)));
+        assert_eq!(next_wakeup(&timers[2..]), Some(now + ms(45)));
+        assert_eq!(next_wakeup::<()>(&[]), None);
+    }
+
+    #[test]
+    fn repeating_and_cancelled_timers() {
+        let mut queue = TimerQueue::new();
+        let repeating = Timer::repeating(Duration::from_millis(10), ());
+        let once = Timer::with_tolerance(repeating.deadline(), Duration::ZERO, ());
+        queue.push(repeating);
+        queue.push(once);
+        let now = repeating.deadline();
+        assert!(queue.cancel(once.token()));
+        assert!(!queue.cancel(once.token()));
+        let fired = queue.pop_expired(now).unwrap();
+        assert_eq!(fired.token(), repeating.token());
+        // The cancelled timer doesn't fire, and the repeating one waits for its next run.
+        assert!(queue.pop_expired(now).is_none());
+        let next = queue.iter().next().unwrap();
+        assert_eq!(next.token(), repeating.token());
+        assert_eq!(next.deadline(), now + Duration::from_millis(10));
+        assert!(queue.cancel(repeating.token()));
+        assert_eq!(queue.next_wakeup(), None);
+    }
+}
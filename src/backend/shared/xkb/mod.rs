@@ -18,10 +18,12 @@ mod keycodes;
 mod xkbcommon_sys;
 use crate::{
     backend::shared::{code_to_location, hardware_keycode_to_code},
+    platform::linux::{XkbKeymap, XkbRuleNames},
     KeyEvent, KeyState, Modifiers,
 };
 use keyboard_types::{Code, Key};
 use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::ptr;
 use xkbcommon_sys::*;
@@ -30,6 +32,7 @@ use xkbcommon_sys::*;
 use x11rb::xcb_ffi::XCBConnection;
 
 #[cfg(feature = "x11")]
+#[derive(Clone, Copy)]
 pub struct DeviceId(std::os::raw::c_int);
 
 /// A global xkb context object.
@@ -100,6 +103,63 @@ impl Context {
         }
     }
 
+    /// Create a keymap from the xkb text format.
+    ///
+    /// Returns `None` if the keymap doesn't compile.
+    pub fn keymap_from_string(&self, text: &str) -> Option<Keymap> {
+        let text = CString::new(text).ok()?;
+        let keymap = unsafe {
+            xkb_keymap_new_from_string(
+                self.0,
+                text.as_ptr(),
+                XKB_KEYMAP_FORMAT_TEXT_V1,
+                XKB_KEYMAP_COMPILE_NO_FLAGS,
+            )
+        };
+        if keymap.is_null() {
+            return None;
+        }
+        Some(Keymap(keymap))
+    }
+
+    /// Create a keymap from its rules, model, layout, variant and options.
+    ///
+    /// Returns `None` if the keymap doesn't compile.
+    pub fn keymap_from_names(&self, names: &XkbRuleNames) -> Option<Keymap> {
+        // Empty names are passed as null, so that xkb fills in its defaults.
+        let name = |s: &str| match s {
+            "" => Ok(None),
+            s => CString::new(s).map(Some),
+        };
+        let rules = name(&names.rules).ok()?;
+        let model = name(&names.model).ok()?;
+        let layout = name(&names.layout).ok()?;
+        let variant = name(&names.variant).ok()?;
+        let options = name(&names.options).ok()?;
+        let ptr = |s: &Option<CString>| s.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+        let names = xkb_rule_names {
+            rules: ptr(&rules),
+            model: ptr(&model),
+            layout: ptr(&layout),
+            variant: ptr(&variant),
+            options: ptr(&options),
+        };
+        let keymap =
+            unsafe { xkb_keymap_new_from_names(self.0, &names, XKB_KEYMAP_COMPILE_NO_FLAGS) };
+        if keymap.is_null() {
+            return None;
+        }
+        Some(Keymap(keymap))
+    }
+
+    /// Create a keymap that the application asked for.
+    pub fn compile(&self, keymap: &XkbKeymap) -> Option<Keymap> {
+        match keymap {
+            XkbKeymap::Names(names) => self.keymap_from_names(names),
+            XkbKeymap::Text(text) => self.keymap_from_string(text),
+        }
+    }
+
     /// Set the log level using `tracing` levels.
     ///
     /// Because `xkb` has a `critical` error, each rust error maps to 1 above (e.g. error ->
@@ -140,6 +200,36 @@ impl Keymap {
     pub fn state(&self) -> State {
         State::new(self)
     }
+
+    /// The current state of `device`, which the server then reports to
+    /// [`State::update_mask`].
+    #[cfg(feature = "x11")]
+    pub fn state_from_device(&self, conn: &XCBConnection, device: DeviceId) -> Option<State> {
+        let state = unsafe {
+            xkb_x11_state_new_from_device(
+                self.0,
+                conn.get_raw_xcb_connection() as *mut xcb_connection_t,
+                device.0,
+            )
+        };
+        if state.is_null() {
+            return None;
+        }
+        Some(State::with_state(self, state))
+    }
+
+    /// The keymap in the xkb text format.
+    pub fn as_string(&self) -> Option<String> {
+        unsafe {
+            let text = xkb_keymap_get_as_string(self.0, XKB_KEYMAP_FORMAT_TEXT_V1);
+            if text.is_null() {
+                return None;
+            }
+            let string = CStr::from_ptr(text).to_string_lossy().into_owned();
+            nix::libc::free(text as *mut _);
+            Some(string)
+        }
+    }
 }
 
 impl Clone for Keymap {
@@ -173,8 +263,11 @@ pub struct ModsIndices {
 
 impl State {
     pub fn new(keymap: &Keymap) -> Self {
+        Self::with_state(keymap, unsafe { xkb_state_new(keymap.0) })
+    }
+
+    fn with_state(keymap: &Keymap, state: *mut xkb_state) -> Self {
         let keymap = keymap.0;
-        let state = unsafe { xkb_state_new(keymap) };
         let mod_idx = |str: &'static [u8]| unsafe {
             xkb_keymap_mod_get_index(keymap, str.as_ptr() as *mut c_char)
         };
@@ -191,11 +284,34 @@ impl State {
         }
     }
 
+    /// Builds the event for a key, and updates the state with it.
+    ///
+    /// This is for when we keep track of the keyboard state ourselves, so it mustn't be mixed
+    /// with [`update_mask`](State::update_mask).
+    #[cfg(feature = "wayland")]
     pub fn key_event(&mut self, scancode: u32, state: KeyState, repeat: bool) -> KeyEvent {
+        self.make_key_event(scancode, state, repeat, true)
+    }
+
+    /// Builds the event for a key, in the state that the server last reported to
+    /// [`update_mask`](State::update_mask).
+    #[cfg(feature = "x11")]
+    pub fn server_key_event(&mut self, scancode: u32, state: KeyState, repeat: bool) -> KeyEvent {
+        self.make_key_event(scancode, state, repeat, false)
+    }
+
+    fn make_key_event(
+        &mut self,
+        scancode: u32,
+        state: KeyState,
+        repeat: bool,
+        update: bool,
+    ) -> KeyEvent {
         let code = u16::try_from(scancode)
             .map(hardware_keycode_to_code)
             .unwrap_or(Code::Unidentified);
-        let key = self.get_logical_key(scancode);
+        let keysym = self.key_get_one_sym(scancode);
+        let key = self.get_logical_key(scancode, keysym);
         // TODO this is lazy - really should use xkb i.e. augment the get_logical_key method.
         let location = code_to_location(code);
 
@@ -205,7 +321,7 @@ impl State {
         let mut mods = Modifiers::empty();
         // Update xkb's state (e.g. return capitals if we've pressed shift)
         unsafe {
-            if !repeat {
+            if update && !repeat {
                 xkb_state_update_key(
                     self.state,
                     scancode,
@@ -241,11 +357,71 @@ impl State {
             mods,
             repeat,
             is_composing,
+            xkb_keysym: Some(keysym),
+        }
+    }
+
+    /// Replace the modifier and layout state with the one reported by the server.
+    ///
+    /// The depressed and latched layouts are offsets, which can be negative. Once the server
+    /// reports the state, keys must only be looked up with
+    /// [`server_key_event`](State::server_key_event).
+    #[cfg(feature = "x11")]
+    pub fn update_mask(
+        &mut self,
+        depressed_mods: u32,
+        latched_mods: u32,
+        locked_mods: u32,
+        depressed_layout: i32,
+        latched_layout: i32,
+        locked_layout: u32,
+    ) {
+        unsafe {
+            // xkbcommon takes the offsets as unsigned, and reads them back as signed.
+            xkb_state_update_mask(
+                self.state,
+                depressed_mods,
+                latched_mods,
+                locked_mods,
+                depressed_layout as u32,
+                latched_layout as u32,
+                locked_layout,
+            );
+        }
+    }
+
+    /// The keymap that this state is for.
+    #[cfg(feature = "x11")]
+    pub fn keymap(&self) -> Keymap {
+        Keymap(unsafe { xkb_keymap_ref(xkb_state_get_keymap(self.state)) })
+    }
+
+    /// The index of the layout that is currently in effect.
+    #[cfg(feature = "x11")]
+    pub fn active_layout(&self) -> u32 {
+        unsafe { xkb_state_serialize_layout(self.state, XKB_STATE_LAYOUT_EFFECTIVE) }
+    }
+
+    /// The number of layouts in the keymap.
+    #[cfg(feature = "x11")]
+    pub fn num_layouts(&self) -> u32 {
+        unsafe { xkb_keymap_num_layouts(xkb_state_get_keymap(self.state)) }
+    }
+
+    /// The name of the layout with the given index, e.g. "English (US)".
+    #[cfg(feature = "x11")]
+    pub fn layout_name(&self, idx: u32) -> Option<String> {
+        unsafe {
+            let name = xkb_keymap_layout_get_name(xkb_state_get_keymap(self.state), idx);
+            if name.is_null() {
+                return None;
+            }
+            Some(CStr::from_ptr(name).to_string_lossy().into_owned())
         }
     }
 
-    fn get_logical_key(&mut self, scancode: u32) -> Key {
-        let mut key = keycodes::map_key(self.key_get_one_sym(scancode));
+    fn get_logical_key(&mut self, scancode: u32, keysym: u32) -> Key {
+        let mut key = keycodes::map_key(keysym);
         if matches!(key, Key::Unidentified) {
             if let Some(s) = self.key_get_utf8(scancode) {
                 key = Key::Character(s);
@@ -15,75 +15,58 @@
 #![allow(clippy::single_match)]
 
 use super::{
-    clipboard, display, error::Error, events::WaylandSource, keyboard, outputs, pointers, surfaces,
-    window::WindowHandle,
+    clipboard, display, dnd, error::Error, events::WaylandSource, keyboard, outputs, pointers,
+    protocols::cursor_shape_v1::client::wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
+    protocols::fractional_scale_v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+    surfaces, text_input::TextInput, window::WindowHandle,
 };
 
-use crate::{backend, mouse, AppHandler, TimerToken};
+use crate::{
+    mouse, AppHandler, DispatchPolicy, KeyboardLayout, LocaleInfo, MediaCommand, SensorEvent,
+    SensorKind, TimerToken,
+};
 
 use calloop;
 
 use std::{
     cell::{Cell, RefCell},
-    collections::{BTreeMap, BinaryHeap},
+    collections::BTreeMap,
     rc::Rc,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
-use crate::backend::shared::linux;
+use crate::backend::shared::{linux, xkb, TimerQueue};
+use crate::common_util::IdleTracker;
+use crate::platform::linux::XkbKeymap;
 use wayland_client::protocol::wl_keyboard::WlKeyboard;
 use wayland_client::protocol::wl_registry;
 use wayland_client::{
     self as wl,
     protocol::{
         wl_compositor::WlCompositor,
-        wl_pointer::WlPointer,
+        wl_pointer::{self, WlPointer},
+        wl_region::WlRegion,
         wl_seat::{self, WlSeat},
         wl_shm::{self, WlShm},
+        wl_subcompositor::WlSubcompositor,
         wl_surface::WlSurface,
     },
 };
-use wayland_cursor::CursorTheme;
+use wayland_protocols::unstable::idle_inhibit::v1::client::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1;
+use wayland_protocols::unstable::keyboard_shortcuts_inhibit::v1::client::zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1;
+use wayland_protocols::presentation_time::client::wp_presentation::{self, WpPresentation};
+use wayland_protocols::unstable::pointer_constraints::v1::client::zwp_pointer_constraints_v1::ZwpPointerConstraintsV1;
+use wayland_protocols::unstable::relative_pointer::v1::client::zwp_relative_pointer_manager_v1::ZwpRelativePointerManagerV1;
+use wayland_protocols::unstable::text_input::v3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3;
+use wayland_protocols::unstable::xdg_decoration::v1::client::zxdg_decoration_manager_v1::ZxdgDecorationManagerV1;
+use wayland_protocols::unstable::xdg_foreign::v2::client::zxdg_exporter_v2::ZxdgExporterV2;
+use wayland_protocols::unstable::xdg_foreign::v2::client::zxdg_importer_v2::ZxdgImporterV2;
+use wayland_protocols::viewporter::client::wp_viewporter::WpViewporter;
 use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_shell_v1::ZwlrLayerShellV1;
 use wayland_protocols::xdg_shell::client::xdg_positioner::XdgPositioner;
 use wayland_protocols::xdg_shell::client::xdg_surface;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) struct Timer(backend::shared::Timer<u64>);
-
-impl Timer {
-    pub(crate) fn new(id: u64, deadline: Instant) -> Self {
-        Self(backend::shared::Timer::new(deadline, id))
-    }
-
-    pub(crate) fn id(self) -> u64 {
-        self.0.data
-    }
-
-    pub(crate) fn deadline(&self) -> Instant {
-        self.0.deadline()
-    }
-
-    pub fn token(&self) -> TimerToken {
-        self.0.token()
-    }
-}
-
-impl std::cmp::Ord for Timer {
-    /// Ordering is so that earliest deadline sorts first
-    // "Earliest deadline first" that a std::collections::BinaryHeap will have the earliest timer
-    // at its head, which is just what is needed for timer management.
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.deadline().cmp(&other.0.deadline()).reverse()
-    }
-}
-
-impl std::cmp::PartialOrd for Timer {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 #[derive(Clone)]
 pub struct Application {
     pub(super) data: std::sync::Arc<Data>,
@@ -93,7 +76,23 @@ pub struct Application {
 pub(crate) struct Data {
     pub(super) wayland: std::rc::Rc<display::Environment>,
     pub(super) zwlr_layershell_v1: Option<wl::Main<ZwlrLayerShellV1>>,
+    pub(super) zxdg_exporter_v2: Option<wl::Main<ZxdgExporterV2>>,
+    pub(super) zxdg_importer_v2: Option<wl::Main<ZxdgImporterV2>>,
+    pub(super) zwp_pointer_constraints_v1: Option<wl::Main<ZwpPointerConstraintsV1>>,
+    pub(super) zwp_relative_pointer_manager_v1: Option<wl::Main<ZwpRelativePointerManagerV1>>,
+    pub(super) wp_viewporter: Option<wl::Main<WpViewporter>>,
+    pub(super) wp_fractional_scale_manager_v1: Option<wl::Main<WpFractionalScaleManagerV1>>,
+    pub(super) zwp_text_input_manager_v3: Option<wl::Main<ZwpTextInputManagerV3>>,
+    pub(super) zxdg_decoration_manager_v1: Option<wl::Main<ZxdgDecorationManagerV1>>,
+    pub(super) zwp_idle_inhibit_manager_v1: Option<wl::Main<ZwpIdleInhibitManagerV1>>,
+    pub(super) zwp_keyboard_shortcuts_inhibit_manager_v1:
+        Option<wl::Main<ZwpKeyboardShortcutsInhibitManagerV1>>,
+    pub(super) wp_presentation: Option<wl::Main<WpPresentation>>,
+    /// The clock that `wp_presentation` reports times in.
+    pub(super) presentation_clock: Cell<Option<u32>>,
     pub(super) wl_compositor: wl::Main<WlCompositor>,
+    /// Needed for the decorations we draw when the compositor won't.
+    pub(super) wl_subcompositor: Option<wl::Main<WlSubcompositor>>,
     pub(super) wl_shm: wl::Main<WlShm>,
     /// A map of wayland object IDs to outputs.
     ///
@@ -119,23 +118,42 @@ pub(crate) struct Data {
     pub(super) timer_handle: calloop::timer::TimerHandle<TimerToken>,
     /// We stuff this here until the event loop, then `take` it and use it.
     timer_source: RefCell<Option<calloop::timer::Timer<TimerToken>>>,
+    /// Wakes the event loop up, when there is idle work to do.
+    pub(super) idle_ping: calloop::ping::Ping,
+    /// We stuff this here until the event loop, then `take` it and use it.
+    idle_ping_source: RefCell<Option<calloop::ping::PingSource>>,
+    /// Tracks our input, to tell the `AppHandler` when the application goes idle.
+    pub(super) idle: IdleTracker,
+    /// Wakes the event loop up when the application would go idle.
+    idle_timer: calloop::timer::TimerHandle<()>,
+    /// We stuff this here until the event loop, then `take` it and use it.
+    idle_timer_source: RefCell<Option<calloop::timer::Timer<()>>>,
+    /// The wakeup that is scheduled on `idle_timer`, and when it is for.
+    idle_check: RefCell<Option<(calloop::timer::Timeout, Instant)>>,
     /// Currently pending timers
     ///
     /// The extra data is the surface this timer is for.
-    pub(super) timers: RefCell<BinaryHeap<Timer>>,
+    pub(super) timers: RefCell<TimerQueue<u64>>,
 
     pub(super) roundtrip_requested: RefCell<bool>,
-
-    /// track if the display was flushed during the event loop.
-    /// prevents double flushing unnecessarily.
-    pub(super) display_flushed: RefCell<bool>,
     /// reference to the pointer events manager.
     pub(super) pointer: pointers::Pointer,
     /// reference to the keyboard events manager.
     keyboard: keyboard::Manager,
-    clipboard: clipboard::Manager,
+    pub(super) clipboard: clipboard::Manager,
+    primary: clipboard::Manager,
+    pub(super) dnd: dnd::State,
     // wakeup events when outputs are added/removed.
     outputsqueue: RefCell<Option<calloop::channel::Channel<outputs::Event>>>,
+    /// Commands from the desktop's media controls, which arrive on the D-Bus thread and are
+    /// delivered to the `AppHandler` after the idle work.
+    media_commands: Arc<Mutex<Vec<MediaCommand>>>,
+    /// Readings from the enabled sensors, which arrive on their own threads and are delivered
+    /// to the `AppHandler` after the idle work.
+    sensor_events: Arc<Mutex<Vec<SensorEvent>>>,
+    /// The sensors that the application enabled.
+    #[cfg(feature = "dbus")]
+    sensors: crate::dbus::Sensors,
 }
 
 impl Application {
@@ -209,7 +227,7 @@ impl Application {
 
         let zwlr_layershell_v1 = env
             .registry
-            .instantiate_exact::<ZwlrLayerShellV1>(1)
+            .instantiate_range::<ZwlrLayerShellV1>(1, 4)
             .map_or_else(
                 |e| {
                     tracing::info!("unable to instantiate layershell {:?}", e);
@@ -218,6 +236,120 @@ impl Application {
                 Some,
             );
 
+        let zxdg_exporter_v2 = env
+            .registry
+            .instantiate_exact::<ZxdgExporterV2>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate xdg-foreign exporter {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zxdg_importer_v2 = env
+            .registry
+            .instantiate_exact::<ZxdgImporterV2>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate xdg-foreign importer {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zwp_pointer_constraints_v1 = env
+            .registry
+            .instantiate_exact::<ZwpPointerConstraintsV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate pointer constraints {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zwp_relative_pointer_manager_v1 = env
+            .registry
+            .instantiate_exact::<ZwpRelativePointerManagerV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate relative pointer manager {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let wp_viewporter = env
+            .registry
+            .instantiate_exact::<WpViewporter>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate viewporter {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let wp_presentation = env
+            .registry
+            .instantiate_exact::<WpPresentation>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate presentation time {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let wp_fractional_scale_manager_v1 = env
+            .registry
+            .instantiate_exact::<WpFractionalScaleManagerV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate fractional scale manager {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zwp_text_input_manager_v3 = env
+            .registry
+            .instantiate_exact::<ZwpTextInputManagerV3>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate text input manager {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zxdg_decoration_manager_v1 = env
+            .registry
+            .instantiate_exact::<ZxdgDecorationManagerV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate decoration manager {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zwp_idle_inhibit_manager_v1 = env
+            .registry
+            .instantiate_exact::<ZwpIdleInhibitManagerV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate idle inhibit manager {:?}", e);
+                    None
+                },
+                Some,
+            );
+        let zwp_keyboard_shortcuts_inhibit_manager_v1 = env
+            .registry
+            .instantiate_exact::<ZwpKeyboardShortcutsInhibitManagerV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!(
+                        "unable to instantiate keyboard shortcuts inhibit manager {:?}",
+                        e
+                    );
+                    None
+                },
+                Some,
+            );
+
         let wl_compositor = env
             .registry
             .instantiate_range::<WlCompositor>(1, 5)
@@ -226,21 +358,68 @@ impl Application {
             .registry
             .instantiate_exact::<WlShm>(1)
             .map_err(|e| Error::global("wl_shm", 1, e))?;
+        let wl_subcompositor = env
+            .registry
+            .instantiate_exact::<WlSubcompositor>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate subcompositor {:?}", e);
+                    None
+                },
+                Some,
+            );
 
         let timer_source = calloop::timer::Timer::new().unwrap();
         let timer_handle = timer_source.handle();
+        let (idle_ping, idle_ping_source) = calloop::ping::make_ping().unwrap();
+        let idle_timer_source = calloop::timer::Timer::new().unwrap();
+        let sensor_events: Arc<Mutex<Vec<SensorEvent>>> = Default::default();
+        #[cfg(feature = "dbus")]
+        let sensors = crate::dbus::Sensors::new({
+            let queue = sensor_events.clone();
+            let idle_ping = idle_ping.clone();
+            Arc::new(move |event| {
+                queue.lock().unwrap().push(event);
+                idle_ping.ping();
+            })
+        });
 
-        // TODO the cursor theme size needs more refinement, it should probably be the size needed to
-        // draw sharp cursors on the largest scaled monitor.
+        let wp_cursor_shape_manager_v1 = env
+            .registry
+            .instantiate_exact::<WpCursorShapeManagerV1>(1)
+            .map_or_else(
+                |e| {
+                    tracing::info!("unable to instantiate cursor shape manager {:?}", e);
+                    None
+                },
+                Some,
+            );
         let pointer = pointers::Pointer::new(
-            CursorTheme::load(64, &wl_shm),
+            (*wl_shm).clone(),
+            wp_cursor_shape_manager_v1,
             wl_compositor.create_surface(),
         );
 
         // We need to have keyboard events set up for our seats before the next roundtrip.
+        let clipboard = clipboard::Manager::new(&env.display, &env.registry)?;
+        let primary = clipboard::Manager::primary(&env.display, &env.registry, &clipboard);
+
         let appdata = std::sync::Arc::new(Data {
             zwlr_layershell_v1,
+            zxdg_exporter_v2,
+            zxdg_importer_v2,
+            zwp_pointer_constraints_v1,
+            zwp_relative_pointer_manager_v1,
+            wp_viewporter,
+            wp_fractional_scale_manager_v1,
+            zwp_text_input_manager_v3,
+            zxdg_decoration_manager_v1,
+            zwp_idle_inhibit_manager_v1,
+            zwp_keyboard_shortcuts_inhibit_manager_v1,
+            wp_presentation,
+            presentation_clock: Cell::new(None),
             wl_compositor,
+            wl_subcompositor,
             wl_shm: wl_shm.clone(),
             outputs: Rc::new(RefCell::new(BTreeMap::new())),
             seats,
@@ -250,13 +429,24 @@ impl Application {
             active_surface_id: RefCell::new(std::collections::VecDeque::with_capacity(20)),
             timer_handle,
             timer_source: RefCell::new(Some(timer_source)),
-            timers: RefCell::new(BinaryHeap::new()),
-            display_flushed: RefCell::new(false),
+            idle_ping,
+            idle_ping_source: RefCell::new(Some(idle_ping_source)),
+            idle: IdleTracker::default(),
+            idle_timer: idle_timer_source.handle(),
+            idle_timer_source: RefCell::new(Some(idle_timer_source)),
+            idle_check: RefCell::new(None),
+            timers: RefCell::new(TimerQueue::new()),
             pointer,
             keyboard: keyboard::Manager::default(),
-            clipboard: clipboard::Manager::new(&env.display, &env.registry)?,
+            clipboard,
+            primary,
+            dnd: dnd::State::default(),
             roundtrip_requested: RefCell::new(false),
             outputsqueue: RefCell::new(Some(outputqueue)),
+            media_commands: Default::default(),
+            sensor_events,
+            #[cfg(feature = "dbus")]
+            sensors,
             wayland: std::rc::Rc::new(env),
         });
 
@@ -269,6 +459,28 @@ impl Application {
             }
         }));
 
+        if let Some(presentation) = &appdata.wp_presentation {
+            presentation.quick_assign({
+                let appdata = std::sync::Arc::downgrade(&appdata);
+                move |_, event, _| {
+                    if let wp_presentation::Event::ClockId { clk_id } = event {
+                        if let Some(appdata) = appdata.upgrade() {
+                            appdata.presentation_clock.set(Some(clk_id));
+                        }
+                    }
+                }
+            });
+        }
+
+        appdata.clipboard.set_drag_handler(Box::new({
+            let appdata = std::sync::Arc::downgrade(&appdata);
+            move |clipboard, event| {
+                if let Some(appdata) = appdata.upgrade() {
+                    appdata.dnd.handle_event(&appdata, clipboard, event);
+                }
+            }
+        }));
+
         // Setup seat event listeners with our application
         for (id, seat) in appdata.seats.borrow().iter() {
             let id = *id; // move into closure.
@@ -277,13 +489,17 @@ impl Application {
                 tracing::debug!("seat events {:?} {:?} {:?}", d1, event, d3);
                 let mut seat = seat.borrow_mut();
                 appdata.clipboard.attach(&mut seat);
+                appdata.primary.attach(&mut seat);
                 match event {
                     wl_seat::Event::Capabilities { capabilities } => {
                         seat.capabilities = capabilities;
                         if capabilities.contains(wl_seat::Capability::Keyboard)
                             && seat.keyboard.is_none()
                         {
-                            seat.keyboard = Some(appdata.keyboard.attach(id, seat.wl_seat.clone()));
+                            seat.keyboard = Some(appdata.keyboard.attach(id, seat.wl_seat.clone(), appdata.clipboard.clone()));
+                            if let Some(manager) = &appdata.zwp_text_input_manager_v3 {
+                                seat.text_input = Some(TextInput::new(manager, &seat.wl_seat, &appdata));
+                            }
                         }
                         if capabilities.contains(wl_seat::Capability::Pointer)
                             && seat.pointer.is_none()
@@ -292,8 +508,17 @@ impl Application {
                             appdata.pointer.attach(pointer.detach());
                             pointer.quick_assign({
                                 let app = appdata.clone();
+                                let wl_seat = seat.wl_seat.detach();
                                 move |pointer, event, _| {
-                                    pointers::Pointer::consume(app.clone(), pointer.detach(), event);
+                                    if matches!(
+                                        event,
+                                        wl_pointer::Event::Motion { .. }
+                                            | wl_pointer::Event::Button { .. }
+                                            | wl_pointer::Event::Axis { .. }
+                                    ) {
+                                        app.idle.input();
+                                    }
+                                    pointers::Pointer::consume(app.clone(), &wl_seat, pointer.detach(), event);
                                 }
                             });
                             seat.pointer = Some(pointer);
@@ -321,11 +546,14 @@ impl Application {
         Ok(Application { data: appdata })
     }
 
-    pub fn run(mut self, _handler: Option<Box<dyn AppHandler>>) {
+    pub fn run(mut self, handler: Option<Box<dyn AppHandler>>) {
         tracing::info!("wayland event loop initiated");
+        let handler = Rc::new(RefCell::new(handler));
         // NOTE if we want to call this function more than once, we will need to put the timer
         // source back.
         let timer_source = self.data.timer_source.borrow_mut().take().unwrap();
+        let idle_ping_source = self.data.idle_ping_source.borrow_mut().take().unwrap();
+        let idle_timer_source = self.data.idle_timer_source.borrow_mut().take().unwrap();
         // flush pending events (otherwise anything we submitted since sync will never be sent)
         self.data.wayland.display.flush().unwrap();
 
@@ -340,27 +568,37 @@ impl Application {
         handle.register_dispatcher(wayland_dispatcher).unwrap();
         handle
             .insert_source(self.data.outputsqueue.take().unwrap(), {
+                let handler = handler.clone();
                 move |evt, _ignored, appdata| match evt {
                     calloop::channel::Event::Closed => {}
-                    calloop::channel::Event::Msg(output) => match output {
-                        outputs::Event::Located(output) => {
-                            tracing::debug!("output added {:?} {:?}", output.gid, output.id());
-                            appdata
-                                .outputs
-                                .borrow_mut()
-                                .insert(output.id(), output.clone());
-                            for (_, win) in appdata.handles_iter() {
-                                surfaces::Outputs::inserted(&win, &output);
+                    calloop::channel::Event::Msg(output) => {
+                        match output {
+                            outputs::Event::Located(output) => {
+                                tracing::debug!("output added {:?} {:?}", output.gid, output.id());
+                                appdata
+                                    .outputs
+                                    .borrow_mut()
+                                    .insert(output.id(), output.clone());
+                                for (_, win) in appdata.handles_iter() {
+                                    surfaces::Outputs::inserted(&win, &output);
+                                }
                             }
-                        }
-                        outputs::Event::Removed(output) => {
-                            tracing::debug!("output removed {:?} {:?}", output.gid, output.id());
-                            appdata.outputs.borrow_mut().remove(&output.id());
-                            for (_, win) in appdata.handles_iter() {
-                                surfaces::Outputs::removed(&win, &output);
+                            outputs::Event::Removed(output) => {
+                                tracing::debug!(
+                                    "output removed {:?} {:?}",
+                                    output.gid,
+                                    output.id()
+                                );
+                                appdata.outputs.borrow_mut().remove(&output.id());
+                                for (_, win) in appdata.handles_iter() {
+                                    surfaces::Outputs::removed(&win, &output);
+                                }
                             }
                         }
-                    },
+                        if let Some(handler) = handler.borrow_mut().as_mut() {
+                            handler.monitors_changed();
+                        }
+                    }
                 }
             })
             .unwrap();
@@ -372,10 +610,29 @@ impl Application {
             })
             .unwrap();
 
+        // The idle work itself is done after every dispatch, below; this only wakes us up.
+        handle
+            .insert_source(idle_ping_source, |_, _, _| {})
+            .unwrap();
+        handle
+            .insert_source(idle_timer_source, |_, _, _| {})
+            .unwrap();
+
+        if let Some(animation_source) = self.data.pointer.take_animation_source() {
+            handle
+                .insert_source(animation_source, |_, _, appdata| {
+                    appdata.pointer.animate();
+                    if let Err(cause) = appdata.wayland.display.flush() {
+                        tracing::warn!("unable to flush display: {:?}", cause);
+                    }
+                })
+                .unwrap();
+        }
+
         let signal = eventloop.get_signal();
-        let handle = handle.clone();
 
-        let res = eventloop.run(Duration::from_millis(20), &mut self.data, move |appdata| {
+        // We sleep until something happens: a wayland event, a timer, or idle work.
+        let res = eventloop.run(None, &mut self.data, move |appdata| {
             if appdata.shutdown.get() {
                 tracing::debug!("shutting down, requested");
                 signal.stop();
@@ -388,7 +645,20 @@ impl Application {
                 return;
             }
 
-            Data::idle_repaint(handle.clone());
+            appdata.run_idle();
+
+            let commands = std::mem::take(&mut *appdata.media_commands.lock().unwrap());
+            let sensor_events = std::mem::take(&mut *appdata.sensor_events.lock().unwrap());
+            if let Some(handler) = handler.borrow_mut().as_mut() {
+                for command in commands {
+                    handler.media_command(command);
+                }
+                for event in sensor_events {
+                    handler.sensor(event);
+                }
+                appdata.idle.dispatch(&mut **handler);
+            }
+            appdata.schedule_idle_check();
         });
 
         match res {
@@ -399,15 +669,86 @@ impl Application {
 
     pub fn quit(&self) {
         self.data.shutdown.set(true);
+        self.data.idle_ping.ping();
     }
 
     pub fn clipboard(&self) -> clipboard::Clipboard {
         clipboard::Clipboard::from(&self.data.clipboard)
     }
 
+    pub fn primary_clipboard(&self) -> clipboard::Clipboard {
+        clipboard::Clipboard::from(&self.data.primary)
+    }
+
+    pub fn xkb_keymap(&self) -> Option<String> {
+        self.data.keyboard.keymap()
+    }
+
+    pub fn set_xkb_keymap(&self, keymap: Option<XkbKeymap>) -> bool {
+        // Keys are handled on another thread, which can't share our xkb objects, so we check
+        // that the keymap compiles here and hand it over as text.
+        let text = match keymap {
+            Some(keymap) => match xkb::Context::new()
+                .compile(&keymap)
+                .and_then(|keymap| keymap.as_string())
+            {
+                Some(text) => Some(text),
+                None => return false,
+            },
+            None => None,
+        };
+        self.data.keyboard.set_keymap(text);
+        true
+    }
+
+    #[cfg(feature = "dbus")]
+    pub fn enable_sensor(&self, kind: SensorKind) -> bool {
+        self.data.sensors.enable(kind)
+    }
+
+    #[cfg(not(feature = "dbus"))]
+    pub fn enable_sensor(&self, _kind: SensorKind) -> bool {
+        tracing::warn!("Application::enable_sensor needs the dbus feature on Wayland.");
+        false
+    }
+
+    pub fn disable_sensor(&self, _kind: SensorKind) {
+        #[cfg(feature = "dbus")]
+        self.data.sensors.disable(_kind)
+    }
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.data.idle.set_timeout(timeout);
+        self.data.schedule_idle_check();
+    }
+
+    pub fn set_dispatch_policy(&self, _policy: DispatchPolicy) {
+        // The wayland source is registered before the timer source, so calloop dispatches
+        // input first, and idle callbacks only run once both are done.
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        tracing::warn!("keyboard_layouts is unimplemented on wayland");
+        Vec::new()
+    }
+
     pub fn get_locale() -> String {
         linux::env::locale()
     }
+
+    pub fn locale_info() -> LocaleInfo {
+        linux::env::locale_info()
+    }
+
+    #[cfg(feature = "dbus")]
+    pub(crate) fn media_command_sink(&self) -> Option<crate::dbus::MediaCommandSink> {
+        let queue = self.data.media_commands.clone();
+        let idle_ping = self.data.idle_ping.clone();
+        Some(Arc::new(move |command| {
+            queue.lock().unwrap().push(command);
+            idle_ping.ping();
+        }))
+    }
 }
 
 impl surfaces::Compositor for Data {
@@ -419,6 +760,10 @@ impl surfaces::Compositor for Data {
         self.wl_compositor.create_surface()
     }
 
+    fn create_region(&self) -> wl::Main<WlRegion> {
+        self.wl_compositor.create_region()
+    }
+
     fn shared_mem(&self) -> wl::Main<WlShm> {
         self.wl_shm.clone()
     }
@@ -434,6 +779,34 @@ impl surfaces::Compositor for Data {
     fn zwlr_layershell_v1(&self) -> Option<wl::Main<ZwlrLayerShellV1>> {
         self.zwlr_layershell_v1.clone()
     }
+
+    fn wp_viewporter(&self) -> Option<wl::Main<WpViewporter>> {
+        self.wp_viewporter.clone()
+    }
+
+    fn wp_fractional_scale_manager_v1(&self) -> Option<wl::Main<WpFractionalScaleManagerV1>> {
+        self.wp_fractional_scale_manager_v1.clone()
+    }
+
+    fn zxdg_decoration_manager_v1(&self) -> Option<wl::Main<ZxdgDecorationManagerV1>> {
+        self.zxdg_decoration_manager_v1.clone()
+    }
+
+    fn wl_subcompositor(&self) -> Option<wl::Main<WlSubcompositor>> {
+        self.wl_subcompositor.clone()
+    }
+
+    fn wp_presentation(&self) -> Option<wl::Main<WpPresentation>> {
+        // `Instant` is measured with the monotonic clock.
+        let monotonic = nix::time::ClockId::CLOCK_MONOTONIC.as_raw() as u32;
+        self.wp_presentation
+            .clone()
+            .filter(|_| self.presentation_clock.get() == Some(monotonic))
+    }
+
+    fn idle_ping(&self) -> calloop::ping::Ping {
+        self.idle_ping.clone()
+    }
 }
 
 impl Data {
@@ -455,7 +828,7 @@ impl Data {
         Ok(())
     }
 
-    fn current_window_id(&self) -> u64 {
+    pub(super) fn current_window_id(&self) -> u64 {
         static DEFAULT: u64 = 0_u64;
         *self.active_surface_id.borrow().get(0).unwrap_or(&DEFAULT)
     }
@@ -472,20 +845,19 @@ impl Data {
         let mut expired_timers = Vec::with_capacity(1);
         let mut timers = self.timers.borrow_mut();
         let now = Instant::now();
-        while matches!(timers.peek(), Some(timer) if timer.deadline() < now) {
-            // timer has passed
-            expired_timers.push(timers.pop().unwrap());
+        while let Some(timer) = timers.pop_expired(now) {
+            expired_timers.push(timer);
         }
         drop(timers);
         for expired in expired_timers {
-            let win = match self.handles.borrow().get(&expired.id()).cloned() {
+            let win = match self.handles.borrow().get(&expired.data).cloned() {
                 Some(s) => s,
                 None => {
                     // NOTE this might be expected
                     tracing::warn!(
                         "received event for surface that doesn't exist any more {:?} {:?}",
                         expired,
-                        expired.id()
+                        expired.data
                     );
                     continue;
                 }
@@ -502,11 +874,14 @@ impl Data {
             }
         }
 
-        // Get the deadline soonest and queue it.
-        if let Some(timer) = self.timers.borrow().peek() {
+        // Wake up for the timer that can wait the least, and run any others that are due then.
+        let timers = self.timers.borrow();
+        if let Some(wakeup) = timers.next_wakeup() {
+            self.timer_handle.cancel_all_timeouts();
             self.timer_handle
-                .add_timeout(timer.deadline() - now, timer.token());
+                .add_timeout(wakeup.saturating_duration_since(now), TimerToken::INVALID);
         }
+        drop(timers);
         // Now flush so the events actually get sent (we don't do this automatically because we
         // aren't in a wayland callback.
         self.wayland.display.flush().unwrap();
@@ -517,24 +892,33 @@ impl Data {
         self.handles.borrow().clone().into_iter()
     }
 
-    fn idle_repaint(loophandle: calloop::LoopHandle<'_, std::sync::Arc<Data>>) {
-        loophandle.insert_idle({
-            move |appdata| {
-                tracing::trace!("idle processing initiated");
-                for (_id, winhandle) in appdata.handles_iter() {
-                    winhandle.request_anim_frame();
-                    winhandle.run_idle();
-                    // if we already flushed this cycle don't flush again.
-                    if *appdata.display_flushed.borrow() {
-                        tracing::trace!("idle repaint flushing display initiated");
-                        if let Err(cause) = appdata.wayland.queue.borrow().display().flush() {
-                            tracing::warn!("unable to flush display: {:?}", cause);
-                        }
-                    }
-                }
-                tracing::trace!("idle processing completed");
-            }
-        });
+    /// Run the idle callbacks of every window, and send the requests they made before the
+    /// event loop goes back to sleep.
+    /// Makes sure that the event loop wakes up when the application would go idle.
+    fn schedule_idle_check(&self) {
+        let deadline = self.idle.deadline();
+        let mut check = self.idle_check.borrow_mut();
+        if check.as_ref().map(|(_, at)| *at) == deadline {
+            return;
+        }
+        if let Some((timeout, _)) = check.take() {
+            self.idle_timer.cancel_timeout(&timeout);
+        }
+        if let Some(deadline) = deadline {
+            let delay = deadline.saturating_duration_since(Instant::now());
+            *check = Some((self.idle_timer.add_timeout(delay, ()), deadline));
+        }
+    }
+
+    fn run_idle(&self) {
+        tracing::trace!("idle processing initiated");
+        for (_id, winhandle) in self.handles_iter() {
+            winhandle.run_idle();
+        }
+        if let Err(cause) = self.wayland.queue.borrow().display().flush() {
+            tracing::warn!("unable to flush display: {:?}", cause);
+        }
+        tracing::trace!("idle processing completed");
     }
 }
 
@@ -559,6 +943,7 @@ pub struct Seat {
     capabilities: wl_seat::Capability,
     keyboard: Option<wl::Main<WlKeyboard>>,
     pointer: Option<wl::Main<WlPointer>>,
+    text_input: Option<Rc<TextInput>>,
 }
 
 impl Seat {
@@ -569,6 +954,21 @@ impl Seat {
             capabilities: wl_seat::Capability::empty(),
             keyboard: None,
             pointer: None,
+            text_input: None,
         }
     }
 }
+
+impl crate::platform::linux::ApplicationExt for crate::Application {
+    fn primary_clipboard(&self) -> crate::Clipboard {
+        self.backend_app.primary_clipboard().into()
+    }
+
+    fn xkb_keymap(&self) -> Option<String> {
+        self.backend_app.xkb_keymap()
+    }
+
+    fn set_xkb_keymap(&self, keymap: Option<XkbKeymap>) -> bool {
+        self.backend_app.set_xkb_keymap(keymap)
+    }
+}
@@ -13,24 +13,78 @@
 // limitations under the License.
 
 //! Interactions with the system pasteboard on wayland compositors.
+//!
+//! There are two of them: the clipboard, from the core `wl_data_device` protocol, and the
+//! primary selection, from `zwp_primary_selection_v1`. They work the same way, so they share
+//! the implementation, with the protocol objects wrapped in enums.
 use super::application;
 use super::error as waylanderr;
 use crate::clipboard::{ClipboardFormat, FormatId};
-use std::io::Read;
+use std::io::{Read, Write};
+use std::os::unix::io::RawFd;
 use wayland_client as wl;
 use wayland_client::protocol::wl_data_device;
 use wayland_client::protocol::wl_data_device_manager;
 use wayland_client::protocol::wl_data_offer;
 use wayland_client::protocol::wl_data_source;
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_protocols::unstable::primary_selection::v1::client::{
+    zwp_primary_selection_device_manager_v1 as primary_manager,
+    zwp_primary_selection_device_v1 as primary_device,
+    zwp_primary_selection_offer_v1 as primary_offer,
+    zwp_primary_selection_source_v1 as primary_source,
+};
+
+#[derive(Debug, Clone)]
+enum DeviceManager {
+    Clipboard(wl::Main<wl_data_device_manager::WlDataDeviceManager>),
+    Primary(wl::Main<primary_manager::ZwpPrimarySelectionDeviceManagerV1>),
+}
+
+#[derive(Debug, Clone)]
+enum Device {
+    Clipboard(wl::Main<wl_data_device::WlDataDevice>),
+    Primary(wl::Main<primary_device::ZwpPrimarySelectionDeviceV1>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum OfferObject {
+    Clipboard(wl_data_offer::WlDataOffer),
+    Primary(wl::Main<primary_offer::ZwpPrimarySelectionOfferV1>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Clipboard(wl::Main<wl_data_source::WlDataSource>),
+    Primary(wl::Main<primary_source::ZwpPrimarySelectionSourceV1>),
+}
+
+impl OfferObject {
+    fn receive(&self, mimetype: String, fd: RawFd) {
+        match self {
+            OfferObject::Clipboard(o) => o.receive(mimetype, fd),
+            OfferObject::Primary(o) => o.receive(mimetype, fd),
+        }
+    }
+}
+
+impl Source {
+    fn destroy(&self) {
+        match self {
+            Source::Clipboard(s) => s.destroy(),
+            Source::Primary(s) => s.destroy(),
+        }
+    }
+}
 
 #[derive(Clone)]
 struct Offer {
-    wobj: wl::Main<wl_data_offer::WlDataOffer>,
+    wobj: OfferObject,
     mimetype: String,
 }
 
 impl Offer {
-    fn new(d: wl::Main<wl_data_offer::WlDataOffer>, mimetype: impl Into<String>) -> Self {
+    fn new(d: OfferObject, mimetype: impl Into<String>) -> Self {
         Self {
             wobj: d,
             mimetype: mimetype.into(),
@@ -74,22 +128,115 @@ impl From<Vec<Offer>> for Data {
     }
 }
 
+/// The contents of the selection while we own it.
+struct Selection {
+    source: Source,
+    formats: std::rc::Rc<Vec<(String, Vec<u8>)>>,
+}
+
 struct Inner {
     display: wl::Display,
-    wobj: wl::Main<wl_data_device_manager::WlDataDeviceManager>,
-    wdsobj: wl::Main<wl_data_source::WlDataSource>,
+    /// `None` for the primary selection, if the compositor doesn't support it.
+    wobj: Option<DeviceManager>,
+    /// The device of the first seat. We don't support more than one.
+    device: std::cell::RefCell<Option<Device>>,
     devices: std::rc::Rc<std::cell::RefCell<Data>>,
+    /// What we put on the selection, until another client replaces it.
+    selection: std::cell::RefCell<Option<Selection>>,
+    /// The serial of the latest input event, which the compositor checks before letting us
+    /// take the selection.
+    serial: std::rc::Rc<std::cell::Cell<u32>>,
+    /// Where the drag and drop events of the data device go.
+    drag_handler: std::cell::RefCell<Option<DragHandler>>,
 }
 
 impl std::fmt::Debug for Inner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("")
             .field("wobj", &self.wobj)
-            .field("wdsobj", &self.wdsobj)
+            .field("device", &self.device)
             .finish()
     }
 }
 
+impl Inner {
+    /// Our own data, if we are the owner of the selection.
+    ///
+    /// This must be used instead of going through the compositor, because the transfer would
+    /// block on ourselves.
+    fn owned(&self, mimetype: &str) -> Option<Option<Vec<u8>>> {
+        let selection = self.selection.borrow();
+        let formats = &selection.as_ref()?.formats;
+        Some(
+            formats
+                .iter()
+                .find(|(m, _)| m.starts_with(mimetype))
+                .map(|(_, data)| data.clone()),
+        )
+    }
+
+    fn mimetypes(&self) -> Vec<String> {
+        if let Some(selection) = &*self.selection.borrow() {
+            return selection.formats.iter().map(|(m, _)| m.clone()).collect();
+        }
+        let data = self.devices.borrow();
+        let current = data.current.borrow();
+        current.iter().map(|offer| offer.mimetype.clone()).collect()
+    }
+
+    fn offer_mimetype(&self, offer: OfferObject, mimetype: String) {
+        let data = self.devices.borrow_mut();
+        data.pending.borrow_mut().push(Offer::new(offer, mimetype));
+    }
+
+    /// The selection has changed, to the offer that was introduced last if `has_offer`.
+    fn selection_changed(&self, has_offer: bool) {
+        if has_offer {
+            let data = self.devices.borrow();
+            tracing::debug!(
+                "current data offers {:?} {:?}",
+                data.current.borrow().len(),
+                data.pending.borrow().len()
+            );
+            let upd = Data::from(data.pending.take());
+            drop(data);
+            tracing::debug!(
+                "updated data offers {:?} {:?}",
+                upd.current.borrow().len(),
+                upd.pending.borrow().len()
+            );
+            self.devices.replace(upd);
+        } else {
+            let upd = Data::from(Vec::new());
+            self.devices.replace(upd);
+        }
+    }
+
+    /// Another client wants the data we put on the selection.
+    fn send(formats: &[(String, Vec<u8>)], mimetype: String, fd: RawFd) {
+        let mut io: std::fs::File = unsafe { std::os::unix::io::FromRawFd::from_raw_fd(fd) };
+        if let Some((_, data)) = formats.iter().find(|(m, _)| *m == mimetype) {
+            if let Err(cause) = io.write_all(data) {
+                tracing::error!("clipboard unable to send copied content {:?}", cause);
+            }
+        }
+    }
+
+    /// Someone else took the selection.
+    fn cancelled(inner: &std::rc::Weak<Inner>, source: Source) {
+        if let Some(inner) = inner.upgrade() {
+            let mut selection = inner.selection.borrow_mut();
+            if matches!(&*selection, Some(s) if s.source == source) {
+                *selection = None;
+            }
+        }
+        source.destroy();
+    }
+}
+
+/// Receives the drag and drop events of the data device.
+pub(super) type DragHandler = Box<dyn Fn(&Manager, wl_data_device::Event)>;
+
 #[derive(Debug, Clone)]
 pub struct Manager {
     inner: std::rc::Rc<Inner>,
@@ -108,64 +255,262 @@ impl Manager {
             tracing::info!("clipboard {:?} event {:?}", i, event);
         });
 
-        let ds = m.create_data_source();
-        ds.quick_assign(|i, event, _ignored| {
-            tracing::info!("clipboard {:?} event {:?}", i, event);
-        });
+        Ok(Self::with_device_manager(
+            display,
+            Some(DeviceManager::Clipboard(m)),
+            Default::default(),
+        ))
+    }
+
+    /// The primary selection, which shares the input serial with `clipboard`.
+    ///
+    /// If the compositor doesn't support it, the selection is always empty.
+    pub(super) fn primary(
+        display: &wl::Display,
+        gm: &wl::GlobalManager,
+        clipboard: &Manager,
+    ) -> Self {
+        let m = gm
+            .instantiate_exact::<primary_manager::ZwpPrimarySelectionDeviceManagerV1>(1)
+            .map_err(|cause| tracing::info!("no primary selection support {:?}", cause))
+            .ok();
+
+        if let Some(m) = &m {
+            m.quick_assign(|i, event, _ignored| {
+                tracing::info!("primary selection {:?} event {:?}", i, event);
+            });
+        }
+
+        Self::with_device_manager(
+            display,
+            m.map(DeviceManager::Primary),
+            clipboard.inner.serial.clone(),
+        )
+    }
 
-        Ok(Self {
+    fn with_device_manager(
+        display: &wl::Display,
+        wobj: Option<DeviceManager>,
+        serial: std::rc::Rc<std::cell::Cell<u32>>,
+    ) -> Self {
+        Self {
             inner: std::rc::Rc::new(Inner {
-                wobj: m,
-                wdsobj: ds,
+                wobj,
                 display: display.clone(),
+                device: Default::default(),
                 devices: Default::default(),
+                selection: Default::default(),
+                serial,
+                drag_handler: Default::default(),
             }),
-        })
+        }
     }
 
-    pub fn attach<'a>(&'a self, seat: &'a mut application::Seat) {
-        let device = self.inner.wobj.get_data_device(&seat.wl_seat);
-        device.quick_assign({
-            let m = self.inner.clone();
+    /// Records the serial of an input event, for taking the selection later.
+    pub(super) fn input_serial(&self, serial: u32) {
+        self.inner.serial.set(serial);
+    }
+
+    /// Sets where the drag and drop events of the data device go.
+    pub(super) fn set_drag_handler(&self, handler: DragHandler) {
+        self.inner.drag_handler.replace(Some(handler));
+    }
+
+    /// Takes the mime types of the offer that was introduced last.
+    ///
+    /// This is for drags; the selection takes its offers itself.
+    pub(super) fn take_offered_mimetypes(&self) -> Vec<String> {
+        let data = self.inner.devices.borrow();
+        let offers = data.pending.take();
+        offers.into_iter().map(|offer| offer.mimetype).collect()
+    }
+
+    /// Reads the data of a drag offer.
+    pub(super) fn receive_offer(
+        &self,
+        offer: &wl_data_offer::WlDataOffer,
+        mimetype: &str,
+    ) -> Option<Vec<u8>> {
+        self.initiate(Offer::new(OfferObject::Clipboard(offer.clone()), mimetype))
+    }
+
+    /// Starts a drag from `origin`, offering `formats` (mime types and their data) to be
+    /// copied, with `icon` under the pointer.
+    ///
+    /// `ended` is called once the drag is over, whether the data was dropped or not. Returns
+    /// `false` if the drag couldn't be started.
+    pub(super) fn start_drag(
+        &self,
+        formats: Vec<(String, Vec<u8>)>,
+        origin: &WlSurface,
+        icon: Option<&WlSurface>,
+        ended: impl Fn() + 'static,
+    ) -> bool {
+        let (wobj, device) = match (&self.inner.wobj, self.inner.device.borrow().clone()) {
+            (Some(DeviceManager::Clipboard(wobj)), Some(Device::Clipboard(device))) => {
+                (wobj.clone(), device)
+            }
+            _ => {
+                tracing::warn!("unable to start drag, no data device");
+                return false;
+            }
+        };
+        let formats = std::rc::Rc::new(formats);
+        let source = wobj.create_data_source();
+        source.quick_assign({
+            let formats = formats.clone();
             move |i, event, _ignored| match event {
-                wl_data_device::Event::DataOffer { id } => {
-                    let offer = id;
-                    offer.quick_assign({
-                        let m = m.clone();
-                        move |i, event, _ignored| match event {
-                            wl_data_offer::Event::Offer { mime_type } => {
-                                let data = m.devices.borrow_mut();
-                                let offer = Offer::new(i, mime_type);
-                                data.pending.borrow_mut().push(offer);
+                wl_data_source::Event::Send { mime_type, fd } => {
+                    Inner::send(&formats, mime_type, fd)
+                }
+                wl_data_source::Event::Cancelled | wl_data_source::Event::DndFinished => {
+                    i.destroy();
+                    ended();
+                }
+                _ => tracing::debug!("drag source unhandled {:?} event {:?}", i, event),
+            }
+        });
+        for (mimetype, _) in formats.iter() {
+            source.offer(mimetype.clone());
+        }
+        if source.as_ref().version() >= wl_data_source::REQ_SET_ACTIONS_SINCE {
+            source.set_actions(wl_data_device_manager::DndAction::Copy);
+        }
+        device.start_drag(Some(&source), origin, icon, self.inner.serial.get());
+        if let Err(cause) = self.inner.display.flush() {
+            tracing::error!("unable to start drag {:?}", cause);
+        }
+        true
+    }
+
+    pub fn attach<'a>(&'a self, seat: &'a mut application::Seat) {
+        if self.inner.device.borrow().is_some() {
+            return;
+        }
+        let m = self.inner.clone();
+        let device = match &self.inner.wobj {
+            None => return,
+            Some(DeviceManager::Clipboard(wobj)) => {
+                let device = wobj.get_data_device(&seat.wl_seat);
+                device.quick_assign(move |i, event, _ignored| match event {
+                    wl_data_device::Event::DataOffer { id } => {
+                        id.quick_assign({
+                            let m = m.clone();
+                            move |i, event, _ignored| match event {
+                                wl_data_offer::Event::Offer { mime_type } => {
+                                    m.offer_mimetype(OfferObject::Clipboard(i.detach()), mime_type)
+                                }
+                                // The actions only matter to the drag and drop code, which
+                                // asks for them rather than being told.
+                                wl_data_offer::Event::SourceActions { .. }
+                                | wl_data_offer::Event::Action { .. } => {}
+                                _ => {
+                                    tracing::warn!("clipboard unhandled {:?} event {:?}", i, event)
+                                }
                             }
-                            _ => tracing::warn!("clipboard unhandled {:?} event {:?}", i, event),
+                        });
+                    }
+                    wl_data_device::Event::Selection { id } => m.selection_changed(id.is_some()),
+                    event @ (wl_data_device::Event::Enter { .. }
+                    | wl_data_device::Event::Motion { .. }
+                    | wl_data_device::Event::Leave
+                    | wl_data_device::Event::Drop) => {
+                        if let Some(handler) = &*m.drag_handler.borrow() {
+                            handler(&Manager { inner: m.clone() }, event);
                         }
-                    });
+                    }
+                    _ => tracing::warn!("clipboard unhandled {:?} event {:?}", i, event),
+                });
+                Device::Clipboard(device)
+            }
+            Some(DeviceManager::Primary(wobj)) => {
+                let device = wobj.get_device(&seat.wl_seat);
+                device.quick_assign(move |i, event, _ignored| match event {
+                    primary_device::Event::DataOffer { offer } => {
+                        offer.quick_assign({
+                            let m = m.clone();
+                            move |i, event, _ignored| match event {
+                                primary_offer::Event::Offer { mime_type } => {
+                                    m.offer_mimetype(OfferObject::Primary(i), mime_type)
+                                }
+                                _ => tracing::warn!("primary unhandled {:?} event {:?}", i, event),
+                            }
+                        });
+                    }
+                    primary_device::Event::Selection { id } => m.selection_changed(id.is_some()),
+                    _ => tracing::warn!("primary unhandled {:?} event {:?}", i, event),
+                });
+                Device::Primary(device)
+            }
+        };
+        self.inner.device.replace(Some(device));
+    }
+
+    /// Takes the selection, offering `formats` (mime types and their data) to other clients.
+    fn set_selection(&self, formats: Vec<(String, Vec<u8>)>) {
+        let device = match self.inner.device.borrow().clone() {
+            Some(device) => device,
+            None => {
+                tracing::warn!("clipboard copy failed, no data device");
+                return;
+            }
+        };
+        let formats = std::rc::Rc::new(formats);
+        let serial = self.inner.serial.get();
+        let m = std::rc::Rc::downgrade(&self.inner);
+        let source = match (&self.inner.wobj, device) {
+            (Some(DeviceManager::Clipboard(wobj)), Device::Clipboard(device)) => {
+                let source = wobj.create_data_source();
+                source.quick_assign({
+                    let formats = formats.clone();
+                    move |i, event, _ignored| match event {
+                        wl_data_source::Event::Send { mime_type, fd } => {
+                            Inner::send(&formats, mime_type, fd)
+                        }
+                        wl_data_source::Event::Cancelled => {
+                            Inner::cancelled(&m, Source::Clipboard(i))
+                        }
+                        _ => tracing::debug!("clipboard unhandled {:?} event {:?}", i, event),
+                    }
+                });
+                for (mimetype, _) in formats.iter() {
+                    source.offer(mimetype.clone());
                 }
-                wl_data_device::Event::Selection { id } => {
-                    if id.is_some() {
-                        let data = m.devices.borrow();
-                        tracing::debug!(
-                            "current data offers {:?} {:?}",
-                            data.current.borrow().len(),
-                            data.pending.borrow().len()
-                        );
-                        let upd = Data::from(data.pending.take());
-                        drop(data);
-                        tracing::debug!(
-                            "updated data offers {:?} {:?}",
-                            upd.current.borrow().len(),
-                            upd.pending.borrow().len()
-                        );
-                        m.devices.replace(upd);
-                    } else {
-                        let upd = Data::from(Vec::new());
-                        m.devices.replace(upd);
+                device.set_selection(Some(&source), serial);
+                Source::Clipboard(source)
+            }
+            (Some(DeviceManager::Primary(wobj)), Device::Primary(device)) => {
+                let source = wobj.create_source();
+                source.quick_assign({
+                    let formats = formats.clone();
+                    move |i, event, _ignored| match event {
+                        primary_source::Event::Send { mime_type, fd } => {
+                            Inner::send(&formats, mime_type, fd)
+                        }
+                        primary_source::Event::Cancelled => {
+                            Inner::cancelled(&m, Source::Primary(i))
+                        }
+                        _ => tracing::debug!("primary unhandled {:?} event {:?}", i, event),
                     }
+                });
+                for (mimetype, _) in formats.iter() {
+                    source.offer(mimetype.clone());
                 }
-                _ => tracing::warn!("clipboard unhandled {:?} event {:?}", i, event),
+                device.set_selection(Some(&source), serial);
+                Source::Primary(source)
             }
-        });
+            _ => unreachable!("devices come from their own manager"),
+        };
+        let old = self
+            .inner
+            .selection
+            .replace(Some(Selection { source, formats }));
+        if let Some(old) = old {
+            old.source.destroy();
+        }
+        if let Err(cause) = self.inner.display.flush() {
+            tracing::error!("clipboard failed to set selection {:?}", cause);
+        }
     }
 
     fn initiate(&self, o: Offer) -> Option<Vec<u8>> {
@@ -209,6 +554,9 @@ impl Manager {
 
     pub(super) fn receive(&self, mimetype: impl Into<String>) -> Option<Vec<u8>> {
         let mimetype: String = mimetype.into();
+        if let Some(data) = self.inner.owned(&mimetype) {
+            return data;
+        }
         if let Some(offer) = self.inner.devices.borrow().receive(&mimetype) {
             return self.initiate(offer);
         }
@@ -236,13 +584,22 @@ impl Clipboard {
 
     /// Put a string onto the system clipboard.
     pub fn put_string(&mut self, s: impl AsRef<str>) {
-        let _s = s.as_ref().to_string();
-        self.inner.inner.wdsobj.offer(Clipboard::UTF8.to_string());
+        self.put_formats(&[ClipboardFormat::from(s.as_ref())]);
     }
 
     /// Put multi-format data on the system clipboard.
-    pub fn put_formats(&mut self, _formats: &[ClipboardFormat]) {
-        tracing::warn!("clipboard copy not implemented");
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        let mut offers = Vec::new();
+        for format in formats {
+            if format.identifier == ClipboardFormat::TEXT {
+                // Wayland clients mostly look for the mime types, rather than the X11 name.
+                for mimetype in [Clipboard::UTF8, Clipboard::TEXT] {
+                    offers.push((mimetype.to_string(), format.data.clone()));
+                }
+            }
+            offers.push((format.identifier.to_string(), format.data.clone()));
+        }
+        self.inner.set_selection(offers);
     }
 
     /// Get a string from the system clipboard, if one is available.
@@ -262,9 +619,12 @@ impl Clipboard {
 
     /// Given a list of supported clipboard types, returns the supported type which has
     /// highest priority on the system clipboard, or `None` if no types are supported.
-    pub fn preferred_format(&self, _formats: &[FormatId]) -> Option<FormatId> {
-        tracing::warn!("clipboard preferred_format not implemented");
-        None
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        let available = self.inner.inner.mimetypes();
+        formats
+            .iter()
+            .find(|f| available.iter().any(|m| m.starts_with(**f)))
+            .copied()
     }
 
     /// Return data in a given format, if available.
@@ -275,8 +635,15 @@ impl Clipboard {
         self.inner.receive(format)
     }
 
+    pub fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        // Data offers only list their MIME types.
+        self.available_type_names()
+            .into_iter()
+            .map(|mime| (mime, None))
+            .collect()
+    }
+
     pub fn available_type_names(&self) -> Vec<String> {
-        tracing::warn!("clipboard available_type_names not implemented");
-        Vec::new()
+        self.inner.inner.mimetypes()
     }
 }
@@ -0,0 +1,373 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drag and drop, with the data device of the core protocol.
+//!
+//! The data device belongs to the clipboard, which passes its drag events on to us.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wayland_client as wl;
+use wayland_client::protocol::wl_buffer::WlBuffer;
+use wayland_client::protocol::wl_data_device;
+use wayland_client::protocol::wl_data_device_manager::DndAction;
+use wayland_client::protocol::wl_data_offer::{self, WlDataOffer};
+use wayland_client::protocol::wl_surface::WlSurface;
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::dnd::{DragData, DragImage, DropEffect};
+use crate::kurbo::Point;
+use crate::window::WinHandler;
+
+use super::application::Data;
+use super::clipboard;
+use super::surfaces::buffers::image_buffer;
+use super::window::WindowHandle;
+
+const URI_LIST: &str = "text/uri-list";
+const UTF8: &str = "text/plain;charset=utf-8";
+const TEXT: &str = "text/plain";
+const UTF8_STRING: &str = "UTF8_STRING";
+
+/// The drag and drop state of the application.
+#[derive(Default)]
+pub(super) struct State {
+    /// The drag that is over one of our windows.
+    target: RefCell<Option<Target>>,
+    /// The drag we started, until it ends.
+    source: Rc<RefCell<Option<Source>>>,
+}
+
+struct Target {
+    window: WindowHandle,
+    offer: Option<WlDataOffer>,
+    serial: u32,
+    /// The mime type we ask for if the drop is accepted.
+    mimetype: Option<String>,
+    /// The mime types offered by the drag.
+    mimetypes: Vec<String>,
+    /// The custom formats to read when the drag is dropped, if `data` is only a preview.
+    pending: Option<Vec<FormatId>>,
+    data: DragData,
+    pos: Point,
+    effect: DropEffect,
+}
+
+struct Source {
+    /// The data of the drag, which we use directly when the drag is over our own windows,
+    /// because reading it through the compositor would block on ourselves.
+    data: DragData,
+    _icon: Option<Icon>,
+}
+
+/// The image under the pointer during a drag.
+struct Icon {
+    surface: wl::Main<WlSurface>,
+    buffer: wl::Main<WlBuffer>,
+}
+
+impl State {
+    pub(super) fn handle_event(
+        &self,
+        appdata: &Data,
+        clipboard: &clipboard::Manager,
+        event: wl_data_device::Event,
+    ) {
+        match event {
+            wl_data_device::Event::Enter {
+                serial,
+                surface,
+                x,
+                y,
+                id,
+            } => {
+                let mimetypes = clipboard.take_offered_mimetypes();
+                if let Some(old) = self.target.take() {
+                    old.leave();
+                }
+                let window = match find_window(appdata, &surface) {
+                    Some(window) => window,
+                    None => {
+                        tracing::warn!("drag entered a surface that isn't a window");
+                        if let Some(offer) = id {
+                            offer.destroy();
+                        }
+                        return;
+                    }
+                };
+                let formats = window
+                    .data()
+                    .map(|data| data.drop_formats.borrow().clone())
+                    .unwrap_or_default();
+                // Reading the data of another application's drag waits for that application,
+                // so until the drop the handler only sees which formats it has.
+                let (data, pending) = match (&*self.source.borrow(), &id) {
+                    (Some(source), _) => (source.data.clone(), None),
+                    (None, Some(_)) => (preview(&mimetypes, &formats), Some(formats.clone())),
+                    (None, None) => (DragData::default(), None),
+                };
+                let mut target = Target {
+                    window,
+                    offer: id,
+                    serial,
+                    mimetype: preferred_mimetype(&mimetypes, &formats),
+                    mimetypes,
+                    pending,
+                    data,
+                    pos: Point::new(x, y),
+                    effect: DropEffect::None,
+                };
+                target.effect = target.with_handler(|h, pos, data| h.drag_enter(pos, data));
+                target.respond();
+                self.target.replace(Some(target));
+            }
+            wl_data_device::Event::Motion { x, y, .. } => {
+                if let Some(mut target) = self.target.take() {
+                    target.pos = Point::new(x, y);
+                    target.effect = target.with_handler(|h, pos, data| h.drag_over(pos, data));
+                    target.respond();
+                    self.target.replace(Some(target));
+                }
+            }
+            wl_data_device::Event::Leave => {
+                if let Some(target) = self.target.take() {
+                    target.leave();
+                }
+            }
+            wl_data_device::Event::Drop => {
+                if let Some(target) = self.target.take() {
+                    target.drop(clipboard);
+                }
+            }
+            _ => tracing::warn!("unexpected drag event {:?}", event),
+        }
+    }
+
+    pub(super) fn start_drag(
+        &self,
+        appdata: &Data,
+        origin: &WlSurface,
+        data: DragData,
+        image: Option<DragImage>,
+    ) {
+        let icon = image.and_then(|image| Icon::new(appdata, &image));
+        let source = self.source.clone();
+        let started = appdata.clipboard.start_drag(
+            offers(&data),
+            origin,
+            icon.as_ref().map(|icon| &**icon.surface),
+            move || {
+                source.replace(None);
+            },
+        );
+        if started {
+            self.source.replace(Some(Source { data, _icon: icon }));
+        }
+    }
+}
+
+impl Target {
+    fn with_handler<T: Default>(
+        &self,
+        f: impl FnOnce(&mut dyn WinHandler, Point, &DragData) -> T,
+    ) -> T {
+        self.window
+            .data()
+            .and_then(|data| data.with_handler(|h| f(h, self.pos, &self.data)))
+            .unwrap_or_default()
+    }
+
+    /// Tells the compositor whether, and how, we would take the drop.
+    fn respond(&self) {
+        let offer = match &self.offer {
+            Some(offer) => offer,
+            None => return,
+        };
+        let action = match self.effect {
+            DropEffect::None => DndAction::None,
+            DropEffect::Copy => DndAction::Copy,
+            DropEffect::Move => DndAction::Move,
+            // Wayland has no links; copying is the closest.
+            DropEffect::Link => DndAction::Copy,
+        };
+        let mimetype = match action.is_empty() {
+            true => None,
+            false => self.mimetype.clone(),
+        };
+        offer.accept(self.serial, mimetype);
+        if offer.as_ref().version() >= wl_data_offer::REQ_SET_ACTIONS_SINCE {
+            offer.set_actions(action, action);
+        }
+    }
+
+    fn leave(self) {
+        self.with_handler(|h, _, _| h.drag_leave());
+        if let Some(offer) = &self.offer {
+            offer.destroy();
+        }
+    }
+
+    fn drop(mut self, clipboard: &clipboard::Manager) {
+        if self.effect == DropEffect::None {
+            return self.leave();
+        }
+        if let (Some(offer), Some(formats)) = (&self.offer, self.pending.take()) {
+            self.data = read(clipboard, offer, &self.mimetypes, &formats);
+        }
+        let effect = self.with_handler(|h, pos, data| h.drag_drop(pos, data));
+        if let Some(offer) = &self.offer {
+            if effect != DropEffect::None
+                && offer.as_ref().version() >= wl_data_offer::REQ_FINISH_SINCE
+            {
+                offer.finish();
+            }
+            offer.destroy();
+        }
+    }
+}
+
+impl Icon {
+    fn new(appdata: &Data, image: &DragImage) -> Option<Icon> {
+        let (width, height) = (image.width as i32, image.height as i32);
+        let size = width * height * 4;
+        if width <= 0 || height <= 0 || image.pixels.len() < size as usize {
+            return None;
+        }
+        let buffer = match image_buffer(appdata.wl_shm.clone(), width, height, &image.pixels) {
+            Ok(buffer) => buffer,
+            Err(cause) => {
+                tracing::warn!("unable to create drag icon {:?}", cause);
+                return None;
+            }
+        };
+
+        let surface = appdata.wl_compositor.create_surface();
+        surface.quick_assign(|_, _, _| {});
+        let (hot_x, hot_y) = image.hotspot;
+        surface.attach(Some(&buffer), -(hot_x as i32), -(hot_y as i32));
+        surface.damage(0, 0, width, height);
+        surface.commit();
+        Some(Icon { surface, buffer })
+    }
+}
+
+impl Drop for Icon {
+    fn drop(&mut self) {
+        self.surface.destroy();
+        self.buffer.destroy();
+    }
+}
+
+pub(super) fn find_window(appdata: &Data, surface: &WlSurface) -> Option<WindowHandle> {
+    appdata
+        .handles_iter()
+        .map(|(_, window)| window)
+        .find(|window| {
+            window
+                .data()
+                .map_or(false, |data| data.wl_surface().detach() == *surface)
+        })
+}
+
+/// The mime type to accept, out of the ones a drag offers.
+fn preferred_mimetype(mimetypes: &[String], formats: &[FormatId]) -> Option<String> {
+    [URI_LIST, UTF8, TEXT, UTF8_STRING]
+        .iter()
+        .chain(formats)
+        .find(|m| mimetypes.iter().any(|offered| offered == *m))
+        .map(|m| m.to_string())
+        .or_else(|| mimetypes.first().cloned())
+}
+
+/// What the handler sees of a drag before it's dropped: the formats that are offered,
+/// without their data.
+fn preview(mimetypes: &[String], formats: &[FormatId]) -> DragData {
+    let offered = |mimetype: &str| mimetypes.iter().any(|offered| offered == mimetype);
+    let text = [UTF8, TEXT, UTF8_STRING]
+        .iter()
+        .any(|m| offered(m))
+        .then(String::new);
+    let uris = match offered(URI_LIST) {
+        true => vec![String::new()],
+        false => Vec::new(),
+    };
+    let formats = formats
+        .iter()
+        .filter(|format| offered(format))
+        .map(|format| ClipboardFormat::new(format, Vec::new()))
+        .collect();
+    DragData {
+        text,
+        uris,
+        formats,
+    }
+}
+
+fn read(
+    clipboard: &clipboard::Manager,
+    offer: &WlDataOffer,
+    mimetypes: &[String],
+    formats: &[FormatId],
+) -> DragData {
+    let receive = |mimetype: &str| {
+        if mimetypes.iter().any(|offered| offered == mimetype) {
+            clipboard.receive_offer(offer, mimetype)
+        } else {
+            None
+        }
+    };
+    let text = [UTF8, TEXT, UTF8_STRING]
+        .iter()
+        .find_map(|m| receive(m))
+        .and_then(|text| String::from_utf8(text).ok());
+    // A uri-list has a URI on each line, and comments starting with '#'.
+    let uris = receive(URI_LIST)
+        .map(|list| {
+            String::from_utf8_lossy(&list)
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default();
+    let formats = formats
+        .iter()
+        .filter_map(|format| Some(ClipboardFormat::new(format, receive(format)?)))
+        .collect();
+    DragData {
+        text,
+        uris,
+        formats,
+    }
+}
+
+/// The mime types, and their data, that a drag of `data` offers.
+fn offers(data: &DragData) -> Vec<(String, Vec<u8>)> {
+    let mut offers = Vec::new();
+    if !data.uris.is_empty() {
+        let list: String = data.uris.iter().map(|uri| format!("{}\r\n", uri)).collect();
+        offers.push((URI_LIST.to_string(), list.into_bytes()));
+    }
+    if let Some(text) = &data.text {
+        for mimetype in [UTF8, TEXT, UTF8_STRING] {
+            offers.push((mimetype.to_string(), text.clone().into_bytes()));
+        }
+    }
+    for format in &data.formats {
+        offers.push((format.identifier.to_string(), format.data.clone()));
+    }
+    offers
+}
@@ -76,8 +76,6 @@ impl EventSource for WaylandSource {
     {
         tracing::trace!("processing events invoked {:?} {:?}", ready, token);
 
-        self.appdata.display_flushed.replace(false);
-
         let winhandle = match self.appdata.acquire_current_window() {
             Some(winhandle) => winhandle,
             None => {
@@ -129,8 +127,6 @@ impl EventSource for WaylandSource {
             // quickly. Either it is slowed down or we are a spammer.
             // Should not really happen, if it does we do nothing and will flush again later.
             tracing::warn!("unable to flush display: {:?}", e);
-        } else {
-            self.appdata.display_flushed.replace(true);
         }
 
         tracing::trace!("event queue completed");
@@ -1,17 +1,26 @@
 use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
 use wayland_client as wlc;
 use wayland_client::protocol::wl_keyboard;
 use wayland_client::protocol::wl_seat;
 
 use crate::keyboard_types::KeyState;
 use crate::text;
+use crate::FocusDirection;
 use crate::KeyEvent;
 use crate::Modifiers;
 
 use super::application::Data;
+use super::clipboard;
 use super::surfaces::buffers;
 use crate::backend::shared::xkb;
 
+/// What the keyboard thread sends to the event loop.
+enum KeyboardEvent {
+    Key(KeyEvent),
+    Modifiers(Modifiers),
+}
+
 #[allow(unused)]
 #[derive(Clone)]
 struct CachedKeyPress {
@@ -21,7 +30,7 @@ struct CachedKeyPress {
     key: u32,
     repeat: bool,
     state: wayland_client::protocol::wl_keyboard::KeyState,
-    queue: calloop::channel::Sender<KeyEvent>,
+    queue: calloop::channel::Sender<KeyboardEvent>,
 }
 
 impl CachedKeyPress {
@@ -47,23 +56,39 @@ impl Default for Repeat {
     }
 }
 
+/// Keymaps in the xkb text format, shared between the application and the keyboard thread.
+#[derive(Default)]
+struct Keymaps {
+    /// The keymap that the compositor sent.
+    system: Option<String>,
+    /// The keymap that the application installed, which we use instead of the compositor's.
+    custom: Option<String>,
+    /// Counts changes to `custom`, so that the keyboard thread knows when to recompile.
+    generation: u64,
+}
+
 struct Keyboard {
     /// Whether we've currently got keyboard focus.
     focused: bool,
     repeat: Repeat,
     last_key_press: Option<CachedKeyPress>,
+    keymaps: Arc<Mutex<Keymaps>>,
+    /// The generation of `keymaps` that `xkb_state` was compiled from.
+    generation: u64,
     xkb_context: xkb::Context,
     xkb_keymap: std::cell::RefCell<Option<xkb::Keymap>>,
     xkb_state: std::cell::RefCell<Option<xkb::State>>,
     xkb_mods: std::cell::Cell<Modifiers>,
 }
 
-impl Default for Keyboard {
-    fn default() -> Self {
+impl Keyboard {
+    fn new(keymaps: Arc<Mutex<Keymaps>>) -> Self {
         Self {
             focused: false,
             repeat: Repeat::default(),
             last_key_press: None,
+            keymaps,
+            generation: 0,
             xkb_context: xkb::Context::new(),
             xkb_keymap: std::cell::RefCell::new(None),
             xkb_state: std::cell::RefCell::new(None),
@@ -100,7 +125,22 @@ impl Keyboard {
         }
     }
 
+    /// Switches to the keymap that the application installed since the last key, if any.
+    fn update_keymap(&mut self) {
+        let keymaps = self.keymaps.lock().unwrap();
+        if keymaps.generation == self.generation {
+            return;
+        }
+        self.generation = keymaps.generation;
+        let text = keymaps.custom.as_ref().or(keymaps.system.as_ref());
+        if let Some(keymap) = text.and_then(|text| self.xkb_context.keymap_from_string(text)) {
+            self.xkb_state.replace(Some(keymap.state()));
+            self.xkb_keymap.replace(Some(keymap));
+        }
+    }
+
     fn keystroke<'a>(&'a mut self, keystroke: &'a CachedKeyPress) {
+        self.update_keymap();
         let keystate = match keystroke.state {
             wl_keyboard::KeyState::Released => {
                 self.replace_last_key_press(self.release_last_key_press(keystroke));
@@ -120,7 +160,7 @@ impl Keyboard {
         );
         event.mods = self.xkb_mods.get();
 
-        if let Err(cause) = keystroke.queue.send(event) {
+        if let Err(cause) = keystroke.queue.send(KeyboardEvent::Key(event)) {
             tracing::error!("failed to send druid key event: {:?}", cause);
         }
     }
@@ -129,7 +169,7 @@ impl Keyboard {
         &mut self,
         seat: u32,
         event: wl_keyboard::Event,
-        keyqueue: calloop::channel::Sender<KeyEvent>,
+        keyqueue: calloop::channel::Sender<KeyboardEvent>,
     ) {
         tracing::trace!("consume {:?} -> {:?}", seat, event);
         match event {
@@ -152,6 +192,16 @@ impl Keyboard {
                     .to_vec()
                 };
 
+                let mut keymaps = self.keymaps.lock().unwrap();
+                keymaps.system = Some(
+                    String::from_utf8_lossy(&keymap_data)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                );
+                if keymaps.custom.is_some() {
+                    return;
+                }
+
                 // keymap data is '\0' terminated.
                 let keymap = self.xkb_context.keymap_from_slice(&keymap_data);
                 let keymapstate = keymap.state();
@@ -189,7 +239,14 @@ impl Keyboard {
                 })
             }
             wl_keyboard::Event::Modifiers { .. } => {
-                self.xkb_mods.replace(event_to_mods(event));
+                // This comes after the key event of a modifier key, which still has the old
+                // modifiers, so we pass the change on by itself.
+                let mods = event_to_mods(event);
+                if self.xkb_mods.replace(mods) != mods {
+                    if let Err(cause) = keyqueue.send(KeyboardEvent::Modifiers(mods)) {
+                        tracing::error!("failed to send modifiers: {:?}", cause);
+                    }
+                }
             }
             wl_keyboard::Event::RepeatInfo { rate, delay } => {
                 tracing::trace!("keyboard repeat info received {:?} {:?}", rate, delay);
@@ -206,24 +263,31 @@ impl Keyboard {
 }
 
 pub(super) struct State {
-    apptx: calloop::channel::Sender<KeyEvent>,
-    apprx: std::cell::RefCell<Option<calloop::channel::Channel<KeyEvent>>>,
-    tx: calloop::channel::Sender<(u32, wl_keyboard::Event, calloop::channel::Sender<KeyEvent>)>,
+    apptx: calloop::channel::Sender<KeyboardEvent>,
+    apprx: std::cell::RefCell<Option<calloop::channel::Channel<KeyboardEvent>>>,
+    tx: calloop::channel::Sender<(
+        u32,
+        wl_keyboard::Event,
+        calloop::channel::Sender<KeyboardEvent>,
+    )>,
+    keymaps: Arc<Mutex<Keymaps>>,
 }
 
 impl Default for State {
     fn default() -> Self {
-        let (apptx, apprx) = calloop::channel::channel::<KeyEvent>();
+        let (apptx, apprx) = calloop::channel::channel::<KeyboardEvent>();
         let (tx, rx) = calloop::channel::channel::<(
             u32,
             wl_keyboard::Event,
-            calloop::channel::Sender<KeyEvent>,
+            calloop::channel::Sender<KeyboardEvent>,
         )>();
         let state = Self {
             apptx,
             apprx: std::cell::RefCell::new(Some(apprx)),
             tx,
+            keymaps: Arc::default(),
         };
+        let keymaps = state.keymaps.clone();
 
         std::thread::spawn(move || {
             let mut eventloop: calloop::EventLoop<(calloop::LoopSignal, Keyboard)> =
@@ -268,7 +332,7 @@ impl Default for State {
             eventloop
                 .run(
                     std::time::Duration::from_secs(60),
-                    &mut (signal, Keyboard::default()),
+                    &mut (signal, Keyboard::new(keymaps)),
                     |_ignored| {
                         tracing::trace!("keyboard event loop idle");
                     },
@@ -337,12 +401,18 @@ impl Manager {
         &self,
         id: u32,
         seat: wlc::Main<wl_seat::WlSeat>,
+        clipboard: clipboard::Manager,
     ) -> wlc::Main<wl_keyboard::WlKeyboard> {
         let keyboard = seat.get_keyboard();
         keyboard.quick_assign({
             let tx = self.inner.tx.clone();
             let queue = self.inner.apptx.clone();
             move |_, event, _| {
+                if let wl_keyboard::Event::Enter { serial, .. }
+                | wl_keyboard::Event::Key { serial, .. } = event
+                {
+                    clipboard.input_serial(serial);
+                }
                 if let Err(cause) = tx.send((id, event, queue.clone())) {
                     tracing::error!("failed to transmit keyboard event {:?}", cause);
                 };
@@ -352,6 +422,20 @@ impl Manager {
         keyboard
     }
 
+    /// The keymap in effect, in the xkb text format.
+    pub(super) fn keymap(&self) -> Option<String> {
+        let keymaps = self.inner.keymaps.lock().unwrap();
+        keymaps.custom.clone().or_else(|| keymaps.system.clone())
+    }
+
+    /// Uses `keymap`, in the xkb text format, instead of the compositor's keymap, or goes back
+    /// to the compositor's keymap if it's `None`.
+    pub(super) fn set_keymap(&self, keymap: Option<String>) {
+        let mut keymaps = self.inner.keymaps.lock().unwrap();
+        keymaps.custom = keymap;
+        keymaps.generation += 1;
+    }
+
     // TODO turn struct into a calloop event source.
     pub(super) fn events<'a>(&self, handle: &'a calloop::LoopHandle<std::sync::Arc<Data>>) {
         let rx = self.inner.apprx.borrow_mut().take().unwrap();
@@ -359,7 +443,18 @@ impl Manager {
             .insert_source(rx, {
                 move |evt, _ignored, appdata| {
                     let evt = match evt {
-                        calloop::channel::Event::Msg(e) => e,
+                        calloop::channel::Event::Msg(KeyboardEvent::Key(e)) => {
+                            appdata.idle.input();
+                            e
+                        }
+                        calloop::channel::Event::Msg(KeyboardEvent::Modifiers(mods)) => {
+                            if let Some(winhandle) = appdata.acquire_current_window() {
+                                if let Some(windata) = winhandle.data() {
+                                    windata.with_handler(|handler| handler.modifiers_changed(mods));
+                                }
+                            }
+                            return;
+                        }
                         calloop::channel::Event::Closed => {
                             tracing::info!("keyboard events receiver closed");
                             return;
@@ -368,6 +463,9 @@ impl Manager {
 
                     if let Some(winhandle) = appdata.acquire_current_window() {
                         if let Some(windata) = winhandle.data() {
+                            if evt.repeat && !windata.key_repeat.get() {
+                                return;
+                            }
                             windata.with_handler({
                                 let windata = windata.clone();
                                 let evt = evt;
@@ -381,11 +479,16 @@ impl Manager {
                                         );
                                     }
                                     KeyState::Down => {
-                                        let handled = text::simulate_input(
+                                        let mut handled = text::simulate_input(
                                             handler,
                                             windata.active_text_input.get(),
                                             evt.clone(),
                                         );
+                                        if let (false, Some(direction)) =
+                                            (handled, FocusDirection::from_key_event(&evt))
+                                        {
+                                            handled = handler.focus_navigation(direction);
+                                        }
                                         tracing::trace!(
                                             "key press event down {:?} {:?} {:?}",
                                             handled,
@@ -17,14 +17,17 @@
 pub mod application;
 pub mod clipboard;
 mod display;
+mod dnd;
 pub mod error;
 mod events;
 pub mod keyboard;
 pub mod menu;
 mod outputs;
 pub mod pointers;
+mod protocols;
 pub mod screen;
 pub mod surfaces;
+mod text_input;
 pub mod window;
 
 /// Little enum to make it clearer what some return values mean.
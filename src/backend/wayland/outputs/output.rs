@@ -12,6 +12,8 @@ pub fn detect(
 ) -> Result<calloop::channel::Channel<outputs::Event>, error::Error> {
     let (outputstx, outputsrx) = calloop::channel::channel::<outputs::Event>();
     let xdg_output_manager_id: std::cell::RefCell<Option<u32>> = std::cell::RefCell::new(None);
+    // The ids of the outputs that we bound, by the names of their globals.
+    let bound = std::cell::RefCell::new(std::collections::HashMap::<u32, u32>::new());
     display::GlobalEventDispatch::subscribe(env, {
         move |event: &'_ wlc::GlobalEvent,
               registry: &'_ wlc::Attached<wl_registry::WlRegistry>,
@@ -38,6 +40,7 @@ pub fn detect(
 
                     let version = version.min(3);
                     let output = registry.bind::<wl_output::WlOutput>(version, id);
+                    bound.borrow_mut().insert(id, output.as_ref().id());
                     let xdgm = (*xdg_output_manager_id.borrow()).map(|xdgm_id| {
                         registry.bind::<zxdg_output_manager_v1::ZxdgOutputManagerV1>(3, xdgm_id)
                     });
@@ -74,11 +77,20 @@ pub fn detect(
                         }
                     });
                 }
-                wlc::GlobalEvent::Removed { interface, .. } => {
+                wlc::GlobalEvent::Removed { id, interface } => {
                     if interface.as_str() != "wl_output" {
                         return;
                     }
                     tracing::debug!("output removed event {:?} {:?}", registry, interface);
+                    if let Some(gid) = bound.borrow_mut().remove(id) {
+                        let meta = outputs::Meta {
+                            gid,
+                            ..Default::default()
+                        };
+                        if let Err(cause) = outputstx.send(outputs::Event::Removed(meta)) {
+                            tracing::warn!("unable to transmit output removal {:?}", cause);
+                        }
+                    }
                 }
             };
         }
@@ -1,15 +1,26 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use wayland_client::protocol::wl_buffer::WlBuffer;
 use wayland_client::protocol::wl_pointer;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::protocol::wl_shm::WlShm;
 use wayland_client::protocol::wl_surface::{self, WlSurface};
 use wayland_client::{self as wl};
-use wayland_cursor::CursorImageBuffer;
 use wayland_cursor::CursorTheme;
 
+use crate::common_util::session_input_source;
 use crate::keyboard::Modifiers;
 use crate::kurbo::{Point, Vec2};
 use crate::mouse;
 
 use super::application::Data;
+use super::protocols::cursor_shape_v1::client::wp_cursor_shape_device_v1::{
+    self, WpCursorShapeDeviceV1,
+};
+use super::protocols::cursor_shape_v1::client::wp_cursor_shape_manager_v1::WpCursorShapeManagerV1;
+use super::surfaces::frame::Frame as WindowFrame;
 
 // Button constants (linux specific)
 const BTN_LEFT: u32 = 0x110;
@@ -80,6 +91,16 @@ impl ClickDebouncer {
     }
 }
 
+/// A frame of a cursor.
+struct Frame {
+    buffer: WlBuffer,
+    /// The buffer scale, which the hotspot is already divided by.
+    scale: i32,
+    hotspot: (i32, i32),
+    /// How long to show the frame for, if the cursor is animated.
+    duration: Option<Duration>,
+}
+
 /// Collect up mouse events then emit them together on a pointer frame.
 pub(crate) struct Pointer {
     /// The image surface which contains the cursor image.
@@ -93,10 +114,31 @@ pub(crate) struct Pointer {
     wl_pointer: std::cell::RefCell<Option<wl_pointer::WlPointer>>,
     // used to keep track of the current clicking
     clickevent: std::cell::RefCell<ClickDebouncer>,
-    /// cursor theme data.
-    theme: std::cell::RefCell<CursorTheme>,
+    /// The shared memory that cursor themes are loaded into.
+    wl_shm: wl::Attached<WlShm>,
+    /// cursor theme data, for each scale that we've drawn the cursor at.
+    themes: std::cell::RefCell<HashMap<i32, CursorTheme>>,
+    /// The scale to draw the cursor at, which is the scale of the window it is over.
+    scale: std::cell::Cell<i32>,
+    /// Sets cursors by name, so that the compositor draws them, if it supports that.
+    shape_manager: Option<wl::Main<WpCursorShapeManagerV1>>,
+    shape_device: std::cell::RefCell<Option<wl::Main<WpCursorShapeDeviceV1>>>,
+    /// The serial of the last time the pointer entered one of our surfaces, which setting the
+    /// cursor needs.
+    enter_serial: std::cell::Cell<u32>,
     /// Cache the current cursor, so we can see if it changed
     current_cursor: std::cell::RefCell<mouse::Cursor>,
+    /// When the current cursor was shown, for animated cursors.
+    animation_start: std::cell::Cell<Instant>,
+    /// Fires when the next frame of an animated cursor is due.
+    animation_timer: calloop::timer::TimerHandle<()>,
+    animation_source: std::cell::RefCell<Option<calloop::timer::Timer<()>>>,
+    /// Whether the cursor is hidden, in kiosk mode.
+    hidden: std::cell::Cell<bool>,
+    /// The wheel notches of the next axis movement, from a mouse wheel that scrolls in steps.
+    discrete: std::cell::Cell<Option<(wl_pointer::Axis, i32)>>,
+    /// The decorations that the pointer is over, which get its events instead of the window.
+    decorations: std::cell::RefCell<Option<Rc<WindowFrame>>>,
 }
 
 /// Raw wayland pointer events.
@@ -114,6 +156,11 @@ pub(crate) enum PointerEvent {
     },
     /// Axis movement
     Axis { axis: wl_pointer::Axis, value: f64 },
+    /// The number of wheel notches of the axis movement that follows
+    AxisDiscrete {
+        axis: wl_pointer::Axis,
+        discrete: i32,
+    },
     /// Mouse left
     Leave,
 }
@@ -131,29 +178,73 @@ pub(crate) enum MouseEvtKind {
 #[allow(unused)]
 impl Pointer {
     /// Create a new pointer
-    pub fn new(theme: CursorTheme, cursor: wl::Main<WlSurface>) -> Self {
+    pub fn new(
+        wl_shm: wl::Attached<WlShm>,
+        shape_manager: Option<wl::Main<WpCursorShapeManagerV1>>,
+        cursor: wl::Main<WlSurface>,
+    ) -> Self {
+        let animation_source = calloop::timer::Timer::new().unwrap();
         // ignore all events
         cursor.quick_assign(|a1, event, a2| {
             tracing::trace!("pointer surface event {:?} {:?} {:?}", a1, event, a2);
         });
 
         Pointer {
-            theme: std::cell::RefCell::new(theme),
+            wl_shm,
+            themes: std::cell::RefCell::new(HashMap::new()),
+            scale: std::cell::Cell::new(1),
+            shape_manager,
+            shape_device: std::cell::RefCell::new(None),
+            enter_serial: std::cell::Cell::new(0),
             buttons: std::cell::RefCell::new(mouse::MouseButtons::new()),
             pos: std::cell::Cell::new(Point::ZERO), // will get set before we emit any events
             queued_events: std::cell::RefCell::new(VecDeque::with_capacity(3)), // should be enough most of the time
             cursor_surface: cursor,
             wl_pointer: std::cell::RefCell::new(None),
             current_cursor: std::cell::RefCell::new(mouse::Cursor::Arrow),
+            animation_start: std::cell::Cell::new(Instant::now()),
+            animation_timer: animation_source.handle(),
+            animation_source: std::cell::RefCell::new(Some(animation_source)),
             clickevent: std::cell::RefCell::new(ClickDebouncer::default()),
+            hidden: std::cell::Cell::new(false),
+            discrete: std::cell::Cell::new(None),
+            decorations: std::cell::RefCell::new(None),
         }
     }
 
     pub fn attach(&self, current: wl_pointer::WlPointer) {
         tracing::trace!("attaching pointer reference {:?}", current);
+        let device = self
+            .shape_manager
+            .as_ref()
+            .map(|manager| manager.get_pointer(&current));
+        if let Some(old) = self.shape_device.replace(device) {
+            old.destroy();
+        }
         self.wl_pointer.replace(Some(current));
     }
 
+    /// Draws the cursor at the scale of the window that it's over, and redraws it if that
+    /// changed.
+    pub(super) fn set_scale(&self, scale: f64) {
+        // Compositors scale cursors down more nicely than up.
+        let scale = (scale.ceil() as i32).max(1);
+        if self.scale.replace(scale) != scale && !self.hidden.get() {
+            let cursor = self.current_cursor.borrow().clone();
+            self.show_frame(&cursor, self.animation_start.get().elapsed());
+        }
+    }
+
+    /// The pointer of the seat, if it has one.
+    pub fn wl_pointer(&self) -> Option<wl_pointer::WlPointer> {
+        self.wl_pointer.borrow().clone()
+    }
+
+    /// The timer that drives the animation of cursors, to add to the event loop.
+    pub(super) fn take_animation_source(&self) -> Option<calloop::timer::Timer<()>> {
+        self.animation_source.take()
+    }
+
     #[inline]
     pub fn push(&self, event: PointerEvent) {
         self.queued_events.borrow_mut().push_back(event);
@@ -178,23 +269,90 @@ impl Pointer {
         if current == cursor {
             return;
         }
+        if self.hidden.get() || self.decorations.borrow().is_some() {
+            // It's shown when the cursor is unhidden, or comes back to the window.
+            self.current_cursor.replace(cursor);
+            return;
+        }
+        self.show(cursor);
+    }
 
+    /// Shows `cursor` while the pointer is over our decorations, keeping the window's cursor
+    /// for when it comes back.
+    fn show_decoration_cursor(&self, cursor: &mouse::Cursor) {
+        if self.hidden.get() {
+            if let Some(wl_pointer) = &*self.wl_pointer.borrow() {
+                wl_pointer.set_cursor(self.enter_serial.get(), None, 0, 0);
+            }
+        } else {
+            self.show_frame(cursor, Duration::ZERO);
+        }
+    }
+
+    /// Hides the cursor, or shows it again.
+    pub fn set_hidden(&self, hidden: bool) {
+        if self.hidden.replace(hidden) == hidden {
+            return;
+        }
+        if hidden {
+            self.animation_timer.cancel_all_timeouts();
+            if let Some(wl_pointer) = &*self.wl_pointer.borrow() {
+                wl_pointer.set_cursor(self.enter_serial.get(), None, 0, 0);
+            }
+        } else {
+            let cursor = self.current_cursor.borrow().clone();
+            self.show(cursor);
+        }
+    }
+
+    fn show(&self, cursor: mouse::Cursor) {
+        tracing::trace!("showing cursor {:?}", cursor);
+        if self.show_frame(&cursor, Duration::ZERO) {
+            self.current_cursor.replace(cursor);
+            self.animation_start.set(Instant::now());
+        }
+    }
+
+    /// Shows the next frame of the current cursor, if it's animated.
+    pub(super) fn animate(&self) {
+        if self.hidden.get() {
+            return;
+        }
+        let cursor = self.current_cursor.borrow().clone();
+        self.show_frame(&cursor, self.animation_start.get().elapsed());
+    }
+
+    /// Shows the frame of `cursor` that is due `elapsed` into its animation, and schedules the
+    /// next one. Returns `false` if there's no image for the cursor.
+    fn show_frame(&self, cursor: &mouse::Cursor, elapsed: Duration) -> bool {
+        self.animation_timer.cancel_all_timeouts();
         let b = self.wl_pointer.borrow_mut();
         let wl_pointer = match &*b {
-            None => return,
+            None => return false,
             Some(p) => p,
         };
 
-        tracing::trace!("replacing cursor {:?} -> {:?}", current, cursor);
-        let buffer = match self.get_cursor_buffer(&cursor) {
-            None => return,
-            Some(b) => b,
-        };
+        if let (Some(device), Some(shape)) = (&*self.shape_device.borrow(), cursor_shape(cursor)) {
+            // The compositor animates the cursor itself.
+            device.set_shape(self.enter_serial.get(), shape);
+            return true;
+        }
 
-        let (hot_x, hot_y) = buffer.hotspot();
-        self.current_cursor.replace(cursor);
-        wl_pointer.set_cursor(0, Some(&self.cursor_surface), hot_x as i32, hot_y as i32);
-        self.cursor_surface.attach(Some(&*buffer), 0, 0);
+        let frame = match self.get_cursor_buffer(cursor, elapsed) {
+            None => return false,
+            Some(frame) => frame,
+        };
+        let (hot_x, hot_y) = frame.hotspot;
+        wl_pointer.set_cursor(
+            self.enter_serial.get(),
+            Some(&self.cursor_surface),
+            hot_x,
+            hot_y,
+        );
+        if self.cursor_surface.as_ref().version() >= wl_surface::REQ_SET_BUFFER_SCALE_SINCE {
+            self.cursor_surface.set_buffer_scale(frame.scale);
+        }
+        self.cursor_surface.attach(Some(&frame.buffer), 0, 0);
 
         if self.cursor_surface.as_ref().version() >= wl_surface::REQ_DAMAGE_BUFFER_SINCE {
             self.cursor_surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
@@ -203,53 +361,105 @@ impl Pointer {
         }
 
         self.cursor_surface.commit();
+        if let Some(duration) = frame.duration {
+            self.animation_timer.add_timeout(duration, ());
+        }
+        true
     }
 
-    fn get_cursor_buffer(&self, cursor: &mouse::Cursor) -> Option<CursorImageBuffer> {
+    /// The frame to show for a cursor, `elapsed` into its animation.
+    fn get_cursor_buffer(&self, cursor: &mouse::Cursor, elapsed: Duration) -> Option<Frame> {
         #[allow(deprecated)]
-        match cursor {
-            mouse::Cursor::Arrow => self.unpack_image_buffer("left_ptr"),
-            mouse::Cursor::IBeam => self.unpack_image_buffer("xterm"),
-            mouse::Cursor::Crosshair => self.unpack_image_buffer("cross"),
-            mouse::Cursor::OpenHand => self.unpack_image_buffer("openhand"),
-            mouse::Cursor::NotAllowed => self.unpack_image_buffer("X_cursor"),
-            mouse::Cursor::ResizeLeftRight => self.unpack_image_buffer("row-resize"),
-            mouse::Cursor::ResizeUpDown => self.unpack_image_buffer("col-resize"),
-            mouse::Cursor::Pointer => self.unpack_image_buffer("pointer"),
-            mouse::Cursor::Custom(_) => {
-                tracing::warn!("custom cursors not implemented");
-                self.unpack_image_buffer("left_ptr")
+        let name = match cursor {
+            mouse::Cursor::Arrow => "left_ptr",
+            mouse::Cursor::IBeam => "xterm",
+            mouse::Cursor::Crosshair => "cross",
+            mouse::Cursor::OpenHand => "openhand",
+            mouse::Cursor::NotAllowed => "X_cursor",
+            mouse::Cursor::ResizeLeftRight => "row-resize",
+            mouse::Cursor::ResizeUpDown => "col-resize",
+            mouse::Cursor::AllScroll => "all-scroll",
+            mouse::Cursor::Pointer => "pointer",
+            mouse::Cursor::Wait => "watch",
+            mouse::Cursor::Progress => "left_ptr_watch",
+            mouse::Cursor::Custom(custom) => {
+                return Some(Frame {
+                    buffer: custom.buffer().clone(),
+                    scale: 1,
+                    hotspot: custom.hotspot(),
+                    duration: None,
+                });
             }
-        }
-    }
-
-    // Just use the first image, people using animated cursors have already made bad life
-    // choices and shouldn't expect it to work.
-    fn unpack_image_buffer(&self, name: &str) -> Option<CursorImageBuffer> {
-        self.theme
-            .borrow_mut()
-            .get_cursor(name)
-            .map(|c| c[c.frame_and_duration(0).frame_index].clone())
+        };
+        let scale = self.scale.get();
+        let mut themes = self.themes.borrow_mut();
+        let theme = themes
+            .entry(scale)
+            .or_insert_with(|| load_theme(&self.wl_shm, scale));
+        let images = theme.get_cursor(name)?;
+        // The frame timing loops the animation by itself.
+        let frame = images.frame_and_duration(elapsed.as_millis() as u32);
+        let image = &images[frame.frame_index];
+        let (hot_x, hot_y) = image.hotspot();
+        // If the theme doesn't have the size we asked for, it may have picked one that we
+        // can't scale by, and then we show it as it is.
+        let (width, height) = image.dimensions();
+        let scale = if width % scale as u32 == 0 && height % scale as u32 == 0 {
+            scale
+        } else {
+            1
+        };
+        Some(Frame {
+            buffer: (**image).clone(),
+            scale,
+            hotspot: (hot_x as i32 / scale, hot_y as i32 / scale),
+            duration: (images.image_count() > 1)
+                .then(|| Duration::from_millis(frame.frame_duration.max(1).into())),
+        })
     }
 
     pub(super) fn consume(
         appdata: std::sync::Arc<Data>,
+        seat: &WlSeat,
         source: wl_pointer::WlPointer,
         event: wl_pointer::Event,
     ) {
+        // Events over our decorations are handled as they come, without waiting for a frame.
+        let decorations = appdata.pointer.decorations.borrow().clone();
         match event {
             wl_pointer::Event::Enter {
+                serial,
                 surface,
                 surface_x,
                 surface_y,
-                ..
             } => {
+                appdata.pointer.enter_serial.set(serial);
+                if let Some(decorations) = WindowFrame::from_surface(&surface) {
+                    let point = Point::new(surface_x, surface_y);
+                    let cursor = decorations
+                        .pointer_motion(point)
+                        .unwrap_or(mouse::Cursor::Arrow);
+                    appdata.pointer.decorations.replace(Some(decorations));
+                    appdata.pointer.show_decoration_cursor(&cursor);
+                    return;
+                }
+                // The cursor has to be set again with the new serial.
+                if appdata.pointer.hidden.get() {
+                    source.set_cursor(serial, None, 0, 0);
+                } else {
+                    let cursor = appdata.pointer.current_cursor.borrow().clone();
+                    appdata.pointer.show(cursor);
+                }
                 appdata.pointer.push(PointerEvent::Motion {
                     point: Point::new(surface_x, surface_y),
                     pointer: source,
                 });
             }
             wl_pointer::Event::Leave { surface, .. } => {
+                if let Some(decorations) = appdata.pointer.decorations.take() {
+                    decorations.pointer_leave();
+                    return;
+                }
                 appdata.pointer.push(PointerEvent::Leave);
             }
             wl_pointer::Event::Motion {
@@ -257,17 +467,46 @@ impl Pointer {
                 surface_y,
                 ..
             } => {
+                if let Some(decorations) = decorations {
+                    if let Some(cursor) =
+                        decorations.pointer_motion(Point::new(surface_x, surface_y))
+                    {
+                        appdata.pointer.show_decoration_cursor(&cursor);
+                    }
+                    return;
+                }
                 appdata.pointer.push(PointerEvent::Motion {
                     point: Point::new(surface_x, surface_y),
                     pointer: source,
                 });
             }
-            wl_pointer::Event::Button { button, state, .. } => {
+            wl_pointer::Event::Button {
+                serial,
+                button,
+                state,
+                ..
+            } => {
+                appdata.clipboard.input_serial(serial);
+                if let Some(decorations) = decorations {
+                    if let Some(button) = linux_to_mouse_button(button) {
+                        let pressed = state == wl_pointer::ButtonState::Pressed;
+                        decorations.pointer_button(seat, serial, button, pressed);
+                    }
+                    return;
+                }
                 appdata.pointer.push(PointerEvent::Button { button, state });
             }
+            // There's nothing to scroll in the decorations.
+            wl_pointer::Event::Axis { .. } | wl_pointer::Event::AxisDiscrete { .. }
+                if decorations.is_some() => {}
             wl_pointer::Event::Axis { axis, value, .. } => {
                 appdata.pointer.push(PointerEvent::Axis { axis, value });
             }
+            wl_pointer::Event::AxisDiscrete { axis, discrete } => {
+                appdata
+                    .pointer
+                    .push(PointerEvent::AxisDiscrete { axis, discrete });
+            }
             wl_pointer::Event::Frame => {
                 let winhandle = match appdata.acquire_current_window().and_then(|w| w.data()) {
                     Some(w) => w,
@@ -277,10 +516,29 @@ impl Pointer {
                         return;
                     }
                 };
+                appdata.pointer.set_scale(winhandle.scale.get());
                 let mut winhandle = winhandle.handler.borrow_mut();
 
-                // (re-entrancy) call user code
+                // A frame can contain several motions, for instance from a high-rate mouse, which
+                // we deliver as a single event.
+                let mut events = Vec::new();
+                let mut moves = Vec::new();
                 while let Some(event) = appdata.pointer.dequeue() {
+                    match event {
+                        MouseEvtKind::Move(evt) => moves.push(evt),
+                        event => {
+                            events.extend(
+                                mouse::coalesce_moves(std::mem::take(&mut moves))
+                                    .map(MouseEvtKind::Move),
+                            );
+                            events.push(event);
+                        }
+                    }
+                }
+                events.extend(mouse::coalesce_moves(moves).map(MouseEvtKind::Move));
+
+                // (re-entrancy) call user code
+                for event in events {
                     match event {
                         MouseEvtKind::Move(evt) => winhandle.mouse_move(&evt),
                         MouseEvtKind::Up(evt) => winhandle.mouse_up(&evt),
@@ -312,7 +570,10 @@ impl Pointer {
                         count: 0,
                         focus: false,
                         button: mouse::MouseButton::None,
-                        wheel_delta: Vec2::ZERO,
+                        wheel_delta: mouse::ScrollDelta::ZERO,
+                        momentum_phase: mouse::MomentumPhase::None,
+                        source: session_input_source(),
+                        coalesced: Vec::new(),
                     }));
                 }
                 PointerEvent::Button { button, state } => {
@@ -335,7 +596,10 @@ impl Pointer {
                                     count: 1,
                                     focus: false,
                                     button,
-                                    wheel_delta: Vec2::ZERO,
+                                    wheel_delta: mouse::ScrollDelta::ZERO,
+                                    momentum_phase: mouse::MomentumPhase::None,
+                                    source: session_input_source(),
+                                    coalesced: Vec::new(),
                                 },
                             ))
                         }
@@ -349,7 +613,10 @@ impl Pointer {
                                     count: 0,
                                     focus: false,
                                     button,
-                                    wheel_delta: Vec2::ZERO,
+                                    wheel_delta: mouse::ScrollDelta::ZERO,
+                                    momentum_phase: mouse::MomentumPhase::None,
+                                    source: session_input_source(),
+                                    coalesced: Vec::new(),
                                 },
                             ))
                         }
@@ -360,15 +627,30 @@ impl Pointer {
                     };
                     return Some(evt);
                 }
+                PointerEvent::AxisDiscrete { axis, discrete } => {
+                    self.discrete.set(Some((axis, discrete)));
+                    continue;
+                }
                 PointerEvent::Axis { axis, value } => {
-                    let wheel_delta = match axis {
-                        Axis::VerticalScroll => Vec2::new(0., value),
-                        Axis::HorizontalScroll => Vec2::new(value, 0.),
+                    // Wheels that scroll in steps scroll lines, everything else scrolls
+                    // smoothly.
+                    let discrete = self.discrete.take().filter(|(a, _)| *a == axis);
+                    let delta = match discrete {
+                        Some((_, notches)) => notches as f64 * mouse::LINES_PER_NOTCH,
+                        None => value,
+                    };
+                    let delta = match axis {
+                        Axis::VerticalScroll => Vec2::new(0., delta),
+                        Axis::HorizontalScroll => Vec2::new(delta, 0.),
                         _ => {
                             log::error!("axis direction not vertical or horizontal");
                             continue;
                         }
                     };
+                    let wheel_delta = match discrete {
+                        Some(_) => mouse::ScrollDelta::Lines(delta),
+                        None => mouse::ScrollDelta::Pixels(delta),
+                    };
                     return Some(MouseEvtKind::Wheel(mouse::MouseEvent {
                         pos: self.pos.get(),
                         buttons: *self.buttons.borrow(),
@@ -377,6 +659,9 @@ impl Pointer {
                         focus: false,
                         button: mouse::MouseButton::None,
                         wheel_delta,
+                        momentum_phase: mouse::MomentumPhase::None,
+                        source: session_input_source(),
+                        coalesced: Vec::new(),
                     }));
                 }
                 PointerEvent::Leave => {
@@ -390,10 +675,47 @@ impl Pointer {
 
 impl Drop for Pointer {
     fn drop(&mut self) {
+        if let Some(device) = self.shape_device.take() {
+            device.destroy();
+        }
+        if let Some(manager) = &self.shape_manager {
+            manager.destroy();
+        }
         self.cursor_surface.destroy();
     }
 }
 
+/// Loads the user's cursor theme, at their cursor size times `scale`.
+fn load_theme(wl_shm: &wl::Attached<WlShm>, scale: i32) -> CursorTheme {
+    let name = std::env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".to_string());
+    let size = std::env::var("XCURSOR_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(24);
+    CursorTheme::load_from_name(&name, size * scale as u32, wl_shm)
+}
+
+/// The shape that the compositor knows `cursor` by, if it isn't a custom cursor.
+fn cursor_shape(cursor: &mouse::Cursor) -> Option<wp_cursor_shape_device_v1::Shape> {
+    use wp_cursor_shape_device_v1::Shape;
+    #[allow(deprecated)]
+    let shape = match cursor {
+        mouse::Cursor::Arrow => Shape::Default,
+        mouse::Cursor::IBeam => Shape::Text,
+        mouse::Cursor::Crosshair => Shape::Crosshair,
+        mouse::Cursor::OpenHand => Shape::Grab,
+        mouse::Cursor::NotAllowed => Shape::NotAllowed,
+        mouse::Cursor::ResizeLeftRight => Shape::EwResize,
+        mouse::Cursor::ResizeUpDown => Shape::NsResize,
+        mouse::Cursor::AllScroll => Shape::AllScroll,
+        mouse::Cursor::Pointer => Shape::Pointer,
+        mouse::Cursor::Wait => Shape::Wait,
+        mouse::Cursor::Progress => Shape::Progress,
+        mouse::Cursor::Custom(_) => return None,
+    };
+    Some(shape)
+}
+
 #[inline]
 fn linux_to_mouse_button(button: u32) -> Option<mouse::MouseButton> {
     match button {
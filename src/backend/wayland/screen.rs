@@ -13,13 +13,24 @@
 // limitations under the License.
 
 //! wayland Monitors and Screen information.
-use crate::screen::Monitor;
+use crate::screen::{ColorShift, Monitor, MonitorId};
 
 use super::error;
 use super::outputs;
 
 fn _get_monitors() -> Result<Vec<Monitor>, error::Error> {
-    let metas = outputs::current()?;
+    // While the application is running, it keeps track of the outputs already.
+    let metas = match crate::Application::try_global() {
+        Some(app) => app
+            .backend_app
+            .data
+            .outputs
+            .borrow()
+            .values()
+            .cloned()
+            .collect(),
+        None => outputs::current()?,
+    };
     let monitors: Vec<Monitor> = metas
         .iter()
         .map(|m| {
@@ -27,7 +38,13 @@ fn _get_monitors() -> Result<Vec<Monitor>, error::Error> {
                 (m.position.x as f64, m.position.y as f64),
                 (m.logical.width as f64, m.logical.height as f64),
             );
+            // The refresh rate is in mHz, and 0 if the compositor doesn't know it.
+            let refresh_rate = (m.refresh > 0).then(|| m.refresh as f64 / 1000.0);
             Monitor::new(false, rect, rect)
+                .with_id(MonitorId::from_raw(m.gid as u64))
+                .with_scale(m.scale.max(1.0))
+                .with_refresh_rate(refresh_rate)
+                .with_name((!m.name.is_empty()).then(|| m.name.clone()))
         })
         .collect();
     Ok(monitors)
@@ -45,3 +62,14 @@ pub(crate) fn get_monitors() -> Vec<Monitor> {
         }
     }
 }
+
+#[cfg(feature = "dbus")]
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    crate::dbus::color_shift()
+}
+
+#[cfg(not(feature = "dbus"))]
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    tracing::warn!("Screen::get_color_shift() needs the dbus feature on Wayland.");
+    None
+}
@@ -32,8 +32,14 @@ use super::surface;
 
 /// Number of bytes for a pixel (argb = 4)
 pub(super) const PIXEL_WIDTH: i32 = 4;
-/// Number of frames we need (2 for double buffering)
-pub(super) const NUM_FRAMES: i32 = 2;
+/// Number of frames we need (3 for triple buffering)
+///
+/// Compositors can hold on to two buffers at once: the one on screen, and one that they have
+/// been sent but haven't shown yet. A third one means we always have one to attach.
+///
+/// Nothing draws into these buffers yet: handlers render through their own GPU surface, and
+/// there is no software paint path that hands a buffer to them.
+pub(super) const NUM_FRAMES: i32 = 3;
 
 /// A collection of buffers that can change size.
 ///
@@ -43,8 +49,10 @@ pub struct Buffers<const N: usize> {
     released: Cell<Vec<Buffer>>,
     /// The actual buffer objects.
     buffers: Cell<Option<[Buffer; N]>>,
-    /// Which buffer is the next to present. Iterates through to `N-1` then wraps. Draw to this
-    /// buffer
+    /// Which buffer is the next to present.
+    ///
+    /// After presenting, this moves on to a buffer that the compositor has released, trying them
+    /// in order from the one after the presented buffer.
     pending: Cell<usize>,
     /// The physical size of the buffers.
     ///
@@ -116,7 +124,7 @@ impl<const N: usize> Buffers<N> {
         self.buffers_recreate();
 
         // paint if we have a buffer available.
-        if self.pending_buffer_released() {
+        if self.select_released_buffer() {
             self.paint_unchecked(window);
         }
 
@@ -203,6 +211,27 @@ impl<const N: usize> Buffers<N> {
         self.with_buffers(|buffers| f(buffers.as_ref().map(|buffers| &buffers[self.pending.get()])))
     }
 
+    /// Makes a buffer that the compositor has released the pending one, if there is one.
+    ///
+    /// The compositor doesn't have to release buffers in the order we attached them, so the next
+    /// buffer in turn isn't necessarily the first one to be free.
+    fn select_released_buffer(&self) -> bool {
+        let start = self.pending.get();
+        let released = self.with_buffers(|buffers| {
+            let buffers = buffers.as_ref()?;
+            (0..N)
+                .map(|i| (start + i) % N)
+                .find(|&idx| !buffers[idx].in_use.get())
+        });
+        match released {
+            Some(idx) => {
+                self.pending.set(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// For checking whether the next buffer is free.
     fn pending_buffer_released(&self) -> bool {
         self.with_pending_buffer(|buf| buf.map(|buf| !buf.in_use.get()).unwrap_or(false))
@@ -434,6 +463,37 @@ impl Drop for Shm {
     }
 }
 
+/// Creates a buffer holding a fixed image, such as a cursor or a drag icon.
+///
+/// `pixels` is the image, row by row from the top, as premultiplied RGBA with 8 bits per
+/// channel. It must hold at least `width * height` pixels.
+pub fn image_buffer(
+    wl_shm: wl::Main<WlShm>,
+    width: i32,
+    height: i32,
+    pixels: &[u8],
+) -> Result<wl::Main<WlBuffer>, nix::Error> {
+    let size = width * height * PIXEL_WIDTH;
+    let mut shm = Shm::new(wl_shm)?;
+    shm.resize(size)?;
+    // Argb8888 is premultiplied, and little endian, so the bytes are in BGRA order.
+    let mut mmap = unsafe { shm.mmap(0, size as usize) };
+    for (dst, src) in mmap.chunks_exact_mut(4).zip(pixels.chunks_exact(4)) {
+        dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+    }
+    let pool = shm.create_pool();
+    let buffer = pool.create_buffer(
+        0,
+        width,
+        height,
+        width * PIXEL_WIDTH,
+        wl_shm::Format::Argb8888,
+    );
+    pool.destroy();
+    buffer.quick_assign(|_, _, _| {});
+    Ok(buffer)
+}
+
 pub struct Mmap {
     ptr: NonNull<c_void>,
     size: usize,
@@ -552,11 +612,11 @@ impl RawSize {
         self.width * self.height * PIXEL_WIDTH * frames
     }
 
-    pub fn scale(self, scale: i32) -> Self {
+    pub fn scale(self, scale: f64) -> Self {
         // NOTE no overflow checking atm.
         RawSize {
-            width: self.width * scale,
-            height: self.height * scale,
+            width: (self.width as f64 * scale).round() as i32,
+            height: (self.height as f64 * scale).round() as i32,
         }
     }
 
@@ -610,13 +670,14 @@ pub struct RawRect {
 }
 
 impl RawRect {
-    pub fn scale(self, scale: i32) -> Self {
+    pub fn scale(self, scale: f64) -> Self {
         // NOTE no overflow checking atm.
+        // Round outwards, so that fractional scales cover every pixel the rect touches.
         RawRect {
-            x0: self.x0 * scale,
-            y0: self.y0 * scale,
-            x1: self.x1 * scale,
-            y1: self.y1 * scale,
+            x0: (self.x0 as f64 * scale).floor() as i32,
+            y0: (self.y0 as f64 * scale).floor() as i32,
+            x1: (self.x1 as f64 * scale).ceil() as i32,
+            y1: (self.y1 as f64 * scale).ceil() as i32,
         }
     }
 }
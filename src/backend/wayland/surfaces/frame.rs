@@ -0,0 +1,594 @@
+//! The titlebar and borders that we draw around toplevel windows when the compositor won't.
+
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+
+use cairo_sys as cairo;
+use wayland_client as wlc;
+use wayland_client::protocol::wl_buffer;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::protocol::wl_shm::{self, WlShm};
+use wayland_client::protocol::wl_subsurface::WlSubsurface;
+use wayland_client::protocol::wl_surface::{self, WlSurface};
+use wayland_protocols::xdg_shell::client::xdg_surface::XdgSurface;
+use wayland_protocols::xdg_shell::client::xdg_toplevel::{self, ResizeEdge, XdgToplevel};
+
+use crate::colors::SystemColor;
+use crate::kurbo::{Point, Rect, Size};
+use crate::mouse::{Cursor, MouseButton};
+use crate::platform::linux::DecorationTheme;
+
+use super::buffers;
+use super::surface;
+use super::{Compositor, CompositorHandle};
+
+/// The width of the invisible border around the window that resizes it.
+const BORDER: f64 = 8.0;
+/// How far from the outer corners the border resizes in both directions.
+const CORNER: f64 = 16.0;
+/// Two clicks on the titlebar at most this far apart maximize the window.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// The space between the title and the edges of the titlebar.
+const TITLE_PADDING: f64 = 12.0;
+
+/// The parts of the frame that react to the mouse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Part {
+    Titlebar,
+    Minimize,
+    Maximize,
+    Close,
+    Edge(ResizeEdge),
+}
+
+impl Part {
+    fn is_button(self) -> bool {
+        matches!(self, Part::Minimize | Part::Maximize | Part::Close)
+    }
+
+    fn cursor(part: Option<Part>) -> Cursor {
+        match part {
+            Some(Part::Edge(ResizeEdge::Left | ResizeEdge::Right)) => Cursor::ResizeLeftRight,
+            Some(Part::Edge(_)) => Cursor::ResizeUpDown,
+            _ => Cursor::Arrow,
+        }
+    }
+}
+
+/// The states of a toplevel window that the frame cares about.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct States {
+    pub(super) maximized: bool,
+    pub(super) fullscreen: bool,
+    pub(super) activated: bool,
+}
+
+impl States {
+    /// Reads the states out of an `xdg_toplevel` configure event.
+    pub(super) fn from_raw(raw: &[u8]) -> States {
+        let mut states = States::default();
+        for state in raw.chunks_exact(4) {
+            let state = u32::from_ne_bytes([state[0], state[1], state[2], state[3]]);
+            match xdg_toplevel::State::from_raw(state) {
+                Some(xdg_toplevel::State::Maximized) => states.maximized = true,
+                Some(xdg_toplevel::State::Fullscreen) => states.fullscreen = true,
+                Some(xdg_toplevel::State::Activated) => states.activated = true,
+                _ => {}
+            }
+        }
+        states
+    }
+}
+
+/// A titlebar with the window's title and buttons, and invisible borders to resize it by.
+///
+/// It is a subsurface below the window's content, reaching out past the content by the height
+/// of the titlebar and the width of the borders. The window geometry includes the titlebar, so
+/// the sizes that the compositor configures the window with include it too.
+pub(crate) struct Frame {
+    wl_surface: wlc::Main<WlSurface>,
+    subsurface: wlc::Main<WlSubsurface>,
+    wl_shm: wlc::Main<WlShm>,
+    xdg_surface: wlc::Main<XdgSurface>,
+    xdg_toplevel: wlc::Main<XdgToplevel>,
+    /// The window's content, which the frame is drawn around.
+    window: surface::Surface,
+    /// `None` if the application turned the frame off.
+    theme: RefCell<Option<DecorationTheme>>,
+    title: RefCell<String>,
+    /// Whether the compositor left the decorations to us.
+    client_side: Cell<bool>,
+    show_titlebar: Cell<bool>,
+    states: Cell<States>,
+    /// The logical size of the window's content.
+    size: Cell<Size>,
+    /// Whether the frame was shown when it was last laid out.
+    shown: Cell<bool>,
+    /// The position of the pointer, if it is over the frame.
+    pointer: Cell<Option<Point>>,
+    hovered: Cell<Option<Part>>,
+    /// The button that was pressed, which acts if it is still under the pointer on release.
+    pressed: Cell<Option<Part>>,
+    last_titlebar_click: Cell<Option<Instant>>,
+}
+
+impl Frame {
+    /// Creates the frame for `window`, which stays hidden until the compositor leaves the
+    /// decorations to us.
+    ///
+    /// Returns `None` if the compositor doesn't support subsurfaces.
+    pub(super) fn new(
+        compositor: &CompositorHandle,
+        window: surface::Surface,
+        xdg_surface: wlc::Main<XdgSurface>,
+        xdg_toplevel: wlc::Main<XdgToplevel>,
+        show_titlebar: bool,
+    ) -> Option<Rc<Frame>> {
+        let subcompositor = compositor.wl_subcompositor()?;
+        let parent = window.inner.wl_surface();
+        let wl_surface = compositor.create_surface()?;
+        // We don't care which outputs the frame is on, the window keeps track of that.
+        wl_surface.quick_assign(|_, _, _| {});
+        let subsurface = subcompositor.get_subsurface(&wl_surface, &parent);
+        subsurface.place_below(&parent);
+        // Hovering over the buttons redraws the frame without the window.
+        subsurface.set_desync();
+
+        let frame = Rc::new(Frame {
+            wl_surface,
+            subsurface,
+            wl_shm: compositor.shared_mem(),
+            xdg_surface,
+            xdg_toplevel,
+            window,
+            theme: RefCell::new(Some(DecorationTheme::default())),
+            title: RefCell::new(String::new()),
+            client_side: Cell::new(false),
+            show_titlebar: Cell::new(show_titlebar),
+            states: Cell::new(States::default()),
+            size: Cell::new(Size::ZERO),
+            shown: Cell::new(false),
+            pointer: Cell::new(None),
+            hovered: Cell::new(None),
+            pressed: Cell::new(None),
+            last_titlebar_click: Cell::new(None),
+        });
+        // Lets the pointer find the frame it is over.
+        frame
+            .wl_surface
+            .as_ref()
+            .user_data()
+            .set(|| Rc::downgrade(&frame));
+        Some(frame)
+    }
+
+    /// The frame that `wl_surface` belongs to, if it is one.
+    pub(crate) fn from_surface(wl_surface: &WlSurface) -> Option<Rc<Frame>> {
+        wl_surface
+            .as_ref()
+            .user_data()
+            .get::<Weak<Frame>>()?
+            .upgrade()
+    }
+
+    pub(super) fn set_theme(&self, theme: Option<DecorationTheme>) {
+        self.theme.replace(theme);
+        self.update();
+    }
+
+    pub(super) fn set_title(&self, title: String) {
+        self.title.replace(title);
+        if self.shown.get() {
+            self.draw();
+        }
+    }
+
+    pub(super) fn set_client_side(&self, client_side: bool) {
+        if self.client_side.replace(client_side) != client_side {
+            self.update();
+        }
+    }
+
+    pub(super) fn set_show_titlebar(&self, show_titlebar: bool) {
+        if self.show_titlebar.replace(show_titlebar) != show_titlebar {
+            self.update();
+        }
+    }
+
+    /// Sets the states from a configure event, which then goes on to size the window.
+    pub(super) fn set_states(&self, states: States) {
+        self.states.set(states);
+    }
+
+    /// Fits the frame around content of the given logical size.
+    pub(super) fn resize(&self, size: Size) {
+        self.size.set(size);
+        self.layout();
+    }
+
+    /// The height of the titlebar above the window's content, or zero if it isn't shown.
+    pub(super) fn titlebar_height(&self) -> f64 {
+        match &*self.theme.borrow() {
+            Some(theme) if self.visible() => theme.titlebar_height.round(),
+            _ => 0.0,
+        }
+    }
+
+    fn visible(&self) -> bool {
+        self.theme.borrow().is_some()
+            && self.client_side.get()
+            && self.show_titlebar.get()
+            && !self.states.get().fullscreen
+    }
+
+    /// The width of the resize borders. Maximized windows can't be resized.
+    fn border(&self) -> f64 {
+        if self.states.get().maximized {
+            0.0
+        } else {
+            BORDER
+        }
+    }
+
+    /// Lays the frame out again, when it changed outside of a configure event.
+    fn update(&self) {
+        if self.layout() {
+            // The window geometry and the position of the frame apply with the window's next
+            // commit.
+            self.window.commit();
+        }
+    }
+
+    /// Positions and draws the frame, or hides it. Returns `false` if the window hasn't been
+    /// configured yet, in which case there's nothing to lay out.
+    fn layout(&self) -> bool {
+        let size = self.size.get();
+        if size.width < 1.0 || size.height < 1.0 {
+            return false;
+        }
+        let titlebar = self.titlebar_height();
+        self.xdg_surface.set_window_geometry(
+            0,
+            -(titlebar as i32),
+            size.width as i32,
+            (size.height + titlebar) as i32,
+        );
+        if self.visible() {
+            let border = self.border();
+            self.subsurface
+                .set_position(-(border as i32), -((titlebar + border) as i32));
+            self.shown.set(true);
+            self.draw();
+        } else if self.shown.replace(false) {
+            self.wl_surface.attach(None, 0, 0);
+            self.wl_surface.commit();
+        }
+        true
+    }
+
+    /// The titlebar, in the frame's coordinates.
+    fn titlebar_rect(&self) -> Rect {
+        let border = self.border();
+        Rect::from_origin_size(
+            (border, border),
+            (self.size.get().width, self.titlebar_height()),
+        )
+    }
+
+    /// The square that `button` takes up at the right of the titlebar.
+    fn button_rect(&self, button: Part) -> Rect {
+        let titlebar = self.titlebar_rect();
+        let side = titlebar.height();
+        let index = match button {
+            Part::Close => 1.0,
+            Part::Maximize => 2.0,
+            _ => 3.0,
+        };
+        let x1 = titlebar.x1 - (index - 1.0) * side;
+        Rect::new(x1 - side, titlebar.y0, x1, titlebar.y1)
+    }
+
+    /// The part of the frame at `pos`, in the frame's coordinates.
+    fn part_at(&self, pos: Point) -> Option<Part> {
+        let titlebar = self.titlebar_rect();
+        if titlebar.contains(pos) {
+            let button = [Part::Close, Part::Maximize, Part::Minimize]
+                .iter()
+                .copied()
+                .find(|button| self.button_rect(*button).contains(pos));
+            return Some(button.unwrap_or(Part::Titlebar));
+        }
+        let border = self.border();
+        if border == 0.0 {
+            return None;
+        }
+        // The window's content covers the middle, so only the border is left.
+        let width = titlebar.width() + 2.0 * border;
+        let height = titlebar.height() + self.size.get().height + 2.0 * border;
+        let left = pos.x < CORNER;
+        let right = pos.x > width - CORNER;
+        let top = pos.y < CORNER;
+        let bottom = pos.y > height - CORNER;
+        let edge = match (top, bottom, left, right) {
+            (true, _, true, _) => ResizeEdge::TopLeft,
+            (true, _, _, true) => ResizeEdge::TopRight,
+            (_, true, true, _) => ResizeEdge::BottomLeft,
+            (_, true, _, true) => ResizeEdge::BottomRight,
+            (true, _, _, _) => ResizeEdge::Top,
+            (_, true, _, _) => ResizeEdge::Bottom,
+            (_, _, true, _) => ResizeEdge::Left,
+            (_, _, _, true) => ResizeEdge::Right,
+            _ => return None,
+        };
+        Some(Part::Edge(edge))
+    }
+
+    /// Called when the pointer enters or moves over the frame, at `pos` in the frame's
+    /// coordinates.
+    ///
+    /// Returns the cursor to show, if it changed.
+    pub(crate) fn pointer_motion(&self, pos: Point) -> Option<Cursor> {
+        let entered = self.pointer.replace(Some(pos)).is_none();
+        let part = self.part_at(pos);
+        let previous = self.hovered.replace(part);
+        if previous == part && !entered {
+            return None;
+        }
+        if previous.map_or(false, Part::is_button) || part.map_or(false, Part::is_button) {
+            self.draw();
+        }
+        Some(Part::cursor(part))
+    }
+
+    pub(crate) fn pointer_leave(&self) {
+        self.pointer.set(None);
+        self.pressed.set(None);
+        if self.hovered.take().map_or(false, Part::is_button) {
+            self.draw();
+        }
+    }
+
+    pub(crate) fn pointer_button(
+        &self,
+        seat: &WlSeat,
+        serial: u32,
+        button: MouseButton,
+        pressed: bool,
+    ) {
+        let pos = match self.pointer.get() {
+            Some(pos) => pos,
+            None => return,
+        };
+        let part = self.part_at(pos);
+        match (button, pressed, part) {
+            (MouseButton::Left, true, Some(Part::Titlebar)) => {
+                let now = Instant::now();
+                let last = self.last_titlebar_click.replace(Some(now));
+                if last.map_or(false, |last| now - last < DOUBLE_CLICK) {
+                    self.last_titlebar_click.set(None);
+                    self.toggle_maximized();
+                } else {
+                    self.xdg_toplevel._move(seat, serial);
+                }
+            }
+            (MouseButton::Left, true, Some(Part::Edge(edge))) => {
+                self.xdg_toplevel.resize(seat, serial, edge);
+            }
+            (MouseButton::Left, true, part) => self.pressed.set(part),
+            (MouseButton::Left, false, part) => {
+                // Like other buttons, moving off before releasing cancels the click.
+                if self.pressed.take() != part {
+                    return;
+                }
+                match part {
+                    Some(Part::Minimize) => self.xdg_toplevel.set_minimized(),
+                    Some(Part::Maximize) => self.toggle_maximized(),
+                    Some(Part::Close) => {
+                        self.window
+                            .inner
+                            .with_handler(|handler| handler.request_close());
+                    }
+                    _ => {}
+                }
+            }
+            (MouseButton::Right, true, Some(Part::Titlebar)) => {
+                // The menu is placed relative to the window geometry, which starts at the
+                // titlebar.
+                let border = self.border();
+                self.xdg_toplevel.show_window_menu(
+                    seat,
+                    serial,
+                    (pos.x - border) as i32,
+                    (pos.y - border) as i32,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn toggle_maximized(&self) {
+        if self.states.get().maximized {
+            self.xdg_toplevel.unset_maximized();
+        } else {
+            self.xdg_toplevel.set_maximized();
+        }
+    }
+
+    /// Draws the frame into a new buffer, and shows it.
+    fn draw(&self) {
+        let theme = match &*self.theme.borrow() {
+            Some(theme) => theme.clone(),
+            None => return,
+        };
+        let scale = if self.wl_surface.as_ref().version() >= wl_surface::REQ_SET_BUFFER_SCALE_SINCE
+        {
+            // Compositors scale buffers down more nicely than up.
+            (self.window.inner.scale.get().ceil() as i32).max(1)
+        } else {
+            1
+        };
+        let border = self.border();
+        let size = self.size.get();
+        let width = (size.width + 2.0 * border) as i32 * scale;
+        let height = (size.height + self.titlebar_height() + 2.0 * border) as i32 * scale;
+        let stride = width * buffers::PIXEL_WIDTH;
+
+        let mut shm = match buffers::Shm::new(self.wl_shm.clone()) {
+            Ok(shm) => shm,
+            Err(e) => {
+                tracing::error!("unable to allocate memory for the decorations: {}", e);
+                return;
+            }
+        };
+        // The memory starts out zeroed, which leaves the borders transparent.
+        if let Err(e) = shm.resize(stride * height) {
+            tracing::error!("unable to allocate memory for the decorations: {}", e);
+            return;
+        }
+        let mut pixels = unsafe { shm.mmap(0, (stride * height) as usize) };
+        self.paint(&theme, &mut pixels, width, height, scale as f64);
+        drop(pixels);
+
+        let pool = shm.create_pool();
+        let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888);
+        pool.destroy();
+        // Each drawing gets its own buffer, which is done with once the compositor releases it.
+        buffer.quick_assign(|buffer, event, _| {
+            if let wl_buffer::Event::Release = event {
+                buffer.destroy();
+            }
+        });
+        if scale != 1 {
+            self.wl_surface.set_buffer_scale(scale);
+        }
+        self.wl_surface.attach(Some(&buffer), 0, 0);
+        self.wl_surface.damage(0, 0, i32::MAX, i32::MAX);
+        self.wl_surface.commit();
+    }
+
+    /// Paints the titlebar into `pixels`, which hold the whole frame.
+    fn paint(
+        &self,
+        theme: &DecorationTheme,
+        pixels: &mut [u8],
+        width: i32,
+        height: i32,
+        scale: f64,
+    ) {
+        let titlebar = self.titlebar_rect();
+        let active = self.states.get().activated;
+        let hovered = self.hovered.get();
+        let title = CString::new(self.title.borrow().replace('\0', "")).unwrap_or_default();
+        let title_space = Rect::new(
+            titlebar.x0 + TITLE_PADDING,
+            titlebar.y0,
+            self.button_rect(Part::Minimize).x0 - TITLE_PADDING,
+            titlebar.y1,
+        );
+
+        // Safety: `pixels` holds `height` rows of `width` pixels, and outlives the cairo
+        // surface.
+        unsafe {
+            let surface = cairo::cairo_image_surface_create_for_data(
+                pixels.as_mut_ptr(),
+                cairo::FORMAT_A_RGB32,
+                width,
+                height,
+                width * buffers::PIXEL_WIDTH,
+            );
+            let cr = cairo::cairo_create(surface);
+            cairo::cairo_scale(cr, scale, scale);
+
+            set_color(
+                cr,
+                if active {
+                    theme.titlebar
+                } else {
+                    theme.titlebar_inactive
+                },
+            );
+            rectangle(cr, titlebar);
+            cairo::cairo_fill(cr);
+
+            cairo::cairo_set_line_width(cr, 1.0);
+            for &button in &[Part::Minimize, Part::Maximize, Part::Close] {
+                let rect = self.button_rect(button);
+                if hovered == Some(button) {
+                    let hover = match button {
+                        Part::Close => theme.close_hover,
+                        _ => theme.button_hover,
+                    };
+                    set_color(cr, hover);
+                    rectangle(cr, rect);
+                    cairo::cairo_fill(cr);
+                }
+                // The symbols are drawn on half pixels, so that their lines are sharp.
+                let center = rect.center();
+                let (x, y) = (center.x.round() + 0.5, center.y.round() + 0.5);
+                let r = (rect.height() * 0.15).round();
+                set_color(cr, theme.button);
+                match button {
+                    Part::Minimize => {
+                        cairo::cairo_move_to(cr, x - r, y + r);
+                        cairo::cairo_line_to(cr, x + r, y + r);
+                    }
+                    Part::Maximize => {
+                        cairo::cairo_rectangle(cr, x - r, y - r, 2.0 * r, 2.0 * r);
+                    }
+                    _ => {
+                        cairo::cairo_move_to(cr, x - r, y - r);
+                        cairo::cairo_line_to(cr, x + r, y + r);
+                        cairo::cairo_move_to(cr, x + r, y - r);
+                        cairo::cairo_line_to(cr, x - r, y + r);
+                    }
+                }
+                cairo::cairo_stroke(cr);
+            }
+
+            // The title is centered, and cut off before the buttons.
+            cairo::cairo_select_font_face(
+                cr,
+                b"sans-serif\0".as_ptr() as *const c_char,
+                cairo::FONT_SLANT_NORMAL,
+                cairo::FONT_WEIGHT_BOLD,
+            );
+            cairo::cairo_set_font_size(cr, theme.font_size);
+            let mut extents = std::mem::zeroed::<cairo::TextExtents>();
+            cairo::cairo_text_extents(cr, title.as_ptr(), &mut extents);
+            let x = (titlebar.center().x - extents.width / 2.0).max(title_space.x0);
+            let y = titlebar.center().y - extents.height / 2.0;
+            rectangle(cr, title_space);
+            cairo::cairo_clip(cr);
+            set_color(cr, theme.title);
+            cairo::cairo_move_to(cr, x - extents.x_bearing, y - extents.y_bearing);
+            cairo::cairo_show_text(cr, title.as_ptr());
+
+            cairo::cairo_destroy(cr);
+            cairo::cairo_surface_flush(surface);
+            cairo::cairo_surface_destroy(surface);
+        }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        self.subsurface.destroy();
+        self.wl_surface.destroy();
+    }
+}
+
+unsafe fn set_color(cr: *mut cairo::cairo_t, color: SystemColor) {
+    cairo::cairo_set_source_rgb(
+        cr,
+        color.r as f64 / 255.0,
+        color.g as f64 / 255.0,
+        color.b as f64 / 255.0,
+    );
+}
+
+unsafe fn rectangle(cr: *mut cairo::cairo_t, rect: Rect) {
+    cairo::cairo_rectangle(cr, rect.x0, rect.y0, rect.width(), rect.height());
+}
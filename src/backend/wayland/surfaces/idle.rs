@@ -19,9 +19,13 @@ impl std::fmt::Debug for Kind {
     }
 }
 
+/// Schedules work on a window's handler. It can be sent to other threads, and wakes the event
+/// loop up if it is sleeping.
 #[derive(Clone)]
 pub struct Handle {
     pub(super) queue: std::sync::Arc<std::sync::Mutex<Vec<Kind>>>,
+    /// Wakes the event loop up, which runs the queue.
+    pub(super) ping: calloop::ping::Ping,
 }
 
 impl Handle {
@@ -36,14 +40,17 @@ impl Handle {
         F: FnOnce(&mut dyn window::WinHandler) + Send + 'static,
     {
         tracing::trace!("add_idle_callback initiated");
-        let mut queue = self.queue.lock().unwrap();
-        queue.push(Kind::Callback(Box::new(callback)));
+        self.queue
+            .lock()
+            .unwrap()
+            .push(Kind::Callback(Box::new(callback)));
+        self.ping.ping();
     }
 
     pub fn add_idle_token(&self, token: window::IdleToken) {
         tracing::trace!("add_idle_token initiated {:?}", token);
-        let mut queue = self.queue.lock().unwrap();
-        queue.push(Kind::Token(token));
+        self.queue.lock().unwrap().push(Kind::Token(token));
+        self.ping.ping();
     }
 }
 
@@ -56,3 +63,45 @@ pub(crate) fn run(state: &Handle, winhandle: &mut dyn window::WinHandler) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use super::*;
+    use crate::{IdleToken, Region, WindowHandle};
+
+    #[derive(Default)]
+    struct Recorder(Vec<IdleToken>);
+
+    impl window::WinHandler for Recorder {
+        fn connect(&mut self, _: &WindowHandle) {}
+        fn prepare_paint(&mut self) {}
+        fn paint(&mut self, _: &Region, _: window::PaintReason) {}
+        fn idle(&mut self, token: IdleToken) {
+            self.0.push(token);
+        }
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn idle_from_another_thread() {
+        let (ping, _source) = calloop::ping::make_ping().unwrap();
+        let handle = Handle {
+            queue: Default::default(),
+            ping,
+        };
+        let sender = handle.clone();
+        std::thread::spawn(move || {
+            sender.add_idle_token(IdleToken::new(1));
+            sender.add_idle_callback(|h| h.idle(IdleToken::new(2)));
+        })
+        .join()
+        .unwrap();
+        let mut recorder = Recorder::default();
+        run(&handle, &mut recorder);
+        assert_eq!(recorder.0, [IdleToken::new(1), IdleToken::new(2)]);
+    }
+}
@@ -152,7 +152,14 @@ impl Config {
         let ls = surface.inner.ls_surface.borrow();
         ls.set_exclusive_zone(self.exclusive_zone);
         ls.set_anchor(self.anchor);
-        ls.set_keyboard_interactivity(self.keyboard_interactivity);
+        let mut keyboard_interactivity = self.keyboard_interactivity;
+        if keyboard_interactivity
+            == layershell::zwlr_layer_surface_v1::KeyboardInteractivity::OnDemand
+            && ls.as_ref().version() < 4
+        {
+            keyboard_interactivity = layershell::zwlr_layer_surface_v1::KeyboardInteractivity::None;
+        }
+        ls.set_keyboard_interactivity(keyboard_interactivity);
         ls.set_margin(
             self.margin.top,
             self.margin.right,
@@ -166,6 +173,40 @@ impl Config {
     }
 }
 
+impl From<crate::Layer> for layershell::zwlr_layer_shell_v1::Layer {
+    fn from(layer: crate::Layer) -> Self {
+        match layer {
+            crate::Layer::Background => Self::Background,
+            crate::Layer::Bottom => Self::Bottom,
+            crate::Layer::Top => Self::Top,
+            crate::Layer::Overlay => Self::Overlay,
+        }
+    }
+}
+
+impl From<crate::LayerAnchor> for layershell::zwlr_layer_surface_v1::Anchor {
+    fn from(anchor: crate::LayerAnchor) -> Self {
+        let mut edges = Self::empty();
+        edges.set(Self::Top, anchor.top);
+        edges.set(Self::Bottom, anchor.bottom);
+        edges.set(Self::Left, anchor.left);
+        edges.set(Self::Right, anchor.right);
+        edges
+    }
+}
+
+impl From<crate::KeyboardInteractivity>
+    for layershell::zwlr_layer_surface_v1::KeyboardInteractivity
+{
+    fn from(interactivity: crate::KeyboardInteractivity) -> Self {
+        match interactivity {
+            crate::KeyboardInteractivity::None => Self::None,
+            crate::KeyboardInteractivity::Exclusive => Self::Exclusive,
+            crate::KeyboardInteractivity::OnDemand => Self::OnDemand,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -254,8 +295,11 @@ impl Surface {
                 let mut dim = handle.inner.config.initial_size;
                 // compositor is deferring to the client for determining the size
                 // when values are zero.
-                if width != 0 && height != 0 {
-                    dim = kurbo::Size::new(width as f64, height as f64);
+                if width != 0 {
+                    dim.width = width as f64;
+                }
+                if height != 0 {
+                    dim.height = height as f64;
                 }
 
                 let ls = handle.inner.ls_surface.borrow();
@@ -393,7 +437,7 @@ impl Handle for Surface {
             .set_focused_text_field(active_field);
     }
 
-    fn get_idle_handle(&self) -> super::idle::Handle {
+    fn get_idle_handle(&self) -> Option<super::idle::Handle> {
         return self.inner.wl_surface.borrow().get_idle_handle();
     }
 
@@ -1,18 +1,27 @@
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_client::protocol::wl_region::WlRegion;
 use wayland_client::protocol::wl_shm::WlShm;
+use wayland_client::protocol::wl_subcompositor::WlSubcompositor;
 use wayland_client::{self as wlc, protocol::wl_surface::WlSurface};
+use wayland_protocols::presentation_time::client::wp_presentation::WpPresentation;
+use wayland_protocols::unstable::xdg_decoration::v1::client::zxdg_decoration_manager_v1::ZxdgDecorationManagerV1;
+use wayland_protocols::viewporter::client::wp_viewporter::WpViewporter;
 use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_shell_v1::ZwlrLayerShellV1;
 use wayland_protocols::xdg_shell::client::xdg_popup;
 use wayland_protocols::xdg_shell::client::xdg_positioner;
 use wayland_protocols::xdg_shell::client::xdg_surface;
 
 use crate::kurbo;
+use crate::platform::linux::DecorationTheme;
 use crate::Scale;
 use crate::TextFieldToken;
 
 use super::error;
 use super::outputs;
+use super::protocols::fractional_scale_v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
 
 pub mod buffers;
+pub mod frame;
 pub mod idle;
 pub mod layershell;
 pub mod popup;
@@ -24,15 +33,29 @@ pub static GLOBAL_ID: crate::Counter = crate::Counter::new();
 pub trait Compositor {
     fn output(&self, id: u32) -> Option<outputs::Meta>;
     fn create_surface(&self) -> wlc::Main<WlSurface>;
+    fn create_region(&self) -> wlc::Main<WlRegion>;
     fn shared_mem(&self) -> wlc::Main<WlShm>;
     fn get_xdg_surface(&self, surface: &wlc::Main<WlSurface>)
         -> wlc::Main<xdg_surface::XdgSurface>;
     fn get_xdg_positioner(&self) -> wlc::Main<xdg_positioner::XdgPositioner>;
     fn zwlr_layershell_v1(&self) -> Option<wlc::Main<ZwlrLayerShellV1>>;
+    fn wp_viewporter(&self) -> Option<wlc::Main<WpViewporter>>;
+    fn wp_fractional_scale_manager_v1(&self) -> Option<wlc::Main<WpFractionalScaleManagerV1>>;
+    fn zxdg_decoration_manager_v1(&self) -> Option<wlc::Main<ZxdgDecorationManagerV1>>;
+    fn wl_subcompositor(&self) -> Option<wlc::Main<WlSubcompositor>>;
+    /// The presentation-time global, if its clock is the one `Instant` uses.
+    fn wp_presentation(&self) -> Option<wlc::Main<WpPresentation>>;
+    /// Wakes the event loop up to run idle callbacks.
+    fn idle_ping(&self) -> calloop::ping::Ping;
 }
 
 pub trait Decor {
     fn inner_set_title(&self, title: String);
+    fn show_titlebar(&self, show_titlebar: bool);
+    /// Makes the surface fullscreen, on `output` if it is given.
+    fn set_fullscreen(&self, fullscreen: bool, output: Option<&WlOutput>);
+    /// Sets the look of the decorations we draw when the compositor won't, or turns them off.
+    fn set_client_decorations(&self, theme: Option<DecorationTheme>);
 }
 
 impl dyn Decor {
@@ -63,7 +86,7 @@ pub trait Handle {
     fn invalidate_rect(&self, rect: kurbo::Rect);
     fn remove_text_field(&self, token: TextFieldToken);
     fn set_focused_text_field(&self, active_field: Option<TextFieldToken>);
-    fn get_idle_handle(&self) -> idle::Handle;
+    fn get_idle_handle(&self) -> Option<idle::Handle>;
     fn get_scale(&self) -> Scale;
     fn run_idle(&self);
     fn release(&self);
@@ -134,6 +157,13 @@ impl Compositor for CompositorHandle {
         }
     }
 
+    fn create_region(&self) -> wlc::Main<WlRegion> {
+        match self.inner.upgrade() {
+            None => panic!("unable to acquire underlying compositor to create a region"),
+            Some(c) => c.create_region(),
+        }
+    }
+
     fn shared_mem(&self) -> wlc::Main<WlShm> {
         match self.inner.upgrade() {
             None => panic!("unable to acquire underlying compositor to acquire shared memory"),
@@ -166,4 +196,35 @@ impl Compositor for CompositorHandle {
             Some(c) => c.zwlr_layershell_v1(),
         }
     }
+
+    fn wp_viewporter(&self) -> Option<wlc::Main<WpViewporter>> {
+        self.inner.upgrade().and_then(|c| c.wp_viewporter())
+    }
+
+    fn wp_fractional_scale_manager_v1(&self) -> Option<wlc::Main<WpFractionalScaleManagerV1>> {
+        self.inner
+            .upgrade()
+            .and_then(|c| c.wp_fractional_scale_manager_v1())
+    }
+
+    fn zxdg_decoration_manager_v1(&self) -> Option<wlc::Main<ZxdgDecorationManagerV1>> {
+        self.inner
+            .upgrade()
+            .and_then(|c| c.zxdg_decoration_manager_v1())
+    }
+
+    fn wl_subcompositor(&self) -> Option<wlc::Main<WlSubcompositor>> {
+        self.inner.upgrade().and_then(|c| c.wl_subcompositor())
+    }
+
+    fn wp_presentation(&self) -> Option<wlc::Main<WpPresentation>> {
+        self.inner.upgrade().and_then(|c| c.wp_presentation())
+    }
+
+    fn idle_ping(&self) -> calloop::ping::Ping {
+        match self.inner.upgrade() {
+            None => panic!("unable to acquire underlying compositor to wake the event loop"),
+            Some(c) => c.idle_ping(),
+        }
+    }
 }
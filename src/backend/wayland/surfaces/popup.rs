@@ -213,7 +213,7 @@ impl Handle for Surface {
         self.inner.wl_surface.set_focused_text_field(active_field)
     }
 
-    fn get_idle_handle(&self) -> super::idle::Handle {
+    fn get_idle_handle(&self) -> Option<super::idle::Handle> {
         self.inner.wl_surface.get_idle_handle()
     }
 
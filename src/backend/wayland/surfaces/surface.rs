@@ -1,18 +1,42 @@
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use wayland_client as wlc;
-use wayland_client::protocol::wl_surface;
+use wayland_client::protocol::{wl_callback, wl_output, wl_pointer, wl_seat, wl_surface};
+use wayland_protocols::presentation_time::client::wp_presentation_feedback;
+use wayland_protocols::unstable::idle_inhibit::v1::client::{
+    zwp_idle_inhibit_manager_v1, zwp_idle_inhibitor_v1,
+};
+use wayland_protocols::unstable::keyboard_shortcuts_inhibit::v1::client::{
+    zwp_keyboard_shortcuts_inhibit_manager_v1, zwp_keyboard_shortcuts_inhibitor_v1,
+};
+use wayland_protocols::unstable::pointer_constraints::v1::client::{
+    zwp_confined_pointer_v1, zwp_locked_pointer_v1, zwp_pointer_constraints_v1,
+};
+use wayland_protocols::unstable::relative_pointer::v1::client::zwp_relative_pointer_v1;
+use wayland_protocols::unstable::xdg_foreign::v2::client::{
+    zxdg_exported_v2, zxdg_exporter_v2, zxdg_imported_v2, zxdg_importer_v2,
+};
+use wayland_protocols::viewporter::client::wp_viewport;
 use wayland_protocols::xdg_shell::client::xdg_popup;
 use wayland_protocols::xdg_shell::client::xdg_positioner;
 use wayland_protocols::xdg_shell::client::xdg_surface;
 
+use crate::autoscroll::Autoscroll;
+use crate::clipboard::FormatId;
+use crate::common_util::{estimated_frame_info, shape_to_rects, DEFAULT_REFRESH_INTERVAL};
+use crate::dispatch;
 use crate::kurbo;
-use crate::window;
-use crate::{region::Region, scale::Scale, TextFieldToken};
+use crate::platform::linux::DecorationTheme;
+use crate::shortcut_capture::ShortcutCapture;
+use crate::window::{self, FrameInfo};
+use crate::{region::Region, scale::Scale, MonitorId, TextFieldToken};
 
 use super::super::Changed;
 
 use super::super::outputs;
+use super::super::protocols::fractional_scale_v1::client::wp_fractional_scale_v1;
+use super::super::text_input::TextInput;
 use super::buffers;
 use super::error;
 use super::idle;
@@ -21,7 +45,7 @@ use super::{Compositor, CompositorHandle, Decor, Handle, Outputs};
 
 pub enum DeferredTask {
     Paint,
-    AnimationClear,
+    TextInput,
 }
 
 #[derive(Clone)]
@@ -42,6 +66,9 @@ impl Surface {
         initial_size: kurbo::Size,
     ) -> Self {
         let compositor = CompositorHandle::new(c);
+        let autoscroll = Rc::new(Autoscroll::default());
+        let shortcut_capture = Rc::new(ShortcutCapture::default());
+        let handler = dispatch::wrap(handler, &autoscroll, &shortcut_capture);
         let wl_surface = match compositor.create_surface() {
             None => panic!("unable to create surface"),
             Some(v) => v,
@@ -51,15 +78,39 @@ impl Surface {
             compositor: compositor.clone(),
             wl_surface: RefCell::new(wl_surface),
             outputs: RefCell::new(std::collections::HashSet::new()),
+            preferred_output: Cell::new(None),
             buffers: buffers::Buffers::new(compositor.shared_mem(), initial_size.into()),
             logical_size: Cell::new(initial_size),
-            scale: Cell::new(1),
+            scale: Cell::new(1.0),
+            forced_scale: Cell::new(None),
+            fractional_scale: RefCell::new(None),
+            viewport: RefCell::new(None),
+            refresh_interval: Cell::new(DEFAULT_REFRESH_INTERVAL),
             anim_frame_requested: Cell::new(false),
+            frame_pending: Rc::new(Cell::new(false)),
+            last_presentation: Rc::new(Cell::new(None)),
+            paint_deferred: Cell::new(false),
             handler: RefCell::new(handler),
+            autoscroll,
+            shortcut_capture,
             idle_queue: std::sync::Arc::new(std::sync::Mutex::new(vec![])),
+            idle_ping: compositor.idle_ping(),
             active_text_input: Cell::new(None),
+            text_input: RefCell::new(None),
+            ime_allowed: Cell::new(true),
+            key_repeat: Cell::new(true),
+            drop_formats: RefCell::new(Vec::new()),
             damaged_region: RefCell::new(Region::EMPTY),
             deferred_tasks: RefCell::new(std::collections::VecDeque::new()),
+            mouse_passthrough: Cell::new(false),
+            input_shape: RefCell::new(None),
+            exported: RefCell::new(None),
+            imported: RefCell::new(None),
+            confined_pointer: RefCell::new(None),
+            locked_pointer: RefCell::new(None),
+            relative_pointer: RefCell::new(None),
+            idle_inhibitor: RefCell::new(None),
+            shortcuts_inhibitors: RefCell::new(Vec::new()),
         });
 
         // register to receive wl_surface events.
@@ -95,6 +146,8 @@ impl Surface {
                 None => panic!("unable to create surface"),
                 Some(v) => v,
             });
+        // The frame callback of the old surface will never arrive.
+        current.frame_pending.set(false);
         Surface::initsurface(current);
         Self {
             inner: current.clone(),
@@ -109,6 +162,41 @@ impl Surface {
                 Surface::consume_surface_event(&current, &a, &event, &b);
             }
         });
+
+        // With fractional scaling the compositor tells us which scale to render at, and a
+        // viewport scales the buffer back down to the logical size. Without it, we pick the
+        // integer scale of the outputs the surface is on (see `consume_surface_event`).
+        let manager = current.compositor.wp_fractional_scale_manager_v1();
+        let viewporter = current.compositor.wp_viewporter();
+        if let (Some(manager), Some(viewporter)) = (manager, viewporter) {
+            let wl_surface = current.wl_surface.borrow();
+            let fractional_scale = manager.get_fractional_scale(&wl_surface);
+            fractional_scale.quick_assign({
+                let current = std::sync::Arc::downgrade(current);
+                move |_, event, _| {
+                    let wp_fractional_scale_v1::Event::PreferredScale { scale } = event;
+                    if let Some(current) =
+                        current.upgrade().filter(|c| c.forced_scale.get().is_none())
+                    {
+                        // The scale is sent in 120ths.
+                        current.set_fractional_scale(scale as f64 / 120.0);
+                    }
+                }
+            });
+            let viewport = viewporter.get_viewport(&wl_surface);
+            drop(wl_surface);
+            // The old objects belong to the surface that was replaced.
+            if let Some(old) = current.fractional_scale.replace(Some(fractional_scale)) {
+                old.destroy();
+            }
+            if let Some(old) = current.viewport.replace(Some(viewport)) {
+                old.destroy();
+            }
+            current.update_viewport();
+        } else if let Some(scale) = current.forced_scale.get() {
+            // A replaced surface starts out with a buffer scale of 1.
+            current.wl_surface.borrow().set_buffer_scale(scale as i32);
+        }
     }
 
     pub(super) fn consume_surface_event(
@@ -129,26 +217,25 @@ impl Surface {
             }
             _ => tracing::warn!("unhandled wayland surface event {:?}", event),
         }
+        current.update_refresh_interval();
+        current.report_outputs();
 
-        if current.wl_surface.borrow().as_ref().version() >= wl_surface::REQ_SET_BUFFER_SCALE_SINCE
+        if current.fractional_scale.borrow().is_none()
+            && current.forced_scale.get().is_none()
+            && current.wl_surface.borrow().as_ref().version()
+                >= wl_surface::REQ_SET_BUFFER_SCALE_SINCE
         {
-            let new_scale = current.recompute_scale();
-            if current.set_scale(new_scale).is_changed() {
-                current.wl_surface.borrow().set_buffer_scale(new_scale);
-                // We also need to change the physical size to match the new scale
-                current
-                    .buffers
-                    .set_size(buffers::RawSize::from(current.logical_size.get()).scale(new_scale));
-                // always repaint, because the scale changed.
-                current.schedule_deferred_task(DeferredTask::Paint);
-            }
+            current.set_buffer_scale(current.recompute_scale());
         }
     }
 }
 
 impl Outputs for Surface {
     fn removed(&self, o: &outputs::Meta) {
-        self.inner.outputs.borrow_mut().remove(&o.id());
+        if self.inner.outputs.borrow_mut().remove(&o.id()) {
+            self.inner.update_refresh_interval();
+            self.inner.report_outputs();
+        }
     }
 
     fn inserted(&self, _: &outputs::Meta) {
@@ -177,8 +264,8 @@ impl Handle for Surface {
         self.inner.set_focused_text_field(active_field)
     }
 
-    fn get_idle_handle(&self) -> idle::Handle {
-        self.inner.get_idle_handle()
+    fn get_idle_handle(&self) -> Option<idle::Handle> {
+        Some(self.inner.get_idle_handle())
     }
 
     fn get_scale(&self) -> Scale {
@@ -223,7 +310,9 @@ pub struct Data {
     pub(super) wl_surface: RefCell<wlc::Main<wl_surface::WlSurface>>,
 
     /// The outputs that our surface is present on (we should get the first enter event early).
-    pub(super) outputs: RefCell<std::collections::HashSet<u32>>,
+    pub(crate) outputs: RefCell<std::collections::HashSet<u32>>,
+    /// The output to go fullscreen on, if the application chose one.
+    pub(crate) preferred_output: Cell<Option<MonitorId>>,
 
     /// Buffers in our shared memory.
     // Buffers sometimes need to move references to themselves into closures, so must be behind a
@@ -232,14 +321,42 @@ pub struct Data {
     /// The logical size of the next frame.
     pub(crate) logical_size: Cell<kurbo::Size>,
     /// The scale we are rendering to (defaults to 1)
-    pub(crate) scale: Cell<i32>,
+    pub(crate) scale: Cell<f64>,
+    /// The scale set by `WindowBuilder::force_scale`, which wins over the outputs and the
+    /// compositor.
+    forced_scale: Cell<Option<f64>>,
+    /// The compositor's preferred scale for the surface, if it supports fractional scaling.
+    fractional_scale: RefCell<Option<wlc::Main<wp_fractional_scale_v1::WpFractionalScaleV1>>>,
+    /// Scales the buffer to the logical size, when we use fractional scaling.
+    viewport: RefCell<Option<wlc::Main<wp_viewport::WpViewport>>>,
 
     /// Contains the callbacks from user code.
     pub(crate) handler: RefCell<Box<dyn window::WinHandler>>,
+    pub(crate) autoscroll: Rc<Autoscroll>,
+    pub(crate) shortcut_capture: Rc<ShortcutCapture>,
     pub(crate) active_text_input: Cell<Option<TextFieldToken>>,
+    /// The text input of the seat whose keyboard focus we have, if it supports IME.
+    pub(in crate::backend::wayland) text_input: RefCell<Option<Rc<TextInput>>>,
+    /// Whether the input method is enabled for the focused text field.
+    ime_allowed: Cell<bool>,
+    /// Whether key repeats are passed on to the handler.
+    pub(crate) key_repeat: Cell<bool>,
+    /// The custom formats read from drags over the surface.
+    pub(crate) drop_formats: RefCell<Vec<FormatId>>,
+
+    /// The time between refreshes of the outputs that the surface is on.
+    refresh_interval: Cell<Duration>,
 
     /// Whether we have requested an animation frame. This stops us requesting more than 1.
     anim_frame_requested: Cell<bool>,
+    /// Whether we are waiting for the compositor's frame callback for the last frame we
+    /// presented. Until it arrives, animation frames and paints wait.
+    frame_pending: Rc<Cell<bool>>,
+    /// Whether a paint was held back until the frame callback.
+    paint_deferred: Cell<bool>,
+    /// When the compositor last showed a frame of ours, and the refresh interval of the output
+    /// it was shown on, if it said.
+    last_presentation: Rc<Cell<Option<(Instant, Option<Duration>)>>>,
     /// Rects of the image that are damaged and need repainting in the logical coordinate space.
     ///
     /// This lives outside `data` because they can be borrowed concurrently without re-entrancy.
@@ -251,9 +368,37 @@ pub struct Data {
     deferred_tasks: RefCell<std::collections::VecDeque<DeferredTask>>,
 
     idle_queue: std::sync::Arc<std::sync::Mutex<Vec<idle::Kind>>>,
+    idle_ping: calloop::ping::Ping,
+
+    /// Whether the surface ignores pointer input entirely.
+    mouse_passthrough: Cell<bool>,
+    /// The part of the surface that receives pointer input, in display points, or `None` for
+    /// all of it.
+    input_shape: RefCell<Option<Vec<kurbo::Rect>>>,
+
+    /// The xdg-foreign export of this surface, which is revoked when it is destroyed.
+    exported: RefCell<Option<wlc::Main<zxdg_exported_v2::ZxdgExportedV2>>>,
+    /// A surface of another process that this one is transient for.
+    imported: RefCell<Option<wlc::Main<zxdg_imported_v2::ZxdgImportedV2>>>,
+    /// The pointer confinement requested with `confine_pointer`, if any.
+    confined_pointer: RefCell<Option<wlc::Main<zwp_confined_pointer_v1::ZwpConfinedPointerV1>>>,
+    /// The pointer lock requested with `lock_pointer`, until it is released or lost.
+    locked_pointer: RefCell<Option<wlc::Main<zwp_locked_pointer_v1::ZwpLockedPointerV1>>>,
+    /// The relative pointer that raw pointer input comes from, while it is on.
+    relative_pointer: RefCell<Option<wlc::Main<zwp_relative_pointer_v1::ZwpRelativePointerV1>>>,
+    /// Keeps the screen on while the surface is visible, in kiosk mode.
+    idle_inhibitor: RefCell<Option<wlc::Main<zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1>>>,
+    /// Keeps the compositor's shortcuts from firing, one for each seat, in kiosk mode.
+    shortcuts_inhibitors: RefCell<
+        Vec<wlc::Main<zwp_keyboard_shortcuts_inhibitor_v1::ZwpKeyboardShortcutsInhibitorV1>>,
+    >,
 }
 
 impl Data {
+    pub(crate) fn wl_surface(&self) -> wlc::Main<wl_surface::WlSurface> {
+        self.wl_surface.borrow().clone()
+    }
+
     pub(crate) fn output(&self) -> Option<outputs::Meta> {
         match self.outputs.borrow().iter().find(|_| true) {
             None => None,
@@ -314,6 +459,7 @@ impl Data {
         let previous_logical_size = self.logical_size.replace(dim);
         if previous_logical_size != dim {
             self.buffers.set_size(raw_logical_size.scale(scale));
+            self.update_viewport();
         }
 
         dim
@@ -332,6 +478,59 @@ impl Data {
         );
     }
 
+    pub(crate) fn refresh_interval(&self) -> Duration {
+        self.refresh_interval.get()
+    }
+
+    /// Recompute the refresh interval from the outputs that the surface is on, and tell the
+    /// handler if it changed.
+    /// The timing of the frame that is about to be drawn: it will be shown at the first
+    /// refresh after now, counting from the last frame the compositor showed.
+    fn frame_info(&self) -> FrameInfo {
+        let now = Instant::now();
+        let (last, refresh) = match self.last_presentation.get() {
+            Some(last) => last,
+            None => return estimated_frame_info(self.refresh_interval.get()),
+        };
+        let interval = refresh.unwrap_or_else(|| self.refresh_interval.get());
+        let frames = ((now - last).as_secs_f64() / interval.as_secs_f64()).floor() as u32 + 1;
+        FrameInfo {
+            present_time: last + interval * frames,
+            refresh_interval: interval,
+        }
+    }
+
+    fn update_refresh_interval(&self) {
+        // We assume that the compositor keeps up with the fastest of the outputs. Their refresh
+        // rates are in mHz.
+        let refresh = self
+            .outputs
+            .borrow()
+            .iter()
+            .filter_map(|id| self.compositor.output(*id))
+            .map(|output| output.refresh)
+            .max();
+        let interval = match refresh {
+            Some(mhz) if mhz > 0 => Duration::from_secs_f64(1000.0 / mhz as f64),
+            _ => DEFAULT_REFRESH_INTERVAL,
+        };
+        if self.refresh_interval.replace(interval) != interval {
+            self.with_handler(|h| h.refresh_interval_changed(interval));
+        }
+    }
+
+    /// Tell the handler which outputs the surface is on.
+    fn report_outputs(&self) {
+        let mut outputs: Vec<_> = self
+            .outputs
+            .borrow()
+            .iter()
+            .map(|id| MonitorId::from_raw(*id as u64))
+            .collect();
+        outputs.sort();
+        self.with_handler(|h| h.outputs_changed(&outputs));
+    }
+
     /// Recompute the scale to use (the maximum of all the scales for the different outputs this
     /// surface is drawn to).
     fn recompute_scale(&self) -> i32 {
@@ -342,26 +541,83 @@ impl Data {
     /// Sets the scale
     ///
     /// Up to the caller to make sure `physical_size`, `logical_size` and `scale` are consistent.
-    fn set_scale(&self, new_scale: i32) -> Changed {
+    fn set_scale(&self, new_scale: f64) -> Changed {
         tracing::debug!("set_scale initiated");
         if self.scale.get() != new_scale {
             self.scale.set(new_scale);
             // (re-entrancy) Report change to client
             self.handler
                 .borrow_mut()
-                .scale(Scale::new(new_scale as f64, new_scale as f64));
+                .scale(Scale::new(new_scale, new_scale));
             Changed::Changed
         } else {
             Changed::Unchanged
         }
     }
 
+    /// Switches to an integer scale, which the compositor applies to the buffer.
+    fn set_buffer_scale(&self, new_scale: i32) {
+        if self.set_scale(new_scale as f64).is_changed() {
+            self.wl_surface.borrow().set_buffer_scale(new_scale);
+            // We also need to change the physical size to match the new scale
+            self.buffers
+                .set_size(buffers::RawSize::from(self.logical_size.get()).scale(new_scale as f64));
+            // always repaint, because the scale changed.
+            self.schedule_deferred_task(DeferredTask::Paint);
+        }
+    }
+
+    /// Renders at `scale` from now on, ignoring the outputs and the compositor's preference.
+    ///
+    /// Without fractional scaling the buffer scale has to be a whole number, so the scale is
+    /// rounded.
+    pub(crate) fn force_scale(&self, scale: f64) {
+        if self.viewport.borrow().is_some() {
+            self.forced_scale.set(Some(scale));
+            self.set_fractional_scale(scale);
+        } else if self.wl_surface.borrow().as_ref().version()
+            >= wl_surface::REQ_SET_BUFFER_SCALE_SINCE
+        {
+            let scale = scale.round().max(1.0);
+            self.forced_scale.set(Some(scale));
+            self.set_buffer_scale(scale as i32);
+        } else {
+            tracing::warn!("the compositor can't scale buffers, so the scale can't be forced");
+        }
+    }
+
+    /// Switches to the fractional scale preferred by the compositor.
+    fn set_fractional_scale(&self, new_scale: f64) {
+        if self.set_scale(new_scale).is_changed() {
+            // The buffer scale stays at 1; the viewport maps the buffer to the logical size.
+            self.buffers
+                .set_size(buffers::RawSize::from(self.logical_size.get()).scale(new_scale));
+            // always repaint, because the scale changed.
+            self.schedule_deferred_task(DeferredTask::Paint);
+        }
+    }
+
+    /// Tells the compositor the logical size of the surface, if the buffer is scaled by a
+    /// viewport.
+    fn update_viewport(&self) {
+        if let Some(viewport) = &*self.viewport.borrow() {
+            let size = buffers::RawSize::from(self.logical_size.get());
+            if size.is_empty() {
+                // An empty destination is a protocol error, so we unset it instead.
+                viewport.set_destination(-1, -1);
+            } else {
+                viewport.set_destination(size.width, size.height);
+            }
+        }
+    }
+
     /// Paint the next frame.
     ///
     /// The buffers object is responsible for calling this function after we called
     /// `request_paint`.
     ///
-    /// - `buf` is what we draw the frame into
+    /// - `buf` is the buffer that is attached for the frame. It isn't drawn into, because there
+    ///   is no software paint path for handlers yet.
     /// - `size` is the physical size in pixels we are drawing.
     /// - `force` means draw the whole frame, even if it wasn't all invalidated.
     pub(super) fn paint(&self, physical_size: buffers::RawSize, buf: &mut [u8], force: bool) {
@@ -403,9 +659,245 @@ impl Data {
         // reset damage ready for next frame.
         self.damaged_region.borrow_mut().clear();
         self.buffers.attach(self);
+        // Ask to be told when the compositor shows this frame, which is when it's worth
+        // drawing the next one.
+        let frame_pending = self.frame_pending.clone();
+        self.wl_surface
+            .borrow()
+            .frame()
+            .quick_assign(move |_, event, _| {
+                if let wl_callback::Event::Done { .. } = event {
+                    frame_pending.set(false);
+                }
+            });
+        self.frame_pending.set(true);
+        if let Some(presentation) = self.compositor.wp_presentation() {
+            let last_presentation = self.last_presentation.clone();
+            presentation
+                .feedback(&self.wl_surface.borrow())
+                .quick_assign(move |_, event, _| {
+                    if let wp_presentation_feedback::Event::Presented {
+                        tv_sec_hi,
+                        tv_sec_lo,
+                        tv_nsec,
+                        refresh,
+                        ..
+                    } = event
+                    {
+                        let secs = ((tv_sec_hi as u64) << 32) | tv_sec_lo as u64;
+                        let refresh = (refresh > 0).then(|| Duration::from_nanos(refresh as u64));
+                        if let Some(time) = monotonic_instant(Duration::new(secs, tv_nsec)) {
+                            last_presentation.set(Some((time, refresh)));
+                        }
+                    }
+                });
+        }
         self.wl_surface.borrow().commit();
     }
 
+    pub(crate) fn set_mouse_passthrough(&self, passthrough: bool) {
+        self.mouse_passthrough.set(passthrough);
+        self.update_input_region();
+    }
+
+    pub(crate) fn set_shape(&self, shape: Option<kurbo::BezPath>) {
+        let rects = shape.map(|shape| shape_to_rects(&shape, Scale::default()));
+        self.input_shape.replace(rects);
+        self.update_input_region();
+    }
+
+    /// Exports the surface with xdg-foreign, calling `f` with its handle.
+    pub(crate) fn export(
+        &self,
+        exporter: &wlc::Main<zxdg_exporter_v2::ZxdgExporterV2>,
+        f: Box<dyn FnOnce(Option<String>)>,
+    ) {
+        let exported = exporter.export_toplevel(&self.wl_surface.borrow());
+        let mut f = Some(f);
+        exported.quick_assign(move |_, event, _| match event {
+            zxdg_exported_v2::Event::Handle { handle } => {
+                if let Some(f) = f.take() {
+                    f(Some(format!("wayland:{}", handle)));
+                }
+            }
+            _ => tracing::warn!("unhandled zxdg_exported_v2 event {:?}", event),
+        });
+        if let Some(old) = self.exported.replace(Some(exported)) {
+            old.destroy();
+        }
+    }
+
+    /// Makes the surface transient for the surface of another process exported as `handle`.
+    pub(crate) fn set_foreign_parent(
+        &self,
+        importer: &wlc::Main<zxdg_importer_v2::ZxdgImporterV2>,
+        handle: &str,
+    ) {
+        let imported = importer.import_toplevel(handle.to_owned());
+        imported.quick_assign(|imported, event, _| match event {
+            zxdg_imported_v2::Event::Destroyed => {
+                tracing::debug!("foreign parent surface is gone");
+                imported.destroy();
+            }
+            _ => tracing::warn!("unhandled zxdg_imported_v2 event {:?}", event),
+        });
+        imported.set_parent_of(&self.wl_surface.borrow());
+        if let Some(old) = self.imported.replace(Some(imported)) {
+            old.destroy();
+        }
+    }
+
+    /// Confines `pointer` to `region` of the surface, in logical coordinates, or releases it.
+    pub(crate) fn confine_pointer(
+        &self,
+        constraints: &wlc::Main<zwp_pointer_constraints_v1::ZwpPointerConstraintsV1>,
+        pointer: &wl_pointer::WlPointer,
+        region: Option<kurbo::Rect>,
+    ) {
+        if let Some(old) = self.confined_pointer.take() {
+            old.destroy();
+        }
+        let region = match region {
+            Some(region) => region.round(),
+            None => return,
+        };
+        // A surface can only have one constraint on a pointer.
+        self.unlock_pointer();
+        let wl_region = self.compositor.create_region();
+        wl_region.add(
+            region.x0 as i32,
+            region.y0 as i32,
+            region.width() as i32,
+            region.height() as i32,
+        );
+        let confined = constraints.confine_pointer(
+            &self.wl_surface.borrow(),
+            pointer,
+            Some(&wl_region),
+            zwp_pointer_constraints_v1::Lifetime::Persistent,
+        );
+        wl_region.destroy();
+        confined.quick_assign(|_, event, _| {
+            tracing::trace!("confined pointer event {:?}", event);
+        });
+        self.confined_pointer.replace(Some(confined));
+    }
+
+    /// Locks `pointer` in place over the surface, releasing any confinement.
+    pub(crate) fn lock_pointer(
+        self: &std::sync::Arc<Self>,
+        constraints: &wlc::Main<zwp_pointer_constraints_v1::ZwpPointerConstraintsV1>,
+        pointer: &wl_pointer::WlPointer,
+    ) {
+        if let Some(confined) = self.confined_pointer.take() {
+            confined.destroy();
+        }
+        self.unlock_pointer();
+        let locked = constraints.lock_pointer(
+            &self.wl_surface.borrow(),
+            pointer,
+            None,
+            zwp_pointer_constraints_v1::Lifetime::Oneshot,
+        );
+        locked.quick_assign({
+            let data = std::sync::Arc::downgrade(self);
+            move |_, event, _| match event {
+                // With a oneshot lifetime, the lock is dead once the compositor breaks it.
+                zwp_locked_pointer_v1::Event::Unlocked => {
+                    if let Some(data) = data.upgrade() {
+                        data.unlock_pointer();
+                        data.with_handler(|handler| handler.pointer_lock_lost());
+                    }
+                }
+                event => tracing::trace!("locked pointer event {:?}", event),
+            }
+        });
+        self.locked_pointer.replace(Some(locked));
+    }
+
+    pub(crate) fn unlock_pointer(&self) {
+        if let Some(locked) = self.locked_pointer.take() {
+            locked.destroy();
+        }
+    }
+
+    /// Replaces the relative pointer that raw pointer input comes from, or turns raw input off.
+    pub(crate) fn set_relative_pointer(
+        &self,
+        relative: Option<wlc::Main<zwp_relative_pointer_v1::ZwpRelativePointerV1>>,
+    ) {
+        if let Some(old) = self.relative_pointer.replace(relative) {
+            old.destroy();
+        }
+    }
+
+    /// Keeps the screen from blanking while the surface is visible, or stops if `manager` is
+    /// `None`.
+    pub(crate) fn inhibit_idle(
+        &self,
+        manager: Option<&wlc::Main<zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1>>,
+    ) {
+        if let Some(old) = self.idle_inhibitor.take() {
+            old.destroy();
+        }
+        if let Some(manager) = manager {
+            let inhibitor = manager.create_inhibitor(&self.wl_surface.borrow());
+            self.idle_inhibitor.replace(Some(inhibitor));
+        }
+    }
+
+    /// Asks the compositor to send its own shortcuts on `seats` to the surface instead, or
+    /// stops if `manager` is `None`. The compositor may ask the user first.
+    pub(crate) fn inhibit_shortcuts(
+        &self,
+        manager: Option<
+            &wlc::Main<
+                zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1,
+            >,
+        >,
+        seats: &[wl_seat::WlSeat],
+    ) {
+        for old in self.shortcuts_inhibitors.take() {
+            old.destroy();
+        }
+        if let Some(manager) = manager {
+            let inhibitors = seats.iter().map(|seat| {
+                let inhibitor = manager.inhibit_shortcuts(&self.wl_surface.borrow(), seat);
+                inhibitor.quick_assign(|_, event, _| {
+                    tracing::debug!("keyboard shortcuts inhibitor event {:?}", event);
+                });
+                inhibitor
+            });
+            self.shortcuts_inhibitors.replace(inhibitors.collect());
+        }
+    }
+
+    /// Tells the compositor which part of the surface receives pointer input: nothing at all
+    /// with mouse passthrough, otherwise the window shape or (by default) everything.
+    fn update_input_region(&self) {
+        let surface = self.wl_surface.borrow();
+        let input_shape = self.input_shape.borrow();
+        if self.mouse_passthrough.get() || input_shape.is_some() {
+            let region = self.compositor.create_region();
+            if !self.mouse_passthrough.get() {
+                for rect in input_shape.iter().flatten() {
+                    region.add(
+                        rect.x0 as i32,
+                        rect.y0 as i32,
+                        rect.width() as i32,
+                        rect.height() as i32,
+                    );
+                }
+            }
+            surface.set_input_region(Some(&region));
+            region.destroy();
+        } else {
+            surface.set_input_region(None);
+        }
+        // The input region is double-buffered state, so it only applies on commit.
+        surface.commit();
+    }
+
     /// Request invalidation of the entire window contents.
     fn invalidate(&self) {
         tracing::trace!("invalidate initiated");
@@ -455,10 +947,25 @@ impl Data {
     fn run_deferred_task(&self, task: DeferredTask) {
         match task {
             DeferredTask::Paint => {
-                self.buffers.request_paint(self);
+                if self.frame_pending.get() {
+                    self.paint_deferred.set(true);
+                } else {
+                    self.paint_deferred.set(false);
+                    self.buffers.request_paint(self);
+                }
             }
-            DeferredTask::AnimationClear => {
-                self.anim_frame_requested.set(false);
+            DeferredTask::TextInput => {
+                if let Some(text_input) = self.text_input.borrow().clone() {
+                    // Without a field, the input method is disabled and keys come through as
+                    // they are.
+                    let field = self
+                        .active_text_input
+                        .get()
+                        .filter(|_| self.ime_allowed.get());
+                    self.with_handler_and_dont_check_the_other_borrows(|handler| {
+                        text_input.update(handler, field)
+                    });
+                }
             }
         }
     }
@@ -466,7 +973,7 @@ impl Data {
     pub(super) fn get_size(&self) -> kurbo::Size {
         // size in pixels, so we must apply scale.
         let logical_size = self.logical_size.get();
-        let scale = self.scale.get() as f64;
+        let scale = self.scale.get();
         kurbo::Size::new(
             logical_size.width as f64 * scale,
             logical_size.height as f64 * scale,
@@ -477,32 +984,43 @@ impl Data {
         if self.anim_frame_requested.replace(true) {
             return;
         }
-
-        let idle = self.get_idle_handle();
-        idle.add_idle_callback(move |winhandle| {
-            winhandle.prepare_paint();
-        });
-        self.schedule_deferred_task(DeferredTask::AnimationClear);
+        // The frame is delivered by `run_idle`, so make sure the event loop gets there.
+        self.idle_ping.ping();
     }
 
     pub(super) fn remove_text_field(&self, token: TextFieldToken) {
         if self.active_text_input.get() == Some(token) {
             self.active_text_input.set(None);
+            self.schedule_deferred_task(DeferredTask::TextInput);
         }
     }
 
     pub(super) fn set_focused_text_field(&self, active_field: Option<TextFieldToken>) {
         self.active_text_input.set(active_field);
+        self.schedule_deferred_task(DeferredTask::TextInput);
+    }
+
+    pub(crate) fn set_ime_allowed(&self, allowed: bool) {
+        if self.ime_allowed.replace(allowed) != allowed {
+            self.schedule_deferred_task(DeferredTask::TextInput);
+        }
+    }
+
+    pub(in crate::backend::wayland) fn update_text_field(&self, token: TextFieldToken) {
+        if self.active_text_input.get() == Some(token) {
+            self.schedule_deferred_task(DeferredTask::TextInput);
+        }
     }
 
     pub(super) fn get_idle_handle(&self) -> idle::Handle {
         idle::Handle {
             queue: self.idle_queue.clone(),
+            ping: self.idle_ping.clone(),
         }
     }
 
     pub(super) fn get_scale(&self) -> Scale {
-        let scale = self.scale.get() as f64;
+        let scale = self.scale.get();
         Scale::new(scale, scale)
     }
 
@@ -510,13 +1028,66 @@ impl Data {
         self.with_handler(|winhandle| {
             idle::run(&self.get_idle_handle(), winhandle);
         });
+
+        // Animation frames and paints wait for the compositor to show the last frame, so that
+        // we draw at the display's refresh rate.
+        if self.frame_pending.get() {
+            return;
+        }
+        if self.anim_frame_requested.replace(false) {
+            let info = self.frame_info();
+            self.with_handler(|winhandle| {
+                winhandle.prepare_frame(info);
+                winhandle.prepare_paint();
+            });
+        }
+        if !self.frame_pending.get() && self.paint_deferred.get() {
+            self.schedule_deferred_task(DeferredTask::Paint);
+            self.run_deferred_tasks();
+        }
     }
 
     pub(super) fn release(&self) {
+        if let Some(exported) = self.exported.take() {
+            exported.destroy();
+        }
+        if let Some(imported) = self.imported.take() {
+            imported.destroy();
+        }
+        if let Some(confined) = self.confined_pointer.take() {
+            confined.destroy();
+        }
+        self.unlock_pointer();
+        if let Some(relative) = self.relative_pointer.take() {
+            relative.destroy();
+        }
+        if let Some(inhibitor) = self.idle_inhibitor.take() {
+            inhibitor.destroy();
+        }
+        for inhibitor in self.shortcuts_inhibitors.take() {
+            inhibitor.destroy();
+        }
+        if let Some(fractional_scale) = self.fractional_scale.take() {
+            fractional_scale.destroy();
+        }
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
         self.wl_surface.borrow().destroy();
     }
 }
 
+/// Converts a time on the monotonic clock to an `Instant`.
+fn monotonic_instant(time: Duration) -> Option<Instant> {
+    let now = Instant::now();
+    let clock = nix::time::clock_gettime(nix::time::ClockId::CLOCK_MONOTONIC).ok()?;
+    let clock = Duration::new(clock.tv_sec() as u64, clock.tv_nsec() as u32);
+    match clock.checked_sub(time) {
+        Some(ago) => now.checked_sub(ago),
+        None => now.checked_add(time - clock),
+    }
+}
+
 #[derive(Default)]
 pub struct Dead;
 
@@ -536,6 +1107,18 @@ impl Decor for Dead {
     fn inner_set_title(&self, title: String) {
         tracing::warn!("set_title not implemented for this surface: {:?}", title);
     }
+
+    fn show_titlebar(&self, _show_titlebar: bool) {
+        tracing::warn!("show_titlebar not implemented for this surface");
+    }
+
+    fn set_fullscreen(&self, _fullscreen: bool, _output: Option<&wl_output::WlOutput>) {
+        tracing::warn!("set_fullscreen not implemented for this surface");
+    }
+
+    fn set_client_decorations(&self, _theme: Option<DecorationTheme>) {
+        tracing::warn!("set_client_decorations not implemented for this surface");
+    }
 }
 
 impl Outputs for Dead {
@@ -576,8 +1159,9 @@ impl Handle for Dead {
         tracing::warn!("set_focused_text_field invoked on a dead surface")
     }
 
-    fn get_idle_handle(&self) -> idle::Handle {
-        panic!("get_idle_handle invoked on a dead surface")
+    fn get_idle_handle(&self) -> Option<idle::Handle> {
+        tracing::warn!("get_idle_handle invoked on a dead surface");
+        None
     }
 
     fn get_scale(&self) -> Scale {
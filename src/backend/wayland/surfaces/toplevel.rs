@@ -1,11 +1,20 @@
+use std::rc::Rc;
+
 use wayland_client as wlc;
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_protocols::unstable::xdg_decoration::v1::client::zxdg_toplevel_decoration_v1::{
+    self, Mode, ZxdgToplevelDecorationV1,
+};
 use wayland_protocols::xdg_shell::client::xdg_surface;
 use wayland_protocols::xdg_shell::client::xdg_toplevel;
 
+use crate::common_util;
 use crate::kurbo;
-use crate::window;
+use crate::platform::linux::DecorationTheme;
+use crate::window::{self, DecorationMode};
 
 use super::error;
+use super::frame::{self, Frame};
 use super::surface;
 use super::Compositor;
 use super::CompositorHandle;
@@ -18,6 +27,10 @@ struct Inner {
     wl_surface: surface::Surface,
     pub(super) xdg_surface: wlc::Main<xdg_surface::XdgSurface>,
     pub(super) xdg_toplevel: wlc::Main<xdg_toplevel::XdgToplevel>,
+    /// Negotiates who draws the decorations, if the compositor supports xdg-decoration.
+    decoration: Option<wlc::Main<ZxdgToplevelDecorationV1>>,
+    /// The decorations we draw when the compositor won't, if it supports subsurfaces.
+    frame: Option<Rc<Frame>>,
 }
 
 impl From<Inner> for std::sync::Arc<surface::Data> {
@@ -37,12 +50,23 @@ impl Surface {
         handler: Box<dyn window::WinHandler>,
         size: kurbo::Size,
         min_size: Option<kurbo::Size>,
+        resize_increments: Option<kurbo::Size>,
+        show_titlebar: bool,
     ) -> Self {
+        // Resize increments are counted from the requested minimum size.
+        let base_size = min_size.unwrap_or(kurbo::Size::ZERO);
         let min_size = min_size.unwrap_or_else(|| kurbo::Size::from((1.0, 1.0)));
         let compositor = CompositorHandle::new(c);
         let wl_surface = surface::Surface::new(compositor.clone(), handler, kurbo::Size::ZERO);
         let xdg_surface = compositor.get_xdg_surface(&wl_surface.inner.wl_surface.borrow());
         let xdg_toplevel = xdg_surface.get_toplevel();
+        let frame = Frame::new(
+            &compositor,
+            wl_surface.clone(),
+            xdg_surface.clone(),
+            xdg_toplevel.clone(),
+            show_titlebar,
+        );
 
         // register to receive xdg_surface events.
         xdg_surface.quick_assign({
@@ -62,6 +86,7 @@ impl Surface {
 
         xdg_toplevel.quick_assign({
             let wl_surface = wl_surface.clone();
+            let frame = frame.clone();
             move |_xdg_toplevel, event, a3| match event {
                 xdg_toplevel::Event::Configure {
                     width,
@@ -76,19 +101,36 @@ impl Surface {
                         a3
                     );
 
+                    let states = frame::States::from_raw(&states);
+                    if let Some(frame) = &frame {
+                        frame.set_states(states);
+                    }
+                    // The compositor's size includes the titlebar, if we draw one.
+                    let titlebar = frame.as_ref().map_or(0.0, |frame| frame.titlebar_height());
+
                     // If the width or height arguments are zero, it means the client should decide its own window dimension.
                     // This may happen when the compositor needs to configure the state of the surface
                     // but doesn't have any information about any previous or expected dimension.
                     let (width, height) = if width == 0 || height == 0 {
                         (size.width, size.height)
                     } else {
-                        (width as f64, height as f64)
+                        (width as f64, height as f64 - titlebar)
                     };
 
-                    let dim =
+                    let mut dim =
                         kurbo::Size::new(width.max(min_size.width), height.max(min_size.height));
 
+                    // The compositor insists on the exact size of maximized and fullscreen
+                    // windows, but otherwise we're free to pick something smaller.
+                    let constrained = states.maximized || states.fullscreen;
+                    if let Some(increments) = resize_increments.filter(|_| !constrained) {
+                        dim = common_util::snap_to_increments(dim, base_size, increments);
+                    }
+
                     wl_surface.update_dimensions(dim);
+                    if let Some(frame) = &frame {
+                        frame.resize(dim);
+                    }
                 }
                 xdg_toplevel::Event::Close => {
                     tracing::info!("xdg close event {:?}", event);
@@ -98,10 +140,41 @@ impl Surface {
             }
         });
 
+        let decoration = compositor
+            .zxdg_decoration_manager_v1()
+            .map(|manager| manager.get_toplevel_decoration(&xdg_toplevel));
+        if let Some(decoration) = &decoration {
+            decoration.quick_assign({
+                let wl_surface = wl_surface.clone();
+                let frame = frame.clone();
+                move |_, event, _| match event {
+                    zxdg_toplevel_decoration_v1::Event::Configure { mode } => {
+                        let mode = match mode {
+                            Mode::ServerSide => DecorationMode::ServerSide,
+                            _ => DecorationMode::ClientSide,
+                        };
+                        if let Some(frame) = &frame {
+                            frame.set_client_side(mode == DecorationMode::ClientSide);
+                        }
+                        wl_surface
+                            .inner
+                            .with_handler(|handler| handler.decoration_mode_changed(mode));
+                    }
+                    _ => tracing::warn!("unhandled decoration event {:?}", event),
+                }
+            });
+            decoration.set_mode(decoration_mode(show_titlebar));
+        } else if let Some(frame) = &frame {
+            // Without xdg-decoration, the compositor never draws decorations.
+            frame.set_client_side(true);
+        }
+
         let inner = Inner {
             wl_surface,
             xdg_toplevel,
             xdg_surface,
+            decoration,
+            frame,
         };
 
         inner
@@ -127,6 +200,24 @@ impl Surface {
     pub(crate) fn commit(&self) {
         self.inner.wl_surface.commit();
     }
+
+    /// Sets the app id, which compositors use to group windows and to find their desktop entry.
+    pub(crate) fn set_app_id(&self, app_id: String) {
+        self.inner.xdg_toplevel.set_app_id(app_id);
+    }
+
+    /// Whether the compositor supports server-side decorations at all.
+    pub(crate) fn can_decorate(&self) -> bool {
+        self.inner.decoration.is_some()
+    }
+}
+
+fn decoration_mode(show_titlebar: bool) -> Mode {
+    if show_titlebar {
+        Mode::ServerSide
+    } else {
+        Mode::ClientSide
+    }
 }
 
 impl Popup for Surface {
@@ -142,8 +233,36 @@ impl Popup for Surface {
 
 impl Decor for Surface {
     fn inner_set_title(&self, title: String) {
+        if let Some(frame) = &self.inner.frame {
+            frame.set_title(title.clone());
+        }
         self.inner.xdg_toplevel.set_title(title);
     }
+
+    fn show_titlebar(&self, show_titlebar: bool) {
+        if let Some(decoration) = &self.inner.decoration {
+            decoration.set_mode(decoration_mode(show_titlebar));
+        }
+        if let Some(frame) = &self.inner.frame {
+            frame.set_show_titlebar(show_titlebar);
+        }
+    }
+
+    fn set_fullscreen(&self, fullscreen: bool, output: Option<&WlOutput>) {
+        if fullscreen {
+            // Without an output, the compositor picks one.
+            self.inner.xdg_toplevel.set_fullscreen(output);
+        } else {
+            self.inner.xdg_toplevel.unset_fullscreen();
+        }
+    }
+
+    fn set_client_decorations(&self, theme: Option<DecorationTheme>) {
+        match &self.inner.frame {
+            Some(frame) => frame.set_theme(theme),
+            None => tracing::warn!("the compositor doesn't support subsurfaces for decorations"),
+        }
+    }
 }
 
 impl From<&Surface> for std::sync::Arc<surface::Data> {
@@ -0,0 +1,303 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! IME support, through the text-input-v3 protocol.
+//!
+//! The compositor runs the input method, and tells us about preedit and committed text. We
+//! apply that to the focused text field through its [`InputHandler`], and tell the compositor
+//! about the text around the cursor and where the cursor is, so it can place its candidate
+//! window.
+//!
+//! [`InputHandler`]: crate::text::InputHandler
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::{Arc, Weak};
+
+use wayland_client as wl;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_protocols::unstable::text_input::v3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3;
+use wayland_protocols::unstable::text_input::v3::client::zwp_text_input_v3::{
+    self, ChangeCause, ZwpTextInputV3,
+};
+
+use crate::keyboard::{CompositionEvent, CompositionState};
+use crate::text::{Affinity, Selection};
+use crate::window::WinHandler;
+use crate::TextFieldToken;
+
+use super::application::Data;
+use super::dnd;
+use super::surfaces::surface;
+
+/// The most text around the cursor that we send; the protocol doesn't allow more than 4000
+/// bytes.
+const MAX_SURROUNDING: usize = 4000;
+
+/// A seat's text input.
+pub(super) struct TextInput {
+    wl: wl::Main<ZwpTextInputV3>,
+    /// The surface that has keyboard focus on this seat, if it is one of ours.
+    surface: RefCell<Option<Weak<surface::Data>>>,
+    /// The text field the input method is enabled for.
+    field: Cell<Option<TextFieldToken>>,
+    /// The events since the last `done`, which are applied all together.
+    pending: RefCell<Pending>,
+    /// The preedit text in the document, if we are composing.
+    preedit: RefCell<Option<String>>,
+}
+
+#[derive(Default)]
+struct Pending {
+    preedit: Option<(String, i32, i32)>,
+    commit: Option<String>,
+    delete: Option<(u32, u32)>,
+}
+
+impl TextInput {
+    pub(super) fn new(
+        manager: &wl::Main<ZwpTextInputManagerV3>,
+        seat: &WlSeat,
+        appdata: &Arc<Data>,
+    ) -> Rc<TextInput> {
+        let text_input = Rc::new(TextInput {
+            wl: manager.get_text_input(seat),
+            surface: RefCell::new(None),
+            field: Cell::new(None),
+            pending: RefCell::new(Pending::default()),
+            preedit: RefCell::new(None),
+        });
+        text_input.wl.quick_assign({
+            let appdata = Arc::downgrade(appdata);
+            let text_input = Rc::downgrade(&text_input);
+            move |_, event, _| {
+                if let (Some(appdata), Some(text_input)) = (appdata.upgrade(), text_input.upgrade())
+                {
+                    text_input.consume(&appdata, event);
+                }
+            }
+        });
+        text_input
+    }
+
+    fn consume(self: &Rc<Self>, appdata: &Data, event: zwp_text_input_v3::Event) {
+        tracing::trace!("text input event {:?}", event);
+        match event {
+            zwp_text_input_v3::Event::Enter { surface } => {
+                let data = match dnd::find_window(appdata, &surface).and_then(|w| w.data()) {
+                    Some(data) => data,
+                    None => return,
+                };
+                self.surface.replace(Some(Arc::downgrade(&data)));
+                data.text_input.replace(Some(self.clone()));
+                data.schedule_deferred_task(surface::DeferredTask::TextInput);
+                data.run_deferred_tasks();
+            }
+            zwp_text_input_v3::Event::Leave { .. } => {
+                // The compositor forgets the preedit text, so we remove it from the document.
+                self.pending.take();
+                if self.preedit.borrow().is_some() {
+                    self.done();
+                }
+                if let Some(data) = self.surface.take().and_then(|data| data.upgrade()) {
+                    data.text_input.take();
+                }
+                if self.field.take().is_some() {
+                    self.wl.disable();
+                    self.wl.commit();
+                }
+            }
+            zwp_text_input_v3::Event::PreeditString {
+                text,
+                cursor_begin,
+                cursor_end,
+            } => {
+                self.pending.borrow_mut().preedit =
+                    text.map(|text| (text, cursor_begin, cursor_end));
+            }
+            zwp_text_input_v3::Event::CommitString { text } => {
+                self.pending.borrow_mut().commit = text;
+            }
+            zwp_text_input_v3::Event::DeleteSurroundingText {
+                before_length,
+                after_length,
+            } => {
+                self.pending.borrow_mut().delete = Some((before_length, after_length));
+            }
+            zwp_text_input_v3::Event::Done { .. } => self.done(),
+            _ => tracing::warn!("unhandled text input event {:?}", event),
+        }
+    }
+
+    /// Applies the pending events to the focused text field.
+    fn done(&self) {
+        let pending = self.pending.take();
+        let data = match self.surface.borrow().as_ref().and_then(Weak::upgrade) {
+            Some(data) => data,
+            None => return,
+        };
+        let token = match self.field.get() {
+            Some(token) => token,
+            None => return,
+        };
+        data.with_handler(|handler| {
+            self.apply(handler, token, pending);
+            self.send_state(handler, token, ChangeCause::InputMethod);
+        });
+    }
+
+    fn apply(&self, handler: &mut dyn WinHandler, token: TextFieldToken, pending: Pending) {
+        let preedit = pending.preedit.filter(|(text, ..)| !text.is_empty());
+        let mut input = handler.acquire_input_lock(token, true);
+        // The protocol says to remove the old preedit text, then delete around the cursor,
+        // then insert the committed text, and then insert the new preedit text.
+        if let Some(range) = input.composition_range() {
+            input.replace_range(range, "");
+        }
+        if let Some((before, after)) = pending.delete {
+            let selection = input.selection().range();
+            let start = selection.start.saturating_sub(before as usize);
+            let end = (selection.end + after as usize).min(input.len());
+            if input.is_char_boundary(start) && input.is_char_boundary(end) {
+                input.replace_range(selection.end..end, "");
+                input.replace_range(start..selection.start, "");
+                input.set_selection(Selection::new(
+                    start,
+                    selection.end - (selection.start - start),
+                ));
+            }
+        }
+        if let Some(text) = &pending.commit {
+            let selection = input.selection().range();
+            input.replace_range(selection, text);
+        }
+        if let Some((text, begin, end)) = &preedit {
+            let selection = input.selection().range();
+            let start = selection.start;
+            input.replace_range(selection, text);
+            input.set_composition_range(Some(start..start + text.len()));
+            // A negative cursor means the input method wants it hidden, which we can't do, so
+            // we leave it at the end of the preedit.
+            if *begin >= 0 && *end >= 0 {
+                let (begin, end) = (start + *begin as usize, start + *end as usize);
+                if input.is_char_boundary(begin) && input.is_char_boundary(end) {
+                    input.set_selection(Selection::new(begin, end));
+                }
+            }
+        }
+        drop(input);
+        handler.release_input_lock(token);
+
+        let was_composing = self.preedit.borrow().is_some();
+        if was_composing && (pending.commit.is_some() || preedit.is_none()) {
+            self.preedit.take();
+            handler.composition(CompositionEvent {
+                state: CompositionState::End,
+                text: pending.commit.unwrap_or_default(),
+                cursor: None,
+            });
+        }
+        if let Some((text, begin, end)) = preedit {
+            if self.preedit.replace(Some(text.clone())).is_none() {
+                handler.composition(CompositionEvent {
+                    state: CompositionState::Start,
+                    text: String::new(),
+                    cursor: None,
+                });
+            }
+            let cursor = (begin >= 0 && end >= 0).then(|| begin as usize..end as usize);
+            handler.composition(CompositionEvent {
+                state: CompositionState::Update,
+                text,
+                cursor,
+            });
+        }
+    }
+
+    /// Enables or disables the input method for `field`, which is the surface's focused text
+    /// field, and tells the compositor about its text.
+    pub(super) fn update(&self, handler: &mut dyn WinHandler, field: Option<TextFieldToken>) {
+        if self.field.replace(field) != field {
+            // Whatever was being composed in the old field stays there.
+            if let Some(text) = self.preedit.take() {
+                handler.composition(CompositionEvent {
+                    state: CompositionState::End,
+                    text,
+                    cursor: None,
+                });
+            }
+            self.pending.take();
+            match field {
+                Some(_) => self.wl.enable(),
+                None => {
+                    self.wl.disable();
+                    self.wl.commit();
+                }
+            }
+        }
+        if let Some(token) = field {
+            self.send_state(handler, token, ChangeCause::Other);
+        }
+    }
+
+    fn send_state(&self, handler: &mut dyn WinHandler, token: TextFieldToken, cause: ChangeCause) {
+        let input = handler.acquire_input_lock(token, false);
+        let selection = input.selection();
+        let line = input.line_range(selection.active, Affinity::Downstream);
+        let surrounding = line.start.min(selection.min())..line.end.max(selection.max());
+        let surrounding = (surrounding.len() <= MAX_SURROUNDING).then(|| {
+            (
+                input.slice(surrounding.clone()).into_owned(),
+                selection.active - surrounding.start,
+                selection.anchor - surrounding.start,
+            )
+        });
+        let cursor_rect = input
+            .slice_bounding_box(selection.range())
+            .or_else(|| input.bounding_box());
+        drop(input);
+        handler.release_input_lock(token);
+
+        if let Some((text, cursor, anchor)) = surrounding {
+            self.wl
+                .set_surrounding_text(text, cursor as i32, anchor as i32);
+            self.wl.set_text_change_cause(cause);
+        }
+        if let Some(rect) = cursor_rect {
+            let rect = rect.expand();
+            self.wl.set_cursor_rectangle(
+                rect.x0 as i32,
+                rect.y0 as i32,
+                rect.width() as i32,
+                rect.height() as i32,
+            );
+        }
+        self.wl.commit();
+    }
+}
+
+impl std::fmt::Debug for TextInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextInput")
+            .field("wl", &self.wl)
+            .field("field", &self.field)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for TextInput {
+    fn drop(&mut self) {
+        self.wl.destroy();
+    }
+}
@@ -14,6 +14,8 @@
 #![allow(clippy::single_match)]
 
 use tracing;
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_protocols::unstable::relative_pointer::v1::client::zwp_relative_pointer_v1;
 use wayland_protocols::xdg_shell::client::xdg_popup;
 use wayland_protocols::xdg_shell::client::xdg_positioner;
 use wayland_protocols::xdg_shell::client::xdg_surface;
@@ -23,18 +25,30 @@ use raw_window_handle::{
     WaylandDisplayHandle, WaylandWindowHandle,
 };
 
-use super::application::{self, Timer};
+use super::application;
 use super::{error::Error, menu::Menu, outputs, surfaces};
 
 use crate::{
+    application::KioskOptions,
+    backend::shared::Timer,
+    clipboard::FormatId,
+    colors::HighContrastColors,
+    common_util::DEFAULT_REFRESH_INTERVAL,
     dialog::FileDialogOptions,
+    dnd::{DragData, DragImage},
     error::Error as ShellError,
-    kurbo::{Insets, Point, Rect, Size},
+    kurbo::{BezPath, Insets, Point, Rect, Size, Vec2},
+    locale::LocaleInfo,
     mouse::{Cursor, CursorDesc},
+    platform::linux::DecorationTheme,
     scale::Scale,
     text::Event,
-    window::{self, FileDialogToken, TimerToken, WinHandler, WindowLevel},
-    TextFieldToken,
+    window::{
+        self, DecorationMode, FileDialogToken, FullscreenOptions, KeyboardInteractivity, Layer,
+        LayerAnchor, ScrollbarMetrics, Theme, TimerToken, WinHandler, WindowLevel,
+    },
+    window_group::WindowGroup,
+    KeyEvent, KeyboardLayout, Monitor, MonitorId, Screen, TextFieldToken,
 };
 
 pub use surfaces::idle::Handle as IdleHandle;
@@ -106,8 +120,8 @@ impl WindowHandle {
         tracing::warn!("resizable is unimplemented on wayland");
     }
 
-    pub fn show_titlebar(&self, _show_titlebar: bool) {
-        tracing::warn!("show_titlebar is unimplemented on wayland");
+    pub fn show_titlebar(&self, show_titlebar: bool) {
+        self.inner.decor.show_titlebar(show_titlebar);
     }
 
     pub fn set_position(&self, _position: Point) {
@@ -119,10 +133,27 @@ impl WindowHandle {
         Point::ZERO
     }
 
+    pub fn monitor(&self) -> Option<Monitor> {
+        // We don't know where the window is, only which outputs it is on.
+        let data = self.inner.surface.data()?;
+        let id = data.outputs.borrow().iter().min().copied()?;
+        let id = MonitorId::from_raw(id as u64);
+        Screen::get_monitors().into_iter().find(|m| m.id() == id)
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        tracing::warn!("map_to_screen is unimplemented on wayland");
+        point
+    }
+
     pub fn content_insets(&self) -> Insets {
         Insets::from(0.)
     }
 
+    pub fn safe_area(&self) -> Insets {
+        Insets::ZERO
+    }
+
     pub fn set_size(&self, size: Size) {
         self.inner.surface.set_size(size);
     }
@@ -140,6 +171,58 @@ impl WindowHandle {
         window::WindowState::Maximized
     }
 
+    pub fn set_fullscreen(&self, options: Option<FullscreenOptions>) {
+        let output = self.preferred_wl_output();
+        // The compositor decides what happens to panels, so the options don't apply.
+        self.inner
+            .decor
+            .set_fullscreen(options.is_some(), output.as_ref());
+    }
+
+    pub fn preferred_output(&self, monitor: Option<MonitorId>) {
+        if let Some(data) = self.inner.surface.data() {
+            data.preferred_output.set(monitor);
+        }
+    }
+
+    /// The output that the application asked to go fullscreen on, if it is still connected.
+    fn preferred_wl_output(&self) -> Option<WlOutput> {
+        let id = self.inner.surface.data()?.preferred_output.get()?;
+        let appdata = self.inner.appdata.upgrade()?;
+        let outputs = appdata.outputs.borrow();
+        outputs.get(&(id.into_raw() as u32))?.output.clone()
+    }
+
+    pub(crate) fn set_kiosk(&self, options: KioskOptions) {
+        let (appdata, data) = match (self.inner.appdata.upgrade(), self.inner.surface.data()) {
+            (Some(appdata), Some(data)) => (appdata, data),
+            _ => return,
+        };
+        let idle = &appdata.zwp_idle_inhibit_manager_v1;
+        if options.inhibit_screensaver && idle.is_none() {
+            tracing::warn!("unable to inhibit the screensaver: idle inhibit is unavailable");
+        }
+        data.inhibit_idle(idle.as_ref().filter(|_| options.inhibit_screensaver));
+        let shortcuts = &appdata.zwp_keyboard_shortcuts_inhibit_manager_v1;
+        if options.block_system_shortcuts && shortcuts.is_none() {
+            tracing::warn!("unable to block system shortcuts: shortcuts inhibit is unavailable");
+        }
+        let seats: Vec<_> = appdata
+            .seats
+            .borrow()
+            .values()
+            .map(|seat| seat.borrow().wl_seat.detach())
+            .collect();
+        data.inhibit_shortcuts(
+            shortcuts
+                .as_ref()
+                .filter(|_| options.block_system_shortcuts),
+            &seats,
+        );
+        // There is only one cursor, but the kiosk window covers everything anyway.
+        appdata.pointer.set_hidden(options.hide_cursor);
+    }
+
     pub fn handle_titlebar(&self, _val: bool) {
         tracing::warn!("handle_titlebar is unimplemented on wayland");
     }
@@ -166,11 +249,54 @@ impl WindowHandle {
         tracing::warn!("unimplemented bring_to_front_and_focus initiated");
     }
 
+    pub fn raise_above(&self, _other: &WindowHandle) {
+        tracing::warn!("raise_above is unimplemented on wayland");
+    }
+
+    pub fn lower_below(&self, _other: &WindowHandle) {
+        tracing::warn!("lower_below is unimplemented on wayland");
+    }
+
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        if let Some(data) = self.inner.surface.data() {
+            data.set_mouse_passthrough(passthrough);
+        }
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        if let Some(data) = self.inner.surface.data() {
+            data.key_repeat.set(enabled);
+        }
+    }
+
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        if let Some(data) = self.inner.surface.data() {
+            data.set_ime_allowed(allowed);
+        }
+    }
+
+    pub fn set_shape(&self, shape: Option<BezPath>) {
+        if let Some(data) = self.inner.surface.data() {
+            data.set_shape(shape);
+        }
+    }
+
     /// Request a new paint, but without invalidating anything.
     pub fn request_anim_frame(&self) {
         self.inner.surface.request_anim_frame();
     }
 
+    pub fn set_max_frame_rate(&self, _fps: Option<f32>) {
+        tracing::warn!("set_max_frame_rate is unimplemented on wayland");
+    }
+
+    pub fn refresh_interval(&self) -> std::time::Duration {
+        match self.inner.surface.data() {
+            Some(data) => data.refresh_interval(),
+            None => DEFAULT_REFRESH_INTERVAL,
+        }
+    }
+
     /// Request invalidation of the entire window contents.
     pub fn invalidate(&self) {
         self.inner.surface.invalidate();
@@ -182,6 +308,11 @@ impl WindowHandle {
         self.inner.surface.invalidate_rect(rect);
     }
 
+    pub fn scroll_region(&self, rect: Rect, _delta: Vec2) {
+        // TODO: copy between the shm buffers, once something draws into them.
+        self.invalidate_rect(rect);
+    }
+
     pub fn add_text_field(&self) -> TextFieldToken {
         TextFieldToken::next()
     }
@@ -194,35 +325,51 @@ impl WindowHandle {
         self.inner.surface.set_focused_text_field(active_field);
     }
 
-    pub fn update_text_field(&self, _token: TextFieldToken, _update: Event) {
-        // noop until we get a real text input implementation
+    pub fn update_text_field(&self, token: TextFieldToken, _update: Event) {
+        if let Some(data) = self.data() {
+            data.update_text_field(token);
+        }
     }
 
     pub fn request_timer(&self, deadline: std::time::Instant) -> TimerToken {
+        self.request_timer_with_tolerance(deadline, std::time::Duration::ZERO)
+    }
+
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: std::time::Instant,
+        tolerance: std::time::Duration,
+    ) -> TimerToken {
+        self.schedule_timer(Timer::with_tolerance(deadline, tolerance, self.id()))
+    }
+
+    pub fn request_repeating_timer(&self, interval: std::time::Duration) -> TimerToken {
+        self.schedule_timer(Timer::repeating(interval, self.id()))
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        // The calloop timeout stays set, but it just finds nothing to run.
+        if let Some(appdata) = self.inner.appdata.upgrade() {
+            appdata.timers.borrow_mut().cancel(token);
+        }
+    }
+
+    fn schedule_timer(&self, timer: Timer<u64>) -> TimerToken {
         let appdata = match self.inner.appdata.upgrade() {
             Some(d) => d,
             None => {
                 tracing::warn!("requested timer on a window that was destroyed");
-                return Timer::new(self.id(), deadline).token();
+                return timer.token();
             }
         };
 
         let now = instant::Instant::now();
         let mut timers = appdata.timers.borrow_mut();
-        let sooner = timers
-            .peek()
-            .map(|timer| deadline < timer.deadline())
-            .unwrap_or(true);
-
-        let timer = Timer::new(self.id(), deadline);
+        let sooner = timers.iter().all(|other| timer.latest() < other.latest());
         timers.push(timer);
 
         // It is possible that the deadline has passed since it was set.
-        let timeout = if deadline < now {
-            std::time::Duration::ZERO
-        } else {
-            deadline - now
-        };
+        let timeout = timer.latest().saturating_duration_since(now);
 
         if sooner {
             appdata.timer_handle.cancel_all_timeouts();
@@ -238,9 +385,158 @@ impl WindowHandle {
         }
     }
 
-    pub fn make_cursor(&self, _desc: &CursorDesc) -> Option<Cursor> {
-        tracing::warn!("unimplemented make_cursor initiated");
-        None
+    pub fn confine_pointer(&self, region: Option<Rect>) {
+        let appdata = match self.inner.appdata.upgrade() {
+            Some(appdata) => appdata,
+            None => return,
+        };
+        let constraints = match &appdata.zwp_pointer_constraints_v1 {
+            Some(constraints) => constraints,
+            None => {
+                if region.is_some() {
+                    tracing::warn!(
+                        "unable to confine pointer: pointer constraints are unavailable"
+                    );
+                }
+                return;
+            }
+        };
+        match (appdata.pointer.wl_pointer(), self.inner.surface.data()) {
+            (Some(pointer), Some(data)) => data.confine_pointer(constraints, &pointer, region),
+            _ => tracing::warn!("unable to confine pointer: there is no pointer"),
+        }
+    }
+
+    pub fn lock_pointer(&self) {
+        let appdata = match self.inner.appdata.upgrade() {
+            Some(appdata) => appdata,
+            None => return,
+        };
+        let constraints = match &appdata.zwp_pointer_constraints_v1 {
+            Some(constraints) => constraints,
+            None => {
+                tracing::warn!("unable to lock pointer: pointer constraints are unavailable");
+                return;
+            }
+        };
+        match (appdata.pointer.wl_pointer(), self.inner.surface.data()) {
+            (Some(pointer), Some(data)) => data.lock_pointer(constraints, &pointer),
+            _ => tracing::warn!("unable to lock pointer: there is no pointer"),
+        }
+    }
+
+    pub fn unlock_pointer(&self) {
+        if let Some(data) = self.inner.surface.data() {
+            data.unlock_pointer();
+        }
+    }
+
+    pub fn set_raw_pointer_input(&self, enabled: bool) {
+        let data = match self.inner.surface.data() {
+            Some(data) => data,
+            None => return,
+        };
+        if !enabled {
+            data.set_relative_pointer(None);
+            return;
+        }
+        let appdata = match self.inner.appdata.upgrade() {
+            Some(appdata) => appdata,
+            None => return,
+        };
+        let manager = match &appdata.zwp_relative_pointer_manager_v1 {
+            Some(manager) => manager,
+            None => {
+                tracing::warn!(
+                    "unable to get raw pointer input: relative pointers are unavailable"
+                );
+                return;
+            }
+        };
+        let pointer = match appdata.pointer.wl_pointer() {
+            Some(pointer) => pointer,
+            None => {
+                tracing::warn!("unable to get raw pointer input: there is no pointer");
+                return;
+            }
+        };
+        let relative = manager.get_relative_pointer(&pointer);
+        relative.quick_assign({
+            let id = self.id();
+            let appdata = self.inner.appdata.clone();
+            let data = std::sync::Arc::downgrade(&data);
+            move |_, event, _| {
+                let (dx, dy) = match event {
+                    zwp_relative_pointer_v1::Event::RelativeMotion {
+                        dx_unaccel,
+                        dy_unaccel,
+                        ..
+                    } => (dx_unaccel, dy_unaccel),
+                    _ => return,
+                };
+                // Every window with raw input on gets the motion, but like other pointer
+                // events it only goes to the current one.
+                let current = appdata.upgrade().map(|appdata| appdata.current_window_id());
+                if current != Some(id) {
+                    return;
+                }
+                if let Some(data) = data.upgrade() {
+                    let delta = Vec2::new(dx, dy);
+                    data.with_handler(|handler| handler.raw_pointer_motion(delta));
+                }
+            }
+        });
+        data.set_relative_pointer(Some(relative));
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        if let Some(data) = self.inner.surface.data() {
+            data.autoscroll.begin(origin);
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        if let Some(data) = self.inner.surface.data() {
+            data.shortcut_capture.begin(callback);
+        }
+    }
+
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        let appdata = self.inner.appdata.upgrade()?;
+        let (width, height) = (desc.width as i32, desc.height as i32);
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        match surfaces::buffers::image_buffer(appdata.wl_shm.clone(), width, height, &desc.pixels) {
+            Ok(buffer) => Some(Cursor::Custom(CustomCursor(std::rc::Rc::new(
+                CustomCursorImage {
+                    buffer,
+                    hotspot: (desc.hot.x as i32, desc.hot.y as i32),
+                },
+            )))),
+            Err(cause) => {
+                tracing::error!("unable to create custom cursor {:?}", cause);
+                None
+            }
+        }
+    }
+
+    pub fn start_drag(&self, data: DragData, image: Option<DragImage>) {
+        let appdata = match self.inner.appdata.upgrade() {
+            Some(appdata) => appdata,
+            None => return,
+        };
+        if let Some(surface) = self.inner.surface.data() {
+            appdata
+                .dnd
+                .start_drag(&appdata, &surface.wl_surface(), data, image);
+        }
+    }
+
+    pub fn set_drop_formats(&self, formats: &[FormatId]) {
+        if let Some(data) = self.inner.surface.data() {
+            data.drop_formats.replace(formats.to_vec());
+        }
     }
 
     pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
@@ -255,7 +551,16 @@ impl WindowHandle {
 
     /// Get a handle that can be used to schedule an idle task.
     pub fn get_idle_handle(&self) -> Option<IdleHandle> {
-        Some(self.inner.surface.get_idle_handle())
+        self.inner.surface.get_idle_handle()
+    }
+
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        tracing::warn!("input_language is unimplemented on wayland");
+        None
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        application::Application::locale_info()
     }
 
     /// Get the `Scale` of the window.
@@ -263,6 +568,23 @@ impl WindowHandle {
         Ok(self.inner.surface.get_scale())
     }
 
+    pub fn text_scale_factor(&self) -> f64 {
+        tracing::warn!("WindowHandle::text_scale_factor is currently unimplemented for wayland.");
+        1.0
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        tracing::warn!(
+            "WindowHandle::high_contrast_colors is currently unimplemented for wayland."
+        );
+        None
+    }
+
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        // Every toolkit draws its own scrollbars on Wayland.
+        ScrollbarMetrics::default()
+    }
+
     pub fn set_menu(&self, _menu: Menu) {
         tracing::warn!("set_menu not implement for wayland");
     }
@@ -275,6 +597,11 @@ impl WindowHandle {
         self.inner.decor.set_title(title);
     }
 
+    pub fn set_theme_override(&self, _theme: Option<Theme>) {
+        // There is no protocol for telling the compositor how to draw our decorations.
+        tracing::warn!("set_theme_override is unimplemented on wayland");
+    }
+
     pub(super) fn run_idle(&self) {
         self.inner.surface.run_idle();
     }
@@ -329,14 +656,97 @@ unsafe impl HasRawDisplayHandle for WindowHandle {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
-pub struct CustomCursor;
+impl crate::platform::linux::WindowHandleExt for crate::WindowHandle {
+    fn export_handle(&self, f: Box<dyn FnOnce(Option<String>)>) {
+        let exporter = self
+            .0
+            .inner
+            .appdata
+            .upgrade()
+            .and_then(|appdata| appdata.zxdg_exporter_v2.clone());
+        match (exporter, self.0.inner.surface.data()) {
+            (Some(exporter), Some(data)) => data.export(&exporter, f),
+            _ => {
+                tracing::warn!("unable to export window: xdg-foreign is unavailable");
+                f(None);
+            }
+        }
+    }
+
+    fn set_transient_for_foreign(&self, handle: &str) -> bool {
+        let handle = match handle.strip_prefix("wayland:") {
+            Some(handle) => handle,
+            None => return false,
+        };
+        let importer = self
+            .0
+            .inner
+            .appdata
+            .upgrade()
+            .and_then(|appdata| appdata.zxdg_importer_v2.clone());
+        match (importer, self.0.inner.surface.data()) {
+            (Some(importer), Some(data)) => {
+                data.set_foreign_parent(&importer, handle);
+                true
+            }
+            _ => {
+                tracing::warn!("unable to import window: xdg-foreign is unavailable");
+                false
+            }
+        }
+    }
+
+    fn set_momentum_scroll(&self, _enabled: bool) {
+        // Wayland compositors already report touchpad scrolling smoothly, with the momentum
+        // left to the application, so there's nothing to synthesize.
+    }
+
+    fn set_client_decorations(&self, theme: Option<DecorationTheme>) {
+        self.0.inner.decor.set_client_decorations(theme);
+    }
+}
+
+/// A cursor image in shared memory, which the pointer shows by attaching it to the cursor
+/// surface.
+#[derive(Clone)]
+pub struct CustomCursor(std::rc::Rc<CustomCursorImage>);
+
+pub(super) struct CustomCursorImage {
+    buffer: wayland_client::Main<wayland_client::protocol::wl_buffer::WlBuffer>,
+    hotspot: (i32, i32),
+}
+
+impl CustomCursor {
+    pub(super) fn buffer(&self) -> &wayland_client::protocol::wl_buffer::WlBuffer {
+        &self.0.buffer
+    }
+
+    pub(super) fn hotspot(&self) -> (i32, i32) {
+        self.0.hotspot
+    }
+}
+
+impl PartialEq for CustomCursor {
+    fn eq(&self, other: &Self) -> bool {
+        std::rc::Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CustomCursor {}
+
+impl Drop for CustomCursorImage {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
 
 /// Builder abstraction for creating new windows
 pub(crate) struct WindowBuilder {
     appdata: std::sync::Weak<application::Data>,
     handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
     title: String,
+    taskbar_group: Option<String>,
     menu: Option<Menu>,
     position: Option<Point>,
     level: WindowLevel,
@@ -344,8 +754,11 @@ pub(crate) struct WindowBuilder {
     // pre-scaled
     size: Size,
     min_size: Option<Size>,
+    resize_increments: Option<Size>,
     resizable: bool,
     show_titlebar: bool,
+    focusable: bool,
+    forced_scale: Option<Scale>,
 }
 
 impl WindowBuilder {
@@ -353,15 +766,20 @@ impl WindowBuilder {
         WindowBuilder {
             appdata: std::sync::Arc::downgrade(&app.data),
             handler: None,
+            group: None,
             title: String::new(),
+            taskbar_group: None,
             menu: None,
             size: Size::new(0.0, 0.0),
             position: None,
             level: WindowLevel::AppWindow,
             state: None,
             min_size: None,
+            resize_increments: None,
             resizable: true,
             show_titlebar: true,
+            focusable: true,
+            forced_scale: None,
         }
     }
 
@@ -369,6 +787,10 @@ impl WindowBuilder {
         self.handler = Some(handler);
     }
 
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
     pub fn set_size(&mut self, size: Size) {
         self.size = size;
     }
@@ -377,6 +799,10 @@ impl WindowBuilder {
         self.min_size = Some(size);
     }
 
+    pub fn set_resize_increments(&mut self, increments: Size) {
+        self.resize_increments = Some(increments);
+    }
+
     pub fn resizable(&mut self, resizable: bool) {
         self.resizable = resizable;
     }
@@ -403,15 +829,55 @@ impl WindowBuilder {
         self.state = Some(state);
     }
 
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.focusable = focusable;
+    }
+
+    pub fn set_activate_on_show(&mut self, _activate_on_show: bool) {
+        // Ignored: compositors decide whether a new toplevel is activated, and we never
+        // request activation ourselves.
+    }
+
+    pub fn show_after_first_paint(&mut self, _show_after_first_paint: bool) {
+        // Ignored: a wayland surface isn't mapped until a buffer has been attached to it,
+        // so this is always the case.
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
     pub fn set_title(&mut self, title: impl Into<String>) {
         self.title = title.into();
     }
 
+    pub fn set_taskbar_group(&mut self, group: String) {
+        self.taskbar_group = Some(group);
+    }
+
+    pub fn set_restoration_id(&mut self, _id: String) {
+        // Ignored: only macOS restores windows.
+    }
+
     pub fn set_menu(&mut self, menu: Menu) {
         self.menu = Some(menu);
     }
 
     pub fn build(self) -> Result<WindowHandle, ShellError> {
+        let forced_scale = self.forced_scale;
+        let handle = self.create()?;
+        // Wayland surfaces have a single scale for both axes. Forcing it after `connect` tells
+        // the handler about it the same way as a change of output.
+        if let (Some(scale), Some(data)) = (forced_scale, handle.data()) {
+            data.force_scale(scale.x());
+        }
+        Ok(handle)
+    }
+
+    fn create(mut self) -> Result<WindowHandle, ShellError> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
         if matches!(self.menu, Some(_)) {
             tracing::warn!("menus unimplemented for wayland");
         }
@@ -422,21 +888,47 @@ impl WindowBuilder {
             return self.create_popup(parent);
         }
 
-        if let WindowLevel::DropDown(parent) = level {
+        // Popups never take the keyboard focus. Dismissing them on outside clicks would need an
+        // xdg_popup grab, which needs the serial of the triggering input event.
+        if let WindowLevel::DropDown(parent) | WindowLevel::Tooltip(parent) = level {
             return self.create_popup(parent);
         }
 
+        if let WindowLevel::Layer {
+            layer,
+            anchor,
+            exclusive_zone,
+            keyboard_interactivity,
+        } = level
+        {
+            return self.create_layer(layer, anchor, exclusive_zone, keyboard_interactivity);
+        }
+
         let appdata = match self.appdata.upgrade() {
             Some(d) => d,
             None => return Err(ShellError::ApplicationDropped),
         };
 
+        // xdg-shell toplevels can't opt out of keyboard focus; only layer-shell surfaces can.
+        if !self.focusable {
+            tracing::warn!("set_focusable(false) is only supported for layer windows on wayland");
+        }
+
         let handler = self.handler.expect("must set a window handler");
 
-        let surface =
-            surfaces::toplevel::Surface::new(appdata.clone(), handler, self.size, self.min_size);
+        let surface = surfaces::toplevel::Surface::new(
+            appdata.clone(),
+            handler,
+            self.size,
+            self.min_size,
+            self.resize_increments,
+            self.show_titlebar,
+        );
 
         (&surface as &dyn surfaces::Decor).set_title(self.title);
+        if let Some(group) = self.taskbar_group {
+            surface.set_app_id(group);
+        }
 
         let handle = WindowHandle::new(
             surface.clone(),
@@ -466,6 +958,12 @@ impl WindowBuilder {
             let handle = handle.clone();
             move |winhandle| winhandle.connect(&handle.into())
         });
+        // Otherwise we hear from the compositor once it has decided.
+        if !surface.can_decorate() {
+            surface.with_handler(|winhandle| {
+                winhandle.decoration_mode_changed(DecorationMode::ClientSide)
+            });
+        }
 
         Ok(handle)
     }
@@ -488,6 +986,50 @@ impl WindowBuilder {
 
         popup::create(&parent.0, &config, self.appdata, self.handler)
     }
+
+    fn create_layer(
+        self,
+        layer: Layer,
+        anchor: LayerAnchor,
+        exclusive_zone: i32,
+        mut keyboard_interactivity: KeyboardInteractivity,
+    ) -> Result<WindowHandle, ShellError> {
+        if !self.focusable {
+            keyboard_interactivity = KeyboardInteractivity::None;
+        }
+        let appdata = match self.appdata.upgrade() {
+            Some(d) => d,
+            None => return Err(ShellError::ApplicationDropped),
+        };
+        if appdata.zwlr_layershell_v1.is_none() {
+            return Err(ShellError::Platform(Error::string(
+                "the compositor doesn't support layer shell",
+            )));
+        }
+
+        // A size of zero stretches the surface between the edges it is anchored to.
+        let mut size = self.size;
+        if anchor.left && anchor.right {
+            size.width = 0.0;
+        }
+        if anchor.top && anchor.bottom {
+            size.height = 0.0;
+        }
+
+        let mut builder = layershell::Builder::new(application::Application { data: appdata });
+        builder.config = surfaces::layershell::Config {
+            initial_size: size,
+            layer: layer.into(),
+            keyboard_interactivity: keyboard_interactivity.into(),
+            anchor: anchor.into(),
+            exclusive_zone,
+            ..Default::default()
+        };
+        if let Some(handler) = self.handler {
+            builder.set_handler(handler);
+        }
+        builder.build()
+    }
 }
 
 #[allow(unused)]
@@ -14,20 +14,83 @@
 
 //! Web implementation of features at the application scope.
 
-use crate::application::AppHandler;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use instant::{Duration, Instant};
+use js_sys::{Array, Date, Intl, Object, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::application::{AppHandler, DispatchPolicy};
+use crate::common_util::IdleTracker;
+use crate::keyboard::KeyboardLayout;
+use crate::locale::LocaleInfo;
+use crate::sensors::SensorKind;
 
 use super::clipboard::Clipboard;
 use super::error::Error;
 
 #[derive(Clone)]
-pub(crate) struct Application;
+pub(crate) struct Application {
+    idle: Rc<Idle>,
+}
+
+/// Tells the `AppHandler` when the application goes idle.
+#[derive(Default)]
+struct Idle {
+    tracker: IdleTracker,
+    handler: RefCell<Option<Box<dyn AppHandler>>>,
+    /// The `setTimeout` that is scheduled to check for idleness, and when it is for.
+    check: RefCell<Option<(i32, Instant)>>,
+}
+
+/// The events that are the user's input, which keeps the application from going idle.
+const INPUT_EVENTS: &[&str] = &[
+    "keydown",
+    "keyup",
+    "pointerdown",
+    "pointerup",
+    "pointermove",
+    "wheel",
+    "touchstart",
+    "touchmove",
+    "touchend",
+];
 
 impl Application {
     pub fn new() -> Result<Application, Error> {
-        Ok(Application)
+        Ok(Application {
+            idle: Default::default(),
+        })
     }
 
-    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {}
+    pub fn run(self, handler: Option<Box<dyn AppHandler>>) {
+        *self.idle.handler.borrow_mut() = handler;
+        let window = match web_sys::window() {
+            Some(window) => window,
+            None => return,
+        };
+        for event_type in INPUT_EVENTS {
+            let idle = self.idle.clone();
+            let closure = Closure::wrap(Box::new(move || {
+                if idle.tracker.input() {
+                    idle.update();
+                }
+            }) as Box<dyn FnMut()>);
+            // Listen while the event is captured, so that we hear about it before anyone can
+            // stop it from propagating.
+            window
+                .add_event_listener_with_callback_and_bool(
+                    event_type,
+                    closure.as_ref().unchecked_ref(),
+                    true,
+                )
+                .unwrap();
+            closure.forget();
+        }
+        self.idle.update();
+    }
 
     pub fn quit(&self) {}
 
@@ -35,9 +98,129 @@ impl Application {
         Clipboard
     }
 
+    pub fn enable_sensor(&self, _kind: SensorKind) -> bool {
+        tracing::warn!(
+            "Application::enable_sensor is currently unimplemented for the web backend."
+        );
+        false
+    }
+
+    pub fn disable_sensor(&self, _kind: SensorKind) {}
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.idle.tracker.set_timeout(timeout);
+        self.idle.update();
+    }
+
+    pub fn set_dispatch_policy(&self, _policy: DispatchPolicy) {
+        // The browser runs the event loop.
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        tracing::warn!(
+            "Application::keyboard_layouts is currently unimplemented for the web backend."
+        );
+        Vec::new()
+    }
+
     pub fn get_locale() -> String {
         web_sys::window()
             .and_then(|w| w.navigator().language())
             .unwrap_or_else(|| "en-US".into())
     }
+
+    pub fn locale_info() -> LocaleInfo {
+        let locale = Application::get_locale();
+        let mut info = LocaleInfo {
+            locale: locale.clone(),
+            ..LocaleInfo::default()
+        };
+        let locales = Array::of1(&locale.into());
+        // Format samples with `Intl`, and see what comes out.
+        let parts = |formatted: Array| {
+            formatted
+                .iter()
+                .filter_map(|part| {
+                    let ty = Reflect::get(&part, &"type".into()).ok()?.as_string()?;
+                    let value = Reflect::get(&part, &"value".into()).ok()?.as_string()?;
+                    Some((ty, value))
+                })
+                .collect::<Vec<_>>()
+        };
+        let numbers = Intl::NumberFormat::new(&locales, &Object::new());
+        for (ty, value) in parts(numbers.format_to_parts(1234567.5)) {
+            match ty.as_str() {
+                "decimal" => info.decimal_separator = value,
+                "group" => info.grouping_separator = value,
+                _ => (),
+            }
+        }
+        let date = Date::new_0();
+        let dates =
+            parts(Intl::DateTimeFormat::new(&locales, &Object::new()).format_to_parts(&date));
+        // Turn the date back into a pattern.
+        let date_pattern: String = dates
+            .iter()
+            .map(|(ty, value)| match ty.as_str() {
+                "day" => "d".into(),
+                "month" => "M".into(),
+                "year" => "y".into(),
+                _ => format!("'{}'", value.replace('\'', "")),
+            })
+            .collect();
+        let time_options = Object::new();
+        let _ = Reflect::set(&time_options, &"hour".into(), &"numeric".into());
+        let times =
+            parts(Intl::DateTimeFormat::new(&locales, &time_options).format_to_parts(&date));
+        let time_pattern = if times.iter().any(|(ty, _)| ty == "dayPeriod") {
+            "h"
+        } else {
+            "H"
+        };
+        info.apply_patterns(&date_pattern, time_pattern);
+        info
+    }
+}
+
+impl Idle {
+    /// Tells the `AppHandler` about any change, and schedules the next check.
+    fn update(self: &Rc<Self>) {
+        // The handler is borrowed if it's the one changing the timeout.
+        if let Ok(mut handler) = self.handler.try_borrow_mut() {
+            if let Some(handler) = handler.as_mut() {
+                self.tracker.dispatch(&mut **handler);
+            }
+        }
+        let window = match web_sys::window() {
+            Some(window) => window,
+            None => return,
+        };
+        let deadline = self.tracker.deadline();
+        let mut check = self.check.borrow_mut();
+        if check.as_ref().map(|(_, at)| *at) == deadline {
+            return;
+        }
+        if let Some((id, _)) = check.take() {
+            window.clear_timeout_with_handle(id);
+        }
+        if let Some(deadline) = deadline {
+            let interval = deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis();
+            let idle = Rc::downgrade(self);
+            let f = move || {
+                if let Some(idle) = idle.upgrade() {
+                    idle.check.borrow_mut().take();
+                    idle.update();
+                }
+            };
+            let id = window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    Closure::once_into_js(f).as_ref().unchecked_ref(),
+                    interval.min(i32::MAX as u128) as i32,
+                )
+                .expect("Failed to call setTimeout with a callback");
+            *check = Some((id, deadline));
+        }
+    }
 }
@@ -53,6 +53,11 @@ impl Clipboard {
         None
     }
 
+    pub fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        tracing::warn!("unimplemented");
+        Vec::new()
+    }
+
     pub fn available_type_names(&self) -> Vec<String> {
         tracing::warn!("unimplemented");
         Vec::new()
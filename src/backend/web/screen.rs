@@ -14,9 +14,14 @@
 
 //! Monitor and Screen information ignored for web.
 
-use crate::screen::Monitor;
+use crate::screen::{ColorShift, Monitor};
 
 pub(crate) fn get_monitors() -> Vec<Monitor> {
     tracing::warn!("Screen::get_monitors() is not implemented for web.");
     Vec::new()
 }
+
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    tracing::warn!("Screen::get_color_shift() is not implemented for web.");
+    None
+}
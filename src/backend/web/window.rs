@@ -15,18 +15,19 @@
 //! Web window creation and management.
 
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
 
-use instant::Instant;
+use instant::{Duration, Instant};
 use tracing::{error, warn};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, WebWindowHandle};
 
-use crate::kurbo::{Insets, Point, Rect, Size, Vec2};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
 
 use crate::piet::{PietText, RenderContext};
 
@@ -34,19 +35,35 @@ use super::application::Application;
 use super::error::Error;
 use super::keycodes::convert_keyboard_event;
 use super::menu::Menu;
-use crate::common_util::{ClickCounter, IdleCallback};
+use crate::application::KioskOptions;
+use crate::autoscroll::Autoscroll;
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
+use crate::common_util::{
+    estimated_frame_info, ClickCounter, IdleCallback, DEFAULT_REFRESH_INTERVAL,
+};
 use crate::dialog::{FileDialogOptions, FileDialogType};
+use crate::dnd::{DragData, DragImage};
 use crate::error::Error as ShellError;
 use crate::scale::{Scale, ScaledArea};
 
-use crate::keyboard::{KeyState, Modifiers};
-use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
+use crate::dispatch;
+use crate::keyboard::{KeyEvent, KeyState, KeyboardLayout, Modifiers};
+use crate::locale::LocaleInfo;
+use crate::mouse::{
+    Cursor, CursorDesc, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent,
+    ScrollDelta,
+};
 use crate::region::Region;
+use crate::screen::{Monitor, MonitorId};
+use crate::shortcut_capture::ShortcutCapture;
 use crate::text::{simulate_input, Event};
 use crate::window;
 use crate::window::{
-    FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel,
+    FileDialogToken, FocusDirection, FullscreenOptions, IdleToken, PaintReason, ScrollbarMetrics,
+    TextFieldToken, Theme, TimerToken, WinHandler, WindowLevel,
 };
+use crate::window_group::WindowGroup;
 
 // This is a macro instead of a function since KeyboardEvent and MouseEvent has identical functions
 // to query modifier key states.
@@ -71,9 +88,11 @@ macro_rules! get_modifiers {
 /// Builder abstraction for creating new windows.
 pub(crate) struct WindowBuilder {
     handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
     title: String,
     cursor: Cursor,
     menu: Option<Menu>,
+    forced_scale: Option<Scale>,
 }
 
 #[derive(Clone, Default)]
@@ -111,9 +130,13 @@ enum IdleKind {
 
 struct WindowState {
     scale: Cell<Scale>,
+    /// A scale to use instead of the device pixel ratio.
+    forced_scale: Option<Scale>,
     area: Cell<ScaledArea>,
     idle_queue: Arc<Mutex<Vec<IdleKind>>>,
     handler: RefCell<Box<dyn WinHandler>>,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
     window: web_sys::Window,
     canvas: web_sys::HtmlCanvasElement,
     canvas_size: Option<Size>,
@@ -121,7 +144,13 @@ struct WindowState {
     invalid: RefCell<Region>,
     click_counter: ClickCounter,
     active_text_input: Cell<Option<TextFieldToken>>,
+    // Whether key repeats are passed on to the handler.
+    key_repeat: Cell<bool>,
     rendering_soon: Cell<bool>,
+    /// Whether the handler asked for an animation frame since the last render.
+    anim_frame_requested: Cell<bool>,
+    /// The browser's ids for the timers that haven't fired yet, so that they can be cancelled.
+    timers: RefCell<HashMap<TimerToken, i32>>,
 }
 
 // TODO: support custom cursors
@@ -130,13 +159,21 @@ pub struct CustomCursor;
 
 impl WindowState {
     fn render(&self) {
+        // Animation frames requested from here on are for the next frame.
+        let reason = if self.anim_frame_requested.replace(false) {
+            PaintReason::Animation
+        } else {
+            PaintReason::Damage
+        };
+        let info = estimated_frame_info(DEFAULT_REFRESH_INTERVAL);
+        self.handler.borrow_mut().prepare_frame(info);
         self.handler.borrow_mut().prepare_paint();
 
         let mut piet_ctx = piet_common::Piet::new(self.context.clone(), self.window.clone());
         if let Err(e) = piet_ctx.with_save(|ctx| {
             let invalid = self.invalid.borrow();
             ctx.clip(invalid.to_bez_path());
-            self.handler.borrow_mut().paint(ctx, &invalid);
+            self.handler.borrow_mut().paint(ctx, &invalid, reason);
             Ok(())
         }) {
             error!("piet error on render: {:?}", e);
@@ -181,7 +218,7 @@ impl WindowState {
     /// Updates the canvas size and scale factor and returns `Scale` and `ScaledArea`.
     fn update_scale_and_area(&self) -> (Scale, ScaledArea) {
         let (css_width, css_height, dpr) = self.get_window_size_and_dpr();
-        let scale = Scale::new(dpr, dpr);
+        let scale = self.forced_scale.unwrap_or_else(|| Scale::new(dpr, dpr));
         let area = ScaledArea::from_dp(Size::new(css_width, css_height), scale);
         let size_px = area.size_px();
         self.canvas.set_width(size_px.width as u32);
@@ -208,7 +245,10 @@ fn setup_mouse_down_callback(ws: &Rc<WindowState>) {
                 count,
                 focus: false,
                 button,
-                wheel_delta: Vec2::ZERO,
+                wheel_delta: ScrollDelta::ZERO,
+                momentum_phase: MomentumPhase::None,
+                source: input_source(&event),
+                coalesced: Vec::new(),
             };
             state.handler.borrow_mut().mouse_down(&event);
         }
@@ -227,7 +267,10 @@ fn setup_mouse_up_callback(ws: &Rc<WindowState>) {
                 count: 0,
                 focus: false,
                 button,
-                wheel_delta: Vec2::ZERO,
+                wheel_delta: ScrollDelta::ZERO,
+                momentum_phase: MomentumPhase::None,
+                source: input_source(&event),
+                coalesced: Vec::new(),
             };
             state.handler.borrow_mut().mouse_up(&event);
         }
@@ -245,7 +288,10 @@ fn setup_mouse_move_callback(ws: &Rc<WindowState>) {
             count: 0,
             focus: false,
             button: MouseButton::None,
-            wheel_delta: Vec2::ZERO,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: input_source(&event),
+            coalesced: Vec::new(),
         };
         state.handler.borrow_mut().mouse_move(&event);
     });
@@ -259,13 +305,12 @@ fn setup_scroll_callback(ws: &Rc<WindowState>) {
         let dx = event.delta_x();
         let dy = event.delta_y();
 
-        // The value 35.0 was manually picked to produce similar behavior to mac/linux.
         let wheel_delta = match delta_mode {
-            web_sys::WheelEvent::DOM_DELTA_PIXEL => Vec2::new(dx, dy),
-            web_sys::WheelEvent::DOM_DELTA_LINE => Vec2::new(35.0 * dx, 35.0 * dy),
+            web_sys::WheelEvent::DOM_DELTA_PIXEL => ScrollDelta::Pixels(Vec2::new(dx, dy)),
+            web_sys::WheelEvent::DOM_DELTA_LINE => ScrollDelta::Lines(Vec2::new(dx, dy)),
             web_sys::WheelEvent::DOM_DELTA_PAGE => {
                 let size_dp = state.area.get().size_dp();
-                Vec2::new(size_dp.width * dx, size_dp.height * dy)
+                ScrollDelta::Pixels(Vec2::new(size_dp.width * dx, size_dp.height * dy))
             }
             _ => {
                 warn!("Invalid deltaMode in WheelEvent: {}", delta_mode);
@@ -281,6 +326,9 @@ fn setup_scroll_callback(ws: &Rc<WindowState>) {
             focus: false,
             button: MouseButton::None,
             wheel_delta,
+            momentum_phase: MomentumPhase::None,
+            source: input_source(&event),
+            coalesced: Vec::new(),
         };
         state.handler.borrow_mut().wheel(&event);
     });
@@ -310,13 +358,30 @@ fn setup_keydown_callback(ws: &Rc<WindowState>) {
     register_window_event_listener(ws, "keydown", move |event: web_sys::KeyboardEvent| {
         let modifiers = get_modifiers!(event);
         let kb_event = convert_keyboard_event(&event, modifiers, KeyState::Down);
+        if kb_event.repeat && !state.key_repeat.get() {
+            return;
+        }
+        let focus = FocusDirection::from_key_event(&kb_event);
         let mut handler = state.handler.borrow_mut();
-        if simulate_input(&mut **handler, state.active_text_input.get(), kb_event) {
+        let mut handled = simulate_input(&mut **handler, state.active_text_input.get(), kb_event);
+        if let (false, Some(direction)) = (handled, focus) {
+            // If the content doesn't take the focus, the browser moves it to the next element.
+            handled = handler.focus_navigation(direction);
+        }
+        if handled {
             event.prevent_default();
         }
     });
 }
 
+fn setup_locale_callback(ws: &Rc<WindowState>) {
+    let state = ws.clone();
+    register_window_event_listener(ws, "languagechange", move |_: web_sys::Event| {
+        let info = Application::locale_info();
+        state.handler.borrow_mut().locale_changed(info);
+    });
+}
+
 /// A helper function to register a window event listener with `addEventListener`.
 fn register_window_event_listener<F, E>(window_state: &Rc<WindowState>, event_type: &str, f: F)
 where
@@ -353,15 +418,18 @@ fn setup_web_callbacks(window_state: &Rc<WindowState>) {
     setup_scroll_callback(window_state);
     setup_keyup_callback(window_state);
     setup_keydown_callback(window_state);
+    setup_locale_callback(window_state);
 }
 
 impl WindowBuilder {
     pub fn new(_app: Application) -> WindowBuilder {
         WindowBuilder {
             handler: None,
+            group: None,
             title: String::new(),
             cursor: Cursor::Arrow,
             menu: None,
+            forced_scale: None,
         }
     }
 
@@ -370,6 +438,10 @@ impl WindowBuilder {
         self.handler = Some(handler);
     }
 
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
     pub fn set_size(&mut self, _: Size) {
         // Ignored
     }
@@ -378,6 +450,10 @@ impl WindowBuilder {
         // Ignored
     }
 
+    pub fn set_resize_increments(&mut self, _: Size) {
+        // Ignored
+    }
+
     pub fn resizable(&mut self, _resizable: bool) {
         // Ignored
     }
@@ -402,15 +478,42 @@ impl WindowBuilder {
         // ignored
     }
 
+    pub fn set_focusable(&mut self, _focusable: bool) {
+        // Ignored
+    }
+
+    pub fn set_activate_on_show(&mut self, _activate_on_show: bool) {
+        // Ignored
+    }
+
+    pub fn show_after_first_paint(&mut self, _show_after_first_paint: bool) {
+        // Ignored
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
     pub fn set_title<S: Into<String>>(&mut self, title: S) {
         self.title = title.into();
     }
 
+    pub fn set_taskbar_group(&mut self, _group: String) {
+        // Ignored: the browser decides how its tabs are grouped.
+    }
+
+    pub fn set_restoration_id(&mut self, _id: String) {
+        // Ignored: only macOS restores windows.
+    }
+
     pub fn set_menu(&mut self, menu: Menu) {
         self.menu = Some(menu);
     }
 
-    pub fn build(self) -> Result<WindowHandle, Error> {
+    pub fn build(mut self) -> Result<WindowHandle, Error> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
         let window = web_sys::window().ok_or(Error::NoWindow)?;
         let canvas = window
             .document()
@@ -437,10 +540,10 @@ impl WindowBuilder {
             .dyn_into::<web_sys::CanvasRenderingContext2d>()
             .map_err(|_| Error::JsCast)?;
         // Create the Scale for resolution scaling
-        let scale = {
+        let scale = self.forced_scale.unwrap_or_else(|| {
             let dpr = window.device_pixel_ratio();
             Scale::new(dpr, dpr)
-        };
+        });
         let area = {
             // The initial size in display points isn't necessarily the final size in display points
             let size_dp = Size::new(canvas.offset_width() as f64, canvas.offset_height() as f64);
@@ -454,13 +557,18 @@ impl WindowBuilder {
 
         set_cursor(&canvas, &self.cursor);
 
-        let handler = self.handler.unwrap();
+        let autoscroll = Rc::new(Autoscroll::default());
+        let shortcut_capture = Rc::new(ShortcutCapture::default());
+        let handler = dispatch::wrap(self.handler.unwrap(), &autoscroll, &shortcut_capture);
 
         let window = Rc::new(WindowState {
             scale: Cell::new(scale),
+            forced_scale: self.forced_scale,
             area: Cell::new(area),
             idle_queue: Default::default(),
             handler: RefCell::new(handler),
+            autoscroll,
+            shortcut_capture,
             window,
             canvas,
             canvas_size,
@@ -468,7 +576,10 @@ impl WindowBuilder {
             invalid: RefCell::new(Region::EMPTY),
             click_counter: ClickCounter::default(),
             active_text_input: Cell::new(None),
+            key_repeat: Cell::new(true),
             rendering_soon: Cell::new(false),
+            anim_frame_requested: Cell::new(false),
+            timers: RefCell::new(HashMap::new()),
         });
 
         setup_web_callbacks(&window);
@@ -512,6 +623,21 @@ impl WindowHandle {
         Point::new(0.0, 0.0)
     }
 
+    pub fn monitor(&self) -> Option<Monitor> {
+        warn!("WindowHandle::monitor unimplemented for web.");
+        None
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        // Pages can't find out where the viewport is on the screen, but all of our windows are
+        // in the same viewport, so it works just as well for mapping between them.
+        if let Some(s) = self.0.upgrade() {
+            let rect = s.canvas.get_bounding_client_rect();
+            return Point::new(rect.left(), rect.top()) + point.to_vec2();
+        }
+        point
+    }
+
     pub fn set_size(&self, _size: Size) {
         warn!("WindowHandle::set_size unimplemented for web.");
     }
@@ -522,7 +648,12 @@ impl WindowHandle {
     }
 
     pub fn content_insets(&self) -> Insets {
-        warn!("WindowHandle::content_insets unimplemented for web.");
+        // The canvas has no decorations of its own.
+        Insets::ZERO
+    }
+
+    pub fn safe_area(&self) -> Insets {
+        // TODO: read the CSS safe-area-inset-* environment variables on mobile browsers.
         Insets::ZERO
     }
 
@@ -535,6 +666,20 @@ impl WindowHandle {
         window::WindowState::Restored
     }
 
+    pub fn set_fullscreen(&self, _options: Option<FullscreenOptions>) {
+        warn!("WindowHandle::set_fullscreen unimplemented for web.");
+    }
+
+    pub fn preferred_output(&self, _monitor: Option<MonitorId>) {
+        warn!("WindowHandle::preferred_output unimplemented for web.");
+    }
+
+    pub(crate) fn set_kiosk(&self, options: KioskOptions) {
+        if options != KioskOptions::OFF {
+            warn!("Kiosk mode lockdown unimplemented for web.");
+        }
+    }
+
     pub fn handle_titlebar(&self, _val: bool) {
         warn!("WindowHandle::handle_titlebar unimplemented for web.");
     }
@@ -547,10 +692,53 @@ impl WindowHandle {
         warn!("bring_to_frontand_focus unimplemented for web");
     }
 
+    pub fn raise_above(&self, _other: &WindowHandle) {
+        warn!("WindowHandle::raise_above unimplemented for web");
+    }
+
+    pub fn lower_below(&self, _other: &WindowHandle) {
+        warn!("WindowHandle::lower_below unimplemented for web");
+    }
+
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        if let Some(s) = self.0.upgrade() {
+            let value = if passthrough { "none" } else { "auto" };
+            if let Err(e) = s.canvas.style().set_property("pointer-events", value) {
+                warn!("failed to set pointer-events: {:?}", e);
+            }
+        }
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        if let Some(s) = self.0.upgrade() {
+            s.key_repeat.set(enabled);
+        }
+    }
+
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        // Ignored: key events aren't composed on web.
+    }
+
+    pub fn set_shape(&self, _shape: Option<BezPath>) {
+        warn!("WindowHandle::set_shape unimplemented for web.");
+    }
+
     pub fn request_anim_frame(&self) {
+        if let Some(s) = self.0.upgrade() {
+            s.anim_frame_requested.set(true);
+        }
         self.render_soon();
     }
 
+    pub fn set_max_frame_rate(&self, _fps: Option<f32>) {
+        warn!("WindowHandle::set_max_frame_rate unimplemented for web.");
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        // Browsers don't say how often the display refreshes.
+        DEFAULT_REFRESH_INTERVAL
+    }
+
     pub fn invalidate_rect(&self, rect: Rect) {
         if let Some(s) = self.0.upgrade() {
             s.invalid.borrow_mut().add_rect(rect);
@@ -567,6 +755,11 @@ impl WindowHandle {
         self.render_soon();
     }
 
+    pub fn scroll_region(&self, rect: Rect, _delta: Vec2) {
+        // TODO: draw the canvas onto itself instead.
+        self.invalidate_rect(rect);
+    }
+
     pub fn text(&self) -> PietText {
         let s = self
             .0
@@ -598,6 +791,15 @@ impl WindowHandle {
         // no-op for now, until we get a properly implemented text input
     }
 
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Instant,
+        _tolerance: Duration,
+    ) -> TimerToken {
+        // The browser doesn't take a tolerance; it throttles timers in the background anyway.
+        self.request_timer(deadline)
+    }
+
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
         use std::convert::TryFrom;
         let interval = deadline.duration_since(Instant::now()).as_millis();
@@ -614,32 +816,109 @@ impl WindowHandle {
         if let Some(state) = self.0.upgrade() {
             let s = state.clone();
             let f = move || {
+                s.timers.borrow_mut().remove(&token);
                 if let Ok(mut handler_borrow) = s.handler.try_borrow_mut() {
                     handler_borrow.timer(token);
                 }
             };
-            state
+            let id = state
                 .window
                 .set_timeout_with_callback_and_timeout_and_arguments_0(
                     Closure::once_into_js(f).as_ref().unchecked_ref(),
                     interval,
                 )
                 .expect("Failed to call setTimeout with a callback");
+            state.timers.borrow_mut().insert(token, id);
         }
         token
     }
 
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        use std::convert::TryFrom;
+        let interval = i32::try_from(interval.as_millis()).unwrap_or(i32::MAX);
+        let token = TimerToken::next();
+
+        if let Some(state) = self.0.upgrade() {
+            // The interval keeps running until it's cancelled, so it mustn't keep the window
+            // alive.
+            let s = Rc::downgrade(&state);
+            let f = move || {
+                if let Some(s) = s.upgrade() {
+                    if let Ok(mut handler_borrow) = s.handler.try_borrow_mut() {
+                        handler_borrow.timer(token);
+                    }
+                }
+            };
+            let id = state
+                .window
+                .set_interval_with_callback_and_timeout_and_arguments_0(
+                    Closure::wrap(Box::new(f) as Box<dyn FnMut()>)
+                        .into_js_value()
+                        .unchecked_ref(),
+                    interval,
+                )
+                .expect("Failed to call setInterval with a callback");
+            state.timers.borrow_mut().insert(token, id);
+        }
+        token
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        if let Some(state) = self.0.upgrade() {
+            if let Some(id) = state.timers.borrow_mut().remove(&token) {
+                // Timeouts and intervals share their ids, so this clears either.
+                state.window.clear_interval_with_handle(id);
+            }
+        }
+    }
+
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         if let Some(s) = self.0.upgrade() {
             set_cursor(&s.canvas, cursor);
         }
     }
 
+    pub fn confine_pointer(&self, _region: Option<Rect>) {
+        warn!("WindowHandle::confine_pointer unimplemented for web.");
+    }
+
+    pub fn lock_pointer(&self) {
+        warn!("WindowHandle::lock_pointer unimplemented for web.");
+    }
+
+    pub fn unlock_pointer(&self) {
+        warn!("WindowHandle::unlock_pointer unimplemented for web.");
+    }
+
+    pub fn set_raw_pointer_input(&self, _enabled: bool) {
+        warn!("WindowHandle::set_raw_pointer_input unimplemented for web.");
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        if let Some(s) = self.0.upgrade() {
+            s.autoscroll.begin(origin);
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        if let Some(s) = self.0.upgrade() {
+            s.shortcut_capture.begin(callback);
+        }
+    }
+
     pub fn make_cursor(&self, _cursor_desc: &CursorDesc) -> Option<Cursor> {
         warn!("Custom cursors are not yet supported in the web backend");
         None
     }
 
+    pub fn start_drag(&self, _data: DragData, _image: Option<DragImage>) {
+        warn!("start_drag is currently unimplemented for web.");
+    }
+
+    pub fn set_drop_formats(&self, _formats: &[FormatId]) {
+        // Drops aren't supported on web yet.
+    }
+
     pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
         warn!("open_file is currently unimplemented for web.");
         None
@@ -680,6 +959,15 @@ impl WindowHandle {
         })
     }
 
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        warn!("WindowHandle::input_language unimplemented for web.");
+        None
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        Application::locale_info()
+    }
+
     /// Get the `Scale` of the window.
     pub fn get_scale(&self) -> Result<Scale, ShellError> {
         Ok(self
@@ -690,6 +978,21 @@ impl WindowHandle {
             .get())
     }
 
+    pub fn text_scale_factor(&self) -> f64 {
+        // Browsers fold their text zoom into the device pixel ratio.
+        1.0
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        // Browsers apply forced colors to the page themselves, but they don't reach the canvas.
+        None
+    }
+
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        // The page's own scrollbars aren't something we can measure from the canvas.
+        ScrollbarMetrics::default()
+    }
+
     pub fn set_menu(&self, _menu: Menu) {
         warn!("set_menu unimplemented for web");
     }
@@ -704,6 +1007,10 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_theme_override(&self, _theme: Option<Theme>) {
+        warn!("WindowHandle::set_theme_override unimplemented for web.");
+    }
+
     #[cfg(feature = "accesskit")]
     pub fn update_accesskit_if_active(
         &self,
@@ -765,6 +1072,15 @@ fn mouse_button(button: i16) -> Option<MouseButton> {
     }
 }
 
+/// Events that a script dispatched aren't trusted.
+fn input_source(event: &web_sys::Event) -> InputSource {
+    if event.is_trusted() {
+        InputSource::Hardware
+    } else {
+        InputSource::Injected
+    }
+}
+
 fn mouse_buttons(mask: u16) -> MouseButtons {
     let mut buttons = MouseButtons::new();
     if mask & 1 != 0 {
@@ -800,6 +1116,9 @@ fn set_cursor(canvas: &web_sys::HtmlCanvasElement, cursor: &Cursor) {
                 Cursor::NotAllowed => "not-allowed",
                 Cursor::ResizeLeftRight => "ew-resize",
                 Cursor::ResizeUpDown => "ns-resize",
+                Cursor::AllScroll => "all-scroll",
+                Cursor::Wait => "wait",
+                Cursor::Progress => "progress",
                 // TODO: support custom cursors
                 Cursor::Custom(_) => "default",
             },
@@ -20,37 +20,55 @@ use std::mem;
 use std::ptr;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use winapi::shared::minwindef::{FALSE, HINSTANCE};
 use winapi::shared::ntdef::LPCWSTR;
-use winapi::shared::windef::{DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, HCURSOR, HWND};
-use winapi::shared::winerror::HRESULT_FROM_WIN32;
+use winapi::shared::windef::{
+    DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, DPI_AWARENESS_CONTEXT_SYSTEM_AWARE,
+    DPI_AWARENESS_CONTEXT_UNAWARE, HCURSOR, HWND,
+};
+use winapi::shared::winerror::{FAILED, HRESULT_FROM_WIN32};
 use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::ole2::OleInitialize;
 use winapi::um::shellscalingapi::PROCESS_PER_MONITOR_DPI_AWARE;
-use winapi::um::winnls::GetUserDefaultLocaleName;
+use winapi::um::winnls::{
+    GetLocaleInfoEx, GetUserDefaultLocaleName, LOCALE_SDECIMAL, LOCALE_SSHORTDATE,
+    LOCALE_STHOUSAND, LOCALE_STIMEFORMAT,
+};
 use winapi::um::winnt::LOCALE_NAME_MAX_LENGTH;
 use winapi::um::winuser::{
-    DispatchMessageW, GetAncestor, GetMessageW, LoadIconW, PeekMessageW, PostMessageW,
-    PostQuitMessage, RegisterClassW, TranslateAcceleratorW, TranslateMessage, GA_ROOT,
-    IDI_APPLICATION, MSG, PM_NOREMOVE, WM_TIMER, WNDCLASSW,
+    DispatchMessageW, GetAncestor, GetMessageW, IsDialogMessageW, KillTimer, LoadIconW,
+    PeekMessageW, PostMessageW, PostQuitMessage, RegisterClassW, SetTimer, TranslateAcceleratorW,
+    TranslateMessage, GA_ROOT, IDI_APPLICATION, MSG, PM_NOREMOVE, PM_QS_INPUT, PM_REMOVE,
+    USER_TIMER_MAXIMUM, WM_KEYFIRST, WM_KEYLAST, WM_MOUSEFIRST, WM_MOUSELAST, WM_POINTERUP,
+    WM_POINTERUPDATE, WM_TIMER, WM_TOUCH, WNDCLASSW,
 };
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, DispatchPolicy};
+use crate::common_util::IdleTracker;
+use crate::keyboard::KeyboardLayout;
+use crate::locale::LocaleInfo;
+use crate::platform::windows::DpiMode;
+use crate::sensors::SensorKind;
 
 use super::accels;
 use super::clipboard::Clipboard;
 use super::error::Error;
+use super::keyboard;
 use super::util::{self, FromWide, ToWide, CLASS_NAME, OPTIONAL_FUNCTIONS};
 use super::window::{self, DS_REQUEST_DESTROY};
 
 #[derive(Clone)]
 pub(crate) struct Application {
     state: Rc<RefCell<State>>,
+    idle: Rc<IdleTracker>,
 }
 
 struct State {
     quitting: bool,
     windows: HashSet<HWND>,
+    dispatch_policy: DispatchPolicy,
 }
 
 /// Used to ensure the window class is registered only once per process.
@@ -62,8 +80,12 @@ impl Application {
         let state = Rc::new(RefCell::new(State {
             quitting: false,
             windows: HashSet::new(),
+            dispatch_policy: DispatchPolicy::default(),
         }));
-        Ok(Application { state })
+        Ok(Application {
+            state,
+            idle: Default::default(),
+        })
     }
 
     /// Initialize the app. At the moment, this is mostly needed for hi-dpi.
@@ -71,6 +93,13 @@ impl Application {
     #[allow(clippy::unnecessary_wraps)]
     fn init() -> Result<(), Error> {
         util::attach_console();
+        // Drag and drop needs OLE.
+        unsafe {
+            let hr = OleInitialize(ptr::null_mut());
+            if FAILED(hr) {
+                tracing::warn!("failed to initialize OLE: {}", Error::Hr(hr));
+            }
+        }
         if let Some(func) = OPTIONAL_FUNCTIONS.SetProcessDpiAwarenessContext {
             // This function is only supported on windows 10
             unsafe {
@@ -115,13 +144,16 @@ impl Application {
         self.state.borrow_mut().windows.remove(&hwnd)
     }
 
-    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
+    pub fn run(self, mut handler: Option<Box<dyn AppHandler>>) {
+        // A thread timer that wakes us up when the application would go idle.
+        let mut idle_timer = None;
         unsafe {
             // Handle windows messages.
             //
             // NOTE: Code here will not run when we aren't in charge of the message loop. That
             // will include when moving or resizing the window, and when showing modal dialogs.
             loop {
+                set_idle_timer(&mut idle_timer, self.idle.deadline());
                 let mut msg = mem::MaybeUninit::uninit();
 
                 // Timer messages have a low priority and tend to get delayed. Peeking for them
@@ -135,7 +167,22 @@ impl Application {
                     PM_NOREMOVE,
                 );
 
-                let res = GetMessageW(msg.as_mut_ptr(), ptr::null_mut(), 0, 0);
+                // Posted messages, which include our idle callbacks, are retrieved before input.
+                // Take input out of the queue first, so that a flood of them can't starve it.
+                let input_first = self.state.borrow().dispatch_policy == DispatchPolicy::InputFirst;
+                let res = if input_first
+                    && PeekMessageW(
+                        msg.as_mut_ptr(),
+                        ptr::null_mut(),
+                        0,
+                        0,
+                        PM_REMOVE | PM_QS_INPUT,
+                    ) != 0
+                {
+                    1
+                } else {
+                    GetMessageW(msg.as_mut_ptr(), ptr::null_mut(), 0, 0)
+                };
                 if res <= 0 {
                     if res == -1 {
                         tracing::error!(
@@ -146,15 +193,28 @@ impl Application {
                     break;
                 }
                 let mut msg: MSG = msg.assume_init();
-                let accels = accels::find_accels(GetAncestor(msg.hwnd, GA_ROOT));
-                let translated = accels.map_or(false, |it| {
+                if is_input(msg.message) {
+                    self.idle.input();
+                }
+                let root = GetAncestor(msg.hwnd, GA_ROOT);
+                let accels = accels::find_accels(root);
+                let mut translated = accels.map_or(false, |it| {
                     TranslateAcceleratorW(msg.hwnd, it.handle(), &mut msg) != 0
                 });
+                // Let the dialog manager move the focus between native views embedded in our
+                // windows, the way it would in a dialog box.
+                if !translated && root != msg.hwnd && self.state.borrow().windows.contains(&root) {
+                    translated = IsDialogMessageW(root, &mut msg) != 0;
+                }
                 if !translated {
                     TranslateMessage(&msg);
                     DispatchMessageW(&msg);
                 }
+                if let Some(handler) = handler.as_mut() {
+                    self.idle.dispatch(&mut **handler);
+                }
             }
+            set_idle_timer(&mut idle_timer, None);
         }
     }
 
@@ -189,6 +249,28 @@ impl Application {
         Clipboard
     }
 
+    pub fn enable_sensor(&self, _kind: SensorKind) -> bool {
+        tracing::warn!(
+            "Application::enable_sensor is currently unimplemented for the Windows backend."
+        );
+        false
+    }
+
+    pub fn disable_sensor(&self, _kind: SensorKind) {}
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        // The message loop picks up the new deadline after the current message.
+        self.idle.set_timeout(timeout);
+    }
+
+    pub fn set_dispatch_policy(&self, policy: DispatchPolicy) {
+        self.state.borrow_mut().dispatch_policy = policy;
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        keyboard::keyboard_layouts()
+    }
+
     pub fn get_locale() -> String {
         let mut buf = [0u16; LOCALE_NAME_MAX_LENGTH];
         let len_with_null =
@@ -203,4 +285,86 @@ impl Application {
             "en-US".into()
         })
     }
+
+    pub fn locale_info() -> LocaleInfo {
+        // A null locale name means the user's locale, including their overrides.
+        let item = |item| {
+            // The longest of these, the short date format, is at most 80 characters.
+            let mut buf = [0u16; 80];
+            let len_with_null = unsafe {
+                GetLocaleInfoEx(ptr::null(), item, buf.as_mut_ptr(), buf.len() as _) as usize
+            };
+            if len_with_null > 0 {
+                buf.get(..len_with_null - 1).and_then(FromWide::to_string)
+            } else {
+                None
+            }
+        };
+        let mut info = LocaleInfo {
+            locale: Application::get_locale(),
+            ..LocaleInfo::default()
+        };
+        if let Some(decimal) = item(LOCALE_SDECIMAL) {
+            info.decimal_separator = decimal;
+        }
+        if let Some(group) = item(LOCALE_STHOUSAND) {
+            info.grouping_separator = group;
+        }
+        if let (Some(date), Some(time)) = (item(LOCALE_SSHORTDATE), item(LOCALE_STIMEFORMAT)) {
+            info.apply_patterns(&date, &time);
+        }
+        info
+    }
+}
+
+impl crate::platform::windows::ApplicationExt for crate::Application {
+    fn set_dpi_awareness(&self, mode: DpiMode) -> bool {
+        let func = match OPTIONAL_FUNCTIONS.SetThreadDpiAwarenessContext {
+            Some(func) => func,
+            None => {
+                tracing::warn!("Setting the DPI awareness requires Windows 10 version 1607");
+                return false;
+            }
+        };
+        let context = match mode {
+            DpiMode::PerMonitorV2 => DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+            DpiMode::System => DPI_AWARENESS_CONTEXT_SYSTEM_AWARE,
+            DpiMode::Unaware => DPI_AWARENESS_CONTEXT_UNAWARE,
+        };
+        // This returns the old context, or null if the new one was invalid.
+        unsafe { !func(context).is_null() }
+    }
+}
+
+/// Whether the message is the user's input, which keeps the application from going idle.
+fn is_input(message: u32) -> bool {
+    matches!(
+        message,
+        WM_KEYFIRST..=WM_KEYLAST
+            | WM_MOUSEFIRST..=WM_MOUSELAST
+            | WM_TOUCH
+            | WM_POINTERUPDATE..=WM_POINTERUP
+    )
+}
+
+/// Replaces the thread timer in `timer` with one that fires at `deadline`.
+///
+/// The timer only has to wake up the message loop, so it has no window and no callback.
+unsafe fn set_idle_timer(timer: &mut Option<(usize, Instant)>, deadline: Option<Instant>) {
+    if timer.map(|(_, at)| at) == deadline {
+        return;
+    }
+    if let Some((id, _)) = timer.take() {
+        KillTimer(ptr::null_mut(), id);
+    }
+    if let Some(deadline) = deadline {
+        let elapse = deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis();
+        let elapse = elapse.clamp(1, USER_TIMER_MAXIMUM as u128) as u32;
+        let id = SetTimer(ptr::null_mut(), 0, elapse, None);
+        if id != 0 {
+            *timer = Some((id, deadline));
+        }
+    }
 }
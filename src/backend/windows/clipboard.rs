@@ -142,6 +142,15 @@ impl Clipboard {
         .flatten()
     }
 
+    pub fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        with_clipboard(|| {
+            iter_clipboard_types()
+                .map(|id| (get_format_name(id), format_size(id)))
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
     pub fn available_type_names(&self) -> Vec<String> {
         with_clipboard(|| {
             iter_clipboard_types()
@@ -166,7 +175,7 @@ fn with_clipboard<V>(f: impl FnOnce() -> V) -> Option<V> {
     }
 }
 
-unsafe fn make_handle(format: &ClipboardFormat) -> HANDLE {
+pub(super) unsafe fn make_handle(format: &ClipboardFormat) -> HANDLE {
     if format.identifier == ClipboardFormat::TEXT {
         let s = std::str::from_utf8_unchecked(&format.data);
         let wstr = s.to_wide();
@@ -184,7 +193,7 @@ unsafe fn make_handle(format: &ClipboardFormat) -> HANDLE {
     }
 }
 
-fn get_format_id(format: FormatId) -> Option<UINT> {
+pub(super) fn get_format_id(format: FormatId) -> Option<UINT> {
     if let Some((id, _)) = STANDARD_FORMATS.iter().find(|(_, s)| s == &format) {
         return Some(*id);
     }
@@ -261,6 +270,27 @@ fn iter_clipboard_types() -> impl Iterator<Item = UINT> {
     }
 }
 
+/// Returns the size of the data in a format, if it is stored in global memory.
+///
+/// Must be called while the clipboard is open.
+fn format_size(format: UINT) -> Option<usize> {
+    // These are GDI handles, not global memory; CF_OWNERDISPLAY has no data at all.
+    const NOT_HGLOBAL: &[UINT] = &[2, 9, 14, 0x80, 0x82, 0x8E];
+    if NOT_HGLOBAL.contains(&format) {
+        return None;
+    }
+    unsafe {
+        let handle = GetClipboardData(format);
+        if handle.is_null() {
+            return None;
+        }
+        match GlobalSize(handle) {
+            0 => None,
+            size => Some(size),
+        }
+    }
+}
+
 fn get_format_name(format: UINT) -> String {
     if let Some(name) = get_standard_format_name(format) {
         return name.to_owned();
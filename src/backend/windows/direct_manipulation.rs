@@ -0,0 +1,581 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Smooth scrolling on precision touchpads, with Direct Manipulation.
+//!
+//! Unless a window asks for them, Windows turns touchpad gestures into `WM_MOUSEWHEEL`
+//! messages, which scroll in jumps. So each window gets a Direct Manipulation viewport, and
+//! hands it the touchpad contacts from `DM_POINTERHITTEST`. While the viewport moves, a timer
+//! updates it every frame, and its event handler sends the movement to the window procedure
+//! as `DS_SCROLL_EVENT` messages, in pixels and with the phase of the momentum glide.
+//!
+//! Windows doesn't have a way to scroll without content to scroll, so the viewport looks at
+//! the middle of a much bigger content rectangle. It moves back there whenever a gesture ends.
+
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+
+use std::cell::Cell;
+use std::ptr::null_mut;
+
+use tracing::warn;
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{REFCLSID, REFIID};
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, LOWORD, LPARAM, UINT, WPARAM};
+use winapi::shared::windef::{HWND, RECT};
+use winapi::shared::winerror::S_OK;
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winnt::HRESULT;
+use winapi::um::winuser::{
+    KillTimer, SendMessageW, SetTimer, MSG, POINTER_INPUT_TYPE, PT_TOUCHPAD,
+};
+use winapi::{Class, Interface, DEFINE_GUID, ENUM, RIDL};
+use wio::com::ComPtr;
+
+use crate::kurbo::Vec2;
+use crate::mouse::MomentumPhase;
+
+use super::dnd::{add_ref, query_interface, release, Object};
+use super::error::Error;
+use super::util::{as_result, OPTIONAL_FUNCTIONS};
+use super::window::{DIRECT_MANIPULATION_TIMER_ID, DS_SCROLL_EVENT};
+
+// Direct Manipulation isn't in winapi.
+ENUM! {enum DIRECTMANIPULATION_STATUS {
+    DIRECTMANIPULATION_BUILDING = 0,
+    DIRECTMANIPULATION_ENABLED = 1,
+    DIRECTMANIPULATION_DISABLED = 2,
+    DIRECTMANIPULATION_RUNNING = 3,
+    DIRECTMANIPULATION_INERTIA = 4,
+    DIRECTMANIPULATION_READY = 5,
+    DIRECTMANIPULATION_SUSPENDED = 6,
+}}
+
+ENUM! {enum DIRECTMANIPULATION_CONFIGURATION {
+    DIRECTMANIPULATION_CONFIGURATION_INTERACTION = 0x1,
+    DIRECTMANIPULATION_CONFIGURATION_TRANSLATION_X = 0x2,
+    DIRECTMANIPULATION_CONFIGURATION_TRANSLATION_Y = 0x4,
+    DIRECTMANIPULATION_CONFIGURATION_TRANSLATION_INERTIA = 0x20,
+    DIRECTMANIPULATION_CONFIGURATION_RAILS_X = 0x100,
+    DIRECTMANIPULATION_CONFIGURATION_RAILS_Y = 0x200,
+}}
+
+ENUM! {enum DIRECTMANIPULATION_VIEWPORT_OPTIONS {
+    DIRECTMANIPULATION_VIEWPORT_OPTIONS_MANUALUPDATE = 0x2,
+}}
+
+RIDL! {#[uuid(0x54e2_11b6, 0x3650, 0x4f75, 0x83, 0x34, 0xfa, 0x35, 0x95, 0x98, 0xe1, 0xc5)]
+class DirectManipulationManager;}
+
+RIDL! {#[uuid(0xfbf5_d3b4, 0x70c7, 0x4163, 0x93, 0x22, 0x5a, 0x6f, 0x66, 0x0d, 0x6f, 0xbc)]
+interface IDirectManipulationManager(IDirectManipulationManagerVtbl): IUnknown(IUnknownVtbl) {
+    fn Activate(
+        window: HWND,
+    ) -> HRESULT,
+    fn Deactivate(
+        window: HWND,
+    ) -> HRESULT,
+    fn RegisterHitTestTarget(
+        window: HWND,
+        hitTestWindow: HWND,
+        type_: UINT,
+    ) -> HRESULT,
+    fn ProcessInput(
+        message: *const MSG,
+        handled: *mut BOOL,
+    ) -> HRESULT,
+    fn GetUpdateManager(
+        riid: REFIID,
+        object: *mut *mut c_void,
+    ) -> HRESULT,
+    fn CreateViewport(
+        frameInfo: *mut IUnknown,
+        window: HWND,
+        riid: REFIID,
+        object: *mut *mut c_void,
+    ) -> HRESULT,
+    fn CreateContent(
+        frameInfo: *mut IUnknown,
+        clsid: REFCLSID,
+        riid: REFIID,
+        object: *mut *mut c_void,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xb0ae_62fd, 0xbe34, 0x46e7, 0x9c, 0xaa, 0xd3, 0x61, 0xfa, 0xcb, 0xb9, 0xcc)]
+interface IDirectManipulationUpdateManager(IDirectManipulationUpdateManagerVtbl):
+    IUnknown(IUnknownVtbl) {
+    fn RegisterWaitHandleCallback(
+        handle: *mut c_void,
+        eventHandler: *mut IUnknown,
+        cookie: *mut DWORD,
+    ) -> HRESULT,
+    fn UnregisterWaitHandleCallback(
+        cookie: DWORD,
+    ) -> HRESULT,
+    fn Update(
+        frameInfo: *mut IUnknown,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0x28b8_5a3d, 0x60a0, 0x48bd, 0x9b, 0xa1, 0x5c, 0xe8, 0xd9, 0xea, 0x3a, 0x6d)]
+interface IDirectManipulationViewport(IDirectManipulationViewportVtbl): IUnknown(IUnknownVtbl) {
+    fn Enable() -> HRESULT,
+    fn Disable() -> HRESULT,
+    fn SetContact(
+        pointerId: u32,
+    ) -> HRESULT,
+    fn ReleaseContact(
+        pointerId: u32,
+    ) -> HRESULT,
+    fn ReleaseAllContacts() -> HRESULT,
+    fn GetStatus(
+        status: *mut DIRECTMANIPULATION_STATUS,
+    ) -> HRESULT,
+    fn GetTag(
+        riid: REFIID,
+        object: *mut *mut c_void,
+        id: *mut u32,
+    ) -> HRESULT,
+    fn SetTag(
+        object: *mut IUnknown,
+        id: u32,
+    ) -> HRESULT,
+    fn GetViewportRect(
+        viewport: *mut RECT,
+    ) -> HRESULT,
+    fn SetViewportRect(
+        viewport: *const RECT,
+    ) -> HRESULT,
+    fn ZoomToRect(
+        left: f32,
+        top: f32,
+        right: f32,
+        bottom: f32,
+        animate: BOOL,
+    ) -> HRESULT,
+    fn SetViewportTransform(
+        matrix: *const f32,
+        pointCount: DWORD,
+    ) -> HRESULT,
+    fn SyncDisplayTransform(
+        matrix: *const f32,
+        pointCount: DWORD,
+    ) -> HRESULT,
+    fn GetPrimaryContent(
+        riid: REFIID,
+        object: *mut *mut c_void,
+    ) -> HRESULT,
+    fn AddContent(
+        content: *mut IDirectManipulationContent,
+    ) -> HRESULT,
+    fn RemoveContent(
+        content: *mut IDirectManipulationContent,
+    ) -> HRESULT,
+    fn SetViewportOptions(
+        options: DIRECTMANIPULATION_VIEWPORT_OPTIONS,
+    ) -> HRESULT,
+    fn AddConfiguration(
+        configuration: DIRECTMANIPULATION_CONFIGURATION,
+    ) -> HRESULT,
+    fn RemoveConfiguration(
+        configuration: DIRECTMANIPULATION_CONFIGURATION,
+    ) -> HRESULT,
+    fn ActivateConfiguration(
+        configuration: DIRECTMANIPULATION_CONFIGURATION,
+    ) -> HRESULT,
+    fn SetManualGesture(
+        configuration: UINT,
+    ) -> HRESULT,
+    fn SetChaining(
+        enabledTypes: UINT,
+    ) -> HRESULT,
+    fn AddEventHandler(
+        window: HWND,
+        eventHandler: *mut IUnknown,
+        cookie: *mut DWORD,
+    ) -> HRESULT,
+    fn RemoveEventHandler(
+        cookie: DWORD,
+    ) -> HRESULT,
+    fn SetInputMode(
+        mode: UINT,
+    ) -> HRESULT,
+    fn SetUpdateMode(
+        mode: UINT,
+    ) -> HRESULT,
+    fn Stop() -> HRESULT,
+    fn Abandon() -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xb899_62cb, 0x3d89, 0x442b, 0xbb, 0x58, 0x50, 0x98, 0xfa, 0x0f, 0x9f, 0x16)]
+interface IDirectManipulationContent(IDirectManipulationContentVtbl): IUnknown(IUnknownVtbl) {
+    fn GetContentRect(
+        contentSize: *mut RECT,
+    ) -> HRESULT,
+    fn SetContentRect(
+        contentSize: *const RECT,
+    ) -> HRESULT,
+    fn GetViewport(
+        riid: REFIID,
+        object: *mut *mut c_void,
+    ) -> HRESULT,
+    fn GetTag(
+        riid: REFIID,
+        object: *mut *mut c_void,
+        id: *mut u32,
+    ) -> HRESULT,
+    fn SetTag(
+        object: *mut IUnknown,
+        id: u32,
+    ) -> HRESULT,
+    fn GetOutputTransform(
+        matrix: *mut f32,
+        pointCount: DWORD,
+    ) -> HRESULT,
+    fn GetContentTransform(
+        matrix: *mut f32,
+        pointCount: DWORD,
+    ) -> HRESULT,
+    fn SyncContentTransform(
+        matrix: *const f32,
+        pointCount: DWORD,
+    ) -> HRESULT,
+}}
+
+DEFINE_GUID! {IID_IDirectManipulationViewportEventHandler,
+0x9521_21da, 0xd69f, 0x45f9, 0xb0, 0xf9, 0xf2, 0x39, 0x44, 0x32, 0x1a, 0x6d}
+
+#[repr(C)]
+struct IDirectManipulationViewportEventHandlerVtbl {
+    parent: IUnknownVtbl,
+    OnViewportStatusChanged: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        viewport: *mut IDirectManipulationViewport,
+        current: DIRECTMANIPULATION_STATUS,
+        previous: DIRECTMANIPULATION_STATUS,
+    ) -> HRESULT,
+    OnViewportUpdated: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        viewport: *mut IDirectManipulationViewport,
+    ) -> HRESULT,
+    OnContentUpdated: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        viewport: *mut IDirectManipulationViewport,
+        content: *mut IDirectManipulationContent,
+    ) -> HRESULT,
+}
+
+static VIEWPORT_EVENT_HANDLER_VTBL: IDirectManipulationViewportEventHandlerVtbl =
+    IDirectManipulationViewportEventHandlerVtbl {
+        parent: IUnknownVtbl {
+            QueryInterface: query_interface::<ViewportEventHandler>,
+            AddRef: add_ref::<ViewportEventHandler>,
+            Release: release::<ViewportEventHandler>,
+        },
+        OnViewportStatusChanged: on_viewport_status_changed,
+        OnViewportUpdated: on_viewport_updated,
+        OnContentUpdated: on_content_updated,
+    };
+
+/// The size of the content that the viewport scrolls over, in pixels.
+///
+/// The viewport starts each gesture in the middle, so a single gesture can go half of this
+/// far in any direction.
+const CONTENT_SIZE: i32 = 1 << 20;
+
+/// How often the viewport is updated while it moves, in milliseconds.
+const UPDATE_INTERVAL: UINT = 16;
+
+/// A scroll event, sent to the window procedure with `DS_SCROLL_EVENT`.
+pub(super) struct ScrollMessage {
+    /// How far to scroll, in pixels.
+    pub(super) delta: Vec2,
+    pub(super) phase: MomentumPhase,
+}
+
+/// The Direct Manipulation viewport of a window.
+pub(super) struct DirectManipulation {
+    hwnd: HWND,
+    manager: ComPtr<IDirectManipulationManager>,
+    update_manager: ComPtr<IDirectManipulationUpdateManager>,
+    viewport: ComPtr<IDirectManipulationViewport>,
+    handler_cookie: DWORD,
+}
+
+/// Turns the viewport's movement into scroll events.
+struct ViewportEventHandler {
+    hwnd: HWND,
+    content: ComPtr<IDirectManipulationContent>,
+    status: Cell<DIRECTMANIPULATION_STATUS>,
+    /// The content's offset at the last update, in pixels.
+    last_offset: Cell<Vec2>,
+    /// Whether a momentum glide has started sending events.
+    gliding: Cell<bool>,
+}
+
+impl DirectManipulation {
+    /// Sets up a viewport for `hwnd`, which is `width` by `height` pixels.
+    ///
+    /// This fails on Windows 7, which doesn't have Direct Manipulation.
+    pub(super) fn new(hwnd: HWND, width: i32, height: i32) -> Result<DirectManipulation, Error> {
+        unsafe {
+            let mut manager: *mut IDirectManipulationManager = null_mut();
+            as_result(CoCreateInstance(
+                &DirectManipulationManager::uuidof(),
+                null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IDirectManipulationManager::uuidof(),
+                &mut manager as *mut _ as *mut *mut c_void,
+            ))?;
+            let manager = ComPtr::from_raw(manager);
+
+            let mut update_manager: *mut IDirectManipulationUpdateManager = null_mut();
+            as_result(manager.GetUpdateManager(
+                &IDirectManipulationUpdateManager::uuidof(),
+                &mut update_manager as *mut _ as *mut *mut c_void,
+            ))?;
+            let update_manager = ComPtr::from_raw(update_manager);
+
+            let mut viewport: *mut IDirectManipulationViewport = null_mut();
+            as_result(manager.CreateViewport(
+                null_mut(),
+                hwnd,
+                &IDirectManipulationViewport::uuidof(),
+                &mut viewport as *mut _ as *mut *mut c_void,
+            ))?;
+            let viewport = ComPtr::from_raw(viewport);
+
+            let mut content: *mut IDirectManipulationContent = null_mut();
+            as_result(viewport.GetPrimaryContent(
+                &IDirectManipulationContent::uuidof(),
+                &mut content as *mut _ as *mut *mut c_void,
+            ))?;
+            let content = ComPtr::from_raw(content);
+
+            as_result(viewport.ActivateConfiguration(
+                DIRECTMANIPULATION_CONFIGURATION_INTERACTION
+                    | DIRECTMANIPULATION_CONFIGURATION_TRANSLATION_X
+                    | DIRECTMANIPULATION_CONFIGURATION_TRANSLATION_Y
+                    | DIRECTMANIPULATION_CONFIGURATION_TRANSLATION_INERTIA
+                    | DIRECTMANIPULATION_CONFIGURATION_RAILS_X
+                    | DIRECTMANIPULATION_CONFIGURATION_RAILS_Y,
+            ))?;
+            // We drive the updates with a timer, rather than a compositor.
+            as_result(
+                viewport.SetViewportOptions(DIRECTMANIPULATION_VIEWPORT_OPTIONS_MANUALUPDATE),
+            )?;
+            as_result(content.SetContentRect(&RECT {
+                left: 0,
+                top: 0,
+                right: CONTENT_SIZE,
+                bottom: CONTENT_SIZE,
+            }))?;
+
+            let handler = ViewportEventHandler {
+                hwnd,
+                content,
+                status: Cell::new(DIRECTMANIPULATION_BUILDING),
+                last_offset: Cell::new(Vec2::ZERO),
+                gliding: Cell::new(false),
+            };
+            let handler = Object::create(
+                &VIEWPORT_EVENT_HANDLER_VTBL,
+                IID_IDirectManipulationViewportEventHandler,
+                handler,
+            );
+            let mut handler_cookie = 0;
+            let hr = viewport.AddEventHandler(hwnd, handler, &mut handler_cookie);
+            // If it was added, the viewport has its own reference now.
+            (*handler).Release();
+            as_result(hr)?;
+
+            let dm = DirectManipulation {
+                hwnd,
+                manager,
+                update_manager,
+                viewport,
+                handler_cookie,
+            };
+            dm.set_size(width, height);
+            as_result(dm.manager.Activate(hwnd))?;
+            as_result(dm.viewport.Enable())?;
+            Ok(dm)
+        }
+    }
+
+    /// Updates the viewport after the window was resized to `width` by `height` pixels.
+    pub(super) fn set_size(&self, width: i32, height: i32) {
+        unsafe {
+            let rect = RECT {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            };
+            if let Err(e) = as_result(self.viewport.SetViewportRect(&rect)) {
+                warn!("failed to resize the Direct Manipulation viewport: {}", e);
+            }
+            center(&self.viewport, width as f32, height as f32);
+        }
+    }
+
+    /// Gives a new pointer to the viewport if it's on a touchpad, and returns whether it did.
+    ///
+    /// This is the answer to `DM_POINTERHITTEST`, whose `WPARAM` has the pointer's id.
+    pub(super) fn pointer_hit_test(&self, wparam: WPARAM) -> bool {
+        let get_pointer_type = match OPTIONAL_FUNCTIONS.GetPointerType {
+            Some(f) => f,
+            None => return false,
+        };
+        let pointer_id = LOWORD(wparam as u32) as u32;
+        unsafe {
+            let mut pointer_type: POINTER_INPUT_TYPE = 0;
+            if get_pointer_type(pointer_id, &mut pointer_type) == FALSE
+                || pointer_type != PT_TOUCHPAD
+            {
+                return false;
+            }
+            match as_result(self.viewport.SetContact(pointer_id)) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("failed to give a touchpad contact to the viewport: {}", e);
+                    false
+                }
+            }
+        }
+    }
+
+    /// Moves the viewport along. This is called by the timer while it moves.
+    pub(super) fn update(&self) {
+        unsafe {
+            if let Err(e) = as_result(self.update_manager.Update(null_mut())) {
+                warn!("failed to update the Direct Manipulation viewport: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for DirectManipulation {
+    fn drop(&mut self) {
+        unsafe {
+            KillTimer(self.hwnd, DIRECT_MANIPULATION_TIMER_ID);
+            self.viewport.Stop();
+            self.viewport.RemoveEventHandler(self.handler_cookie);
+            self.viewport.Abandon();
+            self.manager.Deactivate(self.hwnd);
+        }
+    }
+}
+
+/// Moves the viewport back to the middle of the content, without animating it.
+unsafe fn center(viewport: &IDirectManipulationViewport, width: f32, height: f32) {
+    let middle = CONTENT_SIZE as f32 / 2.0;
+    let (left, top) = (middle - width / 2.0, middle - height / 2.0);
+    viewport.ZoomToRect(left, top, left + width, top + height, FALSE);
+}
+
+impl ViewportEventHandler {
+    /// The content's offset in the viewport, in pixels.
+    unsafe fn offset(&self) -> Vec2 {
+        // A 2D affine transform: scale x, skew y, skew x, scale y, then the offsets.
+        let mut matrix = [0f32; 6];
+        self.content
+            .GetContentTransform(matrix.as_mut_ptr(), matrix.len() as DWORD);
+        Vec2::new(matrix[4] as f64, matrix[5] as f64)
+    }
+
+    fn send(&self, delta: Vec2, phase: MomentumPhase) {
+        let mut message = ScrollMessage { delta, phase };
+        unsafe {
+            SendMessageW(
+                self.hwnd,
+                DS_SCROLL_EVENT,
+                0,
+                &mut message as *mut ScrollMessage as LPARAM,
+            );
+        }
+    }
+}
+
+unsafe extern "system" fn on_viewport_status_changed(
+    this: *mut IUnknown,
+    viewport: *mut IDirectManipulationViewport,
+    current: DIRECTMANIPULATION_STATUS,
+    previous: DIRECTMANIPULATION_STATUS,
+) -> HRESULT {
+    let handler = Object::<ViewportEventHandler>::get(this);
+    handler.status.set(current);
+    match current {
+        DIRECTMANIPULATION_RUNNING => {
+            handler.last_offset.set(handler.offset());
+            SetTimer(
+                handler.hwnd,
+                DIRECT_MANIPULATION_TIMER_ID,
+                UPDATE_INTERVAL,
+                None,
+            );
+        }
+        DIRECTMANIPULATION_INERTIA => handler.gliding.set(false),
+        DIRECTMANIPULATION_READY => {
+            KillTimer(handler.hwnd, DIRECT_MANIPULATION_TIMER_ID);
+            if previous == DIRECTMANIPULATION_INERTIA && handler.gliding.get() {
+                handler.send(Vec2::ZERO, MomentumPhase::Ended);
+            }
+            let mut rect: RECT = std::mem::zeroed();
+            (*viewport).GetViewportRect(&mut rect);
+            center(
+                &*viewport,
+                (rect.right - rect.left) as f32,
+                (rect.bottom - rect.top) as f32,
+            );
+        }
+        _ => (),
+    }
+    S_OK
+}
+
+unsafe extern "system" fn on_viewport_updated(
+    _this: *mut IUnknown,
+    _viewport: *mut IDirectManipulationViewport,
+) -> HRESULT {
+    S_OK
+}
+
+unsafe extern "system" fn on_content_updated(
+    this: *mut IUnknown,
+    _viewport: *mut IDirectManipulationViewport,
+    _content: *mut IDirectManipulationContent,
+) -> HRESULT {
+    let handler = Object::<ViewportEventHandler>::get(this);
+    let phase = match handler.status.get() {
+        DIRECTMANIPULATION_RUNNING => MomentumPhase::None,
+        DIRECTMANIPULATION_INERTIA if handler.gliding.get() => MomentumPhase::Changed,
+        DIRECTMANIPULATION_INERTIA => MomentumPhase::Began,
+        // The viewport is moving back to the middle.
+        _ => return S_OK,
+    };
+    let offset = handler.offset();
+    // When the fingers move the content down, the view scrolls up.
+    let delta = handler.last_offset.get() - offset;
+    handler.last_offset.set(offset);
+    if delta != Vec2::ZERO {
+        if phase == MomentumPhase::Began {
+            handler.gliding.set(true);
+        }
+        handler.send(delta, phase);
+    }
+    S_OK
+}
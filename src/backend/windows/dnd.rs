@@ -0,0 +1,613 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drag and drop, with OLE.
+//!
+//! Each window registers a drop target, which sends the drag events to the window procedure
+//! as `DS_DRAG_EVENT` messages, so that they reach the handler the same way as everything else.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::ptr::{null, null_mut};
+use std::rc::Rc;
+
+use tracing::warn;
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, GUID, REFIID};
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, LPARAM, TRUE, UINT, ULONG};
+use winapi::shared::windef::{HBITMAP, HWND, POINT, POINTL, SIZE};
+use winapi::shared::winerror::{
+    DRAGDROP_S_CANCEL, DRAGDROP_S_DROP, DRAGDROP_S_USEDEFAULTCURSORS, E_NOINTERFACE, FAILED, S_OK,
+};
+use winapi::shared::wtypes::DVASPECT_CONTENT;
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::objidl::{IDataObject, STGMEDIUM_u, FORMATETC, STGMEDIUM, TYMED_HGLOBAL};
+use winapi::um::ole2::{RegisterDragDrop, RevokeDragDrop};
+use winapi::um::oleidl::{
+    IDropTarget, DROPEFFECT_COPY, DROPEFFECT_LINK, DROPEFFECT_MOVE, DROPEFFECT_NONE,
+};
+use winapi::um::shellapi::{DragQueryFileW, HDROP};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winbase::{GlobalFree, GlobalLock, GlobalSize, GlobalUnlock};
+use winapi::um::wingdi::DeleteObject;
+use winapi::um::winnt::{HRESULT, LPWSTR};
+use winapi::um::winuser::{
+    ScreenToClient, SendMessageW, CF_UNICODETEXT, MK_LBUTTON, MK_MBUTTON, MK_RBUTTON,
+};
+use winapi::{Interface, DEFINE_GUID, RIDL};
+use wio::com::ComPtr;
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::dnd::{DragData, DragImage, DropEffect};
+use crate::platform::windows::ThumbnailBitmap;
+
+use super::clipboard::{get_format_id, make_handle};
+use super::error::Error;
+use super::util::{FromWide, ToWide};
+use super::window::{create_iconic_bitmap, DS_DRAG_EVENT};
+
+// The parts of OLE and the shell that aren't exported by winapi.
+#[link(name = "ole32")]
+extern "system" {
+    fn DoDragDrop(
+        data: *mut IDataObject,
+        source: *mut IUnknown,
+        ok_effects: DWORD,
+        effect: *mut DWORD,
+    ) -> HRESULT;
+    fn ReleaseStgMedium(medium: *mut STGMEDIUM);
+}
+
+#[link(name = "shell32")]
+extern "system" {
+    fn SHCreateDataObject(
+        pidl_folder: *const c_void,
+        count: UINT,
+        pidls: *const c_void,
+        inner: *mut IDataObject,
+        riid: REFIID,
+        out: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+DEFINE_GUID! {CLSID_DragDropHelper,
+0x4657_278a, 0x411b, 0x11d2, 0x83, 0x9a, 0x00, 0xc0, 0x4f, 0xd9, 0x18, 0xd0}
+
+DEFINE_GUID! {IID_IDropSource,
+0x0000_0121, 0x0000, 0x0000, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46}
+
+#[repr(C)]
+pub struct SHDRAGIMAGE {
+    sizeDragImage: SIZE,
+    ptOffset: POINT,
+    hbmpDragImage: HBITMAP,
+    crColorKey: DWORD,
+}
+
+RIDL! {#[uuid(0xde5b_f786, 0x477a, 0x11d2, 0x83, 0x9d, 0x00, 0xc0, 0x4f, 0xd9, 0x18, 0xd0)]
+interface IDragSourceHelper(IDragSourceHelperVtbl): IUnknown(IUnknownVtbl) {
+    fn InitializeFromBitmap(
+        pshdi: *mut SHDRAGIMAGE,
+        pDataObject: *mut IDataObject,
+    ) -> HRESULT,
+    // InitializeFromWindow follows, but we don't use it.
+}}
+
+// These are our own, rather than winapi's, because winapi passes the points by reference.
+#[repr(C)]
+struct IDropTargetVtbl {
+    parent: IUnknownVtbl,
+    DragEnter: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        pDataObj: *mut IDataObject,
+        grfKeyState: DWORD,
+        pt: POINTL,
+        pdwEffect: *mut DWORD,
+    ) -> HRESULT,
+    DragOver: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        grfKeyState: DWORD,
+        pt: POINTL,
+        pdwEffect: *mut DWORD,
+    ) -> HRESULT,
+    DragLeave: unsafe extern "system" fn(This: *mut IUnknown) -> HRESULT,
+    Drop: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        pDataObj: *mut IDataObject,
+        grfKeyState: DWORD,
+        pt: POINTL,
+        pdwEffect: *mut DWORD,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDropSourceVtbl {
+    parent: IUnknownVtbl,
+    QueryContinueDrag: unsafe extern "system" fn(
+        This: *mut IUnknown,
+        fEscapePressed: BOOL,
+        grfKeyState: DWORD,
+    ) -> HRESULT,
+    GiveFeedback: unsafe extern "system" fn(This: *mut IUnknown, dwEffect: DWORD) -> HRESULT,
+}
+
+static DROP_TARGET_VTBL: IDropTargetVtbl = IDropTargetVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: query_interface::<DropTarget>,
+        AddRef: add_ref::<DropTarget>,
+        Release: release::<DropTarget>,
+    },
+    DragEnter: drag_enter,
+    DragOver: drag_over,
+    DragLeave: drag_leave,
+    Drop: drag_drop,
+};
+
+static DROP_SOURCE_VTBL: IDropSourceVtbl = IDropSourceVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: query_interface::<()>,
+        AddRef: add_ref::<()>,
+        Release: release::<()>,
+    },
+    QueryContinueDrag: query_continue_drag,
+    GiveFeedback: give_feedback,
+};
+
+/// A drag event, sent to the window procedure with `DS_DRAG_EVENT`.
+pub(super) struct DragMessage<'a> {
+    pub(super) kind: DragKind,
+    /// The position of the pointer, in client pixels.
+    pub(super) pos: POINT,
+    pub(super) data: &'a DragData,
+    /// What the handler would do with the drop.
+    pub(super) effect: DropEffect,
+}
+
+pub(super) enum DragKind {
+    Enter,
+    Over,
+    Leave,
+    Drop,
+}
+
+/// A COM object implemented here, with a single interface.
+#[repr(C)]
+pub(super) struct Object<T> {
+    vtbl: *const c_void,
+    refs: Cell<ULONG>,
+    iid: GUID,
+    inner: T,
+}
+
+impl<T> Object<T> {
+    /// Returns the new object, with one reference.
+    pub(super) fn create<V>(vtbl: &'static V, iid: GUID, inner: T) -> *mut IUnknown {
+        let object = Object {
+            vtbl: vtbl as *const V as *const c_void,
+            refs: Cell::new(1),
+            iid,
+            inner,
+        };
+        Box::into_raw(Box::new(object)) as *mut IUnknown
+    }
+
+    pub(super) unsafe fn get<'a>(this: *mut IUnknown) -> &'a T {
+        &(*(this as *const Object<T>)).inner
+    }
+}
+
+pub(super) unsafe extern "system" fn query_interface<T>(
+    this: *mut IUnknown,
+    riid: REFIID,
+    out: *mut *mut c_void,
+) -> HRESULT {
+    let object = &*(this as *const Object<T>);
+    if IsEqualGUID(&*riid, &IUnknown::uuidof()) || IsEqualGUID(&*riid, &object.iid) {
+        add_ref::<T>(this);
+        *out = this as *mut c_void;
+        S_OK
+    } else {
+        *out = null_mut();
+        E_NOINTERFACE
+    }
+}
+
+pub(super) unsafe extern "system" fn add_ref<T>(this: *mut IUnknown) -> ULONG {
+    let object = &*(this as *const Object<T>);
+    object.refs.set(object.refs.get() + 1);
+    object.refs.get()
+}
+
+pub(super) unsafe extern "system" fn release<T>(this: *mut IUnknown) -> ULONG {
+    let refs = {
+        let object = &*(this as *const Object<T>);
+        object.refs.set(object.refs.get() - 1);
+        object.refs.get()
+    };
+    if refs == 0 {
+        drop(Box::from_raw(this as *mut Object<T>));
+    }
+    refs
+}
+
+/// The drop target of a window.
+struct DropTarget {
+    hwnd: HWND,
+    /// The custom formats that the window reads from drags.
+    formats: Rc<RefCell<Vec<FormatId>>>,
+    /// The data of the drag over the window.
+    data: RefCell<DragData>,
+}
+
+impl DropTarget {
+    /// Sends a drag event to the window, and returns the resulting effect.
+    unsafe fn send(&self, kind: DragKind, pt: POINTL, allowed: DWORD) -> DWORD {
+        let mut pos = POINT { x: pt.x, y: pt.y };
+        ScreenToClient(self.hwnd, &mut pos);
+        let data = self.data.borrow();
+        let mut message = DragMessage {
+            kind,
+            pos,
+            data: &data,
+            effect: DropEffect::None,
+        };
+        SendMessageW(
+            self.hwnd,
+            DS_DRAG_EVENT,
+            0,
+            &mut message as *mut DragMessage as LPARAM,
+        );
+        let effect = match message.effect {
+            DropEffect::None => DROPEFFECT_NONE,
+            DropEffect::Copy => DROPEFFECT_COPY,
+            DropEffect::Move => DROPEFFECT_MOVE,
+            DropEffect::Link => DROPEFFECT_LINK,
+        };
+        effect & allowed
+    }
+}
+
+unsafe extern "system" fn drag_enter(
+    this: *mut IUnknown,
+    data: *mut IDataObject,
+    _keys: DWORD,
+    pt: POINTL,
+    effect: *mut DWORD,
+) -> HRESULT {
+    let target = Object::<DropTarget>::get(this);
+    *target.data.borrow_mut() = read(&*data, &target.formats.borrow());
+    *effect = target.send(DragKind::Enter, pt, *effect);
+    S_OK
+}
+
+unsafe extern "system" fn drag_over(
+    this: *mut IUnknown,
+    _keys: DWORD,
+    pt: POINTL,
+    effect: *mut DWORD,
+) -> HRESULT {
+    let target = Object::<DropTarget>::get(this);
+    *effect = target.send(DragKind::Over, pt, *effect);
+    S_OK
+}
+
+unsafe extern "system" fn drag_leave(this: *mut IUnknown) -> HRESULT {
+    let target = Object::<DropTarget>::get(this);
+    target.send(DragKind::Leave, POINTL { x: 0, y: 0 }, DROPEFFECT_NONE);
+    target.data.take();
+    S_OK
+}
+
+unsafe extern "system" fn drag_drop(
+    this: *mut IUnknown,
+    _data: *mut IDataObject,
+    _keys: DWORD,
+    pt: POINTL,
+    effect: *mut DWORD,
+) -> HRESULT {
+    let target = Object::<DropTarget>::get(this);
+    *effect = target.send(DragKind::Drop, pt, *effect);
+    target.data.take();
+    S_OK
+}
+
+unsafe extern "system" fn query_continue_drag(
+    _this: *mut IUnknown,
+    escape_pressed: BOOL,
+    keys: DWORD,
+) -> HRESULT {
+    if escape_pressed != FALSE {
+        DRAGDROP_S_CANCEL
+    } else if keys & (MK_LBUTTON | MK_RBUTTON | MK_MBUTTON) as DWORD == 0 {
+        DRAGDROP_S_DROP
+    } else {
+        S_OK
+    }
+}
+
+unsafe extern "system" fn give_feedback(_this: *mut IUnknown, _effect: DWORD) -> HRESULT {
+    DRAGDROP_S_USEDEFAULTCURSORS
+}
+
+/// Makes `hwnd` a drop target.
+///
+/// `formats` are the custom formats to read from drags, in addition to text and files.
+pub(super) fn register(hwnd: HWND, formats: Rc<RefCell<Vec<FormatId>>>) {
+    let target = DropTarget {
+        hwnd,
+        formats,
+        data: RefCell::new(DragData::default()),
+    };
+    unsafe {
+        let target = Object::create(&DROP_TARGET_VTBL, IDropTarget::uuidof(), target);
+        let hr = RegisterDragDrop(hwnd, target as *mut IDropTarget);
+        if FAILED(hr) {
+            warn!("failed to register the drop target: {}", Error::Hr(hr));
+        }
+        // If it was registered, the system has its own reference now.
+        (*target).Release();
+    }
+}
+
+/// Stops `hwnd` from being a drop target.
+pub(super) fn revoke(hwnd: HWND) {
+    unsafe {
+        RevokeDragDrop(hwnd);
+    }
+}
+
+/// Drags `data` until it is dropped or the drag is cancelled.
+///
+/// This runs a modal loop, so it must not be called while the handler is borrowed.
+pub(super) fn start_drag(data: &DragData, image: Option<&DragImage>) {
+    unsafe {
+        let mut object: *mut IDataObject = null_mut();
+        let hr = SHCreateDataObject(
+            null(),
+            0,
+            null(),
+            null_mut(),
+            &IDataObject::uuidof(),
+            &mut object as *mut _ as *mut *mut c_void,
+        );
+        if FAILED(hr) {
+            warn!("failed to create the drag data: {}", Error::Hr(hr));
+            return;
+        }
+        let object = ComPtr::from_raw(object);
+        for format in formats(data) {
+            let id = match get_format_id(format.identifier) {
+                Some(id) => id,
+                None => continue,
+            };
+            let handle = make_handle(&format);
+            let medium = STGMEDIUM {
+                tymed: TYMED_HGLOBAL,
+                u: handle as *mut STGMEDIUM_u,
+                pUnkForRelease: null_mut(),
+            };
+            // winapi has the wrong type for the medium.
+            let medium = &medium as *const STGMEDIUM as *const FORMATETC;
+            if FAILED(object.SetData(&format_etc(id), medium, TRUE)) {
+                warn!("failed to set drag data for {}", format.identifier);
+                GlobalFree(handle);
+            }
+        }
+        if let Some(image) = image {
+            set_drag_image(&object, image);
+        }
+
+        let source = Object::create(&DROP_SOURCE_VTBL, IID_IDropSource, ());
+        let mut effect = DROPEFFECT_NONE;
+        let hr = DoDragDrop(object.as_raw(), source, DROPEFFECT_COPY, &mut effect);
+        (*source).Release();
+        if FAILED(hr) {
+            warn!("drag failed: {}", Error::Hr(hr));
+        }
+    }
+}
+
+unsafe fn set_drag_image(object: &ComPtr<IDataObject>, image: &DragImage) {
+    let mut helper: *mut IDragSourceHelper = null_mut();
+    let hr = CoCreateInstance(
+        &CLSID_DragDropHelper,
+        null_mut(),
+        CLSCTX_INPROC_SERVER,
+        &IDragSourceHelper::uuidof(),
+        &mut helper as *mut _ as *mut *mut c_void,
+    );
+    if FAILED(hr) {
+        warn!("failed to create the drag image helper: {}", Error::Hr(hr));
+        return;
+    }
+    let helper = ComPtr::from_raw(helper);
+    let bitmap = ThumbnailBitmap {
+        width: image.width,
+        height: image.height,
+        pixels: image.pixels.clone(),
+    };
+    let hbitmap = create_iconic_bitmap(&bitmap, None);
+    if hbitmap.is_null() {
+        return;
+    }
+    let mut drag_image = SHDRAGIMAGE {
+        sizeDragImage: SIZE {
+            cx: image.width as i32,
+            cy: image.height as i32,
+        },
+        ptOffset: POINT {
+            x: image.hotspot.0 as i32,
+            y: image.hotspot.1 as i32,
+        },
+        hbmpDragImage: hbitmap,
+        // No color key, we have alpha.
+        crColorKey: 0xFFFF_FFFF,
+    };
+    // On success, the helper owns the bitmap.
+    if FAILED(helper.InitializeFromBitmap(&mut drag_image, object.as_raw())) {
+        DeleteObject(hbitmap as *mut c_void);
+    }
+}
+
+fn format_etc(format: UINT) -> FORMATETC {
+    FORMATETC {
+        cfFormat: format as u16,
+        ptd: null(),
+        dwAspect: DVASPECT_CONTENT,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL,
+    }
+}
+
+/// Calls `f` with the global memory that `object` has in `format`, if any.
+unsafe fn with_global<T>(
+    object: &IDataObject,
+    format: UINT,
+    f: impl FnOnce(*mut c_void) -> Option<T>,
+) -> Option<T> {
+    let mut medium: STGMEDIUM = mem::zeroed();
+    if object.GetData(&format_etc(format), &mut medium) != S_OK {
+        return None;
+    }
+    // winapi declares the union as a pointer, but it's the handle itself.
+    let result = f(medium.u as *mut c_void);
+    ReleaseStgMedium(&mut medium);
+    result
+}
+
+/// Reads the text, files and `formats` of a drag.
+unsafe fn read(object: &IDataObject, formats: &[FormatId]) -> DragData {
+    let text = with_global(object, CF_UNICODETEXT, |handle| {
+        let text = (GlobalLock(handle) as LPWSTR).to_string();
+        GlobalUnlock(handle);
+        text
+    });
+    let uris = with_global(object, 15 /* CF_HDROP */, |handle| {
+        let drop = handle as HDROP;
+        let count = DragQueryFileW(drop, u32::MAX, null_mut(), 0);
+        let uris = (0..count)
+            .filter_map(|i| {
+                let len = DragQueryFileW(drop, i, null_mut(), 0) as usize;
+                let mut path = vec![0u16; len + 1];
+                DragQueryFileW(drop, i, path.as_mut_ptr(), path.len() as UINT);
+                path[..len].to_string().map(|path| path_to_uri(&path))
+            })
+            .collect();
+        Some(uris)
+    })
+    .unwrap_or_default();
+    let formats = formats
+        .iter()
+        .filter_map(|format| {
+            let data = with_global(object, get_format_id(format)?, |handle| {
+                let size = GlobalSize(handle);
+                let locked = GlobalLock(handle) as *const u8;
+                let data = std::slice::from_raw_parts(locked, size).to_vec();
+                GlobalUnlock(handle);
+                Some(data)
+            })?;
+            Some(ClipboardFormat::new(format, data))
+        })
+        .collect();
+    DragData {
+        text,
+        uris,
+        formats,
+    }
+}
+
+/// The clipboard formats that a drag of `data` offers.
+fn formats(data: &DragData) -> Vec<ClipboardFormat> {
+    let mut formats = Vec::new();
+    if let Some(text) = &data.text {
+        formats.push(ClipboardFormat::from(text.as_str()));
+    }
+    let paths: Vec<String> = data
+        .uris
+        .iter()
+        .filter_map(|uri| uri_to_path(uri))
+        .collect();
+    if !paths.is_empty() {
+        // A DROPFILES header, saying that the paths start after it and are wide strings,
+        // then each path, then an empty one.
+        let mut bytes = Vec::new();
+        for field in [20, 0, 0, 0, 1u32] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        for path in &paths {
+            for unit in path.to_wide() {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&[0, 0]);
+        formats.push(ClipboardFormat::new("CF_HDROP", bytes));
+    }
+    formats.extend(data.formats.iter().cloned());
+    formats
+}
+
+/// Converts a path, like `C:\a b` or `\\server\share`, to a `file:` URI.
+fn path_to_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = match path.strip_prefix("//") {
+        Some(unc) => format!("file://{}", unc),
+        None => format!("file:///{}", path),
+    };
+    // Only the characters that can't be in a URI need escaping.
+    let start = "file://".len();
+    let escaped: String = uri[start..]
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b':' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    uri.truncate(start);
+    uri + &escaped
+}
+
+/// Converts a `file:` URI to a path, or returns `None` for other URIs.
+fn uri_to_path(uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix("file://")?;
+    let path = match rest.strip_prefix('/') {
+        Some(local) => local.to_string(),
+        // A host means a share.
+        None => format!("//{}", rest),
+    };
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&decoded).replace('/', "\\"))
+}
@@ -20,29 +20,35 @@ use std::convert::TryInto;
 use std::mem;
 use std::ops::RangeInclusive;
 
-use crate::keyboard::{Code, KbKey, KeyEvent, KeyState, Location, Modifiers};
+use crate::keyboard::{Code, KbKey, KeyEvent, KeyState, KeyboardLayout, Location, Modifiers};
 
-use winapi::shared::minwindef::{HKL, INT, LPARAM, UINT, WPARAM};
-use winapi::shared::ntdef::SHORT;
+use super::util::FromWide;
+
+use winapi::ctypes::c_int;
+use winapi::shared::minwindef::{BOOL, DWORD, HKL, INT, LPARAM, UINT, WPARAM};
+use winapi::shared::ntdef::{LANGID, LONG, SHORT};
 use winapi::shared::windef::HWND;
+use winapi::um::imm::{ImmGetContext, ImmReleaseContext, HIMC};
+use winapi::um::winnls::{GetLocaleInfoW, LCIDToLocaleName};
+use winapi::um::winnt::{LOCALE_NAME_MAX_LENGTH, MAKELCID, SORT_DEFAULT};
 use winapi::um::winuser::{
-    GetKeyState, GetKeyboardLayout, MapVirtualKeyExW, PeekMessageW, ToUnicodeEx, VkKeyScanW,
-    MAPVK_VK_TO_CHAR, MAPVK_VSC_TO_VK_EX, PM_NOREMOVE, VK_ACCEPT, VK_ADD, VK_APPS, VK_ATTN,
-    VK_BACK, VK_BROWSER_BACK, VK_BROWSER_FAVORITES, VK_BROWSER_FORWARD, VK_BROWSER_HOME,
-    VK_BROWSER_REFRESH, VK_BROWSER_SEARCH, VK_BROWSER_STOP, VK_CANCEL, VK_CAPITAL, VK_CLEAR,
-    VK_CONTROL, VK_CONVERT, VK_CRSEL, VK_DECIMAL, VK_DELETE, VK_DIVIDE, VK_DOWN, VK_END, VK_EREOF,
-    VK_ESCAPE, VK_EXECUTE, VK_EXSEL, VK_F1, VK_F10, VK_F11, VK_F12, VK_F13, VK_F14, VK_F15, VK_F16,
-    VK_F17, VK_F18, VK_F19, VK_F2, VK_F20, VK_F21, VK_F22, VK_F23, VK_F24, VK_F3, VK_F4, VK_F5,
-    VK_F6, VK_F7, VK_F8, VK_F9, VK_FINAL, VK_HELP, VK_HOME, VK_INSERT, VK_JUNJA, VK_KANA, VK_KANJI,
-    VK_LAUNCH_APP1, VK_LAUNCH_APP2, VK_LAUNCH_MAIL, VK_LAUNCH_MEDIA_SELECT, VK_LCONTROL, VK_LEFT,
-    VK_LMENU, VK_LSHIFT, VK_LWIN, VK_MEDIA_NEXT_TRACK, VK_MEDIA_PLAY_PAUSE, VK_MEDIA_PREV_TRACK,
-    VK_MEDIA_STOP, VK_MENU, VK_MODECHANGE, VK_MULTIPLY, VK_NEXT, VK_NONCONVERT, VK_NUMLOCK,
-    VK_NUMPAD0, VK_NUMPAD1, VK_NUMPAD2, VK_NUMPAD3, VK_NUMPAD4, VK_NUMPAD5, VK_NUMPAD6, VK_NUMPAD7,
-    VK_NUMPAD8, VK_NUMPAD9, VK_OEM_ATTN, VK_OEM_CLEAR, VK_PAUSE, VK_PLAY, VK_PRINT, VK_PRIOR,
-    VK_PROCESSKEY, VK_RCONTROL, VK_RETURN, VK_RIGHT, VK_RMENU, VK_RSHIFT, VK_RWIN, VK_SCROLL,
-    VK_SELECT, VK_SHIFT, VK_SLEEP, VK_SNAPSHOT, VK_SUBTRACT, VK_TAB, VK_UP, VK_VOLUME_DOWN,
-    VK_VOLUME_MUTE, VK_VOLUME_UP, VK_ZOOM, WM_CHAR, WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP,
-    WM_SYSCHAR, WM_SYSKEYDOWN, WM_SYSKEYUP,
+    GetKeyState, GetKeyboardLayout, GetKeyboardLayoutList, MapVirtualKeyExW, PeekMessageW,
+    ToUnicodeEx, VkKeyScanW, MAPVK_VK_TO_CHAR, MAPVK_VSC_TO_VK_EX, PM_NOREMOVE, VK_ACCEPT, VK_ADD,
+    VK_APPS, VK_ATTN, VK_BACK, VK_BROWSER_BACK, VK_BROWSER_FAVORITES, VK_BROWSER_FORWARD,
+    VK_BROWSER_HOME, VK_BROWSER_REFRESH, VK_BROWSER_SEARCH, VK_BROWSER_STOP, VK_CANCEL, VK_CAPITAL,
+    VK_CLEAR, VK_CONTROL, VK_CONVERT, VK_CRSEL, VK_DECIMAL, VK_DELETE, VK_DIVIDE, VK_DOWN, VK_END,
+    VK_EREOF, VK_ESCAPE, VK_EXECUTE, VK_EXSEL, VK_F1, VK_F10, VK_F11, VK_F12, VK_F13, VK_F14,
+    VK_F15, VK_F16, VK_F17, VK_F18, VK_F19, VK_F2, VK_F20, VK_F21, VK_F22, VK_F23, VK_F24, VK_F3,
+    VK_F4, VK_F5, VK_F6, VK_F7, VK_F8, VK_F9, VK_FINAL, VK_HELP, VK_HOME, VK_INSERT, VK_JUNJA,
+    VK_KANA, VK_KANJI, VK_LAUNCH_APP1, VK_LAUNCH_APP2, VK_LAUNCH_MAIL, VK_LAUNCH_MEDIA_SELECT,
+    VK_LCONTROL, VK_LEFT, VK_LMENU, VK_LSHIFT, VK_LWIN, VK_MEDIA_NEXT_TRACK, VK_MEDIA_PLAY_PAUSE,
+    VK_MEDIA_PREV_TRACK, VK_MEDIA_STOP, VK_MENU, VK_MODECHANGE, VK_MULTIPLY, VK_NEXT,
+    VK_NONCONVERT, VK_NUMLOCK, VK_NUMPAD0, VK_NUMPAD1, VK_NUMPAD2, VK_NUMPAD3, VK_NUMPAD4,
+    VK_NUMPAD5, VK_NUMPAD6, VK_NUMPAD7, VK_NUMPAD8, VK_NUMPAD9, VK_OEM_ATTN, VK_OEM_CLEAR,
+    VK_PAUSE, VK_PLAY, VK_PRINT, VK_PRIOR, VK_PROCESSKEY, VK_RCONTROL, VK_RETURN, VK_RIGHT,
+    VK_RMENU, VK_RSHIFT, VK_RWIN, VK_SCROLL, VK_SELECT, VK_SHIFT, VK_SLEEP, VK_SNAPSHOT,
+    VK_SUBTRACT, VK_TAB, VK_UP, VK_VOLUME_DOWN, VK_VOLUME_MUTE, VK_VOLUME_UP, VK_ZOOM, WM_CHAR,
+    WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP, WM_SYSCHAR, WM_SYSKEYDOWN, WM_SYSKEYUP,
 };
 
 const VK_ABNT_C2: INT = 0xc2;
@@ -104,6 +110,120 @@ pub(super) unsafe fn is_last_message(hwnd: HWND, msg: UINT, lparam: LPARAM) -> b
     avail == 0 || msg.lParam & SCAN_MASK != lparam & SCAN_MASK
 }
 
+/// Describe the keyboard layout identified by `hkl`.
+pub(super) fn layout_from_hkl(hkl: HKL) -> KeyboardLayout {
+    // Not exported by winapi.
+    const LOCALE_SLOCALIZEDDISPLAYNAME: DWORD = 0x2;
+
+    // The low word of an HKL is the language identifier of the layout.
+    let lcid = MAKELCID(hkl as usize as LANGID, SORT_DEFAULT);
+    let mut buf = [0u16; LOCALE_NAME_MAX_LENGTH];
+    let len = unsafe { LCIDToLocaleName(lcid, buf.as_mut_ptr(), buf.len() as c_int, 0) };
+    let language = if len > 0 {
+        buf.get(..len as usize - 1).and_then(FromWide::to_string)
+    } else {
+        None
+    };
+    let mut buf = [0u16; 128];
+    let len = unsafe {
+        GetLocaleInfoW(
+            lcid,
+            LOCALE_SLOCALIZEDDISPLAYNAME,
+            buf.as_mut_ptr(),
+            buf.len() as c_int,
+        )
+    };
+    let name = if len > 0 {
+        buf.get(..len as usize - 1).and_then(FromWide::to_string)
+    } else {
+        None
+    };
+    KeyboardLayout {
+        id: format!("{:08X}", hkl as usize as u32),
+        name: name.or_else(|| language.clone()).unwrap_or_default(),
+        language,
+    }
+}
+
+/// The keyboard layouts in the user's input language list.
+pub(super) fn keyboard_layouts() -> Vec<KeyboardLayout> {
+    unsafe {
+        let count = GetKeyboardLayoutList(0, std::ptr::null_mut());
+        let mut hkls: Vec<HKL> = vec![std::ptr::null_mut(); count.max(0) as usize];
+        let count = GetKeyboardLayoutList(hkls.len() as c_int, hkls.as_mut_ptr());
+        hkls.truncate(count.max(0) as usize);
+        hkls.into_iter().map(layout_from_hkl).collect()
+    }
+}
+
+// The parts of the IME API that aren't exported by winapi.
+#[link(name = "imm32")]
+extern "system" {
+    fn ImmGetCompositionStringW(himc: HIMC, index: DWORD, buf: *mut u16, len: DWORD) -> LONG;
+    fn ImmNotifyIME(himc: HIMC, action: DWORD, index: DWORD, value: DWORD) -> BOOL;
+    fn ImmAssociateContextEx(hwnd: HWND, himc: HIMC, flags: DWORD) -> BOOL;
+}
+const NI_COMPOSITIONSTR: DWORD = 0x0015;
+const CPS_CANCEL: DWORD = 0x0004;
+const IACE_DEFAULT: DWORD = 0x0010;
+pub(super) const GCS_COMPSTR: DWORD = 0x0008;
+const GCS_CURSORPOS: DWORD = 0x0080;
+pub(super) const GCS_RESULTSTR: DWORD = 0x0800;
+
+/// Lets the IME compose text in `hwnd`, or stops it so that keys arrive as they are.
+pub(super) fn set_ime_allowed(hwnd: HWND, allowed: bool) {
+    unsafe {
+        if allowed {
+            // Gives the window back the input context that Windows made for it.
+            ImmAssociateContextEx(hwnd, std::ptr::null_mut(), IACE_DEFAULT);
+            return;
+        }
+        // Cancelling first sends the messages that end the composition in progress.
+        let himc = ImmGetContext(hwnd);
+        if !himc.is_null() {
+            ImmNotifyIME(himc, NI_COMPOSITIONSTR, CPS_CANCEL, 0);
+            ImmReleaseContext(hwnd, himc);
+        }
+        // Without an input context, there's nothing for the IME to compose in.
+        ImmAssociateContextEx(hwnd, std::ptr::null_mut(), 0);
+    }
+}
+
+/// Read one of the IME's composition strings, as announced by `WM_IME_COMPOSITION`.
+///
+/// `index` should be `GCS_COMPSTR` or `GCS_RESULTSTR`. For `GCS_COMPSTR`, this also
+/// returns the position of the cursor, as a byte offset into the string.
+pub(super) fn composition_string(hwnd: HWND, index: DWORD) -> Option<(String, usize)> {
+    unsafe {
+        let himc = ImmGetContext(hwnd);
+        if himc.is_null() {
+            return None;
+        }
+        // The returned sizes are in bytes, not characters.
+        let len = ImmGetCompositionStringW(himc, index, std::ptr::null_mut(), 0);
+        let mut buf = vec![0u16; len.max(0) as usize / 2];
+        let len = ImmGetCompositionStringW(himc, index, buf.as_mut_ptr(), len.max(0) as DWORD);
+        let cursor = ImmGetCompositionStringW(himc, GCS_CURSORPOS, std::ptr::null_mut(), 0);
+        ImmReleaseContext(hwnd, himc);
+        if len < 0 {
+            return None;
+        }
+        let text = buf[..len as usize / 2].to_string()?;
+        // The cursor position is in UTF-16 code units.
+        let mut utf16 = 0;
+        let cursor = text
+            .char_indices()
+            .find(|(_, c)| {
+                let done = utf16 >= cursor.max(0) as usize;
+                utf16 += c.len_utf16();
+                done
+            })
+            .map(|(idx, _)| idx)
+            .unwrap_or(text.len());
+        Some((text, cursor))
+    }
+}
+
 const MODIFIER_MAP: &[(INT, Modifiers, SHORT)] = &[
     (VK_MENU, Modifiers::ALT, 0x80),
     (VK_CAPITAL, Modifiers::CAPS_LOCK, 0x1),
@@ -613,6 +733,7 @@ impl KeyboardState {
                         is_composing: false,
                         location,
                         repeat,
+                        xkb_keysym: None,
                     };
                     Some(event)
                 } else {
@@ -638,6 +759,7 @@ impl KeyboardState {
                     is_composing: false,
                     location,
                     repeat,
+                    xkb_keysym: None,
                 };
                 Some(event)
             }
@@ -672,6 +794,7 @@ impl KeyboardState {
                         is_composing: false,
                         location,
                         repeat,
+                        xkb_keysym: None,
                     };
                     Some(event)
                 } else {
@@ -26,6 +26,7 @@ use winapi::um::winuser::*;
 use super::util::ToWide;
 use crate::hotkey::HotKey;
 use crate::keyboard::{KbKey, Modifiers};
+use crate::strings::{self, BuiltinString};
 
 /// A menu object, which can be either a top-level menubar or a
 /// submenu.
@@ -191,36 +192,42 @@ fn convert_hotkey(id: u32, key: &HotKey) -> Option<ACCEL> {
 /// Format the hotkey in a Windows-native way.
 fn format_hotkey(key: &HotKey, s: &mut String) {
     let key_mods: Modifiers = key.mods.into();
-    if key_mods.ctrl() {
-        s.push_str("Ctrl+");
-    }
-    if key_mods.shift() {
-        s.push_str("Shift+");
-    }
-    if key_mods.alt() {
-        s.push_str("Alt+");
-    }
-    if key_mods.meta() {
-        s.push_str("Windows+");
+    for (pressed, name) in [
+        (key_mods.ctrl(), BuiltinString::KeyCtrl),
+        (key_mods.shift(), BuiltinString::KeyShift),
+        (key_mods.alt(), BuiltinString::KeyAlt),
+        (key_mods.meta(), BuiltinString::KeyWindows),
+    ] {
+        if pressed {
+            s.push_str(&strings::get(name));
+            s.push('+');
+        }
     }
-    match &key.key {
+    let name = match &key.key {
         KbKey::Character(c) => match c.as_str() {
-            "+" => s.push_str("Plus"),
-            "-" => s.push_str("Minus"),
-            " " => s.push_str("Space"),
-            _ => s.extend(c.chars().flat_map(|c| c.to_uppercase())),
+            "+" => BuiltinString::KeyPlus,
+            "-" => BuiltinString::KeyMinus,
+            " " => BuiltinString::KeySpace,
+            _ => {
+                s.extend(c.chars().flat_map(|c| c.to_uppercase()));
+                return;
+            }
         },
-        KbKey::Escape => s.push_str("Esc"),
-        KbKey::Delete => s.push_str("Del"),
-        KbKey::Insert => s.push_str("Ins"),
-        KbKey::PageUp => s.push_str("PgUp"),
-        KbKey::PageDown => s.push_str("PgDn"),
-        // These names match LibreOffice.
-        KbKey::ArrowLeft => s.push_str("Left"),
-        KbKey::ArrowRight => s.push_str("Right"),
-        KbKey::ArrowUp => s.push_str("Up"),
-        KbKey::ArrowDown => s.push_str("Down"),
-        _ => write!(s, "{}", key.key)
-            .unwrap_or_else(|err| tracing::warn!("Failed to convert hotkey to string: {}", err)),
-    }
+        KbKey::Escape => BuiltinString::KeyEscape,
+        KbKey::Delete => BuiltinString::KeyDelete,
+        KbKey::Insert => BuiltinString::KeyInsert,
+        KbKey::PageUp => BuiltinString::KeyPageUp,
+        KbKey::PageDown => BuiltinString::KeyPageDown,
+        KbKey::ArrowLeft => BuiltinString::KeyLeft,
+        KbKey::ArrowRight => BuiltinString::KeyRight,
+        KbKey::ArrowUp => BuiltinString::KeyUp,
+        KbKey::ArrowDown => BuiltinString::KeyDown,
+        _ => {
+            if let Err(err) = write!(s, "{}", key.key) {
+                tracing::warn!("Failed to convert hotkey to string: {}", err);
+            }
+            return;
+        }
+    };
+    s.push_str(&strings::get(name));
 }
@@ -19,6 +19,8 @@ pub mod application;
 pub mod clipboard;
 // pub mod dcomp;
 pub mod dialog;
+mod direct_manipulation;
+mod dnd;
 pub mod error;
 mod keyboard;
 pub mod menu;
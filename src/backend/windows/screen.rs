@@ -15,6 +15,8 @@
 //! Windows Monitors and Screen information.
 
 use super::error::Error;
+use super::util::{FromWide, ToWide, OPTIONAL_FUNCTIONS};
+use super::window::SCALE_TARGET_DPI;
 use std::mem::size_of;
 use std::ptr::null_mut;
 use tracing::warn;
@@ -22,10 +24,29 @@ use winapi::shared::minwindef::*;
 use winapi::shared::windef::*;
 use winapi::shared::winerror::*;
 use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::shellscalingapi::MDT_EFFECTIVE_DPI;
+use winapi::um::wingdi::{GetBValue, GetGValue, GetRValue, DEVMODEW, DISPLAY_DEVICEW};
+use winapi::um::winreg::{
+    RegGetValueW, HKEY_CURRENT_USER, LSTATUS, RRF_RT_REG_BINARY, RRF_RT_REG_DWORD,
+};
 use winapi::um::winuser::*;
 
+use crate::colors::{HighContrastColors, SystemColor};
 use crate::kurbo::Rect;
-use crate::screen::Monitor;
+use crate::scale::Scale;
+use crate::screen::{ColorShift, Monitor};
+use crate::window::ScrollbarMetrics;
+
+/// The registry key where Windows stores the "Text size" accessibility setting.
+const ACCESSIBILITY_KEY: &str = "Software\\Microsoft\\Accessibility";
+
+/// The registry key where Windows 11 stores the "Always show scrollbars" setting.
+const CONTROL_PANEL_ACCESSIBILITY_KEY: &str = "Control Panel\\Accessibility";
+
+/// The registry key where Windows stores the current state of "Night light".
+///
+/// This isn't documented, but its layout has been stable since Windows 10 1703.
+const NIGHT_LIGHT_STATE_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\CloudStore\\Store\\DefaultAccount\\Current\\default$windows.data.bluelightreduction.bluelightreductionstate\\windows.data.bluelightreduction.bluelightreductionstate";
 
 unsafe extern "system" fn monitorenumproc(
     hmonitor: HMONITOR,
@@ -33,19 +54,13 @@ unsafe extern "system" fn monitorenumproc(
     _lprect: LPRECT,
     _lparam: LPARAM,
 ) -> BOOL {
-    let rect = RECT {
-        left: 0,
-        top: 0,
-        right: 0,
-        bottom: 0,
-    };
-    let mut info = MONITORINFO {
-        cbSize: size_of::<MONITORINFO>() as u32,
-        rcMonitor: rect,
-        rcWork: rect,
-        dwFlags: 0,
-    };
-    if GetMonitorInfoW(hmonitor, &mut info) == 0 {
+    let mut info: MONITORINFOEXW = std::mem::zeroed();
+    info.cbSize = size_of::<MONITORINFOEXW>() as u32;
+    if GetMonitorInfoW(
+        hmonitor,
+        &mut info as *mut MONITORINFOEXW as *mut MONITORINFO,
+    ) == 0
+    {
         warn!(
             "failed to get Monitor Info: {}",
             Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
@@ -64,11 +79,57 @@ unsafe extern "system" fn monitorenumproc(
         info.rcWork.right as f64,
         info.rcWork.bottom as f64,
     );
+    let monitor = Monitor::new(primary, rect, work_rect)
+        .with_scale(monitor_scale(hmonitor))
+        .with_refresh_rate(device_refresh_rate(&info.szDevice))
+        .with_name(device_name(&info.szDevice));
     let monitors = _lparam as *mut Vec<Monitor>;
-    (*monitors).push(Monitor::new(primary, rect, work_rect));
+    (*monitors).push(monitor);
     TRUE
 }
 
+/// Returns the scale factor of a monitor, or 1.0 before Windows 8.1.
+unsafe fn monitor_scale(hmonitor: HMONITOR) -> f64 {
+    match OPTIONAL_FUNCTIONS.GetDpiForMonitor {
+        Some(func) => {
+            let mut dpi_x = 0;
+            let mut dpi_y = 0;
+            func(hmonitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y);
+            match dpi_x {
+                0 => 1.0,
+                dpi => dpi as f64 / SCALE_TARGET_DPI,
+            }
+        }
+        None => 1.0,
+    }
+}
+
+/// Returns the refresh rate of a display device, such as `\\.\DISPLAY1`, if it is known.
+unsafe fn device_refresh_rate(device: &[u16]) -> Option<f64> {
+    let mut mode: DEVMODEW = std::mem::zeroed();
+    mode.dmSize = size_of::<DEVMODEW>() as u16;
+    if EnumDisplaySettingsW(device.as_ptr(), ENUM_CURRENT_SETTINGS, &mut mode) == 0 {
+        return None;
+    }
+    // 0 and 1 stand for the hardware's default rate.
+    match mode.dmDisplayFrequency {
+        0 | 1 => None,
+        hz => Some(hz as f64),
+    }
+}
+
+/// Returns the name of the monitor attached to a display device, such as "Generic PnP Monitor".
+unsafe fn device_name(device: &[u16]) -> Option<String> {
+    let mut display: DISPLAY_DEVICEW = std::mem::zeroed();
+    display.cb = size_of::<DISPLAY_DEVICEW>() as u32;
+    if EnumDisplayDevicesW(device.as_ptr(), 0, &mut display, 0) == 0 {
+        return None;
+    }
+    let name = &display.DeviceString;
+    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    FromWide::to_string(&name[..len]).filter(|name| !name.is_empty())
+}
+
 pub(crate) fn get_monitors() -> Vec<Monitor> {
     unsafe {
         let monitors = Vec::<Monitor>::new();
@@ -82,3 +143,207 @@ pub(crate) fn get_monitors() -> Vec<Monitor> {
         monitors
     }
 }
+
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    let key = NIGHT_LIGHT_STATE_KEY.to_wide();
+    let value = "Data".to_wide();
+    let mut data = [0u8; 256];
+    let mut size = data.len() as DWORD;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_BINARY,
+            null_mut(),
+            data.as_mut_ptr() as *mut _,
+            &mut size,
+        )
+    };
+    if status != ERROR_SUCCESS as LSTATUS {
+        // The key doesn't exist on versions of Windows without night light.
+        return None;
+    }
+    let active = match night_light_active(&data[..size as usize]) {
+        Some(active) => active,
+        None => {
+            warn!("unrecognized night light state in the registry");
+            return None;
+        }
+    };
+    // The strength lives in a separate settings blob whose layout changes between releases.
+    Some(ColorShift::new(active, None))
+}
+
+/// Reads whether night light is on from its CloudStore state blob.
+///
+/// The format isn't documented. The blob starts with the "CB" CloudStore header, followed by
+/// the time of the last change. The 19th byte is the length of the rest of the state: 0x15
+/// while night light is on, which adds a field, and 0x13 while it is off. Anything else is a
+/// layout we don't know, so we don't guess.
+fn night_light_active(data: &[u8]) -> Option<bool> {
+    if !data.starts_with(&[0x43, 0x42, 0x01, 0x00]) {
+        return None;
+    }
+    match data.get(18) {
+        Some(0x15) => Some(true),
+        Some(0x13) => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the "Text size" accessibility setting, as a multiple of the default size.
+pub(crate) fn text_scale_factor() -> f64 {
+    let key = ACCESSIBILITY_KEY.to_wide();
+    let value = "TextScaleFactor".to_wide();
+    let mut percent: DWORD = 0;
+    let mut size = std::mem::size_of::<DWORD>() as DWORD;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            null_mut(),
+            &mut percent as *mut DWORD as *mut _,
+            &mut size,
+        )
+    };
+    // The value only exists once the user has moved the slider.
+    if status != ERROR_SUCCESS as LSTATUS || percent == 0 {
+        return 1.0;
+    }
+    percent as f64 / 100.0
+}
+
+/// Returns the size of scrollbars at `scale`, in display points, and whether the user lets
+/// them hide while not scrolling.
+pub(crate) fn scrollbar_metrics(scale: Scale) -> ScrollbarMetrics {
+    let metric = |index| {
+        let px = unsafe {
+            match OPTIONAL_FUNCTIONS.GetSystemMetricsForDpi {
+                Some(func) => func(index, (scale.x() * SCALE_TARGET_DPI) as UINT),
+                // Before Windows 10 this is at the system DPI, which is usually the same.
+                None => GetSystemMetrics(index),
+            }
+        };
+        px as f64 / scale.x()
+    };
+    let key = CONTROL_PANEL_ACCESSIBILITY_KEY.to_wide();
+    let value = "DynamicScrollbars".to_wide();
+    let mut dynamic: DWORD = 0;
+    let mut size = std::mem::size_of::<DWORD>() as DWORD;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            null_mut(),
+            &mut dynamic as *mut DWORD as *mut _,
+            &mut size,
+        )
+    };
+    ScrollbarMetrics {
+        width: metric(SM_CXVSCROLL),
+        min_thumb_length: metric(SM_CYVTHUMB),
+        // The setting only exists on Windows 11, where it is on unless "Always show
+        // scrollbars" is.
+        overlay: status == ERROR_SUCCESS as LSTATUS && dynamic != 0,
+    }
+}
+
+/// Returns the palette of the high-contrast theme, if one is active.
+pub(crate) fn high_contrast_colors() -> Option<HighContrastColors> {
+    let mut hc = HIGHCONTRASTW {
+        cbSize: size_of::<HIGHCONTRASTW>() as UINT,
+        dwFlags: 0,
+        lpszDefaultScheme: null_mut(),
+    };
+    let ok = unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            hc.cbSize,
+            &mut hc as *mut HIGHCONTRASTW as *mut _,
+            0,
+        )
+    };
+    if ok == FALSE || hc.dwFlags & HCF_HIGHCONTRASTON == 0 {
+        return None;
+    }
+    // While a high-contrast theme is on, the system colors are its palette.
+    let color = |index| {
+        let rgb = unsafe { GetSysColor(index) };
+        SystemColor::rgb(GetRValue(rgb), GetGValue(rgb), GetBValue(rgb))
+    };
+    Some(HighContrastColors {
+        window: color(COLOR_WINDOW),
+        window_text: color(COLOR_WINDOWTEXT),
+        highlight: color(COLOR_HIGHLIGHT),
+        highlight_text: color(COLOR_HIGHLIGHTTEXT),
+        button: color(COLOR_BTNFACE),
+        button_text: color(COLOR_BTNTEXT),
+        disabled_text: color(COLOR_GRAYTEXT),
+        hyperlink: color(COLOR_HOTLIGHT),
+    })
+}
+
+/// Returns how many lines a notch of the mouse wheel scrolls, or `None` if it scrolls a page.
+///
+/// Tilting the wheel sideways scrolls characters rather than lines, which we treat alike.
+pub(crate) fn wheel_scroll_lines(horizontal: bool) -> Option<f64> {
+    let action = if horizontal {
+        SPI_GETWHEELSCROLLCHARS
+    } else {
+        SPI_GETWHEELSCROLLLINES
+    };
+    let mut lines: UINT = 3;
+    unsafe {
+        if SystemParametersInfoW(action, 0, &mut lines as *mut UINT as *mut _, 0) == FALSE {
+            warn!(
+                "SystemParametersInfoW failed: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+        }
+    }
+    match lines {
+        WHEEL_PAGESCROLL => None,
+        lines => Some(lines as f64),
+    }
+}
+
+/// Returns the refresh rate of the monitor that most of `hwnd` is on, if it is known.
+pub(crate) fn monitor_refresh_rate(hwnd: HWND) -> Option<f64> {
+    unsafe {
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        let mut info: MONITORINFOEXW = std::mem::zeroed();
+        info.cbSize = size_of::<MONITORINFOEXW>() as u32;
+        if GetMonitorInfoW(
+            monitor,
+            &mut info as *mut MONITORINFOEXW as *mut MONITORINFO,
+        ) == 0
+        {
+            return None;
+        }
+        device_refresh_rate(&info.szDevice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn night_light_state() {
+        let mut data = vec![0x43, 0x42, 0x01, 0x00, 0x0a, 0x02, 0x01, 0x00, 0x2a, 0x06];
+        data.extend_from_slice(&[0x9d, 0xb3, 0xd6, 0x9c, 0x06, 0x2a, 0x2b, 0x0e]);
+        data.extend_from_slice(&[0x15, 0x43, 0x42, 0x01, 0x00, 0x02, 0x01]);
+        assert_eq!(night_light_active(&data), Some(true));
+        data[18] = 0x13;
+        assert_eq!(night_light_active(&data), Some(false));
+        data[18] = 0x17;
+        assert_eq!(night_light_active(&data), None);
+        assert_eq!(night_light_active(&data[..18]), None);
+        assert_eq!(night_light_active(&data[4..]), None);
+    }
+}
@@ -14,7 +14,7 @@
 
 //! Timer state.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 use std::time::Instant;
 
 use crate::window::TimerToken;
@@ -23,6 +23,8 @@ pub struct TimerSlots {
     // Note: we can remove this when checked_duration_since lands.
     next_fresh_id: u64,
     free_slots: BTreeSet<u64>,
+    // The slots of the timers that keep firing until they're cancelled.
+    repeating: HashSet<u64>,
 }
 
 impl TimerSlots {
@@ -30,6 +32,7 @@ impl TimerSlots {
         TimerSlots {
             next_fresh_id: starting_ix,
             free_slots: Default::default(),
+            repeating: Default::default(),
         }
     }
 
@@ -44,8 +47,17 @@ impl TimerSlots {
         }
     }
 
+    pub fn set_repeating(&mut self, token: TimerToken) {
+        self.repeating.insert(token.into_raw());
+    }
+
+    pub fn is_repeating(&self, token: TimerToken) -> bool {
+        self.repeating.contains(&token.into_raw())
+    }
+
     pub fn free(&mut self, token: TimerToken) {
         let id = token.into_raw();
+        self.repeating.remove(&id);
         if self.next_fresh_id == id + 1 {
             self.next_fresh_id -= 1;
         } else {
@@ -115,14 +115,38 @@ pub(crate) fn recti_to_rect(rect: RECT) -> Rect {
     )
 }
 
+/// Converts a `Rect` with integer coordinates to a winapi `RECT`.
+#[inline]
+pub(crate) fn rect_to_recti(rect: Rect) -> RECT {
+    RECT {
+        left: rect.x0 as i32,
+        top: rect.y0 as i32,
+        right: rect.x1 as i32,
+        bottom: rect.y1 as i32,
+    }
+}
+
 // Types for functions we want to load, which are only supported on newer windows versions
 // from user32.dll
 type GetDpiForSystem = unsafe extern "system" fn() -> UINT;
 type GetDpiForWindow = unsafe extern "system" fn(HWND) -> UINT;
 type SetProcessDpiAwarenessContext =
     unsafe extern "system" fn(winapi::shared::windef::DPI_AWARENESS_CONTEXT) -> BOOL;
+type SetThreadDpiAwarenessContext =
+    unsafe extern "system" fn(
+        winapi::shared::windef::DPI_AWARENESS_CONTEXT,
+    ) -> winapi::shared::windef::DPI_AWARENESS_CONTEXT;
 type GetSystemMetricsForDpi =
     unsafe extern "system" fn(winapi::ctypes::c_int, UINT) -> winapi::ctypes::c_int;
+type SetCoalescableTimer = unsafe extern "system" fn(
+    HWND,
+    winapi::shared::basetsd::UINT_PTR,
+    UINT,
+    winapi::um::winuser::TIMERPROC,
+    winapi::shared::ntdef::ULONG,
+) -> winapi::shared::basetsd::UINT_PTR;
+type GetPointerType =
+    unsafe extern "system" fn(u32, *mut winapi::um::winuser::POINTER_INPUT_TYPE) -> BOOL;
 // from shcore.dll
 type GetDpiForMonitor = unsafe extern "system" fn(HMONITOR, MONITOR_DPI_TYPE, *mut UINT, *mut UINT);
 type SetProcessDpiAwareness = unsafe extern "system" fn(PROCESS_DPI_AWARENESS) -> HRESULT;
@@ -137,9 +161,12 @@ pub struct OptionalFunctions {
     pub GetDpiForSystem: Option<GetDpiForSystem>,
     pub GetDpiForWindow: Option<GetDpiForWindow>,
     pub SetProcessDpiAwarenessContext: Option<SetProcessDpiAwarenessContext>,
+    pub SetThreadDpiAwarenessContext: Option<SetThreadDpiAwarenessContext>,
     pub GetDpiForMonitor: Option<GetDpiForMonitor>,
     pub SetProcessDpiAwareness: Option<SetProcessDpiAwareness>,
     pub GetSystemMetricsForDpi: Option<GetSystemMetricsForDpi>,
+    pub SetCoalescableTimer: Option<SetCoalescableTimer>,
+    pub GetPointerType: Option<GetPointerType>,
     pub DCompositionCreateDevice: Option<DCompositionCreateDevice>,
 }
 
@@ -192,8 +219,11 @@ fn load_optional_functions() -> OptionalFunctions {
     let mut GetDpiForMonitor = None;
     let mut GetDpiForWindow = None;
     let mut SetProcessDpiAwarenessContext = None;
+    let mut SetThreadDpiAwarenessContext = None;
     let mut SetProcessDpiAwareness = None;
     let mut GetSystemMetricsForDpi = None;
+    let mut SetCoalescableTimer = None;
+    let mut GetPointerType = None;
     let mut DCompositionCreateDevice = None;
 
     if shcore.is_null() {
@@ -209,7 +239,10 @@ fn load_optional_functions() -> OptionalFunctions {
         load_function!(user32, GetDpiForSystem, "10");
         load_function!(user32, GetDpiForWindow, "10");
         load_function!(user32, SetProcessDpiAwarenessContext, "10");
+        load_function!(user32, SetThreadDpiAwarenessContext, "10");
         load_function!(user32, GetSystemMetricsForDpi, "10");
+        load_function!(user32, SetCoalescableTimer, "8");
+        load_function!(user32, GetPointerType, "8");
     }
 
     if dcomp.is_null() {
@@ -222,9 +255,12 @@ fn load_optional_functions() -> OptionalFunctions {
         GetDpiForSystem,
         GetDpiForWindow,
         SetProcessDpiAwarenessContext,
+        SetThreadDpiAwarenessContext,
         GetDpiForMonitor,
         SetProcessDpiAwareness,
         GetSystemMetricsForDpi,
+        SetCoalescableTimer,
+        GetPointerType,
         DCompositionCreateDevice,
     }
 }
@@ -34,43 +34,80 @@ use winapi::ctypes::{c_int, c_void};
 use winapi::shared::minwindef::*;
 use winapi::shared::windef::*;
 use winapi::shared::winerror::*;
-use winapi::um::dwmapi::{DwmExtendFrameIntoClientArea, DwmSetWindowAttribute};
+use winapi::shared::wtypes::{VARTYPE, VT_LPWSTR};
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::dwmapi::{
+    DwmExtendFrameIntoClientArea, DwmGetCompositionTimingInfo, DwmInvalidateIconicBitmaps,
+    DwmSetIconicLivePreviewBitmap, DwmSetIconicThumbnail, DwmSetWindowAttribute, DWMWA_CLOAK,
+    DWMWA_FORCE_ICONIC_REPRESENTATION, DWMWA_HAS_ICONIC_BITMAP, DWM_TIMING_INFO,
+};
 use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::profileapi::{QueryPerformanceCounter, QueryPerformanceFrequency};
+use winapi::um::propidl::PROPVARIANT;
+use winapi::um::propkey::PKEY_AppUserModel_ID;
+use winapi::um::propsys::IPropertyStore;
+use winapi::um::shellapi::SHGetPropertyStoreForWindow;
 use winapi::um::shellscalingapi::MDT_EFFECTIVE_DPI;
+use winapi::um::shobjidl_core::{CLSID_TaskbarList, ITaskbarList3};
 use winapi::um::uxtheme::*;
+use winapi::um::winbase::SetThreadExecutionState;
 use winapi::um::wingdi::*;
 use winapi::um::winnt::*;
 use winapi::um::winuser::*;
+use winapi::Interface;
+use wio::com::ComPtr;
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, Win32WindowHandle,
     WindowsDisplayHandle,
 };
 
-use crate::kurbo::{Insets, Point, Rect, Size, Vec2};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
 
 use super::accels::register_accel;
 use super::application::Application;
 use super::dialog::get_file_dialog_path;
+use super::direct_manipulation::{DirectManipulation, ScrollMessage};
+use super::dnd::{self, DragKind, DragMessage};
 use super::error::Error;
 use super::keyboard::{self, KeyboardState};
 use super::menu::Menu;
 // use super::paint;
+use super::screen;
 use super::timers::TimerSlots;
-use super::util::{self, ToWide, OPTIONAL_FUNCTIONS};
+use super::util::{self, as_result, FromWide, ToWide, OPTIONAL_FUNCTIONS};
 
-use crate::common_util::IdleCallback;
+use crate::application::KioskOptions;
+use crate::autoscroll::Autoscroll;
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
+use crate::common_util::{self, FrameThrottle, IdleCallback};
 use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::dispatch;
+use crate::dnd::{DragData, DragImage, DropEffect};
 use crate::error::Error as ShellError;
-use crate::keyboard::{KbKey, KeyState};
-use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
+use crate::keyboard::{
+    CompositionEvent, CompositionState, KbKey, KeyEvent, KeyState, KeyboardLayout,
+};
+use crate::locale::LocaleInfo;
+use crate::menu;
+use crate::mouse::{
+    self, Cursor, CursorDesc, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent,
+    ScrollDelta,
+};
+use crate::platform::windows::ThumbnailBitmap;
 use crate::region::Region;
 use crate::scale::{Scalable, Scale, ScaledArea};
+use crate::screen::{monitor_containing, Monitor, MonitorId};
+use crate::shortcut_capture::ShortcutCapture;
 use crate::text::{simulate_input, Event};
 use crate::window;
 use crate::window::{
-    FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel,
+    FileDialogToken, FocusDirection, FrameInfo, FullscreenOptions, IdleToken, PaintReason,
+    ScrollbarMetrics, SystemBar, TextFieldToken, Theme, TimerToken, WinHandler, WindowLevel,
 };
+use crate::window_group::WindowGroup;
 
 /// The backend target DPI.
 ///
@@ -81,7 +118,9 @@ pub(crate) const SCALE_TARGET_DPI: f64 = 96.0;
 pub(crate) struct WindowBuilder {
     app: Application,
     handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
     title: String,
+    taskbar_group: Option<String>,
     menu: Option<Menu>,
     present_strategy: PresentStrategy,
     resizable: bool,
@@ -89,9 +128,15 @@ pub(crate) struct WindowBuilder {
     size: Option<Size>,
     transparent: bool,
     min_size: Option<Size>,
+    resize_increments: Option<Size>,
     position: Option<Point>,
     level: Option<WindowLevel>,
     state: window::WindowState,
+    focusable: bool,
+    activate_on_show: bool,
+    show_after_first_paint: bool,
+    forced_scale: Option<Scale>,
+    allow_elevated_drop: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -153,7 +198,14 @@ enum DeferredOp {
     SetSize(Size),
     SetResizable(bool),
     SetWindowState(window::WindowState),
+    SetFullscreen(Option<FullscreenOptions>),
+    RaiseAbove(HWND),
+    LowerBelow(HWND),
+    SetMousePassthrough(bool),
+    SetShape(Option<BezPath>),
+    SetImeAllowed(bool),
     ReleaseMouseCapture,
+    StartDrag(DragData, Option<DragImage>),
 }
 
 #[derive(Clone, Debug, Default)]
@@ -233,10 +285,51 @@ struct WindowState {
     is_resizable: Cell<bool>,
     handle_titlebar: Cell<bool>,
     active_text_input: Cell<Option<TextFieldToken>>,
+    // Whether key repeats are passed on to the handler.
+    key_repeat: Cell<bool>,
+    // The last text size setting that we told the handler about.
+    text_scale_factor: Cell<f64>,
+    // The last high-contrast palette that we told the handler about.
+    high_contrast_colors: Cell<Option<HighContrastColors>>,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
+    // The part of the client area that the cursor is confined to while we have focus, in
+    // display points.
+    pointer_confinement: Cell<Option<Rect>>,
+    // The pixel of the client area that the cursor is locked to, if it is locked.
+    pointer_lock: Cell<Option<Rect>>,
     // Is the window focusable ("activatable" in Win32 terminology)?
     // False for tooltips, to prevent stealing focus from owner window.
     is_focusable: bool,
+    // Whether showing the window should also activate it.
+    activate_on_show: bool,
+    // Whether the window is waiting for its first paint before it becomes visible.
+    // Until then, it is cloaked when shown.
+    hidden_until_paint: Cell<bool>,
+    /// Whether the handler asked for an animation frame since the last `WM_PAINT`.
+    anim_frame_requested: Cell<bool>,
+    frame_throttle: FrameThrottle,
+    // A scale to use instead of the monitor's.
+    forced_scale: Option<Scale>,
     window_level: WindowLevel,
+    // Tooltips and drop-downs opened from this window, which are closed when it is clicked or
+    // loses focus.
+    popups: RefCell<Vec<WindowHandle>>,
+    // Images shown by the taskbar instead of the window's contents.
+    thumbnail: RefCell<Option<ThumbnailBitmap>>,
+    peek_preview: RefCell<Option<ThumbnailBitmap>>,
+    // Whether raw mouse motion is sent to this window.
+    raw_pointer_input: Cell<bool>,
+    // Whether the system's pen gestures, such as press and hold to right-click, are on.
+    pen_gestures: Cell<bool>,
+    // The style and placement to restore when the window leaves fullscreen.
+    fullscreen_restore: Cell<Option<(isize, WINDOWPLACEMENT)>>,
+    // What kiosk mode currently locks down, so that we only undo what we did.
+    kiosk: Cell<KioskOptions>,
+    // The custom formats that the drop target reads from drags.
+    drop_formats: Rc<RefCell<Vec<FormatId>>>,
+    // Smooth scrolling for precision touchpads, unless the system doesn't have it.
+    direct_manipulation: RefCell<Option<DirectManipulation>>,
     #[cfg(feature = "accesskit")]
     uia_init_marker: UiaInitMarker, // zero size
     #[cfg(feature = "accesskit")]
@@ -274,6 +367,7 @@ struct MyWndProc {
 struct WndState {
     handler: Box<dyn WinHandler>,
     min_size: Option<Size>,
+    resize_increments: Option<Size>,
     keyboard_state: KeyboardState,
     // Stores a set of all mouse buttons that are currently holding mouse
     // capture. When the first mouse button is down on our window we enter
@@ -281,10 +375,16 @@ struct WndState {
     captured_mouse_buttons: MouseButtons,
     // Is this window the topmost window under the mouse cursor
     has_mouse_focus: bool,
+    // The message time of the last WM_MOUSEMOVE, for finding the positions merged into the next.
+    last_mouse_move_time: DWORD,
     //TODO: track surrogate orphan
     last_click_time: Instant,
     last_click_pos: (i32, i32),
     click_count: u8,
+    // Whether the IME is currently composing text.
+    ime_composing: bool,
+    // The part of a line that the mouse wheel has scrolled, but that hasn't been sent yet.
+    wheel_remainder: Vec2,
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -301,6 +401,14 @@ impl Drop for HCursor {
     }
 }
 
+/// The id of the timer we use to delay animation frames when the frame rate is limited.
+///
+/// The timers requested by the `WinHandler` count up from 1, so they won't reach this.
+const FRAME_THROTTLE_TIMER_ID: usize = usize::MAX;
+
+/// The id of the timer that updates the Direct Manipulation viewport while it moves.
+pub(super) const DIRECT_MANIPULATION_TIMER_ID: usize = usize::MAX - 1;
+
 /// Message indicating there are idle tasks to run.
 const DS_RUN_IDLE: UINT = WM_USER;
 
@@ -316,12 +424,67 @@ const DS_RUN_IDLE: UINT = WM_USER;
 /// time it is handled, we can successfully borrow the handler.
 pub(crate) const DS_REQUEST_DESTROY: UINT = WM_USER + 1;
 
+/// Message relaying a drag event from the window's drop target.
+///
+/// The `LPARAM` points to a `dnd::DragMessage`, which the handler's answer is written to.
+pub(super) const DS_DRAG_EVENT: UINT = WM_USER + 2;
+
+/// Message relaying a touchpad scroll from the window's Direct Manipulation viewport.
+///
+/// The `LPARAM` points to a `direct_manipulation::ScrollMessage`.
+pub(super) const DS_SCROLL_EVENT: UINT = WM_USER + 3;
+
 impl Default for PresentStrategy {
     fn default() -> PresentStrategy {
         PresentStrategy::Sequential
     }
 }
 
+/// The timing of the next frame that the desktop compositor shows, if it knows.
+fn dwm_frame_info() -> Option<FrameInfo> {
+    unsafe {
+        let mut timing: DWM_TIMING_INFO = mem::zeroed();
+        timing.cbSize = mem::size_of::<DWM_TIMING_INFO>() as u32;
+        // The window must be null since Windows 8.1; the timing is for the whole desktop.
+        if FAILED(DwmGetCompositionTimingInfo(null_mut(), &mut timing)) {
+            return None;
+        }
+        let mut frequency: LARGE_INTEGER = mem::zeroed();
+        let mut counter: LARGE_INTEGER = mem::zeroed();
+        if QueryPerformanceFrequency(&mut frequency) == 0
+            || QueryPerformanceCounter(&mut counter) == 0
+        {
+            return None;
+        }
+        let frequency = *frequency.QuadPart() as u64;
+        let now = *counter.QuadPart() as u64;
+        let period = timing.qpcRefreshPeriod;
+        if frequency == 0 || period == 0 {
+            return None;
+        }
+        // `qpcVBlank` is the last vertical blank, so we look for the first one after now.
+        let mut next = timing.qpcVBlank;
+        if next <= now {
+            next += ((now - next) / period + 1) * period;
+        }
+        let to_duration = |ticks: u64| Duration::from_secs_f64(ticks as f64 / frequency as f64);
+        Some(FrameInfo {
+            present_time: Instant::now() + to_duration(next - now),
+            refresh_interval: to_duration(period),
+        })
+    }
+}
+
+/// Where mouse input comes from. A session can be disconnected and reconnected over RDP, so
+/// this is checked for each event.
+fn input_source() -> InputSource {
+    if unsafe { GetSystemMetrics(SM_REMOTESESSION) } != 0 {
+        InputSource::Remote
+    } else {
+        InputSource::Hardware
+    }
+}
+
 /// Extract the buttons that are being held down from wparam in mouse events.
 fn get_buttons(wparam: WPARAM) -> MouseButtons {
     let mut buttons = MouseButtons::new();
@@ -410,10 +573,530 @@ fn set_style(hwnd: HWND, resizable: bool, titlebar: bool) {
     }
 }
 
+/// Makes `hwnd` cover its monitor without borders, or puts it back the way it was.
+///
+/// `restore` holds the window's style and placement from before it went fullscreen.
+fn set_fullscreen(
+    hwnd: HWND,
+    restore: &Cell<Option<(isize, WINDOWPLACEMENT)>>,
+    options: Option<FullscreenOptions>,
+) {
+    unsafe {
+        let options = match options {
+            Some(options) => options,
+            None => {
+                if let Some((style, placement)) = restore.take() {
+                    SetWindowLongPtrW(hwnd, GWL_STYLE, style);
+                    SetWindowPlacement(hwnd, &placement);
+                    SetWindowPos(
+                        hwnd,
+                        null_mut(),
+                        0,
+                        0,
+                        0,
+                        0,
+                        SWP_NOMOVE
+                            | SWP_NOSIZE
+                            | SWP_NOZORDER
+                            | SWP_NOOWNERZORDER
+                            | SWP_FRAMECHANGED,
+                    );
+                }
+                return;
+            }
+        };
+        if restore.get().is_none() {
+            let mut placement: WINDOWPLACEMENT = mem::zeroed();
+            placement.length = mem::size_of::<WINDOWPLACEMENT>() as u32;
+            if GetWindowPlacement(hwnd, &mut placement) == 0 {
+                warn!(
+                    "failed to get window placement: {}",
+                    Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+                );
+                return;
+            }
+            let style = GetWindowLongPtrW(hwnd, GWL_STYLE);
+            restore.set(Some((style, placement)));
+            SetWindowLongPtrW(hwnd, GWL_STYLE, style & !(WS_OVERLAPPEDWINDOW as isize));
+        }
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        let mut info: MONITORINFO = mem::zeroed();
+        info.cbSize = mem::size_of::<MONITORINFO>() as u32;
+        if GetMonitorInfoW(monitor, &mut info) == 0 {
+            warn!("failed to get monitor info for fullscreen window");
+            return;
+        }
+        // The shell moves the taskbar behind a window that covers the whole monitor.
+        let rect = match options.dock {
+            SystemBar::Visible => info.rcWork,
+            SystemBar::AutoHide | SystemBar::Hidden => info.rcMonitor,
+        };
+        if SetWindowPos(
+            hwnd,
+            HWND_TOP,
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+        ) == 0
+        {
+            warn!(
+                "failed to make window fullscreen: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+        }
+    }
+}
+
+/// Sends raw mouse input to `hwnd`, or stops sending it with `None`.
+///
+/// Raw input is registered for the whole process, so this replaces the window that gets it.
+fn register_raw_mouse(hwnd: Option<HWND>) -> bool {
+    // winapi only has these with the hidusage feature.
+    const HID_USAGE_PAGE_GENERIC: USHORT = 0x01;
+    const HID_USAGE_GENERIC_MOUSE: USHORT = 0x02;
+
+    let device = RAWINPUTDEVICE {
+        usUsagePage: HID_USAGE_PAGE_GENERIC,
+        usUsage: HID_USAGE_GENERIC_MOUSE,
+        dwFlags: if hwnd.is_some() { 0 } else { RIDEV_REMOVE },
+        hwndTarget: hwnd.unwrap_or(null_mut()),
+    };
+    unsafe {
+        if RegisterRawInputDevices(&device, 1, mem::size_of::<RAWINPUTDEVICE>() as UINT) == FALSE {
+            warn!(
+                "failed to register for raw mouse input: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+            return false;
+        }
+    }
+    true
+}
+
+// Not exported by winapi.
+const WM_TABLET_QUERYSYSTEMGESTURESTATUS: UINT = 0x02CC;
+const PEN_SERVICE_PROPERTY: &str = "MicrosoftTabletPenServiceProperty";
+
+/// The `TABLET_DISABLE_*` flags that turn off press and hold, tap and barrel button feedback,
+/// and flicks.
+const PEN_GESTURES_OFF: LRESULT = 0x0000_0001 | 0x0000_0008 | 0x0000_0010 | 0x0001_0000;
+
+/// Lets lower-integrity processes send us the messages needed for file drops and
+/// `WM_COPYDATA`, which UIPI would otherwise block when we're running elevated.
+fn allow_elevated_drop(hwnd: HWND) {
+    // Not exported by winapi.
+    const MSGFLT_ALLOW: DWORD = 1;
+    const WM_COPYGLOBALDATA: UINT = 0x0049;
+
+    for &msg in &[WM_DROPFILES, WM_COPYDATA, WM_COPYGLOBALDATA] {
+        unsafe {
+            if ChangeWindowMessageFilterEx(hwnd, msg, MSGFLT_ALLOW, null_mut()) == 0 {
+                warn!(
+                    "failed to change the message filter for {:#x}: {}",
+                    msg,
+                    Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+                );
+            }
+        }
+    }
+}
+
+/// Gives `hwnd` its own AppUserModelID, which the taskbar uses to group windows.
+fn set_app_user_model_id(hwnd: HWND, id: &str) -> Result<(), Error> {
+    unsafe {
+        let mut store: *mut IPropertyStore = null_mut();
+        as_result(SHGetPropertyStoreForWindow(
+            hwnd,
+            &IPropertyStore::uuidof(),
+            &mut store as *mut _ as *mut _,
+        ))?;
+        let store = ComPtr::from_raw(store);
+        let mut id = id.to_wide();
+        let mut value: PROPVARIANT = mem::zeroed();
+        value.vt = VT_LPWSTR as VARTYPE;
+        // The store copies the string, so it only has to outlive `SetValue`.
+        *value.data.pwszVal_mut() = id.as_mut_ptr();
+        as_result(store.SetValue(&PKEY_AppUserModel_ID, &value))?;
+        as_result(store.Commit())
+    }
+}
+
+/// Moves `hwnd` in the z-order so that it is directly below `insert_after`.
+fn set_z_order(hwnd: HWND, insert_after: HWND) {
+    unsafe {
+        if SetWindowPos(
+            hwnd,
+            insert_after,
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+        ) == 0
+        {
+            warn!(
+                "failed to reorder window: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+        }
+    }
+}
+
+/// Makes `hwnd` transparent to mouse input, or opaque again.
+///
+/// Hit-testing only skips `WS_EX_TRANSPARENT` windows if they're also layered. We leave
+/// `WS_EX_LAYERED` set when turning passthrough off, since it's harmless at full opacity.
+fn set_mouse_passthrough(hwnd: HWND, passthrough: bool) {
+    unsafe {
+        let ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32;
+        let new_ex_style = if passthrough {
+            ex_style | WS_EX_LAYERED | WS_EX_TRANSPARENT
+        } else {
+            ex_style & !WS_EX_TRANSPARENT
+        };
+        if new_ex_style == ex_style {
+            return;
+        }
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, new_ex_style as _);
+        if ex_style & WS_EX_LAYERED == 0
+            && new_ex_style & WS_EX_LAYERED != 0
+            && SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA) == 0
+        {
+            warn!(
+                "failed to make window layered: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+        }
+    }
+}
+
+/// Clips `hwnd` to the union of `rects`, given in pixels relative to the client area.
+fn set_window_shape(hwnd: HWND, rects: Option<Vec<Rect>>) {
+    unsafe {
+        let region = match rects {
+            None => null_mut(),
+            Some(rects) => {
+                // Window regions are relative to the window's top left corner, which is not
+                // the same as the client area's if the window has a frame.
+                let mut window_rect: RECT = mem::zeroed();
+                let mut origin = POINT { x: 0, y: 0 };
+                GetWindowRect(hwnd, &mut window_rect);
+                ClientToScreen(hwnd, &mut origin);
+                let (dx, dy) = (origin.x - window_rect.left, origin.y - window_rect.top);
+
+                let region = CreateRectRgn(0, 0, 0, 0);
+                for r in rects {
+                    let rect_region = CreateRectRgn(
+                        r.x0 as i32 + dx,
+                        r.y0 as i32 + dy,
+                        r.x1 as i32 + dx,
+                        r.y1 as i32 + dy,
+                    );
+                    CombineRgn(region, region, rect_region, RGN_OR);
+                    DeleteObject(rect_region as _);
+                }
+                region
+            }
+        };
+        // On success, the system owns the region.
+        if SetWindowRgn(hwnd, region, TRUE) == 0 {
+            warn!(
+                "failed to set the window shape: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+            if !region.is_null() {
+                DeleteObject(region as _);
+            }
+        }
+    }
+}
+
+/// Confines the cursor to `rect`, in pixels relative to the client area of `hwnd`, or
+/// releases it.
+///
+/// The confinement is system wide, so this should only be done while `hwnd` has focus.
+fn clip_cursor(hwnd: HWND, rect: Option<Rect>) {
+    unsafe {
+        let result = match rect {
+            Some(rect) => {
+                let mut origin = POINT { x: 0, y: 0 };
+                ClientToScreen(hwnd, &mut origin);
+                let rect = RECT {
+                    left: origin.x + rect.x0 as i32,
+                    top: origin.y + rect.y0 as i32,
+                    right: origin.x + rect.x1 as i32,
+                    bottom: origin.y + rect.y1 as i32,
+                };
+                ClipCursor(&rect)
+            }
+            None => ClipCursor(null()),
+        };
+        if result == 0 {
+            warn!(
+                "failed to confine the cursor: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+        }
+    }
+}
+
+/// Moves the focus to the first (or, if `previous`, the last) native child of `hwnd` that is a
+/// tab stop, if there is one.
+fn focus_child_tab_stop(hwnd: HWND, previous: bool) {
+    unsafe {
+        let child = GetNextDlgTabItem(hwnd, null_mut(), previous as BOOL);
+        if !child.is_null() && child != hwnd {
+            SetFocus(child);
+        }
+    }
+}
+
+/// Hides or reveals `hwnd` without changing whether it is shown.
+///
+/// A cloaked window is still laid out and painted, but DWM doesn't draw it.
+fn set_cloaked(hwnd: HWND, cloaked: bool) {
+    let value: BOOL = cloaked.into();
+    unsafe {
+        let hr = DwmSetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAK,
+            &value as *const _ as *const c_void,
+            mem::size_of::<BOOL>() as u32,
+        );
+        if FAILED(hr) {
+            warn!("failed to cloak window: {}", Error::Hr(hr));
+        }
+    }
+}
+
+/// Makes DWM draw the titlebar of `hwnd` dark or light.
+///
+/// <https://docs.microsoft.com/en-us/windows/apps/desktop/modernize/apply-windows-themes>
+fn set_dark_mode(hwnd: HWND, dark: bool) {
+    const DWMWA_USE_IMMERSIVE_DARK_MODE: u32 = 20;
+    let value: BOOL = dark.into();
+    // This fails on versions of Windows without dark mode, which just keep the default.
+    unsafe {
+        DwmSetWindowAttribute(
+            hwnd,
+            DWMWA_USE_IMMERSIVE_DARK_MODE,
+            &value as *const _ as *const c_void,
+            mem::size_of::<BOOL>() as u32,
+        );
+    }
+}
+
+thread_local! {
+    /// The low-level keyboard hook that blocks system shortcuts in kiosk mode, and the window
+    /// that they are blocked for.
+    static KIOSK_HOOK: Cell<Option<(HHOOK, HWND)>> = Cell::new(None);
+}
+
+/// Starts or stops blocking system shortcuts while `hwnd` is in the foreground.
+fn set_shortcuts_blocked(hwnd: HWND, blocked: bool) {
+    unsafe {
+        if let Some((hook, _)) = KIOSK_HOOK.with(|h| h.take()) {
+            UnhookWindowsHookEx(hook);
+        }
+        if !blocked {
+            return;
+        }
+        // Low-level hooks run on this thread, from the message loop.
+        let hook = SetWindowsHookExW(WH_KEYBOARD_LL, Some(kiosk_keyboard_hook), null_mut(), 0);
+        if hook.is_null() {
+            warn!(
+                "failed to block system shortcuts: {}",
+                Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+            );
+            return;
+        }
+        KIOSK_HOOK.with(|h| h.set(Some((hook, hwnd))));
+    }
+}
+
+/// Swallows the shortcuts that would switch away from the kiosk window. The shell doesn't see
+/// them either, but Ctrl+Alt+Del can't be blocked.
+unsafe extern "system" fn kiosk_keyboard_hook(
+    code: c_int,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if code == HC_ACTION {
+        let info = &*(lparam as *const KBDLLHOOKSTRUCT);
+        let alt = info.flags & LLKHF_ALTDOWN != 0;
+        let ctrl = GetAsyncKeyState(VK_CONTROL) < 0;
+        let shortcut = match info.vkCode as c_int {
+            VK_LWIN | VK_RWIN => true,
+            VK_TAB => alt,
+            VK_ESCAPE => alt || ctrl,
+            _ => false,
+        };
+        let ours = KIOSK_HOOK
+            .with(|h| h.get())
+            .is_some_and(|(_, hwnd)| GetForegroundWindow() == hwnd);
+        if shortcut && ours {
+            return 1;
+        }
+    }
+    CallNextHookEx(null_mut(), code, wparam, lparam)
+}
+
+/// Tells DWM whether to ask us for the taskbar thumbnail and peek preview, instead of
+/// capturing the window.
+fn set_iconic_representation(hwnd: HWND, iconic: bool) {
+    let value: BOOL = iconic.into();
+    unsafe {
+        for attr in [DWMWA_FORCE_ICONIC_REPRESENTATION, DWMWA_HAS_ICONIC_BITMAP] {
+            let hr = DwmSetWindowAttribute(
+                hwnd,
+                attr,
+                &value as *const _ as *const c_void,
+                mem::size_of::<BOOL>() as u32,
+            );
+            if FAILED(hr) {
+                warn!("failed to set the iconic representation: {}", Error::Hr(hr));
+                return;
+            }
+        }
+        if iconic {
+            // Make DWM ask for the new images.
+            DwmInvalidateIconicBitmaps(hwnd);
+        }
+    }
+}
+
+/// Creates a bitmap that DWM can show from `bitmap`, scaled down if necessary to fit in
+/// `max_size`.
+///
+/// Returns null if `bitmap` is empty or the bitmap couldn't be created. The caller owns
+/// the result.
+pub(super) unsafe fn create_iconic_bitmap(
+    bitmap: &ThumbnailBitmap,
+    max_size: Option<(u32, u32)>,
+) -> HBITMAP {
+    let (src_w, src_h) = (bitmap.width as usize, bitmap.height as usize);
+    if src_w == 0 || src_h == 0 || bitmap.pixels.len() < src_w * src_h * 4 {
+        return null_mut();
+    }
+    let scale = match max_size {
+        Some((w, h)) => (w as f64 / src_w as f64)
+            .min(h as f64 / src_h as f64)
+            .min(1.0),
+        None => 1.0,
+    };
+    let w = ((src_w as f64 * scale) as usize).max(1);
+    let h = ((src_h as f64 * scale) as usize).max(1);
+
+    let mut info: BITMAPINFO = mem::zeroed();
+    info.bmiHeader.biSize = mem::size_of::<BITMAPINFOHEADER>() as u32;
+    info.bmiHeader.biWidth = w as i32;
+    // A negative height makes the rows go from the top down, like ours.
+    info.bmiHeader.biHeight = -(h as i32);
+    info.bmiHeader.biPlanes = 1;
+    info.bmiHeader.biBitCount = 32;
+    info.bmiHeader.biCompression = BI_RGB;
+    let mut bits: *mut c_void = null_mut();
+    let dib = CreateDIBSection(null_mut(), &info, DIB_RGB_COLORS, &mut bits, null_mut(), 0);
+    if dib.is_null() || bits.is_null() {
+        warn!(
+            "failed to create a thumbnail bitmap: {}",
+            Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+        );
+        return null_mut();
+    }
+
+    // DWM wants premultiplied BGRA. Nearest neighbor scaling is good enough for thumbnails.
+    let dst = std::slice::from_raw_parts_mut(bits as *mut u8, w * h * 4);
+    for y in 0..h {
+        let src_y = y * src_h / h;
+        for x in 0..w {
+            let src_x = x * src_w / w;
+            let s = (src_y * src_w + src_x) * 4;
+            let d = (y * w + x) * 4;
+            dst[d] = bitmap.pixels[s + 2];
+            dst[d + 1] = bitmap.pixels[s + 1];
+            dst[d + 2] = bitmap.pixels[s];
+            dst[d + 3] = bitmap.pixels[s + 3];
+        }
+    }
+    dib
+}
+
+/// The positions, in client pixels and oldest first, that Windows merged into a `WM_MOUSEMOVE`
+/// at (`x`, `y`) and `time`. Only positions since the `WM_MOUSEMOVE` at `last_time` are returned.
+unsafe fn mouse_move_history(
+    hwnd: HWND,
+    x: i32,
+    y: i32,
+    time: DWORD,
+    last_time: DWORD,
+) -> Vec<(i32, i32)> {
+    const MAX_POINTS: usize = 64;
+    if last_time == 0 {
+        return Vec::new();
+    }
+    let mut current = POINT { x, y };
+    ClientToScreen(hwnd, &mut current);
+    let mut input = MOUSEMOVEPOINT {
+        x: current.x & 0xFFFF,
+        y: current.y & 0xFFFF,
+        time,
+        dwExtraInfo: 0,
+    };
+    let mut history: [MOUSEMOVEPOINT; MAX_POINTS] = mem::zeroed();
+    let count = GetMouseMovePointsEx(
+        mem::size_of::<MOUSEMOVEPOINT>() as UINT,
+        &mut input,
+        history.as_mut_ptr(),
+        MAX_POINTS as c_int,
+        GMMP_USE_DISPLAY_POINTS,
+    );
+    if count < 0 {
+        // This fails if the current position has already dropped out of the history.
+        return Vec::new();
+    }
+    // The history is newest first, and the times wrap around.
+    let mut points: Vec<_> = history[..count as usize]
+        .iter()
+        .take_while(|p| (p.time.wrapping_sub(last_time) as i32) > 0)
+        .map(|p| {
+            // The coordinates are 16 bits, so they wrap around left of and above the primary
+            // monitor.
+            let wrap = |c: c_int| if c > 0x7FFF { c - 0x10000 } else { c };
+            let mut point = POINT {
+                x: wrap(p.x),
+                y: wrap(p.y),
+            };
+            ScreenToClient(hwnd, &mut point);
+            (point.x, point.y)
+        })
+        .collect();
+    points.reverse();
+    points
+}
+
+unsafe fn set_thumbnail_clip(hwnd: HWND, clip: Option<RECT>) -> Result<(), Error> {
+    let mut taskbar: *mut ITaskbarList3 = null_mut();
+    as_result(CoCreateInstance(
+        &CLSID_TaskbarList,
+        null_mut(),
+        CLSCTX_INPROC_SERVER,
+        &ITaskbarList3::uuidof(),
+        &mut taskbar as *mut *mut ITaskbarList3 as *mut LPVOID,
+    ))?;
+    let taskbar = ComPtr::from_raw(taskbar);
+    as_result(taskbar.HrInit())?;
+    let mut clip = clip;
+    let clip_ptr = clip.as_mut().map_or(null_mut(), |r| r as *mut RECT);
+    as_result(taskbar.SetThumbnailClip(hwnd, clip_ptr))
+}
+
 impl WndState {
     // Renders but does not present.
-    fn render(&mut self, invalid: &Region) {
-        self.handler.paint(invalid);
+    fn render(&mut self, invalid: &Region, reason: PaintReason) {
+        self.handler.paint(invalid, reason);
     }
 
     fn enter_mouse_capture(&mut self, hwnd: HWND, button: MouseButton) {
@@ -429,6 +1112,29 @@ impl WndState {
         self.captured_mouse_buttons.remove(button);
         self.captured_mouse_buttons.is_empty()
     }
+
+    fn start_composition(&mut self) {
+        if !self.ime_composing {
+            self.ime_composing = true;
+            self.handler.composition(CompositionEvent {
+                state: CompositionState::Start,
+                text: String::new(),
+                cursor: None,
+            });
+        }
+    }
+
+    /// End the current composition, if any, committing `text`.
+    fn end_composition(&mut self, text: String) {
+        if self.ime_composing {
+            self.ime_composing = false;
+            self.handler.composition(CompositionEvent {
+                state: CompositionState::End,
+                text,
+                cursor: None,
+            });
+        }
+    }
 }
 
 impl MyWndProc {
@@ -468,7 +1174,7 @@ impl MyWndProc {
     }
 
     fn set_scale(&self, scale: Scale) {
-        self.with_window_state(move |state| state.scale.set(scale))
+        self.with_window_state(move |state| state.scale.set(state.forced_scale.unwrap_or(scale)))
     }
 
     /// Takes the invalid region and returns it, replacing it with the empty region.
@@ -502,6 +1208,14 @@ impl MyWndProc {
         self.with_window_state(|state| state.is_transparent.get())
     }
 
+    /// Close any tooltips and drop-downs that were opened from this window.
+    fn dismiss_popups(&self) {
+        let popups = self.with_window_state(|state| state.popups.take());
+        for popup in popups {
+            popup.close();
+        }
+    }
+
     fn handle_deferred_queue(&self) {
         let q = self.with_window_state(move |state| state.deferred_queue.replace(Vec::new()));
         for op in q {
@@ -548,6 +1262,25 @@ impl MyWndProc {
                         );
                     };
                 },
+                DeferredOp::RaiseAbove(other) => {
+                    // SetWindowPos puts the window *below* the insert-after window, so we insert
+                    // after whatever is currently directly above `other`.
+                    let above = unsafe { GetWindow(other, GW_HWNDPREV) };
+                    if above != hwnd {
+                        let insert_after = if above.is_null() { HWND_TOP } else { above };
+                        set_z_order(hwnd, insert_after);
+                    }
+                }
+                DeferredOp::LowerBelow(other) => set_z_order(hwnd, other),
+                DeferredOp::SetMousePassthrough(passthrough) => {
+                    set_mouse_passthrough(hwnd, passthrough)
+                }
+                DeferredOp::SetShape(shape) => {
+                    let scale = self.scale();
+                    let rects = shape.map(|shape| common_util::shape_to_rects(&shape, scale));
+                    set_window_shape(hwnd, rects);
+                }
+                DeferredOp::SetImeAllowed(allowed) => keyboard::set_ime_allowed(hwnd, allowed),
                 DeferredOp::ShowTitlebar(titlebar) => {
                     self.with_window_state(|s| s.has_titlebar.set(titlebar));
                     set_style(hwnd, self.resizable(), titlebar);
@@ -570,6 +1303,11 @@ impl MyWndProc {
                         ShowWindow(hwnd, show);
                     }
                 }
+                DeferredOp::SetFullscreen(options) => {
+                    self.with_window_state(|s| {
+                        set_fullscreen(hwnd, &s.fullscreen_restore, options)
+                    });
+                }
                 DeferredOp::SaveAs(options, token) => {
                     let info = unsafe {
                         get_file_dialog_path(hwnd, FileDialogType::Save, options)
@@ -618,6 +1356,7 @@ impl MyWndProc {
                         }
                     }
                 },
+                DeferredOp::StartDrag(data, image) => dnd::start_drag(&data, image.as_ref()),
             }
         } else {
             warn!("Could not get HWND");
@@ -639,6 +1378,15 @@ impl MyWndProc {
             }
         }
     }
+
+    /// Tells the handler if the high-contrast palette has changed since it last heard of it.
+    fn update_high_contrast_colors(&self) {
+        let colors = screen::high_contrast_colors();
+        let old = self.with_window_state(|w| w.high_contrast_colors.replace(colors));
+        if old != colors {
+            self.with_wnd_state(|s| s.handler.high_contrast_changed(colors));
+        }
+    }
 }
 
 impl WndProc for MyWndProc {
@@ -689,6 +1437,9 @@ impl WndProc for MyWndProc {
 
                 if let Some(state) = self.handle.borrow().state.upgrade() {
                     state.hwnd.set(hwnd);
+                    state
+                        .frame_throttle
+                        .set_refresh_rate(screen::monitor_refresh_rate(hwnd));
                 }
                 if let Some(state) = self.state.borrow_mut().as_mut() {
                     let handle = self.handle.borrow().to_owned();
@@ -737,16 +1488,50 @@ impl WndProc for MyWndProc {
                 Some(0)
             }
             WM_ERASEBKGND => Some(0),
+            // We want to see Tab and the arrow keys even when we're hosted in a dialog, so that
+            // the handler gets to move the focus within its content first.
+            WM_GETDLGCODE => Some(DLGC_WANTALLKEYS),
             WM_SETFOCUS => {
+                // Windows forgets the confinement when another window takes focus.
+                if let Some(rect) = self.with_window_state(|w| w.pointer_confinement.get()) {
+                    clip_cursor(hwnd, Some(rect.to_px(self.scale()).round()));
+                }
                 self.with_wnd_state(|s| s.handler.got_focus());
                 Some(0)
             }
             WM_KILLFOCUS => {
+                let (confined, lock_lost) = self.with_window_state(|w| {
+                    (
+                        w.pointer_confinement.get().is_some(),
+                        w.pointer_lock.take().is_some(),
+                    )
+                });
+                if confined || lock_lost {
+                    clip_cursor(hwnd, None);
+                }
+                self.dismiss_popups();
                 self.with_wnd_state(|s| s.handler.lost_focus());
+                if lock_lost {
+                    self.with_wnd_state(|s| s.handler.pointer_lock_lost());
+                }
                 Some(0)
             }
             WM_PAINT => unsafe {
+                self.with_window_state(|w| w.frame_throttle.frame_started());
+                // Animation frames requested from here on are for the next frame.
+                let reason = if self.with_window_state(|w| w.anim_frame_requested.replace(false)) {
+                    PaintReason::Animation
+                } else {
+                    PaintReason::Damage
+                };
+                let missed = self.with_window_state(|w| w.frame_throttle.missed_frames(reason));
+                let info = dwm_frame_info()
+                    .unwrap_or_else(|| self.with_window_state(|w| w.frame_throttle.frame_info()));
                 self.with_wnd_state(|s| {
+                    if missed > 0 {
+                        s.handler.frame_missed(missed);
+                    }
+                    s.handler.prepare_frame(info);
                     // We call prepare_paint before GetUpdateRect, so that anything invalidated during
                     // prepare_paint will be reflected in GetUpdateRect.
                     s.handler.prepare_paint();
@@ -762,11 +1547,92 @@ impl WndProc for MyWndProc {
                     let invalid = self.take_invalid();
                     if !invalid.rects().is_empty() {
                         s.handler.rebuild_resources();
-                        s.render(&invalid);
+                        s.render(&invalid, reason);
+                        if self.with_window_state(|w| w.hidden_until_paint.replace(false)) {
+                            set_cloaked(hwnd, false);
+                        }
                     }
                 });
                 Some(0)
             },
+            WM_DWMSENDICONICTHUMBNAIL => unsafe {
+                let max_size = (HIWORD(lparam as u32) as u32, LOWORD(lparam as u32) as u32);
+                self.with_window_state(|w| {
+                    let thumbnail = w.thumbnail.borrow();
+                    let peek_preview = w.peek_preview.borrow();
+                    if let Some(bitmap) = thumbnail.as_ref().or(peek_preview.as_ref()) {
+                        let hbitmap = create_iconic_bitmap(bitmap, Some(max_size));
+                        if !hbitmap.is_null() {
+                            let hr = DwmSetIconicThumbnail(hwnd, hbitmap, 0);
+                            if FAILED(hr) {
+                                warn!("failed to set the taskbar thumbnail: {}", Error::Hr(hr));
+                            }
+                            DeleteObject(hbitmap as _);
+                        }
+                    }
+                });
+                Some(0)
+            },
+            WM_DWMSENDICONICLIVEPREVIEWBITMAP => unsafe {
+                self.with_window_state(|w| {
+                    let thumbnail = w.thumbnail.borrow();
+                    let peek_preview = w.peek_preview.borrow();
+                    if let Some(bitmap) = peek_preview.as_ref().or(thumbnail.as_ref()) {
+                        let hbitmap = create_iconic_bitmap(bitmap, None);
+                        if !hbitmap.is_null() {
+                            let hr = DwmSetIconicLivePreviewBitmap(hwnd, hbitmap, null_mut(), 0);
+                            if FAILED(hr) {
+                                warn!("failed to set the peek preview: {}", Error::Hr(hr));
+                            }
+                            DeleteObject(hbitmap as _);
+                        }
+                    }
+                });
+                Some(0)
+            },
+            WM_TABLET_QUERYSYSTEMGESTURESTATUS => {
+                if self.with_window_state(|w| w.pen_gestures.get()) {
+                    None
+                } else {
+                    Some(PEN_GESTURES_OFF)
+                }
+            }
+            WM_SETTINGCHANGE => {
+                // The text size setting doesn't get a message of its own, so we check it whenever
+                // some setting changes.
+                let factor = screen::text_scale_factor();
+                let changed = self.with_window_state(|w| w.text_scale_factor.replace(factor));
+                if changed != factor {
+                    self.with_wnd_state(|s| s.handler.text_scale_factor_changed(factor));
+                }
+                if wparam == SPI_SETHIGHCONTRAST as WPARAM {
+                    self.update_high_contrast_colors();
+                }
+                // Changes to the regional settings are sent with "intl".
+                let area = lparam as LPWSTR;
+                if !area.is_null() && area.to_string().as_deref() == Some("intl") {
+                    let info = Application::locale_info();
+                    self.with_wnd_state(|s| s.handler.locale_changed(info));
+                }
+                None
+            }
+            WM_SYSCOLORCHANGE => {
+                // Switching between high-contrast themes only changes the colors.
+                self.update_high_contrast_colors();
+                None
+            }
+            WM_MOVE | WM_DISPLAYCHANGE => {
+                // The window may now be on a monitor with a different refresh rate.
+                let refresh_rate = screen::monitor_refresh_rate(hwnd);
+                let state = self.handle.borrow().state.upgrade();
+                if let Some(state) = state {
+                    if state.frame_throttle.set_refresh_rate(refresh_rate) {
+                        let interval = state.frame_throttle.refresh_interval();
+                        self.with_wnd_state(|s| s.handler.refresh_interval_changed(interval));
+                    }
+                }
+                None
+            }
             WM_DPICHANGED => unsafe {
                 let x = HIWORD(wparam as u32) as f64 / SCALE_TARGET_DPI;
                 let y = LOWORD(wparam as u32) as f64 / SCALE_TARGET_DPI;
@@ -870,22 +1736,69 @@ impl WndProc for MyWndProc {
                 if width == 0 || height == 0 {
                     return Some(0);
                 }
+                self.with_window_state(|w| {
+                    if let Some(dm) = &*w.direct_manipulation.borrow() {
+                        dm.set_size(width as i32, height as i32);
+                    }
+                });
                 self.with_wnd_state(|s| {
                     let scale = self.scale();
                     let area = ScaledArea::from_px((width as f64, height as f64), scale);
                     let size_dp = area.size_dp();
                     self.set_area(area);
                     s.handler.size(size_dp);
-                    s.render(&size_dp.to_rect().into());
+                    s.render(&size_dp.to_rect().into(), PaintReason::Damage);
                 })
                 .map(|_| 0)
             }
             WM_COMMAND => {
-                self.with_wnd_state(|s| s.handler.command(LOWORD(wparam as u32) as u32));
+                let focus = self.with_window_state(|w| w.active_text_input.get());
+                self.with_wnd_state(|s| {
+                    menu::run_command(&mut *s.handler, focus, LOWORD(wparam as u32) as u32)
+                });
                 Some(0)
             }
             //TODO: WM_SYSCOMMAND
-            WM_CHAR | WM_SYSCHAR | WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP
+            // We only observe the IME messages, and let DefWindowProc turn the
+            // result into WM_CHAR messages as usual.
+            WM_IME_STARTCOMPOSITION => {
+                self.with_wnd_state(|s| s.start_composition());
+                None
+            }
+            WM_IME_COMPOSITION => {
+                let flags = lparam as DWORD;
+                let result = if flags & keyboard::GCS_RESULTSTR != 0 {
+                    keyboard::composition_string(hwnd, keyboard::GCS_RESULTSTR)
+                } else {
+                    None
+                };
+                let preedit = if flags & keyboard::GCS_COMPSTR != 0 {
+                    keyboard::composition_string(hwnd, keyboard::GCS_COMPSTR)
+                } else {
+                    None
+                };
+                self.with_wnd_state(|s| {
+                    if let Some((text, _)) = result {
+                        s.end_composition(text);
+                    }
+                    // The IME may start a new composition as soon as it commits one.
+                    let preedit = preedit.filter(|(text, _)| s.ime_composing || !text.is_empty());
+                    if let Some((text, cursor)) = preedit {
+                        s.start_composition();
+                        s.handler.composition(CompositionEvent {
+                            state: CompositionState::Update,
+                            text,
+                            cursor: Some(cursor..cursor),
+                        });
+                    }
+                });
+                None
+            }
+            WM_IME_ENDCOMPOSITION => {
+                self.with_wnd_state(|s| s.end_composition(String::new()));
+                None
+            }
+            WM_CHAR | WM_SYSCHAR | WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP
             | WM_INPUTLANGCHANGE => {
                 unsafe {
                     // We must call keyboard::is_last_message outside of the
@@ -910,6 +1823,12 @@ impl WndProc for MyWndProc {
                                 && (event.key == KbKey::Alt || event.key == KbKey::F10);
                             match event.state {
                                 KeyState::Down => {
+                                    if event.repeat
+                                        && !self.with_window_state(|w| w.key_repeat.get())
+                                    {
+                                        return true;
+                                    }
+                                    let focus = FocusDirection::from_key_event(&event);
                                     let keydown_handled = self.with_window_state(|window_state| {
                                         simulate_input(
                                             &mut *s.handler,
@@ -920,6 +1839,15 @@ impl WndProc for MyWndProc {
                                     if keydown_handled || handle_menu {
                                         return true;
                                     }
+                                    if let Some(direction) = focus {
+                                        if !s.handler.focus_navigation(direction) {
+                                            focus_child_tab_stop(
+                                                hwnd,
+                                                direction == FocusDirection::Previous,
+                                            );
+                                        }
+                                        return true;
+                                    }
                                 }
                                 KeyState::Up => {
                                     s.handler.key_up(event);
@@ -929,6 +1857,10 @@ impl WndProc for MyWndProc {
                                 }
                             }
                         }
+                        if msg == WM_INPUTLANGCHANGE {
+                            s.handler
+                                .input_language_changed(keyboard::layout_from_hkl(lparam as HKL));
+                        }
                         false
                     });
                     if handled == Some(true) {
@@ -939,19 +1871,41 @@ impl WndProc for MyWndProc {
                 }
             }
             WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
-                // TODO: apply mouse sensitivity based on
-                // SPI_GETWHEELSCROLLLINES setting.
                 let handled = self.with_wnd_state(|s| {
-                    let system_delta = HIWORD(wparam as u32) as i16 as f64;
+                    let notches = HIWORD(wparam as u32) as i16 as f64 / WHEEL_DELTA as f64;
                     let down_state = LOWORD(wparam as u32) as usize;
                     let mods = s.keyboard_state.get_modifiers();
                     let is_shift = mods.shift();
-                    let wheel_delta = match msg {
-                        WM_MOUSEWHEEL if is_shift => Vec2::new(-system_delta, 0.),
-                        WM_MOUSEWHEEL => Vec2::new(0., -system_delta),
-                        WM_MOUSEHWHEEL => Vec2::new(system_delta, 0.),
+                    let notches = match msg {
+                        WM_MOUSEWHEEL if is_shift => Vec2::new(-notches, 0.),
+                        WM_MOUSEWHEEL => Vec2::new(0., -notches),
+                        WM_MOUSEHWHEEL => Vec2::new(notches, 0.),
                         _ => unreachable!(),
                     };
+                    let wheel_delta = match screen::wheel_scroll_lines(notches.x != 0.0) {
+                        Some(lines_per_notch) => {
+                            // High-resolution wheels send fractions of a notch. We add them
+                            // up, so that a line is only scrolled once it is complete.
+                            let lines = notches * lines_per_notch;
+                            if s.wheel_remainder.dot(lines) < 0.0 {
+                                s.wheel_remainder = Vec2::ZERO;
+                            }
+                            let total = s.wheel_remainder + lines;
+                            let whole = Vec2::new(total.x.trunc(), total.y.trunc());
+                            s.wheel_remainder = total - whole;
+                            if whole == Vec2::ZERO {
+                                return true;
+                            }
+                            ScrollDelta::Lines(whole)
+                        }
+                        None => {
+                            let size = self.with_window_state(|w| w.area.get().size_dp());
+                            ScrollDelta::Pixels(Vec2::new(
+                                notches.x * size.width,
+                                notches.y * size.height,
+                            ))
+                        }
+                    };
 
                     let mut p = POINT {
                         x: LOWORD(lparam as u32) as i16 as i32,
@@ -977,6 +1931,9 @@ impl WndProc for MyWndProc {
                         focus: false,
                         button: MouseButton::None,
                         wheel_delta,
+                        momentum_phase: MomentumPhase::None,
+                        source: input_source(),
+                        coalesced: Vec::new(),
                     };
                     s.handler.wheel(&event);
                     true
@@ -1015,25 +1972,66 @@ impl WndProc for MyWndProc {
                         }
                     }
 
-                    let pos = Point::new(x as f64, y as f64).to_dp(self.scale());
+                    let scale = self.scale();
                     let mods = s.keyboard_state.get_modifiers();
                     let buttons = get_buttons(wparam);
-                    let event = MouseEvent {
-                        pos,
-                        buttons,
-                        mods,
-                        count: 0,
-                        focus: false,
-                        button: MouseButton::None,
-                        wheel_delta: Vec2::ZERO,
-                    };
-                    s.handler.mouse_move(&event);
+                    let time = unsafe { GetMessageTime() } as DWORD;
+                    let last_time = mem::replace(&mut s.last_mouse_move_time, time);
+                    let mut points = unsafe { mouse_move_history(hwnd, x, y, time, last_time) };
+                    // The history doesn't always reach the current position, for instance if the
+                    // window moved under the pointer.
+                    if points.last() != Some(&(x, y)) {
+                        points.push((x, y));
+                    }
+                    let events = points
+                        .into_iter()
+                        .map(|(x, y)| MouseEvent {
+                            pos: Point::new(x as f64, y as f64).to_dp(scale),
+                            buttons,
+                            mods,
+                            count: 0,
+                            focus: false,
+                            button: MouseButton::None,
+                            wheel_delta: ScrollDelta::ZERO,
+                            momentum_phase: MomentumPhase::None,
+                            source: input_source(),
+                            coalesced: Vec::new(),
+                        })
+                        .collect();
+                    if let Some(event) = mouse::coalesce_moves(events) {
+                        s.handler.mouse_move(&event);
+                    }
                 });
                 Some(0)
             }
+            WM_INPUT => unsafe {
+                let mut input: RAWINPUT = mem::zeroed();
+                let mut size = mem::size_of::<RAWINPUT>() as UINT;
+                let read = GetRawInputData(
+                    lparam as HRAWINPUT,
+                    RID_INPUT,
+                    &mut input as *mut RAWINPUT as LPVOID,
+                    &mut size,
+                    mem::size_of::<RAWINPUTHEADER>() as UINT,
+                );
+                if read != UINT::MAX && input.header.dwType == RIM_TYPEMOUSE {
+                    let mouse = input.data.mouse();
+                    // Tablets and remote desktop sessions report absolute positions instead.
+                    if mouse.usFlags & MOUSE_MOVE_ABSOLUTE == 0
+                        && (mouse.lLastX != 0 || mouse.lLastY != 0)
+                    {
+                        let delta = Vec2::new(mouse.lLastX as f64, mouse.lLastY as f64);
+                        self.with_wnd_state(|s| s.handler.raw_pointer_motion(delta));
+                    }
+                }
+                // DefWindowProc cleans up after the input.
+                None
+            },
             WM_MOUSELEAVE => {
                 self.with_wnd_state(|s| {
                     s.has_mouse_focus = false;
+                    // Don't report the positions outside the window when the pointer comes back.
+                    s.last_mouse_move_time = 0;
                     s.handler.mouse_leave();
                 });
                 Some(0)
@@ -1045,6 +2043,12 @@ impl WndProc for MyWndProc {
             WM_LBUTTONDBLCLK | WM_LBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONDBLCLK
             | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDBLCLK | WM_MBUTTONDOWN | WM_MBUTTONUP
             | WM_XBUTTONDBLCLK | WM_XBUTTONDOWN | WM_XBUTTONUP => {
+                if matches!(
+                    msg,
+                    WM_LBUTTONDOWN | WM_MBUTTONDOWN | WM_RBUTTONDOWN | WM_XBUTTONDOWN
+                ) {
+                    self.dismiss_popups();
+                }
                 if let Some(button) = match msg {
                     WM_LBUTTONDBLCLK | WM_LBUTTONDOWN | WM_LBUTTONUP => Some(MouseButton::Left),
                     WM_RBUTTONDBLCLK | WM_RBUTTONDOWN | WM_RBUTTONUP => Some(MouseButton::Right),
@@ -1105,7 +2109,10 @@ impl WndProc for MyWndProc {
                             count,
                             focus: false,
                             button,
-                            wheel_delta: Vec2::ZERO,
+                            wheel_delta: ScrollDelta::ZERO,
+                            momentum_phase: MomentumPhase::None,
+                            source: input_source(),
+                            coalesced: Vec::new(),
                         };
                         if count > 0 {
                             s.enter_mouse_capture(hwnd, button);
@@ -1131,16 +2138,40 @@ impl WndProc for MyWndProc {
                 Some(0)
             }
             WM_DESTROY => {
+                dnd::revoke(hwnd);
+                let direct_manipulation = self.with_window_state(|w| w.direct_manipulation.take());
+                drop(direct_manipulation);
+                if self.with_window_state(|w| w.raw_pointer_input.get()) {
+                    register_raw_mouse(None);
+                }
                 self.with_wnd_state(|s| s.handler.destroy());
                 Some(0)
             }
             WM_TIMER => {
                 let id = wparam;
-                unsafe {
-                    KillTimer(hwnd, id);
+                if id == FRAME_THROTTLE_TIMER_ID {
+                    unsafe {
+                        KillTimer(hwnd, id);
+                    }
+                    self.handle.borrow().schedule_paint();
+                    return Some(0);
+                }
+                if id == DIRECT_MANIPULATION_TIMER_ID {
+                    self.with_window_state(|w| {
+                        if let Some(dm) = &*w.direct_manipulation.borrow() {
+                            dm.update();
+                        }
+                    });
+                    return Some(0);
                 }
                 let token = TimerToken::from_raw(id as u64);
-                self.handle.borrow().free_timer_slot(token);
+                // Repeating timers keep going until they're cancelled.
+                if !self.handle.borrow().is_repeating_timer(token) {
+                    unsafe {
+                        KillTimer(hwnd, id);
+                    }
+                    self.handle.borrow().free_timer_slot(token);
+                }
                 self.with_wnd_state(|s| s.handler.timer(token));
                 Some(1)
             }
@@ -1159,6 +2190,107 @@ impl WndProc for MyWndProc {
                 });
                 Some(0)
             }
+            WM_SIZING => {
+                let rect = unsafe { &mut *(lparam as *mut RECT) };
+                let scale = self.scale();
+                self.with_wnd_state(|s| {
+                    let increments = s.resize_increments?.to_px(scale);
+                    let base = s.min_size.unwrap_or(Size::ZERO).to_px(scale);
+                    // The rect is for the whole window, but the increments are for the client area.
+                    let (frame_width, frame_height) = unsafe {
+                        let mut window_rect = mem::zeroed();
+                        let mut client_rect = mem::zeroed();
+                        if GetWindowRect(hwnd, &mut window_rect) == 0
+                            || GetClientRect(hwnd, &mut client_rect) == 0
+                        {
+                            return None;
+                        }
+                        (
+                            (window_rect.right - window_rect.left)
+                                - (client_rect.right - client_rect.left),
+                            (window_rect.bottom - window_rect.top)
+                                - (client_rect.bottom - client_rect.top),
+                        )
+                    };
+                    let client = Size::new(
+                        (rect.right - rect.left - frame_width) as f64,
+                        (rect.bottom - rect.top - frame_height) as f64,
+                    );
+                    let client = common_util::snap_to_increments(client, base, increments);
+                    let width = client.width.round() as i32 + frame_width;
+                    let height = client.height.round() as i32 + frame_height;
+                    // Keep the edge opposite the one being dragged in place.
+                    match wparam as u32 {
+                        WMSZ_LEFT | WMSZ_TOPLEFT | WMSZ_BOTTOMLEFT => {
+                            rect.left = rect.right - width
+                        }
+                        _ => rect.right = rect.left + width,
+                    }
+                    match wparam as u32 {
+                        WMSZ_TOP | WMSZ_TOPLEFT | WMSZ_TOPRIGHT => rect.top = rect.bottom - height,
+                        _ => rect.bottom = rect.top + height,
+                    }
+                    Some(TRUE as LRESULT)
+                })
+                .flatten()
+            }
+            DS_DRAG_EVENT => {
+                let message = unsafe { &mut *(lparam as *mut DragMessage) };
+                let pos =
+                    Point::new(message.pos.x as f64, message.pos.y as f64).to_dp(self.scale());
+                self.with_wnd_state(|s| {
+                    message.effect = match message.kind {
+                        DragKind::Enter => s.handler.drag_enter(pos, message.data),
+                        DragKind::Over => s.handler.drag_over(pos, message.data),
+                        DragKind::Drop => s.handler.drag_drop(pos, message.data),
+                        DragKind::Leave => {
+                            s.handler.drag_leave();
+                            DropEffect::None
+                        }
+                    };
+                });
+                Some(0)
+            }
+            DM_POINTERHITTEST => {
+                let handled = self.with_window_state(|w| {
+                    w.direct_manipulation
+                        .borrow()
+                        .as_ref()
+                        .map_or(false, |dm| dm.pointer_hit_test(wparam))
+                });
+                if handled {
+                    Some(0)
+                } else {
+                    None
+                }
+            }
+            DS_SCROLL_EVENT => {
+                let message = unsafe { &*(lparam as *const ScrollMessage) };
+                let mut p = POINT { x: 0, y: 0 };
+                unsafe {
+                    GetCursorPos(&mut p);
+                    ScreenToClient(hwnd, &mut p);
+                }
+                let scale = self.scale();
+                let pos = Point::new(p.x as f64, p.y as f64).to_dp(scale);
+                let delta = Vec2::new(message.delta.x / scale.x(), message.delta.y / scale.y());
+                self.with_wnd_state(|s| {
+                    let event = MouseEvent {
+                        pos,
+                        buttons: MouseButtons::new(),
+                        mods: s.keyboard_state.get_modifiers(),
+                        count: 0,
+                        focus: false,
+                        button: MouseButton::None,
+                        wheel_delta: ScrollDelta::Pixels(delta),
+                        momentum_phase: message.phase,
+                        source: input_source(),
+                        coalesced: Vec::new(),
+                    };
+                    s.handler.wheel(&event);
+                });
+                Some(0)
+            }
             DS_RUN_IDLE => self
                 .with_wnd_state(|s| {
                     let queue = self.handle.borrow().take_idle_queue();
@@ -1215,7 +2347,9 @@ impl WindowBuilder {
         WindowBuilder {
             app,
             handler: None,
+            group: None,
             title: String::new(),
+            taskbar_group: None,
             menu: None,
             resizable: true,
             show_titlebar: true,
@@ -1223,9 +2357,15 @@ impl WindowBuilder {
             present_strategy: Default::default(),
             size: None,
             min_size: None,
+            resize_increments: None,
             position: None,
             level: None,
             state: window::WindowState::Restored,
+            focusable: true,
+            activate_on_show: true,
+            show_after_first_paint: false,
+            forced_scale: None,
+            allow_elevated_drop: false,
         }
     }
 
@@ -1234,6 +2374,10 @@ impl WindowBuilder {
         self.handler = Some(handler);
     }
 
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
     pub fn set_size(&mut self, size: Size) {
         self.size = Some(size);
     }
@@ -1242,6 +2386,10 @@ impl WindowBuilder {
         self.min_size = Some(size);
     }
 
+    pub fn set_resize_increments(&mut self, increments: Size) {
+        self.resize_increments = Some(increments);
+    }
+
     pub fn resizable(&mut self, resizable: bool) {
         self.resizable = resizable;
     }
@@ -1267,6 +2415,14 @@ impl WindowBuilder {
         self.title = title.into();
     }
 
+    pub fn set_taskbar_group(&mut self, group: String) {
+        self.taskbar_group = Some(group);
+    }
+
+    pub fn set_restoration_id(&mut self, _id: String) {
+        // Ignored: only macOS restores windows.
+    }
+
     pub fn set_menu(&mut self, menu: Menu) {
         self.menu = Some(menu);
     }
@@ -1280,10 +2436,36 @@ impl WindowBuilder {
     }
 
     pub fn set_level(&mut self, level: WindowLevel) {
+        if let WindowLevel::Layer { .. } = level {
+            warn!("layer windows are only supported on wayland");
+        }
         self.level = Some(level)
     }
 
-    pub fn build(self) -> Result<WindowHandle, Error> {
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.focusable = focusable;
+    }
+
+    pub fn set_activate_on_show(&mut self, activate_on_show: bool) {
+        self.activate_on_show = activate_on_show;
+    }
+
+    pub fn show_after_first_paint(&mut self, show_after_first_paint: bool) {
+        self.show_after_first_paint = show_after_first_paint;
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
+    pub fn allow_elevated_drop(&mut self, allow: bool) {
+        self.allow_elevated_drop = allow;
+    }
+
+    pub fn build(mut self) -> Result<WindowHandle, Error> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
         unsafe {
             let class_name = util::CLASS_NAME.to_wide();
             let wndproc = MyWndProc {
@@ -1326,7 +2508,7 @@ impl WindowBuilder {
             if let Some(level) = self.level {
                 window_level = level.clone();
                 match level {
-                    WindowLevel::AppWindow => (),
+                    WindowLevel::AppWindow | WindowLevel::Layer { .. } => (),
                     WindowLevel::Tooltip(parent_window_handle)
                     | WindowLevel::DropDown(parent_window_handle)
                     | WindowLevel::Modal(parent_window_handle) => {
@@ -1352,7 +2534,14 @@ impl WindowBuilder {
                 // Default window level
                 window_level = WindowLevel::AppWindow;
             }
+            if !self.focusable {
+                dwExStyle |= WS_EX_NOACTIVATE;
+                focusable = false;
+            }
 
+            let autoscroll = Rc::new(Autoscroll::default());
+            let shortcut_capture = Rc::new(ShortcutCapture::default());
+            let drop_formats = Rc::new(RefCell::new(Vec::new()));
             let window = WindowState {
                 hwnd: Cell::new(0 as HWND),
                 scale: Cell::new(scale),
@@ -1368,8 +2557,29 @@ impl WindowBuilder {
                 is_transparent: Cell::new(self.transparent),
                 handle_titlebar: Cell::new(false),
                 active_text_input: Cell::new(None),
+                key_repeat: Cell::new(true),
+                text_scale_factor: Cell::new(screen::text_scale_factor()),
+                high_contrast_colors: Cell::new(screen::high_contrast_colors()),
+                autoscroll: autoscroll.clone(),
+                shortcut_capture: shortcut_capture.clone(),
+                pointer_confinement: Cell::new(None),
+                pointer_lock: Cell::new(None),
                 is_focusable: focusable,
+                activate_on_show: self.activate_on_show,
+                hidden_until_paint: Cell::new(self.show_after_first_paint),
+                anim_frame_requested: Cell::new(false),
+                frame_throttle: FrameThrottle::default(),
+                forced_scale: self.forced_scale,
                 window_level,
+                popups: RefCell::new(Vec::new()),
+                thumbnail: RefCell::new(None),
+                peek_preview: RefCell::new(None),
+                raw_pointer_input: Cell::new(false),
+                pen_gestures: Cell::new(true),
+                fullscreen_restore: Cell::new(None),
+                kiosk: Cell::new(KioskOptions::OFF),
+                drop_formats: drop_formats.clone(),
+                direct_manipulation: RefCell::new(None),
                 #[cfg(feature = "accesskit")]
                 uia_init_marker: UiaInitMarker::new(),
                 #[cfg(feature = "accesskit")]
@@ -1381,14 +2591,18 @@ impl WindowBuilder {
             };
 
             let state = WndState {
-                handler: self.handler.unwrap(),
+                handler: dispatch::wrap(self.handler.unwrap(), &autoscroll, &shortcut_capture),
                 min_size: self.min_size,
+                resize_increments: self.resize_increments,
                 keyboard_state: KeyboardState::new(),
                 captured_mouse_buttons: MouseButtons::new(),
                 has_mouse_focus: false,
+                last_mouse_move_time: 0,
                 last_click_time: Instant::now(),
                 last_click_pos: (0, 0),
                 click_count: 0,
+                ime_composing: false,
+                wheel_remainder: Vec2::ZERO,
             };
             win.wndproc.connect(&handle, state);
 
@@ -1427,6 +2641,26 @@ impl WindowBuilder {
                 return Err(Error::NullHwnd);
             }
 
+            if self.allow_elevated_drop {
+                allow_elevated_drop(hwnd);
+            }
+            if let Some(group) = &self.taskbar_group {
+                if let Err(e) = set_app_user_model_id(hwnd, group) {
+                    warn!("failed to set the taskbar group: {}", e);
+                }
+            }
+            dnd::register(hwnd, drop_formats);
+            let mut rect: RECT = mem::zeroed();
+            GetClientRect(hwnd, &mut rect);
+            match DirectManipulation::new(hwnd, rect.right, rect.bottom) {
+                Ok(dm) => {
+                    if let Some(state) = handle.state.upgrade() {
+                        *state.direct_manipulation.borrow_mut() = Some(dm);
+                    }
+                }
+                Err(e) => tracing::info!("no smooth touchpad scrolling: {}", e),
+            }
+
             if let Some(size_dp) = self.size {
                 if let Ok(scale) = handle.get_scale() {
                     let size_px = size_dp.to_px(scale);
@@ -1448,20 +2682,20 @@ impl WindowBuilder {
                 }
             }
 
-            // Dark mode support
-            // https://docs.microsoft.com/en-us/windows/apps/desktop/modernize/apply-windows-themes
-            const DWMWA_USE_IMMERSIVE_DARK_MODE: u32 = 20;
-            let value: BOOL = 1;
-            let value_ptr = &value as *const _ as *const c_void;
-            DwmSetWindowAttribute(
-                hwnd,
-                DWMWA_USE_IMMERSIVE_DARK_MODE,
-                value_ptr,
-                mem::size_of::<BOOL>() as u32,
-            );
+            set_dark_mode(hwnd, true);
 
             self.app.add_window(hwnd);
 
+            if let Some(state) = handle.state.upgrade() {
+                if let WindowLevel::Tooltip(parent) | WindowLevel::DropDown(parent) =
+                    &state.window_level
+                {
+                    if let Some(parent_state) = parent.0.state.upgrade() {
+                        parent_state.popups.borrow_mut().push(handle.clone());
+                    }
+                }
+            }
+
             if let Some(accels) = accels {
                 register_accel(hwnd, &accels);
             }
@@ -1522,6 +2756,30 @@ pub(crate) unsafe extern "system" fn win_proc_dispatch(
     }
 }
 
+impl crate::platform::windows::WindowBuilderExt for crate::WindowBuilder {
+    fn allow_elevated_drop(&mut self, allow: bool) {
+        self.0.allow_elevated_drop(allow);
+    }
+}
+
+impl crate::platform::windows::WindowHandleExt for crate::WindowHandle {
+    fn set_thumbnail(&self, bitmap: Option<ThumbnailBitmap>) {
+        self.0.set_thumbnail(bitmap);
+    }
+
+    fn set_peek_preview(&self, bitmap: Option<ThumbnailBitmap>) {
+        self.0.set_peek_preview(bitmap);
+    }
+
+    fn set_thumbnail_clip(&self, rect: Option<Rect>) {
+        self.0.set_thumbnail_clip(rect);
+    }
+
+    fn set_pen_gestures_enabled(&self, enabled: bool) {
+        self.0.set_pen_gestures_enabled(enabled);
+    }
+}
+
 /// Create a window (same parameters as CreateWindowExW) with associated WndProc.
 #[allow(clippy::too_many_arguments)]
 unsafe fn create_window(
@@ -1569,6 +2827,9 @@ impl Cursor {
             Cursor::NotAllowed => IDC_NO,
             Cursor::ResizeLeftRight => IDC_SIZEWE,
             Cursor::ResizeUpDown => IDC_SIZENS,
+            Cursor::AllScroll => IDC_SIZEALL,
+            Cursor::Wait => IDC_WAIT,
+            Cursor::Progress => IDC_APPSTARTING,
             Cursor::Custom(c) => {
                 return (c.0).0;
             }
@@ -1581,7 +2842,7 @@ impl WindowHandle {
     pub fn show(&self) {
         if let Some(w) = self.state.upgrade() {
             let hwnd = w.hwnd.get();
-            let show = if w.is_focusable {
+            let show = if w.is_focusable && w.activate_on_show {
                 match self.get_window_state() {
                     window::WindowState::Maximized => SW_MAXIMIZE,
                     window::WindowState::Minimized => SW_MINIMIZE,
@@ -1590,6 +2851,9 @@ impl WindowHandle {
             } else {
                 SW_SHOWNOACTIVATE
             };
+            if w.hidden_until_paint.get() {
+                set_cloaked(hwnd, true);
+            }
             unsafe {
                 ShowWindow(hwnd, show);
                 UpdateWindow(hwnd);
@@ -1612,9 +2876,140 @@ impl WindowHandle {
         warn!("bring_to_front_and_focus not yet implemented on windows");
     }
 
+    pub fn raise_above(&self, other: &WindowHandle) {
+        if let Some(other) = other.get_hwnd() {
+            self.defer(DeferredOp::RaiseAbove(other));
+        }
+    }
+
+    pub fn lower_below(&self, other: &WindowHandle) {
+        if let Some(other) = other.get_hwnd() {
+            self.defer(DeferredOp::LowerBelow(other));
+        }
+    }
+
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        self.defer(DeferredOp::SetMousePassthrough(passthrough));
+    }
+
+    pub fn set_shape(&self, shape: Option<BezPath>) {
+        self.defer(DeferredOp::SetShape(shape));
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        if let Some(w) = self.state.upgrade() {
+            w.key_repeat.set(enabled);
+        }
+    }
+
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        // Cancelling the composition sends messages to the window, so this can't happen while
+        // the handler is running.
+        self.defer(DeferredOp::SetImeAllowed(allowed));
+    }
+
+    pub fn set_thumbnail(&self, bitmap: Option<ThumbnailBitmap>) {
+        if let Some(w) = self.state.upgrade() {
+            w.thumbnail.replace(bitmap);
+            self.update_iconic_representation(&w);
+        }
+    }
+
+    pub fn set_peek_preview(&self, bitmap: Option<ThumbnailBitmap>) {
+        if let Some(w) = self.state.upgrade() {
+            w.peek_preview.replace(bitmap);
+            self.update_iconic_representation(&w);
+        }
+    }
+
+    fn update_iconic_representation(&self, w: &WindowState) {
+        let iconic = w.thumbnail.borrow().is_some() || w.peek_preview.borrow().is_some();
+        set_iconic_representation(w.hwnd.get(), iconic);
+    }
+
+    pub fn set_thumbnail_clip(&self, rect: Option<Rect>) {
+        if let Some(w) = self.state.upgrade() {
+            let clip = rect.map(|rect| {
+                let rect = rect.to_px(w.scale.get()).expand();
+                RECT {
+                    left: rect.x0 as i32,
+                    top: rect.y0 as i32,
+                    right: rect.x1 as i32,
+                    bottom: rect.y1 as i32,
+                }
+            });
+            if let Err(e) = unsafe { set_thumbnail_clip(w.hwnd.get(), clip) } {
+                warn!("failed to set the thumbnail clip: {}", e);
+            }
+        }
+    }
+
+    pub fn set_raw_pointer_input(&self, enabled: bool) {
+        if let Some(w) = self.state.upgrade() {
+            if enabled {
+                if register_raw_mouse(Some(w.hwnd.get())) {
+                    w.raw_pointer_input.set(true);
+                }
+            } else if w.raw_pointer_input.replace(false) {
+                register_raw_mouse(None);
+            }
+        }
+    }
+
+    pub fn set_pen_gestures_enabled(&self, enabled: bool) {
+        if let Some(w) = self.state.upgrade() {
+            w.pen_gestures.set(enabled);
+            // Pointer input doesn't send WM_TABLET_QUERYSYSTEMGESTURESTATUS, but the pen
+            // service also reads this property when the pen touches down.
+            let name = PEN_SERVICE_PROPERTY.to_wide();
+            unsafe {
+                if enabled {
+                    RemovePropW(w.hwnd.get(), name.as_ptr());
+                } else if SetPropW(w.hwnd.get(), name.as_ptr(), PEN_GESTURES_OFF as HANDLE) == 0 {
+                    warn!(
+                        "failed to turn off pen gestures: {}",
+                        Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        let hwnd = self.get_hwnd()?;
+        unsafe {
+            let thread = GetWindowThreadProcessId(hwnd, null_mut());
+            Some(keyboard::layout_from_hkl(GetKeyboardLayout(thread)))
+        }
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        Application::locale_info()
+    }
+
     pub fn request_anim_frame(&self) {
+        if let Some(w) = self.state.upgrade() {
+            w.anim_frame_requested.set(true);
+        }
+        self.schedule_paint();
+    }
+
+    /// Asks for a `WM_PAINT`, as soon as the frame rate limit allows.
+    fn schedule_paint(&self) {
         if let Some(w) = self.state.upgrade() {
             let hwnd = w.hwnd.get();
+            if let Some(deadline) = w.frame_throttle.next_frame_deadline() {
+                // WM_TIMER calls us again once the frame is allowed. We round up, so
+                // that the timer doesn't fire just before the deadline.
+                let elapse = deadline
+                    .checked_duration_since(Instant::now())
+                    .map(|d| d.as_millis() as u32 + 1)
+                    .unwrap_or(0);
+                unsafe {
+                    SetTimer(hwnd, FRAME_THROTTLE_TIMER_ID, elapse, None);
+                }
+                return;
+            }
             unsafe {
                 // With the RDW_INTERNALPAINT flag, RedrawWindow causes a WM_PAINT message, but without
                 // invalidating anything. We do this because we won't know the final invalidated region
@@ -1629,13 +3024,26 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        if let Some(w) = self.state.upgrade() {
+            w.frame_throttle.set_max_frame_rate(fps);
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        match self.state.upgrade() {
+            Some(w) => w.frame_throttle.refresh_interval(),
+            None => common_util::DEFAULT_REFRESH_INTERVAL,
+        }
+    }
+
     pub fn invalidate(&self) {
         if let Some(w) = self.state.upgrade() {
             w.invalid
                 .borrow_mut()
                 .set_rect(w.area.get().size_dp().to_rect());
         }
-        self.request_anim_frame();
+        self.schedule_paint();
     }
 
     pub fn invalidate_rect(&self, rect: Rect) {
@@ -1648,7 +3056,45 @@ impl WindowHandle {
                 .borrow_mut()
                 .add_rect(rect.to_px(scale).expand().to_dp(scale));
         }
-        self.request_anim_frame();
+        self.schedule_paint();
+    }
+
+    pub fn scroll_region(&self, rect: Rect, delta: Vec2) {
+        if let Some(w) = self.state.upgrade() {
+            let scale = w.scale.get();
+            let delta_px = delta.to_px(scale);
+            if (delta_px - delta_px.round()).hypot() > 1e-6 {
+                self.invalidate_rect(rect);
+                return;
+            }
+            let delta_px = delta_px.round();
+            let rect_px = rect.to_px(scale).round();
+            let clip = util::rect_to_recti(rect_px);
+            unsafe {
+                // Without SW_INVALIDATE, Windows leaves the uncovered strip alone, and we
+                // invalidate it ourselves below.
+                let result = ScrollWindowEx(
+                    w.hwnd.get(),
+                    delta_px.x as c_int,
+                    delta_px.y as c_int,
+                    &clip,
+                    &clip,
+                    null_mut(),
+                    null_mut(),
+                    0,
+                );
+                if result == ERROR {
+                    warn!(
+                        "ScrollWindowEx failed: {}",
+                        Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+                    );
+                    self.invalidate_rect(rect);
+                    return;
+                }
+            }
+            w.invalid.borrow_mut().scroll(rect_px.to_dp(scale), delta);
+        }
+        self.schedule_paint();
     }
 
     fn defer(&self, op: DeferredOp) {
@@ -1669,6 +3115,13 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_theme_override(&self, theme: Option<Theme>) {
+        if let Some(w) = self.state.upgrade() {
+            // Without an override we keep the dark titlebar that windows are built with.
+            set_dark_mode(w.hwnd.get(), theme != Some(Theme::Light));
+        }
+    }
+
     pub fn show_titlebar(&self, show_titlebar: bool) {
         self.defer(DeferredOp::ShowTitlebar(show_titlebar));
     }
@@ -1684,7 +3137,7 @@ impl WindowHandle {
                     let screen_position = parent_window_handle.get_position() + position.to_vec2();
                     self.defer(DeferredOp::SetPosition(screen_position));
                 }
-                WindowLevel::AppWindow => {
+                WindowLevel::AppWindow | WindowLevel::Layer { .. } => {
                     self.defer(DeferredOp::SetPosition(position));
                 }
             }
@@ -1715,6 +3168,26 @@ impl WindowHandle {
         Point::new(0.0, 0.0)
     }
 
+    pub fn monitor(&self) -> Option<Monitor> {
+        let origin = self.map_to_screen(Point::ZERO);
+        monitor_containing(Rect::from_origin_size(origin, self.get_size()))
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        if let Some(w) = self.state.upgrade() {
+            let mut origin = POINT { x: 0, y: 0 };
+            unsafe {
+                if ClientToScreen(w.hwnd.get(), &mut origin) == FALSE {
+                    warn!("failed to map the client area to the screen");
+                    return point;
+                }
+            }
+            let origin = Point::new(origin.x as f64, origin.y as f64).to_dp(w.scale.get());
+            return origin + point.to_vec2();
+        }
+        point
+    }
+
     pub fn content_insets(&self) -> Insets {
         if let Some(w) = self.state.upgrade() {
             let hwnd = w.hwnd.get();
@@ -1745,6 +3218,10 @@ impl WindowHandle {
         Insets::ZERO
     }
 
+    pub fn safe_area(&self) -> Insets {
+        Insets::ZERO
+    }
+
     // Sets the size of the window in DP
     pub fn set_size(&self, size: Size) {
         self.defer(DeferredOp::SetSize(size));
@@ -1810,6 +3287,40 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_fullscreen(&self, options: Option<FullscreenOptions>) {
+        self.defer(DeferredOp::SetFullscreen(options));
+    }
+
+    pub fn preferred_output(&self, _monitor: Option<MonitorId>) {
+        // Fullscreen windows cover the monitor that they are on.
+    }
+
+    pub(crate) fn set_kiosk(&self, options: KioskOptions) {
+        let w = match self.state.upgrade() {
+            Some(w) => w,
+            None => return,
+        };
+        let old = w.kiosk.replace(options);
+        if options.block_system_shortcuts != old.block_system_shortcuts {
+            set_shortcuts_blocked(w.hwnd.get(), options.block_system_shortcuts);
+        }
+        unsafe {
+            if options.inhibit_screensaver != old.inhibit_screensaver {
+                let mut flags = ES_CONTINUOUS;
+                if options.inhibit_screensaver {
+                    flags |= ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED;
+                }
+                if SetThreadExecutionState(flags) == 0 {
+                    warn!("failed to change the execution state");
+                }
+            }
+            // The cursor has a display count for each thread, so this has to stay balanced.
+            if options.hide_cursor != old.hide_cursor {
+                ShowCursor((!options.hide_cursor).into());
+            }
+        }
+    }
+
     // Allows windows to handle a custom titlebar like it was the default one.
     pub fn handle_titlebar(&self, val: bool) {
         if let Some(w) = self.state.upgrade() {
@@ -1867,15 +3378,61 @@ impl WindowHandle {
     ///
     /// The return value is an identifier.
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.request_timer_with_tolerance(deadline, Duration::ZERO)
+    }
+
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Instant,
+        tolerance: Duration,
+    ) -> TimerToken {
         let (id, elapse) = self.get_timer_slot(deadline);
+        // Larger values are reserved (TIMERV_NO_COALESCING and friends).
+        let tolerance = tolerance.as_millis().min(0x7FFF_FFF5) as u32;
         let id = self
             .get_hwnd()
             // we reuse timer ids; if this is greater than u32::max we have a problem.
-            .map(|hwnd| unsafe { SetTimer(hwnd, id.into_raw() as usize, elapse, None) as u64 })
+            .map(|hwnd| unsafe {
+                let id = id.into_raw() as usize;
+                // Plain `SetTimer` gets the system's default tolerance, which is all we can do
+                // before Windows 8, and all we need if the caller didn't ask for more.
+                match OPTIONAL_FUNCTIONS.SetCoalescableTimer {
+                    Some(func) if tolerance > 0 => func(hwnd, id, elapse, None, tolerance) as u64,
+                    _ => SetTimer(hwnd, id, elapse, None) as u64,
+                }
+            })
             .unwrap_or(0);
         TimerToken::from_raw(id)
     }
 
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        let (token, _) = self.get_timer_slot(Instant::now());
+        // SetTimer takes at least USER_TIMER_MINIMUM anyway.
+        let elapse = interval.as_millis().clamp(1, USER_TIMER_MAXIMUM as u128) as u32;
+        let id = self
+            .get_hwnd()
+            .map(|hwnd| unsafe { SetTimer(hwnd, token.into_raw() as usize, elapse, None) as u64 })
+            .unwrap_or(0);
+        if id != 0 {
+            if let Some(w) = self.state.upgrade() {
+                w.timers.lock().unwrap().set_repeating(token);
+            }
+        }
+        TimerToken::from_raw(id)
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        // Only free the slot if the timer was still pending, since a one-shot timer frees its
+        // own slot when it fires.
+        if unsafe { KillTimer(hwnd, token.into_raw() as usize) } != 0 {
+            self.free_timer_slot(token);
+        }
+    }
+
     /// Set the cursor icon.
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         unsafe {
@@ -1883,72 +3440,130 @@ impl WindowHandle {
         }
     }
 
-    pub fn make_cursor(&self, cursor_desc: &CursorDesc) -> Option<Cursor> {
-        if let Some(hwnd) = self.get_hwnd() {
+    pub fn confine_pointer(&self, region: Option<Rect>) {
+        if let Some(w) = self.state.upgrade() {
+            let hwnd = w.hwnd.get();
+            let had_region = w.pointer_confinement.replace(region).is_some();
+            if region.is_some() {
+                w.pointer_lock.set(None);
+            }
+            // Confining the cursor affects the whole system, so we only do it while focused.
+            if unsafe { GetFocus() } == hwnd && (region.is_some() || had_region) {
+                clip_cursor(hwnd, region.map(|r| r.to_px(w.scale.get()).round()));
+            }
+        }
+    }
+
+    pub fn lock_pointer(&self) {
+        if let Some(w) = self.state.upgrade() {
+            let hwnd = w.hwnd.get();
+            if unsafe { GetFocus() } != hwnd {
+                warn!("can't lock the pointer of a window without focus");
+                return;
+            }
+            let mut pos = POINT { x: 0, y: 0 };
             unsafe {
-                let hdc = GetDC(hwnd);
-                if hdc.is_null() {
-                    return None;
-                }
-                defer!(ReleaseDC(null_mut(), hdc););
-
-                let mask_dc = CreateCompatibleDC(hdc);
-                if mask_dc.is_null() {
-                    return None;
-                }
-                defer!(DeleteDC(mask_dc););
-
-                let bmp_dc = CreateCompatibleDC(hdc);
-                if bmp_dc.is_null() {
-                    return None;
-                }
-                defer!(DeleteDC(bmp_dc););
-
-                let width = 1; //cursor_desc.image.width();
-                let height = 1; //cursor_desc.image.height();
-                let mask = CreateCompatibleBitmap(hdc, width as c_int, height as c_int);
-                if mask.is_null() {
-                    return None;
-                }
-                defer!(DeleteObject(mask as _););
-
-                let bmp = CreateCompatibleBitmap(hdc, width as c_int, height as c_int);
-                if bmp.is_null() {
-                    return None;
-                }
-                defer!(DeleteObject(bmp as _););
-
-                let old_mask = SelectObject(mask_dc, mask as *mut c_void);
-                let old_bmp = SelectObject(bmp_dc, bmp as *mut c_void);
-
-                // for (row_idx, row) in cursor_desc.image.pixel_colors().enumerate() {
-                //     for (col_idx, p) in row.enumerate() {
-                //         let (r, g, b, a) = p.as_rgba8();
-                //         // TODO: what's the story on partial transparency? I couldn't find documentation.
-                //         let mask_px = RGB(255 - a, 255 - a, 255 - a);
-                //         let bmp_px = RGB(r, g, b);
-                //         SetPixel(mask_dc, col_idx as i32, row_idx as i32, mask_px);
-                //         SetPixel(bmp_dc, col_idx as i32, row_idx as i32, bmp_px);
-                //     }
-                // }
-
-                SelectObject(mask_dc, old_mask);
-                SelectObject(bmp_dc, old_bmp);
-
-                let mut icon_info = ICONINFO {
-                    // 0 means it's a cursor, not an icon.
-                    fIcon: 0,
-                    xHotspot: cursor_desc.hot.x as DWORD,
-                    yHotspot: cursor_desc.hot.y as DWORD,
-                    hbmMask: mask,
-                    hbmColor: bmp,
+                GetCursorPos(&mut pos);
+                ScreenToClient(hwnd, &mut pos);
+            }
+            let (x, y) = (pos.x as f64, pos.y as f64);
+            let pixel = Rect::new(x, y, x + 1.0, y + 1.0);
+            w.pointer_confinement.set(None);
+            w.pointer_lock.set(Some(pixel));
+            clip_cursor(hwnd, Some(pixel));
+        }
+    }
+
+    pub fn unlock_pointer(&self) {
+        if let Some(w) = self.state.upgrade() {
+            let hwnd = w.hwnd.get();
+            if w.pointer_lock.take().is_some() && unsafe { GetFocus() } == hwnd {
+                clip_cursor(hwnd, None);
+            }
+        }
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        if let Some(w) = self.state.upgrade() {
+            w.autoscroll.begin(origin);
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        if let Some(w) = self.state.upgrade() {
+            w.shortcut_capture.begin(callback);
+        }
+    }
+
+    pub fn make_cursor(&self, cursor_desc: &CursorDesc) -> Option<Cursor> {
+        let (width, height) = (cursor_desc.width as i32, cursor_desc.height as i32);
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        unsafe {
+            let mut info: BITMAPINFO = mem::zeroed();
+            info.bmiHeader.biSize = mem::size_of::<BITMAPINFOHEADER>() as u32;
+            info.bmiHeader.biWidth = width;
+            // A negative height makes the rows go from the top down, like ours.
+            info.bmiHeader.biHeight = -height;
+            info.bmiHeader.biPlanes = 1;
+            info.bmiHeader.biBitCount = 32;
+            info.bmiHeader.biCompression = BI_RGB;
+            let mut bits: *mut c_void = null_mut();
+            let bmp = CreateDIBSection(null_mut(), &info, DIB_RGB_COLORS, &mut bits, null_mut(), 0);
+            if bmp.is_null() || bits.is_null() {
+                return None;
+            }
+            defer!(DeleteObject(bmp as _););
+
+            // Cursors want BGRA, without premultiplied alpha.
+            let len = width as usize * height as usize * 4;
+            let dst = std::slice::from_raw_parts_mut(bits as *mut u8, len);
+            for (dst, [r, g, b, a]) in dst.chunks_exact_mut(4).zip(cursor_desc.rgba()) {
+                let straight = |c: u8| match a {
+                    0 => 0,
+                    a => (c as u32 * 255 / a as u32).min(255) as u8,
                 };
-                let icon = CreateIconIndirect(&mut icon_info);
+                dst.copy_from_slice(&[straight(b), straight(g), straight(r), a]);
+            }
 
-                Some(Cursor::Custom(CustomCursor(Arc::new(HCursor(icon)))))
+            // The mask is ignored when the color bitmap has alpha, but it has to be there. The
+            // rows of a monochrome bitmap are padded to 16 bits.
+            let mask_bits = vec![0u8; (width as usize).div_ceil(16) * 2 * height as usize];
+            let mask = CreateBitmap(width, height, 1, 1, mask_bits.as_ptr() as *const c_void);
+            if mask.is_null() {
+                return None;
             }
-        } else {
-            None
+            defer!(DeleteObject(mask as _););
+
+            let mut icon_info = ICONINFO {
+                // 0 means it's a cursor, not an icon.
+                fIcon: 0,
+                xHotspot: cursor_desc.hot.x as DWORD,
+                yHotspot: cursor_desc.hot.y as DWORD,
+                hbmMask: mask,
+                hbmColor: bmp,
+            };
+            let icon = CreateIconIndirect(&mut icon_info);
+            if icon.is_null() {
+                warn!(
+                    "failed to create a cursor: {}",
+                    Error::Hr(HRESULT_FROM_WIN32(GetLastError()))
+                );
+                return None;
+            }
+
+            Some(Cursor::Custom(CustomCursor(Arc::new(HCursor(icon)))))
+        }
+    }
+
+    pub fn start_drag(&self, data: DragData, image: Option<DragImage>) {
+        self.defer(DeferredOp::StartDrag(data, image));
+    }
+
+    pub fn set_drop_formats(&self, formats: &[FormatId]) {
+        if let Some(w) = self.state.upgrade() {
+            *w.drop_formats.borrow_mut() = formats.to_vec();
         }
     }
 
@@ -2002,6 +3617,24 @@ impl WindowHandle {
             .get())
     }
 
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        screen::scrollbar_metrics(self.get_scale().unwrap_or_default())
+    }
+
+    pub fn text_scale_factor(&self) -> f64 {
+        self.state
+            .upgrade()
+            .map(|w| w.text_scale_factor.get())
+            .unwrap_or_else(screen::text_scale_factor)
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        self.state
+            .upgrade()
+            .map(|w| w.high_contrast_colors.get())
+            .unwrap_or_else(screen::high_contrast_colors)
+    }
+
     /// Allocate a timer slot.
     ///
     /// Returns an id and an elapsed time in ms
@@ -2022,6 +3655,13 @@ impl WindowHandle {
         }
     }
 
+    fn is_repeating_timer(&self, token: TimerToken) -> bool {
+        self.state
+            .upgrade()
+            .map(|w| w.timers.lock().unwrap().is_repeating(token))
+            .unwrap_or(false)
+    }
+
     #[cfg(feature = "accesskit")]
     pub fn update_accesskit_if_active(
         &self,
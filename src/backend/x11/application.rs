@@ -15,15 +15,19 @@
 //! X11 implementation of features at the application scope.
 
 use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::{TryFrom, TryInto};
 use std::os::unix::io::RawFd;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Error};
 use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::randr::{self, ConnectionExt as _};
 use x11rb::protocol::render::{self, ConnectionExt as _, Pictformat};
+use x11rb::protocol::xfixes::{self, ConnectionExt as _};
+use x11rb::protocol::xkb as xkb_proto;
 use x11rb::protocol::xproto::{
     self, ConnectionExt, CreateWindowAux, EventMask, Timestamp, Visualtype, WindowClass,
 };
@@ -33,7 +37,14 @@ use x11rb::resource_manager::{
 };
 use x11rb::xcb_ffi::XCBConnection;
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, DispatchPolicy};
+use crate::common_util::IdleTracker;
+use crate::dbus::{MediaCommandSink, Sensors};
+use crate::keyboard::KeyboardLayout;
+use crate::locale::LocaleInfo;
+use crate::media::MediaCommand;
+use crate::scale::Scale;
+use crate::sensors::{SensorEvent, SensorKind};
 
 use super::clipboard::Clipboard;
 use super::util;
@@ -77,6 +88,26 @@ use crate::backend::shared::xkb;
 //
 // The type of _NET_WM_NAME
 //
+// _NET_WM_USER_TIME
+//
+// The time of the last user activity in a window. A value of zero asks the window manager not
+// to focus the window when it is mapped.
+//
+// https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm45805407963072
+//
+// _NET_FRAME_EXTENTS
+//
+// The widths of the decorations that the window manager draws around a window, set by the
+// window manager.
+//
+// https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html
+//
+// _NET_RESTACK_WINDOW
+//
+// A client message asking the window manager to restack a window relative to a sibling.
+//
+// https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm45805407999936
+//
 // CLIPBOARD
 //
 // The name of the clipboard selection; used for implementing copy&paste
@@ -99,11 +130,17 @@ x11rb::atom_manager! {
         _NET_WM_PID,
         _NET_WM_NAME,
         UTF8_STRING,
+        _NET_WM_USER_TIME,
+        _NET_FRAME_EXTENTS,
+        _NET_RESTACK_WINDOW,
+        _NET_WM_STATE,
+        _NET_WM_STATE_FULLSCREEN,
         _NET_WM_WINDOW_TYPE,
         _NET_WM_WINDOW_TYPE_NORMAL,
         _NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
         _NET_WM_WINDOW_TYPE_TOOLTIP,
         _NET_WM_WINDOW_TYPE_DIALOG,
+        _GTK_THEME_VARIANT,
         CLIPBOARD,
         PRIMARY,
         TARGETS,
@@ -173,8 +210,24 @@ pub(crate) struct Application {
     idle_write: RawFd,
     /// Support for the render extension in at least version 0.5?
     render_argb32_pictformat_cursor: Option<Pictformat>,
+    /// Support for the xfixes extension in at least version 2.0?
+    xfixes_shape_supported: bool,
     /// Newest timestamp that we received
     timestamp: Rc<Cell<Timestamp>>,
+    /// Commands from the desktop's media controls, which arrive on the D-Bus thread and are
+    /// delivered to the `AppHandler` from the idle loop.
+    media_commands: Arc<Mutex<Vec<MediaCommand>>>,
+    /// Readings from the enabled sensors, which arrive on their own threads and are delivered
+    /// to the `AppHandler` from the idle loop.
+    sensor_events: Arc<Mutex<Vec<SensorEvent>>>,
+    /// The sensors that the application enabled.
+    sensors: Rc<Sensors>,
+    /// The order in which the event loop delivers input, timers and idle callbacks.
+    dispatch_policy: Rc<Cell<DispatchPolicy>>,
+    /// Whether RandR told us that the monitors changed since we last told the `AppHandler`.
+    monitors_changed: Cell<bool>,
+    /// Tracks our input, to tell the `AppHandler` when the application goes idle.
+    idle: Rc<IdleTracker>,
 }
 
 /// The mutable `Application` state.
@@ -183,7 +236,12 @@ struct State {
     quitting: bool,
     /// A collection of all the `Application` windows.
     windows: HashMap<u32, Rc<Window>>,
+    xkb_context: xkb::Context,
+    /// The core keyboard, whose keymap we use unless the application installed its own.
+    xkb_device_id: xkb::DeviceId,
     xkb_state: xkb::State,
+    /// The keys that are currently held down, for telling repeats apart from new presses.
+    held_keys: HashSet<xproto::Keycode>,
 }
 
 #[derive(Clone, Debug)]
@@ -195,6 +253,9 @@ pub(crate) struct Cursors {
     pub not_allowed: Option<xproto::Cursor>,
     pub row_resize: Option<xproto::Cursor>,
     pub col_resize: Option<xproto::Cursor>,
+    pub all_scroll: Option<xproto::Cursor>,
+    pub wait: Option<xproto::Cursor>,
+    pub progress: Option<xproto::Cursor>,
 }
 
 impl Application {
@@ -215,6 +276,43 @@ impl Application {
         conn.xkb_use_extension(1, 0)?
             .reply()
             .context("init xkb extension")?;
+        // The server tells us when the modifiers or the active layout change, and we only look
+        // keys up in its state. xkb calls layouts "groups".
+        let state_changes = u16::from(
+            xkb_proto::StatePart::MODIFIER_STATE
+                | xkb_proto::StatePart::MODIFIER_BASE
+                | xkb_proto::StatePart::MODIFIER_LATCH
+                | xkb_proto::StatePart::MODIFIER_LOCK
+                | xkb_proto::StatePart::GROUP_STATE
+                | xkb_proto::StatePart::GROUP_BASE
+                | xkb_proto::StatePart::GROUP_LATCH
+                | xkb_proto::StatePart::GROUP_LOCK,
+        );
+        conn.xkb_select_events(
+            xkb_proto::ID::USE_CORE_KBD.into(),
+            0u16,
+            0u16,
+            0u16,
+            0u16,
+            &xkb_proto::SelectEventsAux::new().bitcase2(xkb_proto::SelectEventsAuxBitcase2 {
+                affect_state: state_changes,
+                state_details: state_changes,
+            }),
+        )?
+        .check()
+        .context("select xkb state events")?;
+        // By default X reports key repeats as release and press pairs, which look just like the
+        // user pressing the key again. With this, we only get the presses.
+        conn.xkb_per_client_flags(
+            xkb_proto::ID::USE_CORE_KBD.into(),
+            xkb_proto::PerClientFlag::DETECTABLE_AUTO_REPEAT,
+            xkb_proto::PerClientFlag::DETECTABLE_AUTO_REPEAT,
+            0u32,
+            0u32,
+            0u32,
+        )?
+        .reply()
+        .context("enable detectable auto repeat")?;
         let device_id = xkb_context
             .core_keyboard_device_id(&conn)
             .context("get core keyboard device id")?;
@@ -223,13 +321,18 @@ impl Application {
             .keymap_from_device(&conn, device_id)
             .context("key map from device")?;
 
-        let xkb_state = keymap.state();
+        let xkb_state = keymap
+            .state_from_device(&conn, device_id)
+            .context("xkb state from device")?;
         let connection = Rc::new(conn);
         let window_id = Application::create_event_window(&connection, screen_num)?;
         let state = Rc::new(RefCell::new(State {
             quitting: false,
             windows: HashMap::new(),
+            xkb_context,
+            xkb_device_id: device_id,
             xkb_state,
+            held_keys: HashSet::new(),
         }));
 
         let (idle_read, idle_write) = nix::unistd::pipe2(nix::fcntl::OFlag::O_NONBLOCK)?;
@@ -264,6 +367,13 @@ impl Application {
             None
         };
 
+        let xfixes_shape_supported = matches!(connection
+            .extension_information(xfixes::X11_EXTENSION_NAME)?
+            .and_then(|_| connection.xfixes_query_version(2, 0).ok())
+            .map(|cookie| cookie.reply())
+            .transpose()?,
+            Some(version) if version.major_version >= 2);
+
         let handle = x11rb::cursor::Handle::new(connection.as_ref(), screen_num, &rdb)?.reply()?;
         let load_cursor = |cursor| {
             handle
@@ -280,6 +390,9 @@ impl Application {
             not_allowed: load_cursor("not-allowed"),
             row_resize: load_cursor("row-resize"),
             col_resize: load_cursor("col-resize"),
+            all_scroll: load_cursor("all-scroll"),
+            wait: load_cursor("wait"),
+            progress: load_cursor("progress"),
         };
 
         let atoms = Rc::new(
@@ -297,6 +410,23 @@ impl Application {
             .ok_or_else(|| anyhow!("Couldn't get visual from screen"))?;
         let argb_visual_type = util::get_argb_visual_type(&connection, screen)?;
 
+        // Monitors being added, removed or rearranged changes the CRTCs and outputs, which
+        // RandR tells us about from version 1.2.
+        if connection
+            .extension_information(randr::X11_EXTENSION_NAME)?
+            .is_some()
+        {
+            let version = connection.randr_query_version(1, 2)?.reply()?;
+            if (version.major_version, version.minor_version) >= (1, 2) {
+                connection.randr_select_input(
+                    screen.root,
+                    randr::NotifyMask::SCREEN_CHANGE
+                        | randr::NotifyMask::CRTC_CHANGE
+                        | randr::NotifyMask::OUTPUT_CHANGE,
+                )?;
+            }
+        }
+
         let timestamp = Rc::new(Cell::new(x11rb::CURRENT_TIME));
         let pending_events = Default::default();
         let clipboard = Clipboard::new(
@@ -316,6 +446,15 @@ impl Application {
             Rc::clone(&timestamp),
         );
 
+        let sensor_events: Arc<Mutex<Vec<SensorEvent>>> = Default::default();
+        let sensors = Rc::new(Sensors::new({
+            let queue = sensor_events.clone();
+            Arc::new(move |event| {
+                queue.lock().unwrap().push(event);
+                wake_idle_pipe(idle_write);
+            })
+        }));
+
         Ok(Application {
             connection,
             rdb,
@@ -333,16 +472,53 @@ impl Application {
             pending_events: Default::default(),
             marker: std::marker::PhantomData,
             render_argb32_pictformat_cursor,
+            xfixes_shape_supported,
             timestamp,
+            media_commands: Default::default(),
+            sensor_events,
+            sensors,
+            dispatch_policy: Default::default(),
+            monitors_changed: Cell::new(false),
+            idle: Default::default(),
         })
     }
 
+    /// The scale that the user chose with `Xft.dpi`, or with the `DRUID_X11_DPI` environment
+    /// variable.
+    pub(crate) fn dpi_scale(&self) -> Scale {
+        let env_dpi = std::env::var("DRUID_X11_DPI")
+            .ok()
+            .map(|x| x.parse::<f64>());
+        match env_dpi.or_else(|| self.rdb.get_value("Xft.dpi", "").transpose()) {
+            Some(Ok(dpi)) => {
+                let scale = dpi / 96.;
+                Scale::new(scale, scale)
+            }
+            None => Scale::default(),
+            Some(Err(err)) => {
+                let default = Scale::default();
+                tracing::warn!(
+                    "Unable to parse dpi: {:?}, defaulting to {:?}",
+                    err,
+                    default
+                );
+                default
+            }
+        }
+    }
+
     /// Return the ARGB32 pictformat of the server, but only if RENDER's CreateCursor is supported
     #[inline]
     pub(crate) fn render_argb32_pictformat_cursor(&self) -> Option<Pictformat> {
         self.render_argb32_pictformat_cursor
     }
 
+    /// Whether we can set window shapes (and in particular input regions) with xfixes.
+    #[inline]
+    pub(crate) fn xfixes_shape_supported(&self) -> bool {
+        self.xfixes_shape_supported
+    }
+
     fn create_event_window(conn: &Rc<XCBConnection>, screen_num: usize) -> Result<u32, Error> {
         let id = conn.generate_id()?;
         let setup = conn.setup();
@@ -461,6 +637,16 @@ impl Application {
             };
             self.timestamp.set(timestamp);
         }
+        if matches!(
+            ev,
+            Event::KeyPress(_)
+                | Event::KeyRelease(_)
+                | Event::ButtonPress(_)
+                | Event::ButtonRelease(_)
+                | Event::MotionNotify(_)
+        ) {
+            self.idle.input();
+        }
         match ev {
             // NOTE: When adding handling for any of the following events,
             //       there must be a check against self.window_id
@@ -475,16 +661,25 @@ impl Application {
                     .context("EXPOSE - failed to get window")?;
                 w.handle_expose(ev).context("EXPOSE - failed to handle")?;
             }
+            Event::GraphicsExposure(ev) => {
+                let w = self
+                    .window(ev.drawable)
+                    .context("GRAPHICS_EXPOSURE - failed to get window")?;
+                w.handle_graphics_exposure(ev)
+                    .context("GRAPHICS_EXPOSURE - failed to handle")?;
+            }
             Event::KeyPress(ev) => {
                 let w = self
                     .window(ev.event)
                     .context("KEY_PRESS - failed to get window")?;
                 let hw_keycode = ev.detail;
                 let mut state = borrow_mut!(self.state)?;
-                let key_event = state.xkb_state.key_event(
+                let repeat = !state.held_keys.insert(hw_keycode);
+                // The server tells us how keys change the state, with `XkbStateNotify`.
+                let key_event = state.xkb_state.server_key_event(
                     hw_keycode as _,
                     keyboard_types::KeyState::Down,
-                    false,
+                    repeat,
                 );
 
                 w.handle_key_event(key_event);
@@ -495,10 +690,12 @@ impl Application {
                     .context("KEY_PRESS - failed to get window")?;
                 let hw_keycode = ev.detail;
                 let mut state = borrow_mut!(self.state)?;
-                let key_event =
-                    state
-                        .xkb_state
-                        .key_event(hw_keycode as _, keyboard_types::KeyState::Up, false);
+                state.held_keys.remove(&hw_keycode);
+                let key_event = state.xkb_state.server_key_event(
+                    hw_keycode as _,
+                    keyboard_types::KeyState::Up,
+                    false,
+                );
 
                 w.handle_key_event(key_event);
             }
@@ -531,7 +728,24 @@ impl Application {
                 let w = self
                     .window(ev.event)
                     .context("MOTION_NOTIFY - failed to get window")?;
-                w.handle_motion_notify(ev)?;
+                // High-rate mice send motions faster than we handle them. Deliver the ones that
+                // have already arrived together.
+                let mut motions = vec![*ev];
+                loop {
+                    match self.connection.poll_for_event()? {
+                        Some(Event::MotionNotify(next))
+                            if next.event == ev.event && next.state == ev.state =>
+                        {
+                            motions.push(next);
+                        }
+                        Some(other) => {
+                            self.pending_events.borrow_mut().push_back(other);
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+                w.handle_motion_notify(&motions)?;
             }
             Event::ClientMessage(ev) => {
                 let w = self
@@ -599,10 +813,36 @@ impl Application {
                     .context("FOCUS_IN - failed to get window")?;
                 w.handle_got_focus();
             }
+            Event::XkbStateNotify(ev) => {
+                let mut state = borrow_mut!(self.state)?;
+                let old_layout = state.xkb_state.active_layout();
+                state.xkb_state.update_mask(
+                    ev.base_mods.into(),
+                    ev.latched_mods.into(),
+                    ev.locked_mods.into(),
+                    ev.base_group.into(),
+                    ev.latched_group.into(),
+                    u8::from(ev.locked_group).into(),
+                );
+                if old_layout != state.xkb_state.active_layout() {
+                    let windows = state.windows.values().cloned().collect::<Vec<_>>();
+                    drop(state);
+                    if let Some(layout) = self.keyboard_layout() {
+                        for w in windows {
+                            w.handle_input_language_changed(layout.clone());
+                        }
+                    }
+                }
+            }
+            Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => {
+                self.monitors_changed.set(true);
+            }
             Event::FocusOut(ev) => {
                 let w = self
                     .window(ev.event)
                     .context("FOCUS_OUT - failed to get window")?;
+                // We won't hear about keys being released while we don't have focus.
+                borrow_mut!(self.state)?.held_keys.clear();
                 w.handle_lost_focus();
             }
             Event::Error(e) => {
@@ -616,7 +856,7 @@ impl Application {
         Ok(false)
     }
 
-    fn run_inner(self) -> Result<(), Error> {
+    fn run_inner(self, mut handler: Option<Box<dyn AppHandler>>) -> Result<(), Error> {
         // Try to figure out the refresh rate of the current screen. We run the idle loop at that
         // rate. The rate-limiting of the idle loop has two purposes:
         //  - When the present extension is disabled, we paint in the idle loop. By limiting the
@@ -634,6 +874,7 @@ impl Application {
                     .windows
                     .values()
                     .filter_map(|w| w.next_timeout())
+                    .chain(self.idle.deadline())
                     .min()
             } else {
                 tracing::error!("Getting next timeout, application state already borrowed");
@@ -663,20 +904,11 @@ impl Application {
                 .context("Error while waiting for X11 connection")?;
             }
 
-            while let Some(ev) = event {
-                match self.handle_event(&ev) {
-                    Ok(quit) => {
-                        if quit {
-                            return Ok(());
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Error handling event: {:#}", e);
-                    }
-                }
-                event = self.connection.poll_for_event()?;
+            if self.dispatch_events(event)? {
+                return Ok(());
             }
 
+            let input_first = self.dispatch_policy.get() == DispatchPolicy::InputFirst;
             let now = Instant::now();
             if let Some(timeout) = next_timeout {
                 if timeout <= now {
@@ -685,6 +917,10 @@ impl Application {
                         drop(state);
                         for w in values {
                             w.run_timers(now);
+                            // Don't let one window's timers hold up input to the others.
+                            if input_first && self.dispatch_events(None)? {
+                                return Ok(());
+                            }
                         }
                     } else {
                         tracing::error!("In timer loop, application state already borrowed");
@@ -692,6 +928,10 @@ impl Application {
                 }
             }
             if now >= next_idle_time {
+                // Input that arrived while the timers ran goes before the idle callbacks.
+                if input_first && self.dispatch_events(None)? {
+                    return Ok(());
+                }
                 last_idle_time = now;
                 drain_idle_pipe(self.idle_read)?;
 
@@ -702,12 +942,59 @@ impl Application {
                 } else {
                     tracing::error!("In idle loop, application state already borrowed");
                 }
+
+                let commands = std::mem::take(&mut *self.media_commands.lock().unwrap());
+                let sensor_events = std::mem::take(&mut *self.sensor_events.lock().unwrap());
+                if let Some(handler) = handler.as_mut() {
+                    for command in commands {
+                        handler.media_command(command);
+                    }
+                    for event in sensor_events {
+                        handler.sensor(event);
+                    }
+                    // A change usually comes as several events, which we report once.
+                    if self.monitors_changed.take() {
+                        handler.monitors_changed();
+                    }
+                }
             }
+            if let Some(handler) = handler.as_mut() {
+                self.idle.dispatch(&mut **handler);
+            }
+        }
+    }
+
+    /// Handles `event`, and then every event that is already queued, without blocking.
+    ///
+    /// Returns `true` if the application should quit.
+    fn dispatch_events(&self, mut event: Option<Event>) -> Result<bool, Error> {
+        if event.is_none() {
+            event = self.pending_events.borrow_mut().pop_front();
+        }
+        if event.is_none() {
+            event = self.connection.poll_for_event()?;
         }
+        while let Some(ev) = event {
+            match self.handle_event(&ev) {
+                Ok(quit) => {
+                    if quit {
+                        return Ok(true);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Error handling event: {:#}", e);
+                }
+            }
+            event = match self.pending_events.borrow_mut().pop_front() {
+                Some(ev) => Some(ev),
+                None => self.connection.poll_for_event()?,
+            };
+        }
+        Ok(false)
     }
 
-    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
-        if let Err(e) = self.run_inner() {
+    pub fn run(self, handler: Option<Box<dyn AppHandler>>) {
+        if let Err(e) = self.run_inner(handler) {
             tracing::error!("{}", e);
         }
     }
@@ -746,19 +1033,129 @@ impl Application {
         self.clipboard.clone()
     }
 
+    pub fn enable_sensor(&self, kind: SensorKind) -> bool {
+        self.sensors.enable(kind)
+    }
+
+    pub fn disable_sensor(&self, kind: SensorKind) {
+        self.sensors.disable(kind)
+    }
+
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        // The event loop picks up the new deadline the next time around.
+        self.idle.set_timeout(timeout);
+    }
+
+    pub fn set_dispatch_policy(&self, policy: DispatchPolicy) {
+        self.dispatch_policy.set(policy);
+    }
+
+    pub fn keyboard_layouts(&self) -> Vec<KeyboardLayout> {
+        match self.state.try_borrow() {
+            Ok(state) => (0..state.xkb_state.num_layouts())
+                .filter_map(|idx| state.xkb_state.layout_name(idx))
+                .map(keyboard_layout)
+                .collect(),
+            Err(_) => {
+                tracing::error!("Getting keyboard layouts, application state already borrowed");
+                Vec::new()
+            }
+        }
+    }
+
+    /// The keyboard layout that is currently in effect.
+    pub(crate) fn keyboard_layout(&self) -> Option<KeyboardLayout> {
+        let state = self.state.try_borrow().ok()?;
+        let idx = state.xkb_state.active_layout();
+        state.xkb_state.layout_name(idx).map(keyboard_layout)
+    }
+
     pub fn get_locale() -> String {
         linux::env::locale()
     }
 
+    pub fn locale_info() -> LocaleInfo {
+        linux::env::locale_info()
+    }
+
     pub(crate) fn idle_pipe(&self) -> RawFd {
         self.idle_write
     }
+
+    pub(crate) fn media_command_sink(&self) -> Option<MediaCommandSink> {
+        let queue = self.media_commands.clone();
+        let idle_write = self.idle_write;
+        Some(Arc::new(move |command| {
+            queue.lock().unwrap().push(command);
+            wake_idle_pipe(idle_write);
+        }))
+    }
 }
 
 impl crate::platform::linux::ApplicationExt for crate::Application {
     fn primary_clipboard(&self) -> crate::Clipboard {
         self.backend_app.primary.clone().into()
     }
+
+    fn xkb_keymap(&self) -> Option<String> {
+        let state = self.backend_app.state.try_borrow().ok()?;
+        state.xkb_state.keymap().as_string()
+    }
+
+    fn set_xkb_keymap(&self, keymap: Option<crate::platform::linux::XkbKeymap>) -> bool {
+        let mut state = match self.backend_app.state.try_borrow_mut() {
+            Ok(state) => state,
+            Err(_) => {
+                tracing::error!("Setting xkb keymap, application state already borrowed");
+                return false;
+            }
+        };
+        let keymap = match &keymap {
+            Some(keymap) => state.xkb_context.compile(keymap),
+            None => state
+                .xkb_context
+                .keymap_from_device(&self.backend_app.connection, state.xkb_device_id),
+        };
+        match keymap {
+            Some(keymap) => {
+                // Start from the modifiers and layout that the server has now, since it only
+                // tells us when they change.
+                let connection = &self.backend_app.connection;
+                state.xkb_state = keymap
+                    .state_from_device(connection, state.xkb_device_id)
+                    .unwrap_or_else(|| keymap.state());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// xkb only knows the names of its layouts, so that's all we can report.
+fn keyboard_layout(name: String) -> KeyboardLayout {
+    KeyboardLayout {
+        id: name.clone(),
+        name,
+        language: None,
+    }
+}
+
+/// Wakes up the event loop from another thread; `idle_write` should be the writing end of our
+/// idle pipe.
+pub(crate) fn wake_idle_pipe(idle_write: RawFd) {
+    loop {
+        match nix::unistd::write(idle_write, &[0]) {
+            Err(nix::errno::Errno::EINTR) => {}
+            Err(nix::errno::Errno::EAGAIN) => {}
+            Err(e) => {
+                tracing::error!("Failed to write to idle pipe: {}", e);
+                break;
+            }
+            Ok(_) => {
+                break;
+            }
+        }
+    }
 }
 
 /// Clears out our idle pipe; `idle_read` should be the reading end of a pipe that was opened with
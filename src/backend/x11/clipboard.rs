@@ -114,6 +114,10 @@ impl Clipboard {
         self.0.borrow().get_format(format)
     }
 
+    pub fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        self.0.borrow().available_formats()
+    }
+
     pub fn available_type_names(&self) -> Vec<String> {
         self.0.borrow().available_type_names()
     }
@@ -211,6 +215,22 @@ impl ClipboardState {
     }
 
     #[allow(clippy::needless_collect)]
+    fn available_formats(&self) -> Vec<(String, Option<usize>)> {
+        if let Some(contents) = self.contents.as_ref() {
+            // We are the selection owner, so we know the data too
+            return contents
+                .data
+                .iter()
+                .map(|(_, format, data)| (format.clone(), Some(data.len())))
+                .collect();
+        }
+        // Other owners only tell us the targets; their sizes would take a transfer each.
+        self.available_type_names()
+            .into_iter()
+            .map(|name| (name, None))
+            .collect()
+    }
+
     fn available_type_names(&self) -> Vec<String> {
         if let Some(contents) = self.contents.as_ref() {
             // We are the selection owner and can directly return the result
@@ -1,10 +1,11 @@
 //! This module contains functions for opening file dialogs using DBus.
 
 use ashpd::desktop::file_chooser;
-use ashpd::{zbus, WindowIdentifier};
+use ashpd::WindowIdentifier;
 use futures::executor::block_on;
 use tracing::warn;
 
+use crate::strings::{self, BuiltinString};
 use crate::{FileDialogOptions, FileDialogToken, FileInfo};
 
 use super::window::IdleHandle;
@@ -32,28 +33,29 @@ fn dialog(
     open: bool,
 ) -> FileDialogToken {
     let tok = FileDialogToken::next();
+    let title = match options.title.take() {
+        Some(title) => title,
+        None => strings::get(match (open, options.select_directories) {
+            (true, true) => BuiltinString::OpenFolderTitle,
+            (true, false) => BuiltinString::OpenFileTitle,
+            (false, _) => BuiltinString::SaveFileTitle,
+        }),
+    };
 
     std::thread::spawn(move || {
         if let Err(e) = block_on(async {
-            let conn = zbus::Connection::session().await?;
+            let conn = crate::dbus::session().await?;
             let proxy = file_chooser::FileChooserProxy::new(&conn).await?;
             let id = WindowIdentifier::from_xid(window as u64);
             let multi = options.multi_selection;
 
-            let title_owned = options.title.take();
-            let title = match (open, options.select_directories) {
-                (true, true) => "Open Folder",
-                (true, false) => "Open File",
-                (false, _) => "Save File",
-            };
-            let title = title_owned.as_deref().unwrap_or(title);
             let open_result;
             let save_result;
             let uris = if open {
-                open_result = proxy.open_file(&id, title, options.into()).await?;
+                open_result = proxy.open_file(&id, &title, options.into()).await?;
                 open_result.uris()
             } else {
-                save_result = proxy.save_file(&id, title, options.into()).await?;
+                save_result = proxy.save_file(&id, &title, options.into()).await?;
                 save_result.uris()
             };
 
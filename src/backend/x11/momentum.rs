@@ -0,0 +1,202 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Momentum scrolling for mouse wheels.
+//!
+//! X11 only tells us about whole wheel clicks, so scrolling jumps a line at a time and stops
+//! dead. With momentum scrolling, each click adds to a velocity that decays over time, and we
+//! send a wheel event on every tick, so that scrolling glides like it does on Wayland or
+//! macOS. Spinning the wheel quickly makes each click count for more, so that flings go
+//! further.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use crate::kurbo::{Point, Vec2};
+use crate::mouse::{
+    InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent, ScrollDelta,
+};
+use crate::Modifiers;
+
+/// How far a line of a wheel click scrolls, in display points.
+const LINE_HEIGHT: f64 = 40.0;
+/// How often to send wheel events.
+const TICK: Duration = Duration::from_millis(16);
+/// How long it takes the velocity to decay by a factor of e, in seconds.
+const TIME_CONSTANT: f64 = 0.2;
+/// Below this speed, in display points per second, we stop.
+const MIN_SPEED: f64 = 20.0;
+/// Clicks closer together than this are a fling.
+const FLING_INTERVAL: Duration = Duration::from_millis(80);
+/// How much more each click in a fling counts for than the one before.
+const ACCELERATION: f64 = 1.5;
+/// The most that a click in a fling can count for.
+const MAX_BOOST: f64 = 4.0;
+
+/// The momentum scrolling state of a window.
+#[derive(Default)]
+pub(crate) struct Momentum {
+    enabled: Cell<bool>,
+    glide: Cell<Option<Glide>>,
+}
+
+#[derive(Clone, Copy)]
+struct Glide {
+    pos: Point,
+    buttons: MouseButtons,
+    mods: Modifiers,
+    source: InputSource,
+    velocity: Vec2,
+    boost: f64,
+    last_click: Instant,
+    last_tick: Instant,
+    /// Whether we've sent a wheel event for this glide yet.
+    began: bool,
+}
+
+impl Momentum {
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        if !enabled {
+            self.stop();
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Adds a wheel click to the glide.
+    pub(crate) fn click(&self, event: &MouseEvent, now: Instant) {
+        let delta = event.wheel_delta.to_points(LINE_HEIGHT);
+        let glide = match self.glide.get() {
+            // Scrolling the same way again soon after speeds up.
+            Some(glide)
+                if now - glide.last_click < FLING_INTERVAL && glide.velocity.dot(delta) > 0.0 =>
+            {
+                Glide {
+                    boost: (glide.boost * ACCELERATION).min(MAX_BOOST),
+                    ..glide
+                }
+            }
+            // Scrolling the other way stops the glide first.
+            Some(glide) if glide.velocity.dot(delta) < 0.0 => Glide {
+                velocity: Vec2::ZERO,
+                boost: 1.0,
+                ..glide
+            },
+            Some(glide) => Glide {
+                boost: 1.0,
+                ..glide
+            },
+            None => Glide {
+                pos: event.pos,
+                buttons: event.buttons,
+                mods: event.mods,
+                source: event.source,
+                velocity: Vec2::ZERO,
+                boost: 1.0,
+                last_click: now,
+                last_tick: now,
+                began: false,
+            },
+        };
+        self.glide.set(Some(Glide {
+            pos: event.pos,
+            buttons: event.buttons,
+            mods: event.mods,
+            source: event.source,
+            velocity: glide.velocity + impulse(delta * glide.boost),
+            last_click: now,
+            ..glide
+        }));
+    }
+
+    pub(crate) fn stop(&self) {
+        self.glide.set(None);
+    }
+
+    /// When the next wheel event is due, if we're gliding.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.glide.get().map(|glide| glide.last_tick + TICK)
+    }
+
+    /// If a wheel event is due, returns it and works out when the next one is.
+    pub(crate) fn tick(&self, now: Instant) -> Option<MouseEvent> {
+        let mut glide = self
+            .glide
+            .get()
+            .filter(|glide| glide.last_tick + TICK <= now)?;
+        let elapsed = (now - glide.last_tick).as_secs_f64();
+        let (delta, velocity) = decay(glide.velocity, elapsed);
+        let phase = if velocity == Vec2::ZERO {
+            MomentumPhase::Ended
+        } else if glide.began {
+            MomentumPhase::Changed
+        } else {
+            MomentumPhase::Began
+        };
+        glide.velocity = velocity;
+        glide.last_tick = now;
+        glide.began = true;
+        self.glide.set((velocity != Vec2::ZERO).then_some(glide));
+        Some(MouseEvent {
+            pos: glide.pos,
+            buttons: glide.buttons,
+            mods: glide.mods,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: ScrollDelta::Pixels(delta),
+            momentum_phase: phase,
+            source: glide.source,
+            coalesced: Vec::new(),
+        })
+    }
+}
+
+/// The velocity that makes a glide travel `delta` in total.
+fn impulse(delta: Vec2) -> Vec2 {
+    delta / TIME_CONSTANT
+}
+
+/// How far a glide at `velocity` travels in `elapsed` seconds, and its velocity afterwards.
+///
+/// Once the glide gets too slow, it travels the rest of the way at once, and stops.
+fn decay(velocity: Vec2, elapsed: f64) -> (Vec2, Vec2) {
+    let remaining = velocity * TIME_CONSTANT;
+    let velocity = velocity * (-elapsed / TIME_CONSTANT).exp();
+    if velocity.hypot() < MIN_SPEED {
+        (remaining, Vec2::ZERO)
+    } else {
+        (remaining - velocity * TIME_CONSTANT, velocity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glide_travels_whole_click() {
+        let mut velocity = impulse(Vec2::new(0.0, 120.0));
+        let mut total = Vec2::ZERO;
+        while velocity != Vec2::ZERO {
+            let (delta, v) = decay(velocity, TICK.as_secs_f64());
+            total += delta;
+            velocity = v;
+        }
+        assert!((total - Vec2::new(0.0, 120.0)).hypot() < 1e-9);
+    }
+}
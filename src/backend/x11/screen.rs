@@ -16,28 +16,44 @@
 
 use x11rb::connection::Connection;
 use x11rb::errors::ReplyOrIdError;
-use x11rb::protocol::randr::{self, ConnectionExt as _, Crtc};
-use x11rb::protocol::xproto::{Screen, Timestamp};
+use x11rb::protocol::randr::{self, ConnectionExt as _, Crtc, ModeInfo};
+use x11rb::protocol::xproto::{ConnectionExt as _, Screen, Timestamp};
 
+use super::util;
 use crate::kurbo::Rect;
-use crate::screen::Monitor;
+use crate::screen::{ColorShift, Monitor};
 
-fn monitor<Pos>(primary: bool, (x, y): (Pos, Pos), (width, height): (u16, u16)) -> Monitor
+fn monitor<Pos>(
+    primary: bool,
+    (x, y): (Pos, Pos),
+    (width, height): (u16, u16),
+    refresh_rate: Option<f64>,
+    name: Option<String>,
+) -> Monitor
 where
     Pos: Into<i32>,
 {
-    let rect = Rect::from_points(
+    let rect = Rect::from_origin_size(
         (x.into() as f64, y.into() as f64),
         (width as f64, height as f64),
     );
     // TODO: Support for work_rect. It's complicated...
     Monitor::new(primary, rect, rect)
+        .with_refresh_rate(refresh_rate)
+        .with_name(name)
 }
 
 pub(crate) fn get_monitors() -> Vec<Monitor> {
     let result = if let Some(app) = crate::Application::try_global() {
         let app = app.backend_app;
-        get_monitors_impl(app.connection().as_ref(), app.screen_num())
+        // X11 has one scale for all monitors.
+        let scale = app.dpi_scale().x();
+        get_monitors_impl(app.connection().as_ref(), app.screen_num()).map(|monitors| {
+            monitors
+                .into_iter()
+                .map(|monitor| monitor.with_scale(scale))
+                .collect()
+        })
     } else {
         let (conn, screen_num) = match x11rb::connect(None) {
             Ok(res) => res,
@@ -57,6 +73,80 @@ pub(crate) fn get_monitors() -> Vec<Monitor> {
     }
 }
 
+pub(crate) fn get_color_shift() -> Option<ColorShift> {
+    let result = if let Some(app) = crate::Application::try_global() {
+        let app = app.backend_app;
+        get_color_shift_impl(app.connection().as_ref(), app.screen_num())
+    } else {
+        let (conn, screen_num) = match x11rb::connect(None) {
+            Ok(res) => res,
+            Err(err) => {
+                tracing::error!("Error in Screen::get_color_shift(): {:?}", err);
+                return None;
+            }
+        };
+        get_color_shift_impl(&conn, screen_num)
+    };
+    match result {
+        Ok(shift) => shift,
+        Err(err) => {
+            tracing::error!("Error in Screen::get_color_shift(): {:?}", err);
+            None
+        }
+    }
+}
+
+// Night light modes (GNOME, redshift, f.lux...) are implemented on X11 by lowering the blue
+// (and to a lesser extent the green) gamma ramp of each CRTC, so we compare the top of the blue
+// ramp with the top of the red one.
+fn get_color_shift_impl(
+    conn: &impl Connection,
+    screen_num: usize,
+) -> Result<Option<ColorShift>, ReplyOrIdError> {
+    let screen = &conn.setup().roots[screen_num];
+
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    // Per-CRTC gamma ramps were added in RandR 1.2
+    let version = conn.randr_query_version(1, 3)?.reply()?;
+    let (crtcs, config_timestamp) = match (version.major_version, version.minor_version) {
+        (1, minor) if minor < 2 => return Ok(None),
+        (1, 2) => {
+            let reply = conn.randr_get_screen_resources(screen.root)?.reply()?;
+            (reply.crtcs, reply.config_timestamp)
+        }
+        _ => {
+            let reply = conn
+                .randr_get_screen_resources_current(screen.root)?
+                .reply()?;
+            (reply.crtcs, reply.config_timestamp)
+        }
+    };
+
+    let mut strength = None;
+    for crtc in crtcs {
+        let info = conn.randr_get_crtc_info(crtc, config_timestamp)?.reply()?;
+        if info.width == 0 || info.height == 0 {
+            continue;
+        }
+        let gamma = conn.randr_get_crtc_gamma(crtc)?.reply()?;
+        if let (Some(&red), Some(&blue)) = (gamma.red.last(), gamma.blue.last()) {
+            if red != 0 {
+                let crtc_strength = (1.0 - blue as f64 / red as f64).clamp(0.0, 1.0);
+                strength = Some(strength.unwrap_or(0.0f64).max(crtc_strength));
+            }
+        }
+    }
+
+    // Ignore tiny differences, which are just calibration or rounding.
+    Ok(strength.map(|strength| ColorShift::new(strength > 0.01, Some(strength))))
+}
+
 fn get_monitors_impl(
     conn: &impl Connection,
     screen_num: usize,
@@ -86,6 +176,8 @@ fn get_monitors_core(screen: &Screen) -> Result<Vec<Monitor>, ReplyOrIdError> {
         true,
         (0, 0),
         (screen.width_in_pixels, screen.height_in_pixels),
+        None,
+        None,
     )])
 }
 
@@ -93,16 +185,58 @@ fn get_monitors_randr_monitors(
     conn: &impl Connection,
     screen: &Screen,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
-    let result = conn
+    let resources = conn
+        .randr_get_screen_resources_current(screen.root)?
+        .reply()?;
+    let mut result = Vec::new();
+    for info in conn
         .randr_get_monitors(screen.root, true)?
         .reply()?
         .monitors
-        .iter()
-        .map(|info| monitor(info.primary, (info.x, info.y), (info.width, info.height)))
-        .collect();
+    {
+        let name = conn.get_atom_name(info.name)?.reply()?.name;
+        // A monitor can span several outputs, which all have the same mode.
+        let refresh_rate = match info.outputs.first() {
+            Some(&output) => {
+                let crtc = conn
+                    .randr_get_output_info(output, resources.config_timestamp)?
+                    .reply()?
+                    .crtc;
+                crtc_refresh_rate(conn, crtc, &resources.modes, resources.config_timestamp)?
+            }
+            None => None,
+        };
+        result.push(monitor(
+            info.primary,
+            (info.x, info.y),
+            (info.width, info.height),
+            refresh_rate,
+            Some(String::from_utf8_lossy(&name).into_owned()),
+        ));
+    }
     Ok(result)
 }
 
+/// Returns the refresh rate of the mode that `crtc` is showing, if it's showing anything.
+fn crtc_refresh_rate(
+    conn: &impl Connection,
+    crtc: Crtc,
+    modes: &[ModeInfo],
+    config_timestamp: Timestamp,
+) -> Result<Option<f64>, ReplyOrIdError> {
+    if crtc == x11rb::NONE {
+        return Ok(None);
+    }
+    let mode = conn
+        .randr_get_crtc_info(crtc, config_timestamp)?
+        .reply()?
+        .mode;
+    Ok(modes
+        .iter()
+        .find(|info| info.id == mode)
+        .and_then(util::mode_refresh_rate))
+}
+
 fn get_monitors_randr_screen_resources_current(
     conn: &impl Connection,
     screen: &Screen,
@@ -110,7 +244,7 @@ fn get_monitors_randr_screen_resources_current(
     let reply = conn
         .randr_get_screen_resources_current(screen.root)?
         .reply()?;
-    get_monitors_randr_crtcs_timestamp(conn, &reply.crtcs, reply.config_timestamp)
+    get_monitors_randr_crtcs_timestamp(conn, &reply.crtcs, &reply.modes, reply.config_timestamp)
 }
 
 fn get_monitors_randr_screen_resources(
@@ -118,7 +252,7 @@ fn get_monitors_randr_screen_resources(
     screen: &Screen,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
     let reply = conn.randr_get_screen_resources(screen.root)?.reply()?;
-    get_monitors_randr_crtcs_timestamp(conn, &reply.crtcs, reply.config_timestamp)
+    get_monitors_randr_crtcs_timestamp(conn, &reply.crtcs, &reply.modes, reply.config_timestamp)
 }
 
 // This function first sends a number of requests, collect()ing them into a Vec and then gets the
@@ -127,6 +261,7 @@ fn get_monitors_randr_screen_resources(
 fn get_monitors_randr_crtcs_timestamp(
     conn: &impl Connection,
     crtcs: &[Crtc],
+    modes: &[ModeInfo],
     config_timestamp: Timestamp,
 ) -> Result<Vec<Monitor>, ReplyOrIdError> {
     // Request information about all CRTCs
@@ -142,10 +277,25 @@ fn get_monitors_randr_crtcs_timestamp(
         if reply.width != 0 && reply.height != 0 {
             // First CRTC is assumed to be the primary output
             let primary = result.is_empty();
+            let refresh_rate = modes
+                .iter()
+                .find(|info| info.id == reply.mode)
+                .and_then(util::mode_refresh_rate);
+            let name = match reply.outputs.first() {
+                Some(&output) => {
+                    let info = conn
+                        .randr_get_output_info(output, config_timestamp)?
+                        .reply()?;
+                    Some(String::from_utf8_lossy(&info.name).into_owned())
+                }
+                None => None,
+            };
             result.push(monitor(
                 primary,
                 (reply.x, reply.y),
                 (reply.width, reply.height),
+                refresh_rate,
+                name,
             ));
         }
     }
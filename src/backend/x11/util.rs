@@ -19,42 +19,24 @@ use std::rc::Rc;
 use anyhow::{anyhow, Error};
 use x11rb::connection::RequestConnection;
 use x11rb::errors::ReplyError;
-use x11rb::protocol::randr::{ConnectionExt, ModeFlag};
+use x11rb::protocol::randr::{ConnectionExt, GetScreenResourcesReply, ModeFlag, ModeInfo};
 use x11rb::protocol::render::{self, ConnectionExt as _};
-use x11rb::protocol::xproto::{Screen, Visualid, Visualtype, Window};
+use x11rb::protocol::xproto::{ConnectionExt as _, Screen, Visualid, Visualtype, Window};
 use x11rb::xcb_ffi::XCBConnection;
 
+/// Returns the refresh rate of the monitor that the middle of the window is on.
+///
+/// If the window isn't on any monitor, this falls back to the first mode of the screen.
 // See: https://github.com/rtbo/rust-xcb/blob/master/examples/randr_screen_modes.rs
 pub fn refresh_rate(conn: &Rc<XCBConnection>, window_id: Window) -> Option<f64> {
     let try_refresh_rate = || -> Result<f64, Error> {
         let reply = conn.randr_get_screen_resources(window_id)?.reply()?;
 
-        // TODO(x11/render_improvements): Figure out a more correct way of getting the screen's refresh rate.
-        //     Or maybe we don't even need this function if I figure out a better way to schedule redraws?
-        //     Assuming the first mode is the one we want to use. This is probably a bug on some setups.
-        //     Any better way to find the correct one?
-        reply
-            .modes
-            .first()
+        window_mode(conn, window_id, &reply)?
+            .or_else(|| reply.modes.first())
             .ok_or_else(|| anyhow!("didn't get any modes"))
             .and_then(|mode_info| {
-                let flags = mode_info.mode_flags;
-                let vtotal = {
-                    let mut val = mode_info.vtotal;
-                    if (flags & u32::from(ModeFlag::DOUBLE_SCAN)) != 0 {
-                        val *= 2;
-                    }
-                    if (flags & u32::from(ModeFlag::INTERLACE)) != 0 {
-                        val /= 2;
-                    }
-                    val
-                };
-
-                if vtotal != 0 && mode_info.htotal != 0 {
-                    Ok((mode_info.dot_clock as f64) / (vtotal as f64 * mode_info.htotal as f64))
-                } else {
-                    Err(anyhow!("got nonsensical mode values"))
-                }
+                mode_refresh_rate(mode_info).ok_or_else(|| anyhow!("got nonsensical mode values"))
             })
     };
 
@@ -67,6 +49,58 @@ pub fn refresh_rate(conn: &Rc<XCBConnection>, window_id: Window) -> Option<f64>
     }
 }
 
+/// Returns the refresh rate of a display mode, or `None` if the mode doesn't make sense.
+pub fn mode_refresh_rate(mode_info: &ModeInfo) -> Option<f64> {
+    let flags = mode_info.mode_flags;
+    let vtotal = {
+        let mut val = mode_info.vtotal;
+        if (flags & u32::from(ModeFlag::DOUBLE_SCAN)) != 0 {
+            val *= 2;
+        }
+        if (flags & u32::from(ModeFlag::INTERLACE)) != 0 {
+            val /= 2;
+        }
+        val
+    };
+
+    if vtotal != 0 && mode_info.htotal != 0 {
+        Some((mode_info.dot_clock as f64) / (vtotal as f64 * mode_info.htotal as f64))
+    } else {
+        None
+    }
+}
+
+/// Finds the mode of the CRTC that shows the middle of the window.
+fn window_mode<'a>(
+    conn: &Rc<XCBConnection>,
+    window_id: Window,
+    resources: &'a GetScreenResourcesReply,
+) -> Result<Option<&'a ModeInfo>, Error> {
+    let geometry = conn.get_geometry(window_id)?.reply()?;
+    let center = conn
+        .translate_coordinates(
+            window_id,
+            geometry.root,
+            (geometry.width / 2) as i16,
+            (geometry.height / 2) as i16,
+        )?
+        .reply()?;
+    let (x, y) = (center.dst_x as i32, center.dst_y as i32);
+    for crtc in &resources.crtcs {
+        let info = conn
+            .randr_get_crtc_info(*crtc, resources.config_timestamp)?
+            .reply()?;
+        let contains = info.x as i32 <= x
+            && x < info.x as i32 + info.width as i32
+            && info.y as i32 <= y
+            && y < info.y as i32 + info.height as i32;
+        if info.mode != x11rb::NONE && contains {
+            return Ok(resources.modes.iter().find(|mode| mode.id == info.mode));
+        }
+    }
+    Ok(None)
+}
+
 // Apparently you have to get the visualtype this way :|
 fn find_visual_from_screen(screen: &Screen, visual_id: u32) -> Option<Visualtype> {
     for depth in &screen.allowed_depths {
@@ -15,13 +15,12 @@
 //! X11 window creation and window management.
 
 use std::cell::{Cell, RefCell};
-use std::collections::BinaryHeap;
 use std::convert::TryFrom;
 use std::os::unix::io::RawFd;
 use std::panic::Location;
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::scale::Scalable;
 use anyhow::{anyhow, Context, Error};
@@ -29,7 +28,10 @@ use tracing::{error, warn};
 use x11rb::connection::Connection;
 use x11rb::errors::ReplyOrIdError;
 use x11rb::properties::{WmHints, WmHintsState, WmSizeHints};
-use x11rb::protocol::render::Pictformat;
+use x11rb::protocol::render::{ConnectionExt as _, Pictformat};
+use x11rb::protocol::screensaver::ConnectionExt as _;
+use x11rb::protocol::shape;
+use x11rb::protocol::xfixes::ConnectionExt as _;
 use x11rb::protocol::xproto::{
     self, AtomEnum, ChangeWindowAttributesAux, ColormapAlloc, ConfigureNotifyEvent,
     ConfigureWindowAux, ConnectionExt, EventMask, ImageOrder as X11ImageOrder, PropMode,
@@ -43,24 +45,42 @@ use raw_window_handle::{
     XcbWindowHandle,
 };
 
-use crate::backend::shared::Timer;
-use crate::common_util::IdleCallback;
+use crate::application::KioskOptions;
+use crate::autoscroll::Autoscroll;
+use crate::backend::shared::{Timer, TimerQueue};
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
+use crate::common_util::{
+    session_input_source, shape_to_rects, FrameThrottle, IdleCallback, DEFAULT_REFRESH_INTERVAL,
+};
 use crate::dialog::FileDialogOptions;
+use crate::dispatch;
+use crate::dnd::{DragData, DragImage};
 use crate::error::Error as ShellError;
-use crate::keyboard::{KeyState, Modifiers};
-use crate::kurbo::{Insets, Point, Rect, Size, Vec2};
-use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
+use crate::keyboard::{KeyState, KeyboardLayout, Modifiers};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
+use crate::locale::LocaleInfo;
+use crate::mouse::{
+    self, Cursor, CursorDesc, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent,
+    ScrollDelta,
+};
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::{self, Monitor, MonitorId};
+use crate::shortcut_capture::ShortcutCapture;
 use crate::text::{simulate_input, Event};
 use crate::window::{
-    FileDialogToken, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowLevel,
+    FileDialogToken, FocusDirection, FullscreenOptions, IdleToken, PaintReason, ScrollbarMetrics,
+    TextFieldToken, Theme, TimerToken, WinHandler, WindowLevel,
 };
+use crate::window_group::WindowGroup;
 use crate::{window, KeyEvent, ScaledArea};
 
 use super::application::Application;
 use super::dialog;
 use super::menu::Menu;
+use super::momentum::Momentum;
+use super::util;
 
 /// A version of XCB's `xcb_visualtype_t` struct. This was copied from the [example] in x11rb; it
 /// is used to interoperate with cairo.
@@ -97,10 +117,23 @@ impl From<Visualtype> for xcb_visualtype_t {
     }
 }
 
-fn size_hints(resizable: bool, size: Size, min_size: Size) -> WmSizeHints {
+fn size_hints(
+    resizable: bool,
+    size: Size,
+    min_size: Size,
+    resize_increments: Option<Size>,
+) -> WmSizeHints {
     let mut size_hints = WmSizeHints::new();
     if resizable {
         size_hints.min_size = Some((min_size.width as i32, min_size.height as i32));
+        if let Some(increments) = resize_increments {
+            // An increment of 1 pixel means no constraint.
+            size_hints.size_increment = Some((
+                (increments.width.round() as i32).max(1),
+                (increments.height.round() as i32).max(1),
+            ));
+            size_hints.base_size = size_hints.min_size;
+        }
     } else {
         size_hints.min_size = Some((size.width as i32, size.height as i32));
         size_hints.max_size = Some((size.width as i32, size.height as i32));
@@ -111,14 +144,21 @@ fn size_hints(resizable: bool, size: Size, min_size: Size) -> WmSizeHints {
 pub(crate) struct WindowBuilder {
     app: Application,
     handler: Option<Box<dyn WinHandler>>,
+    group: Option<WindowGroup>,
     title: String,
+    taskbar_group: Option<String>,
     transparent: bool,
     position: Option<Point>,
     size: Size,
     min_size: Size,
+    resize_increments: Option<Size>,
     resizable: bool,
     level: WindowLevel,
     state: Option<window::WindowState>,
+    focusable: bool,
+    activate_on_show: bool,
+    show_after_first_paint: bool,
+    forced_scale: Option<Scale>,
 }
 
 impl WindowBuilder {
@@ -126,14 +166,21 @@ impl WindowBuilder {
         WindowBuilder {
             app,
             handler: None,
+            group: None,
             title: String::new(),
+            taskbar_group: None,
             transparent: false,
             position: None,
             size: Size::new(500.0, 400.0),
             min_size: Size::new(0.0, 0.0),
+            resize_increments: None,
             resizable: true,
             level: WindowLevel::AppWindow,
             state: None,
+            focusable: true,
+            activate_on_show: true,
+            show_after_first_paint: false,
+            forced_scale: None,
         }
     }
 
@@ -141,6 +188,10 @@ impl WindowBuilder {
         self.handler = Some(handler);
     }
 
+    pub fn set_group(&mut self, group: WindowGroup) {
+        self.group = Some(group);
+    }
+
     pub fn set_size(&mut self, size: Size) {
         // zero sized window results in server error
         self.size = if size.width == 0. || size.height == 0. {
@@ -154,6 +205,10 @@ impl WindowBuilder {
         self.min_size = min_size;
     }
 
+    pub fn set_resize_increments(&mut self, increments: Size) {
+        self.resize_increments = Some(increments);
+    }
+
     pub fn resizable(&mut self, resizable: bool) {
         self.resizable = resizable;
     }
@@ -172,6 +227,9 @@ impl WindowBuilder {
     }
 
     pub fn set_level(&mut self, level: WindowLevel) {
+        if let WindowLevel::Layer { .. } = level {
+            warn!("layer windows are only supported on wayland");
+        }
         self.level = level;
     }
 
@@ -179,40 +237,49 @@ impl WindowBuilder {
         self.state = Some(state);
     }
 
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.focusable = focusable;
+    }
+
+    pub fn set_activate_on_show(&mut self, activate_on_show: bool) {
+        self.activate_on_show = activate_on_show;
+    }
+
+    pub fn show_after_first_paint(&mut self, show_after_first_paint: bool) {
+        self.show_after_first_paint = show_after_first_paint;
+    }
+
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.forced_scale = Some(scale);
+    }
+
     pub fn set_title<S: Into<String>>(&mut self, title: S) {
         self.title = title.into();
     }
 
+    pub fn set_taskbar_group(&mut self, group: String) {
+        self.taskbar_group = Some(group);
+    }
+
+    pub fn set_restoration_id(&mut self, _id: String) {
+        // Ignored: only macOS restores windows.
+    }
+
     pub fn set_menu(&mut self, _menu: Menu) {
         // TODO(x11/menus): implement WindowBuilder::set_menu (currently a no-op)
     }
 
     // TODO(x11/menus): make menus if requested
-    pub fn build(self) -> Result<WindowHandle, Error> {
+    pub fn build(mut self) -> Result<WindowHandle, Error> {
+        if let Some(group) = self.group.take() {
+            self.handler = self.handler.take().map(|handler| group.wrap(handler));
+        }
         let conn = self.app.connection();
         let screen_num = self.app.screen_num();
         let id = conn.generate_id()?;
         let setup = conn.setup();
 
-        let env_dpi = std::env::var("DRUID_X11_DPI")
-            .ok()
-            .map(|x| x.parse::<f64>());
-
-        let scale = match env_dpi.or_else(|| self.app.rdb.get_value("Xft.dpi", "").transpose()) {
-            Some(Ok(dpi)) => {
-                let scale = dpi / 96.;
-                Scale::new(scale, scale)
-            }
-            None => Scale::default(),
-            Some(Err(err)) => {
-                let default = Scale::default();
-                warn!(
-                    "Unable to parse dpi: {:?}, defaulting to {:?}",
-                    err, default
-                );
-                default
-            }
-        };
+        let scale = self.forced_scale.unwrap_or_else(|| self.app.dpi_scale());
 
         let size_px = self.size.to_px(scale);
         let screen = setup
@@ -256,7 +323,7 @@ impl WindowBuilder {
         };
 
         let (parent, parent_origin) = match &self.level {
-            WindowLevel::AppWindow => (Weak::new(), Vec2::ZERO),
+            WindowLevel::AppWindow | WindowLevel::Layer { .. } => (Weak::new(), Vec2::ZERO),
             WindowLevel::Tooltip(parent)
             | WindowLevel::DropDown(parent)
             | WindowLevel::Modal(parent) => {
@@ -306,7 +373,13 @@ impl WindowBuilder {
             conn.free_colormap(colormap)?;
         }
 
-        let handler = RefCell::new(self.handler.unwrap());
+        let autoscroll = Rc::new(Autoscroll::default());
+        let shortcut_capture = Rc::new(ShortcutCapture::default());
+        let handler = RefCell::new(dispatch::wrap(
+            self.handler.unwrap(),
+            &autoscroll,
+            &shortcut_capture,
+        ));
         // Initialize some properties
         let atoms = self.app.atoms();
         let pid = nix::unistd::Pid::this().as_raw();
@@ -328,7 +401,7 @@ impl WindowBuilder {
             // consecutive null-terminated strings. These specify the Instance and Class names.
             //
             // The code below just imitates what happens on the gtk backend:
-            // - instance: The program's name
+            // - instance: The program's name, or the taskbar group if one was set
             // - class: The program's name with first letter in upper case
 
             // Get the name of the running binary
@@ -337,12 +410,13 @@ impl WindowBuilder {
                 .file_name()
                 .and_then(|name| name.to_str())
                 .unwrap_or("");
+            let instance = self.taskbar_group.as_deref().unwrap_or(name);
 
             // Build the contents of WM_CLASS
-            let mut wm_class = Vec::with_capacity(2 * (name.len() + 1));
-            wm_class.extend(name.as_bytes());
+            let mut wm_class = Vec::with_capacity(instance.len() + name.len() + 2);
+            wm_class.extend(instance.as_bytes());
             wm_class.push(0);
-            if let Some(&first) = wm_class.first() {
+            if let Some(&first) = name.as_bytes().first() {
                 wm_class.push(first.to_ascii_uppercase());
                 wm_class.extend(&name.as_bytes()[1..]);
             }
@@ -371,9 +445,12 @@ impl WindowBuilder {
         .context("set WM_PROTOCOLS")?;
 
         let min_size = self.min_size.to_px(scale);
-        log_x11!(size_hints(self.resizable, size_px, min_size)
-            .set_normal_hints(conn.as_ref(), id)
-            .context("set wm normal hints"));
+        let resize_increments = self.resize_increments.map(|inc| inc.to_px(scale));
+        log_x11!(
+            size_hints(self.resizable, size_px, min_size, resize_increments)
+                .set_normal_hints(conn.as_ref(), id)
+                .context("set wm normal hints")
+        );
 
         // TODO: set _NET_WM_STATE
         let mut hints = WmHints::new();
@@ -384,12 +461,30 @@ impl WindowBuilder {
                 window::WindowState::Restored => WmHintsState::Normal,
             });
         }
+        // Tooltips and drop-downs never take the keyboard focus; it stays with their parent.
+        let is_popup = matches!(
+            self.level,
+            WindowLevel::Tooltip(_) | WindowLevel::DropDown(_)
+        );
+        hints.input = Some(self.focusable && !is_popup);
         log_x11!(hints.set(conn.as_ref(), id).context("set wm hints"));
 
+        if !self.focusable || !self.activate_on_show || is_popup {
+            log_x11!(conn.change_property32(
+                PropMode::REPLACE,
+                id,
+                atoms._NET_WM_USER_TIME,
+                AtomEnum::CARDINAL,
+                &[0],
+            ));
+        }
+
         // set level
         {
             let window_type = match self.level {
-                WindowLevel::AppWindow => atoms._NET_WM_WINDOW_TYPE_NORMAL,
+                WindowLevel::AppWindow | WindowLevel::Layer { .. } => {
+                    atoms._NET_WM_WINDOW_TYPE_NORMAL
+                }
                 WindowLevel::Tooltip(_) => atoms._NET_WM_WINDOW_TYPE_TOOLTIP,
                 WindowLevel::Modal(_) => atoms._NET_WM_WINDOW_TYPE_DIALOG,
                 WindowLevel::DropDown(_) => atoms._NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
@@ -421,15 +516,39 @@ impl WindowBuilder {
             area: Cell::new(ScaledArea::from_px(size_px, scale)),
             scale: Cell::new(scale),
             min_size,
+            resize_increments,
             invalid: RefCell::new(Region::EMPTY),
+            anim_frame_requested: Cell::new(false),
             destroyed: Cell::new(false),
-            timer_queue: Mutex::new(BinaryHeap::new()),
+            timer_queue: Mutex::new(TimerQueue::new()),
             idle_queue: Arc::new(Mutex::new(Vec::new())),
             idle_pipe: self.app.idle_pipe(),
             active_text_field: Cell::new(None),
+            autoscroll,
+            shortcut_capture,
+            pointer_confinement: Cell::new(None),
+            pointer_locked: Cell::new(false),
+            key_repeat: Cell::new(true),
             parent,
+            hidden_until_paint: Cell::new(self.show_after_first_paint),
+            show_requested: Cell::new(false),
+            frame_throttle: FrameThrottle::default(),
+            throttled_frame: Cell::new(None),
+            momentum: Momentum::default(),
+            popups: RefCell::new(Vec::new()),
+            cursor: Cell::new(x11rb::NONE),
+            kiosk: Cell::new(KioskOptions::OFF),
         });
 
+        if is_popup {
+            if let Some(parent) = window.parent.upgrade() {
+                borrow_mut!(parent.popups)?.push(Rc::downgrade(&window));
+            }
+        }
+
+        window
+            .frame_throttle
+            .set_refresh_rate(util::refresh_rate(self.app.connection(), id));
         window.set_title(&self.title);
         if let Some(pos) = self.position {
             window.set_position(pos);
@@ -469,17 +588,44 @@ pub(crate) struct Window {
     scale: Cell<Scale>,
     // min size in px
     min_size: Size,
+    // resize increments in px
+    resize_increments: Option<Size>,
     /// We've told X11 to destroy this window, so don't so any more X requests with this window id.
     destroyed: Cell<bool>,
     /// The region that was invalidated since the last time we rendered.
     invalid: RefCell<Region>,
+    /// Whether the handler asked for an animation frame since the last time we rendered.
+    anim_frame_requested: Cell<bool>,
     /// Timers, sorted by "earliest deadline first"
-    timer_queue: Mutex<BinaryHeap<Timer<()>>>,
+    timer_queue: Mutex<TimerQueue<()>>,
     idle_queue: Arc<Mutex<Vec<IdleKind>>>,
     // Writing to this wakes up the event loop, so that it can run idle handlers.
     idle_pipe: RawFd,
     active_text_field: Cell<Option<TextFieldToken>>,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
+    /// An invisible child window that the pointer is grabbed and confined to.
+    pointer_confinement: Cell<Option<xproto::Window>>,
+    /// Whether the confinement is a pointer lock, to the pixel that the pointer was on.
+    pointer_locked: Cell<bool>,
+    /// Whether key repeats are passed on to the handler.
+    key_repeat: Cell<bool>,
     parent: Weak<Window>,
+    /// If true, we don't map the window until the handler has painted it once.
+    hidden_until_paint: Cell<bool>,
+    /// Whether `show` was called while we were waiting for the first paint.
+    show_requested: Cell<bool>,
+    frame_throttle: FrameThrottle,
+    /// When to redraw, if a redraw was requested too soon after the previous frame.
+    throttled_frame: Cell<Option<Instant>>,
+    /// Synthesizes smooth wheel events from wheel clicks, if enabled.
+    momentum: Momentum,
+    /// Tooltips and drop-downs opened from this window, which are closed when it is clicked
+    /// or loses focus.
+    popups: RefCell<Vec<Weak<Window>>>,
+    /// The cursor that the handler asked for, which isn't shown while kiosk mode hides it.
+    cursor: Cell<xproto::Cursor>,
+    kiosk: Cell<KioskOptions>,
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -557,6 +703,19 @@ impl Window {
     }
 
     fn render(&self) -> Result<(), Error> {
+        self.frame_throttle.frame_started();
+        // Animation frames requested from here on are for the next frame.
+        let reason = if self.anim_frame_requested.replace(false) {
+            PaintReason::Animation
+        } else {
+            PaintReason::Damage
+        };
+        let missed = self.frame_throttle.missed_frames(reason);
+        if missed > 0 {
+            self.with_handler(|h| h.frame_missed(missed));
+        }
+        let info = self.frame_throttle.frame_info();
+        self.with_handler(|h| h.prepare_frame(info));
         self.with_handler(|h| h.prepare_paint());
 
         if self.destroyed() {
@@ -565,28 +724,63 @@ impl Window {
 
         let invalid = std::mem::replace(&mut *borrow_mut!(self.invalid)?, Region::EMPTY);
         self.with_handler_and_dont_check_the_other_borrows(|handler| {
-            handler.paint(&invalid);
+            handler.paint(&invalid, reason);
         });
 
+        if self.hidden_until_paint.replace(false) && self.show_requested.get() {
+            self.show();
+        }
+
         Ok(())
     }
 
     fn show(&self) {
-        if !self.destroyed() {
+        if self.hidden_until_paint.get() {
+            // render will call us again once there's something to show.
+            self.show_requested.set(true);
+            self.invalidate();
+        } else if !self.destroyed() {
             log_x11!(self.app.connection().map_window(self.id));
         }
     }
 
     fn close(&self) {
+        self.dismiss_popups();
         self.destroy();
     }
 
+    /// Close any tooltips and drop-downs that were opened from this window.
+    fn dismiss_popups(&self) {
+        let popups = match self.popups.try_borrow_mut() {
+            Ok(mut popups) => std::mem::take(&mut *popups),
+            Err(_) => return,
+        };
+        for popup in popups.iter().filter_map(Weak::upgrade) {
+            popup.close();
+        }
+    }
+
     /// Set whether the window should be resizable
     fn resizable(&self, resizable: bool) {
         let conn = self.app.connection().as_ref();
-        log_x11!(size_hints(resizable, self.size().size_px(), self.min_size)
-            .set_normal_hints(conn, self.id)
-            .context("set normal hints"));
+        log_x11!(size_hints(
+            resizable,
+            self.size().size_px(),
+            self.min_size,
+            self.resize_increments
+        )
+        .set_normal_hints(conn, self.id)
+        .context("set normal hints"));
+    }
+
+    fn set_transient_for(&self, parent: u32) {
+        log_x11!(self.app.connection().change_property32(
+            PropMode::REPLACE,
+            self.id,
+            AtomEnum::WM_TRANSIENT_FOR,
+            AtomEnum::WINDOW,
+            &[parent],
+        ));
     }
 
     /// Set whether the window should show titlebar
@@ -602,22 +796,60 @@ impl Window {
             .to_vec2()
     }
 
+    /// The position of the window's top left corner on the root window, in display points.
+    fn root_origin(&self) -> Result<Point, Error> {
+        let conn = self.app.connection();
+        let scale = self.scale.get();
+        let geom = conn.get_geometry(self.id)?.reply()?;
+        let cord = conn
+            .translate_coordinates(self.id, geom.root, 0, 0)?
+            .reply()?;
+        Ok(Point::new(cord.dst_x as _, cord.dst_y as _).to_dp(scale))
+    }
+
     fn get_position(&self) -> Point {
-        fn _get_position(window: &Window) -> Result<Point, Error> {
-            let conn = window.app.connection();
-            let scale = window.scale.get();
-            let geom = conn.get_geometry(window.id)?.reply()?;
-            let cord = conn
-                .translate_coordinates(window.id, geom.root, 0, 0)?
-                .reply()?;
-            Ok(Point::new(cord.dst_x as _, cord.dst_y as _).to_dp(scale))
-        }
-        let pos = _get_position(self);
+        let pos = self.root_origin();
         log_x11!(&pos);
         pos.map(|pos| pos - self.parent_origin())
             .unwrap_or_default()
     }
 
+    fn map_to_screen(&self, point: Point) -> Point {
+        let origin = self.root_origin();
+        log_x11!(&origin);
+        // Only the origin goes through the server, so `point` keeps its fractional part.
+        origin.unwrap_or_default() + point.to_vec2()
+    }
+
+    fn content_insets(&self) -> Insets {
+        fn _content_insets(window: &Window) -> Result<Insets, Error> {
+            let conn = window.app.connection();
+            let reply = conn
+                .get_property(
+                    false,
+                    window.id,
+                    window.app.atoms()._NET_FRAME_EXTENTS,
+                    AtomEnum::CARDINAL,
+                    0,
+                    4,
+                )?
+                .reply()?;
+            // The window manager sets this once it has decorated the window, if it does so
+            // at all. Until then, there are no decorations that we know of.
+            let extents: Vec<u32> = reply.value32().map(Iterator::collect).unwrap_or_default();
+            Ok(match extents[..] {
+                [left, right, top, bottom] => {
+                    Insets::new(left as f64, top as f64, right as f64, bottom as f64)
+                        .to_dp(window.scale.get())
+                }
+                _ => Insets::ZERO,
+            })
+        }
+        let insets = _content_insets(self);
+        log_x11!(&insets);
+        insets.unwrap_or(Insets::ZERO)
+    }
+
     fn set_position(&self, pos: Point) {
         let conn = self.app.connection();
         let scale = self.scale.get();
@@ -659,6 +891,299 @@ impl Window {
         ));
     }
 
+    fn set_mouse_passthrough(&self, passthrough: bool) {
+        if self.destroyed() {
+            return;
+        }
+        if !self.app.xfixes_shape_supported() {
+            warn!("Mouse passthrough requires XFixes 2.0, which the X server doesn't support.");
+            return;
+        }
+        log_x11!(self.set_input_region_empty(passthrough));
+    }
+
+    fn confine_pointer(&self, region: Option<Rect>) {
+        if self.destroyed() {
+            return;
+        }
+        log_x11!(self.release_pointer_confinement());
+        if let Some(region) = region {
+            log_x11!(self.grab_pointer_confined(region));
+        }
+    }
+
+    fn lock_pointer(&self) {
+        if self.destroyed() {
+            return;
+        }
+        log_x11!(self.release_pointer_confinement());
+        log_x11!(self.grab_pointer_locked());
+    }
+
+    fn unlock_pointer(&self) {
+        if self.pointer_locked.get() {
+            log_x11!(self.release_pointer_confinement());
+        }
+    }
+
+    /// Grabs the pointer, confining it to the pixel that it is on.
+    fn grab_pointer_locked(&self) -> Result<(), Error> {
+        let pointer = self.app.connection().query_pointer(self.id)?.reply()?;
+        let pos = Point::new(pointer.win_x as f64, pointer.win_y as f64).to_dp(self.scale.get());
+        self.grab_pointer_confined(Rect::from_origin_size(pos, Size::ZERO))?;
+        self.pointer_locked
+            .set(self.pointer_confinement.get().is_some());
+        Ok(())
+    }
+
+    /// Grabs the pointer, confining it to `region`.
+    ///
+    /// X can only confine the pointer to a window, so this makes an invisible child window
+    /// covering `region`. It doesn't select any events, so they go to us as usual.
+    fn grab_pointer_confined(&self, region: Rect) -> Result<(), Error> {
+        let conn = self.app.connection();
+        let region = region.to_px(self.scale.get()).round();
+        let confine_to = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_FROM_PARENT as u8,
+            confine_to,
+            self.id,
+            region.x0 as i16,
+            region.y0 as i16,
+            (region.width() as u16).max(1),
+            (region.height() as u16).max(1),
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &xproto::CreateWindowAux::new(),
+        )?;
+        conn.map_window(confine_to)?;
+        self.pointer_confinement.set(Some(confine_to));
+        let reply = conn
+            .grab_pointer(
+                true,
+                self.id,
+                u32::from(
+                    EventMask::POINTER_MOTION | EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE,
+                ) as u16,
+                xproto::GrabMode::ASYNC,
+                xproto::GrabMode::ASYNC,
+                confine_to,
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )?
+            .reply()?;
+        if reply.status != xproto::GrabStatus::SUCCESS {
+            warn!("failed to confine the pointer: {:?}", reply.status);
+            self.release_pointer_confinement()?;
+        }
+        Ok(())
+    }
+
+    fn release_pointer_confinement(&self) -> Result<(), Error> {
+        self.pointer_locked.set(false);
+        if let Some(confine_to) = self.pointer_confinement.take() {
+            let conn = self.app.connection();
+            conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+            conn.destroy_window(confine_to)?;
+            conn.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Either gives the window an empty input region, so that the server delivers pointer
+    /// events to whatever is below it, or restores the default input region.
+    fn set_input_region_empty(&self, empty: bool) -> Result<(), Error> {
+        let conn = self.app.connection();
+        if empty {
+            let region = conn.generate_id()?;
+            conn.xfixes_create_region(region, &[])?;
+            conn.xfixes_set_window_shape_region(self.id, shape::SK::INPUT, 0, 0, region)?;
+            conn.xfixes_destroy_region(region)?;
+        } else {
+            conn.xfixes_set_window_shape_region(self.id, shape::SK::INPUT, 0, 0, x11rb::NONE)?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+
+    fn set_shape(&self, shape: Option<BezPath>) {
+        if self.destroyed() {
+            return;
+        }
+        if !self.app.xfixes_shape_supported() {
+            warn!("Window shapes require XFixes 2.0, which the X server doesn't support.");
+            return;
+        }
+        log_x11!(self.set_bounding_shape(shape));
+    }
+
+    fn set_bounding_shape(&self, shape: Option<BezPath>) -> Result<(), Error> {
+        let conn = self.app.connection();
+        match shape {
+            Some(shape) => {
+                let rects: Vec<xproto::Rectangle> = shape_to_rects(&shape, self.scale.get())
+                    .into_iter()
+                    .map(|r| xproto::Rectangle {
+                        x: r.x0 as i16,
+                        y: r.y0 as i16,
+                        width: r.width() as u16,
+                        height: r.height() as u16,
+                    })
+                    .collect();
+                let region = conn.generate_id()?;
+                conn.xfixes_create_region(region, &rects)?;
+                conn.xfixes_set_window_shape_region(self.id, shape::SK::BOUNDING, 0, 0, region)?;
+                conn.xfixes_destroy_region(region)?;
+            }
+            None => {
+                conn.xfixes_set_window_shape_region(
+                    self.id,
+                    shape::SK::BOUNDING,
+                    0,
+                    0,
+                    x11rb::NONE,
+                )?;
+            }
+        }
+        conn.flush()?;
+        Ok(())
+    }
+
+    fn restack(&self, sibling: u32, stack_mode: xproto::StackMode) {
+        if self.destroyed() {
+            return;
+        }
+
+        // Our window has most likely been reparented by the window manager, in which case
+        // `sibling` isn't actually our sibling and a ConfigureWindow request would fail.
+        // Ask the window manager to do the restacking for us instead.
+        let conn = self.app.connection();
+        let root = conn.setup().roots[self.app.screen_num()].root;
+        // Source indication 2 means "from a pager or other direct user action".
+        let data = [2, sibling, stack_mode.into(), 0, 0];
+        let event = xproto::ClientMessageEvent::new(
+            32,
+            self.id,
+            self.app.atoms()._NET_RESTACK_WINDOW,
+            data,
+        );
+        log_x11!(conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        ));
+    }
+
+    /// Asks the window manager to make the window fullscreen, or not.
+    ///
+    /// The window manager decides what happens to panels, so there are no options.
+    fn set_fullscreen(&self, fullscreen: bool) {
+        if self.destroyed() {
+            return;
+        }
+        let conn = self.app.connection();
+        let atoms = self.app.atoms();
+        let root = conn.setup().roots[self.app.screen_num()].root;
+        // The actions are _NET_WM_STATE_REMOVE (0) and _NET_WM_STATE_ADD (1), and source
+        // indication 1 means "from an application".
+        let data = [
+            fullscreen as u32,
+            atoms._NET_WM_STATE_FULLSCREEN,
+            x11rb::NONE,
+            1,
+            0,
+        ];
+        let event = xproto::ClientMessageEvent::new(32, self.id, atoms._NET_WM_STATE, data);
+        log_x11!(conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        ));
+    }
+
+    fn set_kiosk(&self, options: KioskOptions) {
+        if self.destroyed() {
+            return;
+        }
+        let old = self.kiosk.replace(options);
+        let conn = self.app.connection();
+        if options.block_system_shortcuts != old.block_system_shortcuts {
+            if options.block_system_shortcuts {
+                // Window managers take their shortcuts with passive grabs, which don't fire
+                // while another client has the keyboard grabbed.
+                log_x11!(self.grab_keyboard());
+            } else {
+                log_x11!(conn.ungrab_keyboard(x11rb::CURRENT_TIME));
+            }
+        }
+        if options.hide_cursor != old.hide_cursor {
+            let cursor = if options.hide_cursor {
+                match self.make_blank_cursor() {
+                    Ok(cursor) => cursor,
+                    Err(e) => {
+                        error!("failed to hide the cursor: {}", e);
+                        return;
+                    }
+                }
+            } else {
+                self.cursor.get()
+            };
+            let changes = ChangeWindowAttributesAux::new().cursor(cursor);
+            log_x11!(conn.change_window_attributes(self.id, &changes));
+            if options.hide_cursor {
+                // The window keeps its own reference.
+                log_x11!(conn.free_cursor(cursor));
+            }
+        }
+        if options.inhibit_screensaver != old.inhibit_screensaver {
+            log_x11!(conn.screensaver_suspend(options.inhibit_screensaver as u32));
+        }
+        log_x11!(conn.flush());
+    }
+
+    fn grab_keyboard(&self) -> Result<(), Error> {
+        let reply = self
+            .app
+            .connection()
+            .grab_keyboard(
+                true,
+                self.id,
+                x11rb::CURRENT_TIME,
+                xproto::GrabMode::ASYNC,
+                xproto::GrabMode::ASYNC,
+            )?
+            .reply()?;
+        if reply.status != xproto::GrabStatus::SUCCESS {
+            warn!("failed to grab the keyboard: {:?}", reply.status);
+        }
+        Ok(())
+    }
+
+    /// Makes a cursor with no visible pixels.
+    fn make_blank_cursor(&self) -> Result<xproto::Cursor, Error> {
+        let conn = self.app.connection();
+        // The contents of a new pixmap are undefined, so we clear it to make the mask empty.
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(1, pixmap, self.id, 1, 1)?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, pixmap, &xproto::CreateGCAux::new().foreground(0))?;
+        let rect = xproto::Rectangle {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        conn.poly_fill_rectangle(pixmap, gc, &[rect])?;
+        conn.free_gc(gc)?;
+        let cursor = conn.generate_id()?;
+        conn.create_cursor(cursor, pixmap, pixmap, 0, 0, 0, 0, 0, 0, 0, 0)?;
+        conn.free_pixmap(pixmap)?;
+        Ok(cursor)
+    }
+
     fn add_invalid_rect(&self, rect: Rect) -> Result<(), Error> {
         let scale = self.scale.get();
         borrow_mut!(self.invalid)?.add_rect(rect.to_px(scale).expand().to_dp(scale));
@@ -674,9 +1199,19 @@ impl Window {
         Ok(())
     }
 
+    fn request_anim_frame(&self) {
+        self.anim_frame_requested.set(true);
+        self.request_redraw();
+    }
+
     /// Schedule a redraw on the idle loop, or if we are waiting on present then schedule it for
     /// when the current present finishes.
-    fn request_anim_frame(&self) {
+    fn request_redraw(&self) {
+        if let Some(deadline) = self.frame_throttle.next_frame_deadline() {
+            // run_timers will request the frame again once it's allowed.
+            self.throttled_frame.set(Some(deadline));
+            return;
+        }
         let idle = IdleHandle {
             queue: Arc::clone(&self.idle_queue),
             pipe: self.idle_pipe,
@@ -689,7 +1224,7 @@ impl Window {
         self.add_invalid_rect(rect)
             .unwrap_or_else(|err| error!("Window::invalidate - failed to invalidate: {}", err));
 
-        self.request_anim_frame();
+        self.request_redraw();
     }
 
     fn invalidate_rect(&self, rect: Rect) {
@@ -697,7 +1232,59 @@ impl Window {
             error!("Window::invalidate_rect - failed to enlarge rect: {}", err);
         }
 
-        self.request_anim_frame();
+        self.request_redraw();
+    }
+
+    fn scroll_region(&self, rect: Rect, delta: Vec2) {
+        if self.destroyed() {
+            return;
+        }
+        match self.copy_scrolled_area(rect, delta) {
+            Ok(true) => self.request_redraw(),
+            Ok(false) => self.invalidate_rect(rect),
+            Err(err) => {
+                error!("Window::scroll_region - failed to copy: {}", err);
+                self.invalidate_rect(rect);
+            }
+        }
+    }
+
+    /// Moves the contents of `rect` by `delta` on the server, and updates the invalid region to
+    /// match. Returns `false` if `delta` isn't a whole number of pixels.
+    fn copy_scrolled_area(&self, rect: Rect, delta: Vec2) -> Result<bool, Error> {
+        let scale = self.scale.get();
+        let delta_px = delta.to_px(scale);
+        if (delta_px - delta_px.round()).hypot() > 1e-6 {
+            return Ok(false);
+        }
+        let delta_px = delta_px.round();
+        let rect_px = rect.to_px(scale).round();
+        let src = rect_px.intersect(rect_px - delta_px);
+        if src.area() > 0.0 {
+            let conn = self.app.connection();
+            let gc = conn.generate_id()?;
+            // If part of the source is obscured, the server tells us which part of the
+            // destination it couldn't fill with a GraphicsExposure event.
+            conn.create_gc(
+                gc,
+                self.id,
+                &xproto::CreateGCAux::new().graphics_exposures(1),
+            )?;
+            conn.copy_area(
+                self.id,
+                self.id,
+                gc,
+                src.x0 as i16,
+                src.y0 as i16,
+                (src.x0 + delta_px.x) as i16,
+                (src.y0 + delta_px.y) as i16,
+                src.width() as u16,
+                src.height() as u16,
+            )?;
+            conn.free_gc(gc)?;
+        }
+        borrow_mut!(self.invalid)?.scroll(rect_px.to_dp(scale), delta);
+        Ok(true)
     }
 
     fn set_title(&self, title: &str) {
@@ -726,6 +1313,35 @@ impl Window {
         ));
     }
 
+    /// Asks the window manager to draw our decorations in a light or dark variant.
+    ///
+    /// `_GTK_THEME_VARIANT` isn't a standard, but mutter, KWin and others follow it.
+    fn set_theme_override(&self, theme: Option<Theme>) {
+        if self.destroyed() {
+            return;
+        }
+        let atoms = self.app.atoms();
+        let conn = self.app.connection();
+        match theme {
+            Some(theme) => {
+                let variant = match theme {
+                    Theme::Light => "light",
+                    Theme::Dark => "dark",
+                };
+                log_x11!(conn.change_property8(
+                    xproto::PropMode::REPLACE,
+                    self.id,
+                    atoms._GTK_THEME_VARIANT,
+                    atoms.UTF8_STRING,
+                    variant.as_bytes(),
+                ));
+            }
+            None => {
+                log_x11!(conn.delete_property(self.id, atoms._GTK_THEME_VARIANT));
+            }
+        }
+    }
+
     fn set_cursor(&self, cursor: &Cursor) {
         let cursors = &self.app.cursors;
         #[allow(deprecated)]
@@ -741,10 +1357,20 @@ impl Window {
             Cursor::NotAllowed => cursors.not_allowed,
             Cursor::ResizeLeftRight => cursors.col_resize,
             Cursor::ResizeUpDown => cursors.row_resize,
+            Cursor::AllScroll => cursors.all_scroll,
+            Cursor::Wait => cursors.wait,
+            Cursor::Progress => cursors.progress,
             Cursor::Custom(custom) => Some(custom.0),
         };
-        if cursor.is_none() {
-            warn!("Unable to load cursor {:?}", cursor);
+        let cursor = match cursor {
+            Some(cursor) => cursor,
+            None => {
+                warn!("Unable to load cursor {:?}", cursor);
+                return;
+            }
+        };
+        self.cursor.set(cursor);
+        if self.kiosk.get().hide_cursor {
             return;
         }
         let conn = self.app.connection();
@@ -763,23 +1389,53 @@ impl Window {
     }
 
     pub fn handle_expose(&self, expose: &xproto::ExposeEvent) -> Result<(), Error> {
-        let rect = Rect::from_origin_size(
-            (expose.x as f64, expose.y as f64),
-            (expose.width as f64, expose.height as f64),
+        self.expose(
+            expose.x,
+            expose.y,
+            expose.width,
+            expose.height,
+            expose.count,
+        )
+    }
+
+    pub fn handle_graphics_exposure(
+        &self,
+        exposure: &xproto::GraphicsExposureEvent,
+    ) -> Result<(), Error> {
+        self.expose(
+            exposure.x,
+            exposure.y,
+            exposure.width,
+            exposure.height,
+            exposure.count,
         )
-        .to_dp(self.scale.get());
+    }
+
+    /// Invalidates an area that the server couldn't draw, given in pixels. `count` is the number
+    /// of related exposures that are still to come.
+    fn expose(&self, x: u16, y: u16, width: u16, height: u16, count: u16) -> Result<(), Error> {
+        let rect = Rect::from_origin_size((x as f64, y as f64), (width as f64, height as f64))
+            .to_dp(self.scale.get());
 
         self.add_invalid_rect(rect)?;
-        if expose.count == 0 {
-            self.request_anim_frame();
+        if count == 0 {
+            self.request_redraw();
         }
         Ok(())
     }
 
     pub fn handle_key_event(&self, event: KeyEvent) {
+        if event.repeat && !self.key_repeat.get() {
+            return;
+        }
         self.with_handler(|h| match event.state {
             KeyState::Down => {
-                simulate_input(h, self.active_text_field.get(), event);
+                let focus = FocusDirection::from_key_event(&event);
+                if !simulate_input(h, self.active_text_field.get(), event) {
+                    if let Some(direction) = focus {
+                        h.focus_navigation(direction);
+                    }
+                }
             }
             KeyState::Up => h.key_up(event),
         });
@@ -801,8 +1457,13 @@ impl Window {
             count: 1,
             focus: false,
             button,
-            wheel_delta: Vec2::ZERO,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: input_source(button_press.response_type),
+            coalesced: Vec::new(),
         };
+        self.dismiss_popups();
+        self.momentum.stop();
         self.with_handler(|h| h.mouse_down(&mouse_event));
         Ok(())
     }
@@ -823,7 +1484,10 @@ impl Window {
             count: 0,
             focus: false,
             button,
-            wheel_delta: Vec2::ZERO,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: input_source(button_release.response_type),
+            coalesced: Vec::new(),
         };
         self.with_handler(|h| h.mouse_up(&mouse_event));
         Ok(())
@@ -834,15 +1498,14 @@ impl Window {
         let mods = key_mods(event.state);
         let scale = self.scale.get();
 
-        // We use a delta of 120 per tick to match the behavior of Windows.
         let is_shift = mods.shift();
-        let delta = match button {
-            4 if is_shift => (-120.0, 0.0),
-            4 => (0.0, -120.0),
-            5 if is_shift => (120.0, 0.0),
-            5 => (0.0, 120.0),
-            6 => (-120.0, 0.0),
-            7 => (120.0, 0.0),
+        let notches = match button {
+            4 if is_shift => Vec2::new(-1.0, 0.0),
+            4 => Vec2::new(0.0, -1.0),
+            5 if is_shift => Vec2::new(1.0, 0.0),
+            5 => Vec2::new(0.0, 1.0),
+            6 => Vec2::new(-1.0, 0.0),
+            7 => Vec2::new(1.0, 0.0),
             _ => return Err(anyhow!("unexpected mouse wheel button: {}", button)),
         };
         let mouse_event = MouseEvent {
@@ -852,29 +1515,42 @@ impl Window {
             count: 0,
             focus: false,
             button: MouseButton::None,
-            wheel_delta: delta.into(),
+            wheel_delta: ScrollDelta::Lines(notches * mouse::LINES_PER_NOTCH),
+            momentum_phase: MomentumPhase::None,
+            source: input_source(event.response_type),
+            coalesced: Vec::new(),
         };
 
+        if self.momentum.is_enabled() {
+            self.momentum.click(&mouse_event, Instant::now());
+            return Ok(());
+        }
         self.with_handler(|h| h.wheel(&mouse_event));
         Ok(())
     }
 
-    pub fn handle_motion_notify(
-        &self,
-        motion_notify: &xproto::MotionNotifyEvent,
-    ) -> Result<(), Error> {
+    /// Handles motion events that arrived together, oldest first.
+    pub fn handle_motion_notify(&self, motions: &[xproto::MotionNotifyEvent]) -> Result<(), Error> {
         let scale = self.scale.get();
-        let mouse_event = MouseEvent {
-            pos: Point::new(motion_notify.event_x as f64, motion_notify.event_y as f64)
-                .to_dp(scale),
-            buttons: mouse_buttons(motion_notify.state),
-            mods: key_mods(motion_notify.state),
-            count: 0,
-            focus: false,
-            button: MouseButton::None,
-            wheel_delta: Vec2::ZERO,
-        };
-        self.with_handler(|h| h.mouse_move(&mouse_event));
+        let events = motions
+            .iter()
+            .map(|motion_notify| MouseEvent {
+                pos: Point::new(motion_notify.event_x as f64, motion_notify.event_y as f64)
+                    .to_dp(scale),
+                buttons: mouse_buttons(motion_notify.state),
+                mods: key_mods(motion_notify.state),
+                count: 0,
+                focus: false,
+                button: MouseButton::None,
+                wheel_delta: ScrollDelta::ZERO,
+                momentum_phase: MomentumPhase::None,
+                source: input_source(motion_notify.response_type),
+                coalesced: Vec::new(),
+            })
+            .collect();
+        if let Some(mouse_event) = mouse::coalesce_moves(events) {
+            self.with_handler(|h| h.mouse_move(&mouse_event));
+        }
         Ok(())
     }
 
@@ -883,7 +1559,20 @@ impl Window {
     }
 
     pub fn handle_lost_focus(&self) {
+        self.dismiss_popups();
+        // Other applications should get the pointer back.
+        let lock_lost = self.pointer_locked.get();
+        if lock_lost {
+            log_x11!(self.release_pointer_confinement());
+        }
         self.with_handler(|h| h.lost_focus());
+        if lock_lost {
+            self.with_handler(|h| h.pointer_lock_lost());
+        }
+    }
+
+    pub fn handle_input_language_changed(&self, layout: KeyboardLayout) {
+        self.with_handler(|h| h.input_language_changed(layout));
     }
 
     pub fn handle_client_message(&self, client_message: &xproto::ClientMessageEvent) {
@@ -904,7 +1593,14 @@ impl Window {
     }
 
     pub fn handle_configure_notify(&self, event: &ConfigureNotifyEvent) -> Result<(), Error> {
-        self.size_changed(Size::new(event.width as f64, event.height as f64))
+        self.size_changed(Size::new(event.width as f64, event.height as f64))?;
+        // The window may have moved to a monitor with a different refresh rate.
+        let refresh_rate = util::refresh_rate(self.app.connection(), self.id);
+        if self.frame_throttle.set_refresh_rate(refresh_rate) {
+            let interval = self.frame_throttle.refresh_interval();
+            self.with_handler(|h| h.refresh_interval_changed(interval));
+        }
+        Ok(())
     }
 
     pub(crate) fn run_idle(&self) {
@@ -935,27 +1631,58 @@ impl Window {
         }
     }
 
+    fn set_max_frame_rate(&self, fps: Option<f32>) {
+        self.frame_throttle.set_max_frame_rate(fps);
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        self.frame_throttle.refresh_interval()
+    }
+
     pub(crate) fn next_timeout(&self) -> Option<Instant> {
-        self.timer_queue
-            .lock()
-            .unwrap()
-            .peek()
-            .map(|timer| timer.deadline())
+        let timers = self.timer_queue.lock().unwrap().next_wakeup();
+        [timers, self.throttled_frame.get(), self.momentum.deadline()]
+            .iter()
+            .flatten()
+            .min()
+            .copied()
     }
 
     pub(crate) fn run_timers(&self, now: Instant) {
-        while let Some(deadline) = self.next_timeout() {
-            if deadline > now {
-                break;
-            }
-            // Remove the timer and get the token
-            let token = self.timer_queue.lock().unwrap().pop().unwrap().token();
+        if matches!(self.throttled_frame.get(), Some(t) if t <= now) {
+            self.throttled_frame.set(None);
+            self.request_redraw();
+        }
+
+        if let Some(event) = self.momentum.tick(now) {
+            self.with_handler(|h| h.wheel(&event));
+        }
+
+        // Don't hold the lock while the handler runs, since it may request more timers.
+        loop {
+            let timer = self.timer_queue.lock().unwrap().pop_expired(now);
+            let token = match timer {
+                Some(timer) => timer.token(),
+                None => break,
+            };
             self.with_handler(|h| h.timer(token));
         }
     }
 }
 
 // Converts from, e.g., the `details` field of `xcb::xproto::ButtonPressEvent`
+/// Where an event came from, given its `response_type`.
+///
+/// The server sets the high bit of the response type on events that a client sent with
+/// `SendEvent`.
+fn input_source(response_type: u8) -> InputSource {
+    if response_type & 0x80 != 0 {
+        InputSource::Injected
+    } else {
+        session_input_source()
+    }
+}
+
 fn mouse_button(button: u8) -> MouseButton {
     match button {
         1 => MouseButton::Left,
@@ -1030,19 +1757,7 @@ pub(crate) enum IdleKind {
 
 impl IdleHandle {
     fn wake(&self) {
-        loop {
-            match nix::unistd::write(self.pipe, &[0]) {
-                Err(nix::errno::Errno::EINTR) => {}
-                Err(nix::errno::Errno::EAGAIN) => {}
-                Err(e) => {
-                    error!("Failed to write to idle pipe: {}", e);
-                    break;
-                }
-                Ok(_) => {
-                    break;
-                }
-            }
-        }
+        super::application::wake_idle_pipe(self.pipe);
     }
 
     pub(crate) fn schedule_redraw(&self) {
@@ -1138,8 +1853,30 @@ impl WindowHandle {
         }
     }
 
+    pub fn monitor(&self) -> Option<Monitor> {
+        let origin = self.map_to_screen(Point::ZERO);
+        screen::monitor_containing(Rect::from_origin_size(origin, self.get_size()))
+    }
+
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        if let Some(w) = self.window.upgrade() {
+            w.map_to_screen(point)
+        } else {
+            error!("Window {} has already been dropped", self.id);
+            point
+        }
+    }
+
     pub fn content_insets(&self) -> Insets {
-        warn!("WindowHandle::content_insets unimplemented for X11 backend.");
+        if let Some(w) = self.window.upgrade() {
+            w.content_insets()
+        } else {
+            error!("Window {} has already been dropped", self.id);
+            Insets::ZERO
+        }
+    }
+
+    pub fn safe_area(&self) -> Insets {
         Insets::ZERO
     }
 
@@ -1169,6 +1906,26 @@ impl WindowHandle {
         window::WindowState::Restored
     }
 
+    pub fn set_fullscreen(&self, options: Option<FullscreenOptions>) {
+        if let Some(w) = self.window.upgrade() {
+            w.set_fullscreen(options.is_some());
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn preferred_output(&self, _monitor: Option<MonitorId>) {
+        // The window manager puts fullscreen windows on the monitor that they are on.
+    }
+
+    pub(crate) fn set_kiosk(&self, options: KioskOptions) {
+        if let Some(w) = self.window.upgrade() {
+            w.set_kiosk(options);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
     pub fn handle_titlebar(&self, _val: bool) {
         warn!("WindowHandle::handle_titlebar is currently unimplemented for X11 backend.");
     }
@@ -1181,6 +1938,63 @@ impl WindowHandle {
         }
     }
 
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        if let Some(w) = self.window.upgrade() {
+            w.app.keyboard_layout()
+        } else {
+            error!("Window {} has already been dropped", self.id);
+            None
+        }
+    }
+
+    pub fn locale_info(&self) -> LocaleInfo {
+        Application::locale_info()
+    }
+
+    pub fn raise_above(&self, other: &WindowHandle) {
+        if let Some(w) = self.window.upgrade() {
+            w.restack(other.id, xproto::StackMode::ABOVE);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn lower_below(&self, other: &WindowHandle) {
+        if let Some(w) = self.window.upgrade() {
+            w.restack(other.id, xproto::StackMode::BELOW);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        if let Some(w) = self.window.upgrade() {
+            w.set_mouse_passthrough(passthrough);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        if let Some(w) = self.window.upgrade() {
+            w.key_repeat.set(enabled);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        // We don't talk to input methods, so key events are never composed.
+    }
+
+    pub fn set_shape(&self, shape: Option<BezPath>) {
+        if let Some(w) = self.window.upgrade() {
+            w.set_shape(shape);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
     pub fn request_anim_frame(&self) {
         if let Some(w) = self.window.upgrade() {
             w.request_anim_frame();
@@ -1189,6 +2003,23 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        if let Some(w) = self.window.upgrade() {
+            w.set_max_frame_rate(fps);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        if let Some(w) = self.window.upgrade() {
+            w.refresh_interval()
+        } else {
+            error!("Window {} has already been dropped", self.id);
+            DEFAULT_REFRESH_INTERVAL
+        }
+    }
+
     pub fn invalidate(&self) {
         if let Some(w) = self.window.upgrade() {
             w.invalidate();
@@ -1205,6 +2036,14 @@ impl WindowHandle {
         }
     }
 
+    pub fn scroll_region(&self, rect: Rect, delta: Vec2) {
+        if let Some(w) = self.window.upgrade() {
+            w.scroll_region(rect, delta);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
     pub fn set_title(&self, title: &str) {
         if let Some(w) = self.window.upgrade() {
             w.set_title(title);
@@ -1213,6 +2052,14 @@ impl WindowHandle {
         }
     }
 
+    pub fn set_theme_override(&self, theme: Option<Theme>) {
+        if let Some(w) = self.window.upgrade() {
+            w.set_theme_override(theme);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
     pub fn set_menu(&self, menu: Menu) {
         if let Some(w) = self.window.upgrade() {
             w.set_menu(menu);
@@ -1244,8 +2091,16 @@ impl WindowHandle {
     }
 
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.request_timer_with_tolerance(deadline, Duration::ZERO)
+    }
+
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Instant,
+        tolerance: Duration,
+    ) -> TimerToken {
         if let Some(w) = self.window.upgrade() {
-            let timer = Timer::new(deadline, ());
+            let timer = Timer::with_tolerance(deadline, tolerance, ());
             w.timer_queue.lock().unwrap().push(timer);
             timer.token()
         } else {
@@ -1253,12 +2108,72 @@ impl WindowHandle {
         }
     }
 
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        if let Some(w) = self.window.upgrade() {
+            let timer = Timer::repeating(interval, ());
+            w.timer_queue.lock().unwrap().push(timer);
+            timer.token()
+        } else {
+            TimerToken::INVALID
+        }
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        if let Some(w) = self.window.upgrade() {
+            w.timer_queue.lock().unwrap().cancel(token);
+        }
+    }
+
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         if let Some(w) = self.window.upgrade() {
             w.set_cursor(cursor);
         }
     }
 
+    pub fn confine_pointer(&self, region: Option<Rect>) {
+        if let Some(w) = self.window.upgrade() {
+            w.confine_pointer(region);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn lock_pointer(&self) {
+        if let Some(w) = self.window.upgrade() {
+            w.lock_pointer();
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn unlock_pointer(&self) {
+        if let Some(w) = self.window.upgrade() {
+            w.unlock_pointer();
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn set_raw_pointer_input(&self, _enabled: bool) {
+        warn!("WindowHandle::set_raw_pointer_input is currently unimplemented for X11 backend.");
+    }
+
+    pub fn begin_autoscroll(&self, origin: Point) {
+        if let Some(w) = self.window.upgrade() {
+            w.autoscroll.begin(origin);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
+    pub fn begin_shortcut_capture(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        if let Some(w) = self.window.upgrade() {
+            w.shortcut_capture.begin(callback);
+        } else {
+            error!("Window {} has already been dropped", self.id);
+        }
+    }
+
     pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
         if let Some(w) = self.window.upgrade() {
             match w.app.render_argb32_pictformat_cursor() {
@@ -1285,6 +2200,14 @@ impl WindowHandle {
         }
     }
 
+    pub fn start_drag(&self, _data: DragData, _image: Option<DragImage>) {
+        warn!("start_drag is currently unimplemented for X11");
+    }
+
+    pub fn set_drop_formats(&self, _formats: &[FormatId]) {
+        // TODO: implement XDND, so that there are drops to read formats from.
+    }
+
     pub fn open_file(&mut self, options: FileDialogOptions) -> Option<FileDialogToken> {
         if let Some(w) = self.window.upgrade() {
             if let Some(idle) = self.get_idle_handle() {
@@ -1332,6 +2255,21 @@ impl WindowHandle {
         }
     }
 
+    pub fn text_scale_factor(&self) -> f64 {
+        // Desktops put the text scaling factor into Xft.dpi, which we already use for the scale.
+        1.0
+    }
+
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        // X11 has no standard way of telling applications about a high-contrast theme.
+        None
+    }
+
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        // Every toolkit draws its own scrollbars on X11.
+        ScrollbarMetrics::default()
+    }
+
     #[cfg(feature = "accesskit")]
     pub fn update_accesskit_if_active(
         &self,
@@ -1364,12 +2302,86 @@ unsafe impl HasRawDisplayHandle for WindowHandle {
         RawDisplayHandle::Xcb(handle)
     }
 }
+
+impl crate::platform::linux::WindowHandleExt for crate::WindowHandle {
+    fn export_handle(&self, f: Box<dyn FnOnce(Option<String>)>) {
+        // X11 window ids are global, so there's nothing to export.
+        f(Some(format!("x11:{:x}", self.0.id)));
+    }
+
+    fn set_transient_for_foreign(&self, handle: &str) -> bool {
+        let parent = match handle
+            .strip_prefix("x11:")
+            .and_then(|id| u32::from_str_radix(id, 16).ok())
+        {
+            Some(parent) => parent,
+            None => return false,
+        };
+        if let Some(w) = self.0.window.upgrade() {
+            w.set_transient_for(parent);
+            true
+        } else {
+            error!("Window {} has already been dropped", self.0.id);
+            false
+        }
+    }
+
+    fn set_momentum_scroll(&self, enabled: bool) {
+        if let Some(w) = self.0.window.upgrade() {
+            w.momentum.set_enabled(enabled);
+        } else {
+            error!("Window {} has already been dropped", self.0.id);
+        }
+    }
+
+    fn set_client_decorations(&self, _theme: Option<crate::platform::linux::DecorationTheme>) {
+        // The window manager always draws the decorations.
+    }
+}
+
 fn make_cursor(
-    _conn: &XCBConnection,
-    _byte_order: X11ImageOrder,
-    _root_window: u32,
-    _argb32_format: Pictformat,
-    _desc: &CursorDesc,
+    conn: &XCBConnection,
+    byte_order: X11ImageOrder,
+    root_window: u32,
+    argb32_format: Pictformat,
+    desc: &CursorDesc,
 ) -> Result<Cursor, ReplyOrIdError> {
-    Ok(Cursor::Arrow)
+    // RENDER wants premultiplied alpha, like we have, but in a u32 with ARGB.
+    let pixels = desc
+        .rgba()
+        .flat_map(|[r, g, b, a]| match byte_order {
+            X11ImageOrder::LSB_FIRST => [b, g, r, a],
+            _ => [a, r, g, b],
+        })
+        .collect::<Vec<u8>>();
+    let width = u16::try_from(desc.width).expect("Invalid cursor width");
+    let height = u16::try_from(desc.height).expect("Invalid cursor height");
+
+    // Get the image into a RENDER Picture.
+    let pixmap = conn.generate_id()?;
+    let gc = conn.generate_id()?;
+    let picture = conn.generate_id()?;
+    conn.create_pixmap(32, pixmap, root_window, width, height)?;
+    conn.create_gc(gc, pixmap, &Default::default())?;
+    conn.put_image(
+        xproto::ImageFormat::Z_PIXMAP,
+        pixmap,
+        gc,
+        width,
+        height,
+        0,
+        0,
+        0,
+        32,
+        &pixels,
+    )?;
+    conn.render_create_picture(picture, pixmap, argb32_format, &Default::default())?;
+    conn.free_gc(gc)?;
+    conn.free_pixmap(pixmap)?;
+
+    let cursor = conn.generate_id()?;
+    conn.render_create_cursor(cursor, picture, desc.hot.x as u16, desc.hot.y as u16)?;
+    conn.render_free_picture(picture)?;
+
+    Ok(Cursor::Custom(CustomCursor(cursor)))
 }
@@ -0,0 +1,496 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measuring input-to-paint latency.
+//!
+//! [`BenchHandler`] wraps an application's [`WinHandler`] and feeds it synthetic mouse
+//! moves at a fixed rate, using the window's timers. It records how long the handler takes
+//! to process each event, how long it takes for a paint to follow, and how long painting
+//! takes. When the run is over, the results are handed to a callback as a [`BenchReport`],
+//! which can be written out as JSON for tracking regressions.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use glazier::{Application, WinHandler, WindowBuilder};
+//! # use glazier::bench::{BenchConfig, BenchHandler};
+//! # fn bench(app: Application, handler: Box<dyn WinHandler>) {
+//! let config = BenchConfig {
+//!     duration: Duration::from_secs(10),
+//!     ..Default::default()
+//! };
+//! let handler = BenchHandler::new(handler, config, |report| println!("{}", report.to_json()));
+//! let mut builder = WindowBuilder::new(app);
+//! builder.set_handler(Box::new(handler));
+//! # }
+//! ```
+
+use std::any::Any;
+use std::time::Duration;
+
+use instant::Instant;
+
+use crate::keyboard::{CompositionEvent, KeyEvent};
+use crate::kurbo::{Insets, Point, Size, Vec2};
+use crate::mouse::{
+    ForceClick, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent, ScrollDelta,
+};
+use crate::text::InputHandler;
+use crate::window::{
+    DecorationMode, FileDialogToken, FocusDirection, FrameInfo, IdleToken, PaintReason, TimerToken,
+    WinHandler, WindowHandle,
+};
+use crate::{
+    DragData, DropEffect, FileInfo, HighContrastColors, KeyboardLayout, LocaleInfo, Modifiers,
+    MonitorId, Region, Scale, TextFieldToken,
+};
+
+/// Settings for a [`BenchHandler`] run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchConfig {
+    /// How many synthetic input events to send per second.
+    pub input_rate: f64,
+    /// How long to keep sending input.
+    pub duration: Duration,
+    /// The expected time between frames, used to count missed frames.
+    pub frame_interval: Duration,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig {
+            input_rate: 120.0,
+            duration: Duration::from_secs(5),
+            frame_interval: Duration::from_micros(16_667),
+        }
+    }
+}
+
+/// Summary statistics for a set of durations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// Compute the statistics of `samples`.
+    pub fn from_samples(samples: &[Duration]) -> LatencyStats {
+        if samples.is_empty() {
+            return LatencyStats::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+        LatencyStats {
+            count: sorted.len(),
+            min: sorted[0],
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            median: percentile(50),
+            p95: percentile(95),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    fn to_json(self) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        format!(
+            r#"{{"count":{},"min_ms":{:.3},"mean_ms":{:.3},"median_ms":{:.3},"p95_ms":{:.3},"max_ms":{:.3}}}"#,
+            self.count,
+            ms(self.min),
+            ms(self.mean),
+            ms(self.median),
+            ms(self.p95),
+            ms(self.max)
+        )
+    }
+}
+
+/// The results of a [`BenchHandler`] run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    /// The number of synthetic input events that were sent.
+    pub events: usize,
+    /// The time the handler took to process each input event.
+    pub handler_latency: LatencyStats,
+    /// The time from an input event to the start of the next paint.
+    ///
+    /// If several events arrive before a paint, only the oldest one is measured.
+    pub paint_delay: LatencyStats,
+    /// The time the handler took to paint.
+    pub paint_duration: LatencyStats,
+    /// The number of frame intervals that passed between an input event and its paint,
+    /// beyond the first.
+    pub missed_frames: usize,
+}
+
+impl BenchReport {
+    /// Format the report as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"events":{},"handler_latency":{},"paint_delay":{},"paint_duration":{},"missed_frames":{}}}"#,
+            self.events,
+            self.handler_latency.to_json(),
+            self.paint_delay.to_json(),
+            self.paint_duration.to_json(),
+            self.missed_frames
+        )
+    }
+}
+
+/// A [`WinHandler`] that drives another one with synthetic input and measures it.
+///
+/// See the [module documentation](self) for details.
+pub struct BenchHandler {
+    inner: Box<dyn WinHandler>,
+    config: BenchConfig,
+    on_finish: Option<Box<dyn FnOnce(BenchReport)>>,
+    handle: WindowHandle,
+    size: Size,
+    start: Option<Instant>,
+    timer: Option<TimerToken>,
+    events: usize,
+    /// The time of the oldest input event that hasn't been painted yet.
+    pending_input: Option<Instant>,
+    handler_latency: Vec<Duration>,
+    paint_delay: Vec<Duration>,
+    paint_duration: Vec<Duration>,
+    missed_frames: usize,
+}
+
+impl BenchHandler {
+    /// Wrap `inner`, calling `on_finish` with the results once `config.duration` has passed.
+    pub fn new(
+        inner: Box<dyn WinHandler>,
+        config: BenchConfig,
+        on_finish: impl FnOnce(BenchReport) + 'static,
+    ) -> BenchHandler {
+        BenchHandler {
+            inner,
+            config,
+            on_finish: Some(Box::new(on_finish)),
+            handle: WindowHandle::default(),
+            size: Size::ZERO,
+            start: None,
+            timer: None,
+            events: 0,
+            pending_input: None,
+            handler_latency: Vec::new(),
+            paint_delay: Vec::new(),
+            paint_duration: Vec::new(),
+            missed_frames: 0,
+        }
+    }
+
+    fn schedule_next_event(&mut self) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        let offset = Duration::from_secs_f64(self.events as f64 / self.config.input_rate);
+        if offset >= self.config.duration {
+            self.finish();
+            return;
+        }
+        // Schedule relative to the start, so that slow handlers don't lower the rate.
+        let delay = (start + offset).saturating_duration_since(Instant::now());
+        self.timer = Some(self.handle.request_timer(delay));
+    }
+
+    fn send_event(&mut self) {
+        // Move in an ellipse around the middle of the window.
+        let angle = self.events as f64 * 0.1;
+        let center = self.size.to_rect().center();
+        let radius = Vec2::new(self.size.width / 3.0, self.size.height / 3.0);
+        let event = MouseEvent {
+            pos: Point::new(
+                center.x + radius.x * angle.cos(),
+                center.y + radius.y * angle.sin(),
+            ),
+            buttons: MouseButtons::new(),
+            mods: Modifiers::empty(),
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: InputSource::Injected,
+            coalesced: Vec::new(),
+        };
+
+        let now = Instant::now();
+        self.pending_input.get_or_insert(now);
+        self.inner.mouse_move(&event);
+        self.handler_latency.push(now.elapsed());
+        self.events += 1;
+    }
+
+    fn finish(&mut self) {
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(BenchReport {
+                events: self.events,
+                handler_latency: LatencyStats::from_samples(&self.handler_latency),
+                paint_delay: LatencyStats::from_samples(&self.paint_delay),
+                paint_duration: LatencyStats::from_samples(&self.paint_duration),
+                missed_frames: self.missed_frames,
+            });
+        }
+    }
+}
+
+impl WinHandler for BenchHandler {
+    fn connect(&mut self, handle: &WindowHandle) {
+        self.handle = handle.clone();
+        self.inner.connect(handle);
+        self.start = Some(Instant::now());
+        self.schedule_next_event();
+    }
+
+    fn size(&mut self, size: Size) {
+        self.size = size;
+        self.inner.size(size)
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.inner.safe_area_changed(insets)
+    }
+
+    fn scale(&mut self, scale: Scale) {
+        self.inner.scale(scale)
+    }
+
+    fn text_scale_factor_changed(&mut self, factor: f64) {
+        self.inner.text_scale_factor_changed(factor)
+    }
+
+    fn high_contrast_changed(&mut self, colors: Option<HighContrastColors>) {
+        self.inner.high_contrast_changed(colors)
+    }
+
+    fn decoration_mode_changed(&mut self, mode: DecorationMode) {
+        self.inner.decoration_mode_changed(mode)
+    }
+
+    fn prepare_frame(&mut self, info: FrameInfo) {
+        self.inner.prepare_frame(info)
+    }
+
+    fn prepare_paint(&mut self) {
+        self.inner.prepare_paint()
+    }
+
+    fn paint(&mut self, invalid: &Region, reason: PaintReason) {
+        let now = Instant::now();
+        if let Some(input) = self.pending_input.take() {
+            let delay = now - input;
+            self.paint_delay.push(delay);
+            let frames = delay.as_secs_f64() / self.config.frame_interval.as_secs_f64();
+            self.missed_frames += frames as usize;
+        }
+        self.inner.paint(invalid, reason);
+        self.paint_duration.push(now.elapsed());
+    }
+
+    fn frame_missed(&mut self, count: u32) {
+        self.inner.frame_missed(count)
+    }
+
+    fn refresh_interval_changed(&mut self, interval: Duration) {
+        self.inner.refresh_interval_changed(interval)
+    }
+
+    fn outputs_changed(&mut self, outputs: &[MonitorId]) {
+        self.inner.outputs_changed(outputs)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_tree(&mut self) -> accesskit::TreeUpdate {
+        self.inner.accesskit_tree()
+    }
+
+    fn rebuild_resources(&mut self) {
+        self.inner.rebuild_resources()
+    }
+
+    fn command(&mut self, id: u32) {
+        self.inner.command(id)
+    }
+
+    fn save_as(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.inner.save_as(token, file)
+    }
+
+    fn open_file(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.inner.open_file(token, file)
+    }
+
+    fn open_files(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.inner.open_files(token, files)
+    }
+
+    fn key_down(&mut self, event: KeyEvent) -> bool {
+        self.inner.key_down(event)
+    }
+
+    fn focus_navigation(&mut self, direction: FocusDirection) -> bool {
+        self.inner.focus_navigation(direction)
+    }
+
+    fn key_up(&mut self, event: KeyEvent) {
+        self.inner.key_up(event)
+    }
+
+    fn modifiers_changed(&mut self, mods: Modifiers) {
+        self.inner.modifiers_changed(mods)
+    }
+
+    fn input_language_changed(&mut self, layout: KeyboardLayout) {
+        self.inner.input_language_changed(layout)
+    }
+
+    fn locale_changed(&mut self, info: LocaleInfo) {
+        self.inner.locale_changed(info)
+    }
+
+    fn composition(&mut self, event: CompositionEvent) {
+        self.inner.composition(event)
+    }
+
+    fn acquire_input_lock(
+        &mut self,
+        token: TextFieldToken,
+        mutable: bool,
+    ) -> Box<dyn InputHandler> {
+        self.inner.acquire_input_lock(token, mutable)
+    }
+
+    fn release_input_lock(&mut self, token: TextFieldToken) {
+        self.inner.release_input_lock(token)
+    }
+
+    fn wheel(&mut self, event: &MouseEvent) {
+        self.inner.wheel(event)
+    }
+
+    fn zoom(&mut self, delta: f64) {
+        self.inner.zoom(delta)
+    }
+
+    fn force_click(&mut self, event: &ForceClick) {
+        self.inner.force_click(event)
+    }
+
+    fn mouse_move(&mut self, event: &MouseEvent) {
+        self.inner.mouse_move(event)
+    }
+
+    fn mouse_down(&mut self, event: &MouseEvent) {
+        self.inner.mouse_down(event)
+    }
+
+    fn mouse_up(&mut self, event: &MouseEvent) {
+        self.inner.mouse_up(event)
+    }
+
+    fn mouse_leave(&mut self) {
+        self.inner.mouse_leave()
+    }
+
+    fn raw_pointer_motion(&mut self, delta: Vec2) {
+        self.inner.raw_pointer_motion(delta)
+    }
+
+    fn pointer_lock_lost(&mut self) {
+        self.inner.pointer_lock_lost()
+    }
+
+    fn drag_enter(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_enter(pos, data)
+    }
+
+    fn drag_over(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_over(pos, data)
+    }
+
+    fn drag_leave(&mut self) {
+        self.inner.drag_leave()
+    }
+
+    fn drag_drop(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_drop(pos, data)
+    }
+
+    fn timer(&mut self, token: TimerToken) {
+        if Some(token) == self.timer {
+            self.timer = None;
+            self.send_event();
+            self.schedule_next_event();
+        } else {
+            self.inner.timer(token)
+        }
+    }
+
+    fn got_focus(&mut self) {
+        self.inner.got_focus()
+    }
+
+    fn lost_focus(&mut self) {
+        self.inner.lost_focus()
+    }
+
+    fn request_close(&mut self) {
+        self.inner.request_close()
+    }
+
+    fn destroy(&mut self) {
+        self.finish();
+        self.inner.destroy()
+    }
+
+    fn idle(&mut self, token: IdleToken) {
+        self.inner.idle(token)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_action(&mut self, request: accesskit::ActionRequest) {
+        self.inner.accesskit_action(request)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self.inner.as_any()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_stats() {
+        let samples: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        let stats = LatencyStats::from_samples(&samples);
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.median, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(
+            LatencyStats::from_samples(&[]).to_json(),
+            r#"{"count":0,"min_ms":0.000,"mean_ms":0.000,"median_ms":0.000,"p95_ms":0.000,"max_ms":0.000}"#
+        );
+    }
+}
@@ -15,6 +15,8 @@
 //! Interacting with the system pasteboard/clipboard.
 pub use crate::backend::clipboard as backend;
 
+use crate::strings::{self, BuiltinString};
+
 /// A handle to the system clipboard.
 ///
 /// To get access to the global clipboard, call [`Application::clipboard()`].
@@ -140,10 +142,33 @@ impl Clipboard {
     }
 
     /// Get a string from the system clipboard, if one is available.
+    ///
+    /// This is the same as [`get_string_raw`]. Most applications pasting into a text field
+    /// want [`get_string_lossy`] instead.
+    ///
+    /// [`get_string_raw`]: Clipboard::get_string_raw
+    /// [`get_string_lossy`]: Clipboard::get_string_lossy
     pub fn get_string(&self) -> Option<String> {
+        self.get_string_raw()
+    }
+
+    /// Get a string from the system clipboard exactly as the platform provides it.
+    ///
+    /// Depending on the platform and the application that copied it, the string may use
+    /// `\r\n` or `\r` line endings, and may contain control characters such as a trailing
+    /// NUL.
+    pub fn get_string_raw(&self) -> Option<String> {
         self.0.get_string()
     }
 
+    /// Get a string from the system clipboard, as plain text.
+    ///
+    /// Line endings are normalized to `\n`, and control characters other than newlines and
+    /// tabs are removed.
+    pub fn get_string_lossy(&self) -> Option<String> {
+        self.get_string_raw().map(|s| plain_text(&s))
+    }
+
     /// Given a list of supported clipboard types, returns the supported type which has
     /// highest priority on the system clipboard, or `None` if no types are supported.
     pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
@@ -161,6 +186,22 @@ impl Clipboard {
         self.0.get_format(format)
     }
 
+    /// Returns the formats that the clipboard currently holds, with descriptions that can be
+    /// shown to the user, for instance in a "Paste Special" dialog.
+    ///
+    /// The formats are in the order of the platform's preference, where it has one. Sizes are
+    /// only known where the platform provides them: on Windows, and on X11 when the data was
+    /// copied from this application. On Windows, asking for the size can make the application
+    /// that copied the data produce it, if it hasn't yet.
+    pub fn available_formats_detailed(&self) -> Vec<ClipboardFormatInfo> {
+        self.0
+            .available_formats()
+            .into_iter()
+            .filter(|(identifier, _)| !SELECTION_TARGETS.contains(&identifier.as_str()))
+            .map(|(identifier, size)| ClipboardFormatInfo::new(identifier, size))
+            .collect()
+    }
+
     /// For debugging: print the resolved identifiers for each type currently
     /// on the clipboard.
     #[doc(hidden)]
@@ -169,6 +210,93 @@ impl Clipboard {
     }
 }
 
+/// The targets of the X11 selection protocol that aren't data formats.
+const SELECTION_TARGETS: &[&str] = &["TARGETS", "TIMESTAMP", "MULTIPLE", "SAVE_TARGETS"];
+
+/// A format that the clipboard holds data in. See [`Clipboard::available_formats_detailed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardFormatInfo {
+    /// The platform's identifier for the format, which can be compared with the [`FormatId`]s
+    /// passed to [`Clipboard::get_format`].
+    pub identifier: String,
+    /// A name for the format that the user would recognize, such as "HTML". Formats that we
+    /// don't know are described by their identifier.
+    ///
+    /// The names are translated like glazier's other [built-in strings], and can be replaced
+    /// the same way.
+    ///
+    /// [built-in strings]: crate::BuiltinString
+    pub description: String,
+    /// The size of the data, in bytes, if it is known.
+    pub size: Option<usize>,
+}
+
+impl ClipboardFormatInfo {
+    fn new(identifier: String, size: Option<usize>) -> Self {
+        let description = match format_description(&identifier) {
+            Some(string) => strings::get(string),
+            None => identifier.clone(),
+        };
+        ClipboardFormatInfo {
+            identifier,
+            description,
+            size,
+        }
+    }
+}
+
+/// The built-in description of a common format, given its MIME type, macOS UTI, Windows
+/// format name or X11 target.
+fn format_description(identifier: &str) -> Option<BuiltinString> {
+    use BuiltinString::*;
+    // MIME types are case-insensitive and may have parameters, like `;charset=utf-8`.
+    let identifier = identifier.to_ascii_lowercase();
+    let essence = identifier.split(';').next().unwrap_or("").trim();
+    let string = match essence {
+        "text/plain"
+        | "utf8_string"
+        | "string"
+        | "text"
+        | "public.plain-text"
+        | "public.utf8-plain-text"
+        | "public.utf16-plain-text"
+        | "cf_text"
+        | "cf_oemtext"
+        | "cf_unicodetext" => FormatPlainText,
+        "text/html" | "public.html" | "html format" => FormatHtml,
+        "text/rtf" | "application/rtf" | "public.rtf" | "rich text format" => FormatRichText,
+        "image/png" | "public.png" | "png" => FormatPng,
+        "image/jpeg" | "public.jpeg" | "jfif" => FormatJpeg,
+        "image/gif" | "com.compuserve.gif" | "gif" => FormatGif,
+        "image/tiff" | "public.tiff" | "cf_tiff" => FormatTiff,
+        "image/bmp" | "com.microsoft.bmp" | "cf_bitmap" | "cf_dib" | "cf_dibv5" => FormatBitmap,
+        "image/svg+xml" | "public.svg-image" => FormatSvg,
+        "application/pdf" | "com.adobe.pdf" => FormatPdf,
+        "text/uri-list" | "public.file-url" | "cf_hdrop" => FormatFiles,
+        "text/x-moz-url" | "public.url" | "uniformresourcelocatorw" => FormatLink,
+        _ => return None,
+    };
+    Some(string)
+}
+
+/// Normalizes line endings in `s` to `\n`, and removes other control characters except tabs.
+fn plain_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                out.push('\n');
+            }
+            '\n' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 /// A type identifier for the system clipboard.
 ///
 /// These should be [`UTI` strings] on macOS, and (by convention?) [MIME types] elsewhere.
@@ -195,6 +323,16 @@ impl ClipboardFormat {
         let data = data.into();
         ClipboardFormat { identifier, data }
     }
+
+    /// The type identifier of the data.
+    pub fn identifier(&self) -> FormatId {
+        self.identifier
+    }
+
+    /// The data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
 }
 
 impl From<String> for ClipboardFormat {
@@ -238,3 +376,32 @@ cfg_if::cfg_if! {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_normalization() {
+        assert_eq!(plain_text("a\r\nb\rc\nd"), "a\nb\nc\nd");
+        assert_eq!(plain_text("a\r\r\nb"), "a\n\nb");
+        assert_eq!(plain_text("tab\there\u{7}\0"), "tab\there");
+    }
+
+    #[test]
+    fn format_descriptions() {
+        assert_eq!(
+            format_description("text/plain;charset=utf-8"),
+            Some(BuiltinString::FormatPlainText)
+        );
+        assert_eq!(
+            format_description("HTML Format"),
+            Some(BuiltinString::FormatHtml)
+        );
+        assert_eq!(
+            format_description("public.png"),
+            Some(BuiltinString::FormatPng)
+        );
+        assert_eq!(format_description("application/x-custom"), None);
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The colors of the user's high-contrast theme.
+
+/// An opaque color in the sRGB color space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SystemColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl SystemColor {
+    /// Creates a color from its red, green and blue components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> SystemColor {
+        SystemColor { r, g, b }
+    }
+}
+
+/// The palette of a high-contrast theme.
+///
+/// While a high-contrast theme is active, applications should draw with these colors instead
+/// of their own, so that the user can read them. Get the palette with
+/// [`WindowHandle::high_contrast_colors`]; changes are reported through
+/// [`WinHandler::high_contrast_changed`].
+///
+/// [`WindowHandle::high_contrast_colors`]: crate::WindowHandle::high_contrast_colors
+/// [`WinHandler::high_contrast_changed`]: crate::WinHandler::high_contrast_changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HighContrastColors {
+    /// The background of windows and text fields.
+    pub window: SystemColor,
+    /// Text on the window background.
+    pub window_text: SystemColor,
+    /// The background of selected items and text.
+    pub highlight: SystemColor,
+    /// Selected text.
+    pub highlight_text: SystemColor,
+    /// The face of buttons.
+    pub button: SystemColor,
+    /// Text on buttons.
+    pub button_text: SystemColor,
+    /// Text of disabled controls.
+    pub disabled_text: SystemColor,
+    /// Hyperlinks.
+    pub hyperlink: SystemColor,
+}
@@ -17,11 +17,16 @@
 use std::cell::Cell;
 use std::num::NonZeroU64;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 use std::time::Duration;
 
 use instant::Instant;
 
-use crate::kurbo::Point;
+use crate::kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape, Size};
+use crate::mouse::InputSource;
+use crate::window::{FrameInfo, PaintReason};
+use crate::AppHandler;
+use crate::Scale;
 use crate::WinHandler;
 
 // This is the default timing on windows.
@@ -50,6 +55,46 @@ pub fn strip_access_key(raw_menu_text: &str) -> String {
     result
 }
 
+/// Shrink `size` so that it is `base` plus a whole number of `increments`.
+///
+/// This is for backends that have to apply resize increments themselves.
+#[allow(dead_code)]
+pub(crate) fn snap_to_increments(size: Size, base: Size, increments: Size) -> Size {
+    let snap = |len: f64, base: f64, increment: f64| {
+        if increment <= 0.0 || len <= base {
+            len
+        } else {
+            base + ((len - base) / increment).floor() * increment
+        }
+    };
+    Size::new(
+        snap(size.width, base.width, increments.width),
+        snap(size.height, base.height, increments.height),
+    )
+}
+
+/// The source to report for pointer events on Linux, where the display server doesn't say
+/// whether they came over a remote desktop connection.
+///
+/// We guess from the environment: xrdp sets `XRDP_SESSION`, and a forwarded X11 display has
+/// a host name in `DISPLAY`, such as `localhost:10.0`.
+#[allow(dead_code)]
+pub(crate) fn session_input_source() -> InputSource {
+    static SOURCE: OnceLock<InputSource> = OnceLock::new();
+    *SOURCE.get_or_init(|| {
+        let xrdp = std::env::var_os("XRDP_SESSION").is_some();
+        let forwarded = std::env::var("DISPLAY")
+            .ok()
+            .and_then(|display| display.split(':').next().map(|host| !host.is_empty()))
+            .unwrap_or(false);
+        if xrdp || forwarded {
+            InputSource::Remote
+        } else {
+            InputSource::Hardware
+        }
+    })
+}
+
 /// A trait for implementing the boxed callback hack.
 pub(crate) trait IdleCallback: Send {
     fn call(self: Box<Self>, a: &mut dyn WinHandler);
@@ -152,3 +197,401 @@ impl Default for ClickCounter {
         ClickCounter::new(MULTI_CLICK_INTERVAL, MULTI_CLICK_MAX_DISTANCE)
     }
 }
+
+/// The refresh interval we assume if the backend doesn't know the real one.
+pub(crate) const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// The timing of a frame that starts now, assuming that it is shown one refresh interval
+/// later.
+pub(crate) fn estimated_frame_info(refresh_interval: Duration) -> FrameInfo {
+    FrameInfo {
+        present_time: Instant::now() + refresh_interval,
+        refresh_interval,
+    }
+}
+
+/// Limits how often a window starts a new animation frame.
+///
+/// Backends record each frame with [`frame_started`], and check [`next_frame_deadline`]
+/// before scheduling another one. They also ask for [`missed_frames`] before painting, to
+/// tell the handler about animation frames that never happened.
+///
+/// [`frame_started`]: FrameThrottle::frame_started
+/// [`next_frame_deadline`]: FrameThrottle::next_frame_deadline
+/// [`missed_frames`]: FrameThrottle::missed_frames
+#[derive(Debug, Default)]
+pub(crate) struct FrameThrottle {
+    min_interval: Cell<Option<Duration>>,
+    last_frame: Cell<Option<Instant>>,
+    refresh_interval: Cell<Option<Duration>>,
+    last_anim_frame: Cell<Option<Instant>>,
+}
+
+#[allow(dead_code)]
+impl FrameThrottle {
+    /// Set the maximum number of frames per second, or `None` for no limit.
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        let min_interval = fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.min_interval.set(min_interval);
+    }
+
+    /// If a frame can't start yet, returns the time at which it can.
+    pub fn next_frame_deadline(&self) -> Option<Instant> {
+        let next = self.last_frame.get()? + self.min_interval.get()?;
+        if next > Instant::now() {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    /// Record that a frame is starting now.
+    pub fn frame_started(&self) {
+        self.last_frame.set(Some(Instant::now()));
+    }
+
+    /// Set the refresh rate of the display that the window is on, if it is known.
+    ///
+    /// Returns `true` if this changes the [`refresh_interval`].
+    ///
+    /// [`refresh_interval`]: FrameThrottle::refresh_interval
+    pub fn set_refresh_rate(&self, hz: Option<f64>) -> bool {
+        let old = self.refresh_interval();
+        let interval = hz
+            .filter(|hz| *hz > 0.0)
+            .map(|hz| Duration::from_secs_f64(1.0 / hz));
+        self.refresh_interval.set(interval);
+        self.refresh_interval() != old
+    }
+
+    /// The time between refreshes of the display that the window is on.
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+            .get()
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL)
+    }
+
+    /// The timing of a frame that starts now, for backends that don't know when the display
+    /// refreshes.
+    pub fn frame_info(&self) -> FrameInfo {
+        estimated_frame_info(self.refresh_interval())
+    }
+
+    /// Record a frame that is about to be painted for `reason`, and return how many animation
+    /// frames were dropped since the previous one.
+    ///
+    /// Only back-to-back animation frames are compared, because the gap before the first frame
+    /// of an animation says nothing about the display.
+    pub fn missed_frames(&self, reason: PaintReason) -> u32 {
+        self.missed_frames_at(reason, Instant::now())
+    }
+
+    fn missed_frames_at(&self, reason: PaintReason, now: Instant) -> u32 {
+        let animating = reason == PaintReason::Animation;
+        let last = self.last_anim_frame.replace(animating.then_some(now));
+        match last {
+            Some(last) if animating => {
+                let interval = self
+                    .refresh_interval()
+                    .max(self.min_interval.get().unwrap_or_default());
+                let frames = (now - last).as_secs_f64() / interval.as_secs_f64();
+                (frames.round() as u32).saturating_sub(1)
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Tells the [`AppHandler`] when the application goes without input for a while.
+///
+/// Backends report each input event with [`input`], and call [`dispatch`] at the
+/// [`deadline`] and whenever [`input`] returns `true`.
+///
+/// [`input`]: IdleTracker::input
+/// [`dispatch`]: IdleTracker::dispatch
+/// [`deadline`]: IdleTracker::deadline
+#[allow(dead_code)]
+pub(crate) struct IdleTracker {
+    timeout: Cell<Option<Duration>>,
+    last_input: Cell<Instant>,
+    /// Whether the handler was told that the application went idle.
+    is_idle: Cell<bool>,
+    /// Whether input arrived since then, which the handler wasn't told about yet.
+    ended: Cell<bool>,
+}
+
+#[allow(dead_code)]
+impl IdleTracker {
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.timeout.set(timeout);
+    }
+
+    /// Record an input event. Returns `true` if this ends an idle period.
+    pub fn input(&self) -> bool {
+        self.last_input.set(Instant::now());
+        let ended = self.is_idle.replace(false);
+        if ended {
+            self.ended.set(true);
+        }
+        ended
+    }
+
+    /// When the application goes idle if there is no more input, unless it already is.
+    pub fn deadline(&self) -> Option<Instant> {
+        let timeout = self.timeout.get().filter(|_| !self.is_idle.get())?;
+        Some(self.last_input.get() + timeout)
+    }
+
+    /// Tell `handler` if the application went idle, or came back since the last call.
+    pub fn dispatch(&self, handler: &mut dyn AppHandler) {
+        self.dispatch_at(handler, Instant::now());
+    }
+
+    fn dispatch_at(&self, handler: &mut dyn AppHandler, now: Instant) {
+        if self.ended.take() {
+            handler.idle_ended();
+        }
+        if self.deadline().is_some_and(|deadline| deadline <= now) {
+            self.is_idle.set(true);
+            handler.idle(now - self.last_input.get());
+        }
+    }
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        IdleTracker {
+            timeout: Cell::new(None),
+            last_input: Cell::new(Instant::now()),
+            is_idle: Cell::new(false),
+            ended: Cell::new(false),
+        }
+    }
+}
+
+/// Approximates the inside of `shape`, scaled by `scale`, by a list of rectangles with
+/// integer coordinates.
+///
+/// This is for backends whose window shapes are made of rectangles. The shape is filled
+/// with the non-zero winding rule, and rows with the same extent are merged.
+#[allow(dead_code)]
+pub(crate) fn shape_to_rects(shape: &BezPath, scale: Scale) -> Vec<Rect> {
+    let shape = Affine::scale_non_uniform(scale.x(), scale.y()) * shape.clone();
+
+    let mut edges = Vec::new();
+    let mut start = Point::ZERO;
+    let mut last = Point::ZERO;
+    shape.flatten(0.1, |el| match el {
+        PathEl::MoveTo(p) => {
+            edges.push((last, start));
+            start = p;
+            last = p;
+        }
+        PathEl::LineTo(p) => {
+            edges.push((last, p));
+            last = p;
+        }
+        PathEl::ClosePath => {
+            edges.push((last, start));
+            last = start;
+        }
+        // `flatten` only produces lines.
+        _ => {}
+    });
+    edges.push((last, start));
+
+    let bbox = shape.bounding_box();
+    let mut rects: Vec<Rect> = Vec::new();
+    // The rects that reach the bottom of the previous row, and so can be extended.
+    let mut open: Vec<usize> = Vec::new();
+    for y in bbox.y0.floor() as i64..bbox.y1.ceil() as i64 {
+        let y = y as f64;
+        let sample = y + 0.5;
+        let mut crossings: Vec<(f64, i32)> = edges
+            .iter()
+            .filter(|(a, b)| (a.y <= sample) != (b.y <= sample))
+            .map(|(a, b)| {
+                let x = a.x + (sample - a.y) / (b.y - a.y) * (b.x - a.x);
+                (x, if b.y > a.y { 1 } else { -1 })
+            })
+            .collect();
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut next_open = Vec::new();
+        let mut winding = 0;
+        let mut span_start = 0.0;
+        for (x, direction) in crossings {
+            let was_inside = winding != 0;
+            winding += direction;
+            if !was_inside {
+                span_start = x.round();
+            } else if winding == 0 && x.round() > span_start {
+                let (x0, x1) = (span_start, x.round());
+                match open
+                    .iter()
+                    .find(|&&i| rects[i].x0 == x0 && rects[i].x1 == x1)
+                {
+                    Some(&i) => {
+                        rects[i].y1 = y + 1.0;
+                        next_open.push(i);
+                    }
+                    None => {
+                        rects.push(Rect::new(x0, y, x1, y + 1.0));
+                        next_open.push(rects.len() - 1);
+                    }
+                }
+            }
+        }
+        open = next_open;
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missed_animation_frames() {
+        let throttle = FrameThrottle::default();
+        throttle.set_refresh_rate(Some(100.0));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert_eq!(throttle.missed_frames_at(PaintReason::Animation, at(0)), 0);
+        assert_eq!(throttle.missed_frames_at(PaintReason::Animation, at(11)), 0);
+        assert_eq!(throttle.missed_frames_at(PaintReason::Animation, at(41)), 2);
+        // A damage frame ends the animation, so the next gap doesn't count.
+        assert_eq!(throttle.missed_frames_at(PaintReason::Damage, at(45)), 0);
+        assert_eq!(
+            throttle.missed_frames_at(PaintReason::Animation, at(200)),
+            0
+        );
+        throttle.set_max_frame_rate(Some(50.0));
+        assert_eq!(
+            throttle.missed_frames_at(PaintReason::Animation, at(220)),
+            0
+        );
+    }
+
+    #[derive(Default)]
+    struct IdleRecorder(Vec<Option<Duration>>);
+
+    impl AppHandler for IdleRecorder {
+        fn idle(&mut self, since_last_input: Duration) {
+            self.0.push(Some(since_last_input));
+        }
+        fn idle_ended(&mut self) {
+            self.0.push(None);
+        }
+    }
+
+    #[test]
+    fn idle_tracker() {
+        let tracker = IdleTracker::default();
+        let mut handler = IdleRecorder::default();
+        let secs = Duration::from_secs;
+        tracker.dispatch_at(&mut handler, Instant::now() + secs(100));
+        assert_eq!(tracker.deadline(), None);
+
+        tracker.set_timeout(Some(secs(10)));
+        assert!(!tracker.input());
+        let start = tracker.deadline().unwrap() - secs(10);
+        tracker.dispatch_at(&mut handler, start + secs(5));
+        tracker.dispatch_at(&mut handler, start + secs(12));
+        // Once idle, there's nothing to wait for until the next input.
+        assert_eq!(tracker.deadline(), None);
+        tracker.dispatch_at(&mut handler, start + secs(30));
+        assert!(tracker.input());
+        assert!(!tracker.input());
+        assert!(tracker.deadline().is_some());
+        tracker.dispatch_at(&mut handler, Instant::now());
+        assert_eq!(handler.0, [Some(secs(12)), None]);
+    }
+
+    fn polygon(points: &[(f64, f64)]) -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to(points[0]);
+        for &point in &points[1..] {
+            path.line_to(point);
+        }
+        path.close_path();
+        path
+    }
+
+    #[test]
+    fn concave_shape_to_rects() {
+        // A U, open at the top.
+        let u = polygon(&[
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 2.0),
+            (4.0, 2.0),
+            (4.0, 0.0),
+            (6.0, 0.0),
+            (6.0, 4.0),
+            (0.0, 4.0),
+        ]);
+        assert_eq!(
+            shape_to_rects(&u, Scale::new(1.0, 1.0)),
+            [
+                Rect::new(0.0, 0.0, 2.0, 2.0),
+                Rect::new(4.0, 0.0, 6.0, 2.0),
+                Rect::new(0.0, 2.0, 6.0, 4.0),
+            ]
+        );
+        assert_eq!(
+            shape_to_rects(&u, Scale::new(2.0, 1.0)),
+            [
+                Rect::new(0.0, 0.0, 4.0, 2.0),
+                Rect::new(8.0, 0.0, 12.0, 2.0),
+                Rect::new(0.0, 2.0, 12.0, 4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn self_touching_shape_to_rects() {
+        // Two squares that touch at a corner, drawn as one outline.
+        let squares = polygon(&[
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 2.0),
+            (4.0, 2.0),
+            (4.0, 4.0),
+            (2.0, 4.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+        ]);
+        assert_eq!(
+            shape_to_rects(&squares, Scale::new(1.0, 1.0)),
+            [Rect::new(0.0, 0.0, 2.0, 2.0), Rect::new(2.0, 2.0, 4.0, 4.0)]
+        );
+        // A square with a slit from its edge to a square hole, so that the outline runs along
+        // itself. The slit has no width, so only the hole is left out.
+        let ring = polygon(&[
+            (0.0, 0.0),
+            (6.0, 0.0),
+            (6.0, 6.0),
+            (3.0, 6.0),
+            (3.0, 4.0),
+            (4.0, 4.0),
+            (4.0, 2.0),
+            (2.0, 2.0),
+            (2.0, 4.0),
+            (3.0, 4.0),
+            (3.0, 6.0),
+            (0.0, 6.0),
+        ]);
+        assert_eq!(
+            shape_to_rects(&ring, Scale::new(1.0, 1.0)),
+            [
+                Rect::new(0.0, 0.0, 6.0, 2.0),
+                Rect::new(0.0, 2.0, 2.0, 4.0),
+                Rect::new(4.0, 2.0, 6.0, 4.0),
+                Rect::new(0.0, 4.0, 6.0, 6.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Access to the D-Bus session bus.
+//!
+//! Glazier talks to desktop services (such as the xdg-desktop-portal file chooser) over a
+//! single connection to the session bus. Applications can register their own objects on the
+//! same connection instead of opening another one and running a second async runtime to
+//! drive it.
+//!
+//! Incoming method calls are handled on the connection's executor thread, not on the thread
+//! running the event loop. Use an [`IdleHandle`] to hand work back to a window.
+//!
+//! [`Mpris`] publishes a media player on the bus, so that the application can be controlled
+//! with the desktop's media keys and widgets. [`ScreencastSession`] shares the screen through
+//! the desktop's portal, which is the only way to do it on Wayland.
+//!
+//! Device sensors are read from iio-sensor-proxy, on a separate connection to the system bus.
+//! On Wayland, the night light state of GNOME and KDE Plasma is read from the session bus.
+//!
+//! This module is only available on Linux and the BSDs, with the `dbus` feature.
+//!
+//! [`IdleHandle`]: crate::IdleHandle
+
+use std::sync::Mutex;
+
+use futures::executor::block_on;
+
+pub use zbus;
+use zbus::Connection;
+
+mod mpris;
+// X11 has gamma ramps to look at instead.
+#[cfg(all(not(feature = "headless"), feature = "wayland"))]
+mod night_light;
+mod screencast;
+// Only the x11 and Wayland backends have an event loop to deliver sensor readings from.
+#[cfg(all(not(feature = "headless"), any(feature = "x11", feature = "wayland")))]
+mod sensors;
+
+pub(crate) use mpris::MediaCommandSink;
+pub use mpris::Mpris;
+#[cfg(all(not(feature = "headless"), feature = "wayland"))]
+pub(crate) use night_light::color_shift;
+pub use screencast::{ScreencastCursor, ScreencastOptions, ScreencastSession, ScreencastStream};
+#[cfg(all(not(feature = "headless"), any(feature = "x11", feature = "wayland")))]
+pub(crate) use sensors::Sensors;
+
+static SESSION: Mutex<Option<Connection>> = Mutex::new(None);
+
+/// Returns the shared connection to the session bus, connecting if necessary.
+pub async fn session() -> zbus::Result<Connection> {
+    if let Some(conn) = SESSION.lock().unwrap().as_ref() {
+        return Ok(conn.clone());
+    }
+    let conn = Connection::session().await?;
+    // If someone else connected in the meantime, use theirs and drop ours.
+    Ok(SESSION.lock().unwrap().get_or_insert(conn).clone())
+}
+
+/// Returns the shared connection to the session bus, blocking until it is connected.
+///
+/// This must not be called from async code; use [`session`] there.
+pub fn session_blocking() -> zbus::Result<Connection> {
+    block_on(session())
+}
+
+/// Serves `iface` at the object path `path` on the session bus.
+///
+/// Returns `false` if an interface of the same type was already served at `path`.
+pub fn serve_at<I: zbus::Interface>(path: &str, iface: I) -> zbus::Result<bool> {
+    let conn = session_blocking()?;
+    let server = conn.object_server();
+    block_on(server.at(path, iface))
+}
+
+/// Requests the well-known name `name` on the session bus.
+pub fn request_name(name: &str) -> zbus::Result<()> {
+    let conn = session_blocking()?;
+    block_on(conn.request_name(name))
+}
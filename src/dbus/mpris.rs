@@ -0,0 +1,328 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The MPRIS media player interface.
+//!
+//! See <https://specifications.freedesktop.org/mpris-spec/latest/>.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::executor::block_on;
+use tracing::warn;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{dbus_interface, Connection, SignalContext};
+
+use crate::{Application, MediaCommand, MediaMetadata, PlaybackStatus};
+
+/// Where backends deliver media commands to, from the D-Bus thread.
+pub(crate) type MediaCommandSink = Arc<dyn Fn(MediaCommand) + Send + Sync>;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+/// We only ever have one track, so it always has the same id.
+const TRACK_ID: &str = "/org/glazier/CurrentTrack";
+
+#[derive(Default)]
+struct State {
+    status: PlaybackStatus,
+    metadata: MediaMetadata,
+    position: Duration,
+}
+
+/// A media player published on the session bus, so that it can be controlled by the
+/// desktop's media keys and widgets.
+///
+/// Commands from the desktop are delivered to [`AppHandler::media_command`]; the application
+/// reports what it is playing with the setters here. The player is unpublished when the
+/// connection to the bus closes, which is when the application exits.
+///
+/// [`AppHandler::media_command`]: crate::AppHandler::media_command
+pub struct Mpris {
+    conn: Connection,
+    state: Arc<Mutex<State>>,
+}
+
+impl Mpris {
+    /// Publishes a media player called `identity`.
+    ///
+    /// `name` becomes part of the player's bus name (`org.mpris.MediaPlayer2.<name>`), so it
+    /// should be unique to the application, for instance the name of its binary.
+    ///
+    /// Fails with [`zbus::Error::Unsupported`] on the GTK backend, which can't deliver the
+    /// commands to the [`AppHandler`].
+    ///
+    /// [`AppHandler`]: crate::AppHandler
+    pub fn new(app: &Application, name: &str, identity: &str) -> zbus::Result<Mpris> {
+        // Without a way to deliver commands, we'd advertise controls that do nothing.
+        let sink = app
+            .backend_app
+            .media_command_sink()
+            .ok_or(zbus::Error::Unsupported)?;
+        let conn = super::session_blocking()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let root = Root {
+            identity: identity.to_owned(),
+        };
+        let player = Player {
+            state: state.clone(),
+            sink,
+        };
+        let server = conn.object_server();
+        block_on(server.at(PATH, root))?;
+        block_on(server.at(PATH, player))?;
+        drop(server);
+        block_on(conn.request_name(format!("org.mpris.MediaPlayer2.{}", name).as_str()))?;
+        Ok(Mpris { conn, state })
+    }
+
+    /// Sets whether the application is playing.
+    pub fn set_playback_status(&self, status: PlaybackStatus) {
+        self.state.lock().unwrap().status = status;
+        let result = block_on(async {
+            let player = self.player().await?;
+            let iface = player.get().await;
+            iface.playback_status_changed(player.signal_context()).await
+        });
+        if let Err(e) = result {
+            warn!("failed to update MPRIS playback status: {}", e);
+        }
+    }
+
+    /// Sets the description of the current track.
+    pub fn set_metadata(&self, metadata: MediaMetadata) {
+        self.state.lock().unwrap().metadata = metadata;
+        let result = block_on(async {
+            let player = self.player().await?;
+            let iface = player.get().await;
+            iface.metadata_changed(player.signal_context()).await
+        });
+        if let Err(e) = result {
+            warn!("failed to update MPRIS metadata: {}", e);
+        }
+    }
+
+    /// Sets the playback position in the current track.
+    ///
+    /// Clients don't expect to be told about the position changing during normal playback,
+    /// so this doesn't notify them. Use [`seeked`] when the position jumps.
+    ///
+    /// [`seeked`]: Mpris::seeked
+    pub fn set_position(&self, position: Duration) {
+        self.state.lock().unwrap().position = position;
+    }
+
+    /// Sets the playback position after a seek, and tells clients about it.
+    pub fn seeked(&self, position: Duration) {
+        self.set_position(position);
+        let result = block_on(async {
+            let player = self.player().await?;
+            Player::seeked(player.signal_context(), micros(position)).await
+        });
+        if let Err(e) = result {
+            warn!("failed to send MPRIS seek: {}", e);
+        }
+    }
+
+    async fn player(&self) -> zbus::Result<zbus::InterfaceRef<Player>> {
+        self.conn.object_server().interface(PATH).await
+    }
+}
+
+fn micros(d: Duration) -> i64 {
+    d.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+/// The `org.mpris.MediaPlayer2` interface, which describes the application.
+struct Root {
+    identity: String,
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[dbus_interface(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn identity(&self) -> String {
+        self.identity.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[dbus_interface(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface, which controls playback.
+struct Player {
+    state: Arc<Mutex<State>>,
+    sink: MediaCommandSink,
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        (self.sink)(MediaCommand::Next);
+    }
+
+    fn previous(&self) {
+        (self.sink)(MediaCommand::Previous);
+    }
+
+    fn pause(&self) {
+        (self.sink)(MediaCommand::Pause);
+    }
+
+    fn play_pause(&self) {
+        (self.sink)(MediaCommand::PlayPause);
+    }
+
+    fn stop(&self) {
+        (self.sink)(MediaCommand::Stop);
+    }
+
+    fn play(&self) {
+        (self.sink)(MediaCommand::Play);
+    }
+
+    fn seek(&self, offset: i64) {
+        (self.sink)(MediaCommand::Seek(offset as f64 / 1_000_000.0));
+    }
+
+    fn set_position(&self, track_id: OwnedObjectPath, position: i64) {
+        // The spec says to ignore requests for other tracks, or out of range positions.
+        if track_id.as_str() == TRACK_ID && position >= 0 {
+            let position = Duration::from_micros(position as u64);
+            (self.sink)(MediaCommand::SetPosition(position));
+        }
+    }
+
+    fn open_uri(&self, _uri: &str) {}
+
+    #[dbus_interface(signal)]
+    async fn seeked(ctxt: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    #[dbus_interface(property)]
+    fn playback_status(&self) -> String {
+        match self.state.lock().unwrap().status {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+        .to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let state = self.state.lock().unwrap();
+        let metadata = &state.metadata;
+        let mut map = HashMap::new();
+        let mut insert = |key: &str, value: Value| {
+            map.insert(key.to_owned(), value.into());
+        };
+        insert(
+            "mpris:trackid",
+            ObjectPath::from_static_str_unchecked(TRACK_ID).into(),
+        );
+        if let Some(length) = metadata.length {
+            insert("mpris:length", micros(length).into());
+        }
+        if let Some(art_url) = &metadata.art_url {
+            insert("mpris:artUrl", art_url.clone().into());
+        }
+        if let Some(title) = &metadata.title {
+            insert("xesam:title", title.clone().into());
+        }
+        if !metadata.artists.is_empty() {
+            insert("xesam:artist", metadata.artists.clone().into());
+        }
+        if let Some(album) = &metadata.album {
+            insert("xesam:album", album.clone().into());
+        }
+        map
+    }
+
+    #[dbus_interface(property)]
+    fn position(&self) -> i64 {
+        micros(self.state.lock().unwrap().position)
+    }
+
+    #[dbus_interface(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[dbus_interface(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[dbus_interface(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[dbus_interface(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
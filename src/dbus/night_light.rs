@@ -0,0 +1,106 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The night light state of GNOME and KDE Plasma.
+//!
+//! On Wayland the compositor shifts the colors itself, so unlike on X11 there are no gamma
+//! ramps to look at. Both desktops publish the state on the session bus instead.
+
+use futures::executor::block_on;
+use zbus::dbus_proxy;
+
+use crate::screen::ColorShift;
+
+#[dbus_proxy(
+    interface = "org.gnome.SettingsDaemon.Color",
+    default_service = "org.gnome.SettingsDaemon.Color",
+    default_path = "/org/gnome/SettingsDaemon/Color"
+)]
+trait GnomeColor {
+    #[dbus_proxy(property)]
+    fn night_light_active(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn temperature(&self) -> zbus::Result<u32>;
+}
+
+#[dbus_proxy(
+    interface = "org.kde.KWin.NightLight",
+    default_service = "org.kde.KWin",
+    default_path = "/org/kde/KWin/NightLight"
+)]
+trait KdeNightLight {
+    #[dbus_proxy(property, name = "running")]
+    fn running(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property, name = "currentTemperature")]
+    fn current_temperature(&self) -> zbus::Result<u32>;
+}
+
+/// Returns the night light state of the running desktop, or `None` if it doesn't publish one.
+pub(crate) fn color_shift() -> Option<ColorShift> {
+    block_on(async {
+        let conn = super::session().await.ok()?;
+        if let Ok(proxy) = GnomeColorProxy::new(&conn).await {
+            if let Ok(active) = proxy.night_light_active().await {
+                let temperature = proxy.temperature().await.ok();
+                return Some(shift(active, temperature));
+            }
+        }
+        if let Ok(proxy) = KdeNightLightProxy::new(&conn).await {
+            if let Ok(active) = proxy.running().await {
+                let temperature = proxy.current_temperature().await.ok();
+                return Some(shift(active, temperature));
+            }
+        }
+        None
+    })
+}
+
+fn shift(active: bool, temperature: Option<u32>) -> ColorShift {
+    // The last temperature is still reported while night light is off.
+    if !active {
+        return ColorShift::new(false, Some(0.0));
+    }
+    ColorShift::new(true, temperature.map(|t| 1.0 - blue_level(t as f64)))
+}
+
+/// The level of the blue channel in the white point of a color temperature, in kelvin.
+///
+/// This is Tanner Helland's fit of the blackbody colors, which is close to the tables that
+/// the desktops use.
+fn blue_level(temperature: f64) -> f64 {
+    if temperature >= 6600.0 {
+        1.0
+    } else if temperature <= 1900.0 {
+        0.0
+    } else {
+        let blue = 138.517_731_223_1 * (temperature / 100.0 - 10.0).ln() - 305.044_792_730_7;
+        (blue / 255.0).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_strength() {
+        assert_eq!(shift(false, Some(3000)), ColorShift::new(false, Some(0.0)));
+        assert_eq!(shift(true, None), ColorShift::new(true, None));
+        assert_eq!(shift(true, Some(6600)).strength(), Some(0.0));
+        assert_eq!(shift(true, Some(1500)).strength(), Some(1.0));
+        let warm = shift(true, Some(3000)).strength().unwrap();
+        let warmer = shift(true, Some(2500)).strength().unwrap();
+        assert!(0.0 < warm && warm < warmer && warmer < 1.0);
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Screen sharing through the xdg-desktop-portal ScreenCast interface.
+//!
+//! See <https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.ScreenCast.html>.
+
+use std::os::unix::io::{AsFd, BorrowedFd, FromRawFd, OwnedFd};
+
+use ashpd::desktop::screencast::{CursorMode, ScreenCastProxy, SourceType};
+use ashpd::desktop::SessionProxy;
+use ashpd::WindowIdentifier;
+use futures::executor::block_on;
+use tracing::warn;
+
+/// How the pointer appears in a screencast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ScreencastCursor {
+    /// The pointer isn't shared.
+    Hidden,
+    /// The pointer is drawn into the frames.
+    #[default]
+    Embedded,
+    /// The pointer's position and image are sent as metadata next to the frames.
+    Metadata,
+}
+
+/// What the user is allowed to share.
+///
+/// See [`ScreencastSession::start`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScreencastOptions {
+    /// Whether the user can share a whole monitor.
+    pub monitors: bool,
+    /// Whether the user can share a single window.
+    pub windows: bool,
+    /// Whether the user can share more than one monitor or window at once.
+    pub multiple: bool,
+    /// How the pointer appears.
+    pub cursor: ScreencastCursor,
+}
+
+impl Default for ScreencastOptions {
+    fn default() -> Self {
+        ScreencastOptions {
+            monitors: true,
+            windows: true,
+            multiple: false,
+            cursor: ScreencastCursor::default(),
+        }
+    }
+}
+
+/// One of the monitors or windows that the user chose to share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScreencastStream {
+    /// The PipeWire node that the frames come from.
+    pub node_id: u32,
+    /// The position of the shared monitor in the compositor's coordinates, if it is known.
+    pub position: Option<(i32, i32)>,
+    /// The size of the stream, if it is known.
+    pub size: Option<(i32, i32)>,
+}
+
+/// A running screencast, chosen by the user in the desktop's screen sharing picker.
+///
+/// On Wayland this is the only way to capture the screen, because applications can't see
+/// each other's windows. The frames are sent as PipeWire streams: connect to PipeWire with
+/// [`pipewire_fd`], and then to the nodes in [`streams`], for instance with the `pipewire`
+/// crate.
+///
+/// The screencast stops when the session is dropped.
+///
+/// [`pipewire_fd`]: ScreencastSession::pipewire_fd
+/// [`streams`]: ScreencastSession::streams
+pub struct ScreencastSession {
+    session: SessionProxy<'static>,
+    streams: Vec<ScreencastStream>,
+    fd: OwnedFd,
+}
+
+impl ScreencastSession {
+    /// Asks the user what to share, and starts sharing it.
+    ///
+    /// This waits for the user to make their choice, which can take a while, so don't block
+    /// the event loop on it. Await it on another thread and use an [`IdleHandle`] to hand the
+    /// session to a window. It fails if the user cancels.
+    ///
+    /// [`IdleHandle`]: crate::IdleHandle
+    pub async fn start(options: ScreencastOptions) -> ashpd::Result<ScreencastSession> {
+        let conn = super::session().await?;
+        let proxy = ScreenCastProxy::new(&conn).await?;
+        let session = proxy.create_session().await?;
+        let result = async {
+            let types = match (options.monitors, options.windows) {
+                (true, true) => SourceType::Monitor | SourceType::Window,
+                (false, true) => SourceType::Window.into(),
+                _ => SourceType::Monitor.into(),
+            };
+            let cursor = match options.cursor {
+                ScreencastCursor::Hidden => CursorMode::Hidden,
+                ScreencastCursor::Embedded => CursorMode::Embedded,
+                ScreencastCursor::Metadata => CursorMode::Metadata,
+            };
+            proxy
+                .select_sources(&session, cursor.into(), types, options.multiple)
+                .await?;
+            let streams = proxy.start(&session, &WindowIdentifier::default()).await?;
+            let fd = proxy.open_pipe_wire_remote(&session).await?;
+            Ok((streams, fd))
+        }
+        .await;
+        let (streams, fd) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if let Err(e) = session.close().await {
+                    warn!("failed to close screencast session: {}", e);
+                }
+                return Err(e);
+            }
+        };
+        let streams = streams
+            .iter()
+            .map(|stream| ScreencastStream {
+                node_id: stream.pipe_wire_node_id(),
+                position: stream.position(),
+                size: stream.size(),
+            })
+            .collect();
+        Ok(ScreencastSession {
+            session,
+            streams,
+            // The portal gives us our own copy of the file descriptor.
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Returns the monitors and windows being shared.
+    pub fn streams(&self) -> &[ScreencastStream] {
+        &self.streams
+    }
+
+    /// Returns the connection to PipeWire that the streams can be read through.
+    ///
+    /// Use [`BorrowedFd::try_clone_to_owned`] to hand it to a library that takes ownership.
+    pub fn pipewire_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for ScreencastSession {
+    fn drop(&mut self) {
+        if let Err(e) = block_on(self.session.close()) {
+            warn!("failed to close screencast session: {}", e);
+        }
+    }
+}
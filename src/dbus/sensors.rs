@@ -0,0 +1,200 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Device sensors, read through iio-sensor-proxy.
+//!
+//! See <https://gitlab.freedesktop.org/hadess/iio-sensor-proxy/>.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures::executor::block_on;
+use futures::future::{AbortHandle, Abortable};
+use futures::stream::{self, Stream, StreamExt};
+use tracing::warn;
+use zbus::{dbus_proxy, Connection};
+
+use crate::sensors::{DeviceOrientation, SensorEvent, SensorKind};
+
+/// Where backends deliver sensor readings to, from the threads watching the sensors.
+pub(crate) type SensorEventSink = Arc<dyn Fn(SensorEvent) + Send + Sync>;
+
+static SYSTEM: Mutex<Option<Connection>> = Mutex::new(None);
+
+#[dbus_proxy(
+    interface = "net.hadess.SensorProxy",
+    default_service = "net.hadess.SensorProxy",
+    default_path = "/net/hadess/SensorProxy"
+)]
+trait SensorProxy {
+    fn claim_accelerometer(&self) -> zbus::Result<()>;
+    fn release_accelerometer(&self) -> zbus::Result<()>;
+    fn claim_light(&self) -> zbus::Result<()>;
+    fn release_light(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn has_accelerometer(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn accelerometer_orientation(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn has_ambient_light(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn light_level_unit(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn light_level(&self) -> zbus::Result<f64>;
+}
+
+/// A sensor that we claimed from iio-sensor-proxy, and the task that watches it.
+struct Claim {
+    proxy: SensorProxyProxy<'static>,
+    watcher: AbortHandle,
+}
+
+/// The sensors that the application has enabled.
+///
+/// iio-sensor-proxy only reads the sensors that some client has claimed, so we hold claims
+/// for just the enabled ones. The proxy drops our claims itself when the application exits.
+pub(crate) struct Sensors {
+    sink: SensorEventSink,
+    claims: Mutex<HashMap<SensorKind, Claim>>,
+}
+
+impl Sensors {
+    pub(crate) fn new(sink: SensorEventSink) -> Sensors {
+        Sensors {
+            sink,
+            claims: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claims the sensor and starts delivering its readings.
+    ///
+    /// Returns `false` if iio-sensor-proxy isn't running, or can't provide the sensor.
+    pub(crate) fn enable(&self, kind: SensorKind) -> bool {
+        let mut claims = self.claims.lock().unwrap();
+        if claims.contains_key(&kind) {
+            return true;
+        }
+        let result = block_on(async {
+            let proxy = SensorProxyProxy::new(&system().await?).await?;
+            Ok::<_, zbus::Error>(claim(&proxy, kind).await?.then_some(proxy))
+        });
+        let proxy = match result {
+            Ok(Some(proxy)) => proxy,
+            Ok(None) => return false,
+            Err(e) => {
+                warn!("failed to claim {:?} from iio-sensor-proxy: {}", kind, e);
+                return false;
+            }
+        };
+        let (watcher, registration) = AbortHandle::new_pair();
+        let watch = Abortable::new(watch(proxy.clone(), kind, self.sink.clone()), registration);
+        std::thread::spawn(move || block_on(watch));
+        claims.insert(kind, Claim { proxy, watcher });
+        true
+    }
+
+    /// Stops delivering the sensor's readings, and releases it.
+    pub(crate) fn disable(&self, kind: SensorKind) {
+        if let Some(claim) = self.claims.lock().unwrap().remove(&kind) {
+            claim.watcher.abort();
+            if let Err(e) = block_on(release(&claim.proxy, kind)) {
+                warn!("failed to release {:?} to iio-sensor-proxy: {}", kind, e);
+            }
+        }
+    }
+}
+
+/// Returns the shared connection to the system bus, connecting if necessary.
+async fn system() -> zbus::Result<Connection> {
+    if let Some(conn) = SYSTEM.lock().unwrap().as_ref() {
+        return Ok(conn.clone());
+    }
+    let conn = Connection::system().await?;
+    Ok(SYSTEM.lock().unwrap().get_or_insert(conn).clone())
+}
+
+/// Claims the sensor that provides `kind`, returning `false` if there isn't one.
+async fn claim(proxy: &SensorProxyProxy<'_>, kind: SensorKind) -> zbus::Result<bool> {
+    match kind {
+        SensorKind::Orientation => {
+            if !proxy.has_accelerometer().await? {
+                return Ok(false);
+            }
+            proxy.claim_accelerometer().await?;
+            Ok(true)
+        }
+        SensorKind::AmbientLight => {
+            // Some light sensors report a percentage of their range, which isn't in lux.
+            if !proxy.has_ambient_light().await? || proxy.light_level_unit().await? != "lux" {
+                return Ok(false);
+            }
+            proxy.claim_light().await?;
+            Ok(true)
+        }
+    }
+}
+
+async fn release(proxy: &SensorProxyProxy<'_>, kind: SensorKind) -> zbus::Result<()> {
+    match kind {
+        SensorKind::Orientation => proxy.release_accelerometer().await,
+        SensorKind::AmbientLight => proxy.release_light().await,
+    }
+}
+
+/// Sends the current reading of a claimed sensor to `sink`, followed by its changes.
+async fn watch(proxy: SensorProxyProxy<'static>, kind: SensorKind, sink: SensorEventSink) {
+    match kind {
+        SensorKind::Orientation => {
+            let changes = proxy.receive_accelerometer_orientation_changed().await;
+            let readings = stream::once(proxy.accelerometer_orientation())
+                .chain(changes.then(|change| async move { change.get().await }))
+                .map(|reading| {
+                    let orientation = reading.ok().as_deref().and_then(device_orientation);
+                    orientation.map(SensorEvent::Orientation)
+                });
+            deliver(readings, &sink).await;
+        }
+        SensorKind::AmbientLight => {
+            let changes = proxy.receive_light_level_changed().await;
+            let readings = stream::once(proxy.light_level())
+                .chain(changes.then(|change| async move { change.get().await }))
+                .map(|reading| reading.ok().map(SensorEvent::AmbientLight));
+            deliver(readings, &sink).await;
+        }
+    }
+}
+
+async fn deliver(readings: impl Stream<Item = Option<SensorEvent>>, sink: &SensorEventSink) {
+    futures::pin_mut!(readings);
+    let mut last = None;
+    while let Some(reading) = readings.next().await {
+        // The first change can repeat the current reading.
+        if let Some(event) = reading.filter(|event| last != Some(*event)) {
+            sink(event);
+            last = Some(event);
+        }
+    }
+}
+
+fn device_orientation(name: &str) -> Option<DeviceOrientation> {
+    // The proxy says "undefined" when the device lies flat, or the orientation is unknown.
+    match name {
+        "normal" => Some(DeviceOrientation::Normal),
+        "bottom-up" => Some(DeviceOrientation::BottomUp),
+        "left-up" => Some(DeviceOrientation::LeftUp),
+        "right-up" => Some(DeviceOrientation::RightUp),
+        _ => None,
+    }
+}
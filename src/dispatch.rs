@@ -0,0 +1,362 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The handler that backends give each window's events to.
+//!
+//! Backends wrap each window's handler with [`wrap`], which gives the events to the features
+//! that the backends share before passing them on:
+//!
+//! - [`Autoscroll`] and [`ShortcutCapture`] take over pointer and key input while they're
+//!   active.
+//! - The modifiers of each key event are compared with the last ones the handler saw, and
+//!   [`WinHandler::modifiers_changed`] is called first if they differ. Backends that hear about
+//!   modifier changes separately from key events call `modifiers_changed` themselves, and the
+//!   ones that don't change anything are dropped.
+
+use std::any::Any;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::autoscroll::Autoscroll;
+use crate::keyboard::{CompositionEvent, KeyEvent};
+use crate::kurbo::{Insets, Point, Size, Vec2};
+use crate::mouse::{ForceClick, MouseEvent};
+use crate::shortcut_capture::ShortcutCapture;
+use crate::text::InputHandler;
+use crate::window::{
+    DecorationMode, FileDialogToken, FocusDirection, FrameInfo, IdleToken, PaintReason, TimerToken,
+    WinHandler, WindowHandle,
+};
+use crate::{
+    DragData, DropEffect, FileInfo, HighContrastColors, KeyboardLayout, LocaleInfo, Modifiers,
+    MonitorId, Region, Scale, TextFieldToken,
+};
+
+/// Wraps a window's handler, so that it gets its events through the window's shared features.
+pub(crate) fn wrap(
+    handler: Box<dyn WinHandler>,
+    autoscroll: &Rc<Autoscroll>,
+    shortcut_capture: &Rc<ShortcutCapture>,
+) -> Box<dyn WinHandler> {
+    Box::new(Dispatcher {
+        inner: handler,
+        autoscroll: autoscroll.clone(),
+        shortcut_capture: shortcut_capture.clone(),
+        mods: Modifiers::empty(),
+    })
+}
+
+struct Dispatcher {
+    inner: Box<dyn WinHandler>,
+    autoscroll: Rc<Autoscroll>,
+    shortcut_capture: Rc<ShortcutCapture>,
+    /// The modifiers that the handler was last told about.
+    mods: Modifiers,
+}
+
+impl Dispatcher {
+    fn update_mods(&mut self, mods: Modifiers) {
+        if mods != self.mods {
+            self.mods = mods;
+            self.inner.modifiers_changed(mods);
+        }
+    }
+}
+
+impl WinHandler for Dispatcher {
+    fn connect(&mut self, handle: &WindowHandle) {
+        self.autoscroll.connect(handle);
+        self.inner.connect(handle)
+    }
+
+    fn size(&mut self, size: Size) {
+        self.inner.size(size)
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.inner.safe_area_changed(insets)
+    }
+
+    fn scale(&mut self, scale: Scale) {
+        self.inner.scale(scale)
+    }
+
+    fn text_scale_factor_changed(&mut self, factor: f64) {
+        self.inner.text_scale_factor_changed(factor)
+    }
+
+    fn high_contrast_changed(&mut self, colors: Option<HighContrastColors>) {
+        self.inner.high_contrast_changed(colors)
+    }
+
+    fn decoration_mode_changed(&mut self, mode: DecorationMode) {
+        self.inner.decoration_mode_changed(mode)
+    }
+
+    fn prepare_frame(&mut self, info: FrameInfo) {
+        self.inner.prepare_frame(info)
+    }
+
+    fn prepare_paint(&mut self) {
+        self.inner.prepare_paint()
+    }
+
+    fn paint(&mut self, invalid: &Region, reason: PaintReason) {
+        self.inner.paint(invalid, reason)
+    }
+
+    fn frame_missed(&mut self, count: u32) {
+        self.inner.frame_missed(count)
+    }
+
+    fn refresh_interval_changed(&mut self, interval: Duration) {
+        self.inner.refresh_interval_changed(interval)
+    }
+
+    fn outputs_changed(&mut self, outputs: &[MonitorId]) {
+        self.inner.outputs_changed(outputs)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_tree(&mut self) -> accesskit::TreeUpdate {
+        self.inner.accesskit_tree()
+    }
+
+    fn rebuild_resources(&mut self) {
+        self.inner.rebuild_resources()
+    }
+
+    fn command(&mut self, id: u32) {
+        self.inner.command(id)
+    }
+
+    fn save_as(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.inner.save_as(token, file)
+    }
+
+    fn open_file(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.inner.open_file(token, file)
+    }
+
+    fn open_files(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.inner.open_files(token, files)
+    }
+
+    fn key_down(&mut self, event: KeyEvent) -> bool {
+        self.update_mods(event.mods);
+        if self.shortcut_capture.key_down(&event) || self.autoscroll.key_down(&event) {
+            return true;
+        }
+        self.inner.key_down(event)
+    }
+
+    fn focus_navigation(&mut self, direction: FocusDirection) -> bool {
+        self.inner.focus_navigation(direction)
+    }
+
+    fn key_up(&mut self, event: KeyEvent) {
+        self.update_mods(event.mods);
+        if self.shortcut_capture.key_up(&event) {
+            return;
+        }
+        self.inner.key_up(event)
+    }
+
+    fn modifiers_changed(&mut self, mods: Modifiers) {
+        self.update_mods(mods)
+    }
+
+    fn input_language_changed(&mut self, layout: KeyboardLayout) {
+        self.inner.input_language_changed(layout)
+    }
+
+    fn locale_changed(&mut self, info: LocaleInfo) {
+        self.inner.locale_changed(info)
+    }
+
+    fn composition(&mut self, event: CompositionEvent) {
+        self.inner.composition(event)
+    }
+
+    fn acquire_input_lock(
+        &mut self,
+        token: TextFieldToken,
+        mutable: bool,
+    ) -> Box<dyn InputHandler> {
+        self.inner.acquire_input_lock(token, mutable)
+    }
+
+    fn release_input_lock(&mut self, token: TextFieldToken) {
+        self.inner.release_input_lock(token)
+    }
+
+    fn wheel(&mut self, event: &MouseEvent) {
+        self.autoscroll.wheel();
+        self.inner.wheel(event)
+    }
+
+    fn zoom(&mut self, delta: f64) {
+        self.inner.zoom(delta)
+    }
+
+    fn force_click(&mut self, event: &ForceClick) {
+        self.inner.force_click(event)
+    }
+
+    fn mouse_move(&mut self, event: &MouseEvent) {
+        if self.autoscroll.mouse_move(event) {
+            return;
+        }
+        self.inner.mouse_move(event)
+    }
+
+    fn mouse_down(&mut self, event: &MouseEvent) {
+        if self.autoscroll.mouse_down(event) {
+            return;
+        }
+        self.inner.mouse_down(event)
+    }
+
+    fn mouse_up(&mut self, event: &MouseEvent) {
+        if self.autoscroll.mouse_up(event) {
+            return;
+        }
+        self.inner.mouse_up(event)
+    }
+
+    fn mouse_leave(&mut self) {
+        self.inner.mouse_leave()
+    }
+
+    fn raw_pointer_motion(&mut self, delta: Vec2) {
+        self.inner.raw_pointer_motion(delta)
+    }
+
+    fn pointer_lock_lost(&mut self) {
+        self.inner.pointer_lock_lost()
+    }
+
+    fn drag_enter(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_enter(pos, data)
+    }
+
+    fn drag_over(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_over(pos, data)
+    }
+
+    fn drag_leave(&mut self) {
+        self.inner.drag_leave()
+    }
+
+    fn drag_drop(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_drop(pos, data)
+    }
+
+    fn timer(&mut self, token: TimerToken) {
+        if self.autoscroll.timer(token, &mut *self.inner) {
+            return;
+        }
+        self.inner.timer(token)
+    }
+
+    fn got_focus(&mut self) {
+        self.inner.got_focus()
+    }
+
+    fn lost_focus(&mut self) {
+        self.update_mods(Modifiers::empty());
+        self.shortcut_capture.lost_focus();
+        self.autoscroll.lost_focus();
+        self.inner.lost_focus()
+    }
+
+    fn request_close(&mut self) {
+        self.inner.request_close()
+    }
+
+    fn destroy(&mut self) {
+        self.shortcut_capture.destroy();
+        self.autoscroll.destroy();
+        self.inner.destroy()
+    }
+
+    fn idle(&mut self, token: IdleToken) {
+        self.inner.idle(token)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_action(&mut self, request: accesskit::ActionRequest) {
+        self.inner.accesskit_action(request)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self.inner.as_any()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::KeyState;
+    use crate::KbKey;
+
+    #[derive(Default)]
+    struct Recorder(Vec<Modifiers>);
+
+    impl WinHandler for Recorder {
+        fn connect(&mut self, _: &WindowHandle) {}
+
+        fn prepare_paint(&mut self) {}
+
+        fn paint(&mut self, _: &Region, _: PaintReason) {}
+
+        fn modifiers_changed(&mut self, mods: Modifiers) {
+            self.0.push(mods);
+        }
+
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    fn shift(state: KeyState, mods: Modifiers) -> KeyEvent {
+        KeyEvent {
+            state,
+            key: KbKey::Shift,
+            mods,
+            ..KeyEvent::default()
+        }
+    }
+
+    #[test]
+    fn only_changes_are_reported() {
+        let mut handler = wrap(Box::<Recorder>::default(), &Rc::default(), &Rc::default());
+        handler.key_down(shift(KeyState::Down, Modifiers::SHIFT));
+        handler.key_down(shift(KeyState::Down, Modifiers::SHIFT));
+        handler.modifiers_changed(Modifiers::SHIFT);
+        handler.key_up(shift(KeyState::Up, Modifiers::empty()));
+        handler.modifiers_changed(Modifiers::CONTROL);
+        handler.lost_focus();
+        let recorder = handler.as_any().downcast_mut::<Recorder>().unwrap();
+        assert_eq!(
+            recorder.0,
+            [
+                Modifiers::SHIFT,
+                Modifiers::empty(),
+                Modifiers::CONTROL,
+                Modifiers::empty()
+            ]
+        );
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drag and drop.
+
+use std::ops::Range;
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::kurbo::{Point, Rect};
+use crate::text::{Affinity, InputHandler, Selection};
+use crate::window::WindowHandle;
+
+/// The data carried by a drag, between windows or applications.
+///
+/// When a drag comes into a window, the data is read as soon as it enters, so the handler
+/// can decide whether to accept it. Only the custom formats that the window asked for with
+/// [`WindowHandle::set_drop_formats`] are read.
+///
+/// On Wayland, the data of a drag from another application is only read when it's dropped.
+/// Until then, it only says which formats the drag has: `text` is empty, `uris` has a
+/// single empty URI, and the custom formats have no data.
+///
+/// [`WindowHandle::set_drop_formats`]: crate::WindowHandle::set_drop_formats
+#[derive(Debug, Clone, Default)]
+pub struct DragData {
+    /// Plain text.
+    pub text: Option<String>,
+    /// Files and other resources, as URIs. Files have `file://` URIs.
+    pub uris: Vec<String>,
+    /// Data in application defined formats.
+    pub formats: Vec<ClipboardFormat>,
+}
+
+impl DragData {
+    /// Returns `true` if the drag doesn't carry anything.
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none() && self.uris.is_empty() && self.formats.is_empty()
+    }
+
+    /// Returns the data in a custom format, if the drag carries it.
+    pub fn format(&self, format: FormatId) -> Option<&[u8]> {
+        self.formats
+            .iter()
+            .find(|f| f.identifier == format)
+            .map(|f| f.data.as_slice())
+    }
+}
+
+/// What a drop target does with the data of a drag.
+///
+/// The platform shows this to the user, usually with the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DropEffect {
+    /// The data can't be dropped here.
+    #[default]
+    None,
+    /// The data is copied.
+    Copy,
+    /// The data is moved, so the source removes its copy.
+    Move,
+    /// A link to the data is created.
+    Link,
+}
+
+/// An image that follows the pointer during a drag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DragImage {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels, row by row from the top, as premultiplied RGBA with 8 bits per channel.
+    pub pixels: Vec<u8>,
+    /// The pixel of the image that is under the pointer, with `(0, 0)` at the top left.
+    pub hotspot: (u32, u32),
+}
+
+/// Drag and drop of text for a text field, for moving and copying the selection.
+///
+/// Keep one of these for each text field, and call it from the drag callbacks of the
+/// [`WinHandler`]. While a drag is over the field, [`caret`] says where to draw the insertion
+/// caret; paint it like the field's own caret. Dropping text that was dragged out of the same
+/// field moves it, and anything else is copied.
+///
+/// [`WinHandler`]: crate::WinHandler
+/// [`caret`]: TextDrag::caret
+#[derive(Debug, Clone, Default)]
+pub struct TextDrag {
+    /// The text being dragged out of the field, if a drag started here.
+    source: Option<(Range<usize>, String)>,
+    /// Where the text would be inserted if it were dropped now.
+    drop_index: Option<usize>,
+}
+
+impl TextDrag {
+    /// Creates a `TextDrag` with no drag underway.
+    pub fn new() -> TextDrag {
+        TextDrag::default()
+    }
+
+    /// Starts dragging the selected text out of the field, as with [`WindowHandle::start_drag`].
+    ///
+    /// Returns `false`, without starting a drag, if nothing is selected.
+    pub fn start(&mut self, window: &WindowHandle, input: &dyn InputHandler) -> bool {
+        let selection = input.selection();
+        if selection.is_caret() {
+            return false;
+        }
+        let range = selection.range();
+        let text = input.slice(range.clone()).into_owned();
+        self.source = Some((range, text.clone()));
+        let data = DragData {
+            text: Some(text),
+            ..DragData::default()
+        };
+        window.start_drag(data, None);
+        true
+    }
+
+    /// Moves the insertion caret to follow a drag at `pos`, from [`WinHandler::drag_enter`] or
+    /// [`WinHandler::drag_over`], and returns the effect to report.
+    ///
+    /// [`WinHandler::drag_enter`]: crate::WinHandler::drag_enter
+    /// [`WinHandler::drag_over`]: crate::WinHandler::drag_over
+    pub fn drag_over(
+        &mut self,
+        input: &dyn InputHandler,
+        pos: Point,
+        data: &DragData,
+    ) -> DropEffect {
+        self.drop_index = data.text.as_ref().and_then(|_| hit_test(input, pos));
+        // Dropping the dragged text inside itself wouldn't do anything.
+        if let (Some((range, _)), Some(index)) = (self.moving(data), self.drop_index) {
+            if range.start < index && index < range.end {
+                self.drop_index = None;
+            }
+        }
+        match self.drop_index {
+            // The platform is only ever offered a copy, even when we move the text ourselves.
+            Some(_) => DropEffect::Copy,
+            None => DropEffect::None,
+        }
+    }
+
+    /// Hides the insertion caret, from [`WinHandler::drag_leave`].
+    ///
+    /// [`WinHandler::drag_leave`]: crate::WinHandler::drag_leave
+    pub fn drag_leave(&mut self) {
+        self.drop_index = None;
+    }
+
+    /// Inserts the dropped text at `pos`, from [`WinHandler::drag_drop`], and selects it.
+    ///
+    /// This needs a mutable lock on the field. Returns the effect to report.
+    ///
+    /// [`WinHandler::drag_drop`]: crate::WinHandler::drag_drop
+    pub fn drop(
+        &mut self,
+        input: &mut dyn InputHandler,
+        pos: Point,
+        data: &DragData,
+    ) -> DropEffect {
+        let effect = self.drag_over(input, pos, data);
+        let moving = self.moving(data).map(|(range, _)| range.clone());
+        self.source = None;
+        let (mut index, text) = match (self.drop_index.take(), &data.text) {
+            (Some(index), Some(text)) => (index, text),
+            _ => return DropEffect::None,
+        };
+        if let Some(range) = moving {
+            input.replace_range(range.clone(), "");
+            if index >= range.end {
+                index -= range.len();
+            }
+        }
+        input.replace_range(index..index, text);
+        input.set_selection(Selection::new(index, index + text.len()));
+        effect
+    }
+
+    /// Returns where the dropped text would be inserted, while a drag is over the field.
+    pub fn drop_index(&self) -> Option<usize> {
+        self.drop_index
+    }
+
+    /// Returns the insertion caret to draw, in window coordinates, while a drag is over the
+    /// field.
+    pub fn caret(&self, input: &dyn InputHandler) -> Option<Rect> {
+        let index = self.drop_index?;
+        let line = input.line_range(index, Affinity::Downstream);
+        let line_box = input
+            .slice_bounding_box(line.clone())
+            .or_else(|| input.bounding_box())?;
+        let x = caret_x(input, &line, index).unwrap_or(line_box.x0);
+        Some(Rect::new(x, line_box.y0, x + 1.0, line_box.y1))
+    }
+
+    /// The text that was dragged out of this field, if `data` is that drag.
+    fn moving(&self, data: &DragData) -> Option<&(Range<usize>, String)> {
+        self.source
+            .as_ref()
+            .filter(|(_, text)| data.text.as_ref() == Some(text))
+    }
+}
+
+/// Finds the caret position closest to `pos`, if it is inside the field.
+fn hit_test(input: &dyn InputHandler, pos: Point) -> Option<usize> {
+    if !input.bounding_box()?.contains(pos) {
+        return None;
+    }
+    // Find the line under the point, or the last one if the point is below the text.
+    let mut line = input.line_range(0, Affinity::Downstream);
+    loop {
+        let below = input
+            .slice_bounding_box(line.clone())
+            .is_some_and(|r| pos.y >= r.y1);
+        if !below {
+            break;
+        }
+        let next = input.line_range(line.end, Affinity::Downstream);
+        if next.start < line.end || next == line {
+            break;
+        }
+        line = next;
+    }
+    // A caret can't go after the line break.
+    let mut end = line.end;
+    if input.slice(line.clone()).ends_with('\n') {
+        end -= 1;
+    }
+    (line.start..=end)
+        .filter(|&i| input.is_char_boundary(i))
+        .min_by(|&a, &b| {
+            let distance =
+                |i| caret_x(input, &line, i).map_or(f64::INFINITY, |x| (x - pos.x).abs());
+            distance(a).total_cmp(&distance(b))
+        })
+}
+
+/// The horizontal position of a caret at `index` in `line`.
+fn caret_x(input: &dyn InputHandler, line: &Range<usize>, index: usize) -> Option<f64> {
+    if index == line.start {
+        input.slice_bounding_box(line.clone()).map(|r| r.x0)
+    } else {
+        input.slice_bounding_box(line.start..index).map(|r| r.x1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::text::Action;
+
+    /// A field with one-line paragraphs, where each character is 10 by 20 points.
+    struct Field {
+        text: String,
+        selection: Selection,
+    }
+
+    impl InputHandler for Field {
+        fn selection(&self) -> Selection {
+            self.selection
+        }
+        fn set_selection(&mut self, selection: Selection) {
+            self.selection = selection;
+        }
+        fn composition_range(&self) -> Option<Range<usize>> {
+            None
+        }
+        fn set_composition_range(&mut self, _: Option<Range<usize>>) {}
+        fn is_char_boundary(&self, i: usize) -> bool {
+            self.text.is_char_boundary(i)
+        }
+        fn len(&self) -> usize {
+            self.text.len()
+        }
+        fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+            self.text[range].into()
+        }
+        fn replace_range(&mut self, range: Range<usize>, text: &str) {
+            self.text.replace_range(range, text);
+        }
+        fn line_range(&self, index: usize, _: Affinity) -> Range<usize> {
+            let start = self.text[..index].rfind('\n').map_or(0, |i| i + 1);
+            let end = self.text[index..]
+                .find('\n')
+                .map_or(self.text.len(), |i| index + i + 1);
+            start..end
+        }
+        fn bounding_box(&self) -> Option<Rect> {
+            Some(Rect::new(0.0, 0.0, 100.0, 100.0))
+        }
+        fn slice_bounding_box(&self, range: Range<usize>) -> Option<Rect> {
+            let line = self.text[..range.start].matches('\n').count() as f64;
+            let x0 =
+                (range.start - self.line_range(range.start, Affinity::Downstream).start) as f64;
+            let x1 = x0 + self.text[range].trim_end_matches('\n').len() as f64;
+            Some(Rect::new(
+                x0 * 10.0,
+                line * 20.0,
+                x1 * 10.0,
+                line * 20.0 + 20.0,
+            ))
+        }
+        fn handle_action(&mut self, _: Action) {}
+    }
+
+    fn text(text: &str) -> DragData {
+        DragData {
+            text: Some(text.into()),
+            ..DragData::default()
+        }
+    }
+
+    #[test]
+    fn drop_text() {
+        let mut field = Field {
+            text: "abc\ndef\n".into(),
+            selection: Selection::caret(0),
+        };
+        let mut drag = TextDrag::new();
+        assert_eq!(
+            drag.drag_over(&field, Point::new(12.0, 25.0), &text("xy")),
+            DropEffect::Copy
+        );
+        assert_eq!(drag.drop_index(), Some(5));
+        assert_eq!(drag.caret(&field), Some(Rect::new(10.0, 20.0, 11.0, 40.0)));
+        // Past the end of a line, the caret goes before the line break.
+        drag.drag_over(&field, Point::new(90.0, 5.0), &text("xy"));
+        assert_eq!(drag.drop_index(), Some(3));
+        drag.drag_over(&field, Point::new(5.0, 90.0), &text("xy"));
+        assert_eq!(drag.drop_index(), Some(8));
+        assert_eq!(
+            drag.drop(&mut field, Point::new(12.0, 25.0), &text("xy")),
+            DropEffect::Copy
+        );
+        assert_eq!(field.text, "abc\ndxyef\n");
+        assert_eq!(field.selection, Selection::new(5, 7));
+        assert_eq!(drag.drop_index(), None);
+    }
+
+    #[test]
+    fn move_text() {
+        let mut field = Field {
+            text: "abc\ndef\n".into(),
+            selection: Selection::new(0, 2),
+        };
+        let mut drag = TextDrag::new();
+        drag.source = Some((0..2, "ab".into()));
+        // Not inside the dragged text.
+        assert_eq!(
+            drag.drag_over(&field, Point::new(12.0, 5.0), &text("ab")),
+            DropEffect::None
+        );
+        drag.drop(&mut field, Point::new(22.0, 25.0), &text("ab"));
+        assert_eq!(field.text, "c\ndeabf\n");
+        assert_eq!(field.selection, Selection::new(4, 6));
+    }
+}
@@ -18,9 +18,9 @@
 // bitflags implementation of the inner Modifiers type.
 #![allow(clippy::trivially_copy_pass_by_ref)]
 
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Range};
 
-pub use keyboard_types::{Code, KeyState, Location};
+pub use keyboard_types::{Code, CompositionState, KeyState, Location};
 
 /// The meaning (mapped value) of a keypress.
 pub type KbKey = keyboard_types::Key;
@@ -49,6 +49,49 @@ pub struct KeyEvent {
     /// Events with this flag should be ignored in a text editor
     /// and instead composition events should be used.
     pub is_composing: bool,
+    pub(crate) xkb_keysym: Option<u32>,
+}
+
+/// A change to the text being composed by an input method.
+///
+/// These events describe the raw preedit text, and are delivered whether or not
+/// there is an active [`InputHandler`]. They are mostly useful for applications
+/// that implement their own text editing and want to draw the preedit text themselves;
+/// if you are using [`InputHandler`], the composition is already applied to your document.
+///
+/// A composition starts with a [`CompositionState::Start`] event, followed by
+/// any number of [`CompositionState::Update`] events, and finishes with a single
+/// [`CompositionState::End`] event.
+///
+/// Note that this type is similar to [`CompositionEvent`] in keyboard-types,
+/// but it also reports the position of the cursor in the preedit text.
+///
+/// [`InputHandler`]: crate::text::InputHandler
+/// [`CompositionEvent`]: keyboard_types::CompositionEvent
+#[non_exhaustive]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CompositionEvent {
+    /// Which stage of the composition this event describes.
+    pub state: CompositionState,
+    /// For [`CompositionState::Update`], the current preedit text. For
+    /// [`CompositionState::End`], the text that was committed, which is empty
+    /// if the composition was cancelled.
+    pub text: String,
+    /// The cursor or selection within the preedit text, as a byte range into `text`,
+    /// if the input method provides one.
+    pub cursor: Option<Range<usize>>,
+}
+
+/// A keyboard layout (or input source) that the user has enabled.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct KeyboardLayout {
+    /// A platform-specific identifier for the layout, such as `com.apple.keylayout.US` on macOS.
+    pub id: String,
+    /// The human readable name of the layout, suitable for showing to the user.
+    pub name: String,
+    /// The language the layout is intended for, as a BCP 47 language tag like `en-US`,
+    /// if the platform provides it.
+    pub language: Option<String>,
 }
 
 /// The modifiers.
@@ -87,8 +130,18 @@ impl KeyEvent {
             mods,
             is_composing: false,
             repeat: false,
+            xkb_keysym: None,
         }
     }
+
+    /// The xkb keysym of the key, like `XK_a` or `XK_Return`, taking the modifiers into
+    /// account.
+    ///
+    /// This is for interoperating with other Linux software that works with keysyms. It is
+    /// only available on the X11 and Wayland backends, and in GTK, whose keyvals are keysyms.
+    pub fn xkb_keysym(&self) -> Option<u32> {
+        self.xkb_keysym
+    }
 }
 
 impl Modifiers {
@@ -114,6 +167,12 @@ impl Modifiers {
         self.0
     }
 
+    /// The inverse of [`raw`](Modifiers::raw).
+    #[cfg_attr(not(feature = "record"), allow(dead_code))]
+    pub(crate) fn from_raw(raw: keyboard_types::Modifiers) -> Modifiers {
+        Modifiers(raw)
+    }
+
     /// Determine whether Shift is set.
     pub fn shift(&self) -> bool {
         self.contains(Modifiers::SHIFT)
@@ -57,38 +57,71 @@ pub use raw_window_handle;
 mod util;
 
 mod application;
+mod autoscroll;
 mod backend;
 mod clipboard;
+mod colors;
 mod common_util;
 mod dialog;
+mod dispatch;
+mod dnd;
 mod error;
 mod hotkey;
 mod keyboard;
+mod locale;
+mod media;
 mod menu;
 mod mouse;
 mod region;
 mod scale;
 mod screen;
+mod sensors;
+mod shortcut_capture;
+mod strings;
 mod window;
+mod window_group;
 
+pub mod bench;
+#[cfg(all(
+    any(target_os = "freebsd", target_os = "linux", target_os = "openbsd"),
+    feature = "dbus"
+))]
+pub mod dbus;
 pub mod platform;
+#[cfg(feature = "record")]
+pub mod record;
 pub mod text;
 
-pub use application::{AppHandler, Application};
-pub use clipboard::{Clipboard, ClipboardFormat, FormatId};
+pub use application::{AppHandler, Application, DispatchPolicy, KioskOptions, SessionBlob};
+pub use clipboard::{Clipboard, ClipboardFormat, ClipboardFormatInfo, FormatId};
+pub use colors::{HighContrastColors, SystemColor};
 pub use common_util::Counter;
 pub use dialog::{FileDialogOptions, FileInfo, FileSpec};
+pub use dnd::{DragData, DragImage, DropEffect, TextDrag};
 pub use error::Error;
 pub use hotkey::{HotKey, RawMods, SysMods};
-pub use keyboard::{Code, IntoKey, KbKey, KeyEvent, KeyState, Location, Modifiers};
-pub use menu::Menu;
-pub use mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
+pub use keyboard::{
+    Code, CompositionEvent, CompositionState, IntoKey, KbKey, KeyEvent, KeyState, KeyboardLayout,
+    Location, Modifiers,
+};
+pub use locale::{DateOrder, LocaleInfo};
+pub use media::{MediaCommand, MediaMetadata, PlaybackStatus};
+pub use menu::{EditCommand, Menu};
+pub use mouse::{
+    Cursor, CursorDesc, ForceClick, InputSource, MomentumPhase, MouseButton, MouseButtons,
+    MouseEvent, ScrollDelta, VelocityTracker,
+};
 pub use region::Region;
 pub use scale::{Scalable, Scale, ScaledArea};
-pub use screen::{Monitor, Screen};
+pub use screen::{ColorShift, Monitor, MonitorId, Screen};
+pub use sensors::{DeviceOrientation, SensorEvent, SensorKind};
+pub use strings::BuiltinString;
 pub use window::{
-    FileDialogToken, IdleHandle, IdleToken, TextFieldToken, TimerToken, WinHandler, WindowBuilder,
-    WindowHandle, WindowLevel, WindowState,
+    DecorationMode, FileDialogToken, FocusDirection, FrameInfo, FullscreenOptions, IdleHandle,
+    IdleToken, KeyboardInteractivity, Layer, LayerAnchor, PaintReason, ScrollbarMetrics, SystemBar,
+    TextFieldToken, Theme, TimerToken, WinHandler, WindowBuilder, WindowHandle, WindowLevel,
+    WindowPosition, WindowState,
 };
+pub use window_group::{WindowGroup, WindowGroupHandler};
 
 pub use keyboard_types;
@@ -0,0 +1,175 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The user's regional formatting preferences.
+
+/// How the user wants numbers, dates and times to be written.
+///
+/// This takes into account the overrides that the user made in the system settings, so it
+/// can differ from what the locale alone would give. Get it with
+/// [`WindowHandle::locale_info`]; changes are reported through
+/// [`WinHandler::locale_changed`].
+///
+/// [`WindowHandle::locale_info`]: crate::WindowHandle::locale_info
+/// [`WinHandler::locale_changed`]: crate::WinHandler::locale_changed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LocaleInfo {
+    /// The locale, as a BCP-47 language tag such as `en-GB`.
+    pub locale: String,
+    /// What separates the whole and fractional parts of a number, such as `.` or `,`.
+    pub decimal_separator: String,
+    /// What separates groups of digits in large numbers, such as `,`, `.` or a space. This is
+    /// empty if digits aren't grouped.
+    pub grouping_separator: String,
+    /// The order of the day, month and year in short dates.
+    pub date_order: DateOrder,
+    /// Whether times are written with a 24-hour clock, rather than with AM and PM.
+    pub uses_24_hour_clock: bool,
+}
+
+/// The order of the day, month and year in a date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+impl Default for LocaleInfo {
+    /// The conventions of the POSIX locale, which backends fall back to when they can't find
+    /// out what the user wants.
+    fn default() -> Self {
+        LocaleInfo {
+            locale: "en-US".into(),
+            decimal_separator: ".".into(),
+            grouping_separator: String::new(),
+            date_order: DateOrder::MonthDayYear,
+            uses_24_hour_clock: true,
+        }
+    }
+}
+
+impl LocaleInfo {
+    /// Sets the date order and the clock from a short date pattern and a time pattern, as far
+    /// as they can be worked out.
+    #[cfg_attr(feature = "headless", allow(dead_code))]
+    pub(crate) fn apply_patterns(&mut self, date: &str, time: &str) {
+        if let Some(order) = date_order(date) {
+            self.date_order = order;
+        }
+        if let Some(h24) = uses_24_hour_clock(time) {
+            self.uses_24_hour_clock = h24;
+        }
+    }
+}
+
+/// The fields of a date or time pattern, in order.
+///
+/// This understands `strftime` patterns, as well as the ICU patterns used by macOS and the
+/// similar ones used by Windows. Quoted literal text in the latter is skipped.
+fn pattern_fields(pattern: &str) -> Vec<char> {
+    let mut fields = Vec::new();
+    if pattern.contains('%') {
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            // Skip the flags and width, as in `%-d` or `%02m`.
+            let mut spec = chars.next();
+            while let Some('-' | '_' | '0'..='9' | 'E' | 'O') = spec {
+                spec = chars.next();
+            }
+            match spec {
+                Some('D') => fields.extend(['m', 'd', 'y']),
+                Some('F') => fields.extend(['y', 'm', 'd']),
+                Some('T' | 'R') => fields.push('H'),
+                Some('r') => fields.push('I'),
+                Some(c) => fields.push(c),
+                None => (),
+            }
+        }
+    } else {
+        let mut quoted = false;
+        let mut prev = None;
+        for c in pattern.chars() {
+            if c == '\'' {
+                quoted = !quoted;
+            } else if !quoted && c.is_ascii_alphabetic() && prev != Some(c) {
+                // Map ICU letters onto strftime ones.
+                fields.push(match c {
+                    'M' | 'L' => 'm',
+                    'm' => 'M',
+                    'Y' | 'u' => 'y',
+                    'h' | 'K' => 'I',
+                    'k' => 'H',
+                    c => c,
+                });
+            }
+            prev = Some(c);
+        }
+    }
+    fields
+}
+
+/// Works out the order of the day, month and year in a short date pattern.
+fn date_order(pattern: &str) -> Option<DateOrder> {
+    let fields = pattern_fields(pattern);
+    let position = |names: &[char]| fields.iter().position(|f| names.contains(f));
+    let day = position(&['d', 'e'])?;
+    let month = position(&['m', 'b', 'B', 'h'])?;
+    let year = position(&['y', 'Y', 'G', 'C'])?;
+    if year < month && month < day {
+        Some(DateOrder::YearMonthDay)
+    } else if day < month {
+        Some(DateOrder::DayMonthYear)
+    } else {
+        Some(DateOrder::MonthDayYear)
+    }
+}
+
+/// Works out whether a time pattern uses a 24-hour clock.
+fn uses_24_hour_clock(pattern: &str) -> Option<bool> {
+    pattern_fields(pattern).iter().find_map(|f| match f {
+        'H' => Some(true),
+        'I' | 'l' => Some(false),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        // strftime, from nl_langinfo.
+        assert_eq!(date_order("%m/%d/%Y"), Some(DateOrder::MonthDayYear));
+        assert_eq!(date_order("%d.%m.%Y"), Some(DateOrder::DayMonthYear));
+        assert_eq!(date_order("%Y年%m月%d日"), Some(DateOrder::YearMonthDay));
+        assert_eq!(date_order("%D"), Some(DateOrder::MonthDayYear));
+        assert_eq!(uses_24_hour_clock("%I:%M:%S %p"), Some(false));
+        assert_eq!(uses_24_hour_clock("%T"), Some(true));
+        // ICU and Windows.
+        assert_eq!(date_order("dd/MM/yyyy"), Some(DateOrder::DayMonthYear));
+        assert_eq!(date_order("y-MM-dd"), Some(DateOrder::YearMonthDay));
+        assert_eq!(
+            date_order("'day' d 'of' M, y"),
+            Some(DateOrder::DayMonthYear)
+        );
+        assert_eq!(uses_24_hour_clock("h:mm:ss tt"), Some(false));
+        assert_eq!(uses_24_hour_clock("HH:mm"), Some(true));
+        assert_eq!(date_order("HH:mm"), None);
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Integration with the desktop's media controls.
+
+use std::time::Duration;
+
+/// A request from the desktop's media controls (for example media keys, or a media widget
+/// in the shell) to the application.
+///
+/// These are delivered to [`AppHandler::media_command`].
+///
+/// [`AppHandler::media_command`]: crate::AppHandler::media_command
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    /// Seek relative to the current position, in seconds. Negative values seek backwards.
+    Seek(f64),
+    /// Seek to an absolute position in the current track.
+    SetPosition(Duration),
+}
+
+/// Whether the application is currently playing something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+/// A description of the current track, shown by the desktop's media controls.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MediaMetadata {
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    /// The length of the track, if it is known.
+    pub length: Option<Duration>,
+    /// A URL (usually a `file://` URL) of the cover art.
+    pub art_url: Option<String>,
+}
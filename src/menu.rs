@@ -12,8 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::application::Application;
 use crate::backend::menu as backend;
 use crate::hotkey::HotKey;
+use crate::text::{Action, Selection};
+use crate::window::{TextFieldToken, WinHandler};
+
+/// A standard editing command, which glazier carries out by itself on the focused text field.
+///
+/// See [`Menu::add_edit_item`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditCommand {
+    /// Sends [`Action::Undo`] to the text field.
+    Undo,
+    /// Sends [`Action::Redo`] to the text field.
+    Redo,
+    /// Moves the selected text to the clipboard.
+    Cut,
+    /// Copies the selected text to the clipboard.
+    Copy,
+    /// Replaces the selection with the text on the clipboard.
+    Paste,
+    /// Selects all of the text.
+    SelectAll,
+}
+
+thread_local! {
+    /// The edit commands of menu items, by their ids.
+    static EDIT_COMMANDS: RefCell<HashMap<u32, EditCommand>> = RefCell::new(HashMap::new());
+}
 
 /// A menu object.
 ///
@@ -77,8 +107,71 @@ impl Menu {
         self.0.add_item(id, text, key, selected, enabled)
     }
 
+    /// Add an item for a standard editing command to this menu.
+    ///
+    /// This is like [`add_item`], except that when the item is chosen while a text field has
+    /// focus, glazier carries out `command` on the field's [`InputHandler`] instead of calling
+    /// [`command()`]. This is what macOS does for the Edit menu, and saves each application
+    /// from doing it.
+    ///
+    /// This currently only works on the platforms with menus: macOS, Windows and GTK.
+    ///
+    /// [`add_item`]: Menu::add_item
+    /// [`InputHandler`]: crate::text::InputHandler
+    /// [`command()`]: crate::WinHandler::command
+    pub fn add_edit_item(
+        &mut self,
+        id: u32,
+        command: EditCommand,
+        text: &str,
+        key: Option<&HotKey>,
+        enabled: bool,
+    ) {
+        EDIT_COMMANDS.with(|commands| commands.borrow_mut().insert(id, command));
+        self.0.add_item(id, text, key, None, enabled)
+    }
+
     /// Add a separator to the menu.
     pub fn add_separator(&mut self) {
         self.0.add_separator()
     }
 }
+
+/// Runs the menu command with `id`, on the text field with `focus` if it's an edit command.
+#[allow(dead_code)] // Not used on all platforms.
+pub(crate) fn run_command<H: WinHandler + ?Sized>(
+    handler: &mut H,
+    focus: Option<TextFieldToken>,
+    id: u32,
+) {
+    let command = EDIT_COMMANDS.with(|commands| commands.borrow().get(&id).copied());
+    let (command, token) = match (command, focus) {
+        (Some(command), Some(token)) => (command, token),
+        _ => return handler.command(id),
+    };
+    let mut clipboard = Application::try_global().map(|app| app.clipboard());
+    let mut input = handler.acquire_input_lock(token, command != EditCommand::Copy);
+    let selection = input.selection();
+    match command {
+        EditCommand::Undo => input.handle_action(Action::Undo),
+        EditCommand::Redo => input.handle_action(Action::Redo),
+        EditCommand::Cut | EditCommand::Copy => {
+            if let (Some(clipboard), false) = (&mut clipboard, selection.is_caret()) {
+                clipboard.put_string(input.slice(selection.range()));
+            }
+            if command == EditCommand::Cut {
+                input.replace_range(selection.range(), "");
+                input.set_selection(Selection::caret(selection.min()));
+            }
+        }
+        EditCommand::Paste => {
+            if let Some(text) = clipboard.and_then(|c| c.get_string_lossy()) {
+                input.replace_range(selection.range(), &text);
+                input.set_selection(Selection::caret(selection.min() + text.len()));
+            }
+        }
+        EditCommand::SelectAll => input.set_selection(Selection::new(0, input.len())),
+    }
+    drop(input);
+    handler.release_input_lock(token);
+}
@@ -14,6 +14,9 @@
 
 //! Common types for representing mouse events and state
 
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
 use crate::backend;
 use crate::kurbo::{Point, Vec2};
 // use crate::piet::ImageBuf;
@@ -53,7 +56,234 @@ pub struct MouseEvent {
     /// deltaX and deltaY values in a web [WheelEvent].
     ///
     /// [WheelEvent]: https://w3c.github.io/uievents/#event-type-wheel
-    pub wheel_delta: Vec2,
+    pub wheel_delta: ScrollDelta,
+    /// Whether this wheel event is part of the glide that continues after the user lifted
+    /// their fingers from the trackpad, or after a fling of the mouse wheel.
+    ///
+    /// Applications that animate their own kinetic scrolling should ignore these events.
+    /// Currently only macOS, and X11 with momentum scrolling enabled, report momentum.
+    pub momentum_phase: MomentumPhase,
+    /// Where the event came from.
+    ///
+    /// Over a remote desktop connection, clicks arrive with extra latency, so an application
+    /// may want to be more lenient with double-click timing or hover delays. Events that
+    /// another program synthesized, for automation or testing, may deserve less trust.
+    /// Currently macOS always reports [`InputSource::Hardware`].
+    pub source: InputSource,
+    /// The mouse-move events that were merged into this one, oldest first.
+    ///
+    /// This is empty if nothing was merged. Use [`coalesced_events`] rather than reading it
+    /// directly.
+    ///
+    /// [`coalesced_events`]: MouseEvent::coalesced_events
+    pub coalesced: Vec<MouseEvent>,
+}
+
+impl MouseEvent {
+    /// The pointer samples that this mouse-move event stands for, oldest first.
+    ///
+    /// Pens and gaming mice report positions much more often than the application paints, so
+    /// the samples that arrive together are delivered as a single event at the newest
+    /// position. Drawing applications can use the samples to get smooth strokes.
+    ///
+    /// The last sample is the event itself, so this is never empty. Currently only the Windows,
+    /// X11 and Wayland backends collect the merged samples; elsewhere this only contains the
+    /// event itself.
+    pub fn coalesced_events(&self) -> &[MouseEvent] {
+        if self.coalesced.is_empty() {
+            std::slice::from_ref(self)
+        } else {
+            &self.coalesced
+        }
+    }
+}
+
+/// The number of lines that a notch of the mouse wheel scrolls, on platforms without a
+/// setting for it.
+#[cfg_attr(feature = "headless", allow(dead_code))]
+pub(crate) const LINES_PER_NOTCH: f64 = 3.0;
+
+/// How far a wheel event scrolls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDelta {
+    /// A number of lines, from a mouse wheel that scrolls in steps. This takes the user's
+    /// setting for the number of lines per notch into account where there is one, and is
+    /// usually three lines per notch otherwise. High-resolution wheels can scroll fractions
+    /// of a line.
+    Lines(Vec2),
+    /// A distance in [display points], from a trackpad or another device that scrolls
+    /// smoothly.
+    ///
+    /// [display points]: crate::Scale
+    Pixels(Vec2),
+}
+
+impl ScrollDelta {
+    /// No movement at all, for events that aren't wheel events.
+    pub const ZERO: ScrollDelta = ScrollDelta::Pixels(Vec2::ZERO);
+
+    /// The distance in display points, when each line is `line_height` display points tall.
+    pub fn to_points(self, line_height: f64) -> Vec2 {
+        match self {
+            ScrollDelta::Lines(lines) => lines * line_height,
+            ScrollDelta::Pixels(points) => points,
+        }
+    }
+
+    /// Returns `true` if this doesn't scroll at all.
+    pub fn is_zero(self) -> bool {
+        match self {
+            ScrollDelta::Lines(delta) | ScrollDelta::Pixels(delta) => delta == Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for ScrollDelta {
+    fn default() -> Self {
+        ScrollDelta::ZERO
+    }
+}
+
+/// Where a wheel event is in a momentum glide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MomentumPhase {
+    /// The event comes directly from the user.
+    #[default]
+    None,
+    /// The first event of a glide.
+    Began,
+    /// The glide goes on.
+    Changed,
+    /// The last event of a glide.
+    Ended,
+}
+
+/// Where a [`MouseEvent`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InputSource {
+    /// A device attached to this computer, or a source that the backend can't tell apart
+    /// from one.
+    #[default]
+    Hardware,
+    /// Another program generated the event, for instance with `XSendEvent` on X11 or
+    /// `dispatchEvent` on the web.
+    Injected,
+    /// The event came over a remote desktop session, such as RDP or a forwarded X11
+    /// display.
+    Remote,
+}
+
+/// A change in how hard a click on a pressure-sensitive trackpad is pressing.
+///
+/// See [`WinHandler::force_click`].
+///
+/// [`WinHandler::force_click`]: crate::WinHandler::force_click
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForceClick {
+    /// The position of the pointer, in [display points] relative to the content area.
+    ///
+    /// [display points]: crate::Scale
+    pub pos: Point,
+    /// The stage the press has reached: 0 before the trackpad clicks, 1 for a normal click,
+    /// and 2 for a force click.
+    pub stage: u32,
+    /// How hard the trackpad is pressed, from 0 to 1.
+    pub pressure: f64,
+    /// How far the press has moved towards the next stage, from 0 to 1, or towards the
+    /// previous one, from 0 to -1.
+    pub stage_transition: f64,
+}
+
+/// Merges mouse-move events that arrived together into one, at the newest position.
+#[cfg_attr(feature = "headless", allow(dead_code))]
+pub(crate) fn coalesce_moves(mut events: Vec<MouseEvent>) -> Option<MouseEvent> {
+    if events.len() > 1 {
+        let mut event = events.last().cloned()?;
+        event.coalesced = events;
+        Some(event)
+    } else {
+        events.pop()
+    }
+}
+
+/// How far back a [`VelocityTracker`] looks when estimating velocity.
+const VELOCITY_HORIZON: Duration = Duration::from_millis(100);
+
+/// The maximum number of samples a [`VelocityTracker`] keeps.
+const VELOCITY_MAX_SAMPLES: usize = 20;
+
+/// Estimates the velocity of the pointer from recent mouse events.
+///
+/// This is meant for fling and momentum gestures: feed it the mouse-move events of a
+/// drag, and ask for the [`velocity`] when the button is released. The estimate is a
+/// least-squares fit over the last 100ms of movement, so it is robust against a single
+/// noisy event, and it drops to zero if the pointer stopped before being released.
+///
+/// [`velocity`]: VelocityTracker::velocity
+#[derive(Debug, Clone, Default)]
+pub struct VelocityTracker {
+    samples: VecDeque<(Instant, Point)>,
+}
+
+impl VelocityTracker {
+    /// Create a new, empty `VelocityTracker`.
+    pub fn new() -> VelocityTracker {
+        VelocityTracker::default()
+    }
+
+    /// Record the position of a mouse event, received now.
+    pub fn add_event(&mut self, event: &MouseEvent) {
+        self.add_point(event.pos, Instant::now());
+    }
+
+    /// Record the position of the pointer at the given time.
+    ///
+    /// Samples must be added in chronological order.
+    pub fn add_point(&mut self, pos: Point, time: Instant) {
+        if self.samples.len() == VELOCITY_MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, pos));
+    }
+
+    /// Forget all the recorded samples, for example at the start of a new drag.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    /// The estimated velocity of the pointer, in display points per second.
+    pub fn velocity(&self) -> Vec2 {
+        self.velocity_at(Instant::now())
+    }
+
+    fn velocity_at(&self, now: Instant) -> Vec2 {
+        let recent = || {
+            self.samples
+                .iter()
+                .filter(move |(time, _)| now.saturating_duration_since(*time) <= VELOCITY_HORIZON)
+        };
+        let count = recent().count();
+        if count < 2 {
+            return Vec2::ZERO;
+        }
+
+        // Fit a line to the positions with respect to time; its slope is the velocity.
+        let secs = |time: Instant| -> f64 { -now.saturating_duration_since(time).as_secs_f64() };
+        let n = count as f64;
+        let mean_t = recent().map(|(time, _)| secs(*time)).sum::<f64>() / n;
+        let mean_pos = recent().fold(Vec2::ZERO, |acc, (_, pos)| acc + pos.to_vec2()) / n;
+        let mut var_t = 0.0;
+        let mut cov = Vec2::ZERO;
+        for (time, pos) in recent() {
+            let dt = secs(*time) - mean_t;
+            var_t += dt * dt;
+            cov += (pos.to_vec2() - mean_pos) * dt;
+        }
+        if var_t < f64::EPSILON {
+            return Vec2::ZERO;
+        }
+        cov / var_t
+    }
 }
 
 /// An indicator of which mouse button was pressed.
@@ -262,36 +492,71 @@ pub enum Cursor {
     NotAllowed,
     ResizeLeftRight,
     ResizeUpDown,
+    /// Content can be scrolled in any direction, shown during [autoscroll].
+    ///
+    /// [autoscroll]: crate::WindowHandle::begin_autoscroll
+    AllScroll,
+    /// The application is busy, and can't be interacted with.
+    Wait,
+    /// The application is busy in the background, but can still be interacted with.
+    Progress,
     // The platform cursor should be small. Any image data that it uses should be shared (i.e.
     // behind an `Arc` or using a platform API that does the sharing).
     Custom(backend::window::CustomCursor),
 }
 
 /// A platform-independent description of a custom cursor.
+///
+/// Turn it into a [`Cursor`] with [`WindowHandle::make_cursor`].
+///
+/// [`WindowHandle::make_cursor`]: crate::WindowHandle::make_cursor
 #[derive(Clone)]
+#[cfg_attr(feature = "headless", allow(dead_code))]
 pub struct CursorDesc {
-    // #[allow(dead_code)] // Not yet used on all platforms.
-    // pub(crate) image: ImageBuf,
-    #[allow(dead_code)] // Not yet used on all platforms.
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// The pixels, row by row from the top, as premultiplied RGBA with 8 bits per channel.
+    pub(crate) pixels: Vec<u8>,
     pub(crate) hot: Point,
 }
 
 impl CursorDesc {
     /// Creates a new `CursorDesc`.
     ///
-    /// `hot` is the "hot spot" of the cursor, measured in terms of the pixels in `image` with
+    /// `pixels` holds the `width` by `height` image, row by row from the top, as premultiplied
+    /// RGBA with 8 bits per channel.
+    ///
+    /// `hot` is the "hot spot" of the cursor, measured in terms of the pixels in the image with
     /// `(0, 0)` at the top left. The hot spot is the logical position of the mouse cursor within
     /// the image. For example, if the image is a picture of a arrow, the hot spot might be the
     /// coordinates of the arrow's tip.
-    pub fn new(
-        //image: ImageBuf,
-        hot: impl Into<Point>,
-    ) -> CursorDesc {
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` is too short for the image.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>, hot: impl Into<Point>) -> CursorDesc {
+        assert!(
+            pixels.len() >= width as usize * height as usize * 4,
+            "not enough pixels for a {}x{} cursor",
+            width,
+            height
+        );
         CursorDesc {
-            //image,
+            width,
+            height,
+            pixels,
             hot: hot.into(),
         }
     }
+
+    /// The pixels, in order, as `(r, g, b, a)`.
+    #[cfg_attr(feature = "headless", allow(dead_code))]
+    pub(crate) fn rgba(&self) -> impl Iterator<Item = [u8; 4]> + '_ {
+        self.pixels
+            .chunks_exact(4)
+            .take(self.width as usize * self.height as usize)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+    }
 }
 
 impl std::fmt::Debug for Cursor {
@@ -306,7 +571,58 @@ impl std::fmt::Debug for Cursor {
             Cursor::NotAllowed => write!(f, "Cursor::NotAllowed"),
             Cursor::ResizeLeftRight => write!(f, "Cursor::ResizeLeftRight"),
             Cursor::ResizeUpDown => write!(f, "Cursor::ResizeUpDown"),
+            Cursor::AllScroll => write!(f, "Cursor::AllScroll"),
+            Cursor::Wait => write!(f, "Cursor::Wait"),
+            Cursor::Progress => write!(f, "Cursor::Progress"),
             Cursor::Custom(_) => write!(f, "Cursor::Custom"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn velocity_tracker() {
+        let start = Instant::now();
+        let mut tracker = VelocityTracker::new();
+        for i in 0..10 {
+            let time = start + Duration::from_millis(10 * i);
+            tracker.add_point(Point::new(5.0 * i as f64, 0.0), time);
+        }
+        let last = start + Duration::from_millis(90);
+        let velocity = tracker.velocity_at(last);
+        assert!((velocity.x - 500.0).abs() < 1e-6);
+        assert!(velocity.y.abs() < 1e-6);
+
+        // The pointer stopped moving a while before we asked.
+        let later = last + Duration::from_millis(200);
+        assert_eq!(tracker.velocity_at(later), Vec2::ZERO);
+    }
+
+    #[test]
+    fn coalesced_moves() {
+        let event = |x: f64| MouseEvent {
+            pos: Point::new(x, 0.0),
+            buttons: MouseButtons::new(),
+            mods: Modifiers::empty(),
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: InputSource::Hardware,
+            coalesced: Vec::new(),
+        };
+        assert_eq!(coalesce_moves(Vec::new()), None);
+
+        let single = coalesce_moves(vec![event(1.0)]).unwrap();
+        assert_eq!(single.coalesced_events(), &[event(1.0)]);
+
+        let merged = coalesce_moves(vec![event(1.0), event(2.0), event(3.0)]).unwrap();
+        assert_eq!(merged.pos, Point::new(3.0, 0.0));
+        let positions: Vec<_> = merged.coalesced_events().iter().map(|e| e.pos.x).collect();
+        assert_eq!(positions, [1.0, 2.0, 3.0]);
+    }
+}
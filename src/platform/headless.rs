@@ -0,0 +1,351 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless backend specific extensions.
+
+use instant::{Duration, Instant};
+
+use crate::kurbo::{Point, Size};
+use crate::{KeyEvent, MouseButton, Scale, ScrollDelta, WindowHandle};
+#[cfg(feature = "record")]
+use crate::{MouseEvent, WinHandler};
+
+/// Synthesizes input for a window of the headless backend.
+///
+/// The events go straight to the window's [`WinHandler`], as if the user had caused them.
+/// Pointer events are marked as [`InputSource::Injected`], and carry the modifiers of the last
+/// injected key event.
+///
+/// Idle callbacks, timers and painting still go through the event loop. Either run the
+/// application, or call [`run_pending`] to catch up on them. The event loop can be told to
+/// hold back the window's timers and frames, to test how the handler copes with a busy
+/// system or a stalled compositor.
+///
+/// [`WinHandler`]: crate::WinHandler
+/// [`InputSource::Injected`]: crate::InputSource::Injected
+/// [`run_pending`]: EventInjector::run_pending
+#[derive(Clone)]
+pub struct EventInjector {
+    window: WindowHandle,
+}
+
+impl EventInjector {
+    /// Creates an injector for the given window.
+    pub fn new(window: &WindowHandle) -> EventInjector {
+        EventInjector {
+            window: window.clone(),
+        }
+    }
+
+    /// Moves the pointer to `pos`.
+    pub fn mouse_move(&self, pos: Point) {
+        self.window.0.inject_mouse_move(pos);
+    }
+
+    /// Presses a mouse button at `pos`. Presses close together in time and space count as
+    /// multiple clicks.
+    pub fn mouse_down(&self, pos: Point, button: MouseButton) {
+        self.window.0.inject_mouse_down(pos, button);
+    }
+
+    /// Releases a mouse button at `pos`.
+    pub fn mouse_up(&self, pos: Point, button: MouseButton) {
+        self.window.0.inject_mouse_up(pos, button);
+    }
+
+    /// Scrolls by `delta` with the pointer at `pos`.
+    pub fn wheel(&self, pos: Point, delta: ScrollDelta) {
+        self.window.0.inject_wheel(pos, delta);
+    }
+
+    /// Delivers a recorded mouse event to `callback`, without changing anything about it.
+    #[cfg(feature = "record")]
+    pub(crate) fn replay_mouse(
+        &self,
+        event: &MouseEvent,
+        callback: fn(&mut dyn WinHandler, &MouseEvent),
+    ) {
+        self.window.0.inject_mouse_event(event, callback);
+    }
+
+    /// Moves the pointer out of the window.
+    pub fn mouse_leave(&self) {
+        self.window.0.inject_mouse_leave();
+    }
+
+    /// Presses a key.
+    ///
+    /// Like a real key press, this goes to the focused text field if the handler doesn't take
+    /// it, and Tab moves the focus. Returns `true` if the key press was handled.
+    pub fn key_down(&self, event: KeyEvent) -> bool {
+        self.window.0.inject_key_down(event)
+    }
+
+    /// Releases a key.
+    pub fn key_up(&self, event: KeyEvent) {
+        self.window.0.inject_key_up(event);
+    }
+
+    /// Resizes the window, as if the user had dragged its edge.
+    pub fn resize(&self, size: Size) {
+        self.window.0.inject_resize(size);
+    }
+
+    /// Changes the scale of the window, as if it had been moved to another monitor.
+    pub fn set_scale(&self, scale: Scale) {
+        self.window.0.inject_scale(scale);
+    }
+
+    /// Gives the window the keyboard focus, or takes it away.
+    pub fn set_focused(&self, focused: bool) {
+        self.window.0.inject_focus(focused);
+    }
+
+    /// Asks the window to close, as if the user had clicked its close button.
+    pub fn request_close(&self) {
+        self.window.0.inject_close_request();
+    }
+
+    /// Fires the window's timers `delay` after their deadlines.
+    pub fn set_timer_delay(&self, delay: Duration) {
+        self.window.0.throttle(|t| t.timer_delay.set(delay));
+    }
+
+    /// Fires the timers that are due at the same time latest deadline first.
+    ///
+    /// Timers only promise not to fire before their deadlines, so this is an order a real
+    /// event loop could pick when it gets to them late.
+    pub fn set_reverse_timers(&self, reverse: bool) {
+        self.window.0.throttle(|t| t.reverse_timers.set(reverse));
+    }
+
+    /// Drops the next `count` timers that come due, without telling the handler.
+    pub fn drop_timers(&self, count: u32) {
+        self.window.0.throttle(|t| t.dropped_timers.set(count));
+    }
+
+    /// Paints nothing until `duration` from now, as if the compositor had stalled.
+    pub fn stall_frames(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.window.0.throttle(|t| t.stalled_until.set(Some(until)));
+    }
+
+    /// Drops the next `count` animation frames without painting them.
+    ///
+    /// During an animation, the handler hears about them from [`WinHandler::frame_missed`]
+    /// when the next frame is painted.
+    ///
+    /// [`WinHandler::frame_missed`]: crate::WinHandler::frame_missed
+    pub fn drop_frames(&self, count: u32) {
+        self.window.0.throttle(|t| t.dropped_frames.set(count));
+    }
+
+    /// Runs the window's expired timers and idle callbacks, and paints it if it needs it.
+    ///
+    /// This is what the event loop does for the window each time around, so it lets tests
+    /// step through it without running the application.
+    pub fn run_pending(&self) {
+        self.window.0.run_pending();
+    }
+}
+
+/// Runs `f` with a new application, and shuts it down afterwards.
+///
+/// Only one application can exist at a time, so the tests that need one take turns.
+#[cfg(test)]
+pub(crate) fn with_test_app(f: impl FnOnce(&crate::Application)) {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _turn = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let app = crate::Application::new().unwrap();
+    f(&app);
+    app.quit();
+    app.run(None);
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::Any;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{
+        Application, IdleToken, KbKey, Modifiers, MouseEvent, PaintReason, Region, TimerToken,
+        WinHandler, WindowBuilder,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Size(Size),
+        MouseDown(Point, u8),
+        Wheel(ScrollDelta, Modifiers),
+        Key(KbKey),
+        Idle(IdleToken),
+        Timer(TimerToken),
+        FrameMissed(u32),
+        Paint,
+        Destroy,
+    }
+
+    struct Recorder(Rc<RefCell<Vec<Seen>>>);
+
+    impl WinHandler for Recorder {
+        fn connect(&mut self, _: &WindowHandle) {}
+        fn size(&mut self, size: Size) {
+            self.0.borrow_mut().push(Seen::Size(size));
+        }
+        fn prepare_paint(&mut self) {}
+        fn paint(&mut self, _: &Region, _: PaintReason) {
+            self.0.borrow_mut().push(Seen::Paint);
+        }
+        fn mouse_down(&mut self, event: &MouseEvent) {
+            self.0
+                .borrow_mut()
+                .push(Seen::MouseDown(event.pos, event.count));
+        }
+        fn wheel(&mut self, event: &MouseEvent) {
+            self.0
+                .borrow_mut()
+                .push(Seen::Wheel(event.wheel_delta, event.mods));
+        }
+        fn key_down(&mut self, event: KeyEvent) -> bool {
+            self.0.borrow_mut().push(Seen::Key(event.key));
+            true
+        }
+        fn idle(&mut self, token: IdleToken) {
+            self.0.borrow_mut().push(Seen::Idle(token));
+        }
+        fn timer(&mut self, token: TimerToken) {
+            self.0.borrow_mut().push(Seen::Timer(token));
+        }
+        fn frame_missed(&mut self, count: u32) {
+            self.0.borrow_mut().push(Seen::FrameMissed(count));
+        }
+        fn destroy(&mut self) {
+            self.0.borrow_mut().push(Seen::Destroy);
+        }
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn injected_events() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        with_test_app(|app| injected_events_in(app, &seen));
+        assert_eq!(*seen.borrow(), [Seen::Destroy]);
+    }
+
+    fn injected_events_in(app: &Application, seen: &Rc<RefCell<Vec<Seen>>>) {
+        let mut builder = WindowBuilder::new(app.clone());
+        builder.set_handler(Box::new(Recorder(seen.clone())));
+        builder.set_size(Size::new(200.0, 100.0));
+        let window = builder.build().unwrap();
+        window.show();
+        let injector = EventInjector::new(&window);
+
+        injector.resize(Size::new(300.0, 150.0));
+        injector.mouse_down(Point::new(10.0, 20.0), MouseButton::Left);
+        injector.mouse_up(Point::new(10.0, 20.0), MouseButton::Left);
+        injector.mouse_down(Point::new(10.0, 20.0), MouseButton::Left);
+        injector.key_down(KeyEvent::for_test(Modifiers::SHIFT, "a"));
+        injector.wheel(Point::ZERO, ScrollDelta::Lines((0.0, 1.0).into()));
+        window
+            .get_idle_handle()
+            .unwrap()
+            .schedule_idle(IdleToken::new(7));
+        injector.run_pending();
+
+        assert_eq!(
+            *seen.borrow(),
+            [
+                Seen::Size(Size::new(200.0, 100.0)),
+                Seen::Size(Size::new(300.0, 150.0)),
+                Seen::MouseDown(Point::new(10.0, 20.0), 1),
+                Seen::MouseDown(Point::new(10.0, 20.0), 2),
+                Seen::Key(KbKey::Character("a".into())),
+                Seen::Wheel(ScrollDelta::Lines((0.0, 1.0).into()), Modifiers::SHIFT),
+                Seen::Idle(IdleToken::new(7)),
+                Seen::Paint,
+            ]
+        );
+
+        seen.borrow_mut().clear();
+    }
+
+    #[test]
+    fn throttled_timers_and_frames() {
+        with_test_app(throttled_timers_and_frames_in);
+    }
+
+    fn throttled_timers_and_frames_in(app: &Application) {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut builder = WindowBuilder::new(app.clone());
+        builder.set_handler(Box::new(Recorder(seen.clone())));
+        let window = builder.build().unwrap();
+        window.show();
+        let injector = EventInjector::new(&window);
+        injector.run_pending();
+        seen.borrow_mut().clear();
+        // Give timers requested back to back different deadlines.
+        let request_timer = || {
+            std::thread::sleep(Duration::from_millis(2));
+            window.request_timer(Duration::ZERO)
+        };
+
+        injector.set_timer_delay(Duration::from_secs(3600));
+        let first = request_timer();
+        let second = request_timer();
+        injector.run_pending();
+        assert_eq!(*seen.borrow(), []);
+        injector.set_timer_delay(Duration::ZERO);
+        injector.set_reverse_timers(true);
+        injector.run_pending();
+        assert_eq!(*seen.borrow(), [Seen::Timer(second), Seen::Timer(first)]);
+
+        seen.borrow_mut().clear();
+        injector.set_reverse_timers(false);
+        injector.drop_timers(1);
+        request_timer();
+        let kept = request_timer();
+        injector.run_pending();
+        assert_eq!(*seen.borrow(), [Seen::Timer(kept)]);
+
+        seen.borrow_mut().clear();
+        injector.stall_frames(Duration::from_secs(3600));
+        window.invalidate();
+        injector.run_pending();
+        assert_eq!(*seen.borrow(), []);
+        injector.stall_frames(Duration::ZERO);
+        injector.run_pending();
+        assert_eq!(*seen.borrow(), [Seen::Paint]);
+
+        // Dropping a frame in the middle of an animation is reported when it carries on.
+        seen.borrow_mut().clear();
+        let next_frame = || {
+            std::thread::sleep(Duration::from_millis(20));
+            window.request_anim_frame();
+            injector.run_pending();
+        };
+        next_frame();
+        injector.drop_frames(1);
+        next_frame();
+        next_frame();
+        let seen = seen.borrow();
+        assert!(matches!(
+            seen[..],
+            [Seen::Paint, Seen::FrameMissed(missed), Seen::Paint] if missed >= 1
+        ));
+    }
+}
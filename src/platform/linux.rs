@@ -13,7 +13,7 @@
 // limitations under the License.
 
 //! Linux specific extensions.
-use crate::Clipboard;
+use crate::{Clipboard, SystemColor};
 
 /// Linux specific extensions to [`Application`]
 ///
@@ -23,6 +23,138 @@ pub trait ApplicationExt {
     ///
     /// This is useful for middle mouse paste.
     fn primary_clipboard(&self) -> Clipboard;
+
+    /// Returns the xkb keymap that key presses are translated with, in the xkb text format.
+    ///
+    /// This is the keymap installed with [`set_xkb_keymap`] if there is one, and otherwise the
+    /// system's.
+    ///
+    /// [`set_xkb_keymap`]: ApplicationExt::set_xkb_keymap
+    fn xkb_keymap(&self) -> Option<String>;
+
+    /// Installs an xkb keymap to translate key presses with, instead of the system's.
+    ///
+    /// This is useful for on-screen keyboards, and for testing. It applies to all of the
+    /// application's windows and seats. `None` goes back to the system's keymap.
+    ///
+    /// Returns `false` if the keymap couldn't be compiled, in which case the keymap in use
+    /// doesn't change.
+    fn set_xkb_keymap(&self, keymap: Option<XkbKeymap>) -> bool;
+}
+
+/// An xkb keymap.
+///
+/// See [`ApplicationExt::set_xkb_keymap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XkbKeymap {
+    /// A keymap put together from its rules, model, layout, variant and options.
+    Names(XkbRuleNames),
+    /// A keymap in the xkb text format, like the output of `xkbcomp`.
+    Text(String),
+}
+
+/// The names that make up an xkb keymap, like the arguments of `setxkbmap`.
+///
+/// Empty names are replaced by xkb's defaults, which come from the `XKB_DEFAULT_*`
+/// environment variables if they are set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbRuleNames {
+    pub rules: String,
+    pub model: String,
+    /// A comma separated list of layouts, like `us,de`.
+    pub layout: String,
+    /// A comma separated list of variants, one for each layout.
+    pub variant: String,
+    /// A comma separated list of options, like `ctrl:nocaps`.
+    pub options: String,
+}
+
+/// Linux specific extensions to [`WindowHandle`]
+///
+/// These let windows from different processes refer to each other, for instance so that a
+/// dialog shown by another process (like an xdg-desktop-portal file chooser) is parented to
+/// a glazier window, or the other way around.
+///
+/// Handles are strings in the format of the portal `parent_window` argument: `wayland:` followed
+/// by an xdg-foreign handle, or `x11:` followed by a window id in hexadecimal.
+///
+/// [`WindowHandle`]: crate::WindowHandle
+pub trait WindowHandleExt {
+    /// Exports this window so that other processes can refer to it.
+    ///
+    /// `f` is called with the handle once it is available, which on Wayland needs a round trip
+    /// to the compositor. It is called with `None` if the window can't be exported.
+    fn export_handle(&self, f: Box<dyn FnOnce(Option<String>)>);
+
+    /// Makes this window transient for a window exported by another process.
+    ///
+    /// Returns `false` if the handle is not one that this backend understands.
+    fn set_transient_for_foreign(&self, handle: &str) -> bool;
+
+    /// Sets whether mouse wheel scrolling glides to a stop, instead of moving a line at a time.
+    ///
+    /// X11 only reports whole wheel clicks. With this enabled, each click is turned into a
+    /// series of smaller [`WinHandler::wheel`] events that slow down over time, and spinning the
+    /// wheel quickly scrolls further, like a fling on a touchpad. It is off by default, and
+    /// does nothing on Wayland.
+    ///
+    /// [`WinHandler::wheel`]: crate::WinHandler::wheel
+    fn set_momentum_scroll(&self, enabled: bool);
+
+    /// Sets the look of the titlebar that glazier draws when the compositor won't, or turns it
+    /// off.
+    ///
+    /// Some Wayland compositors, like GNOME's, leave decorating windows to the application. On
+    /// those, glazier draws a minimal titlebar above the window's content, with the title and
+    /// minimize, maximize and close buttons, and lets the window be moved by its titlebar and
+    /// resized by its edges. It uses [`DecorationTheme::default`] until this is called.
+    ///
+    /// Applications that draw their own titlebar when [`WinHandler::decoration_mode_changed`]
+    /// reports [`DecorationMode::ClientSide`] should pass `None`. This does nothing on X11,
+    /// where the window manager always draws the decorations.
+    ///
+    /// [`WinHandler::decoration_mode_changed`]: crate::WinHandler::decoration_mode_changed
+    /// [`DecorationMode::ClientSide`]: crate::DecorationMode::ClientSide
+    fn set_client_decorations(&self, theme: Option<DecorationTheme>);
+}
+
+/// The look of the decorations that glazier draws on Wayland.
+///
+/// See [`WindowHandleExt::set_client_decorations`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecorationTheme {
+    /// The height of the titlebar, in display points.
+    pub titlebar_height: f64,
+    /// The size of the title, in display points.
+    pub font_size: f64,
+    /// The background of the titlebar while the window is focused.
+    pub titlebar: SystemColor,
+    /// The background of the titlebar while the window isn't focused.
+    pub titlebar_inactive: SystemColor,
+    /// The title text.
+    pub title: SystemColor,
+    /// The symbols on the minimize, maximize and close buttons.
+    pub button: SystemColor,
+    /// The background of the minimize and maximize buttons under the mouse.
+    pub button_hover: SystemColor,
+    /// The background of the close button under the mouse.
+    pub close_hover: SystemColor,
+}
+
+impl Default for DecorationTheme {
+    /// Colors close to GNOME's light theme.
+    fn default() -> Self {
+        DecorationTheme {
+            titlebar_height: 32.0,
+            font_size: 13.0,
+            titlebar: SystemColor::rgb(0xeb, 0xeb, 0xeb),
+            titlebar_inactive: SystemColor::rgb(0xfa, 0xfa, 0xfa),
+            title: SystemColor::rgb(0x2e, 0x34, 0x36),
+            button: SystemColor::rgb(0x2e, 0x34, 0x36),
+            button_hover: SystemColor::rgb(0xd6, 0xd6, 0xd6),
+            close_hover: SystemColor::rgb(0xe0, 0x1b, 0x24),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -32,7 +164,6 @@ mod test {
 
     use super::*;
     use static_assertions as sa;
-    // TODO: impl ApplicationExt for wayland
-    #[cfg(not(feature = "wayland"))]
     sa::assert_impl_all!(Application: ApplicationExt);
+    sa::assert_impl_all!(crate::WindowHandle: WindowHandleExt);
 }
@@ -14,6 +14,8 @@
 
 //! macOS specific extensions.
 
+use crate::{Error, MouseEvent};
+
 /// macOS specific extensions to [`Application`]
 ///
 /// [`Application`]: crate::Application
@@ -28,6 +30,65 @@ pub trait ApplicationExt {
     ///
     /// On platforms with no global application menu, this has no effect.
     fn set_menu(&self, menu: crate::Menu);
+
+    /// Start observing pointer movement and clicks anywhere on the system, including over
+    /// other applications' windows.
+    ///
+    /// This is intended for tools like screen annotators and presentation pointers. The
+    /// callback is not able to modify or swallow the events. Events that are delivered to
+    /// this application's own windows are *not* reported; use the [`WinHandler`] for those.
+    ///
+    /// The monitor stays active until the returned [`GlobalPointerMonitor`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Monitoring requires the user to grant the application accessibility permissions. If they
+    /// haven't been granted yet, this shows the system prompt asking for them and returns an
+    /// error; the application should call this again once the user has responded.
+    ///
+    /// [`WinHandler`]: crate::WinHandler
+    fn add_global_pointer_monitor(
+        &self,
+        callback: Box<dyn FnMut(GlobalPointerEvent)>,
+    ) -> Result<GlobalPointerMonitor, Error>;
+}
+
+/// A pointer event observed by a [`GlobalPointerMonitor`].
+///
+/// The `pos` of the [`MouseEvent`] is in display points, relative to the top left corner of
+/// the virtual screen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlobalPointerEvent {
+    /// The pointer moved, possibly with buttons held down.
+    Move(MouseEvent),
+    /// A mouse button was pressed.
+    Down(MouseEvent),
+    /// A mouse button was released.
+    Up(MouseEvent),
+}
+
+/// An active system-wide pointer monitor.
+///
+/// See [`ApplicationExt::add_global_pointer_monitor`]. Dropping this stops the monitor.
+pub struct GlobalPointerMonitor {
+    remove: Option<Box<dyn FnOnce()>>,
+}
+
+impl GlobalPointerMonitor {
+    #[allow(dead_code)]
+    pub(crate) fn new(remove: impl FnOnce() + 'static) -> GlobalPointerMonitor {
+        GlobalPointerMonitor {
+            remove: Some(Box::new(remove)),
+        }
+    }
+}
+
+impl Drop for GlobalPointerMonitor {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove.take() {
+            remove();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -16,9 +16,18 @@
 
 #[cfg(any(
     doc,
-    any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+    all(
+        not(feature = "headless"),
+        any(target_os = "freebsd", target_os = "linux", target_os = "openbsd")
+    )
 ))]
 pub mod linux;
 
-#[cfg(any(doc, target_os = "macos"))]
+#[cfg(any(doc, all(not(feature = "headless"), target_os = "macos")))]
 pub mod mac;
+
+#[cfg(any(doc, all(not(feature = "headless"), target_os = "windows")))]
+pub mod windows;
+
+#[cfg(feature = "headless")]
+pub mod headless;
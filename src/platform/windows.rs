@@ -0,0 +1,123 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Windows specific extensions.
+
+use crate::kurbo::Rect;
+
+/// How windows are scaled to match the display's DPI.
+///
+/// See [`ApplicationExt::set_dpi_awareness`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DpiMode {
+    /// Windows are told the DPI of the monitor they are on, and are notified when it changes.
+    /// This is the default.
+    PerMonitorV2,
+    /// Windows are rendered for the DPI of the primary monitor, and the system stretches them
+    /// when they are shown on a monitor with a different DPI.
+    System,
+    /// Windows are rendered at 96 DPI, and the system stretches them to the monitor's DPI.
+    Unaware,
+}
+
+/// Windows specific extensions to [`Application`]
+///
+/// [`Application`]: crate::Application
+pub trait ApplicationExt {
+    /// Set how windows created on this thread from now on are scaled.
+    ///
+    /// glazier makes the process per-monitor DPI aware by default. Plugins whose host uses a
+    /// different mode can call this, before creating any windows, to match the host. It only
+    /// affects the calling thread, so it doesn't change the host's own windows and doesn't
+    /// need an application manifest.
+    ///
+    /// Returns `false` if the mode couldn't be set. This requires Windows 10 version 1607
+    /// or newer.
+    fn set_dpi_awareness(&self, mode: DpiMode) -> bool;
+}
+
+/// Windows specific extensions to [`WindowBuilder`]
+///
+/// [`WindowBuilder`]: crate::WindowBuilder
+pub trait WindowBuilderExt {
+    /// Allow lower-integrity processes to drop files on, and send `WM_COPYDATA` to, this window.
+    ///
+    /// When the application runs elevated (e.g. "Run as administrator"), User Interface
+    /// Privilege Isolation blocks these messages from ordinary processes such as Explorer, and
+    /// drag-and-drop silently stops working. Setting this adds the window message filter
+    /// exceptions needed to let them through.
+    ///
+    /// Only enable this if the window is prepared to handle data from untrusted processes.
+    /// It has no effect when the application isn't elevated.
+    fn allow_elevated_drop(&mut self, allow: bool);
+}
+
+/// An image shown by the taskbar in place of a window's contents.
+///
+/// See [`WindowHandleExt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels, row by row from the top, as premultiplied RGBA with 8 bits per channel.
+    pub pixels: Vec<u8>,
+}
+
+/// Windows specific extensions to [`WindowHandle`]
+///
+/// By default the taskbar's thumbnail and peek preview show a scaled down capture of the
+/// whole window. Applications whose window is mostly a large canvas can use the thumbnail
+/// methods to show something more recognizable instead.
+///
+/// [`WindowHandle`]: crate::WindowHandle
+pub trait WindowHandleExt {
+    /// Set the image shown in the window's taskbar thumbnail.
+    ///
+    /// The image is scaled down to fit the thumbnail, keeping its aspect ratio. `None`
+    /// goes back to showing the window's contents.
+    fn set_thumbnail(&self, bitmap: Option<ThumbnailBitmap>);
+
+    /// Set the image shown in place of the window while the user hovers over its thumbnail
+    /// (Aero Peek).
+    ///
+    /// The image is drawn at the top left of the window's client area, at its actual size.
+    /// `None` goes back to showing the window's contents.
+    ///
+    /// While only one of the thumbnail and peek preview is set, its image is used for both.
+    fn set_peek_preview(&self, bitmap: Option<ThumbnailBitmap>);
+
+    /// Only show the part of the window inside `rect`, in display points, in its taskbar
+    /// thumbnail. `None` shows the whole window.
+    ///
+    /// This has no effect while a thumbnail image is set, or before the window is shown.
+    fn set_thumbnail_clip(&self, rect: Option<Rect>);
+
+    /// Turn the system's pen gestures on or off for this window. They are on by default.
+    ///
+    /// These are pressing and holding the pen to right-click, flicks, and the ripples shown
+    /// where the pen taps. Drawing applications that interpret the pen themselves usually
+    /// want them off, so that holding the pen still doesn't turn into a right-click.
+    fn set_pen_gestures_enabled(&self, enabled: bool);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Application, WindowBuilder, WindowHandle};
+
+    use super::*;
+    use static_assertions as sa;
+    sa::assert_impl_all!(Application: ApplicationExt);
+    sa::assert_impl_all!(WindowBuilder: WindowBuilderExt);
+    sa::assert_impl_all!(WindowHandle: WindowHandleExt);
+}
@@ -0,0 +1,1000 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording input, and replaying it later.
+//!
+//! [`RecordHandler`] wraps an application's [`WinHandler`] and writes every callback it gets
+//! to a file, one line each, with the time since the recording started. Input and window
+//! changes are written with their arguments, so that a [`Recording`] of them can be fed back
+//! to the application on the headless backend, reproducing a bug without the machine it
+//! happened on.
+//!
+//! ```no_run
+//! # use glazier::{Application, WinHandler, WindowBuilder};
+//! # use glazier::record::RecordHandler;
+//! # fn record(app: Application, handler: Box<dyn WinHandler>) -> std::io::Result<()> {
+//! let handler = RecordHandler::create(handler, "input.rec")?;
+//! let mut builder = WindowBuilder::new(app);
+//! builder.set_handler(Box::new(handler));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The recording is plain text, so it can be trimmed or edited by hand:
+//!
+//! ```text
+//! glazier-recording 2
+//! 0.000000 size 500 400
+//! 0.531250 mouse_move 120.5 80 none none 0 0 false lines 0 0 none hardware
+//! 0.612003 mouse_down 120.5 80 left left 8 2 false lines 0 0 none hardware
+//! 0.702110 key_down KeyA standard 0 false 'a
+//! 0.750000 timer 3
+//! ```
+//!
+//! Mouse events are written in full: the position, the button, the buttons held down, the
+//! modifiers, the click count, whether the click focused the window, the wheel delta, the
+//! momentum phase and the source. The mouse moves that were merged into an event follow it,
+//! each after a `|`.
+//!
+//! Callbacks that the application causes itself, like timers, idle callbacks and paints, are
+//! written with their name for context only. When replaying, the application requests them
+//! again.
+
+use std::any::Any;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use instant::Instant;
+use tracing::warn;
+
+use crate::keyboard::{CompositionEvent, KeyEvent};
+use crate::kurbo::{Insets, Point, Size, Vec2};
+use crate::mouse::{
+    ForceClick, InputSource, MomentumPhase, MouseButton, MouseButtons, MouseEvent, ScrollDelta,
+};
+use crate::text::InputHandler;
+use crate::window::{
+    DecorationMode, FileDialogToken, FocusDirection, FrameInfo, IdleToken, PaintReason, TimerToken,
+    WinHandler, WindowHandle,
+};
+use crate::{
+    Code, DragData, DropEffect, FileInfo, HighContrastColors, KbKey, KeyState, KeyboardLayout,
+    LocaleInfo, Location, Modifiers, MonitorId, Region, Scale, TextFieldToken,
+};
+
+/// The first line of every recording.
+const HEADER: &str = "glazier-recording 2";
+
+/// A [`WinHandler`] that writes down every callback before passing it on.
+///
+/// See the [module documentation](self) for details.
+pub struct RecordHandler {
+    inner: Box<dyn WinHandler>,
+    /// Where the recording goes, or `None` after a write failed.
+    out: Option<Box<dyn Write>>,
+    start: Instant,
+}
+
+impl RecordHandler {
+    /// Wrap `inner`, writing the recording to `out`.
+    ///
+    /// The clock starts now. Write errors are logged, and end the recording.
+    pub fn new(inner: Box<dyn WinHandler>, out: impl Write + 'static) -> RecordHandler {
+        let mut handler = RecordHandler {
+            inner,
+            out: Some(Box::new(out)),
+            start: Instant::now(),
+        };
+        handler.write(format_args!("{}", HEADER));
+        handler
+    }
+
+    /// Wrap `inner`, writing the recording to a new file at `path`.
+    ///
+    /// Each line is written as soon as it is complete, so the recording survives a crash.
+    pub fn create(inner: Box<dyn WinHandler>, path: impl AsRef<Path>) -> io::Result<RecordHandler> {
+        let file = File::create(path)?;
+        Ok(RecordHandler::new(inner, LineWriter::new(file)))
+    }
+
+    fn write(&mut self, line: fmt::Arguments) {
+        if let Some(out) = &mut self.out {
+            if let Err(e) = writeln!(out, "{}", line) {
+                warn!("failed to write the recording, stopping: {}", e);
+                self.out = None;
+            }
+        }
+    }
+
+    fn record(&mut self, event: fmt::Arguments) {
+        let time = self.start.elapsed().as_secs_f64();
+        self.write(format_args!("{:.6} {}", time, event));
+    }
+
+    fn record_key(&mut self, event: &KeyEvent) {
+        let name = match event.state {
+            KeyState::Down => "key_down",
+            KeyState::Up => "key_up",
+        };
+        self.record(format_args!(
+            "{} {} {} {} {} {}",
+            name,
+            event.code,
+            location_name(event.location),
+            event.mods.raw().bits(),
+            event.repeat,
+            KeyName(&event.key)
+        ));
+    }
+}
+
+impl WinHandler for RecordHandler {
+    fn connect(&mut self, handle: &WindowHandle) {
+        self.record(format_args!("connect"));
+        self.inner.connect(handle)
+    }
+
+    fn size(&mut self, size: Size) {
+        self.record(format_args!("size {} {}", size.width, size.height));
+        self.inner.size(size)
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.record(format_args!("safe_area_changed"));
+        self.inner.safe_area_changed(insets)
+    }
+
+    fn scale(&mut self, scale: Scale) {
+        self.record(format_args!("scale {} {}", scale.x(), scale.y()));
+        self.inner.scale(scale)
+    }
+
+    fn text_scale_factor_changed(&mut self, factor: f64) {
+        self.record(format_args!("text_scale_factor_changed"));
+        self.inner.text_scale_factor_changed(factor)
+    }
+
+    fn high_contrast_changed(&mut self, colors: Option<HighContrastColors>) {
+        self.record(format_args!("high_contrast_changed"));
+        self.inner.high_contrast_changed(colors)
+    }
+
+    fn decoration_mode_changed(&mut self, mode: DecorationMode) {
+        self.record(format_args!("decoration_mode_changed"));
+        self.inner.decoration_mode_changed(mode)
+    }
+
+    fn prepare_frame(&mut self, info: FrameInfo) {
+        self.record(format_args!("prepare_frame"));
+        self.inner.prepare_frame(info)
+    }
+
+    fn prepare_paint(&mut self) {
+        self.record(format_args!("prepare_paint"));
+        self.inner.prepare_paint()
+    }
+
+    fn paint(&mut self, invalid: &Region, reason: PaintReason) {
+        self.record(format_args!("paint"));
+        self.inner.paint(invalid, reason)
+    }
+
+    fn frame_missed(&mut self, count: u32) {
+        self.record(format_args!("frame_missed"));
+        self.inner.frame_missed(count)
+    }
+
+    fn refresh_interval_changed(&mut self, interval: Duration) {
+        self.record(format_args!("refresh_interval_changed"));
+        self.inner.refresh_interval_changed(interval)
+    }
+
+    fn outputs_changed(&mut self, outputs: &[MonitorId]) {
+        self.record(format_args!("outputs_changed"));
+        self.inner.outputs_changed(outputs)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_tree(&mut self) -> accesskit::TreeUpdate {
+        self.record(format_args!("accesskit_tree"));
+        self.inner.accesskit_tree()
+    }
+
+    fn rebuild_resources(&mut self) {
+        self.record(format_args!("rebuild_resources"));
+        self.inner.rebuild_resources()
+    }
+
+    fn command(&mut self, id: u32) {
+        self.record(format_args!("command"));
+        self.inner.command(id)
+    }
+
+    fn save_as(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.record(format_args!("save_as"));
+        self.inner.save_as(token, file)
+    }
+
+    fn open_file(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.record(format_args!("open_file"));
+        self.inner.open_file(token, file)
+    }
+
+    fn open_files(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.record(format_args!("open_files"));
+        self.inner.open_files(token, files)
+    }
+
+    fn key_down(&mut self, event: KeyEvent) -> bool {
+        self.record_key(&event);
+        self.inner.key_down(event)
+    }
+
+    fn focus_navigation(&mut self, direction: FocusDirection) -> bool {
+        self.record(format_args!("focus_navigation"));
+        self.inner.focus_navigation(direction)
+    }
+
+    fn key_up(&mut self, event: KeyEvent) {
+        self.record_key(&event);
+        self.inner.key_up(event)
+    }
+
+    fn modifiers_changed(&mut self, mods: Modifiers) {
+        self.record(format_args!("modifiers_changed"));
+        self.inner.modifiers_changed(mods)
+    }
+
+    fn input_language_changed(&mut self, layout: KeyboardLayout) {
+        self.record(format_args!("input_language_changed"));
+        self.inner.input_language_changed(layout)
+    }
+
+    fn locale_changed(&mut self, info: LocaleInfo) {
+        self.record(format_args!("locale_changed"));
+        self.inner.locale_changed(info)
+    }
+
+    fn composition(&mut self, event: CompositionEvent) {
+        self.record(format_args!("composition"));
+        self.inner.composition(event)
+    }
+
+    fn acquire_input_lock(
+        &mut self,
+        token: TextFieldToken,
+        mutable: bool,
+    ) -> Box<dyn InputHandler> {
+        self.record(format_args!("acquire_input_lock"));
+        self.inner.acquire_input_lock(token, mutable)
+    }
+
+    fn release_input_lock(&mut self, token: TextFieldToken) {
+        self.record(format_args!("release_input_lock"));
+        self.inner.release_input_lock(token)
+    }
+
+    fn wheel(&mut self, event: &MouseEvent) {
+        self.record(format_args!("wheel {}", MouseFields(event)));
+        self.inner.wheel(event)
+    }
+
+    fn zoom(&mut self, delta: f64) {
+        self.record(format_args!("zoom"));
+        self.inner.zoom(delta)
+    }
+
+    fn force_click(&mut self, event: &ForceClick) {
+        self.record(format_args!("force_click"));
+        self.inner.force_click(event)
+    }
+
+    fn mouse_move(&mut self, event: &MouseEvent) {
+        self.record(format_args!("mouse_move {}", MouseFields(event)));
+        self.inner.mouse_move(event)
+    }
+
+    fn mouse_down(&mut self, event: &MouseEvent) {
+        self.record(format_args!("mouse_down {}", MouseFields(event)));
+        self.inner.mouse_down(event)
+    }
+
+    fn mouse_up(&mut self, event: &MouseEvent) {
+        self.record(format_args!("mouse_up {}", MouseFields(event)));
+        self.inner.mouse_up(event)
+    }
+
+    fn mouse_leave(&mut self) {
+        self.record(format_args!("mouse_leave"));
+        self.inner.mouse_leave()
+    }
+
+    fn raw_pointer_motion(&mut self, delta: Vec2) {
+        self.record(format_args!("raw_pointer_motion"));
+        self.inner.raw_pointer_motion(delta)
+    }
+
+    fn pointer_lock_lost(&mut self) {
+        self.record(format_args!("pointer_lock_lost"));
+        self.inner.pointer_lock_lost()
+    }
+
+    fn drag_enter(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.record(format_args!("drag_enter"));
+        self.inner.drag_enter(pos, data)
+    }
+
+    fn drag_over(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.record(format_args!("drag_over"));
+        self.inner.drag_over(pos, data)
+    }
+
+    fn drag_leave(&mut self) {
+        self.record(format_args!("drag_leave"));
+        self.inner.drag_leave()
+    }
+
+    fn drag_drop(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.record(format_args!("drag_drop"));
+        self.inner.drag_drop(pos, data)
+    }
+
+    fn timer(&mut self, token: TimerToken) {
+        self.record(format_args!("timer {}", token.into_raw()));
+        self.inner.timer(token)
+    }
+
+    fn got_focus(&mut self) {
+        self.record(format_args!("got_focus"));
+        self.inner.got_focus()
+    }
+
+    fn lost_focus(&mut self) {
+        self.record(format_args!("lost_focus"));
+        self.inner.lost_focus()
+    }
+
+    fn request_close(&mut self) {
+        self.record(format_args!("request_close"));
+        self.inner.request_close()
+    }
+
+    fn destroy(&mut self) {
+        self.record(format_args!("destroy"));
+        if let Some(out) = &mut self.out {
+            if let Err(e) = out.flush() {
+                warn!("failed to write the recording: {}", e);
+            }
+        }
+        self.inner.destroy()
+    }
+
+    fn idle(&mut self, token: IdleToken) {
+        self.record(format_args!("idle"));
+        self.inner.idle(token)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_action(&mut self, request: accesskit::ActionRequest) {
+        self.record(format_args!("accesskit_action"));
+        self.inner.accesskit_action(request)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self.inner.as_any()
+    }
+}
+
+/// An event in a [`Recording`] that can be replayed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedEvent {
+    Size(Size),
+    Scale(Scale),
+    MouseMove(MouseEvent),
+    MouseDown(MouseEvent),
+    MouseUp(MouseEvent),
+    Wheel(MouseEvent),
+    MouseLeave,
+    /// A key press or release, depending on the event's `state`.
+    Key(KeyEvent),
+    /// The window got the keyboard focus (`true`), or lost it.
+    Focus(bool),
+    RequestClose,
+}
+
+/// The replayable events of a recording made by [`RecordHandler`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    events: Vec<(Duration, RecordedEvent)>,
+}
+
+/// The reason a recording couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line with the problem, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Recording {
+    /// Reads a recording from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Recording, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(text.parse()?)
+    }
+
+    /// The events, with the time since the start of the recording that they happened at.
+    pub fn events(&self) -> &[(Duration, RecordedEvent)] {
+        &self.events
+    }
+
+    /// Feeds the events to a window of the headless backend, at the same pace as they were
+    /// recorded.
+    ///
+    /// The window's timers, idle callbacks and paints run in between, as they would in the
+    /// event loop.
+    #[cfg(feature = "headless")]
+    pub fn replay(&self, injector: &crate::platform::headless::EventInjector) {
+        let start = Instant::now();
+        for (time, event) in &self.events {
+            let due = start + *time;
+            loop {
+                injector.run_pending();
+                let now = Instant::now();
+                if now >= due {
+                    break;
+                }
+                std::thread::sleep((due - now).min(Duration::from_millis(1)));
+            }
+            match event {
+                RecordedEvent::Size(size) => injector.resize(*size),
+                RecordedEvent::Scale(scale) => injector.set_scale(*scale),
+                RecordedEvent::MouseMove(event) => {
+                    injector.replay_mouse(event, |h, event| h.mouse_move(event));
+                }
+                RecordedEvent::MouseDown(event) => {
+                    injector.replay_mouse(event, |h, event| h.mouse_down(event));
+                }
+                RecordedEvent::MouseUp(event) => {
+                    injector.replay_mouse(event, |h, event| h.mouse_up(event));
+                }
+                RecordedEvent::Wheel(event) => {
+                    injector.replay_mouse(event, |h, event| h.wheel(event));
+                }
+                RecordedEvent::MouseLeave => injector.mouse_leave(),
+                RecordedEvent::Key(event) if event.state == KeyState::Down => {
+                    injector.key_down(event.clone());
+                }
+                RecordedEvent::Key(event) => injector.key_up(event.clone()),
+                RecordedEvent::Focus(focused) => injector.set_focused(*focused),
+                RecordedEvent::RequestClose => injector.request_close(),
+            }
+        }
+        injector.run_pending();
+    }
+}
+
+impl FromStr for Recording {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Recording, ParseError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => {
+                return Err(ParseError {
+                    line: 1,
+                    message: format!("expected `{}`", HEADER),
+                })
+            }
+        }
+        let mut events = Vec::new();
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = parse_line(line).map_err(|message| ParseError {
+                line: i + 1,
+                message,
+            })?;
+            if let Some(event) = event {
+                events.push(event);
+            }
+        }
+        Ok(Recording { events })
+    }
+}
+
+/// Parses one line of a recording, returning `None` for callbacks that aren't replayed.
+fn parse_line(line: &str) -> Result<Option<(Duration, RecordedEvent)>, String> {
+    let mut fields = line.splitn(2, ' ');
+    let time: f64 = parse(fields.next())?;
+    if !time.is_finite() || time < 0.0 {
+        return Err(format!("invalid time {}", time));
+    }
+    let mut rest = fields.next().unwrap_or("");
+    let mut next = || -> Option<&str> {
+        let (field, tail) = match rest.find(' ') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+        rest = tail;
+        Some(field).filter(|field| !field.is_empty())
+    };
+    let name = next().ok_or("missing callback name")?;
+    let event = match name {
+        "size" => RecordedEvent::Size(Size::new(parse(next())?, parse(next())?)),
+        "scale" => RecordedEvent::Scale(Scale::new(parse(next())?, parse(next())?)),
+        "mouse_move" => RecordedEvent::MouseMove(parse_mouse(rest)?),
+        "mouse_down" => RecordedEvent::MouseDown(parse_mouse(rest)?),
+        "mouse_up" => RecordedEvent::MouseUp(parse_mouse(rest)?),
+        "wheel" => RecordedEvent::Wheel(parse_mouse(rest)?),
+        "mouse_leave" => RecordedEvent::MouseLeave,
+        "key_down" | "key_up" => {
+            let code: Code = parse(next())?;
+            let location = next().ok_or("missing location")?;
+            let location =
+                parse_location(location).ok_or_else(|| format!("bad location {}", location))?;
+            let mods = parse(next())?;
+            let mods = keyboard_types::Modifiers::from_bits(mods)
+                .ok_or_else(|| format!("bad modifiers {}", mods))?;
+            let repeat = parse(next())?;
+            // The key is the rest of the line, because a character key can be a space.
+            let key = parse_key(rest).ok_or_else(|| format!("bad key {}", rest))?;
+            RecordedEvent::Key(KeyEvent {
+                state: if name == "key_down" {
+                    KeyState::Down
+                } else {
+                    KeyState::Up
+                },
+                key,
+                code,
+                location,
+                mods: Modifiers::from_raw(mods),
+                repeat,
+                ..Default::default()
+            })
+        }
+        "got_focus" => RecordedEvent::Focus(true),
+        "lost_focus" => RecordedEvent::Focus(false),
+        "request_close" => RecordedEvent::RequestClose,
+        _ => return Ok(None),
+    };
+    Ok(Some((Duration::from_secs_f64(time), event)))
+}
+
+fn parse<T: FromStr>(field: Option<&str>) -> Result<T, String> {
+    let field = field.ok_or("missing field")?;
+    field.parse().map_err(|_| format!("bad field {}", field))
+}
+
+/// Writes a mouse event so that [`parse_mouse`] can read it back.
+struct MouseFields<'a>(&'a MouseEvent);
+
+impl fmt::Display for MouseFields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let event = self.0;
+        let (kind, delta) = match event.wheel_delta {
+            ScrollDelta::Lines(delta) => ("lines", delta),
+            ScrollDelta::Pixels(delta) => ("pixels", delta),
+        };
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} {} {} {} {}",
+            event.pos.x,
+            event.pos.y,
+            button_name(event.button),
+            ButtonsName(event.buttons),
+            event.mods.raw().bits(),
+            event.count,
+            event.focus,
+            kind,
+            delta.x,
+            delta.y,
+            momentum_name(event.momentum_phase),
+            source_name(event.source)
+        )?;
+        for merged in &event.coalesced {
+            write!(f, " | {}", MouseFields(merged))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a mouse event written by [`MouseFields`].
+fn parse_mouse(s: &str) -> Result<MouseEvent, String> {
+    let mut events = s.split(" | ").map(parse_mouse_fields);
+    let mut event = events.next().ok_or("missing mouse event")??;
+    event.coalesced = events.collect::<Result<_, _>>()?;
+    Ok(event)
+}
+
+fn parse_mouse_fields(s: &str) -> Result<MouseEvent, String> {
+    let mut fields = s.split(' ');
+    let mut next = || fields.next().filter(|field| !field.is_empty());
+    let pos = Point::new(parse(next())?, parse(next())?);
+    let button = next().ok_or("missing button")?;
+    let button = parse_button(button).ok_or_else(|| format!("bad button {}", button))?;
+    let buttons = next().ok_or("missing buttons")?;
+    let buttons = parse_buttons(buttons).ok_or_else(|| format!("bad buttons {}", buttons))?;
+    let mods = parse(next())?;
+    let mods = keyboard_types::Modifiers::from_bits(mods)
+        .ok_or_else(|| format!("bad modifiers {}", mods))?;
+    let count = parse(next())?;
+    let focus = parse(next())?;
+    let kind = next();
+    let delta = Vec2::new(parse(next())?, parse(next())?);
+    let wheel_delta = match kind {
+        Some("lines") => ScrollDelta::Lines(delta),
+        Some("pixels") => ScrollDelta::Pixels(delta),
+        _ => return Err("expected `lines` or `pixels`".into()),
+    };
+    let momentum = next().ok_or("missing momentum phase")?;
+    let momentum_phase =
+        parse_momentum(momentum).ok_or_else(|| format!("bad momentum phase {}", momentum))?;
+    let source = next().ok_or("missing source")?;
+    let source = parse_source(source).ok_or_else(|| format!("bad source {}", source))?;
+    Ok(MouseEvent {
+        pos,
+        buttons,
+        mods: Modifiers::from_raw(mods),
+        count,
+        focus,
+        button,
+        wheel_delta,
+        momentum_phase,
+        source,
+        coalesced: Vec::new(),
+    })
+}
+
+fn button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::None => "none",
+        MouseButton::Left => "left",
+        MouseButton::Right => "right",
+        MouseButton::Middle => "middle",
+        MouseButton::X1 => "x1",
+        MouseButton::X2 => "x2",
+    }
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    Some(match name {
+        "none" => MouseButton::None,
+        "left" => MouseButton::Left,
+        "right" => MouseButton::Right,
+        "middle" => MouseButton::Middle,
+        "x1" => MouseButton::X1,
+        "x2" => MouseButton::X2,
+        _ => return None,
+    })
+}
+
+const BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left,
+    MouseButton::Right,
+    MouseButton::Middle,
+    MouseButton::X1,
+    MouseButton::X2,
+];
+
+/// Writes a set of buttons as their names joined by commas, or `none`.
+struct ButtonsName(MouseButtons);
+
+impl fmt::Display for ButtonsName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("none");
+        }
+        let mut names = BUTTONS.iter().filter(|b| self.0.contains(**b));
+        if let Some(first) = names.next() {
+            f.write_str(button_name(*first))?;
+        }
+        for button in names {
+            write!(f, ",{}", button_name(*button))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_buttons(names: &str) -> Option<MouseButtons> {
+    if names == "none" {
+        return Some(MouseButtons::new());
+    }
+    names
+        .split(',')
+        .try_fold(MouseButtons::new(), |buttons, name| {
+            match parse_button(name)? {
+                MouseButton::None => None,
+                button => Some(buttons.with(button)),
+            }
+        })
+}
+
+fn momentum_name(phase: MomentumPhase) -> &'static str {
+    match phase {
+        MomentumPhase::None => "none",
+        MomentumPhase::Began => "began",
+        MomentumPhase::Changed => "changed",
+        MomentumPhase::Ended => "ended",
+    }
+}
+
+fn parse_momentum(name: &str) -> Option<MomentumPhase> {
+    Some(match name {
+        "none" => MomentumPhase::None,
+        "began" => MomentumPhase::Began,
+        "changed" => MomentumPhase::Changed,
+        "ended" => MomentumPhase::Ended,
+        _ => return None,
+    })
+}
+
+fn source_name(source: InputSource) -> &'static str {
+    match source {
+        InputSource::Hardware => "hardware",
+        InputSource::Injected => "injected",
+        InputSource::Remote => "remote",
+    }
+}
+
+fn parse_source(name: &str) -> Option<InputSource> {
+    Some(match name {
+        "hardware" => InputSource::Hardware,
+        "injected" => InputSource::Injected,
+        "remote" => InputSource::Remote,
+        _ => return None,
+    })
+}
+
+fn location_name(location: Location) -> &'static str {
+    match location {
+        Location::Standard => "standard",
+        Location::Left => "left",
+        Location::Right => "right",
+        Location::Numpad => "numpad",
+    }
+}
+
+fn parse_location(name: &str) -> Option<Location> {
+    Some(match name {
+        "standard" => Location::Standard,
+        "left" => Location::Left,
+        "right" => Location::Right,
+        "numpad" => Location::Numpad,
+        _ => return None,
+    })
+}
+
+/// Writes a key so that [`parse_key`] can read it back.
+///
+/// Character keys start with a `'`, so that they can't be mistaken for named ones, and have
+/// their backslashes and line breaks escaped.
+struct KeyName<'a>(&'a KbKey);
+
+impl fmt::Display for KeyName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            KbKey::Character(s) => {
+                f.write_str("'")?;
+                for c in s.chars() {
+                    match c {
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                Ok(())
+            }
+            key => write!(f, "{}", key),
+        }
+    }
+}
+
+fn parse_key(s: &str) -> Option<KbKey> {
+    let s = match s.strip_prefix('\'') {
+        Some(s) => s,
+        None => {
+            return s
+                .parse()
+                .ok()
+                .filter(|key| !matches!(key, KbKey::Character(_)))
+        }
+    };
+    let mut key = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        key.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(KbKey::Character(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct Nothing;
+
+    impl WinHandler for Nothing {
+        fn connect(&mut self, _: &WindowHandle) {}
+        fn prepare_paint(&mut self) {}
+        fn paint(&mut self, _: &Region, _: PaintReason) {}
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let out = Shared::default();
+        let mut handler = RecordHandler::new(Box::new(Nothing), out.clone());
+        let mouse = shift_double_click();
+        let space = KeyEvent::for_test(Modifiers::SHIFT, " ");
+        let enter = KeyEvent {
+            code: Code::Enter,
+            ..KeyEvent::for_test(Modifiers::empty(), KbKey::Enter)
+        };
+        handler.size(Size::new(500.0, 400.0));
+        handler.mouse_down(&mouse);
+        handler.timer(TimerToken::from_raw(3));
+        handler.wheel(&mouse);
+        handler.key_down(space.clone());
+        handler.key_down(enter.clone());
+        handler.request_close();
+
+        let text = String::from_utf8(out.0.borrow().clone()).unwrap();
+        let recording: Recording = text.parse().unwrap();
+        let events: Vec<_> = recording.events().iter().map(|(_, e)| e.clone()).collect();
+        assert_eq!(
+            events,
+            vec![
+                RecordedEvent::Size(Size::new(500.0, 400.0)),
+                RecordedEvent::MouseDown(mouse.clone()),
+                RecordedEvent::Wheel(mouse),
+                RecordedEvent::Key(space),
+                RecordedEvent::Key(enter),
+                RecordedEvent::RequestClose,
+            ]
+        );
+        assert_eq!("0.1 key_down".parse::<Recording>().unwrap_err().line, 1);
+    }
+
+    fn shift_double_click() -> MouseEvent {
+        let merged = MouseEvent {
+            pos: Point::new(9.0, 19.5),
+            buttons: MouseButtons::new().with(MouseButton::Left),
+            mods: Modifiers::SHIFT,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: ScrollDelta::ZERO,
+            momentum_phase: MomentumPhase::None,
+            source: InputSource::Remote,
+            coalesced: Vec::new(),
+        };
+        MouseEvent {
+            pos: Point::new(10.5, 20.0),
+            buttons: MouseButtons::new()
+                .with(MouseButton::Left)
+                .with(MouseButton::X1),
+            mods: Modifiers::SHIFT | Modifiers::CONTROL,
+            count: 2,
+            focus: true,
+            button: MouseButton::Left,
+            wheel_delta: ScrollDelta::Pixels(Vec2::new(0.25, -3.0)),
+            momentum_phase: MomentumPhase::Changed,
+            source: InputSource::Remote,
+            coalesced: vec![merged],
+        }
+    }
+
+    /// Keeps the mouse events that a handler gets.
+    #[cfg(feature = "headless")]
+    struct MouseLog(Rc<RefCell<Vec<(&'static str, MouseEvent)>>>);
+
+    #[cfg(feature = "headless")]
+    impl WinHandler for MouseLog {
+        fn connect(&mut self, _: &WindowHandle) {}
+        fn prepare_paint(&mut self) {}
+        fn paint(&mut self, _: &Region, _: PaintReason) {}
+        fn mouse_move(&mut self, event: &MouseEvent) {
+            self.0.borrow_mut().push(("mouse_move", event.clone()));
+        }
+        fn mouse_down(&mut self, event: &MouseEvent) {
+            self.0.borrow_mut().push(("mouse_down", event.clone()));
+        }
+        fn mouse_up(&mut self, event: &MouseEvent) {
+            self.0.borrow_mut().push(("mouse_up", event.clone()));
+        }
+        fn wheel(&mut self, event: &MouseEvent) {
+            self.0.borrow_mut().push(("wheel", event.clone()));
+        }
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn replay_matches_recording() {
+        use crate::platform::headless::{with_test_app, EventInjector};
+        use crate::WindowBuilder;
+
+        let out = Shared::default();
+        let recorded = Rc::new(RefCell::new(Vec::new()));
+        let mut handler = RecordHandler::new(Box::new(MouseLog(recorded.clone())), out.clone());
+        let click = shift_double_click();
+        let release = MouseEvent {
+            count: 0,
+            buttons: MouseButtons::new(),
+            coalesced: Vec::new(),
+            ..click.clone()
+        };
+        handler.mouse_move(&click.coalesced[0]);
+        handler.mouse_down(&click);
+        handler.mouse_up(&release);
+        handler.wheel(&click);
+
+        let text = String::from_utf8(out.0.borrow().clone()).unwrap();
+        let recording: Recording = text.parse().unwrap();
+        let replayed = Rc::new(RefCell::new(Vec::new()));
+        with_test_app(|app| {
+            let mut builder = WindowBuilder::new(app.clone());
+            builder.set_handler(Box::new(MouseLog(replayed.clone())));
+            let window = builder.build().unwrap();
+            recording.replay(&EventInjector::new(&window));
+        });
+
+        assert_eq!(recorded.borrow().len(), 4);
+        assert_eq!(*replayed.borrow(), *recorded.borrow());
+    }
+}
@@ -88,6 +88,41 @@ impl Region {
         }
         self.rects.retain(|r| r.area() > 0.0)
     }
+
+    /// Updates this invalid region for the contents of `rect` moving by `delta`, as in
+    /// [`WindowHandle::scroll_region`].
+    ///
+    /// The parts of this region inside `rect` move along with the contents, and the part of
+    /// `rect` that the contents moved away from is added.
+    ///
+    /// [`WindowHandle::scroll_region`]: crate::WindowHandle::scroll_region
+    pub(crate) fn scroll(&mut self, rect: Rect, delta: Vec2) {
+        let moved: Vec<Rect> = self
+            .rects
+            .iter()
+            .map(|r| (r.intersect(rect) + delta).intersect(rect))
+            .collect();
+        for r in moved {
+            self.add_rect(r);
+        }
+        if delta.x.abs() >= rect.width() || delta.y.abs() >= rect.height() {
+            self.add_rect(rect);
+            return;
+        }
+        // The rows uncovered by the vertical motion...
+        if delta.y > 0.0 {
+            self.add_rect(Rect::new(rect.x0, rect.y0, rect.x1, rect.y0 + delta.y));
+        } else {
+            self.add_rect(Rect::new(rect.x0, rect.y1 + delta.y, rect.x1, rect.y1));
+        }
+        // ...and the columns uncovered by the horizontal motion, in the remaining rows.
+        let (y0, y1) = (rect.y0 + delta.y.max(0.0), rect.y1 + delta.y.min(0.0));
+        if delta.x > 0.0 {
+            self.add_rect(Rect::new(rect.x0, y0, rect.x0 + delta.x, y1));
+        } else {
+            self.add_rect(Rect::new(rect.x1 + delta.x, y0, rect.x1, y1));
+        }
+    }
 }
 
 impl std::ops::AddAssign<Vec2> for Region {
@@ -111,3 +146,37 @@ impl From<Rect> for Region {
         Region { rects: vec![rect] }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_uncovers_strips() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 50.0);
+
+        let mut region = Region::EMPTY;
+        region.scroll(rect, Vec2::new(0.0, -10.0));
+        assert_eq!(region.rects(), &[Rect::new(0.0, 40.0, 100.0, 50.0)]);
+
+        let mut region = Region::EMPTY;
+        region.scroll(rect, Vec2::new(5.0, 10.0));
+        assert_eq!(
+            region.rects(),
+            &[
+                Rect::new(0.0, 0.0, 100.0, 10.0),
+                Rect::new(0.0, 10.0, 5.0, 50.0)
+            ]
+        );
+
+        // Pending damage moves with the contents.
+        let mut region = Region::from(Rect::new(0.0, 20.0, 10.0, 30.0));
+        region.scroll(rect, Vec2::new(0.0, -10.0));
+        assert!(region.rects().contains(&Rect::new(0.0, 10.0, 10.0, 20.0)));
+
+        // Scrolling by more than the size replaces everything.
+        let mut region = Region::EMPTY;
+        region.scroll(rect, Vec2::new(0.0, 60.0));
+        assert_eq!(region.rects(), &[rect]);
+    }
+}
@@ -19,11 +19,34 @@ use crate::kurbo::Rect;
 use std::fmt;
 use std::fmt::Display;
 
+/// Identifies a monitor.
+///
+/// On Wayland this stays the same for as long as the monitor is connected. Elsewhere it is the
+/// monitor's position in [`Screen::get_monitors`], so it can refer to a different monitor after
+/// [`AppHandler::monitors_changed`].
+///
+/// [`AppHandler::monitors_changed`]: crate::AppHandler::monitors_changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MonitorId(u64);
+
+impl MonitorId {
+    #[allow(dead_code)]
+    pub(crate) const fn from_raw(id: u64) -> MonitorId {
+        MonitorId(id)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) const fn into_raw(self) -> u64 {
+        self.0
+    }
+}
+
 /// Monitor struct containing data about a monitor on the system
 ///
 /// Use Screen::get_monitors() to return a Vec<Monitor> of all the monitors on the system
 #[derive(Clone, Debug, PartialEq)]
 pub struct Monitor {
+    id: Option<MonitorId>,
     primary: bool,
     rect: Rect,
     // TODO: Work area, cross_platform
@@ -32,17 +55,54 @@ pub struct Monitor {
     // https://docs.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-monitorinfo
     // Unsure about x11
     work_rect: Rect,
+    scale: f64,
+    refresh_rate: Option<f64>,
+    name: Option<String>,
 }
 
 impl Monitor {
     #[allow(dead_code)]
     pub(crate) fn new(primary: bool, rect: Rect, work_rect: Rect) -> Self {
         Monitor {
+            id: None,
             primary,
             rect,
             work_rect,
+            scale: 1.0,
+            refresh_rate: None,
+            name: None,
         }
     }
+
+    #[allow(dead_code)]
+    pub(crate) fn with_id(mut self, id: MonitorId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn with_refresh_rate(mut self, refresh_rate: Option<f64>) -> Self {
+        self.refresh_rate = refresh_rate;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Returns the id of the monitor.
+    pub fn id(&self) -> MonitorId {
+        self.id.unwrap_or(MonitorId(0))
+    }
+
     /// Returns true if the monitor is the primary monitor.
     /// The primary monitor has its origin at (0, 0) in virtual screen coordinates.
     pub fn is_primary(&self) -> bool {
@@ -59,6 +119,25 @@ impl Monitor {
     pub fn virtual_work_rect(&self) -> Rect {
         self.work_rect
     }
+
+    /// Returns the scale that windows on the monitor are drawn at, as a multiple of the
+    /// platform's standard pixel density.
+    ///
+    /// This is `1.0` if the platform doesn't have a scale for each monitor.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Returns the refresh rate of the monitor, in Hz, if it is known.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.refresh_rate
+    }
+
+    /// Returns a name for the monitor that can be shown to the user, such as the name of the
+    /// connector it is plugged into or the model of the display, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 impl Display for Monitor {
@@ -77,6 +156,47 @@ impl Display for Monitor {
     }
 }
 
+/// Information about system-wide color shifting of the display, such as the
+/// "night light" mode on Windows and GNOME, Night Shift on macOS or redshift/f.lux.
+///
+/// Use [`Screen::get_color_shift`] to query it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorShift {
+    active: bool,
+    strength: Option<f64>,
+}
+
+impl ColorShift {
+    #[allow(dead_code)]
+    pub(crate) fn new(active: bool, strength: Option<f64>) -> Self {
+        ColorShift { active, strength }
+    }
+
+    /// Returns true if the display colors are currently being shifted.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns how strong the shift currently is, from `0.0` (no shift) to `1.0`
+    /// (the blue channel is removed entirely).
+    ///
+    /// This is `None` if the platform doesn't expose the strength.
+    pub fn strength(&self) -> Option<f64> {
+        self.strength
+    }
+}
+
+/// Returns the monitor that most of `rect`, in virtual screen coordinates, is on.
+#[allow(dead_code)]
+pub(crate) fn monitor_containing(rect: Rect) -> Option<Monitor> {
+    Screen::get_monitors()
+        .into_iter()
+        .map(|m| (m.virtual_rect().intersect(rect).area(), m))
+        .filter(|(area, _)| *area > 0.0)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, m)| m)
+}
+
 /// Information about the screen and monitors
 pub struct Screen {}
 impl Screen {
@@ -84,7 +204,12 @@ impl Screen {
     ///
     /// [`monitors`]: struct.Monitor.html
     pub fn get_monitors() -> Vec<Monitor> {
-        backend::screen::get_monitors()
+        let mut monitors = backend::screen::get_monitors();
+        // Backends without ids of their own number the monitors.
+        for (i, monitor) in monitors.iter_mut().enumerate() {
+            monitor.id.get_or_insert(MonitorId(i as u64));
+        }
+        monitors
     }
 
     /// Returns the bounding rectangle of the total virtual screen space in pixels.
@@ -94,4 +219,12 @@ impl Screen {
             .map(|x| x.virtual_rect())
             .fold(Rect::ZERO, |a, b| a.union(b))
     }
+
+    /// Returns whether the display colors are being shifted, for example by a
+    /// "night light" mode, so that color-critical applications can warn the user.
+    ///
+    /// Returns `None` if this can't be determined on the current platform.
+    pub fn get_color_shift() -> Option<ColorShift> {
+        backend::screen::get_color_shift()
+    }
 }
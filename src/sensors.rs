@@ -0,0 +1,70 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Device sensors, such as orientation and ambient light sensors.
+//!
+//! Sensors are opt-in: an application enables the ones it is interested in with
+//! [`Application::enable_sensor`], and readings are then delivered to
+//! [`AppHandler::sensor`].
+//!
+//! Only Linux is supported: sensors are read from iio-sensor-proxy over D-Bus, which needs
+//! the `dbus` feature. On other platforms, enabling a sensor fails.
+//!
+//! [`Application::enable_sensor`]: crate::Application::enable_sensor
+//! [`AppHandler::sensor`]: crate::AppHandler::sensor
+
+/// A kind of sensor that an application can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SensorKind {
+    /// The ambient light level around the device.
+    AmbientLight,
+    /// The coarse orientation of the device, suitable for rotating the UI.
+    Orientation,
+}
+
+/// The coarse physical orientation of a device.
+///
+/// The names describe which edge of the display is pointing up, relative
+/// to the display's natural orientation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceOrientation {
+    /// The device is in its natural orientation.
+    Normal,
+    /// The device is upside down.
+    BottomUp,
+    /// The device is rotated so that its left edge is at the top.
+    LeftUp,
+    /// The device is rotated so that its right edge is at the top.
+    RightUp,
+}
+
+/// A reading from one of the device's sensors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum SensorEvent {
+    /// The ambient light level changed, in lux.
+    AmbientLight(f64),
+    /// The device orientation changed.
+    Orientation(DeviceOrientation),
+}
+
+impl SensorEvent {
+    /// The kind of sensor that produced this event.
+    pub fn kind(&self) -> SensorKind {
+        match self {
+            SensorEvent::AmbientLight(_) => SensorKind::AmbientLight,
+            SensorEvent::Orientation(_) => SensorKind::Orientation,
+        }
+    }
+}
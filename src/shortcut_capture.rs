@@ -0,0 +1,112 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording a key chord, for "press a key to bind" preferences.
+//!
+//! Backends keep a [`ShortcutCapture`] for each window, and [`dispatch::wrap`] gives it the
+//! window's input first. While a capture is pending, it takes over key input.
+//!
+//! [`dispatch::wrap`]: crate::dispatch::wrap
+
+use std::cell::RefCell;
+
+use crate::keyboard::{Code, KeyEvent};
+use crate::KbKey;
+
+/// The shortcut capture state of a window.
+#[derive(Default)]
+pub(crate) struct ShortcutCapture(RefCell<State>);
+
+#[derive(Default)]
+struct State {
+    callback: Option<Box<dyn FnOnce(KeyEvent)>>,
+    /// Keys pressed during the capture, whose releases are hidden from the handler.
+    swallowed_keys: Vec<Code>,
+}
+
+impl ShortcutCapture {
+    /// Sends the next key chord to `callback`, instead of the handler.
+    ///
+    /// This replaces any capture that is already pending.
+    pub(crate) fn begin(&self, callback: Box<dyn FnOnce(KeyEvent)>) {
+        self.0.borrow_mut().callback = Some(callback);
+    }
+
+    /// Returns `true` if `event` was captured.
+    pub(crate) fn key_down(&self, event: &KeyEvent) -> bool {
+        let callback = {
+            let mut state = self.0.borrow_mut();
+            if state.callback.is_none() {
+                return false;
+            }
+            state.swallowed_keys.push(event.code);
+            // Modifiers are part of the chord, so we wait for the key they modify. Repeats are
+            // from keys that were held before the capture started.
+            if is_modifier(&event.key) || event.repeat {
+                return true;
+            }
+            state.callback.take()
+        };
+        // The state isn't borrowed, in case the callback starts another capture.
+        if let Some(callback) = callback {
+            callback(event.clone());
+        }
+        true
+    }
+
+    /// Returns `true` if the release of a key is hidden from the handler.
+    pub(crate) fn key_up(&self, event: &KeyEvent) -> bool {
+        let mut state = self.0.borrow_mut();
+        match state
+            .swallowed_keys
+            .iter()
+            .position(|code| *code == event.code)
+        {
+            Some(i) => {
+                state.swallowed_keys.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn lost_focus(&self) {
+        // The releases of keys held when focus is lost don't come to us.
+        self.0.borrow_mut().swallowed_keys.clear();
+    }
+
+    pub(crate) fn destroy(&self) {
+        self.0.borrow_mut().callback = None;
+    }
+}
+
+fn is_modifier(key: &KbKey) -> bool {
+    matches!(
+        key,
+        KbKey::Alt
+            | KbKey::AltGraph
+            | KbKey::CapsLock
+            | KbKey::Control
+            | KbKey::Fn
+            | KbKey::FnLock
+            | KbKey::Hyper
+            | KbKey::Meta
+            | KbKey::NumLock
+            | KbKey::ScrollLock
+            | KbKey::Shift
+            | KbKey::Super
+            | KbKey::Symbol
+            | KbKey::SymbolLock
+    )
+}
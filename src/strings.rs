@@ -0,0 +1,242 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The text that glazier shows to the user by itself.
+//!
+//! Most of the text in menus and dialogs comes from the application, or from the platform,
+//! which translates it already. What's left are a few fallbacks, which we translate into the
+//! more common languages, and which the application can replace with
+//! [`Application::set_builtin_string`].
+//!
+//! [`Application::set_builtin_string`]: crate::Application::set_builtin_string
+
+use std::sync::Mutex;
+
+use crate::backend::application as backend;
+
+/// A piece of text that glazier shows to the user when the application doesn't provide one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BuiltinString {
+    /// The title of a dialog for opening files, on Linux.
+    OpenFileTitle,
+    /// The title of a dialog for choosing folders, on Linux.
+    OpenFolderTitle,
+    /// The title of a dialog for saving a file, on Linux.
+    SaveFileTitle,
+    /// The names of the modifiers in the shortcuts shown in menus, on Windows.
+    KeyCtrl,
+    KeyShift,
+    KeyAlt,
+    KeyWindows,
+    /// The names of keys in the shortcuts shown in menus, on Windows.
+    KeyPlus,
+    KeyMinus,
+    KeySpace,
+    KeyEscape,
+    KeyDelete,
+    KeyInsert,
+    KeyPageUp,
+    KeyPageDown,
+    KeyLeft,
+    KeyRight,
+    KeyUp,
+    KeyDown,
+    /// The descriptions of common clipboard formats, in
+    /// [`Clipboard::available_formats_detailed`].
+    ///
+    /// [`Clipboard::available_formats_detailed`]: crate::Clipboard::available_formats_detailed
+    FormatPlainText,
+    FormatHtml,
+    FormatRichText,
+    FormatPng,
+    FormatJpeg,
+    FormatGif,
+    FormatTiff,
+    FormatBitmap,
+    FormatSvg,
+    FormatPdf,
+    FormatFiles,
+    FormatLink,
+}
+
+/// The strings that the application replaced.
+static OVERRIDES: Mutex<Vec<(BuiltinString, String)>> = Mutex::new(Vec::new());
+/// The locale to translate into, if the application chose one.
+static LOCALE: Mutex<Option<String>> = Mutex::new(None);
+
+pub(crate) fn set_override(string: BuiltinString, text: Option<String>) {
+    let mut overrides = OVERRIDES.lock().unwrap();
+    overrides.retain(|(s, _)| *s != string);
+    if let Some(text) = text {
+        overrides.push((string, text));
+    }
+}
+
+pub(crate) fn set_locale(locale: Option<String>) {
+    *LOCALE.lock().unwrap() = locale;
+}
+
+/// The text to show for `string`.
+pub(crate) fn get(string: BuiltinString) -> String {
+    let overrides = OVERRIDES.lock().unwrap();
+    if let Some((_, text)) = overrides.iter().find(|(s, _)| *s == string) {
+        return text.clone();
+    }
+    let locale = LOCALE
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(backend::Application::get_locale);
+    translate(language(&locale), string)
+        .unwrap_or_else(|| english(string))
+        .to_string()
+}
+
+/// The language subtag of a locale, such as `de` for `de-AT` or `de_AT.UTF-8`.
+fn language(locale: &str) -> &str {
+    locale.split(['-', '_', '.', '@']).next().unwrap_or("")
+}
+
+fn english(string: BuiltinString) -> &'static str {
+    use BuiltinString::*;
+    match string {
+        OpenFileTitle => "Open File",
+        OpenFolderTitle => "Open Folder",
+        SaveFileTitle => "Save File",
+        KeyCtrl => "Ctrl",
+        KeyShift => "Shift",
+        KeyAlt => "Alt",
+        KeyWindows => "Windows",
+        KeyPlus => "Plus",
+        KeyMinus => "Minus",
+        KeySpace => "Space",
+        KeyEscape => "Esc",
+        KeyDelete => "Del",
+        KeyInsert => "Ins",
+        KeyPageUp => "PgUp",
+        KeyPageDown => "PgDn",
+        // These names match LibreOffice.
+        KeyLeft => "Left",
+        KeyRight => "Right",
+        KeyUp => "Up",
+        KeyDown => "Down",
+        // These names match the "Paste Special" dialogs of office suites.
+        FormatPlainText => "Unformatted text",
+        FormatHtml => "HTML",
+        FormatRichText => "Formatted text (RTF)",
+        FormatPng => "PNG image",
+        FormatJpeg => "JPEG image",
+        FormatGif => "GIF image",
+        FormatTiff => "TIFF image",
+        FormatBitmap => "Bitmap",
+        FormatSvg => "SVG image",
+        FormatPdf => "PDF document",
+        FormatFiles => "Files",
+        FormatLink => "Link",
+    }
+}
+
+/// The translation of `string` into `language`, if we have one. The key names follow the
+/// keyboards sold for the language, where they differ from the English ones.
+fn translate(language: &str, string: BuiltinString) -> Option<&'static str> {
+    use BuiltinString::*;
+    let text = match (language, string) {
+        ("de", OpenFileTitle) => "Datei öffnen",
+        ("de", OpenFolderTitle) => "Ordner öffnen",
+        ("de", SaveFileTitle) => "Datei speichern",
+        ("de", KeyCtrl) => "Strg",
+        ("de", KeyShift) => "Umschalt",
+        ("de", KeySpace) => "Leertaste",
+        ("de", KeyDelete) => "Entf",
+        ("de", KeyInsert) => "Einfg",
+        ("de", FormatPlainText) => "Unformatierter Text",
+        ("de", FormatRichText) => "Formatierter Text (RTF)",
+        ("de", FormatPng) => "PNG-Bild",
+        ("de", FormatJpeg) => "JPEG-Bild",
+        ("de", FormatGif) => "GIF-Bild",
+        ("de", FormatTiff) => "TIFF-Bild",
+        ("de", FormatSvg) => "SVG-Bild",
+        ("de", FormatPdf) => "PDF-Dokument",
+        ("de", FormatFiles) => "Dateien",
+        ("es", OpenFileTitle) => "Abrir archivo",
+        ("es", OpenFolderTitle) => "Abrir carpeta",
+        ("es", SaveFileTitle) => "Guardar archivo",
+        ("es", KeyShift) => "Mayús",
+        ("es", KeySpace) => "Espacio",
+        ("es", KeyDelete) => "Supr",
+        ("es", FormatPlainText) => "Texto sin formato",
+        ("es", FormatRichText) => "Texto con formato (RTF)",
+        ("es", FormatPng) => "Imagen PNG",
+        ("es", FormatJpeg) => "Imagen JPEG",
+        ("es", FormatGif) => "Imagen GIF",
+        ("es", FormatTiff) => "Imagen TIFF",
+        ("es", FormatBitmap) => "Mapa de bits",
+        ("es", FormatSvg) => "Imagen SVG",
+        ("es", FormatPdf) => "Documento PDF",
+        ("es", FormatFiles) => "Archivos",
+        ("es", FormatLink) => "Enlace",
+        ("fr", OpenFileTitle) => "Ouvrir un fichier",
+        ("fr", OpenFolderTitle) => "Ouvrir un dossier",
+        ("fr", SaveFileTitle) => "Enregistrer le fichier",
+        ("fr", KeyShift) => "Maj",
+        ("fr", KeySpace) => "Espace",
+        ("fr", KeyEscape) => "Échap",
+        ("fr", KeyDelete) => "Suppr",
+        ("fr", KeyInsert) => "Inser",
+        ("fr", FormatPlainText) => "Texte non formaté",
+        ("fr", FormatRichText) => "Texte formaté (RTF)",
+        ("fr", FormatPng) => "Image PNG",
+        ("fr", FormatJpeg) => "Image JPEG",
+        ("fr", FormatGif) => "Image GIF",
+        ("fr", FormatTiff) => "Image TIFF",
+        ("fr", FormatSvg) => "Image SVG",
+        ("fr", FormatPdf) => "Document PDF",
+        ("fr", FormatFiles) => "Fichiers",
+        ("fr", FormatLink) => "Lien",
+        ("it", OpenFileTitle) => "Apri file",
+        ("it", OpenFolderTitle) => "Apri cartella",
+        ("it", SaveFileTitle) => "Salva file",
+        ("it", KeyShift) => "Maiusc",
+        ("it", KeySpace) => "Spazio",
+        ("it", KeyDelete) => "Canc",
+        ("it", FormatPlainText) => "Testo non formattato",
+        ("it", FormatRichText) => "Testo formattato (RTF)",
+        ("it", FormatPng) => "Immagine PNG",
+        ("it", FormatJpeg) => "Immagine JPEG",
+        ("it", FormatGif) => "Immagine GIF",
+        ("it", FormatTiff) => "Immagine TIFF",
+        ("it", FormatSvg) => "Immagine SVG",
+        ("it", FormatPdf) => "Documento PDF",
+        ("it", FormatFiles) => "File",
+        ("it", FormatLink) => "Collegamento",
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations() {
+        assert_eq!(language("de_AT.UTF-8"), "de");
+        assert_eq!(language("fr-CA"), "fr");
+        assert_eq!(translate("de", BuiltinString::KeyCtrl), Some("Strg"));
+        // Keys that are the same as in English fall back to it.
+        assert_eq!(translate("de", BuiltinString::KeyAlt), None);
+        assert_eq!(translate("en", BuiltinString::SaveFileTitle), None);
+    }
+}
@@ -870,4 +870,10 @@ pub enum Action {
     ///
     /// Triggered on most operating systems with escape.
     Cancel,
+
+    /// Undoes the last edit.
+    Undo,
+
+    /// Redoes the last edit that was undone.
+    Redo,
 }
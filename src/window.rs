@@ -17,18 +17,26 @@
 use std::any::Any;
 use std::time::Duration;
 
+use instant::Instant;
+
 use crate::application::Application;
 use crate::backend::window as backend;
+use crate::clipboard::FormatId;
+use crate::colors::HighContrastColors;
 use crate::common_util::Counter;
 use crate::dialog::{FileDialogOptions, FileInfo};
+use crate::dnd::{DragData, DragImage, DropEffect};
 use crate::error::Error;
-use crate::keyboard::KeyEvent;
-use crate::kurbo::{Insets, Point, Rect, Size};
+use crate::keyboard::{CompositionEvent, KbKey, KeyEvent, KeyboardLayout, Modifiers};
+use crate::kurbo::{BezPath, Insets, Point, Rect, Size, Vec2};
+use crate::locale::LocaleInfo;
 use crate::menu::Menu;
-use crate::mouse::{Cursor, CursorDesc, MouseEvent};
+use crate::mouse::{Cursor, CursorDesc, ForceClick, MouseEvent};
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::{Monitor, MonitorId, Screen};
 use crate::text::{Event, InputHandler};
+use crate::window_group::WindowGroup;
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
@@ -154,11 +162,97 @@ pub enum WindowLevel {
     /// A top level app window.
     AppWindow,
     /// A window that should stay above app windows - like a tooltip
+    ///
+    /// Tooltips never take the keyboard focus, and are closed automatically when their parent
+    /// window is clicked, loses focus, or is closed.
     Tooltip(WindowHandle),
     /// A user interface element such as a dropdown menu or combo box
+    ///
+    /// Like tooltips, drop-downs never take the keyboard focus (key events keep going to the
+    /// parent window), and are closed automatically when their parent window is clicked, loses
+    /// focus, or is closed.
     DropDown(WindowHandle),
     /// A modal dialog
     Modal(WindowHandle),
+    /// A part of the desktop shell, such as a status bar, dock, or lock screen overlay.
+    ///
+    /// Layer windows are placed by the compositor, against the edges of the screen they are
+    /// anchored to, and are stacked in their own layer rather than with the app windows. They
+    /// have no titlebar.
+    ///
+    /// This is only supported on Wayland, by compositors that implement the layer shell
+    /// protocol (such as those based on wlroots); building the window fails if the compositor
+    /// doesn't. Other backends treat this as [`WindowLevel::AppWindow`].
+    Layer {
+        layer: Layer,
+        anchor: LayerAnchor,
+        /// The size, in [display points] from the anchored edge, of the area that other
+        /// windows are kept out of, as for a panel. Zero reserves nothing, and -1 also ignores
+        /// the areas reserved by other layer windows.
+        ///
+        /// [display points]: crate::Scale
+        exclusive_zone: i32,
+        keyboard_interactivity: KeyboardInteractivity,
+    },
+}
+
+/// The layers of the desktop shell that [`WindowLevel::Layer`] windows are stacked in, from
+/// bottom to top.
+///
+/// App windows are between `Bottom` and `Top`. Fullscreen windows usually cover `Top`, but
+/// not `Overlay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+/// The edges of the screen that a [`WindowLevel::Layer`] window is attached to.
+///
+/// A window that is anchored to two opposite edges is stretched between them. On an axis where
+/// it isn't anchored to either edge, it is centred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LayerAnchor {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// Whether a [`WindowLevel::Layer`] window takes the keyboard focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyboardInteractivity {
+    /// The window never has the keyboard focus.
+    #[default]
+    None,
+    /// The window has the keyboard focus for as long as it is shown, like a lock screen.
+    Exclusive,
+    /// The window gets the keyboard focus when it is clicked, like an app window.
+    ///
+    /// Compositors that don't support this treat it as `None`.
+    OnDemand,
+}
+
+/// Who draws a window's titlebar and borders.
+///
+/// See [`WinHandler::decoration_mode_changed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecorationMode {
+    /// The application draws its own decorations, if it wants any.
+    ClientSide,
+    /// The window system draws the decorations.
+    ServerSide,
+}
+
+/// A light or dark color scheme.
+///
+/// See [`WindowHandle::set_theme_override`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Theme {
+    Light,
+    Dark,
 }
 
 /// Contains the different states a Window can be in.
@@ -169,6 +263,142 @@ pub enum WindowState {
     Restored,
 }
 
+/// What happens to one of the system's bars while a window is fullscreen.
+///
+/// See [`FullscreenOptions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SystemBar {
+    /// The bar is hidden, and comes back when the pointer reaches its edge of the screen.
+    #[default]
+    AutoHide,
+    /// The bar is hidden for as long as the window is fullscreen.
+    Hidden,
+    /// The bar stays visible, and the window doesn't cover it.
+    Visible,
+}
+
+/// How a window shares the screen with the system's bars while it is fullscreen.
+///
+/// Platforms only support some combinations:
+///
+/// - On macOS, the menu bar can't stay visible, and the dock can't stay visible unless the
+///   menu bar does, so `Visible` is treated like `AutoHide`. Hiding the menu bar also hides
+///   the dock.
+/// - On Windows, the taskbar is either covered or not, so `AutoHide` is treated like
+///   `Hidden`. There is no menu bar.
+/// - On Linux the window manager or compositor decides, and the options are ignored.
+///
+/// See [`WindowHandle::set_fullscreen`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FullscreenOptions {
+    /// The menu bar at the top of the screen on macOS.
+    pub menu_bar: SystemBar,
+    /// The dock on macOS, and the taskbar on Windows.
+    pub dock: SystemBar,
+}
+
+/// Where to put a new window.
+///
+/// See [`WindowBuilder::set_position`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowPosition {
+    /// A position in [display points], relative to the parent window if there is one, or the
+    /// origin of the virtual screen if there is no parent.
+    ///
+    /// [display points]: crate::Scale
+    Point(Point),
+    /// A position in [display points], relative to the top left corner of a monitor.
+    ///
+    /// [display points]: crate::Scale
+    OnMonitor(MonitorId, Point),
+    /// The middle of a monitor's work area.
+    Monitor(MonitorId),
+}
+
+impl From<Point> for WindowPosition {
+    fn from(point: Point) -> WindowPosition {
+        WindowPosition::Point(point)
+    }
+}
+
+/// A direction in which to move the keyboard focus.
+///
+/// See [`WinHandler::focus_navigation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FocusDirection {
+    /// To the next item in the traversal order, usually from Tab.
+    Next,
+    /// To the previous item in the traversal order, usually from Shift+Tab.
+    Previous,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl FocusDirection {
+    /// The direction that `event` moves the focus in, if it is Tab or Shift+Tab.
+    pub(crate) fn from_key_event(event: &KeyEvent) -> Option<FocusDirection> {
+        if event.key != KbKey::Tab || event.mods.ctrl() || event.mods.alt() || event.mods.meta() {
+            return None;
+        }
+        if event.mods.shift() {
+            Some(FocusDirection::Previous)
+        } else {
+            Some(FocusDirection::Next)
+        }
+    }
+}
+
+/// Why [`WinHandler::paint`] is being called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaintReason {
+    /// Part of the window needs to be repaired, because it was invalidated by the application or
+    /// by the system, and no animation frame was requested.
+    Damage,
+    /// This is a frame requested by [`WindowHandle::request_anim_frame`].
+    ///
+    /// Animation frames don't invalidate anything by themselves, so the invalid region only
+    /// contains what was invalidated since the last frame, for instance in
+    /// [`WinHandler::prepare_paint`].
+    Animation,
+}
+
+/// The timing of a frame that is about to be drawn. See [`WinHandler::prepare_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameInfo {
+    /// When the frame is expected to appear on the display.
+    pub present_time: Instant,
+    /// The time between refreshes of the display.
+    pub refresh_interval: Duration,
+}
+
+/// The size and behavior of the platform's scrollbars, so that custom scrollbars can match them.
+///
+/// See [`WindowHandle::scrollbar_metrics`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollbarMetrics {
+    /// The thickness of a scrollbar, in display points.
+    pub width: f64,
+    /// The shortest that the thumb gets, however long the content is, in display points.
+    pub min_thumb_length: f64,
+    /// Whether the user wants scrollbars that are drawn over the content and only show up
+    /// while it scrolls, rather than ones that are always visible beside it.
+    pub overlay: bool,
+}
+
+impl Default for ScrollbarMetrics {
+    /// Classic scrollbars, in the proportions that most desktops use. Backends fall back to
+    /// these when the platform doesn't say.
+    fn default() -> Self {
+        ScrollbarMetrics {
+            width: 16.0,
+            min_thumb_length: 16.0,
+            overlay: false,
+        }
+    }
+}
+
 /// A handle to a platform window object.
 #[derive(Clone, Default, PartialEq, Eq)]
 pub struct WindowHandle(pub(crate) backend::WindowHandle);
@@ -202,6 +432,26 @@ impl WindowHandle {
         self.0.get_window_state()
     }
 
+    /// Make the window cover the whole of its display, or `None` to leave fullscreen.
+    ///
+    /// The options say what happens to the menu bar, dock and taskbar, which is useful for
+    /// media players and kiosks. Calling this again while the window is fullscreen changes
+    /// them. On Windows the window becomes borderless; on macOS it gets its own space.
+    pub fn set_fullscreen(&self, options: Option<FullscreenOptions>) {
+        self.0.set_fullscreen(options)
+    }
+
+    /// Sets the monitor that the window should cover when it is fullscreen, or `None` to let
+    /// the system choose.
+    ///
+    /// This is a hint, and it takes effect the next time [`set_fullscreen`] is called. It is
+    /// currently only used on Wayland.
+    ///
+    /// [`set_fullscreen`]: WindowHandle::set_fullscreen
+    pub fn preferred_output(&self, monitor: Option<MonitorId>) {
+        self.0.preferred_output(monitor)
+    }
+
     /// Informs the system that the current location of the mouse should be treated as part of the
     /// window's titlebar. This can be used to implement a custom titlebar widget. Note that
     /// because this refers to the current location of the mouse, you should probably call this
@@ -237,6 +487,44 @@ impl WindowHandle {
         self.0.get_position()
     }
 
+    /// Returns the monitor that most of the window is on, or `None` if it isn't on any.
+    ///
+    /// On Wayland, where windows don't know their position, this is one of the monitors that
+    /// the window is on.
+    pub fn monitor(&self) -> Option<Monitor> {
+        self.0.monitor()
+    }
+
+    /// Converts a point in this window's content area to a point on the virtual screen.
+    ///
+    /// Both points are in [display points]. Unlike offsetting by [`get_position`] and
+    /// [`content_insets`], this uses the platform's own mapping, so it is exact even where the
+    /// window decorations are not known.
+    ///
+    /// Wayland doesn't tell clients where their windows are, so there this returns `point`
+    /// unchanged.
+    ///
+    /// [display points]: crate::Scale
+    /// [`get_position`]: WindowHandle::get_position
+    /// [`content_insets`]: WindowHandle::content_insets
+    pub fn map_to_screen(&self, point: Point) -> Point {
+        self.0.map_to_screen(point)
+    }
+
+    /// Converts a point in this window's content area to a point in `other`'s content area.
+    ///
+    /// This is useful for dragging between the application's own windows, and for positioning
+    /// a popup relative to something in its parent. Both points are in [display points].
+    ///
+    /// [display points]: crate::Scale
+    pub fn map_point_to(&self, other: &WindowHandle, point: Point) -> Point {
+        if self == other {
+            return point;
+        }
+        let other_origin = other.map_to_screen(Point::ZERO);
+        self.map_to_screen(point) - other_origin.to_vec2()
+    }
+
     /// Returns the insets of the window content from its position and size in [display points].
     ///
     /// This is to account for any window system provided chrome, e.g. title bars. For example, if
@@ -246,14 +534,34 @@ impl WindowHandle {
     ///
     /// The details of this function are somewhat platform-dependent. For example, on Windows both
     /// the insets and the window size include the space taken up by the title bar and window
-    /// decorations; on GTK neither the insets nor the window size include the title bar or window
-    /// decorations.
+    /// decorations; on GTK and X11 neither the insets nor the window size include the title bar or
+    /// window decorations. On macOS, the insets include the title bar (and its traffic light
+    /// buttons) even when the content is drawn underneath it.
+    ///
+    /// Windows without system decorations, and backends that have none (like web), have zero
+    /// insets. On X11 the decorations are only known once the window manager has decorated the
+    /// window, which is usually some time after it is shown.
     ///
     /// [display points]: crate::Scale
     pub fn content_insets(&self) -> Insets {
         self.0.content_insets()
     }
 
+    /// Returns the insets of the part of the window that isn't obscured by the hardware, in
+    /// [display points].
+    ///
+    /// Unlike [`content_insets`], which accounts for window decorations, this is about things
+    /// like display cutouts and rounded screen corners. On macOS it is nonzero while the window
+    /// is fullscreen on a display with a camera housing (a "notch"). Desktop platforms without
+    /// such displays always return zero. Changes are reported through
+    /// [`WinHandler::safe_area_changed`].
+    ///
+    /// [`content_insets`]: WindowHandle::content_insets
+    /// [display points]: crate::Scale
+    pub fn safe_area(&self) -> Insets {
+        self.0.safe_area()
+    }
+
     /// Set the window's size in [display points].
     ///
     /// The actual window size in pixels will depend on the platform DPI settings.
@@ -280,10 +588,77 @@ impl WindowHandle {
         self.0.bring_to_front_and_focus()
     }
 
+    /// Move this window in the window stack so that it is directly above `other`.
+    ///
+    /// This is intended for ordering an application's own windows, such as keeping a tool
+    /// palette above the canvas it belongs to. It does not change focus.
+    pub fn raise_above(&self, other: &WindowHandle) {
+        self.0.raise_above(&other.0)
+    }
+
+    /// Move this window in the window stack so that it is directly below `other`.
+    ///
+    /// See [`raise_above`](WindowHandle::raise_above) for details.
+    pub fn lower_below(&self, other: &WindowHandle) {
+        self.0.lower_below(&other.0)
+    }
+
+    /// Make the whole window transparent to mouse input, or opaque again.
+    ///
+    /// While passthrough is enabled, pointer events go to whatever is below the window,
+    /// which is useful for overlays such as frame rate meters. The window still receives
+    /// keyboard input if it has focus. This can be toggled at any time.
+    pub fn set_mouse_passthrough(&self, passthrough: bool) {
+        self.0.set_mouse_passthrough(passthrough)
+    }
+
+    /// Set whether holding down a key sends repeated key down events.
+    ///
+    /// This is enabled by default. Disabling it is useful for games and other applications
+    /// that track which keys are held from key down and key up pairs. Repeats are dropped
+    /// before they reach the handler, so they don't insert text either.
+    ///
+    /// Events that do get through have [`KeyEvent::repeat`] set when they are repeats.
+    ///
+    /// [`KeyEvent::repeat`]: crate::KeyEvent::repeat
+    pub fn set_key_repeat_enabled(&self, enabled: bool) {
+        self.0.set_key_repeat_enabled(enabled)
+    }
+
+    /// Set whether an input method may compose text in this window.
+    ///
+    /// This is allowed by default. Disabling it is useful for games, which want every key as
+    /// a plain key event even while the user has a Chinese, Japanese or Korean input method
+    /// active; otherwise the input method swallows the keys into its composition. Disabling it
+    /// ends a composition in progress. Text fields still get the characters of the keys that
+    /// are pressed, without composition.
+    ///
+    /// This only has an effect on Windows, macOS and Wayland; the other backends don't use
+    /// input methods.
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.0.set_ime_allowed(allowed)
+    }
+
+    /// Give the window a non-rectangular shape, or `None` to make it rectangular again.
+    ///
+    /// The shape is in [display points] relative to the content area. The parts of the
+    /// window outside it are not shown and don't receive mouse input. On macOS and Wayland
+    /// the system only takes care of input, so the window should be [transparent] and paint
+    /// nothing outside the shape. The shape is not updated when the window's scale changes.
+    ///
+    /// [display points]: crate::Scale
+    /// [transparent]: WindowBuilder::set_transparent
+    pub fn set_shape(&self, shape: Option<BezPath>) {
+        self.0.set_shape(shape)
+    }
+
     /// Request that [`prepare_paint`] and [`paint`] be called next time there's the opportunity to
     /// render another frame. This differs from [`invalidate`] and [`invalidate_rect`] in that it
     /// doesn't invalidate any part of the window.
     ///
+    /// On Wayland, the opportunity comes when the compositor has shown the previous frame, so
+    /// animations run at the refresh rate of the display instead of needing a timer.
+    ///
     /// [`invalidate`]: WindowHandle::invalidate
     /// [`invalidate_rect`]: WindowHandle::invalidate_rect
     /// [`paint`]: WinHandler::paint
@@ -292,6 +667,27 @@ impl WindowHandle {
         self.0.request_anim_frame();
     }
 
+    /// Returns the time between refreshes of the display that the window is on.
+    ///
+    /// This is meant for animations that advance in fixed steps, so they can take one step per
+    /// frame. When the window moves to a display with a different refresh rate,
+    /// [`WinHandler::refresh_interval_changed`] is called. Where the refresh rate can't be found
+    /// out, this is the interval of a 60Hz display.
+    pub fn refresh_interval(&self) -> Duration {
+        self.0.refresh_interval()
+    }
+
+    /// Limit how often this window is painted, in frames per second.
+    ///
+    /// When a limit is set, frames requested with [`request_anim_frame`] are delayed until
+    /// enough time has passed since the previous frame. This is useful for secondary windows
+    /// that don't need to animate at the display's full refresh rate. `None` removes the limit.
+    ///
+    /// [`request_anim_frame`]: WindowHandle::request_anim_frame
+    pub fn set_max_frame_rate(&self, fps: Option<f32>) {
+        self.0.set_max_frame_rate(fps);
+    }
+
     /// Request invalidation of the entire window contents.
     pub fn invalidate(&self) {
         self.0.invalidate();
@@ -302,11 +698,37 @@ impl WindowHandle {
         self.0.invalidate_rect(rect);
     }
 
+    /// Moves what is drawn in `rect` by `delta`, and invalidates the part of `rect` that it moved
+    /// away from.
+    ///
+    /// Both are in [display points]. This is for scrolling: where the platform can move the
+    /// pixels that are already on screen, only the newly exposed strip needs to be repainted.
+    /// Anything moved outside of `rect` is discarded. Where the platform can't move pixels, or
+    /// `delta` isn't a whole number of pixels, this invalidates all of `rect`.
+    ///
+    /// This only moves what the platform presents. Applications that present with their own
+    /// GPU swapchain should invalidate instead.
+    ///
+    /// [display points]: crate::Scale
+    pub fn scroll_region(&self, rect: Rect, delta: Vec2) {
+        self.0.scroll_region(rect, delta);
+    }
+
     /// Set the title for this menu.
     pub fn set_title(&self, title: &str) {
         self.0.set_title(title)
     }
 
+    /// Make the window's titlebar and other system chrome light or dark, regardless of the
+    /// system's color scheme, or `None` to go back to the default.
+    ///
+    /// This is for applications that let the user pick a theme. It only affects what the
+    /// system draws; the application still paints its own content. On X11 this is a hint that
+    /// not every window manager follows, and on Wayland and GTK it isn't supported.
+    pub fn set_theme_override(&self, theme: Option<Theme>) {
+        self.0.set_theme_override(theme)
+    }
+
     /// Set the top-level menu for this window.
     pub fn set_menu(&self, menu: Menu) {
         self.0.set_menu(menu.into_inner())
@@ -365,15 +787,169 @@ impl WindowHandle {
         self.0.request_timer(instant::Instant::now() + deadline)
     }
 
+    /// Schedule a timer that may fire up to `tolerance` after its deadline.
+    ///
+    /// This is like [`request_timer`], but lets the platform wake up once for several timers
+    /// that are due around the same time, which saves power in applications with lots of
+    /// periodic work. It never fires before the deadline.
+    ///
+    /// [`request_timer`]: WindowHandle::request_timer
+    pub fn request_timer_with_tolerance(
+        &self,
+        deadline: Duration,
+        tolerance: Duration,
+    ) -> TimerToken {
+        self.0
+            .request_timer_with_tolerance(instant::Instant::now() + deadline, tolerance)
+    }
+
+    /// Schedule a timer that fires every `interval`, until it is cancelled with
+    /// [`cancel_timer`].
+    ///
+    /// Every run causes a [`WinHandler::timer`] call with the returned token. If the
+    /// application falls behind, for instance because the machine was asleep, the missed runs
+    /// are skipped rather than delivered all at once.
+    ///
+    /// [`cancel_timer`]: WindowHandle::cancel_timer
+    pub fn request_repeating_timer(&self, interval: Duration) -> TimerToken {
+        self.0.request_repeating_timer(interval)
+    }
+
+    /// Cancel a timer, so that it doesn't fire (again).
+    ///
+    /// It does nothing if the timer has already fired, unless it is a repeating one.
+    pub fn cancel_timer(&self, token: TimerToken) {
+        self.0.cancel_timer(token)
+    }
+
     /// Set the cursor icon.
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         self.0.set_cursor(cursor)
     }
 
+    /// Keep the pointer inside `region`, in [display points] relative to the content area, or
+    /// let it move freely again with `None`.
+    ///
+    /// Unlike a pointer lock, the cursor stays visible and mouse events keep arriving as
+    /// usual; the cursor just can't leave the region. This is meant for drag interactions like
+    /// color wheels and scrollbars, and should be released when the drag ends. The region is
+    /// not updated if the window moves or its scale changes.
+    ///
+    /// The confinement only applies while the window has focus. On Wayland it starts once the
+    /// pointer is inside the region, on X11 it grabs the pointer, and on macOS it is emulated
+    /// by moving the cursor back into the region when it leaves. This is not supported on GTK
+    /// or web.
+    ///
+    /// [display points]: crate::Scale
+    pub fn confine_pointer(&self, region: Option<Rect>) {
+        self.0.confine_pointer(region)
+    }
+
+    /// Hold the cursor where it is, so that moving the mouse doesn't move it.
+    ///
+    /// Combine this with [`set_raw_pointer_input`] to keep getting the mouse's motion, for
+    /// camera controls and the like, and hide the cursor if it shouldn't be seen. Locking the
+    /// pointer releases a [`confine_pointer`] confinement, and confining it releases the lock.
+    ///
+    /// The window needs focus. The lock ends when the window loses focus or the system breaks
+    /// it, and then [`WinHandler::pointer_lock_lost`] is called. End it yourself with
+    /// [`unlock_pointer`].
+    ///
+    /// On Wayland the compositor may wait until the pointer is over the window to lock it. On
+    /// X11 this grabs the pointer, and on macOS it is emulated by moving the cursor back
+    /// whenever the mouse moves it. This is not supported on GTK or web.
+    ///
+    /// [`set_raw_pointer_input`]: WindowHandle::set_raw_pointer_input
+    /// [`confine_pointer`]: WindowHandle::confine_pointer
+    /// [`unlock_pointer`]: WindowHandle::unlock_pointer
+    pub fn lock_pointer(&self) {
+        self.0.lock_pointer()
+    }
+
+    /// End a pointer lock started with [`lock_pointer`].
+    ///
+    /// [`lock_pointer`]: WindowHandle::lock_pointer
+    pub fn unlock_pointer(&self) {
+        self.0.unlock_pointer()
+    }
+
+    /// Turn on or off the delivery of raw pointer motion to [`WinHandler::raw_pointer_motion`].
+    ///
+    /// Raw motion is what the mouse reports, before the system applies pointer acceleration,
+    /// and it keeps coming when the cursor is stuck against the edge of the screen. This is
+    /// what 3D viewports and games want for camera controls. The deltas are in the device's
+    /// own units, not display points. Mouse events keep arriving as usual.
+    ///
+    /// Motion is only delivered while the window has focus. This uses the relative pointer
+    /// protocol on Wayland and Raw Input on Windows. On macOS the cursor stops moving while raw
+    /// input is on, and the deltas are those of the mouse events, which the system may have
+    /// accelerated. It is not supported on X11, GTK or web.
+    pub fn set_raw_pointer_input(&self, enabled: bool) {
+        self.0.set_raw_pointer_input(enabled)
+    }
+
+    /// Start middle-click autoscroll around `origin`, in [display points].
+    ///
+    /// This is the panning mode of web browsers on Windows. It is usually started from
+    /// [`WinHandler::mouse_down`] for the middle button, with the position of the click. While
+    /// it is active, the cursor shows [`Cursor::AllScroll`], mouse moves are not delivered, and
+    /// [`WinHandler::wheel`] is called regularly with deltas that grow with the pointer's
+    /// distance from `origin`. Paint an origin marker if you want one.
+    ///
+    /// It ends with the next click, which is not delivered, or when the button that started it
+    /// is released after the pointer was moved. Pressing a key, scrolling, or the window losing
+    /// focus also ends it. The cursor is then reset to [`Cursor::Arrow`].
+    ///
+    /// [display points]: crate::Scale
+    pub fn begin_autoscroll(&self, origin: Point) {
+        self.0.begin_autoscroll(origin)
+    }
+
+    /// Record the next key chord, for "press a key to bind" preference UIs.
+    ///
+    /// Until a key that isn't a modifier is pressed, key events are not delivered to the
+    /// handler. That key's [`KeyEvent`] goes to `callback` instead; it carries the logical key,
+    /// the physical [`Code`], and the [`Modifiers`] that were held. The releases of the keys
+    /// pressed during the capture are not delivered either.
+    ///
+    /// Calling this again before a chord is recorded replaces `callback`. If the window is
+    /// destroyed first, `callback` is dropped without being called.
+    ///
+    /// [`Code`]: crate::Code
+    /// [`Modifiers`]: crate::Modifiers
+    pub fn begin_shortcut_capture(&self, callback: impl FnOnce(KeyEvent) + 'static) {
+        self.0.begin_shortcut_capture(Box::new(callback))
+    }
+
+    /// Creates a cursor from an image, for use with [`set_cursor`].
+    ///
+    /// Returns `None` if the platform can't make the cursor.
+    ///
+    /// [`set_cursor`]: WindowHandle::set_cursor
     pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
         self.0.make_cursor(desc)
     }
 
+    /// Start dragging `data` out of the window, with `image` under the pointer.
+    ///
+    /// This should be called while a mouse button is held down, usually from
+    /// [`WinHandler::mouse_move`] once the pointer has moved far enough. The drag ends when the
+    /// button is released; the data is offered to be copied. If `image` is `None`, the
+    /// platform's default image is shown.
+    ///
+    /// This is implemented on Wayland, Windows and macOS.
+    pub fn start_drag(&self, data: DragData, image: Option<DragImage>) {
+        self.0.start_drag(data, image)
+    }
+
+    /// Set the custom formats that this window reads from drags, in addition to text and URIs.
+    ///
+    /// These are the formats that can appear in [`DragData::formats`] in
+    /// [`WinHandler::drag_enter`] and the callbacks after it.
+    pub fn set_drop_formats(&self, formats: &[FormatId]) {
+        self.0.set_drop_formats(formats)
+    }
+
     /// Prompt the user to choose a file to open.
     ///
     /// This won't block immediately; the file dialog will be shown whenever control returns to
@@ -404,13 +980,63 @@ impl WindowHandle {
         self.0.get_idle_handle().map(IdleHandle)
     }
 
+    /// Returns the keyboard layout that is currently used for input in this window.
+    ///
+    /// Changes are reported through [`WinHandler::input_language_changed`].
+    pub fn input_language(&self) -> Option<KeyboardLayout> {
+        self.0.input_language()
+    }
+
+    /// Returns how the user wants numbers, dates and times to be written.
+    ///
+    /// Changes are reported through [`WinHandler::locale_changed`]. On Linux the locale comes
+    /// from the environment, so it doesn't change while the application runs.
+    pub fn locale_info(&self) -> LocaleInfo {
+        self.0.locale_info()
+    }
+
     /// Get the DPI scale of the window.
     ///
     /// The returned [`Scale`](crate::Scale) is a copy and thus its information will be stale after
     /// the platform DPI changes. This means you should not stash it and rely on it later; it is
     /// only guaranteed to be valid for the current pass of the runloop.
     pub fn get_scale(&self) -> Result<Scale, Error> {
-        self.0.get_scale().map_err(Into::into)
+        self.0.get_scale()
+    }
+
+    /// Returns the user's preferred text size, as a multiple of the default size.
+    ///
+    /// This is the platform's text size accessibility setting, such as the "Text size" slider
+    /// on Windows or GNOME's text scaling factor. Unlike the window's [scale](crate::Scale),
+    /// it is meant to apply only to text: applications should multiply their font sizes by it,
+    /// but leave the rest of their layout alone. Changes are reported through
+    /// [`WinHandler::text_scale_factor_changed`].
+    ///
+    /// On macOS this is derived from the size of the system body font, which the user can't
+    /// change while the application is running. With the X11 and GTK backends the setting is
+    /// part of the screen's DPI, so it is already included in the window's scale, and this
+    /// returns `1.0`. It is also `1.0` on platforms without such a setting.
+    pub fn text_scale_factor(&self) -> f64 {
+        self.0.text_scale_factor()
+    }
+
+    /// Returns the palette of the user's high-contrast theme, or `None` if there isn't one
+    /// active.
+    ///
+    /// Changes are reported through [`WinHandler::high_contrast_changed`]. This is only
+    /// implemented on Windows; on other platforms it is always `None`.
+    pub fn high_contrast_colors(&self) -> Option<HighContrastColors> {
+        self.0.high_contrast_colors()
+    }
+
+    /// Returns the size of the platform's scrollbars at the window's current scale, and
+    /// whether the user prefers overlay scrollbars.
+    ///
+    /// The preference is the "Show scroll bars" setting on macOS, "Always show scrollbars" on
+    /// Windows 11 and GTK's overlay scrolling. X11, Wayland and the web have no such settings,
+    /// so they get [`ScrollbarMetrics::default`].
+    pub fn scrollbar_metrics(&self) -> ScrollbarMetrics {
+        self.0.scrollbar_metrics()
     }
 
     /// If and only if the AccessKit adapter has been initialized, call
@@ -440,14 +1066,57 @@ unsafe impl HasRawDisplayHandle for WindowHandle {
 }
 
 /// A builder type for creating new windows.
-pub struct WindowBuilder(backend::WindowBuilder);
+pub struct WindowBuilder(pub(crate) backend::WindowBuilder, Placement);
+
+/// What we need to know to turn a [`WindowPosition`] into a point.
+#[derive(Default)]
+struct Placement {
+    position: Option<WindowPosition>,
+    size: Option<Size>,
+    parent: Option<WindowHandle>,
+}
+
+impl Placement {
+    fn resolve(&self) -> Option<Point> {
+        let (id, offset) = match self.position? {
+            WindowPosition::Point(point) => return Some(point),
+            WindowPosition::OnMonitor(id, offset) => (id, Some(offset)),
+            WindowPosition::Monitor(id) => (id, None),
+        };
+        let monitor = match Screen::get_monitors().into_iter().find(|m| m.id() == id) {
+            Some(monitor) => monitor,
+            None => {
+                tracing::warn!("can't place the window on {:?}, it isn't connected", id);
+                return None;
+            }
+        };
+        let position = match offset {
+            Some(offset) => monitor.virtual_rect().origin() + offset.to_vec2(),
+            None => {
+                // Without a size we can't center the window, so it goes in the corner.
+                let area = monitor.virtual_work_rect();
+                let size = self.size.unwrap_or(Size::ZERO);
+                let corner = area.center() - size.to_vec2() / 2.0;
+                Point::new(corner.x.max(area.x0), corner.y.max(area.y0))
+            }
+        };
+        let parent = self
+            .parent
+            .as_ref()
+            .map_or(Point::ZERO, |p| p.get_position());
+        Some(position - parent.to_vec2())
+    }
+}
 
 impl WindowBuilder {
     /// Create a new `WindowBuilder`.
     ///
     /// Takes the [`Application`](crate::Application) that this window is for.
     pub fn new(app: Application) -> WindowBuilder {
-        WindowBuilder(backend::WindowBuilder::new(app.backend_app))
+        WindowBuilder(
+            backend::WindowBuilder::new(app.backend_app),
+            Placement::default(),
+        )
     }
 
     /// Set the [`WinHandler`] for this window.
@@ -457,6 +1126,13 @@ impl WindowBuilder {
         self.0.set_handler(handler)
     }
 
+    /// Add the window to a [`WindowGroup`].
+    ///
+    /// The window joins the group when it is created, and leaves it when it is destroyed.
+    pub fn set_group(&mut self, group: &WindowGroup) {
+        self.0.set_group(group.clone())
+    }
+
     /// Set the window's initial drawing area size in [display points].
     ///
     /// The actual window size in pixels will depend on the platform DPI settings.
@@ -468,6 +1144,7 @@ impl WindowBuilder {
     ///
     /// [display points]: crate::Scale
     pub fn set_size(&mut self, size: Size) {
+        self.1.size = Some(size);
         self.0.set_size(size)
     }
 
@@ -483,6 +1160,21 @@ impl WindowBuilder {
         self.0.set_min_size(size)
     }
 
+    /// Set the steps in which the window's drawing area is resized, in [display points].
+    ///
+    /// When the user resizes the window, its size will be the minimum size plus a whole
+    /// number of increments. This is useful for applications like terminal emulators,
+    /// which want to be resized in whole character cells. An increment of zero in either
+    /// direction means that direction is not constrained.
+    ///
+    /// This is a hint; some platforms and window managers ignore it, and it doesn't
+    /// apply to maximized or fullscreen windows.
+    ///
+    /// [display points]: crate::Scale
+    pub fn set_resize_increments(&mut self, increments: Size) {
+        self.0.set_resize_increments(increments)
+    }
+
     /// Set whether the window should be resizable.
     pub fn resizable(&mut self, resizable: bool) {
         self.0.resizable(resizable)
@@ -503,21 +1195,101 @@ impl WindowBuilder {
     /// For windows without a parent, it is relative to the origin of the virtual screen.
     /// See also [set_level]
     ///
+    /// The position can also be given on a monitor from [`Application::monitors`], for
+    /// instance to open a tool palette on a chosen screen; see [`WindowPosition`].
+    ///
     /// [set_level]: crate::WindowBuilder::set_level
-    pub fn set_position(&mut self, position: Point) {
-        self.0.set_position(position);
+    pub fn set_position(&mut self, position: impl Into<WindowPosition>) {
+        self.1.position = Some(position.into());
     }
 
     /// Sets the initial [`WindowLevel`].
     pub fn set_level(&mut self, level: WindowLevel) {
+        self.1.parent = match &level {
+            WindowLevel::Tooltip(parent)
+            | WindowLevel::DropDown(parent)
+            | WindowLevel::Modal(parent) => Some(parent.clone()),
+            _ => None,
+        };
         self.0.set_level(level);
     }
 
+    /// Set whether the window can receive keyboard focus.
+    ///
+    /// A window that isn't focusable still receives mouse input, but clicking it won't take
+    /// focus away from the currently focused window. This is useful for tool palettes and
+    /// on-screen keyboards. The default is `true`.
+    ///
+    /// On Wayland, only [`WindowLevel::Layer`] windows can be made unfocusable, which sets their
+    /// keyboard interactivity to [`KeyboardInteractivity::None`].
+    pub fn set_focusable(&mut self, focusable: bool) {
+        self.0.set_focusable(focusable);
+    }
+
+    /// Set whether the window should be activated (given focus) when it is first shown.
+    ///
+    /// The default is `true`.
+    pub fn set_activate_on_show(&mut self, activate_on_show: bool) {
+        self.0.set_activate_on_show(activate_on_show);
+    }
+
+    /// Set whether the window should stay invisible until it has been painted.
+    ///
+    /// If this is `true`, [`WindowHandle::show`] doesn't reveal the window until
+    /// [`WinHandler::paint`] has produced its first frame, which avoids a flash of
+    /// white or black while the application is starting up. The default is `false`.
+    pub fn show_after_first_paint(&mut self, show_after_first_paint: bool) {
+        self.0.show_after_first_paint(show_after_first_paint);
+    }
+
+    /// Use `scale` for this window instead of the scale of the display it's on.
+    ///
+    /// This is meant for testing how an application renders at different scales without
+    /// changing the system settings. The forced scale is what [`WindowHandle::get_scale`]
+    /// and [`WinHandler::scale`] report, and what the window is painted at.
+    ///
+    /// On Wayland only the horizontal scale is used, and it is rounded to a whole number if
+    /// the compositor doesn't support fractional scaling.
+    pub fn force_scale(&mut self, scale: Scale) {
+        self.0.force_scale(scale);
+    }
+
     /// Set the window's initial title.
     pub fn set_title(&mut self, title: impl Into<String>) {
         self.0.set_title(title)
     }
 
+    /// Put the window into a taskbar group of its own, named `group`.
+    ///
+    /// Windows with the same group are grouped together, apart from the application's other
+    /// windows. This lets an application with several profiles, like a browser with "Work" and
+    /// "Personal" windows, keep each profile in its own taskbar button. Use the same name each
+    /// time the profile is opened, so that the desktop can remember what is pinned to it.
+    ///
+    /// - On Windows this is the window's AppUserModelID.
+    /// - On X11 it is the instance name in `WM_CLASS`; the class stays the program's name.
+    /// - On Wayland it is the window's app id.
+    /// - On macOS, where the Dock has one icon per application, it is the window's tabbing
+    ///   identifier, so that windows of the same group are merged into tabs together.
+    pub fn set_taskbar_group(&mut self, group: impl Into<String>) {
+        self.0.set_taskbar_group(group.into())
+    }
+
+    /// Let the system reopen the window when the application is relaunched, for example after
+    /// a system update.
+    ///
+    /// When that happens, `id` is passed to [`AppHandler::restore_window`], which should build
+    /// the same window again. Use an id that tells the application which window it was, such
+    /// as the path of the document it showed.
+    ///
+    /// This is currently only supported on macOS, where the system also restores the window's
+    /// frame.
+    ///
+    /// [`AppHandler::restore_window`]: crate::AppHandler::restore_window
+    pub fn set_restoration_id(&mut self, id: impl Into<String>) {
+        self.0.set_restoration_id(id.into())
+    }
+
     /// Set the window's menu.
     pub fn set_menu(&mut self, menu: Menu) {
         self.0.set_menu(menu.into_inner())
@@ -531,7 +1303,10 @@ impl WindowBuilder {
     /// Attempt to construct the platform window.
     ///
     /// If this fails, your application should exit.
-    pub fn build(self) -> Result<WindowHandle, Error> {
+    pub fn build(mut self) -> Result<WindowHandle, Error> {
+        if let Some(position) = self.1.resolve() {
+            self.0.set_position(position);
+        }
         self.0.build().map(WindowHandle).map_err(Into::into)
     }
 }
@@ -556,12 +1331,58 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn size(&mut self, size: Size) {}
 
+    /// Called when the [safe area](WindowHandle::safe_area) of the window has changed.
+    ///
+    /// This is called after the accompanying [`size`](WinHandler::size), if there is one.
+    #[allow(unused_variables)]
+    fn safe_area_changed(&mut self, insets: Insets) {}
+
     /// Called when the [scale](crate::Scale) of the window has changed.
     ///
     /// This is always called before the accompanying [`size`](WinHandler::size).
     #[allow(unused_variables)]
     fn scale(&mut self, scale: Scale) {}
 
+    /// Called when the user's preferred text size has changed.
+    ///
+    /// See [`WindowHandle::text_scale_factor`].
+    #[allow(unused_variables)]
+    fn text_scale_factor_changed(&mut self, factor: f64) {}
+
+    /// Called when a high-contrast theme has been turned on or off, or its colors have changed.
+    ///
+    /// See [`WindowHandle::high_contrast_colors`].
+    #[allow(unused_variables)]
+    fn high_contrast_changed(&mut self, colors: Option<HighContrastColors>) {}
+
+    /// Called when the window system has decided who draws the window's decorations.
+    ///
+    /// [`WindowBuilder::show_titlebar`] asks for server-side decorations, but on Wayland the
+    /// compositor has the final say: GNOME, for instance, never draws them. If the mode is
+    /// [`DecorationMode::ClientSide`], glazier draws a minimal titlebar of its own, unless the
+    /// application turned that off with `WindowHandleExt::set_client_decorations` in
+    /// `glazier::platform::linux` to draw its own.
+    ///
+    /// This is currently only called on Wayland; elsewhere the platform always draws the
+    /// titlebar when it is shown.
+    #[allow(unused_variables)]
+    fn decoration_mode_changed(&mut self, mode: DecorationMode) {}
+
+    /// Called right before [`prepare_paint`] with the timing of the frame that is about to be
+    /// drawn.
+    ///
+    /// Animations look smoothest when they are advanced to [`FrameInfo::present_time`], the
+    /// time the frame is expected to appear on the display, rather than to the time they are
+    /// drawn at, which varies with how long drawing takes.
+    ///
+    /// The prediction comes from presentation feedback on Wayland and from the desktop
+    /// compositor on Windows. Elsewhere, the frame is assumed to appear one refresh interval
+    /// from now.
+    ///
+    /// [`prepare_paint`]: WinHandler::prepare_paint
+    #[allow(unused_variables)]
+    fn prepare_frame(&mut self, info: FrameInfo) {}
+
     /// Request the handler to prepare to paint the window contents.  In particular, if there are
     /// any regions that need to be repainted on the next call to `paint`, the handler should
     /// invalidate those regions by calling [`WindowHandle::invalidate_rect`] or
@@ -571,7 +1392,36 @@ pub trait WinHandler {
     /// Request the handler to paint the window contents.  `invalid` is the region in [display
     /// points](crate::Scale) that needs to be repainted; painting outside the invalid region will
     /// have no effect.
-    fn paint(&mut self, invalid: &Region);
+    ///
+    /// `reason` says whether this frame is part of an animation or is only repairing damage.
+    fn paint(&mut self, invalid: &Region, reason: PaintReason);
+
+    /// Called before painting an animation frame, when `count` animation frames should have
+    /// been painted since the last one but weren't, for instance because the application or
+    /// the compositor was too slow.
+    ///
+    /// Animations that advance by a fixed step each frame can use this to catch up, and
+    /// animation clocks can resync instead of jumping.
+    #[allow(unused_variables)]
+    fn frame_missed(&mut self, count: u32) {}
+
+    /// Called when the window has moved to a display with a different refresh rate, with the
+    /// new [`WindowHandle::refresh_interval`].
+    #[allow(unused_variables)]
+    fn refresh_interval_changed(&mut self, interval: Duration) {}
+
+    /// Called when the window has moved onto or off a monitor, with the monitors that it now
+    /// overlaps, such as when it is dragged across the edge between two of them. A window on
+    /// mirrored monitors is on all of them.
+    ///
+    /// This can be used to pick the scale and colors to draw with, by looking the monitors up
+    /// in [`Application::monitors`].
+    ///
+    /// This is currently only called on Wayland.
+    ///
+    /// [`Application::monitors`]: crate::Application::monitors
+    #[allow(unused_variables)]
+    fn outputs_changed(&mut self, outputs: &[MonitorId]) {}
 
     /// Request the handler to return an [`accesskit::TreeUpdate`]
     /// with a complete accessibility tree. Must always return
@@ -627,11 +1477,62 @@ pub trait WinHandler {
         false
     }
 
+    /// Called when the user asks to move the keyboard focus, for example with Tab or Shift+Tab.
+    ///
+    /// This is only called for key presses that [`key_down`] didn't handle, and that weren't
+    /// consumed by the active text field. It is also called when the platform moves focus into
+    /// the window's content from native views around it, which on Linux can include the arrow
+    /// keys.
+    ///
+    /// Return `true` if the focus moved to something inside the window. Returning `false` lets
+    /// the platform move the focus on to the next native view (on macOS, when full keyboard
+    /// access is on, this includes controls like buttons), so that embedded native views and
+    /// glazier content can share a traversal order.
+    ///
+    /// [`key_down`]: WinHandler::key_down
+    #[allow(unused_variables)]
+    fn focus_navigation(&mut self, direction: FocusDirection) -> bool {
+        false
+    }
+
     /// Called when a key is released. This corresponds to the WM_KEYUP message
     /// on Windows, or keyUp(withEvent:) on macOS.
     #[allow(unused_variables)]
     fn key_up(&mut self, event: KeyEvent) {}
 
+    /// Called when the modifier keys that are held or locked change, including when a
+    /// modifier is pressed or released on its own.
+    ///
+    /// This lets applications, for example, focus the menu bar when Alt is tapped, or change
+    /// the cursor while Ctrl is held. Whether it comes before or after the [`key_down`] or
+    /// [`key_up`] of the modifier key itself depends on the platform. The modifiers are
+    /// reported as empty when the window loses focus, because the window doesn't hear about
+    /// keys released elsewhere.
+    ///
+    /// [`key_down`]: WinHandler::key_down
+    /// [`key_up`]: WinHandler::key_up
+    #[allow(unused_variables)]
+    fn modifiers_changed(&mut self, mods: Modifiers) {}
+
+    /// Called when the active keyboard layout for this window changes, for example
+    /// because the user switched input languages.
+    #[allow(unused_variables)]
+    fn input_language_changed(&mut self, layout: KeyboardLayout) {}
+
+    /// Called when the user changes their regional formatting preferences.
+    ///
+    /// See [`WindowHandle::locale_info`].
+    #[allow(unused_variables)]
+    fn locale_changed(&mut self, info: LocaleInfo) {}
+
+    /// Called when an input method starts, updates or finishes composing text.
+    ///
+    /// This is called regardless of whether there is an active text field, so
+    /// applications that don't use [`InputHandler`] can still show the preedit text.
+    /// Backends without input method support never call it.
+    #[allow(unused_variables)]
+    fn composition(&mut self, event: CompositionEvent) {}
+
     /// Take a lock for the text document specified by `token`.
     ///
     /// All calls to this method must be balanced with a call to
@@ -677,6 +1578,14 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn zoom(&mut self, delta: f64) {}
 
+    /// Called when the pressure of a click on a Force Touch trackpad changes.
+    ///
+    /// This comes alongside the usual mouse events, so the press still sends
+    /// [`WinHandler::mouse_down`] when it reaches stage 1. Watch for the stage reaching 2 to
+    /// handle a force click, the way Quick Look does. This is only sent on macOS.
+    #[allow(unused_variables)]
+    fn force_click(&mut self, event: &ForceClick) {}
+
     /// Called when the mouse moves.
     #[allow(unused_variables)]
     fn mouse_move(&mut self, event: &MouseEvent) {}
@@ -692,6 +1601,57 @@ pub trait WinHandler {
     /// Called when the mouse cursor has left the application window
     fn mouse_leave(&mut self) {}
 
+    /// Called with the unaccelerated motion of the pointing device, while raw pointer input
+    /// is on.
+    ///
+    /// See [`WindowHandle::set_raw_pointer_input`].
+    #[allow(unused_variables)]
+    fn raw_pointer_motion(&mut self, delta: Vec2) {}
+
+    /// Called when a pointer lock ends without [`WindowHandle::unlock_pointer`], for instance
+    /// because the window lost focus.
+    ///
+    /// See [`WindowHandle::lock_pointer`].
+    fn pointer_lock_lost(&mut self) {}
+
+    /// Called when a drag enters the window, at `pos` in [display points].
+    ///
+    /// Returns what would happen to `data` if it were dropped there. Returning
+    /// [`DropEffect::None`] rejects the drop, but [`drag_over`] is still called as the drag
+    /// moves, and can accept it somewhere else.
+    ///
+    /// [display points]: crate::Scale
+    /// [`drag_over`]: WinHandler::drag_over
+    #[allow(unused_variables)]
+    fn drag_enter(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        DropEffect::None
+    }
+
+    /// Called when a drag moves over the window, after [`drag_enter`].
+    ///
+    /// Returns what would happen to `data` if it were dropped at `pos`.
+    ///
+    /// [`drag_enter`]: WinHandler::drag_enter
+    #[allow(unused_variables)]
+    fn drag_over(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        DropEffect::None
+    }
+
+    /// Called when a drag leaves the window, or is cancelled, without being dropped.
+    fn drag_leave(&mut self) {}
+
+    /// Called when `data` is dropped on the window at `pos`.
+    ///
+    /// This is only called if the last call to [`drag_enter`] or [`drag_over`] accepted the
+    /// drop. Returns what was done with the data.
+    ///
+    /// [`drag_enter`]: WinHandler::drag_enter
+    /// [`drag_over`]: WinHandler::drag_over
+    #[allow(unused_variables)]
+    fn drag_drop(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        DropEffect::None
+    }
+
     /// Called on timer event.
     ///
     /// This is called at (approximately) the requested deadline by a
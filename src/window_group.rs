@@ -0,0 +1,374 @@
+// Copyright 2022 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Groups of windows that are managed together.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::keyboard::{CompositionEvent, KeyEvent};
+use crate::kurbo::{Insets, Point, Size, Vec2};
+use crate::mouse::{ForceClick, MouseEvent};
+use crate::text::InputHandler;
+use crate::window::{
+    DecorationMode, FileDialogToken, FocusDirection, FrameInfo, IdleToken, PaintReason, TimerToken,
+    WinHandler, WindowHandle,
+};
+use crate::{
+    DragData, DropEffect, FileInfo, HighContrastColors, KeyboardLayout, LocaleInfo, Modifiers,
+    MonitorId, Region, Scale, TextFieldToken,
+};
+
+/// Observes the windows in a [`WindowGroup`].
+///
+/// This is to a group what [`AppHandler`] is to the whole application.
+///
+/// [`AppHandler`]: crate::AppHandler
+pub trait WindowGroupHandler {
+    /// Called when a window of the group has been created, after its [`WinHandler::connect`].
+    #[allow(unused_variables)]
+    fn window_added(&mut self, window: &WindowHandle) {}
+
+    /// Called when a window of the group has been destroyed, after its
+    /// [`WinHandler::destroy`].
+    #[allow(unused_variables)]
+    fn window_removed(&mut self, window: &WindowHandle) {}
+
+    /// Called when the last window of the group has been destroyed.
+    fn all_windows_closed(&mut self) {}
+}
+
+/// A set of windows that can be closed together, such as all of the windows showing one
+/// document.
+///
+/// Windows join a group when they are built with [`WindowBuilder::set_group`], and leave it
+/// when they are destroyed. A group lives for as long as any clone of it, or any of its
+/// windows. All groups share the application's event loop.
+///
+/// Create one with [`Application::new_window_group`].
+///
+/// [`WindowBuilder::set_group`]: crate::WindowBuilder::set_group
+/// [`Application::new_window_group`]: crate::Application::new_window_group
+#[derive(Clone, Default)]
+pub struct WindowGroup {
+    windows: Rc<RefCell<Vec<WindowHandle>>>,
+    handler: Rc<RefCell<Option<Box<dyn WindowGroupHandler>>>>,
+    // Notifications waiting to be delivered, because they happened while the handler was
+    // running (for instance when it closes the group's windows).
+    pending: Rc<RefCell<VecDeque<Notification>>>,
+}
+
+enum Notification {
+    Added(WindowHandle),
+    Removed(WindowHandle),
+    AllClosed,
+}
+
+impl WindowGroup {
+    pub(crate) fn new() -> WindowGroup {
+        WindowGroup::default()
+    }
+
+    /// Sets the object that is told about windows joining and leaving the group.
+    ///
+    /// This must not be called from one of the handler's own methods.
+    pub fn set_handler(&self, handler: Box<dyn WindowGroupHandler>) {
+        *self.handler.borrow_mut() = Some(handler);
+    }
+
+    /// Returns the windows in the group, in the order they were created.
+    pub fn windows(&self) -> Vec<WindowHandle> {
+        self.windows.borrow().clone()
+    }
+
+    /// Returns `true` if the group has no windows.
+    pub fn is_empty(&self) -> bool {
+        self.windows.borrow().is_empty()
+    }
+
+    /// Closes all of the windows in the group.
+    ///
+    /// Like [`WindowHandle::close`], this doesn't ask the windows' handlers first.
+    pub fn close_all(&self) {
+        for window in self.windows() {
+            window.close();
+        }
+    }
+
+    /// Wraps the handler of a new window, so that the window joins the group.
+    pub(crate) fn wrap(self, handler: Box<dyn WinHandler>) -> Box<dyn WinHandler> {
+        Box::new(GroupHandler {
+            inner: handler,
+            group: self,
+            handle: None,
+        })
+    }
+
+    fn notify(&self, notification: Notification) {
+        self.pending.borrow_mut().push_back(notification);
+        // If the handler is already running, it gets this when it returns.
+        let mut handler = match self.handler.try_borrow_mut() {
+            Ok(handler) => handler,
+            Err(_) => return,
+        };
+        loop {
+            let notification = match self.pending.borrow_mut().pop_front() {
+                Some(notification) => notification,
+                None => break,
+            };
+            if let Some(handler) = handler.as_mut() {
+                match notification {
+                    Notification::Added(window) => handler.window_added(&window),
+                    Notification::Removed(window) => handler.window_removed(&window),
+                    Notification::AllClosed => handler.all_windows_closed(),
+                }
+            }
+        }
+    }
+
+    fn add(&self, window: &WindowHandle) {
+        self.windows.borrow_mut().push(window.clone());
+        self.notify(Notification::Added(window.clone()));
+    }
+
+    fn remove(&self, window: &WindowHandle) {
+        let empty = {
+            let mut windows = self.windows.borrow_mut();
+            windows.retain(|w| w != window);
+            windows.is_empty()
+        };
+        self.notify(Notification::Removed(window.clone()));
+        if empty {
+            self.notify(Notification::AllClosed);
+        }
+    }
+}
+
+struct GroupHandler {
+    inner: Box<dyn WinHandler>,
+    group: WindowGroup,
+    handle: Option<WindowHandle>,
+}
+
+impl WinHandler for GroupHandler {
+    fn connect(&mut self, handle: &WindowHandle) {
+        self.handle = Some(handle.clone());
+        self.inner.connect(handle);
+        self.group.add(handle);
+    }
+
+    fn size(&mut self, size: Size) {
+        self.inner.size(size)
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.inner.safe_area_changed(insets)
+    }
+
+    fn scale(&mut self, scale: Scale) {
+        self.inner.scale(scale)
+    }
+
+    fn text_scale_factor_changed(&mut self, factor: f64) {
+        self.inner.text_scale_factor_changed(factor)
+    }
+
+    fn high_contrast_changed(&mut self, colors: Option<HighContrastColors>) {
+        self.inner.high_contrast_changed(colors)
+    }
+
+    fn decoration_mode_changed(&mut self, mode: DecorationMode) {
+        self.inner.decoration_mode_changed(mode)
+    }
+
+    fn prepare_frame(&mut self, info: FrameInfo) {
+        self.inner.prepare_frame(info)
+    }
+
+    fn prepare_paint(&mut self) {
+        self.inner.prepare_paint()
+    }
+
+    fn paint(&mut self, invalid: &Region, reason: PaintReason) {
+        self.inner.paint(invalid, reason)
+    }
+
+    fn frame_missed(&mut self, count: u32) {
+        self.inner.frame_missed(count)
+    }
+
+    fn refresh_interval_changed(&mut self, interval: Duration) {
+        self.inner.refresh_interval_changed(interval)
+    }
+
+    fn outputs_changed(&mut self, outputs: &[MonitorId]) {
+        self.inner.outputs_changed(outputs)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_tree(&mut self) -> accesskit::TreeUpdate {
+        self.inner.accesskit_tree()
+    }
+
+    fn rebuild_resources(&mut self) {
+        self.inner.rebuild_resources()
+    }
+
+    fn command(&mut self, id: u32) {
+        self.inner.command(id)
+    }
+
+    fn save_as(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.inner.save_as(token, file)
+    }
+
+    fn open_file(&mut self, token: FileDialogToken, file: Option<FileInfo>) {
+        self.inner.open_file(token, file)
+    }
+
+    fn open_files(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.inner.open_files(token, files)
+    }
+
+    fn key_down(&mut self, event: KeyEvent) -> bool {
+        self.inner.key_down(event)
+    }
+
+    fn focus_navigation(&mut self, direction: FocusDirection) -> bool {
+        self.inner.focus_navigation(direction)
+    }
+
+    fn key_up(&mut self, event: KeyEvent) {
+        self.inner.key_up(event)
+    }
+
+    fn modifiers_changed(&mut self, mods: Modifiers) {
+        self.inner.modifiers_changed(mods)
+    }
+
+    fn input_language_changed(&mut self, layout: KeyboardLayout) {
+        self.inner.input_language_changed(layout)
+    }
+
+    fn locale_changed(&mut self, info: LocaleInfo) {
+        self.inner.locale_changed(info)
+    }
+
+    fn composition(&mut self, event: CompositionEvent) {
+        self.inner.composition(event)
+    }
+
+    fn acquire_input_lock(
+        &mut self,
+        token: TextFieldToken,
+        mutable: bool,
+    ) -> Box<dyn InputHandler> {
+        self.inner.acquire_input_lock(token, mutable)
+    }
+
+    fn release_input_lock(&mut self, token: TextFieldToken) {
+        self.inner.release_input_lock(token)
+    }
+
+    fn wheel(&mut self, event: &MouseEvent) {
+        self.inner.wheel(event)
+    }
+
+    fn zoom(&mut self, delta: f64) {
+        self.inner.zoom(delta)
+    }
+
+    fn force_click(&mut self, event: &ForceClick) {
+        self.inner.force_click(event)
+    }
+
+    fn mouse_move(&mut self, event: &MouseEvent) {
+        self.inner.mouse_move(event)
+    }
+
+    fn mouse_down(&mut self, event: &MouseEvent) {
+        self.inner.mouse_down(event)
+    }
+
+    fn mouse_up(&mut self, event: &MouseEvent) {
+        self.inner.mouse_up(event)
+    }
+
+    fn mouse_leave(&mut self) {
+        self.inner.mouse_leave()
+    }
+
+    fn raw_pointer_motion(&mut self, delta: Vec2) {
+        self.inner.raw_pointer_motion(delta)
+    }
+
+    fn pointer_lock_lost(&mut self) {
+        self.inner.pointer_lock_lost()
+    }
+
+    fn drag_enter(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_enter(pos, data)
+    }
+
+    fn drag_over(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_over(pos, data)
+    }
+
+    fn drag_leave(&mut self) {
+        self.inner.drag_leave()
+    }
+
+    fn drag_drop(&mut self, pos: Point, data: &DragData) -> DropEffect {
+        self.inner.drag_drop(pos, data)
+    }
+
+    fn timer(&mut self, token: TimerToken) {
+        self.inner.timer(token)
+    }
+
+    fn got_focus(&mut self) {
+        self.inner.got_focus()
+    }
+
+    fn lost_focus(&mut self) {
+        self.inner.lost_focus()
+    }
+
+    fn request_close(&mut self) {
+        self.inner.request_close()
+    }
+
+    fn destroy(&mut self) {
+        self.inner.destroy();
+        if let Some(handle) = self.handle.take() {
+            self.group.remove(&handle);
+        }
+    }
+
+    fn idle(&mut self, token: IdleToken) {
+        self.inner.idle(token)
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn accesskit_action(&mut self, request: accesskit::ActionRequest) {
+        self.inner.accesskit_action(request)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self.inner.as_any()
+    }
+}